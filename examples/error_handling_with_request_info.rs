@@ -5,7 +5,7 @@ use std::net::SocketAddr;
 
 // A handler for "/" page.
 async fn home_handler(_: Request<Body>) -> Result<Response<Body>, io::Error> {
-    Err(io::Error::new(io::ErrorKind::Other, "Some errors"))
+    Err(io::Error::other("Some errors"))
 }
 
 // A handler for "/about" page.
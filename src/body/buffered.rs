@@ -0,0 +1,383 @@
+use crate::body::BodyError;
+#[cfg(feature = "fs")]
+use futures_core::Stream;
+use hyper::body::{Bytes, HttpBody};
+use std::fmt::{self, Debug, Formatter};
+#[cfg(feature = "fs")]
+use std::future::Future;
+use std::io;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "fs")]
+use std::pin::Pin;
+#[cfg(feature = "fs")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "fs")]
+use std::sync::Arc;
+#[cfg(feature = "fs")]
+use std::task::{Context, Poll};
+#[cfg(feature = "fs")]
+use std::time::{SystemTime, UNIX_EPOCH};
+#[cfg(feature = "fs")]
+use tokio::io::AsyncWriteExt;
+#[cfg(feature = "fs")]
+use tokio_util::io::ReaderStream;
+
+#[cfg(feature = "fs")]
+static SPILL_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Configures where the request bodies larger than the in-memory limit are spilled, and how large they can get.
+///
+/// Spilling requires the `fs` feature.
+#[derive(Debug, Clone)]
+pub struct SpillConfig {
+    /// The directory of the temporary files, which must exist.
+    pub dir: PathBuf,
+    /// The maximum size of a spilled body in bytes. Larger bodies are rejected with
+    /// [`BodyError::TooLarge`](./enum.BodyError.html#variant.TooLarge).
+    pub max_size: u64,
+}
+
+/// A fully received request body which can be read any number of times.
+///
+/// Small bodies are kept in memory and large ones are spilled into a temporary file which is removed
+/// once the last clone of the `BufferedBody` is dropped.
+///
+/// It's created by the [`RequestBodyExt::buffer_body`](./ext/trait.RequestBodyExt.html#tymethod.buffer_body) method.
+#[derive(Clone)]
+pub struct BufferedBody {
+    inner: Inner,
+}
+
+#[derive(Clone)]
+enum Inner {
+    Memory(Bytes),
+    #[cfg(feature = "fs")]
+    File {
+        file: Arc<SpillFile>,
+        len: u64,
+    },
+}
+
+#[cfg(feature = "fs")]
+struct SpillFile {
+    path: PathBuf,
+}
+
+#[cfg(feature = "fs")]
+impl SpillFile {
+    async fn create(dir: &Path) -> Result<(SpillFile, tokio::fs::File), BodyError> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        let name = format!(
+            "routerify-body-{}-{}-{}.tmp",
+            std::process::id(),
+            nanos,
+            SPILL_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+        );
+        let path = dir.join(name);
+
+        let file = tokio::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .await?;
+
+        Ok((SpillFile { path }, file))
+    }
+}
+
+#[cfg(feature = "fs")]
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+impl BufferedBody {
+    // Takes over a complete file, which is removed once the last clone of the `BufferedBody` is dropped.
+    #[cfg(feature = "fs")]
+    pub(crate) fn from_file(path: PathBuf, len: u64) -> BufferedBody {
+        BufferedBody {
+            inner: Inner::File {
+                file: Arc::new(SpillFile { path }),
+                len,
+            },
+        }
+    }
+
+    /// Returns the length of the body in bytes.
+    pub fn len(&self) -> u64 {
+        match self.inner {
+            Inner::Memory(ref bytes) => bytes.len() as u64,
+            #[cfg(feature = "fs")]
+            Inner::File { len, .. } => len,
+        }
+    }
+
+    /// Checks if the body is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Checks if the body is kept in memory i.e. it wasn't spilled to disk.
+    pub fn is_in_memory(&self) -> bool {
+        matches!(self.inner, Inner::Memory(_))
+    }
+
+    /// Returns the body bytes if the body is kept in memory.
+    pub fn as_bytes(&self) -> Option<&Bytes> {
+        match self.inner {
+            Inner::Memory(ref bytes) => Some(bytes),
+            #[cfg(feature = "fs")]
+            Inner::File { .. } => None,
+        }
+    }
+
+    /// Returns the path of the temporary file if the body was spilled to disk.
+    pub fn path(&self) -> Option<&Path> {
+        match self.inner {
+            Inner::Memory(_) => None,
+            #[cfg(feature = "fs")]
+            Inner::File { ref file, .. } => Some(file.path.as_path()),
+        }
+    }
+
+    /// Reads the whole body into memory.
+    pub async fn to_bytes(&self) -> Result<Bytes, BodyError> {
+        match self.inner {
+            Inner::Memory(ref bytes) => Ok(bytes.clone()),
+            #[cfg(feature = "fs")]
+            Inner::File { ref file, .. } => Ok(Bytes::from(tokio::fs::read(&file.path).await?)),
+        }
+    }
+
+    /// Creates a new [`hyper::Body`](https://docs.rs/hyper/0.14.4/hyper/body/struct.Body.html) which replays the buffered content.
+    ///
+    /// Spilled bodies are streamed from the temporary file, which is opened once the body is first polled, so an error
+    /// opening it is returned by the body stream.
+    pub fn to_body(&self) -> Result<hyper::Body, BodyError> {
+        match self.inner {
+            Inner::Memory(ref bytes) => Ok(hyper::Body::from(bytes.clone())),
+            #[cfg(feature = "fs")]
+            Inner::File { ref file, .. } => {
+                let path = file.path.clone();
+                let state = SpillState::Opening(Box::pin(tokio::fs::File::open(path)));
+
+                // Keep the temporary file alive until the stream is dropped.
+                Ok(hyper::Body::wrap_stream(SpillStream {
+                    state,
+                    _guard: file.clone(),
+                }))
+            }
+        }
+    }
+}
+
+impl From<Bytes> for BufferedBody {
+    fn from(bytes: Bytes) -> Self {
+        BufferedBody {
+            inner: Inner::Memory(bytes),
+        }
+    }
+}
+
+impl Debug for BufferedBody {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.inner {
+            Inner::Memory(ref bytes) => write!(f, "BufferedBody {{ in_memory: {} bytes }}", bytes.len()),
+            #[cfg(feature = "fs")]
+            Inner::File { ref file, len } => write!(f, "BufferedBody {{ spilled: {} bytes at {:?} }}", len, file.path),
+        }
+    }
+}
+
+#[cfg(feature = "fs")]
+type OpenFuture = Pin<Box<dyn Future<Output = io::Result<tokio::fs::File>> + Send>>;
+
+#[cfg(feature = "fs")]
+enum SpillState {
+    Opening(OpenFuture),
+    Reading(ReaderStream<tokio::fs::File>),
+    Done,
+}
+
+#[cfg(feature = "fs")]
+struct SpillStream {
+    state: SpillState,
+    _guard: Arc<SpillFile>,
+}
+
+#[cfg(feature = "fs")]
+impl Stream for SpillStream {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            match self.state {
+                SpillState::Opening(ref mut open) => match open.as_mut().poll(cx) {
+                    Poll::Ready(Ok(file)) => self.state = SpillState::Reading(ReaderStream::new(file)),
+                    Poll::Ready(Err(err)) => {
+                        self.state = SpillState::Done;
+                        return Poll::Ready(Some(Err(err)));
+                    }
+                    Poll::Pending => return Poll::Pending,
+                },
+                SpillState::Reading(ref mut stream) => return Pin::new(stream).poll_next(cx),
+                SpillState::Done => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+/// Aggregates the body in memory up to `limit` bytes and spills the rest into a temporary file as configured by
+/// `spill`.
+///
+/// Without a `spill`, a body larger than `limit` is rejected with [`BodyError::TooLarge`](./enum.BodyError.html#variant.TooLarge),
+/// and so is a body larger than the `max_size` of the `spill`.
+#[cfg(feature = "fs")]
+pub(crate) async fn buffer(
+    mut body: hyper::Body,
+    limit: usize,
+    spill: Option<SpillConfig>,
+) -> Result<BufferedBody, BodyError> {
+    let mut mem = Vec::new();
+    let mut spilled: Option<(SpillFile, tokio::fs::File)> = None;
+    let mut len: u64 = 0;
+
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        len += chunk.len() as u64;
+
+        if len <= limit as u64 {
+            mem.extend_from_slice(&chunk);
+            continue;
+        }
+
+        let spill = spill.as_ref().ok_or(BodyError::TooLarge { limit: limit as u64 })?;
+        if len > spill.max_size {
+            // The partial temporary file, if any, is removed once it's dropped.
+            return Err(BodyError::TooLarge { limit: spill.max_size });
+        }
+
+        if let Some((_, ref mut file)) = spilled {
+            file.write_all(&chunk).await?;
+        } else {
+            let (spill_file, mut file) = SpillFile::create(&spill.dir).await?;
+            file.write_all(&mem).await?;
+            file.write_all(&chunk).await?;
+            mem = Vec::new();
+            spilled = Some((spill_file, file));
+        }
+    }
+
+    match spilled {
+        Some((spill_file, mut file)) => {
+            file.flush().await?;
+            Ok(BufferedBody {
+                inner: Inner::File {
+                    file: Arc::new(spill_file),
+                    len,
+                },
+            })
+        }
+        None => Ok(BufferedBody::from(Bytes::from(mem))),
+    }
+}
+
+/// Aggregates the body in memory up to `limit` bytes and rejects a larger body with
+/// [`BodyError::TooLarge`](./enum.BodyError.html#variant.TooLarge).
+///
+/// Spilling requires the `fs` feature, so a `spill` is rejected with an `Unsupported` I/O error before the body is read.
+#[cfg(not(feature = "fs"))]
+pub(crate) async fn buffer(
+    mut body: hyper::Body,
+    limit: usize,
+    spill: Option<SpillConfig>,
+) -> Result<BufferedBody, BodyError> {
+    if spill.is_some() {
+        return Err(BodyError::Io(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Spilling request bodies to disk requires the fs feature",
+        )));
+    }
+
+    let mut mem = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        if mem.len() + chunk.len() > limit {
+            return Err(BodyError::TooLarge { limit: limit as u64 });
+        }
+        mem.extend_from_slice(&chunk);
+    }
+
+    Ok(BufferedBody::from(Bytes::from(mem)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn keeps_small_bodies_in_memory() {
+        let buffered = buffer(hyper::Body::from("hello"), 16, None).await.unwrap();
+        assert!(buffered.is_in_memory());
+        assert_eq!(buffered.len(), 5);
+        assert_eq!(buffered.to_bytes().await.unwrap(), Bytes::from("hello"));
+    }
+
+    #[tokio::test]
+    async fn rejects_large_bodies_without_spill() {
+        let err = buffer(hyper::Body::from("hello world"), 4, None).await.unwrap_err();
+        assert!(matches!(err, BodyError::TooLarge { limit: 4 }));
+    }
+
+    #[cfg(feature = "fs")]
+    #[tokio::test]
+    async fn spills_large_bodies_and_replays_them() {
+        let spill = SpillConfig {
+            dir: std::env::temp_dir(),
+            max_size: 1024,
+        };
+        let buffered = buffer(hyper::Body::from("hello world"), 4, Some(spill)).await.unwrap();
+        assert!(!buffered.is_in_memory());
+        assert_eq!(buffered.len(), 11);
+
+        let path = buffered.path().unwrap().to_path_buf();
+        assert!(path.exists());
+
+        for _ in 0..2 {
+            let body = buffered.to_body().unwrap();
+            assert_eq!(hyper::body::to_bytes(body).await.unwrap(), Bytes::from("hello world"));
+        }
+
+        drop(buffered);
+        assert!(!path.exists());
+    }
+
+    #[cfg(feature = "fs")]
+    #[tokio::test]
+    async fn rejects_bodies_larger_than_the_spill_limit() {
+        let spill = SpillConfig {
+            dir: std::env::temp_dir(),
+            max_size: 8,
+        };
+        let chunks: Vec<Result<_, io::Error>> = vec![Ok("hello"), Ok(" "), Ok("world")];
+        let body = hyper::Body::wrap_stream(futures::stream::iter(chunks));
+
+        let err = buffer(body, 4, Some(spill)).await.unwrap_err();
+        assert!(matches!(err, BodyError::TooLarge { limit: 8 }));
+    }
+
+    #[cfg(not(feature = "fs"))]
+    #[tokio::test]
+    async fn rejects_spilling_without_the_fs_feature() {
+        let spill = SpillConfig {
+            dir: std::env::temp_dir(),
+            max_size: 1024,
+        };
+        let err = buffer(hyper::Body::from("hello"), 16, Some(spill)).await.unwrap_err();
+        assert!(matches!(err, BodyError::Io(ref err) if err.kind() == io::ErrorKind::Unsupported));
+    }
+}
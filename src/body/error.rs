@@ -0,0 +1,69 @@
+use hyper::StatusCode;
+use std::fmt::{self, Display, Formatter};
+use std::io;
+
+/// The error type returned while reading or buffering a request body.
+#[derive(Debug)]
+pub enum BodyError {
+    /// The body is larger than the allowed limit.
+    TooLarge {
+        /// The limit in bytes which was exceeded.
+        limit: u64,
+    },
+
+    /// An I/O error occurred while spilling the body to disk or while reading it back.
+    Io(io::Error),
+
+    /// An error occurred while receiving the body from the client.
+    Hyper(hyper::Error),
+
+    /// The body wasn't received before the [`Deadline`](../struct.Deadline.html) of the request.
+    Timeout,
+}
+
+impl BodyError {
+    /// Returns the status code of the response that should be sent for the error, which is `408 Request Timeout` for
+    /// [`Timeout`](#variant.Timeout), `413 Payload Too Large` for [`TooLarge`](#variant.TooLarge), `400 Bad Request`
+    /// for [`Hyper`](#variant.Hyper) and `500 Internal Server Error` for [`Io`](#variant.Io).
+    pub fn status(&self) -> StatusCode {
+        match self {
+            BodyError::TooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            BodyError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            BodyError::Hyper(_) => StatusCode::BAD_REQUEST,
+            BodyError::Timeout => StatusCode::REQUEST_TIMEOUT,
+        }
+    }
+}
+
+impl Display for BodyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            BodyError::TooLarge { limit } => write!(f, "Request body exceeds the limit of {} bytes", limit),
+            BodyError::Io(err) => write!(f, "Couldn't buffer the request body: {}", err),
+            BodyError::Hyper(err) => write!(f, "Couldn't read the request body: {}", err),
+            BodyError::Timeout => write!(f, "Request body wasn't received before the deadline"),
+        }
+    }
+}
+
+impl std::error::Error for BodyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BodyError::TooLarge { .. } | BodyError::Timeout => None,
+            BodyError::Io(err) => Some(err),
+            BodyError::Hyper(err) => Some(err),
+        }
+    }
+}
+
+impl From<io::Error> for BodyError {
+    fn from(err: io::Error) -> Self {
+        BodyError::Io(err)
+    }
+}
+
+impl From<hyper::Error> for BodyError {
+    fn from(err: hyper::Error) -> Self {
+        BodyError::Hyper(err)
+    }
+}
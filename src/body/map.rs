@@ -0,0 +1,92 @@
+use crate::RouteError;
+use futures_core::Stream;
+use hyper::body::{Bytes, HttpBody};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// A chunk by chunk transformation of a body, which is applied by the [`map_body`](./fn.map_body.html) function.
+///
+/// The transformation is streaming: each chunk of the original body is mapped as it arrives, so the mapper should only
+/// keep the state it needs e.g. an incomplete tag at the end of a chunk.
+pub trait BodyMapper: Send + 'static {
+    /// Maps a chunk of the original body. An empty output chunk is skipped.
+    fn map_chunk(&mut self, chunk: Bytes) -> crate::Result<Bytes>;
+
+    /// Returns the remaining output once the original body ends. Defaults to nothing.
+    fn finish(&mut self) -> crate::Result<Bytes> {
+        Ok(Bytes::new())
+    }
+}
+
+/// Wraps the body into a stream which transforms it by the mapper.
+///
+/// The length of the mapped body is unknown, so the `Content-Length` header of the original body must be removed.
+/// The trailers of the body are not forwarded.
+///
+/// Please refer to the [`BodyMapper`](./trait.BodyMapper.html) for more info.
+pub fn map_body<M: BodyMapper>(body: hyper::Body, mapper: M) -> hyper::Body {
+    hyper::Body::wrap_stream(MappingStream {
+        body,
+        mapper: Some(mapper),
+    })
+}
+
+struct MappingStream<M> {
+    body: hyper::Body,
+    // It's taken once the body ends, so the stream ends after the output of the `finish` method.
+    mapper: Option<M>,
+}
+
+// The fields are never pinned.
+impl<M> Unpin for MappingStream<M> {}
+
+impl<M: BodyMapper> Stream for MappingStream<M> {
+    type Item = Result<Bytes, RouteError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if self.mapper.is_none() {
+                return Poll::Ready(None);
+            }
+
+            let chunk = match Pin::new(&mut self.body).poll_data(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Some(Ok(chunk))) => self.mapper.as_mut().unwrap().map_chunk(chunk),
+                Poll::Ready(Some(Err(err))) => Err(err.into()),
+                Poll::Ready(None) => self.mapper.take().unwrap().finish(),
+            };
+
+            match chunk {
+                Ok(chunk) if chunk.is_empty() => continue,
+                Ok(chunk) => return Poll::Ready(Some(Ok(chunk))),
+                Err(err) => {
+                    self.mapper = None;
+                    return Poll::Ready(Some(Err(err)));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Upper;
+
+    impl BodyMapper for Upper {
+        fn map_chunk(&mut self, chunk: Bytes) -> crate::Result<Bytes> {
+            Ok(Bytes::from(chunk.to_ascii_uppercase()))
+        }
+
+        fn finish(&mut self) -> crate::Result<Bytes> {
+            Ok(Bytes::from_static(b"!"))
+        }
+    }
+
+    #[tokio::test]
+    async fn maps_streamed_chunks() {
+        let body = map_body(hyper::Body::from("hello"), Upper);
+        assert_eq!(hyper::body::to_bytes(body).await.unwrap(), Bytes::from("HELLO!"));
+    }
+}
@@ -0,0 +1,11 @@
+pub(crate) use self::buffered::buffer;
+pub use self::buffered::{BufferedBody, SpillConfig};
+pub use self::error::BodyError;
+pub use self::map::{map_body, BodyMapper};
+pub(crate) use self::stats::count_body;
+pub use self::stats::ResponseStats;
+
+mod buffered;
+mod error;
+mod map;
+mod stats;
@@ -0,0 +1,165 @@
+use futures_core::Stream;
+use hyper::body::{Bytes, HttpBody};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::sync::watch;
+
+/// The number of response body bytes actually written to the client.
+///
+/// It's put into the response extensions by the [`response_stats`](./middleware/fn.response_stats.html) post middleware,
+/// so the post middlewares which run after it can record the bandwidth metrics. The body is sent after the post
+/// middlewares, so the final numbers are known once the [`finished`](#method.finished) future resolves.
+///
+/// # Examples
+///
+/// ```
+/// use routerify::{Middleware, ResponseStats, Router};
+/// use routerify::middleware::response_stats;
+/// use hyper::{Response, Body};
+/// use std::convert::Infallible;
+///
+/// # fn run() -> Router<Body, Infallible> {
+/// let router = Router::builder()
+///     .middleware(response_stats())
+///     .middleware(Middleware::post(|res: Response<Body>| async move {
+///         if let Some(stats) = res.extensions().get::<ResponseStats>().cloned() {
+///             tokio::spawn(async move {
+///                 let bytes = stats.finished().await;
+///                 println!("Sent {} bytes, completed: {}", bytes, stats.is_complete());
+///             });
+///         }
+///         Ok(res)
+///     }))
+///     .build()
+///     .unwrap();
+/// # router
+/// # }
+/// # run();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ResponseStats {
+    inner: Arc<StatsInner>,
+}
+
+#[derive(Debug)]
+struct StatsInner {
+    bytes: AtomicU64,
+    complete: AtomicBool,
+    finished: watch::Sender<bool>,
+}
+
+impl ResponseStats {
+    pub(crate) fn new() -> ResponseStats {
+        ResponseStats {
+            inner: Arc::new(StatsInner {
+                bytes: AtomicU64::new(0),
+                complete: AtomicBool::new(false),
+                finished: watch::channel(false).0,
+            }),
+        }
+    }
+
+    /// Returns the number of body bytes written so far.
+    pub fn bytes_written(&self) -> u64 {
+        self.inner.bytes.load(Ordering::Acquire)
+    }
+
+    /// Checks if the body is no longer being sent, either because it was sent completely or because it was dropped
+    /// e.g. the client disconnected.
+    pub fn is_finished(&self) -> bool {
+        *self.inner.finished.borrow()
+    }
+
+    /// Checks if the whole body was sent.
+    pub fn is_complete(&self) -> bool {
+        self.inner.complete.load(Ordering::Acquire)
+    }
+
+    /// Waits until the body is no longer being sent and returns the number of body bytes written.
+    pub async fn finished(&self) -> u64 {
+        let mut rx = self.inner.finished.subscribe();
+        let _ = rx.wait_for(|finished| *finished).await;
+        self.bytes_written()
+    }
+
+    fn finish(&self) {
+        self.inner.finished.send_replace(true);
+    }
+}
+
+/// Wraps the body into a stream which counts the bytes passing through it.
+///
+/// The body is complete once its end is reached or `expected_len` bytes are passed, as hyper stops polling a body
+/// with a known length after the last byte. The trailers of the body are not forwarded.
+pub(crate) fn count_body(body: hyper::Body, stats: ResponseStats, expected_len: Option<u64>) -> hyper::Body {
+    hyper::Body::wrap_stream(CountingStream {
+        body,
+        stats,
+        expected_len,
+    })
+}
+
+struct CountingStream {
+    body: hyper::Body,
+    stats: ResponseStats,
+    expected_len: Option<u64>,
+}
+
+impl Stream for CountingStream {
+    type Item = Result<Bytes, hyper::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let poll = Pin::new(&mut self.body).poll_data(cx);
+        match poll {
+            Poll::Ready(Some(Ok(ref chunk))) => {
+                let bytes = self.stats.inner.bytes.fetch_add(chunk.len() as u64, Ordering::AcqRel) + chunk.len() as u64;
+                if self.expected_len.is_some() && bytes >= self.expected_len.unwrap() {
+                    self.stats.inner.complete.store(true, Ordering::Release);
+                }
+            }
+            Poll::Ready(None) => {
+                self.stats.inner.complete.store(true, Ordering::Release);
+                self.stats.finish();
+            }
+            _ => {}
+        }
+        poll
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, None)
+    }
+}
+
+impl Drop for CountingStream {
+    fn drop(&mut self) {
+        self.stats.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn counts_streamed_bytes() {
+        let stats = ResponseStats::new();
+        let body = count_body(hyper::Body::from("hello world"), stats.clone(), None);
+        assert!(!stats.is_finished());
+
+        assert_eq!(hyper::body::to_bytes(body).await.unwrap(), Bytes::from("hello world"));
+        assert_eq!(stats.finished().await, 11);
+        assert!(stats.is_complete());
+    }
+
+    #[tokio::test]
+    async fn finishes_dropped_bodies() {
+        let stats = ResponseStats::new();
+        drop(count_body(hyper::Body::from("hello"), stats.clone(), Some(5)));
+
+        assert_eq!(stats.finished().await, 0);
+        assert!(!stats.is_complete());
+    }
+}
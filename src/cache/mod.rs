@@ -0,0 +1,400 @@
+//! An in-memory cache of the route responses with the `stale-while-revalidate` and `stale-if-error` semantics.
+//!
+//! The [`cached`](./struct.ResponseCache.html#method.cached) method wraps a route handler, so its `GET` responses are
+//! cached under their path and query by the [`CachePolicy`](./struct.CachePolicy.html) of the route. A cached response
+//! is:
+//!
+//! * Fresh for the `ttl`, so it's served without calling the handler.
+//! * Stale for the `stale-while-revalidate` period after that. It's still served immediately, while the handler is
+//!   called once in a background task to refresh it. The task is spawned on the shared
+//!   [`TaskRegistry`](../struct.TaskRegistry.html) if there is one.
+//! * Stale for the `stale-if-error` period after the `ttl`. It's served only if the handler fails or responds with a
+//!   `5xx` status code.
+//!
+//! The served responses from the cache have an `Age` header. Only the `200 OK` responses are cached, unless their
+//! `Cache-Control` header has the `no-store` or the `private` directive, or they have a `Set-Cookie` or a `Vary`
+//! header. The requests with an `Authorization` or a `Cookie` header bypass the cache, as the responses are shared by
+//! all the clients.
+//!
+//! # Examples
+//!
+//! ```
+//! use routerify::{Router, RouteError};
+//! use routerify::cache::{CachePolicy, ResponseCache};
+//! use hyper::{Response, Body};
+//! use std::time::Duration;
+//!
+//! # fn run() -> Router<Body, RouteError> {
+//! let cache = ResponseCache::new();
+//! let policy = CachePolicy::new(Duration::from_secs(10))
+//!     .stale_while_revalidate(Duration::from_secs(60))
+//!     .stale_if_error(Duration::from_secs(3600));
+//!
+//! let router = Router::builder()
+//!     .get(
+//!         "/products",
+//!         cache.cached(policy, |_| async move {
+//!             // An expensive query goes here.
+//!             Ok(Response::new(Body::from("[]")))
+//!         }),
+//!     )
+//!     .build()
+//!     .unwrap();
+//! # router
+//! # }
+//! # run();
+//! ```
+
+use crate::ext::RequestExt;
+use crate::helpers;
+use crate::{HttpError, TaskRegistry};
+use hyper::body::Bytes;
+use hyper::header::{self, HeaderMap, HeaderValue};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const PRUNE_THRESHOLD: usize = 10_000;
+
+type CacheFuture<E> = Pin<Box<dyn Future<Output = Result<Response<Body>, E>> + Send>>;
+
+/// The caching periods of a route.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CachePolicy {
+    ttl: Duration,
+    stale_while_revalidate: Duration,
+    stale_if_error: Duration,
+}
+
+impl CachePolicy {
+    /// Creates a new policy whose responses are fresh for the `ttl`, and aren't served once they are stale.
+    pub fn new(ttl: Duration) -> Self {
+        CachePolicy {
+            ttl,
+            stale_while_revalidate: Duration::ZERO,
+            stale_if_error: Duration::ZERO,
+        }
+    }
+
+    /// Sets the period after the `ttl` in which a stale response is served while it's refreshed in the background.
+    pub fn stale_while_revalidate(mut self, period: Duration) -> Self {
+        self.stale_while_revalidate = period;
+        self
+    }
+
+    /// Sets the period after the `ttl` in which a stale response is served if the handler fails.
+    pub fn stale_if_error(mut self, period: Duration) -> Self {
+        self.stale_if_error = period;
+        self
+    }
+}
+
+/// The store of the cached responses.
+///
+/// It's cheap to clone and the clones share the responses. Please refer to the [module](./index.html) documentation
+/// for more info.
+#[derive(Debug, Clone, Default)]
+pub struct ResponseCache {
+    entries: Arc<Mutex<HashMap<String, Entry>>>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    headers: HeaderMap,
+    body: Bytes,
+    stored_at: Instant,
+    expires_at: Instant,
+    is_refreshing: bool,
+}
+
+// The state of the cached response of a request.
+#[derive(Debug)]
+enum Lookup {
+    Fresh(Response<Body>),
+    Stale { resp: Response<Body>, refresh: bool },
+    Fallback(Response<Body>),
+    Miss,
+}
+
+impl ResponseCache {
+    /// Creates a new empty cache.
+    pub fn new() -> Self {
+        ResponseCache::default()
+    }
+
+    /// Wraps a route handler, so its responses are cached by the policy.
+    pub fn cached<H, R, E>(
+        &self,
+        policy: CachePolicy,
+        handler: H,
+    ) -> impl Fn(Request<Body>) -> CacheFuture<E> + Send + Sync + 'static
+    where
+        H: Fn(Request<Body>) -> R + Send + Sync + 'static,
+        R: Future<Output = Result<Response<Body>, E>> + Send + 'static,
+        E: From<HttpError> + Send + 'static,
+    {
+        let cache = self.clone();
+        let handler = Arc::new(handler);
+        move |req: Request<Body>| {
+            let cache = cache.clone();
+            let handler = handler.clone();
+            Box::pin(async move {
+                // The responses of the authenticated requests may differ per user.
+                if req.method() != Method::GET
+                    || req.headers().contains_key(header::AUTHORIZATION)
+                    || req.headers().contains_key(header::COOKIE)
+                {
+                    return handler(req).await;
+                }
+
+                let key = req
+                    .uri()
+                    .path_and_query()
+                    .map(|path| path.as_str())
+                    .unwrap_or("/")
+                    .to_owned();
+                let fallback = match cache.lookup(&key, &policy, Instant::now()) {
+                    Lookup::Fresh(resp) => return Ok(resp),
+                    Lookup::Stale { resp, refresh } => {
+                        if refresh {
+                            cache.refresh(key, policy, handler, &req);
+                        }
+                        return Ok(resp);
+                    }
+                    Lookup::Fallback(resp) => Some(resp),
+                    Lookup::Miss => None,
+                };
+
+                match (handler(req).await, fallback) {
+                    (Ok(resp), _) if !resp.status().is_server_error() => cache.store(key, &policy, resp).await,
+                    (_, Some(fallback)) => Ok(fallback),
+                    (result, None) => result,
+                }
+            })
+        }
+    }
+
+    /// Removes the cached responses of the path, with any query.
+    pub fn invalidate(&self, path: &str) {
+        self.entries.lock().unwrap().retain(|key, _| {
+            let key_path = key.split_once('?').map(|(key_path, _)| key_path).unwrap_or(key);
+            key_path != path
+        });
+    }
+
+    /// Removes all the cached responses.
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    /// Returns the number of the cached responses, including the stale ones.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    /// Checks if there are no cached responses.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lookup(&self, key: &str, policy: &CachePolicy, now: Instant) -> Lookup {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = match entries.get_mut(key) {
+            Some(entry) => entry,
+            None => return Lookup::Miss,
+        };
+
+        let age = now.saturating_duration_since(entry.stored_at);
+        if age < policy.ttl {
+            Lookup::Fresh(entry.response(age))
+        } else if age < policy.ttl + policy.stale_while_revalidate {
+            let refresh = !entry.is_refreshing;
+            entry.is_refreshing = true;
+            Lookup::Stale {
+                resp: entry.response(age),
+                refresh,
+            }
+        } else if age < policy.ttl + policy.stale_if_error {
+            Lookup::Fallback(entry.response(age))
+        } else {
+            entries.remove(key);
+            Lookup::Miss
+        }
+    }
+
+    // Refreshes the stale response in a background task with a copy of the request.
+    fn refresh<H, R, E>(&self, key: String, policy: CachePolicy, handler: Arc<H>, req: &Request<Body>)
+    where
+        H: Fn(Request<Body>) -> R + Send + Sync + 'static,
+        R: Future<Output = Result<Response<Body>, E>> + Send + 'static,
+        E: From<HttpError> + Send + 'static,
+    {
+        let cache = self.clone();
+        let req = helpers::replay_request(req);
+        let tasks = req.data::<TaskRegistry>().cloned();
+        let task = async move {
+            match handler(req).await {
+                Ok(resp) if !resp.status().is_server_error() => {
+                    let _ = cache.store::<HttpError>(key, &policy, resp).await;
+                }
+                // The stale response is kept, and the next request retries.
+                _ => {
+                    if let Some(entry) = cache.entries.lock().unwrap().get_mut(&key) {
+                        entry.is_refreshing = false;
+                    }
+                }
+            }
+        };
+
+        match tasks {
+            Some(tasks) => {
+                let _ = tasks.spawn(task);
+            }
+            None => {
+                tokio::spawn(task);
+            }
+        }
+    }
+
+    // Buffers and stores the response if it's cacheable, otherwise removes the cached one.
+    async fn store<E: From<HttpError>>(
+        &self,
+        key: String,
+        policy: &CachePolicy,
+        resp: Response<Body>,
+    ) -> Result<Response<Body>, E> {
+        if !is_cacheable(&resp) {
+            self.entries.lock().unwrap().remove(&key);
+            return Ok(resp);
+        }
+
+        let (parts, body) = resp.into_parts();
+        let body = hyper::body::to_bytes(body).await.map_err(|err| {
+            HttpError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Couldn't buffer the response: {}", err),
+            )
+        })?;
+
+        let now = Instant::now();
+        let entry = Entry {
+            headers: parts.headers.clone(),
+            body: body.clone(),
+            stored_at: now,
+            expires_at: now + policy.ttl + policy.stale_while_revalidate.max(policy.stale_if_error),
+            is_refreshing: false,
+        };
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= PRUNE_THRESHOLD {
+            entries.retain(|_, entry| entry.expires_at > now);
+        }
+        entries.insert(key, entry);
+
+        Ok(Response::from_parts(parts, Body::from(body)))
+    }
+}
+
+impl Entry {
+    fn response(&self, age: Duration) -> Response<Body> {
+        let mut resp = Response::new(Body::from(self.body.clone()));
+        *resp.headers_mut() = self.headers.clone();
+        resp.headers_mut().insert(header::AGE, HeaderValue::from(age.as_secs()));
+        resp
+    }
+}
+
+fn is_cacheable(resp: &Response<Body>) -> bool {
+    let is_private = resp
+        .headers()
+        .get_all(header::CACHE_CONTROL)
+        .iter()
+        .filter_map(|val| val.to_str().ok())
+        .flat_map(|val| val.split(','))
+        .map(|directive| directive.trim().to_ascii_lowercase())
+        .any(|directive| directive == "no-store" || directive.starts_with("private"));
+    // The cookies of a client and the variants of a content negotiation mustn't be served to the other clients.
+    let is_per_client = resp.headers().contains_key(header::SET_COOKIE) || resp.headers().contains_key(header::VARY);
+    resp.status() == StatusCode::OK && !is_private && !is_per_client
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn ages_cached_responses() {
+        let cache = ResponseCache::new();
+        let policy = CachePolicy::new(Duration::from_secs(10))
+            .stale_while_revalidate(Duration::from_secs(10))
+            .stale_if_error(Duration::from_secs(30));
+        let resp = Response::new(Body::from("cached"));
+        cache.store::<HttpError>("/a".to_owned(), &policy, resp).await.unwrap();
+        let now = Instant::now();
+
+        assert!(matches!(cache.lookup("/a", &policy, now), Lookup::Fresh(_)));
+        assert!(matches!(
+            cache.lookup("/a", &policy, now + Duration::from_secs(15)),
+            Lookup::Stale { refresh: true, .. }
+        ));
+        assert!(matches!(
+            cache.lookup("/a", &policy, now + Duration::from_secs(16)),
+            Lookup::Stale { refresh: false, .. }
+        ));
+        match cache.lookup("/a", &policy, now + Duration::from_secs(25)) {
+            Lookup::Fallback(resp) => assert_eq!(resp.headers()["age"], "25"),
+            lookup => panic!("Unexpected lookup: {:?}", lookup),
+        }
+        assert!(matches!(
+            cache.lookup("/a", &policy, now + Duration::from_secs(45)),
+            Lookup::Miss
+        ));
+        assert!(cache.is_empty());
+
+        let private = Response::builder()
+            .header("cache-control", "private, max-age=60")
+            .body(Body::empty())
+            .unwrap();
+        assert!(!is_cacheable(&private));
+    }
+
+    #[tokio::test]
+    async fn bypasses_per_client_requests_and_responses() {
+        let cache = ResponseCache::new();
+        let handler = cache.cached(
+            CachePolicy::new(Duration::from_secs(10)),
+            |req: Request<Body>| async move {
+                let mut resp = Response::new(Body::from("user"));
+                if req.uri().path() == "/session" {
+                    resp.headers_mut()
+                        .insert(header::SET_COOKIE, HeaderValue::from_static("id=1"));
+                }
+                if req.uri().path() == "/negotiated" {
+                    resp.headers_mut()
+                        .insert(header::VARY, HeaderValue::from_static("Accept-Language"));
+                }
+                Ok::<_, HttpError>(resp)
+            },
+        );
+
+        let req = Request::builder()
+            .uri("/profile")
+            .header(header::COOKIE, "session=1")
+            .body(Body::empty())
+            .unwrap();
+        handler(req).await.unwrap();
+        for path in ["/session", "/negotiated"].iter() {
+            handler(Request::builder().uri(*path).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+        }
+        assert!(cache.is_empty());
+
+        handler(Request::builder().uri("/public").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(cache.len(), 1);
+    }
+}
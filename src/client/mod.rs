@@ -0,0 +1,229 @@
+//! An outbound HTTP client which propagates the request context to the upstream services. It requires the `client`
+//! feature.
+//!
+//! The [`RequestExt::http_client`](../ext/trait.RequestExt.html#method.http_client) method returns a
+//! [`RequestClient`](./struct.RequestClient.html) bound to the current request, which adds these headers to every
+//! outbound request, unless the outbound request sets them itself:
+//!
+//! * The propagated headers of the current request, which are the `x-request-id`, `traceparent` and `tracestate`
+//!   headers by default. With the `opentelemetry` feature, the `traceparent` and `tracestate` headers are generated
+//!   from the span of the [`otel`](../middleware/otel/index.html) middleware instead.
+//! * The `x-request-timeout-ms` header with the time left until the [`Deadline`](../struct.Deadline.html) put into the
+//!   request context. The outbound request fails once the deadline passes.
+//!
+//! The client is configured by sharing a [`Client`](./struct.Client.html) through the router
+//! [data](../struct.RouterBuilder.html#method.data), otherwise a default one is used.
+//!
+//! # Examples
+//!
+//! ```
+//! use routerify::{Router, RouteError};
+//! use routerify::client::{Client, Deadline};
+//! use routerify::ext::RequestExt;
+//! use hyper::{Body, Response};
+//! use std::time::Duration;
+//!
+//! # fn run() -> Router<Body, RouteError> {
+//! let router = Router::builder()
+//!     .data(Client::new().propagate_header("x-tenant-id"))
+//!     .get("/orders", |req| async move {
+//!         req.set_context(Deadline::after(Duration::from_secs(2)));
+//!
+//!         let resp = req.http_client().get("http://inventory.local/items").await?;
+//!         Ok(Response::new(resp.into_body()))
+//!     })
+//!     .build()
+//!     .unwrap();
+//! # router
+//! # }
+//! # run();
+//! ```
+
+pub use crate::types::Deadline;
+
+use crate::Error;
+use hyper::client::HttpConnector;
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
+use hyper::{Body, Request, Response, Uri};
+use std::convert::TryFrom;
+use std::fmt::{self, Debug, Formatter};
+use std::sync::Arc;
+
+/// The header carrying the time left until the deadline of a request, in milliseconds.
+pub const DEADLINE_HEADER: &str = "x-request-timeout-ms";
+
+const DEFAULT_PROPAGATE_HEADERS: &[&str] = &["x-request-id", "traceparent", "tracestate"];
+
+lazy_static::lazy_static! {
+    static ref DEFAULT_CLIENT: Client = Client::new();
+}
+
+/// The configuration of the outbound HTTP client, shared by the requests.
+///
+/// Please refer to the [module](./index.html) documentation for more info.
+#[derive(Clone)]
+pub struct Client {
+    inner: hyper::Client<HttpConnector, Body>,
+    propagate_headers: Arc<Vec<HeaderName>>,
+}
+
+impl Client {
+    /// Creates a client which propagates the default headers.
+    pub fn new() -> Self {
+        Client::with_client(hyper::Client::new())
+    }
+
+    /// Creates a client which sends the requests through the specified hyper client e.g. with a custom connection pool
+    /// configuration.
+    pub fn with_client(inner: hyper::Client<HttpConnector, Body>) -> Self {
+        Client {
+            inner,
+            propagate_headers: Arc::new(
+                DEFAULT_PROPAGATE_HEADERS
+                    .iter()
+                    .map(|name| HeaderName::from_static(name))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Adds a header to be copied from the current request to the outbound requests.
+    ///
+    /// # Panics
+    ///
+    /// It panics if the header name is invalid.
+    pub fn propagate_header<N: AsRef<str>>(mut self, name: N) -> Self {
+        let name = HeaderName::from_bytes(name.as_ref().as_bytes()).expect("Invalid header name to propagate");
+        Arc::make_mut(&mut self.propagate_headers).push(name);
+        self
+    }
+
+    /// Binds the client to the specified headers and deadline of a request. It's normally called by the
+    /// [`RequestExt::http_client`](../ext/trait.RequestExt.html#method.http_client) method.
+    pub fn bind(&self, headers: &HeaderMap, deadline: Option<Deadline>) -> RequestClient {
+        let mut propagated = HeaderMap::new();
+        for name in self.propagate_headers.iter() {
+            for value in headers.get_all(name) {
+                propagated.append(name.clone(), value.clone());
+            }
+        }
+
+        RequestClient {
+            inner: self.inner.clone(),
+            headers: propagated,
+            deadline,
+        }
+    }
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Client::new()
+    }
+}
+
+impl Debug for Client {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{{ propagate_headers: {:?} }}", self.propagate_headers)
+    }
+}
+
+/// An outbound HTTP client bound to the context of a request.
+///
+/// Please refer to the [module](./index.html) documentation for more info.
+#[derive(Clone)]
+pub struct RequestClient {
+    inner: hyper::Client<HttpConnector, Body>,
+    headers: HeaderMap,
+    deadline: Option<Deadline>,
+}
+
+impl RequestClient {
+    /// Returns the headers which are added to the outbound requests, except the deadline.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    // The trace context of the request is injected into the headers.
+    #[cfg(feature = "opentelemetry")]
+    pub(crate) fn headers_mut(&mut self) -> &mut HeaderMap {
+        &mut self.headers
+    }
+
+    /// Returns the deadline of the outbound requests.
+    pub fn deadline(&self) -> Option<Deadline> {
+        self.deadline
+    }
+
+    /// Sends a `GET` request to the specified uri.
+    pub async fn get<U>(&self, uri: U) -> crate::Result<Response<Body>>
+    where
+        Uri: TryFrom<U>,
+        <Uri as TryFrom<U>>::Error: Into<http::Error>,
+    {
+        let req = Request::get(uri).body(Body::empty()).map_err(Error::wrap)?;
+        self.request(req).await
+    }
+
+    /// Sends a request with the propagated headers.
+    ///
+    /// It fails if the deadline passes before the response head is received.
+    pub async fn request(&self, mut req: Request<Body>) -> crate::Result<Response<Body>> {
+        for name in self.headers.keys() {
+            if !req.headers().contains_key(name) {
+                for value in self.headers.get_all(name) {
+                    req.headers_mut().append(name.clone(), value.clone());
+                }
+            }
+        }
+
+        let deadline = match self.deadline {
+            Some(deadline) => deadline,
+            None => return self.inner.request(req).await.map_err(|err| Error::wrap(err).into()),
+        };
+
+        let remaining = deadline.remaining();
+        if remaining.is_zero() {
+            return Err(Error::new("The request deadline has passed").into());
+        }
+        let name = HeaderName::from_static(DEADLINE_HEADER);
+        if !req.headers().contains_key(&name) {
+            req.headers_mut()
+                .insert(name, HeaderValue::from(remaining.as_millis() as u64));
+        }
+
+        match tokio::time::timeout(remaining, self.inner.request(req)).await {
+            Ok(result) => result.map_err(|err| Error::wrap(err).into()),
+            Err(_) => Err(Error::new("The request deadline has passed").into()),
+        }
+    }
+}
+
+impl Debug for RequestClient {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{{ headers: {:?}, deadline: {:?} }}", self.headers, self.deadline)
+    }
+}
+
+pub(crate) fn default_client() -> &'static Client {
+    &DEFAULT_CLIENT
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binds_propagated_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-request-id", HeaderValue::from_static("abc"));
+        headers.insert("x-tenant-id", HeaderValue::from_static("acme"));
+        headers.insert("authorization", HeaderValue::from_static("secret"));
+
+        let client = Client::new().propagate_header("x-tenant-id").bind(&headers, None);
+        assert_eq!(client.headers().len(), 2);
+        assert_eq!(client.headers()["x-request-id"], "abc");
+        assert_eq!(client.headers()["x-tenant-id"], "acme");
+        assert!(!client.headers().contains_key("authorization"));
+    }
+}
@@ -0,0 +1,286 @@
+//! Serde based body codecs for content negotiated APIs. It requires the `json`, `msgpack` or `cbor` feature.
+//!
+//! The [`RequestBodyExt::decode`](../ext/trait.RequestBodyExt.html#method.decode) method picks the codec by the request
+//! `Content-Type` and the [`responses::negotiated`](../responses/fn.negotiated.html) function picks it by the request
+//! `Accept` header, among the codecs enabled by the features:
+//!
+//! | Feature   | Codec                           | Media type            |
+//! |-----------|---------------------------------|-----------------------|
+//! | `json`    | [`Json`](./struct.Json.html)       | `application/json`    |
+//! | `msgpack` | [`MsgPack`](./struct.MsgPack.html) | `application/msgpack` |
+//! | `cbor`    | [`Cbor`](./struct.Cbor.html)       | `application/cbor`    |
+//!
+//! # Examples
+//!
+//! ```
+//! use routerify::{Router, RouteError};
+//! use routerify::ext::RequestBodyExt;
+//! use routerify::responses;
+//! use hyper::Body;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Greeting {
+//!     name: String,
+//! }
+//!
+//! # fn run() -> Router<Body, RouteError> {
+//! let router = Router::builder()
+//!     .post("/greetings", |mut req| async move {
+//!         let mut greeting = req.decode::<Greeting>().await?;
+//!         greeting.name = format!("Hello {}", greeting.name);
+//!         Ok(responses::negotiated(req.headers(), &greeting)?)
+//!     })
+//!     .build()
+//!     .unwrap();
+//! # router
+//! # }
+//! # run();
+//! ```
+
+use crate::Error;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// A serde based format of the request and the response bodies.
+pub trait Codec {
+    /// Returns the media type of the format e.g. `application/json`.
+    fn media_type(&self) -> &'static str;
+
+    /// Checks if the codec handles the specified media type, without parameters. Defaults to an ASCII case-insensitive
+    /// comparison with the [`media_type`](./trait.Codec.html#tymethod.media_type).
+    fn accepts(&self, media_type: &str) -> bool {
+        media_type.eq_ignore_ascii_case(self.media_type())
+    }
+
+    /// Decodes a value from the body bytes.
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> crate::Result<T>;
+
+    /// Encodes a value into the body bytes.
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> crate::Result<Vec<u8>>;
+}
+
+/// The JSON codec. It requires the `json` feature.
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Json;
+
+#[cfg(feature = "json")]
+impl Codec for Json {
+    fn media_type(&self) -> &'static str {
+        "application/json"
+    }
+
+    fn accepts(&self, media_type: &str) -> bool {
+        media_type.eq_ignore_ascii_case(self.media_type()) || has_suffix_ignore_ascii_case(media_type, "+json")
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> crate::Result<T> {
+        serde_json::from_slice(bytes).map_err(|e| Error::new(format!("Couldn't decode the JSON body: {}", e)).into())
+    }
+
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> crate::Result<Vec<u8>> {
+        serde_json::to_vec(value).map_err(|e| Error::new(format!("Couldn't encode the JSON body: {}", e)).into())
+    }
+}
+
+/// The MessagePack codec, which encodes the structs as maps. It requires the `msgpack` feature.
+#[cfg(feature = "msgpack")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MsgPack;
+
+#[cfg(feature = "msgpack")]
+impl Codec for MsgPack {
+    fn media_type(&self) -> &'static str {
+        "application/msgpack"
+    }
+
+    fn accepts(&self, media_type: &str) -> bool {
+        media_type.eq_ignore_ascii_case(self.media_type()) || media_type.eq_ignore_ascii_case("application/x-msgpack")
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> crate::Result<T> {
+        rmp_serde::from_slice(bytes)
+            .map_err(|e| Error::new(format!("Couldn't decode the MessagePack body: {}", e)).into())
+    }
+
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> crate::Result<Vec<u8>> {
+        rmp_serde::to_vec_named(value)
+            .map_err(|e| Error::new(format!("Couldn't encode the MessagePack body: {}", e)).into())
+    }
+}
+
+/// The CBOR codec. It requires the `cbor` feature.
+#[cfg(feature = "cbor")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Cbor;
+
+#[cfg(feature = "cbor")]
+impl Codec for Cbor {
+    fn media_type(&self) -> &'static str {
+        "application/cbor"
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> crate::Result<T> {
+        ciborium::de::from_reader(bytes).map_err(|e| Error::new(format!("Couldn't decode the CBOR body: {}", e)).into())
+    }
+
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> crate::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        ciborium::ser::into_writer(value, &mut buf)
+            .map_err(|e| Error::new(format!("Couldn't encode the CBOR body: {}", e)))?;
+        Ok(buf)
+    }
+}
+
+#[cfg(feature = "json")]
+fn has_suffix_ignore_ascii_case(val: &str, suffix: &str) -> bool {
+    val.len() >= suffix.len() && val[val.len() - suffix.len()..].eq_ignore_ascii_case(suffix)
+}
+
+// The codecs enabled by the features, in the order of preference.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum BuiltinCodec {
+    #[cfg(feature = "json")]
+    Json,
+    #[cfg(feature = "msgpack")]
+    MsgPack,
+    #[cfg(feature = "cbor")]
+    Cbor,
+}
+
+pub(crate) const BUILTIN_CODECS: &[BuiltinCodec] = &[
+    #[cfg(feature = "json")]
+    BuiltinCodec::Json,
+    #[cfg(feature = "msgpack")]
+    BuiltinCodec::MsgPack,
+    #[cfg(feature = "cbor")]
+    BuiltinCodec::Cbor,
+];
+
+impl BuiltinCodec {
+    pub(crate) fn find(media_type: &str) -> Option<BuiltinCodec> {
+        BUILTIN_CODECS.iter().copied().find(|codec| codec.accepts(media_type))
+    }
+}
+
+impl Codec for BuiltinCodec {
+    fn media_type(&self) -> &'static str {
+        match *self {
+            #[cfg(feature = "json")]
+            BuiltinCodec::Json => Json.media_type(),
+            #[cfg(feature = "msgpack")]
+            BuiltinCodec::MsgPack => MsgPack.media_type(),
+            #[cfg(feature = "cbor")]
+            BuiltinCodec::Cbor => Cbor.media_type(),
+        }
+    }
+
+    fn accepts(&self, media_type: &str) -> bool {
+        match *self {
+            #[cfg(feature = "json")]
+            BuiltinCodec::Json => Json.accepts(media_type),
+            #[cfg(feature = "msgpack")]
+            BuiltinCodec::MsgPack => MsgPack.accepts(media_type),
+            #[cfg(feature = "cbor")]
+            BuiltinCodec::Cbor => Cbor.accepts(media_type),
+        }
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> crate::Result<T> {
+        match *self {
+            #[cfg(feature = "json")]
+            BuiltinCodec::Json => Json.decode(bytes),
+            #[cfg(feature = "msgpack")]
+            BuiltinCodec::MsgPack => MsgPack.decode(bytes),
+            #[cfg(feature = "cbor")]
+            BuiltinCodec::Cbor => Cbor.decode(bytes),
+        }
+    }
+
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> crate::Result<Vec<u8>> {
+        match *self {
+            #[cfg(feature = "json")]
+            BuiltinCodec::Json => Json.encode(value),
+            #[cfg(feature = "msgpack")]
+            BuiltinCodec::MsgPack => MsgPack.encode(value),
+            #[cfg(feature = "cbor")]
+            BuiltinCodec::Cbor => Cbor.encode(value),
+        }
+    }
+}
+
+// Picks the codec for an `Accept` header by the quality values, preferring the earlier codecs on a tie. A missing header
+// accepts any codec.
+pub(crate) fn negotiate(accept: Option<&str>) -> Option<BuiltinCodec> {
+    let accept = match accept {
+        Some(accept) => accept,
+        None => return BUILTIN_CODECS.first().copied(),
+    };
+
+    let mut best: Option<(BuiltinCodec, u32)> = None;
+    for item in accept.split(',') {
+        let mut parts = item.split(';');
+        let media_type = parts.next().unwrap_or("").trim();
+        let quality = parts
+            .filter_map(|param| param.trim().strip_prefix("q="))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .map(|q| (q.clamp(0.0, 1.0) * 1000.0) as u32)
+            .unwrap_or(1000);
+        if quality == 0 {
+            continue;
+        }
+
+        let matched = if media_type == "*/*" || media_type.eq_ignore_ascii_case("application/*") {
+            BUILTIN_CODECS.first().copied()
+        } else {
+            BuiltinCodec::find(media_type)
+        };
+
+        if let Some(codec) = matched {
+            let is_better = match best {
+                Some((_, best_quality)) => quality > best_quality,
+                None => true,
+            };
+            if is_better {
+                best = Some((codec, quality));
+            }
+        }
+    }
+
+    best.map(|(codec, _)| codec)
+}
+
+#[cfg(all(test, feature = "json", feature = "msgpack", feature = "cbor"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negotiates_by_quality() {
+        assert_eq!(negotiate(None).unwrap().media_type(), "application/json");
+        assert_eq!(
+            negotiate(Some("application/cbor;q=0.5, application/msgpack"))
+                .unwrap()
+                .media_type(),
+            "application/msgpack"
+        );
+        assert_eq!(
+            negotiate(Some("text/html, */*;q=0.1")).unwrap().media_type(),
+            "application/json"
+        );
+        assert_eq!(
+            negotiate(Some("application/problem+json")).unwrap().media_type(),
+            "application/json"
+        );
+        assert!(negotiate(Some("text/html, application/json;q=0")).is_none());
+    }
+
+    #[test]
+    fn round_trips_values() {
+        let value = vec![("a".to_owned(), 1u32)];
+        for codec in BUILTIN_CODECS {
+            let bytes = codec.encode(&value).unwrap();
+            assert_eq!(codec.decode::<Vec<(String, u32)>>(&bytes).unwrap(), value);
+        }
+    }
+}
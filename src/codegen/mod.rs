@@ -0,0 +1,529 @@
+//! Generates the API clients from the route table of a router, so the services which call it don't hand-write them.
+//!
+//! The [`rust_client`](./fn.rust_client.html) function emits the source of a Rust module with a `Client` struct, which
+//! has an async method for each method of each route. The path params are the string arguments of the methods, the
+//! optional params are `Option<&str>` ones and a `*` segment is the `rest` argument. The routes defined by a raw regex
+//! and the `/*` catch-all routes are left out.
+//!
+//! The client methods return the responses as they are, or fail with a `ClientError` if the status isn't successful.
+//! The name of a method is derived from the route method and path e.g. `get_users_by_id` for `GET /users/:id`, unless
+//! it's set by the [`RouterBuilder::route_types`](../struct.RouterBuilder.html#method.route_types) method, which sets
+//! the request and the response body types of the route too. The request bodies of the typed routes are serialized
+//! and their response bodies are deserialized as JSON.
+//!
+//! The generated module depends on the `hyper` crate with the `client`, `http1` and `tcp` features, and on the `serde`
+//! and the `serde_json` crates if any route is typed. The type names are emitted as they are, so they have to be in
+//! scope of the module, e.g. by writing a `use` item next to the generated code.
+//!
+//! # Examples
+//!
+//! A build script or a CLI flag of the service writes the client into the client crate:
+//!
+//! ```
+//! use routerify::Router;
+//! use routerify::codegen::{self, RouteTypes};
+//! use hyper::{Response, Body, Method};
+//! use std::convert::Infallible;
+//!
+//! let router: Router<Body, Infallible> = Router::builder()
+//!     .get("/users/:id", |_| async move { Ok(Response::new(Body::from("{}"))) })
+//!     .route_types(Method::GET, "/users/:id", RouteTypes::new().response("User"))
+//!     .post("/users/:id/avatar", |_| async move { Ok(Response::new(Body::empty())) })
+//!     .build()
+//!     .unwrap();
+//!
+//! let client = codegen::rust_client(&router);
+//! assert!(client.contains("pub async fn get_users_by_id(&self, id: &str) -> Result<User, ClientError>"));
+//! assert!(client.contains("pub async fn post_users_by_id_avatar(&self, id: &str, body: Body) -> Result<Response<Body>, ClientError>"));
+//! ```
+
+use crate::regex_generator::{split_path_parts, PathPart};
+use crate::route::Route;
+use crate::Router;
+use hyper::Method;
+use std::collections::HashSet;
+use std::fmt::Write;
+
+// The Rust keywords which can't be used as the identifiers.
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate", "do", "dyn", "else",
+    "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in", "let", "loop", "macro", "match", "mod",
+    "move", "mut", "override", "priv", "pub", "ref", "return", "self", "static", "struct", "super", "trait", "true",
+    "try", "type", "typeof", "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+/// The client method name and the body types of a route, which are set by the
+/// [`RouterBuilder::route_types`](../struct.RouterBuilder.html#method.route_types) method.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RouteTypes {
+    name: Option<String>,
+    request: Option<String>,
+    response: Option<String>,
+}
+
+impl RouteTypes {
+    /// Creates the types of an untyped route.
+    pub fn new() -> Self {
+        RouteTypes::default()
+    }
+
+    /// Sets the name of the client method.
+    pub fn name<N: Into<String>>(mut self, name: N) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Sets the request body type, e.g. `NewUser`, which is serialized as JSON.
+    pub fn request<T: Into<String>>(mut self, type_name: T) -> Self {
+        self.request = Some(type_name.into());
+        self
+    }
+
+    /// Sets the response body type, e.g. `User` or `Vec<User>`, which is deserialized from JSON.
+    pub fn response<T: Into<String>>(mut self, type_name: T) -> Self {
+        self.response = Some(type_name.into());
+        self
+    }
+
+    fn is_typed(&self) -> bool {
+        self.request.is_some() || self.response.is_some()
+    }
+}
+
+// A piece of the request path of a client method.
+enum Piece {
+    Literal(String),
+    Param {
+        ident: String,
+        optional: bool,
+        trailing_slash: bool,
+    },
+    Rest {
+        ident: String,
+    },
+}
+
+/// Generates the source of a Rust client module for the routes of the router.
+///
+/// Please refer to the [module](./index.html) documentation for more info.
+pub fn rust_client<B, E>(router: &Router<B, E>) -> String {
+    let base_path = router.base_path.as_deref().unwrap_or("");
+    let routes = router
+        .routes
+        .iter()
+        .filter(|route| route.raw_regex.is_none() && route.path != "/*")
+        .collect::<Vec<_>>();
+    let is_typed = routes
+        .iter()
+        .any(|route| route.route_types.iter().any(|(_, types)| types.is_typed()));
+
+    let mut out = String::new();
+    out.push_str("// Generated by routerify from the route table. Do not edit.\n\n");
+    out.push_str("use hyper::client::HttpConnector;\n");
+    out.push_str("use hyper::{Body, Method, Request, Response, StatusCode};\n");
+    out.push_str("use std::fmt;\n\n");
+    write_client_error(&mut out, is_typed);
+
+    out.push_str("/// The client of the API.\n");
+    out.push_str("#[derive(Debug, Clone)]\n");
+    out.push_str("pub struct Client {\n    base_url: String,\n    http: hyper::Client<HttpConnector>,\n}\n\n");
+    out.push_str("impl Client {\n");
+    out.push_str("    /// Creates a client of the API at the base URL, e.g. `http://localhost:3000`.\n");
+    out.push_str("    pub fn new<U: Into<String>>(base_url: U) -> Self {\n");
+    out.push_str("        Client::with_http_client(base_url, hyper::Client::new())\n    }\n\n");
+    out.push_str(
+        "    /// Creates a client of the API at the base URL, which sends the requests with the HTTP client.\n",
+    );
+    out.push_str(
+        "    pub fn with_http_client<U: Into<String>>(base_url: U, http: hyper::Client<HttpConnector>) -> Self {\n",
+    );
+    out.push_str("        let base_url = base_url.into().trim_end_matches('/').to_owned();\n");
+    out.push_str("        Client { base_url, http }\n    }\n");
+
+    let mut names = HashSet::new();
+    for route in routes {
+        for method in route.methods.iter() {
+            let types = route
+                .route_types
+                .iter()
+                .find(|(route_method, _)| route_method == method)
+                .map(|(_, types)| types.as_ref().clone())
+                .unwrap_or_default();
+            write_method(&mut out, &mut names, base_path, route, method, &types);
+        }
+    }
+
+    write_send(&mut out);
+    out.push_str("}\n");
+    write_helpers(&mut out, is_typed);
+    out
+}
+
+fn write_client_error(out: &mut String, is_typed: bool) {
+    out.push_str("/// The errors of the client.\n");
+    out.push_str("#[derive(Debug)]\n");
+    out.push_str("pub enum ClientError {\n");
+    out.push_str("    /// The request couldn't be built, e.g. the base URL is invalid.\n");
+    out.push_str("    Request(hyper::http::Error),\n");
+    out.push_str("    /// The request couldn't be sent or the response couldn't be read.\n");
+    out.push_str("    Http(hyper::Error),\n");
+    out.push_str("    /// The response status isn't successful. It has the response body.\n");
+    out.push_str("    Status(StatusCode, hyper::body::Bytes),\n");
+    if is_typed {
+        out.push_str(
+            "    /// The request body couldn't be serialized or the response body couldn't be deserialized.\n",
+        );
+        out.push_str("    Json(serde_json::Error),\n");
+    }
+    out.push_str("}\n\n");
+
+    out.push_str("impl fmt::Display for ClientError {\n");
+    out.push_str("    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {\n");
+    out.push_str("        match self {\n");
+    out.push_str("            ClientError::Request(err) => write!(f, \"Couldn't build the request: {}\", err),\n");
+    out.push_str("            ClientError::Http(err) => write!(f, \"Couldn't send the request: {}\", err),\n");
+    out.push_str(
+        "            ClientError::Status(status, _) => write!(f, \"Unsuccessful response status: {}\", status),\n",
+    );
+    if is_typed {
+        out.push_str("            ClientError::Json(err) => write!(f, \"Couldn't convert the JSON body: {}\", err),\n");
+    }
+    out.push_str("        }\n    }\n}\n\n");
+    out.push_str("impl std::error::Error for ClientError {}\n\n");
+}
+
+fn write_method(
+    out: &mut String,
+    names: &mut HashSet<String>,
+    base_path: &str,
+    route: &Route<impl Sized, impl Sized>,
+    method: &Method,
+    types: &RouteTypes,
+) {
+    let pieces = path_pieces(base_path, &route.path);
+
+    let mut args = vec!["&self".to_owned()];
+    for piece in pieces.iter() {
+        match piece {
+            Piece::Param {
+                ident, optional: true, ..
+            } => args.push(format!("{}: Option<&str>", ident)),
+            Piece::Param { ident, .. } | Piece::Rest { ident } => args.push(format!("{}: &str", ident)),
+            Piece::Literal(_) => {}
+        }
+    }
+    let has_body = matches!(*method, Method::POST | Method::PUT | Method::PATCH);
+    match types.request {
+        Some(ref request) => args.push(format!("body: &{}", request)),
+        None if has_body => args.push("body: Body".to_owned()),
+        None => {}
+    }
+    let output = match types.response {
+        Some(ref response) => response.clone(),
+        None => "Response<Body>".to_owned(),
+    };
+
+    let name = unique_name(
+        names,
+        types.name.clone().unwrap_or_else(|| method_name(method, &route.path)),
+    );
+    let _ = writeln!(
+        out,
+        "\n    /// `{} {}`",
+        method,
+        route.path.trim_end_matches('/').max("/")
+    );
+    let _ = writeln!(
+        out,
+        "    pub async fn {}({}) -> Result<{}, ClientError> {{",
+        name,
+        args.join(", "),
+        output
+    );
+    write_path(out, &pieces);
+
+    let method = match *method {
+        Method::GET
+        | Method::POST
+        | Method::PUT
+        | Method::DELETE
+        | Method::HEAD
+        | Method::OPTIONS
+        | Method::CONNECT
+        | Method::PATCH
+        | Method::TRACE => format!("Method::{}", method),
+        _ => format!("Method::from_bytes(b{:?}).unwrap()", method.as_str()),
+    };
+    let (body, content_type) = match types.request {
+        Some(_) => (
+            "Body::from(serde_json::to_vec(body).map_err(ClientError::Json)?)",
+            "Some(\"application/json\")",
+        ),
+        None if has_body => ("body", "None"),
+        None => ("Body::empty()", "None"),
+    };
+    match types.response {
+        Some(_) => {
+            let _ = writeln!(
+                out,
+                "        let res = self.send({}, path, {}, {}).await?;",
+                method, body, content_type
+            );
+            out.push_str("        read_json(res).await\n");
+        }
+        None => {
+            let _ = writeln!(
+                out,
+                "        self.send({}, path, {}, {}).await",
+                method, body, content_type
+            );
+        }
+    }
+    out.push_str("    }\n");
+}
+
+fn write_path(out: &mut String, pieces: &[Piece]) {
+    if let [Piece::Literal(literal)] = pieces {
+        let _ = writeln!(out, "        let path = {:?}.to_owned();", literal);
+        return;
+    }
+
+    out.push_str("        let mut path = String::new();\n");
+    for piece in pieces {
+        match piece {
+            Piece::Literal(literal) => {
+                let _ = writeln!(out, "        path.push_str({:?});", literal);
+            }
+            Piece::Param {
+                ident,
+                optional,
+                trailing_slash,
+            } => {
+                let slash = if *trailing_slash { " path.push('/');" } else { "" };
+                if *optional {
+                    let _ = writeln!(
+                        out,
+                        "        if let Some({0}) = {0} {{ path.push_str(&encode({0}, false));{1} }}",
+                        ident, slash
+                    );
+                } else {
+                    let _ = writeln!(out, "        path.push_str(&encode({}, false));{}", ident, slash);
+                }
+            }
+            Piece::Rest { ident } => {
+                let _ = writeln!(out, "        path.push_str(&encode({}, true));", ident);
+            }
+        }
+    }
+}
+
+fn write_send(out: &mut String) {
+    out.push_str(
+        r#"
+    async fn send(
+        &self,
+        method: Method,
+        path: String,
+        body: Body,
+        content_type: Option<&str>,
+    ) -> Result<Response<Body>, ClientError> {
+        let mut req = Request::builder().method(method).uri(format!("{}{}", self.base_url, path));
+        if let Some(content_type) = content_type {
+            req = req.header("content-type", content_type);
+        }
+        let req = req.body(body).map_err(ClientError::Request)?;
+
+        let res = self.http.request(req).await.map_err(ClientError::Http)?;
+        if !res.status().is_success() {
+            let status = res.status();
+            let body = hyper::body::to_bytes(res.into_body()).await.map_err(ClientError::Http)?;
+            return Err(ClientError::Status(status, body));
+        }
+        Ok(res)
+    }
+"#,
+    );
+}
+
+fn write_helpers(out: &mut String, is_typed: bool) {
+    out.push_str(
+        r#"
+// Percent-encodes a path param, keeping the slashes of a `*` segment.
+#[allow(dead_code)]
+fn encode(value: &str, keep_slash: bool) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for b in value.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => encoded.push(b as char),
+            b'/' if keep_slash => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    encoded
+}
+"#,
+    );
+    if is_typed {
+        out.push_str(
+            r#"
+#[allow(dead_code)]
+async fn read_json<T: serde::de::DeserializeOwned>(res: Response<Body>) -> Result<T, ClientError> {
+    let body = hyper::body::to_bytes(res.into_body()).await.map_err(ClientError::Http)?;
+    serde_json::from_slice(&body).map_err(ClientError::Json)
+}
+"#,
+        );
+    }
+}
+
+// Splits the request path of a route into the literal and the param pieces. An omitted optional param takes its
+// trailing slash with it, and the trailing slash of the route path is left out.
+fn path_pieces(base_path: &str, route_path: &str) -> Vec<Piece> {
+    let mut pieces = vec![Piece::Literal(base_path.to_owned())];
+    for part in split_path_parts(route_path) {
+        match part {
+            PathPart::Literal(literal) => {
+                let literal = match pieces.last_mut() {
+                    Some(Piece::Param {
+                        optional: true,
+                        trailing_slash,
+                        ..
+                    }) if literal.starts_with('/') => {
+                        *trailing_slash = true;
+                        &literal[1..]
+                    }
+                    _ => literal,
+                };
+                match pieces.last_mut() {
+                    Some(Piece::Literal(prev)) => prev.push_str(literal),
+                    _ => pieces.push(Piece::Literal(literal.to_owned())),
+                }
+            }
+            PathPart::Param { name, optional } => pieces.push(Piece::Param {
+                ident: ident(name),
+                optional,
+                trailing_slash: false,
+            }),
+            PathPart::Wildcard => pieces.push(Piece::Rest {
+                ident: "rest".to_owned(),
+            }),
+        }
+    }
+
+    if let Some(Piece::Literal(literal)) = pieces.last_mut() {
+        let len = literal.trim_end_matches('/').len();
+        literal.truncate(len);
+        if literal.is_empty() {
+            pieces.pop();
+        }
+    }
+    match pieces.last_mut() {
+        Some(Piece::Param { trailing_slash, .. }) => *trailing_slash = false,
+        None => pieces.push(Piece::Literal("/".to_owned())),
+        _ => {}
+    }
+    pieces
+}
+
+// Derives the name of a client method e.g. `get_users_by_id` for `GET /users/:id`.
+fn method_name(method: &Method, route_path: &str) -> String {
+    let mut name = method.as_str().to_ascii_lowercase();
+    for part in split_path_parts(route_path) {
+        match part {
+            PathPart::Literal(literal) => {
+                for segment in literal.split(['/', '.']).filter(|segment| !segment.is_empty()) {
+                    name.push('_');
+                    name.push_str(segment);
+                }
+            }
+            PathPart::Param { name: param, .. } => {
+                name.push_str("_by_");
+                name.push_str(param);
+            }
+            PathPart::Wildcard => name.push_str("_rest"),
+        }
+    }
+    if !name.contains('_') {
+        name.push_str("_root");
+    }
+    ident(&name)
+}
+
+fn unique_name(names: &mut HashSet<String>, name: String) -> String {
+    let mut unique = name.clone();
+    let mut idx = 2;
+    while !names.insert(unique.clone()) {
+        unique = format!("{}_{}", name, idx);
+        idx += 1;
+    }
+    unique
+}
+
+// Converts a name into a snake case identifier, which isn't a keyword nor the `body` argument.
+fn ident(name: &str) -> String {
+    let mut ident = String::with_capacity(name.len());
+    for c in name.chars() {
+        match c {
+            'a'..='z' | '0'..='9' => ident.push(c),
+            'A'..='Z' => ident.push(c.to_ascii_lowercase()),
+            _ if !ident.ends_with('_') => ident.push('_'),
+            _ => {}
+        }
+    }
+    if ident.is_empty() || ident.starts_with(|c: char| c.is_ascii_digit()) {
+        ident.insert(0, '_');
+    }
+    if KEYWORDS.contains(&ident.as_str()) || ident == "body" || ident == "path" {
+        ident.push('_');
+    }
+    ident
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::{Body, Response};
+    use std::convert::Infallible;
+
+    #[test]
+    fn generates_rust_clients() {
+        let router: Router<Body, Infallible> = Router::builder()
+            .base_path("/api")
+            .get("/", |_| async move { Ok(Response::new(Body::empty())) })
+            .get(
+                "/posts/:page?/comments",
+                |_| async move { Ok(Response::new(Body::empty())) },
+            )
+            .get("/files/*", |_| async move { Ok(Response::new(Body::empty())) })
+            .post("/users/:type", |_| async move { Ok(Response::new(Body::empty())) })
+            .route_types(
+                Method::POST,
+                "/users/:type",
+                RouteTypes::new()
+                    .name("create_user")
+                    .request("NewUser")
+                    .response("User"),
+            )
+            .get_regex(
+                r"/v(?P<version>\d+)/",
+                |_| async move { Ok(Response::new(Body::empty())) },
+            )
+            .any(|_| async move { Ok(Response::new(Body::empty())) })
+            .build()
+            .unwrap();
+        let client = rust_client(&router);
+
+        assert!(client.contains("pub async fn get_root(&self) -> Result<Response<Body>, ClientError> {\n        let path = \"/api\".to_owned();"));
+        assert!(client.contains(
+            "pub async fn get_posts_by_page_comments(&self, page: Option<&str>) -> Result<Response<Body>, ClientError> {\n        let mut path = String::new();\n        path.push_str(\"/api/posts/\");\n        if let Some(page) = page { path.push_str(&encode(page, false)); path.push('/'); }\n        path.push_str(\"comments\");\n"
+        ));
+        assert!(client.contains("pub async fn get_files_rest(&self, rest: &str)"));
+        assert!(client.contains("path.push_str(&encode(type_, false));\n        let res = self.send(Method::POST"));
+        assert!(client.contains("path.push_str(&encode(rest, true));"));
+        assert!(client
+            .contains("pub async fn create_user(&self, type_: &str, body: &NewUser) -> Result<User, ClientError>"));
+        assert!(client.contains("Json(serde_json::Error)"));
+        assert!(!client.contains("version"));
+        assert_eq!(method_name(&Method::GET, "/users/:id/"), "get_users_by_id");
+    }
+}
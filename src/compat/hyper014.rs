@@ -0,0 +1,193 @@
+//! The conversions between the hyper 0.14 types a routerify application is built on and the hyper 1.x ones, e.g. the
+//! `Incoming` request bodies and the `Full` response bodies, so the routes can be moved to a hyper 1.x service one by
+//! one.
+//!
+//! The [`handler`](./fn.handler.html) function mounts a hyper 1.x service under a route of the router, and the
+//! [`Hyper1Service`](../../struct.Hyper1Service.html) serves the router on the hyper 1.x connections. The request and
+//! the response functions convert the messages in either direction.
+//!
+//! The method, the URI, the version, the status and the headers are converted. The extensions aren't carried over and
+//! the trailers of the hyper 1.x request bodies are dropped.
+//!
+//! # Examples
+//!
+//! ```
+//! use routerify::{RouteError, Router};
+//! use routerify::compat::hyper014;
+//! use http_body_util::Full;
+//! use hyper::{Body, Response};
+//! use std::convert::Infallible;
+//!
+//! # fn run() -> Router<Body, RouteError> {
+//! // The orders are already served by a hyper 1.x service.
+//! let orders = hyper1::service::service_fn(|_req| async move {
+//!     Ok::<_, Infallible>(http1::Response::new(Full::new(hyper::body::Bytes::from("Orders"))))
+//! });
+//!
+//! let router = Router::builder()
+//!     .get("/", |_| async move { Ok(Response::new(Body::from("Home page"))) })
+//!     .any_method("/orders/*", hyper014::handler(orders))
+//!     .build()
+//!     .unwrap();
+//! # router
+//! # }
+//! # run();
+//! ```
+
+use crate::service::{convert_headers, from_hyper0_response, into_hyper0_request, DataStream};
+use crate::{Error, Hyper1Body};
+use futures_core::future::BoxFuture;
+use hyper::body::{Bytes, HttpBody};
+use std::sync::Arc;
+
+/// Converts a hyper 1.x request, e.g. with an `Incoming` body, into a hyper 0.14 one.
+pub fn request_from_hyper1<In>(req: http1::Request<In>) -> crate::Result<hyper::Request<hyper::Body>>
+where
+    In: http_body1::Body<Data = Bytes> + Send + 'static,
+    In::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    into_hyper0_request(req)
+}
+
+/// Converts a hyper 0.14 request into a hyper 1.x one, whose body streams the hyper 0.14 body.
+pub fn request_into_hyper1<B: HttpBody>(req: hyper::Request<B>) -> crate::Result<http1::Request<Hyper1Body<B>>> {
+    let (parts, body) = req.into_parts();
+
+    let mut builder = http1::Request::builder()
+        .method(parts.method.as_str())
+        .uri(parts.uri.to_string())
+        .version(match parts.version {
+            hyper::Version::HTTP_09 => http1::Version::HTTP_09,
+            hyper::Version::HTTP_10 => http1::Version::HTTP_10,
+            hyper::Version::HTTP_2 => http1::Version::HTTP_2,
+            hyper::Version::HTTP_3 => http1::Version::HTTP_3,
+            _ => http1::Version::HTTP_11,
+        });
+    if let Some(headers) = builder.headers_mut() {
+        *headers = convert_headers(&parts.headers);
+    }
+
+    builder
+        .body(Hyper1Body::new(body))
+        .map_err(|e| Error::new(format!("Couldn't convert the request into a hyper 1.x one: {}", e)).into())
+}
+
+/// Converts a hyper 1.x response, e.g. with a `Full` body, into a hyper 0.14 one.
+pub fn response_from_hyper1<In>(resp: http1::Response<In>) -> crate::Result<hyper::Response<hyper::Body>>
+where
+    In: http_body1::Body<Data = Bytes> + Send + 'static,
+    In::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    let (parts, body) = resp.into_parts();
+
+    let mut builder = hyper::Response::builder()
+        .status(parts.status.as_u16())
+        .version(match parts.version {
+            http1::Version::HTTP_09 => hyper::Version::HTTP_09,
+            http1::Version::HTTP_10 => hyper::Version::HTTP_10,
+            http1::Version::HTTP_2 => hyper::Version::HTTP_2,
+            http1::Version::HTTP_3 => hyper::Version::HTTP_3,
+            _ => hyper::Version::HTTP_11,
+        });
+    for (name, value) in parts.headers.iter() {
+        builder = builder.header(name.as_str(), value.as_bytes());
+    }
+
+    builder
+        .body(hyper::Body::wrap_stream(DataStream { body: Box::pin(body) }))
+        .map_err(|e| Error::new(format!("Couldn't convert the hyper 1.x response: {}", e)).into())
+}
+
+/// Converts a hyper 0.14 response into a hyper 1.x one, whose body streams the hyper 0.14 body.
+pub fn response_into_hyper1<B>(resp: hyper::Response<B>) -> crate::Result<http1::Response<Hyper1Body<B>>> {
+    from_hyper0_response(resp)
+}
+
+/// Creates a route handler which answers the requests by a hyper 1.x service, e.g. the part of the application which
+/// is already moved to hyper 1.x. The path of the request isn't stripped, so the service sees the full request URI.
+///
+/// The errors of the service and of the conversions are passed to the error handler of the router.
+pub fn handler<S, Out>(
+    service: S,
+) -> impl Fn(hyper::Request<hyper::Body>) -> BoxFuture<'static, crate::Result<hyper::Response<hyper::Body>>>
+       + Send
+       + Sync
+       + 'static
+where
+    S: hyper1::service::Service<http1::Request<Hyper1Body<hyper::Body>>, Response = http1::Response<Out>>
+        + Send
+        + Sync
+        + 'static,
+    S::Future: Send + 'static,
+    S::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    Out: http_body1::Body<Data = Bytes> + Send + 'static,
+    Out::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    let service = Arc::new(service);
+    move |req| {
+        let fut = request_into_hyper1(req).map(|req| service.call(req));
+        Box::pin(async move {
+            let resp = fut?.await.map_err(Into::into)?;
+            response_from_hyper1(resp)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_body_util::{BodyExt, Full};
+    use std::convert::Infallible;
+
+    #[tokio::test]
+    async fn converts_requests_and_responses() {
+        let req = hyper::Request::builder()
+            .method("PUT")
+            .uri("/orders/1?draft=true")
+            .header("x-header", "value")
+            .body(hyper::Body::from("Order"))
+            .unwrap();
+        let req = request_into_hyper1(req).unwrap();
+        assert_eq!(req.method(), http1::Method::PUT);
+        assert_eq!(req.uri(), "/orders/1?draft=true");
+        assert_eq!(req.headers()["x-header"], "value");
+
+        let req = request_from_hyper1(req).unwrap();
+        assert_eq!(req.headers()["x-header"], "value");
+        assert_eq!(hyper::body::to_bytes(req.into_body()).await.unwrap(), "Order");
+
+        let resp = http1::Response::builder()
+            .status(201)
+            .header("x-header", "value")
+            .body(Full::new(Bytes::from("Created")))
+            .unwrap();
+        let resp = response_from_hyper1(resp).unwrap();
+        assert_eq!(resp.status(), hyper::StatusCode::CREATED);
+        assert_eq!(resp.headers()["x-header"], "value");
+
+        let resp = response_into_hyper1(resp).unwrap();
+        assert_eq!(resp.status(), http1::StatusCode::CREATED);
+        assert_eq!(resp.into_body().collect().await.unwrap().to_bytes(), "Created");
+    }
+
+    #[tokio::test]
+    async fn answers_by_a_hyper1_service() {
+        let service = hyper1::service::service_fn(|req: http1::Request<Hyper1Body<hyper::Body>>| async move {
+            let path = req.uri().path().to_owned();
+            let body = req.into_body().collect().await.unwrap().to_bytes();
+            let resp = http1::Response::new(Full::new(Bytes::from(format!("{} {:?}", path, body))));
+            Ok::<_, Infallible>(resp)
+        });
+        let handler = handler(service);
+
+        let req = hyper::Request::builder()
+            .uri("/orders/1")
+            .body(hyper::Body::from("Order"))
+            .unwrap();
+        let resp = handler(req).await.unwrap();
+        assert_eq!(
+            hyper::body::to_bytes(resp.into_body()).await.unwrap(),
+            "/orders/1 b\"Order\""
+        );
+    }
+}
@@ -0,0 +1,6 @@
+//! The compatibility shims between the hyper versions, so an application can move to a new hyper version one part at a
+//! time within one process. It requires the `hyper1` feature.
+//!
+//! Please refer to the [`hyper014`](./hyper014/index.html) module for the shims of the hyper 0.14 applications.
+
+pub mod hyper014;
@@ -0,0 +1,246 @@
+//! Loads the routes from a TOML or a YAML manifest, and binds them to the named handlers of a
+//! [`HandlerRegistry`](./struct.HandlerRegistry.html). It requires the `config` feature.
+//!
+//! The handlers and the middlewares are compiled into the binary and registered by name, while the manifest decides at
+//! which paths and methods they are served, so the routes can be changed without recompiling the app. A route of the
+//! manifest has:
+//!
+//! * `path`: The route path e.g. `/users/:id`.
+//! * `methods`: The methods of the route. Defaults to `["GET"]`.
+//! * `handler`: The name of the registered handler.
+//! * `middleware`: The names of the registered middlewares which are added at the path of the route.
+//! * `skip`: The names of the [named](../enum.Middleware.html#method.named) middlewares of the router which the route
+//!   skips.
+//!
+//! The routes are added by the [`RouterBuilder::routes_from`](../struct.RouterBuilder.html#method.routes_from) method,
+//! which fails the build if a route refers to a handler or a middleware which isn't registered.
+//!
+//! # Examples
+//!
+//! ```
+//! use routerify::{Middleware, Router, RouteError};
+//! use routerify::config::{HandlerRegistry, ManifestFormat, RouteManifest};
+//! use hyper::{Response, Body};
+//!
+//! # fn run() -> Router<Body, RouteError> {
+//! let manifest = RouteManifest::from_str(
+//!     r#"
+//!     [[routes]]
+//!     path = "/users/:id"
+//!     handler = "get_user"
+//!     middleware = ["audit"]
+//!
+//!     [[routes]]
+//!     path = "/users"
+//!     methods = ["POST"]
+//!     handler = "create_user"
+//!     "#,
+//!     ManifestFormat::Toml,
+//! )
+//! .unwrap();
+//!
+//! let registry = HandlerRegistry::new()
+//!     .handler("get_user", |_| async move { Ok(Response::new(Body::from("User"))) })
+//!     .handler("create_user", |_| async move { Ok(Response::new(Body::from("Created"))) })
+//!     .middleware("audit", |path| {
+//!         Middleware::pre_with_path(path, |req| async move {
+//!             println!("{} {}", req.method(), req.uri());
+//!             Ok(req)
+//!         })
+//!     });
+//!
+//! let router = Router::builder().routes_from(&manifest, &registry).build().unwrap();
+//! # router
+//! # }
+//! # run();
+//! ```
+
+use crate::middleware::Middleware;
+use crate::Error;
+use hyper::{Method, Request, Response};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Formatter};
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+
+type HandlerFuture<B, E> = Pin<Box<dyn Future<Output = Result<Response<B>, E>> + Send>>;
+pub(crate) type Handler<B, E> = Arc<dyn Fn(Request<hyper::Body>) -> HandlerFuture<B, E> + Send + Sync + 'static>;
+pub(crate) type MiddlewareFactory<B, E> = Box<dyn Fn(&str) -> crate::Result<Middleware<B, E>> + Send + Sync + 'static>;
+
+/// The format of a route manifest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManifestFormat {
+    /// A TOML document with a `[[routes]]` array of tables.
+    Toml,
+    /// A YAML document with a `routes` sequence.
+    Yaml,
+}
+
+/// A route manifest, which is bound to the handlers by the
+/// [`RouterBuilder::routes_from`](../struct.RouterBuilder.html#method.routes_from) method.
+///
+/// Please refer to the [module](./index.html) documentation for more info.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RouteManifest {
+    /// The routes in their matching order.
+    #[serde(default)]
+    pub routes: Vec<RouteConfig>,
+}
+
+/// A route of a [`RouteManifest`](./struct.RouteManifest.html).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RouteConfig {
+    /// The route path.
+    pub path: String,
+    /// The methods of the route.
+    #[serde(default = "default_methods")]
+    pub methods: Vec<String>,
+    /// The name of the handler.
+    pub handler: String,
+    /// The names of the middlewares which are added at the path of the route.
+    #[serde(default)]
+    pub middleware: Vec<String>,
+    /// The names of the middlewares of the router which the route skips.
+    #[serde(default)]
+    pub skip: Vec<String>,
+}
+
+fn default_methods() -> Vec<String> {
+    vec!["GET".to_owned()]
+}
+
+impl RouteManifest {
+    /// Parses a manifest in the specified format.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(src: &str, format: ManifestFormat) -> crate::Result<RouteManifest> {
+        let manifest = match format {
+            ManifestFormat::Toml => toml::from_str(src).map_err(|err| err.to_string()),
+            ManifestFormat::Yaml => serde_yaml::from_str(src).map_err(|err| err.to_string()),
+        };
+        manifest.map_err(|err| Error::new(format!("Couldn't parse the route manifest: {}", err)).into())
+    }
+
+    /// Reads and parses a manifest file. The format is picked by the file extension, which is `toml`, `yaml` or `yml`.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> crate::Result<RouteManifest> {
+        let path = path.as_ref();
+        let format = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => ManifestFormat::Toml,
+            Some("yaml") | Some("yml") => ManifestFormat::Yaml,
+            _ => {
+                return Err(Error::new(format!(
+                    "Couldn't detect the format of the route manifest {:?} by its extension",
+                    path
+                ))
+                .into())
+            }
+        };
+
+        let src = std::fs::read_to_string(path)
+            .map_err(|err| Error::new(format!("Couldn't read the route manifest {:?}: {}", path, err)))?;
+        RouteManifest::from_str(&src, format)
+    }
+}
+
+impl RouteConfig {
+    pub(crate) fn parse_methods(&self) -> crate::Result<Vec<Method>> {
+        self.methods
+            .iter()
+            .map(|method| {
+                Method::from_bytes(method.to_ascii_uppercase().as_bytes())
+                    .map_err(|_| Error::new(format!("Invalid method {:?} of the route {:?}", method, self.path)).into())
+            })
+            .collect()
+    }
+}
+
+/// The named handlers and middlewares which the routes of a [`RouteManifest`](./struct.RouteManifest.html) refer to.
+///
+/// Please refer to the [module](./index.html) documentation for more info.
+pub struct HandlerRegistry<B, E> {
+    pub(crate) handlers: HashMap<String, Handler<B, E>>,
+    pub(crate) middlewares: HashMap<String, MiddlewareFactory<B, E>>,
+}
+
+impl<B: 'static, E: 'static> HandlerRegistry<B, E> {
+    /// Creates a new empty registry.
+    pub fn new() -> Self {
+        HandlerRegistry::default()
+    }
+
+    /// Registers a handler under the name. A handler can be bound to multiple routes.
+    pub fn handler<N, H, R>(mut self, name: N, handler: H) -> Self
+    where
+        N: Into<String>,
+        H: Fn(Request<hyper::Body>) -> R + Send + Sync + 'static,
+        R: Future<Output = Result<Response<B>, E>> + Send + 'static,
+    {
+        let handler: Handler<B, E> = Arc::new(move |req| Box::pin(handler(req)));
+        self.handlers.insert(name.into(), handler);
+        self
+    }
+
+    /// Registers a middleware under the name. The factory creates a middleware at the path of each route which uses it,
+    /// e.g. by the [`Middleware::pre_with_path`](../enum.Middleware.html#method.pre_with_path) method.
+    pub fn middleware<N, F>(mut self, name: N, factory: F) -> Self
+    where
+        N: Into<String>,
+        F: Fn(&str) -> crate::Result<Middleware<B, E>> + Send + Sync + 'static,
+    {
+        self.middlewares.insert(name.into(), Box::new(factory));
+        self
+    }
+}
+
+impl<B, E> Default for HandlerRegistry<B, E> {
+    fn default() -> Self {
+        HandlerRegistry {
+            handlers: HashMap::new(),
+            middlewares: HashMap::new(),
+        }
+    }
+}
+
+impl<B, E> Debug for HandlerRegistry<B, E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut handlers = self.handlers.keys().collect::<Vec<_>>();
+        let mut middlewares = self.middlewares.keys().collect::<Vec<_>>();
+        handlers.sort();
+        middlewares.sort();
+        write!(f, "{{ handlers: {:?}, middlewares: {:?} }}", handlers, middlewares)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_toml_and_yaml_manifests() {
+        let toml = RouteManifest::from_str(
+            "[[routes]]\npath = \"/users/:id\"\nhandler = \"get_user\"\nskip = [\"auth\"]\n",
+            ManifestFormat::Toml,
+        )
+        .unwrap();
+        let yaml = RouteManifest::from_str(
+            "routes:\n  - path: /users/:id\n    handler: get_user\n    skip: [auth]\n",
+            ManifestFormat::Yaml,
+        )
+        .unwrap();
+
+        for manifest in [toml, yaml] {
+            let route = &manifest.routes[0];
+            assert_eq!(route.path, "/users/:id");
+            assert_eq!(route.parse_methods().unwrap(), vec![Method::GET]);
+            assert_eq!(route.skip, vec!["auth".to_owned()]);
+        }
+
+        let err =
+            RouteManifest::from_str("routes:\n  - path: /\n    handlr: home\n", ManifestFormat::Yaml).unwrap_err();
+        assert!(err.to_string().contains("Couldn't parse the route manifest"));
+    }
+}
@@ -11,3 +11,6 @@ pub(crate) const ALL_POSSIBLE_HTTP_METHODS: [Method; 9] = [
     Method::OPTIONS,
     Method::TRACE,
 ];
+
+#[cfg(feature = "protobuf")]
+pub(crate) const PROTOBUF_CONTENT_TYPE: &str = "application/x-protobuf";
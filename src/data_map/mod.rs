@@ -1,6 +1,6 @@
 pub(crate) use map::DataMap;
 pub(crate) use scoped::ScopedDataMap;
-pub(crate) use shared::SharedDataMap;
+pub(crate) use shared::{SharedConfigMaps, SharedDataMap};
 
 mod map;
 mod scoped;
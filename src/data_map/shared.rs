@@ -11,3 +11,14 @@ impl SharedDataMap {
         SharedDataMap { inner: data_map }
     }
 }
+
+// The scoped configs of a request, from the innermost scope to the outermost one. It's kept apart from the data maps
+// in the request extensions.
+#[derive(Debug, Clone)]
+pub(crate) struct SharedConfigMaps(pub(crate) Arc<[SharedDataMap]>);
+
+impl SharedConfigMaps {
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.0.iter().find_map(|config_map| config_map.inner.get::<T>())
+    }
+}
@@ -1,5 +1,12 @@
+use crate::body::BodyError;
+use crate::types::ProblemDetails;
+use hyper::StatusCode;
 use std::error::Error as StdError;
 use std::fmt::{self, Debug, Display, Formatter};
+use std::io;
+use std::num::{ParseFloatError, ParseIntError};
+use std::str::{ParseBoolError, Utf8Error};
+use std::string::FromUtf8Error;
 
 /// The error type used by the error handlers.
 pub type RouteError = Box<dyn StdError + Send + Sync + 'static>;
@@ -39,3 +46,201 @@ impl std::error::Error for Error {
         self.msg.as_str()
     }
 }
+
+/// An error which carries the HTTP status code of the response that should be sent for it.
+///
+/// The default error handler responds with this status code instead of `500 Internal Server Error`.
+/// Custom error handlers can downcast the [`RouteError`](./type.RouteError.html) to this type to do the same.
+pub struct HttpError {
+    status: StatusCode,
+    msg: String,
+    problem: Option<Box<ProblemDetails>>,
+}
+
+impl HttpError {
+    /// Creates a new error instance with the specified status code and message.
+    pub fn new<M: Into<String>>(status: StatusCode, msg: M) -> Self {
+        HttpError {
+            status,
+            msg: msg.into(),
+            problem: None,
+        }
+    }
+
+    /// Returns the status code of the response that should be sent for this error.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Returns the error message.
+    pub fn message(&self) -> &str {
+        self.msg.as_str()
+    }
+
+    /// Returns the problem details the error was created from.
+    pub fn problem_details(&self) -> Option<&ProblemDetails> {
+        self.problem.as_deref()
+    }
+}
+
+impl From<ProblemDetails> for HttpError {
+    /// Creates an error with the status code of the problem, whose message is the detail or the title of the problem.
+    fn from(problem: ProblemDetails) -> Self {
+        HttpError {
+            status: problem.status(),
+            msg: problem.detail().unwrap_or_else(|| problem.title()).to_owned(),
+            problem: Some(Box::new(problem)),
+        }
+    }
+}
+
+impl Display for HttpError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.msg)
+    }
+}
+
+impl Debug for HttpError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "routerify::HttpError({}): {}", self.status, self.msg)
+    }
+}
+
+impl std::error::Error for HttpError {}
+
+// Wraps the errors of the error reporting crates e.g. `anyhow` to keep their detailed reports with the
+// backtraces which are lost when they are converted into a `RouteError`.
+#[cfg_attr(not(any(feature = "anyhow", feature = "eyre")), allow(dead_code))]
+pub(crate) struct ReportError {
+    pub(crate) inner: RouteError,
+    pub(crate) report: String,
+}
+
+impl Display for ReportError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(&self.inner, f)
+    }
+}
+
+impl Debug for ReportError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&self.inner, f)
+    }
+}
+
+impl StdError for ReportError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        self.inner.source()
+    }
+}
+
+/// Converts a handler or middleware error into a `RouteError`.
+pub(crate) fn into_route_error<E: Into<RouteError> + 'static>(err: E) -> RouteError {
+    #[cfg(any(feature = "anyhow", feature = "eyre"))]
+    let mut err = Some(err);
+
+    #[cfg(feature = "anyhow")]
+    {
+        if let Some(report) = (&mut err as &mut dyn std::any::Any).downcast_mut::<Option<anyhow::Error>>() {
+            let report = report.take().expect("The error is taken only once");
+            return Box::new(ReportError {
+                report: format!("{:?}", report),
+                inner: report.into(),
+            });
+        }
+    }
+
+    #[cfg(feature = "eyre")]
+    {
+        if let Some(report) = (&mut err as &mut dyn std::any::Any).downcast_mut::<Option<eyre::Report>>() {
+            let report = report.take().expect("The error is taken only once");
+            return Box::new(ReportError {
+                report: format!("{:?}", report),
+                inner: report.into(),
+            });
+        }
+    }
+
+    #[cfg(any(feature = "anyhow", feature = "eyre"))]
+    let err = err.expect("The error is taken only once");
+
+    err.into()
+}
+
+/// The class of an error which is used to pick the status code of the error response.
+///
+/// It's returned by the [`RouteErrorExt::classify`](./ext/trait.RouteErrorExt.html#tymethod.classify) method.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorClass {
+    /// An [`HttpError`](./struct.HttpError.html) with its status code.
+    Http(StatusCode),
+    /// An operation timed out.
+    Timeout,
+    /// The request body exceeded a size limit.
+    BodyTooLarge,
+    /// The request data could not be parsed or deserialized.
+    Deserialize,
+    /// Any other error.
+    Other,
+}
+
+impl ErrorClass {
+    /// Returns the status code of the response that should be sent for this class of errors.
+    ///
+    /// | Class            | Status                      |
+    /// |------------------|-----------------------------|
+    /// | `Http(status)`   | `status`                    |
+    /// | `Timeout`        | `504 Gateway Timeout`       |
+    /// | `BodyTooLarge`   | `413 Payload Too Large`     |
+    /// | `Deserialize`    | `400 Bad Request`           |
+    /// | `Other`          | `500 Internal Server Error` |
+    pub fn status(&self) -> StatusCode {
+        match *self {
+            ErrorClass::Http(status) => status,
+            ErrorClass::Timeout => StatusCode::GATEWAY_TIMEOUT,
+            ErrorClass::BodyTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorClass::Deserialize => StatusCode::BAD_REQUEST,
+            ErrorClass::Other => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub(crate) fn of(err: &(dyn StdError + 'static)) -> Option<ErrorClass> {
+        if let Some(err) = err.downcast_ref::<HttpError>() {
+            return Some(ErrorClass::Http(err.status()));
+        }
+
+        match err.downcast_ref::<BodyError>() {
+            Some(BodyError::TooLarge { .. }) => return Some(ErrorClass::BodyTooLarge),
+            Some(err @ BodyError::Timeout) | Some(err @ BodyError::Hyper(_)) => {
+                return Some(ErrorClass::Http(err.status()))
+            }
+            _ => {}
+        }
+
+        if let Some(err) = err.downcast_ref::<hyper::Error>() {
+            if err.is_timeout() {
+                return Some(ErrorClass::Timeout);
+            }
+        }
+
+        if let Some(err) = err.downcast_ref::<io::Error>() {
+            match err.kind() {
+                io::ErrorKind::TimedOut => return Some(ErrorClass::Timeout),
+                io::ErrorKind::InvalidData => return Some(ErrorClass::Deserialize),
+                _ => {}
+            }
+        }
+
+        if err.is::<Utf8Error>()
+            || err.is::<FromUtf8Error>()
+            || err.is::<ParseIntError>()
+            || err.is::<ParseFloatError>()
+            || err.is::<ParseBoolError>()
+        {
+            return Some(ErrorClass::Deserialize);
+        }
+
+        None
+    }
+}
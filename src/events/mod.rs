@@ -0,0 +1,204 @@
+//! The structured events of a router, so the dashboards and the metrics can be built without a middleware on every
+//! route.
+//!
+//! An [`Observer`](./struct.Observer.html) is added to a router by the
+//! [`RouterBuilder::observer`](../struct.RouterBuilder.html#method.observer) method. It either publishes the
+//! [`RouterEvent`](./enum.RouterEvent.html)s on a broadcast channel, which drops the oldest events of a lagging
+//! subscriber, or calls a function with each event on the request task. The events aren't built while a channel has
+//! no subscriber.
+//!
+//! Once an observer is added, a panic of a middleware or a route handler is caught: it's reported as a
+//! [`PanicCaught`](./enum.RouterEvent.html#variant.PanicCaught) event and answered by the error handler as an internal
+//! error, instead of closing the connection. The panics can be caught without an observer by the
+//! [`RouterBuilder::catch_panics`](../struct.RouterBuilder.html#method.catch_panics) method.
+//!
+//! # Examples
+//!
+//! ```
+//! use routerify::Router;
+//! use routerify::events::{Observer, RouterEvent};
+//! use hyper::{Response, Body};
+//!
+//! # fn run() -> Router<Body, routerify::Error> {
+//! let observer = Observer::channel(1024);
+//! let mut events = observer.subscribe().unwrap();
+//! tokio::spawn(async move {
+//!     while let Ok(event) = events.recv().await {
+//!         if let RouterEvent::ResponseSent { status, elapsed, .. } = event {
+//!             println!("{} in {:?}", status, elapsed);
+//!         }
+//!     }
+//! });
+//!
+//! let router = Router::builder()
+//!     .observer(observer)
+//!     .get("/", |_| async move { Ok(Response::new(Body::from("Home"))) })
+//!     .build()
+//!     .unwrap();
+//! # router
+//! # }
+//! # let rt = tokio::runtime::Runtime::new().unwrap();
+//! # let _guard = rt.enter();
+//! # run();
+//! ```
+
+use hyper::{Method, StatusCode};
+use std::any::Any;
+use std::fmt::{self, Debug, Formatter};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+type Callback = Arc<dyn Fn(&RouterEvent) + Send + Sync + 'static>;
+
+/// An event of a router. The `path` is the path of the request URI, before the base path is stripped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RouterEvent {
+    /// A request is received.
+    RequestStarted {
+        method: Method,
+        path: String,
+        remote_addr: SocketAddr,
+    },
+    /// A route is matched by the path of a request, including the default `404 Not Found` route.
+    RouteMatched {
+        method: Method,
+        path: String,
+        route_path: String,
+    },
+    /// A pre or a post middleware failed, so the error handler answered the request.
+    MiddlewareFailed {
+        method: Method,
+        path: String,
+        stage: MiddlewareStage,
+        error: String,
+    },
+    /// The response head is ready to be sent. The body may still be streamed afterwards.
+    ResponseSent {
+        method: Method,
+        path: String,
+        status: StatusCode,
+        elapsed: Duration,
+    },
+    /// A middleware or a route handler panicked, so the error handler answered the request.
+    PanicCaught {
+        method: Method,
+        path: String,
+        message: String,
+    },
+}
+
+/// The stage of a failed middleware.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MiddlewareStage {
+    /// A pre middleware.
+    Pre,
+    /// A post middleware.
+    Post,
+}
+
+#[derive(Clone)]
+enum Sink {
+    Channel(broadcast::Sender<RouterEvent>),
+    Callback(Callback),
+}
+
+/// The receiver of the events of a router.
+///
+/// It's cheap to clone and the clones share the channel. Please refer to the [module](./index.html) documentation for
+/// more info.
+#[derive(Clone)]
+pub struct Observer {
+    sink: Sink,
+}
+
+impl Observer {
+    /// Creates an observer which publishes the events on a broadcast channel of the capacity. The subscribers are added
+    /// with the [`subscribe`](#method.subscribe) method.
+    pub fn channel(capacity: usize) -> Observer {
+        Observer {
+            sink: Sink::Channel(broadcast::channel(capacity.max(1)).0),
+        }
+    }
+
+    /// Creates an observer which calls the function with each event. The function runs on the request task, so it
+    /// should be quick.
+    pub fn from_fn<F>(callback: F) -> Observer
+    where
+        F: Fn(&RouterEvent) + Send + Sync + 'static,
+    {
+        Observer {
+            sink: Sink::Callback(Arc::new(callback)),
+        }
+    }
+
+    /// Adds a subscriber of the events. It returns `None` for an observer created by the
+    /// [`from_fn`](#method.from_fn) method.
+    pub fn subscribe(&self) -> Option<broadcast::Receiver<RouterEvent>> {
+        match self.sink {
+            Sink::Channel(ref tx) => Some(tx.subscribe()),
+            Sink::Callback(_) => None,
+        }
+    }
+
+    // The event is only built if someone receives it.
+    pub(crate) fn emit<F: FnOnce() -> RouterEvent>(&self, event: F) {
+        match self.sink {
+            Sink::Channel(ref tx) => {
+                if tx.receiver_count() > 0 {
+                    let _ = tx.send(event());
+                }
+            }
+            Sink::Callback(ref callback) => callback(&event()),
+        }
+    }
+}
+
+impl Debug for Observer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.sink {
+            Sink::Channel(ref tx) => write!(f, "{{ channel: {{ receivers: {} }} }}", tx.receiver_count()),
+            Sink::Callback(_) => write!(f, "{{ callback: Fn }}"),
+        }
+    }
+}
+
+// Catches the panics of the wrapped future, with the message of the panic.
+pub(crate) struct CatchUnwind<F> {
+    fut: Pin<Box<F>>,
+}
+
+impl<F: Future> CatchUnwind<F> {
+    pub(crate) fn new(fut: F) -> Self {
+        CatchUnwind { fut: Box::pin(fut) }
+    }
+}
+
+impl<F: Future> Future for CatchUnwind<F> {
+    type Output = Result<F::Output, String>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let fut = self.fut.as_mut();
+        match panic::catch_unwind(AssertUnwindSafe(move || fut.poll(cx))) {
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(payload) => Poll::Ready(Err(panic_message(payload.as_ref()))),
+        }
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        (*msg).to_owned()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "Box<dyn Any>".to_owned()
+    }
+}
@@ -0,0 +1,277 @@
+use crate::body::{self, BodyError, BufferedBody, SpillConfig};
+#[cfg(feature = "codec")]
+use crate::codec::{BuiltinCodec, Codec};
+use crate::types::{Deadline, RequestContext, RequestMemory};
+#[cfg(any(feature = "protobuf", feature = "codec"))]
+use crate::HttpError;
+use hyper::body::Bytes;
+use hyper::Request;
+use std::future::Future;
+use std::pin::Pin;
+
+/// The future returned by [`RequestBodyExt::buffer_body`](./trait.RequestBodyExt.html#tymethod.buffer_body).
+pub type BufferBodyFuture<'a> = Pin<Box<dyn Future<Output = Result<BufferedBody, BodyError>> + Send + 'a>>;
+
+/// The future returned by [`RequestBodyExt::read_body_with_deadline`](./trait.RequestBodyExt.html#tymethod.read_body_with_deadline).
+pub type ReadBodyFuture<'a> = Pin<Box<dyn Future<Output = Result<Bytes, BodyError>> + Send + 'a>>;
+
+/// The future returned by [`RequestBodyExt::proto`](./trait.RequestBodyExt.html#tymethod.proto).
+#[cfg(feature = "protobuf")]
+pub type ProtoFuture<'a, M> = Pin<Box<dyn Future<Output = Result<M, HttpError>> + Send + 'a>>;
+
+#[cfg(feature = "protobuf")]
+const DEFAULT_PROTO_LIMIT: usize = 1024 * 1024;
+
+/// The future returned by [`RequestBodyExt::decode`](./trait.RequestBodyExt.html#method.decode).
+#[cfg(feature = "codec")]
+pub type DecodeFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, HttpError>> + Send + 'a>>;
+
+#[cfg(feature = "codec")]
+const DEFAULT_DECODE_LIMIT: usize = 1024 * 1024;
+
+/// A extension trait which extends the [`hyper::Request`](https://docs.rs/hyper/0.14.4/hyper/struct.Request.html) type with body related methods.
+pub trait RequestBodyExt {
+    /// Reads the whole request body and puts a replay of it back as the request body, so that the route handler
+    /// can still consume it.
+    ///
+    /// Bodies up to `limit` bytes are kept in memory. Larger bodies are spilled into a temporary file as configured by
+    /// the [`SpillConfig`](../struct.SpillConfig.html), or rejected with
+    /// [`BodyError::TooLarge`](../enum.BodyError.html#variant.TooLarge) if no `spill` is provided or they exceed its
+    /// `max_size`. Spilling requires the `fs` feature, without which a `spill` is rejected with an I/O error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::{Router, Middleware, SpillConfig};
+    /// use routerify::ext::RequestBodyExt;
+    /// use hyper::{Request, Body};
+    ///
+    /// # fn run() -> Router<Body, routerify::BodyError> {
+    /// let router = Router::builder()
+    ///     .middleware(Middleware::pre(|mut req: Request<Body>| async move {
+    ///         let spill = SpillConfig {
+    ///             dir: std::env::temp_dir(),
+    ///             max_size: 16 * 1024 * 1024,
+    ///         };
+    ///         let body = req.buffer_body(64 * 1024, Some(spill)).await?;
+    ///         println!("Received {} bytes", body.len());
+    ///
+    ///         Ok(req)
+    ///     }))
+    ///     .build()
+    ///     .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    fn buffer_body(&mut self, limit: usize, spill: Option<SpillConfig>) -> BufferBodyFuture<'_>;
+
+    /// Reads the whole request body of up to `limit` bytes into memory before the [`Deadline`](../struct.Deadline.html)
+    /// put into the request context, if any, and puts a replay of it back as the request body.
+    ///
+    /// It fails with [`BodyError::Timeout`](../enum.BodyError.html#variant.Timeout) once the deadline passes,
+    /// [`BodyError::TooLarge`](../enum.BodyError.html#variant.TooLarge) for a larger body and
+    /// [`BodyError::Hyper`](../enum.BodyError.html#variant.Hyper) if the body can't be received from the client, which
+    /// the default error handler responds to with `408 Request Timeout`, `413 Payload Too Large` and `400 Bad Request`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::{Router, RouteError, Deadline};
+    /// use routerify::ext::{RequestBodyExt, RequestExt};
+    /// use hyper::{Response, Body};
+    /// use std::time::Duration;
+    ///
+    /// # fn run() -> Router<Body, RouteError> {
+    /// let router = Router::builder()
+    ///     .post("/uploads", |mut req| async move {
+    ///         req.set_context(Deadline::after(Duration::from_secs(5)));
+    ///
+    ///         let body = req.read_body_with_deadline(64 * 1024).await?;
+    ///         Ok(Response::new(Body::from(format!("Received {} bytes", body.len()))))
+    ///     })
+    ///     .build()
+    ///     .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    fn read_body_with_deadline(&mut self, limit: usize) -> ReadBodyFuture<'_>;
+
+    /// Reads the request body and decodes it as a protobuf message of up to 1 MiB. It requires the `protobuf` feature.
+    ///
+    /// It fails with [`HttpError`](../struct.HttpError.html)s of status `415 Unsupported Media Type` unless the
+    /// `Content-Type` is `application/x-protobuf` or `application/protobuf`, `413 Payload Too Large` for a larger body
+    /// and `400 Bad Request` if the message can't be decoded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::{Router, RouteError};
+    /// use routerify::ext::RequestBodyExt;
+    /// use hyper::Body;
+    ///
+    /// #[derive(Clone, PartialEq, prost::Message)]
+    /// struct Greeting {
+    ///     #[prost(string, tag = "1")]
+    ///     name: String,
+    /// }
+    ///
+    /// # fn run() -> Router<Body, RouteError> {
+    /// let router = Router::builder()
+    ///     .post("/greetings", |mut req| async move {
+    ///         let mut greeting = req.proto::<Greeting>().await?;
+    ///         greeting.name = format!("Hello {}", greeting.name);
+    ///         Ok(routerify::proto(&greeting))
+    ///     })
+    ///     .build()
+    ///     .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    #[cfg(feature = "protobuf")]
+    fn proto<M: prost::Message + Default + 'static>(&mut self) -> ProtoFuture<'_, M> {
+        self.proto_with_limit(DEFAULT_PROTO_LIMIT)
+    }
+
+    /// Reads the request body and decodes it as a protobuf message of up to `limit` bytes. It requires the `protobuf`
+    /// feature.
+    ///
+    /// Please refer to the [`proto`](./trait.RequestBodyExt.html#method.proto) method for more info.
+    #[cfg(feature = "protobuf")]
+    fn proto_with_limit<M: prost::Message + Default + 'static>(&mut self, limit: usize) -> ProtoFuture<'_, M>;
+
+    /// Reads the request body of up to 1 MiB and decodes it with the [codec](../codec/index.html) picked by the
+    /// `Content-Type` header. It requires the `json`, `msgpack` or `cbor` feature.
+    ///
+    /// It fails with [`HttpError`](../struct.HttpError.html)s of status `415 Unsupported Media Type` if no enabled codec
+    /// handles the `Content-Type`, `413 Payload Too Large` for a larger body and `400 Bad Request` if the body can't be
+    /// decoded.
+    ///
+    /// Please refer to the [codec](../codec/index.html) module documentation for an example.
+    #[cfg(feature = "codec")]
+    fn decode<T: serde::de::DeserializeOwned + 'static>(&mut self) -> DecodeFuture<'_, T> {
+        self.decode_with_limit(DEFAULT_DECODE_LIMIT)
+    }
+
+    /// Reads the request body of up to `limit` bytes and decodes it with the codec picked by the `Content-Type` header.
+    ///
+    /// Please refer to the [`decode`](./trait.RequestBodyExt.html#method.decode) method for more info.
+    #[cfg(feature = "codec")]
+    fn decode_with_limit<T: serde::de::DeserializeOwned + 'static>(&mut self, limit: usize) -> DecodeFuture<'_, T>;
+}
+
+impl RequestBodyExt for Request<hyper::Body> {
+    fn buffer_body(&mut self, limit: usize, spill: Option<SpillConfig>) -> BufferBodyFuture<'_> {
+        Box::pin(async move {
+            let body = std::mem::take(self.body_mut());
+            let buffered = body::buffer(body, limit, spill).await?;
+            if let Some(memory) = RequestMemory::of(self.extensions()) {
+                memory.record_body(&buffered);
+            }
+            *self.body_mut() = buffered.to_body()?;
+            Ok(buffered)
+        })
+    }
+
+    fn read_body_with_deadline(&mut self, limit: usize) -> ReadBodyFuture<'_> {
+        Box::pin(async move {
+            let deadline = self
+                .extensions()
+                .get::<RequestContext>()
+                .and_then(|ctx| ctx.get::<Deadline>());
+            let body = std::mem::take(self.body_mut());
+            let buffered = match deadline {
+                Some(deadline) => {
+                    let deadline = tokio::time::Instant::from_std(deadline.instant());
+                    tokio::time::timeout_at(deadline, body::buffer(body, limit, None))
+                        .await
+                        .map_err(|_| BodyError::Timeout)??
+                }
+                None => body::buffer(body, limit, None).await?,
+            };
+            if let Some(memory) = RequestMemory::of(self.extensions()) {
+                memory.record_body(&buffered);
+            }
+            let bytes = buffered
+                .as_bytes()
+                .expect("A body within the limit is kept in memory")
+                .clone();
+            *self.body_mut() = hyper::Body::from(bytes.clone());
+            Ok(bytes)
+        })
+    }
+
+    #[cfg(feature = "protobuf")]
+    fn proto_with_limit<M: prost::Message + Default + 'static>(&mut self, limit: usize) -> ProtoFuture<'_, M> {
+        Box::pin(async move {
+            let is_proto = self
+                .headers()
+                .get(hyper::header::CONTENT_TYPE)
+                .and_then(|val| val.to_str().ok())
+                .and_then(|val| val.split(';').next())
+                .map(|mime| {
+                    let mime = mime.trim();
+                    mime.eq_ignore_ascii_case(crate::constants::PROTOBUF_CONTENT_TYPE)
+                        || mime.eq_ignore_ascii_case("application/protobuf")
+                })
+                .unwrap_or(false);
+            if !is_proto {
+                return Err(HttpError::new(
+                    hyper::StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                    "Expected a protobuf request body",
+                ));
+            }
+
+            let body = std::mem::take(self.body_mut());
+            let buffered = body::buffer(body, limit, None).await.map_err(|err| match err {
+                BodyError::TooLarge { .. } => HttpError::new(hyper::StatusCode::PAYLOAD_TOO_LARGE, err.to_string()),
+                _ => HttpError::new(hyper::StatusCode::BAD_REQUEST, err.to_string()),
+            })?;
+            if let Some(memory) = RequestMemory::of(self.extensions()) {
+                memory.record_body(&buffered);
+            }
+            let bytes = buffered.as_bytes().expect("A body within the limit is kept in memory");
+
+            M::decode(bytes.clone()).map_err(|err| {
+                HttpError::new(
+                    hyper::StatusCode::BAD_REQUEST,
+                    format!("Couldn't decode the protobuf request body: {}", err),
+                )
+            })
+        })
+    }
+
+    #[cfg(feature = "codec")]
+    fn decode_with_limit<T: serde::de::DeserializeOwned + 'static>(&mut self, limit: usize) -> DecodeFuture<'_, T> {
+        Box::pin(async move {
+            let codec = self
+                .headers()
+                .get(hyper::header::CONTENT_TYPE)
+                .and_then(|val| val.to_str().ok())
+                .and_then(|val| val.split(';').next())
+                .and_then(|mime| BuiltinCodec::find(mime.trim()))
+                .ok_or_else(|| {
+                    HttpError::new(
+                        hyper::StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                        "Unsupported request body content type",
+                    )
+                })?;
+
+            let body = std::mem::take(self.body_mut());
+            let buffered = body::buffer(body, limit, None).await.map_err(|err| match err {
+                BodyError::TooLarge { .. } => HttpError::new(hyper::StatusCode::PAYLOAD_TOO_LARGE, err.to_string()),
+                _ => HttpError::new(hyper::StatusCode::BAD_REQUEST, err.to_string()),
+            })?;
+            if let Some(memory) = RequestMemory::of(self.extensions()) {
+                memory.record_body(&buffered);
+            }
+            let bytes = buffered.as_bytes().expect("A body within the limit is kept in memory");
+
+            codec
+                .decode(bytes)
+                .map_err(|err| HttpError::new(hyper::StatusCode::BAD_REQUEST, err.to_string()))
+        })
+    }
+}
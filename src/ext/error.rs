@@ -0,0 +1,167 @@
+use crate::error::ReportError;
+use crate::ErrorClass;
+use std::error::Error as StdError;
+
+/// A extension trait which extends the [`RouteError`](../type.RouteError.html) type with some helpful methods to
+/// inspect the chain of its sources.
+///
+/// # Examples
+///
+/// ```
+/// use routerify::{Router, RouteError, ErrorClass};
+/// use routerify::ext::RouteErrorExt;
+/// use hyper::{Response, Body, StatusCode};
+///
+/// async fn error_handler(err: RouteError) -> Response<Body> {
+///     for cause in err.chain() {
+///         eprintln!("Caused by: {}", cause);
+///     }
+///
+///     if let Some(err) = err.find_cause::<std::io::Error>() {
+///         eprintln!("I/O error kind: {:?}", err.kind());
+///     }
+///
+///     Response::builder()
+///         .status(err.classify().status())
+///         .body(Body::from("Something went wrong"))
+///         .unwrap()
+/// }
+///
+/// # fn run() -> Router<Body, RouteError> {
+/// let router = Router::builder()
+///     .err_handler(error_handler)
+///     .build()
+///     .unwrap();
+/// # router
+/// # }
+/// # run();
+/// ```
+pub trait RouteErrorExt {
+    /// Returns an iterator over the error itself followed by its sources.
+    ///
+    /// With the `anyhow` or the `eyre` feature, an `anyhow::Error` or an `eyre::Report` returned by a handler is
+    /// unwrapped, so the chain starts with the original error and [`find_cause`](#tymethod.find_cause) can be used
+    /// to downcast it.
+    fn chain(&self) -> Chain<'_>;
+
+    /// Returns the first error in the [chain](#tymethod.chain) of the type `T`.
+    fn find_cause<T: StdError + 'static>(&self) -> Option<&T>;
+
+    /// Classifies the error by the first error in the [chain](#tymethod.chain) of a known type.
+    ///
+    /// * [`HttpError`](../struct.HttpError.html) is classified as `ErrorClass::Http` with its status.
+    /// * [`BodyError::TooLarge`](../enum.BodyError.html#variant.TooLarge) is classified as `ErrorClass::BodyTooLarge`.
+    /// * [`BodyError::Timeout`](../enum.BodyError.html#variant.Timeout) and
+    ///   [`BodyError::Hyper`](../enum.BodyError.html#variant.Hyper) are classified as `ErrorClass::Http` with the
+    ///   [status](../enum.BodyError.html#method.status) of the error.
+    /// * Timed out `hyper::Error`s and `std::io::Error`s are classified as `ErrorClass::Timeout`.
+    /// * `std::io::Error`s of the `InvalidData` kind, UTF-8 errors and the parse errors of the primitive types
+    ///   are classified as `ErrorClass::Deserialize`.
+    fn classify(&self) -> ErrorClass;
+
+    /// Returns the detailed report of an `anyhow::Error` or an `eyre::Report` returned by a handler, including the
+    /// backtrace if it was captured. It requires the `anyhow` or the `eyre` feature.
+    fn report(&self) -> Option<&str>;
+}
+
+impl RouteErrorExt for dyn StdError + Send + Sync + 'static {
+    fn chain(&self) -> Chain<'_> {
+        let err: &(dyn StdError + 'static) = match self.downcast_ref::<ReportError>() {
+            Some(report_err) => report_err.inner.as_ref(),
+            None => self,
+        };
+        Chain { next: Some(err) }
+    }
+
+    fn find_cause<T: StdError + 'static>(&self) -> Option<&T> {
+        self.chain().find_map(|err| err.downcast_ref::<T>())
+    }
+
+    fn classify(&self) -> ErrorClass {
+        self.chain().find_map(ErrorClass::of).unwrap_or(ErrorClass::Other)
+    }
+
+    fn report(&self) -> Option<&str> {
+        self.downcast_ref::<ReportError>()
+            .map(|report_err| report_err.report.as_str())
+    }
+}
+
+/// An iterator over an error and its sources. It's created by the [`RouteErrorExt::chain`](./trait.RouteErrorExt.html#tymethod.chain) method.
+#[derive(Debug, Clone)]
+pub struct Chain<'a> {
+    next: Option<&'a (dyn StdError + 'static)>,
+}
+
+impl<'a> Iterator for Chain<'a> {
+    type Item = &'a (dyn StdError + 'static);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = self.next.take()?;
+        self.next = next.source();
+        Some(next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BodyError, HttpError, RouteError};
+    use hyper::StatusCode;
+    use std::fmt::{self, Display, Formatter};
+    use std::io;
+
+    #[derive(Debug)]
+    struct Wrapper(io::Error);
+
+    impl Display for Wrapper {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            write!(f, "Could not load the config")
+        }
+    }
+
+    impl StdError for Wrapper {
+        fn source(&self) -> Option<&(dyn StdError + 'static)> {
+            Some(&self.0)
+        }
+    }
+
+    #[test]
+    fn walks_the_source_chain() {
+        let err: RouteError = Box::new(Wrapper(io::Error::new(io::ErrorKind::TimedOut, "timed out")));
+
+        assert_eq!(err.chain().count(), 2);
+        assert_eq!(err.find_cause::<io::Error>().unwrap().kind(), io::ErrorKind::TimedOut);
+        assert!(err.find_cause::<HttpError>().is_none());
+        assert_eq!(err.classify(), ErrorClass::Timeout);
+    }
+
+    #[test]
+    fn classifies_known_errors() {
+        let err: RouteError = Box::new(HttpError::new(StatusCode::FORBIDDEN, "Forbidden"));
+        assert_eq!(err.classify().status(), StatusCode::FORBIDDEN);
+
+        let err: RouteError = Box::new(BodyError::TooLarge { limit: 1 });
+        assert_eq!(err.classify(), ErrorClass::BodyTooLarge);
+
+        let err: RouteError = Box::new(BodyError::Timeout);
+        assert_eq!(err.classify(), ErrorClass::Http(StatusCode::REQUEST_TIMEOUT));
+
+        let err: RouteError = Box::new("abc".parse::<u32>().unwrap_err());
+        assert_eq!(err.classify(), ErrorClass::Deserialize);
+
+        let err: RouteError = crate::Error::new("Something failed").into();
+        assert_eq!(err.classify().status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[cfg(feature = "anyhow")]
+    #[test]
+    fn unwraps_anyhow_errors() {
+        let report = anyhow::Error::new(HttpError::new(StatusCode::CONFLICT, "Conflict")).context("Could not save");
+        let err = crate::error::into_route_error(report);
+
+        assert!(err.report().unwrap().contains("Could not save"));
+        assert_eq!(err.find_cause::<HttpError>().unwrap().status(), StatusCode::CONFLICT);
+        assert_eq!(err.classify(), ErrorClass::Http(StatusCode::CONFLICT));
+    }
+}
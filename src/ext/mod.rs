@@ -1,3 +1,11 @@
+#[cfg(feature = "codec")]
+pub use body::DecodeFuture;
+#[cfg(feature = "protobuf")]
+pub use body::ProtoFuture;
+pub use body::{BufferBodyFuture, ReadBodyFuture, RequestBodyExt};
+pub use error::{Chain, RouteErrorExt};
 pub use request::RequestExt;
 
+mod body;
+mod error;
 mod request;
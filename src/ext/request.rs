@@ -1,7 +1,19 @@
-use crate::data_map::SharedDataMap;
-use crate::types::{RequestContext, RequestMeta, RouteParams};
-use hyper::Request;
+#[cfg(feature = "client")]
+use crate::client::{self, Client, RequestClient};
+use crate::data_map::{SharedConfigMaps, SharedDataMap};
+use crate::middleware::csp::CspNonce;
+use crate::middleware::feature_flags::FlagSet;
+#[cfg(feature = "opentelemetry")]
+use crate::middleware::otel::TraceContext;
+#[cfg(feature = "client")]
+use crate::types::Deadline;
+use crate::types::{ConnectionInfo, Forwarded, FromParam, RequestContext, RequestMeta, RouteParams};
+#[cfg(feature = "typed-headers")]
+use crate::HttpError;
+use hyper::{Request, Response};
 use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
 
 /// A extension trait which extends the [`hyper::Request`](https://docs.rs/hyper/0.14.4/hyper/struct.Request.html) and [`http::Parts`](https://docs.rs/http/0.2.4/http/request/struct.Parts.html) types with some helpful methods.
 pub trait RequestExt {
@@ -58,6 +70,141 @@ pub trait RequestExt {
     /// ```
     fn param<P: Into<String>>(&self, param_name: P) -> Option<&String>;
 
+    /// It returns the value of an optional route parameter e.g. `:month?` in `/archive/:year/:month?`.
+    ///
+    /// If the optional segment is missing in the request path, it returns the default value specified in the path
+    /// as `:month?=01` or `None` if there's no default. Unlike [`param`](#tymethod.param), it also returns `None`
+    /// instead of panicking when the route parameters are not populated yet.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::Router;
+    /// use routerify::ext::RequestExt;
+    /// use hyper::{Response, Body};
+    /// # use std::convert::Infallible;
+    ///
+    /// # fn run() -> Router<Body, Infallible> {
+    /// let router = Router::builder()
+    ///     .get("/archive/:year/:month?/:page?=1", |req| async move {
+    ///         let year = req.param("year").unwrap();
+    ///         let month = req.param_opt("month").unwrap_or("all");
+    ///         let page = req.param_opt("page").unwrap();
+    ///
+    ///         Ok(Response::new(Body::from(format!("Archive of {}/{}, page {}", year, month, page))))
+    ///      })
+    ///      .build()
+    ///      .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    fn param_opt<P: Into<String>>(&self, param_name: P) -> Option<&str>;
+
+    /// It converts the route parameter value into the type `T` through the [`FromParam`](../trait.FromParam.html) trait.
+    ///
+    /// It returns `None` if the parameter is missing and the conversion error if the value can't be converted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::Router;
+    /// use routerify::ext::RequestExt;
+    /// use hyper::{Response, Body, StatusCode};
+    /// # use std::convert::Infallible;
+    ///
+    /// # fn run() -> Router<Body, Infallible> {
+    /// let router = Router::builder()
+    ///     .get("/users/:id", |req| async move {
+    ///         match req.param_typed::<u64, _>("id") {
+    ///             Some(Ok(id)) => Ok(Response::new(Body::from(format!("User: {}", id)))),
+    ///             _ => Ok(Response::builder()
+    ///                 .status(StatusCode::BAD_REQUEST)
+    ///                 .body(Body::from("Invalid user id"))
+    ///                 .unwrap()),
+    ///         }
+    ///      })
+    ///      .build()
+    ///      .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    fn param_typed<T: FromParam, P: Into<String>>(&self, param_name: P) -> Option<Result<T, T::Error>>;
+
+    /// It returns the route parameters for the modification, e.g. by a pre middleware which canonicalizes a slug before
+    /// the route handler runs.
+    ///
+    /// The parameters of the matched route are available to the pre middlewares. The modified parameters are kept for
+    /// the route handler and they're returned by the [`RequestInfo::params`](../struct.RequestInfo.html#method.params)
+    /// method to the post middlewares and the error handler.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::{Middleware, Router};
+    /// use routerify::ext::RequestExt;
+    /// use hyper::{Response, Request, Body};
+    /// # use std::convert::Infallible;
+    ///
+    /// # fn run() -> Router<Body, Infallible> {
+    /// let router = Router::builder()
+    ///     .middleware(Middleware::pre(|mut req: Request<Body>| async move {
+    ///         if let Some(slug) = req.param("slug").map(|slug| slug.to_lowercase()) {
+    ///             req.params_mut().set("slug", slug);
+    ///         }
+    ///         Ok(req)
+    ///     }))
+    ///     .get("/posts/:slug", |req| async move {
+    ///         Ok(Response::new(Body::from(format!("Post: {}", req.param("slug").unwrap()))))
+    ///      })
+    ///      .build()
+    ///      .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    fn params_mut(&mut self) -> &mut RouteParams;
+
+    /// It sets the value of a route parameter, e.g. by a pre middleware which rewrites a tenant name.
+    ///
+    /// Please refer to the [`params_mut`](#tymethod.params_mut) method for more info.
+    fn set_param<N: Into<String>, V: Into<String>>(&mut self, param_name: N, param_val: V) {
+        self.params_mut().set(param_name, param_val);
+    }
+
+    /// It forwards the request to another path of the router, e.g. an error page or the route of an alias, without a
+    /// round trip to the client. The path is relative to the base path and it may have a query string, otherwise the
+    /// query string of the request is kept.
+    ///
+    /// The returned response must be returned by the route handler. The router then matches the request again by the
+    /// new path, with its method, headers, body and context, and the response of the new route is sent instead. A
+    /// request which is forwarded too many times is answered with `508 Loop Detected` by the error handler.
+    ///
+    /// The [`http::Parts`](https://docs.rs/http/0.2.4/http/request/struct.Parts.html) are forwarded with an empty body.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::Router;
+    /// use routerify::ext::RequestExt;
+    /// use hyper::{Response, Body};
+    /// # use std::convert::Infallible;
+    ///
+    /// # fn run() -> Router<Body, Infallible> {
+    /// let router = Router::builder()
+    ///     .get("/about-us", |req| async move { Ok(req.forward_to("/about")) })
+    ///     .get("/about", |_| async move { Ok(Response::new(Body::from("About"))) })
+    ///     .build()
+    ///     .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    fn forward_to<B: Default, P: Into<String>>(self, path: P) -> Response<B>
+    where
+        Self: Sized;
+
     /// It returns the remote address of the incoming request.
     ///
     /// # Examples
@@ -83,12 +230,113 @@ pub trait RequestExt {
     /// ```
     fn remote_addr(&self) -> SocketAddr;
 
+    /// It returns a [`CancellationToken`](../struct.CancellationToken.html) which is cancelled when the client
+    /// disconnects before the response is sent, so that the long-running handlers can abort the expensive work.
+    ///
+    /// If the request is not served by a [`RequestService`](../struct.RequestService.html), the returned token is never cancelled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::Router;
+    /// use routerify::ext::RequestExt;
+    /// use hyper::{Response, Body, StatusCode};
+    /// use std::time::Duration;
+    /// # use std::convert::Infallible;
+    ///
+    /// # fn run() -> Router<Body, Infallible> {
+    /// let router = Router::builder()
+    ///     .get("/report", |req| async move {
+    ///         let token = req.cancellation_token();
+    ///
+    ///         tokio::select! {
+    ///             _ = token.cancelled() => Ok(Response::builder()
+    ///                 .status(StatusCode::SERVICE_UNAVAILABLE)
+    ///                 .body(Body::empty())
+    ///                 .unwrap()),
+    ///             _ = tokio::time::sleep(Duration::from_secs(10)) => Ok(Response::new(Body::from("Report"))),
+    ///         }
+    ///      })
+    ///      .build()
+    ///      .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    fn cancellation_token(&self) -> CancellationToken;
+
+    /// It returns the info of the connection the request is received on e.g. the local and the peer addresses, the TLS
+    /// details and the HTTP version.
+    ///
+    /// It's `None` if the serving layer didn't attach it. Please refer to the [`ConnectionInfo`](../struct.ConnectionInfo.html) for more info.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::Router;
+    /// use routerify::ext::RequestExt;
+    /// use hyper::{Response, Body};
+    /// # use std::convert::Infallible;
+    ///
+    /// # fn run() -> Router<Body, Infallible> {
+    /// let router = Router::builder()
+    ///     .get("/hello", |req| async move {
+    ///         let secure = req.connection_info().map(|info| info.is_secure()).unwrap_or(false);
+    ///
+    ///         Ok(Response::new(Body::from(format!("Secure: {}", secure))))
+    ///      })
+    ///      .build()
+    ///      .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    fn connection_info(&self) -> Option<&ConnectionInfo>;
+
     /// Access data which was shared by the [`RouterBuilder`](../struct.RouterBuilder.html) method
     /// [`data`](../struct.RouterBuilder.html#method.data).
     ///
     /// Please refer to the [Data and State Sharing](../index.html#data-and-state-sharing) for more info.
     fn data<T: Send + Sync + 'static>(&self) -> Option<&T>;
 
+    /// Access the config of the innermost scope of the request which was set by the
+    /// [`RouterBuilder::config`](../struct.RouterBuilder.html#method.config) method, e.g. the limit of a middleware
+    /// which is shared by the scopes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::{Router, Middleware};
+    /// use routerify::ext::RequestExt;
+    /// use hyper::{Response, Request, Body};
+    /// # use std::convert::Infallible;
+    ///
+    /// struct RateLimit(u32);
+    ///
+    /// # fn run() -> Router<Body, Infallible> {
+    /// let api = Router::builder()
+    ///     .config(RateLimit(10))
+    ///     .get("/users", |_| async move { Ok(Response::new(Body::from("Users"))) })
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// let router = Router::builder()
+    ///     .config(RateLimit(100))
+    ///     .middleware(Middleware::pre(|req: Request<Body>| async move {
+    ///         // It's 10 for the `/api` requests and 100 for the other ones.
+    ///         let limit = req.scope_config::<RateLimit>().map(|limit| limit.0);
+    ///         # let _ = limit;
+    ///         Ok(req)
+    ///     }))
+    ///     .scope("/api", api)
+    ///     .build()
+    ///     .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    fn scope_config<T: Send + Sync + 'static>(&self) -> Option<&T>;
+
     /// Access data in the request context.
     fn context<T: Send + Sync + Clone + 'static>(&self) -> Option<T>;
 
@@ -121,6 +369,101 @@ pub trait RequestExt {
     /// # run();
     /// ```
     fn set_context<T: Send + Sync + Clone + 'static>(&self, val: T);
+
+    /// It returns the path prefix which was stripped from the request path by the
+    /// [`RouterBuilder::base_path`](../struct.RouterBuilder.html#method.base_path) option.
+    ///
+    /// It's `None` if the option isn't set or the request is outside of the base path.
+    fn base_path(&self) -> Option<&str>;
+
+    /// It prepends the [`base_path`](#tymethod.base_path) of the request to a root relative path, so the links
+    /// rendered by the handlers point into the app. The other paths and the absolute URLs are returned as is.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::Router;
+    /// use routerify::ext::RequestExt;
+    /// use hyper::{Response, Body};
+    /// # use std::convert::Infallible;
+    ///
+    /// # fn run() -> Router<Body, Infallible> {
+    /// let router = Router::builder()
+    ///     .base_path("/myapp")
+    ///     .get("/", |req| async move {
+    ///         // Renders the `/myapp/about` link.
+    ///         Ok(Response::new(Body::from(format!("<a href=\"{}\">About</a>", req.url_for("/about")))))
+    ///      })
+    ///      .build()
+    ///      .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    fn url_for(&self, path: &str) -> String;
+
+    /// It returns the `Content-Security-Policy` nonce generated for the request by the
+    /// [`csp`](../middleware/csp/index.html) middleware, so it can be rendered into the inline `<script>` and `<style>` tags.
+    ///
+    /// It's `None` if the request didn't pass through the middleware.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::Router;
+    /// use routerify::ext::RequestExt;
+    /// use routerify::middleware::csp::ContentSecurityPolicy;
+    /// use hyper::{Response, Body};
+    /// # use std::convert::Infallible;
+    ///
+    /// # fn run() -> Router<Body, Infallible> {
+    /// let csp = ContentSecurityPolicy::new("script-src 'nonce-{nonce}'");
+    /// let router = Router::builder()
+    ///     .middleware(csp.pre_middleware())
+    ///     .middleware(csp.post_middleware())
+    ///     .get("/", |req| async move {
+    ///         let nonce = req.csp_nonce().unwrap();
+    ///
+    ///         Ok(Response::new(Body::from(format!("<script nonce=\"{}\"></script>", nonce))))
+    ///      })
+    ///      .build()
+    ///      .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    fn csp_nonce(&self) -> Option<String>;
+
+    /// It checks if a feature flag evaluated by the [`feature_flags`](../middleware/feature_flags/index.html)
+    /// middleware is on for the request.
+    ///
+    /// It's `false` for the unknown flags and if the request didn't pass through the middleware.
+    ///
+    /// Please refer to the [`feature_flags`](../middleware/feature_flags/index.html) module documentation for an example.
+    fn flag(&self, name: &str) -> bool;
+
+    /// It returns an outbound HTTP client which propagates the request id, trace headers and
+    /// [`Deadline`](../struct.Deadline.html) of the request to the upstream services. It requires the `client`
+    /// feature.
+    ///
+    /// Please refer to the [`client`](../client/index.html) module documentation for more info.
+    #[cfg(feature = "client")]
+    fn http_client(&self) -> RequestClient;
+
+    /// It returns the OpenTelemetry context of the request span started by the
+    /// [`otel`](../middleware/otel/index.html) middleware. It requires the `opentelemetry` feature.
+    ///
+    /// It's `None` if the request didn't pass through the middleware.
+    #[cfg(feature = "opentelemetry")]
+    fn trace_context(&self) -> Option<opentelemetry::Context>;
+
+    /// It decodes the request header of the type `H` e.g. [`ContentType`](../headers/typed/struct.ContentType.html). It
+    /// requires the `typed-headers` feature.
+    ///
+    /// It's `Ok(None)` if the request has no such header. Please refer to the [`typed`](../headers/typed/index.html)
+    /// module documentation for more info.
+    #[cfg(feature = "typed-headers")]
+    fn typed_header<H: crate::headers::typed::Header>(&self) -> Result<Option<H>, HttpError>;
 }
 
 fn params(ext: &http::Extensions) -> &RouteParams {
@@ -129,10 +472,30 @@ fn params(ext: &http::Extensions) -> &RouteParams {
         .expect("Routerify: No RouteParams added while processing request")
 }
 
+fn params_mut(ext: &mut http::Extensions) -> &mut RouteParams {
+    if ext.get::<RequestMeta>().is_none() {
+        ext.insert(RequestMeta::with_route_params(RouteParams::new()));
+    }
+    ext.get_mut::<RequestMeta>()
+        .expect("Routerify: No RequestMeta added while processing request")
+        .route_params_mut()
+}
+
 fn param<P: Into<String>>(ext: &http::Extensions, param_name: P) -> Option<&String> {
     params(ext).get(&param_name.into())
 }
 
+fn param_opt<P: Into<String>>(ext: &http::Extensions, param_name: P) -> Option<&str> {
+    ext.get::<RequestMeta>()
+        .and_then(|meta| meta.route_params())
+        .and_then(|params| params.get(param_name))
+        .map(|val| val.as_str())
+}
+
+fn param_typed<T: FromParam, P: Into<String>>(ext: &http::Extensions, param_name: P) -> Option<Result<T, T::Error>> {
+    param_opt(ext, param_name).map(T::from_param)
+}
+
 fn remote_addr(ext: &http::Extensions) -> SocketAddr {
     ext.get::<RequestMeta>()
         .and_then(|meta| meta.remote_addr())
@@ -140,8 +503,19 @@ fn remote_addr(ext: &http::Extensions) -> SocketAddr {
         .expect("Routerify: No remote address added while processing request")
 }
 
+fn cancellation_token(ext: &http::Extensions) -> CancellationToken {
+    ext.get::<RequestMeta>()
+        .and_then(|meta| meta.cancellation_token())
+        .cloned()
+        .unwrap_or_default()
+}
+
+fn connection_info(ext: &http::Extensions) -> Option<&ConnectionInfo> {
+    ext.get::<RequestMeta>().and_then(|meta| meta.connection_info())
+}
+
 fn data<T: Send + Sync + 'static>(ext: &http::Extensions) -> Option<&T> {
-    let shared_data_maps = ext.get::<Vec<SharedDataMap>>();
+    let shared_data_maps = ext.get::<Arc<[SharedDataMap]>>();
 
     if let Some(shared_data_maps) = shared_data_maps {
         for shared_data_map in shared_data_maps.iter() {
@@ -154,6 +528,11 @@ fn data<T: Send + Sync + 'static>(ext: &http::Extensions) -> Option<&T> {
     None
 }
 
+fn scope_config<T: Send + Sync + 'static>(ext: &http::Extensions) -> Option<&T> {
+    ext.get::<SharedConfigMaps>()
+        .and_then(|scope_configs| scope_configs.get::<T>())
+}
+
 fn context<T: Send + Sync + Clone + 'static>(ext: &http::Extensions) -> Option<T> {
     let ctx = ext.get::<RequestContext>().expect("Context must be present");
     ctx.get::<T>()
@@ -164,6 +543,42 @@ fn set_context<T: Send + Sync + Clone + 'static>(ext: &http::Extensions, val: T)
     ctx.set(val)
 }
 
+fn base_path(ext: &http::Extensions) -> Option<&str> {
+    ext.get::<RequestMeta>().and_then(|meta| meta.base_path())
+}
+
+fn url_for(ext: &http::Extensions, path: &str) -> String {
+    match base_path(ext) {
+        Some(base_path) if path.starts_with('/') && !path.starts_with("//") => format!("{}{}", base_path, path),
+        _ => path.to_owned(),
+    }
+}
+
+fn csp_nonce(ext: &http::Extensions) -> Option<String> {
+    context::<CspNonce>(ext).map(|nonce| nonce.0)
+}
+
+fn flag(ext: &http::Extensions, name: &str) -> bool {
+    matches!(context::<FlagSet>(ext), Some(flags) if flags.is_enabled(name))
+}
+
+#[cfg(feature = "client")]
+fn http_client(ext: &http::Extensions, headers: &http::HeaderMap) -> RequestClient {
+    let client = data::<Client>(ext).unwrap_or_else(|| client::default_client());
+    #[allow(unused_mut)]
+    let mut client = client.bind(headers, context::<Deadline>(ext));
+    #[cfg(feature = "opentelemetry")]
+    if let Some(cx) = trace_context(ext) {
+        crate::middleware::otel::inject(&cx, client.headers_mut());
+    }
+    client
+}
+
+#[cfg(feature = "opentelemetry")]
+fn trace_context(ext: &http::Extensions) -> Option<opentelemetry::Context> {
+    context::<TraceContext>(ext).map(|cx| cx.0)
+}
+
 impl RequestExt for Request<hyper::Body> {
     fn params(&self) -> &RouteParams {
         params(self.extensions())
@@ -173,14 +588,42 @@ impl RequestExt for Request<hyper::Body> {
         param(self.extensions(), param_name)
     }
 
+    fn param_opt<P: Into<String>>(&self, param_name: P) -> Option<&str> {
+        param_opt(self.extensions(), param_name)
+    }
+
+    fn param_typed<T: FromParam, P: Into<String>>(&self, param_name: P) -> Option<Result<T, T::Error>> {
+        param_typed(self.extensions(), param_name)
+    }
+
+    fn params_mut(&mut self) -> &mut RouteParams {
+        params_mut(self.extensions_mut())
+    }
+
+    fn forward_to<B: Default, P: Into<String>>(self, path: P) -> Response<B> {
+        Forwarded::into_response(self, path.into())
+    }
+
     fn remote_addr(&self) -> SocketAddr {
         remote_addr(self.extensions())
     }
 
+    fn cancellation_token(&self) -> CancellationToken {
+        cancellation_token(self.extensions())
+    }
+
+    fn connection_info(&self) -> Option<&ConnectionInfo> {
+        connection_info(self.extensions())
+    }
+
     fn data<T: Send + Sync + 'static>(&self) -> Option<&T> {
         data(self.extensions())
     }
 
+    fn scope_config<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        scope_config(self.extensions())
+    }
+
     fn context<T: Send + Sync + Clone + 'static>(&self) -> Option<T> {
         context(self.extensions())
     }
@@ -188,6 +631,37 @@ impl RequestExt for Request<hyper::Body> {
     fn set_context<T: Send + Sync + Clone + 'static>(&self, val: T) {
         set_context(self.extensions(), val)
     }
+
+    fn base_path(&self) -> Option<&str> {
+        base_path(self.extensions())
+    }
+
+    fn url_for(&self, path: &str) -> String {
+        url_for(self.extensions(), path)
+    }
+
+    fn csp_nonce(&self) -> Option<String> {
+        csp_nonce(self.extensions())
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        flag(self.extensions(), name)
+    }
+
+    #[cfg(feature = "client")]
+    fn http_client(&self) -> RequestClient {
+        http_client(self.extensions(), self.headers())
+    }
+
+    #[cfg(feature = "opentelemetry")]
+    fn trace_context(&self) -> Option<opentelemetry::Context> {
+        trace_context(self.extensions())
+    }
+
+    #[cfg(feature = "typed-headers")]
+    fn typed_header<H: crate::headers::typed::Header>(&self) -> Result<Option<H>, HttpError> {
+        crate::headers::typed::typed_header(self.headers())
+    }
 }
 
 impl RequestExt for http::request::Parts {
@@ -199,14 +673,42 @@ impl RequestExt for http::request::Parts {
         param(&self.extensions, param_name)
     }
 
+    fn param_opt<P: Into<String>>(&self, param_name: P) -> Option<&str> {
+        param_opt(&self.extensions, param_name)
+    }
+
+    fn param_typed<T: FromParam, P: Into<String>>(&self, param_name: P) -> Option<Result<T, T::Error>> {
+        param_typed(&self.extensions, param_name)
+    }
+
+    fn params_mut(&mut self) -> &mut RouteParams {
+        params_mut(&mut self.extensions)
+    }
+
+    fn forward_to<B: Default, P: Into<String>>(self, path: P) -> Response<B> {
+        Forwarded::into_response(Request::from_parts(self, hyper::Body::empty()), path.into())
+    }
+
     fn remote_addr(&self) -> SocketAddr {
         remote_addr(&self.extensions)
     }
 
+    fn cancellation_token(&self) -> CancellationToken {
+        cancellation_token(&self.extensions)
+    }
+
+    fn connection_info(&self) -> Option<&ConnectionInfo> {
+        connection_info(&self.extensions)
+    }
+
     fn data<T: Send + Sync + 'static>(&self) -> Option<&T> {
         data(&self.extensions)
     }
 
+    fn scope_config<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        scope_config(&self.extensions)
+    }
+
     fn context<T: Send + Sync + Clone + 'static>(&self) -> Option<T> {
         context(&self.extensions)
     }
@@ -214,4 +716,35 @@ impl RequestExt for http::request::Parts {
     fn set_context<T: Send + Sync + Clone + 'static>(&self, val: T) {
         set_context(&self.extensions, val)
     }
+
+    fn base_path(&self) -> Option<&str> {
+        base_path(&self.extensions)
+    }
+
+    fn url_for(&self, path: &str) -> String {
+        url_for(&self.extensions, path)
+    }
+
+    fn csp_nonce(&self) -> Option<String> {
+        csp_nonce(&self.extensions)
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        flag(&self.extensions, name)
+    }
+
+    #[cfg(feature = "client")]
+    fn http_client(&self) -> RequestClient {
+        http_client(&self.extensions, &self.headers)
+    }
+
+    #[cfg(feature = "opentelemetry")]
+    fn trace_context(&self) -> Option<opentelemetry::Context> {
+        trace_context(&self.extensions)
+    }
+
+    #[cfg(feature = "typed-headers")]
+    fn typed_header<H: crate::headers::typed::Header>(&self) -> Result<Option<H>, HttpError> {
+        crate::headers::typed::typed_header(&self.headers)
+    }
 }
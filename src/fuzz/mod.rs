@@ -0,0 +1,223 @@
+//! Deterministic entry points and arbitrary inputs to fuzz the route tables, e.g. with
+//! [`cargo fuzz`](https://rust-fuzz.github.io/book/cargo-fuzz.html). It requires the `fuzz` feature.
+//!
+//! A [`FuzzTarget`](./struct.FuzzTarget.html) matches the requests against the routes of a router the same way it's
+//! done when the router is served, including the percent decoding and the base path, but without executing any
+//! middleware or handler, so it's synchronous and has no side effects. The invalid inputs are rejected with `None`
+//! instead of an error, so any panic found by the fuzzer is a bug in the matching.
+//!
+//! The [`RouteTable`](./struct.RouteTable.html) is an [`Arbitrary`](https://docs.rs/arbitrary/1/arbitrary/trait.Arbitrary.html)
+//! table of route paths which is biased towards the path syntax, to fuzz the route regex generator, and the
+//! [`seed_corpus`](./struct.FuzzTarget.html#method.seed_corpus) method returns a request for each route of a router to
+//! start the fuzzer from.
+//!
+//! # Examples
+//!
+//! A `fuzz/fuzz_targets/match_routes.rs` target of the route table of an app:
+//!
+//! ```ignore
+//! #![no_main]
+//! use libfuzzer_sys::fuzz_target;
+//! use routerify::fuzz::FuzzTarget;
+//!
+//! lazy_static::lazy_static! {
+//!     static ref TARGET: FuzzTarget<hyper::Body, routerify::RouteError> = FuzzTarget::new(my_app::router()).unwrap();
+//! }
+//!
+//! fuzz_target!(|data: &[u8]| {
+//!     let _ = TARGET.match_input(data);
+//! });
+//! ```
+//!
+//! A target of the route regex generator:
+//!
+//! ```ignore
+//! #![no_main]
+//! use libfuzzer_sys::fuzz_target;
+//! use routerify::fuzz::RouteTable;
+//!
+//! fuzz_target!(|input: (RouteTable, Vec<u8>)| {
+//!     if let Ok(target) = input.0.target() {
+//!         let _ = target.match_input(&input.1);
+//!     }
+//! });
+//! ```
+
+use crate::helpers;
+use crate::{RouteParams, Router};
+use arbitrary::{Arbitrary, Unstructured};
+use hyper::body::HttpBody;
+use hyper::{Body, Method, Response, Uri};
+use std::convert::{Infallible, TryFrom};
+use std::fmt::{self, Debug, Formatter};
+
+// The path segments which the arbitrary route paths are made of, besides the arbitrary strings.
+const SEGMENTS: &[&str] = &[
+    "users", "api", "v1", "", ":id", ":name", ":id?", ":slug?", "*", "%2F", "%", "(", "[a-z]", ".", "+", "\\", "é",
+];
+
+/// The route which a request is matched to.
+#[derive(Debug, Clone)]
+pub struct RouteMatch {
+    /// The path of the route.
+    pub route_path: String,
+    /// The params of the route, parsed from the request path.
+    pub params: RouteParams,
+}
+
+/// A router which is prepared to match the requests without serving them.
+///
+/// Please refer to the [module](./index.html) documentation for more info.
+pub struct FuzzTarget<B, E> {
+    router: Router<B, E>,
+}
+
+impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static>
+    FuzzTarget<B, E>
+{
+    /// Prepares the routes of the router to be matched.
+    pub fn new(mut router: Router<B, E>) -> crate::Result<Self> {
+        router.init_regex_set()?;
+        Ok(FuzzTarget { router })
+    }
+
+    /// Matches a request with the method and the path, which may have a query, to the routes. It returns `None` if the
+    /// method or the path is invalid, or no route matches it.
+    pub fn match_only(&self, method: &[u8], path: &[u8]) -> Option<RouteMatch> {
+        let method = Method::from_bytes(method).ok()?;
+        let uri = Uri::try_from(path).ok()?;
+
+        let (target_path, _) = helpers::route_target_path(uri.path(), self.router.base_path.as_deref()).ok()?;
+
+        let route = self.router.matched_route(&method, &target_path)?;
+        Some(RouteMatch {
+            route_path: route.path.clone(),
+            params: route.generate_route_params(&target_path),
+        })
+    }
+
+    /// Matches a request line of the form `METHOD PATH`, e.g. `GET /users/1`, to the routes. It splits the raw fuzzer
+    /// input for the [`match_only`](#method.match_only) method.
+    pub fn match_input(&self, data: &[u8]) -> Option<RouteMatch> {
+        let idx = data.iter().position(|b| *b == b' ')?;
+        self.match_only(&data[..idx], &data[idx + 1..])
+    }
+
+    /// Returns a request line for each method of each route, with the params and the `*` segments filled in, as the
+    /// initial corpus of the [`match_input`](#method.match_input) target. The raw regex routes are left out.
+    pub fn seed_corpus(&self) -> Vec<Vec<u8>> {
+        let mut corpus = Vec::new();
+        for route in self.router.routes.iter().filter(|route| route.raw_regex.is_none()) {
+            let path = route
+                .path
+                .split('/')
+                .map(|segment| match segment {
+                    "*" => "a/b",
+                    _ if segment.starts_with(':') => "1",
+                    _ => segment,
+                })
+                .collect::<Vec<_>>()
+                .join("/");
+            for method in route.methods.iter() {
+                let line = format!("{} {}", method, path).into_bytes();
+                if !corpus.contains(&line) {
+                    corpus.push(line);
+                }
+            }
+        }
+        corpus
+    }
+}
+
+impl<B, E> Debug for FuzzTarget<B, E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{{ routes: {} }}", self.router.routes.len())
+    }
+}
+
+/// An arbitrary table of route paths.
+///
+/// Please refer to the [module](./index.html) documentation for more info.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteTable {
+    paths: Vec<String>,
+}
+
+impl RouteTable {
+    /// Creates a table from the raw fuzzer input.
+    pub fn from_bytes(data: &[u8]) -> Self {
+        RouteTable::arbitrary_take_rest(Unstructured::new(data)).unwrap_or(RouteTable { paths: Vec::new() })
+    }
+
+    /// Returns the route paths.
+    pub fn paths(&self) -> &[String] {
+        &self.paths
+    }
+
+    /// Builds a router with a `GET` route at each path, and prepares it to be matched. It fails if a path is invalid.
+    pub fn target(&self) -> crate::Result<FuzzTarget<Body, Infallible>> {
+        let mut builder = Router::builder();
+        for path in self.paths.iter() {
+            builder = builder.get(path.as_str(), |_| async move { Ok(Response::new(Body::empty())) });
+        }
+        FuzzTarget::new(builder.build()?)
+    }
+}
+
+impl<'a> Arbitrary<'a> for RouteTable {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let len = u.int_in_range(0..=8)?;
+        let mut paths = Vec::with_capacity(len);
+        for _ in 0..len {
+            let mut path = String::new();
+            for _ in 0..u.int_in_range(0..=6)? {
+                path.push('/');
+                match u.ratio(1, 4)? {
+                    true => path.push_str(<&str>::arbitrary(u)?),
+                    false => path.push_str(u.choose(SEGMENTS)?),
+                }
+            }
+            paths.push(path);
+        }
+        Ok(RouteTable { paths })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_requests_without_panicking() {
+        let router: Router<Body, Infallible> = Router::builder()
+            .get("/users/:id", |_| async move { Ok(Response::new(Body::empty())) })
+            .post("/files/*", |_| async move { Ok(Response::new(Body::empty())) })
+            .build()
+            .unwrap();
+        let target = FuzzTarget::new(router).unwrap();
+
+        let found = target.match_input(b"GET /users/J%C3%B6rg?x=1").unwrap();
+        assert_eq!(found.route_path, "/users/:id/");
+        assert_eq!(found.params.get("id").unwrap(), "Jörg");
+        assert!(target.match_only(b"GET", b"/users/%FF").is_none());
+        assert!(target.match_only(b"G ET", b"/users/1").is_none());
+        assert!(target.match_input(b"DELETE /users/1").is_none());
+        assert_eq!(
+            target.seed_corpus(),
+            vec![b"GET /users/1/".to_vec(), b"POST /files/a/b".to_vec()]
+        );
+
+        for seed in 0..64_u8 {
+            let data = (0..256)
+                .map(|idx| seed.wrapping_mul(31).wrapping_add(idx as u8))
+                .collect::<Vec<_>>();
+            let table = RouteTable::from_bytes(&data);
+            if let Ok(target) = table.target() {
+                for path in table.paths() {
+                    let _ = target.match_only(b"GET", path.as_bytes());
+                }
+                let _ = target.match_input(&data);
+            }
+        }
+    }
+}
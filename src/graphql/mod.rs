@@ -0,0 +1,236 @@
+//! A GraphQL endpoint built on [`async-graphql`](https://docs.rs/async-graphql). It requires the `graphql` feature.
+//!
+//! The [`route`](./fn.route.html) function creates a router to be mounted as a [scope](../struct.RouterBuilder.html#method.scope)
+//! which handles:
+//!
+//! * `POST` requests with a JSON body, a batch of queries or a
+//!   [multipart](https://github.com/jaydenseric/graphql-multipart-request-spec) body with file uploads.
+//! * `GET` requests with the query in the query string.
+//! * `GET` requests without a query with the GraphiQL playground, if it's enabled by the
+//!   [`GraphQL::graphiql`](./struct.GraphQL.html#method.graphiql) method.
+//!
+//! The resolvers can access the router [data](../struct.RouterBuilder.html#method.data) through the
+//! [`RouterData`](./struct.RouterData.html) in the GraphQL context.
+//!
+//! # Examples
+//!
+//! ```
+//! use async_graphql::{Context, EmptyMutation, EmptySubscription, Object, Schema};
+//! use routerify::graphql::{self, RouterData};
+//! use routerify::{Router, RouteError};
+//! use hyper::Body;
+//!
+//! struct Query;
+//!
+//! #[Object]
+//! impl Query {
+//!     async fn greeting(&self, ctx: &Context<'_>) -> String {
+//!         let greeting = ctx.data_unchecked::<RouterData>().get::<String>().unwrap();
+//!         greeting.clone()
+//!     }
+//! }
+//!
+//! # fn run() -> Router<Body, RouteError> {
+//! let schema = Schema::new(Query, EmptyMutation, EmptySubscription);
+//!
+//! let router = Router::builder()
+//!     .data("Hello world".to_owned())
+//!     .scope("/graphql", graphql::GraphQL::new(schema).graphiql(true).into_router())
+//!     .build()
+//!     .unwrap();
+//! # router
+//! # }
+//! # run();
+//! ```
+
+use crate::data_map::SharedDataMap;
+use crate::Router;
+use async_graphql::http::{GraphiQLSource, MultipartOptions};
+use async_graphql::{BatchRequest, Executor};
+use hyper::body::{Bytes, HttpBody};
+use hyper::header::{self, HeaderName, HeaderValue};
+use hyper::{Body, Request, Response, StatusCode};
+use std::fmt::{self, Debug, Formatter};
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio_util::compat::TokioAsyncReadCompatExt;
+use tokio_util::io::StreamReader;
+
+/// Creates a router which serves the GraphQL schema with the default options.
+///
+/// Please refer to the [module](./index.html) documentation for more info.
+pub fn route<X, E>(executor: X) -> Router<Body, E>
+where
+    X: Executor,
+    E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+{
+    GraphQL::new(executor).into_router()
+}
+
+/// The configuration of the GraphQL endpoint.
+///
+/// Please refer to the [module](./index.html) documentation for more info.
+#[derive(Clone)]
+pub struct GraphQL<X> {
+    executor: X,
+    graphiql: bool,
+    multipart: MultipartOptions,
+}
+
+impl<X: Executor> GraphQL<X> {
+    /// Creates a new endpoint of the GraphQL schema, or any other async-graphql executor.
+    pub fn new(executor: X) -> Self {
+        GraphQL {
+            executor,
+            graphiql: false,
+            multipart: MultipartOptions::default(),
+        }
+    }
+
+    /// Serves the GraphiQL playground for the `GET` requests without a query. Defaults to `false`.
+    pub fn graphiql(mut self, enabled: bool) -> Self {
+        self.graphiql = enabled;
+        self
+    }
+
+    /// Sets the limits of the file uploads of the multipart requests.
+    pub fn multipart_options(mut self, options: MultipartOptions) -> Self {
+        self.multipart = options;
+        self
+    }
+
+    /// Creates a router to be mounted as a scope.
+    pub fn into_router<E>(self) -> Router<Body, E>
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        let config = Arc::new(self);
+        let get_config = config.clone();
+
+        Router::builder()
+            .post("/", move |req| {
+                let config = config.clone();
+                async move { Ok(config.handle_post(req).await) }
+            })
+            .get("/", move |req| {
+                let config = get_config.clone();
+                async move { Ok(config.handle_get(req).await) }
+            })
+            .build()
+            .expect("Couldn't create the GraphQL router")
+    }
+
+    async fn handle_post(&self, req: Request<Body>) -> Response<Body> {
+        let content_type = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|val| val.to_str().ok())
+            .map(|val| val.to_owned());
+        let data = RouterData::from_request(&req);
+        let reader = StreamReader::new(BodyStream(req.into_body())).compat();
+
+        match async_graphql::http::receive_batch_body(content_type, reader, self.multipart).await {
+            Ok(batch) => self.execute(batch.data(data)).await,
+            Err(err) => error_response(err.to_string()),
+        }
+    }
+
+    async fn handle_get(&self, req: Request<Body>) -> Response<Body> {
+        let query = req.uri().query().unwrap_or("");
+        if query.is_empty() && self.graphiql {
+            let html = GraphiQLSource::build().endpoint(req.uri().path()).finish();
+            return Response::builder()
+                .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+                .body(Body::from(html))
+                .expect("Couldn't create the GraphiQL response");
+        }
+
+        match async_graphql::http::parse_query_string(query) {
+            Ok(request) => {
+                let data = RouterData::from_request(&req);
+                self.execute(BatchRequest::Single(request).data(data)).await
+            }
+            Err(err) => error_response(err.to_string()),
+        }
+    }
+
+    async fn execute(&self, batch: BatchRequest) -> Response<Body> {
+        let batch_resp = self.executor.execute_batch(batch).await;
+
+        let body = match serde_json::to_vec(&batch_resp) {
+            Ok(body) => body,
+            Err(err) => return error_response(format!("Couldn't encode the GraphQL response: {}", err)),
+        };
+
+        let mut resp = Response::new(Body::from(body));
+        let headers = resp.headers_mut();
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        for (name, value) in batch_resp.http_headers_iter() {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_str().as_bytes()),
+                HeaderValue::from_bytes(value.as_bytes()),
+            ) {
+                headers.append(name, value);
+            }
+        }
+        resp
+    }
+}
+
+impl<X> Debug for GraphQL<X> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{{ graphiql: {:?} }}", self.graphiql)
+    }
+}
+
+/// The router data of a GraphQL request, which is put into the context of the resolvers.
+#[derive(Clone)]
+pub struct RouterData {
+    shared_data_maps: Option<Arc<[SharedDataMap]>>,
+}
+
+impl RouterData {
+    fn from_request(req: &Request<Body>) -> Self {
+        RouterData {
+            shared_data_maps: req.extensions().get::<Arc<[SharedDataMap]>>().cloned(),
+        }
+    }
+
+    /// Returns the data of the specified type, looked up the same way as the
+    /// [`RequestExt::data`](../ext/trait.RequestExt.html#tymethod.data) method does.
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.shared_data_maps
+            .as_ref()?
+            .iter()
+            .find_map(|shared_data_map| shared_data_map.inner.get::<T>())
+    }
+}
+
+impl Debug for RouterData {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "RouterData")
+    }
+}
+
+fn error_response(msg: String) -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::BAD_REQUEST)
+        .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
+        .body(Body::from(msg))
+        .expect("Couldn't create the GraphQL error response")
+}
+
+// Streams the request body with I/O errors, as expected by the `StreamReader`.
+struct BodyStream(Body);
+
+impl futures_core::Stream for BodyStream {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.0)
+            .poll_data(cx)
+            .map(|chunk| chunk.map(|chunk| chunk.map_err(io::Error::other)))
+    }
+}
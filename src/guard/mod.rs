@@ -0,0 +1,197 @@
+//! Guards which decide if a request may reach the handler of a route.
+//!
+//! A [`Guard`](./trait.Guard.html) checks the parts of a request, after the pre middlewares have run and the route
+//! params are set, and either allows the request or denies it with an [`HttpError`](../struct.HttpError.html) e.g.
+//! `403 Forbidden`, which is handled by the error handler. The guards are attached to the routes at a path by the
+//! [`RouterBuilder::guard`](../struct.RouterBuilder.html#method.guard) method, and the guards of a scope's router
+//! stay on its routes once it's mounted.
+//!
+//! The guards are composed by the [`and`](./trait.Guard.html#method.and), [`or`](./trait.Guard.html#method.or) and
+//! [`not`](./trait.Guard.html#method.not) combinators. The context of the request, e.g. the authenticated
+//! [`Principal`](../struct.Principal.html), and the shared data are available through the
+//! [`RequestExt`](../ext/trait.RequestExt.html) methods of the parts.
+//!
+//! # Examples
+//!
+//! ```
+//! use routerify::{HttpError, Principal, Router};
+//! use routerify::guard::{Guard, GuardFuture, GuardOutcome};
+//! use routerify::prelude::*;
+//! use http::request::Parts;
+//! use hyper::{Response, Body, StatusCode};
+//! use std::convert::Infallible;
+//!
+//! struct Authenticated;
+//!
+//! impl Guard for Authenticated {
+//!     fn check<'a>(&'a self, req: &'a Parts) -> GuardFuture<'a> {
+//!         Box::pin(async move {
+//!             match req.context::<Principal>() {
+//!                 Some(_) => GuardOutcome::Allow,
+//!                 None => GuardOutcome::Deny(HttpError::new(StatusCode::UNAUTHORIZED, "Unauthorized")),
+//!             }
+//!         })
+//!     }
+//! }
+//!
+//! let internal = |req: &Parts| match req.headers.contains_key("x-internal") {
+//!     true => GuardOutcome::Allow,
+//!     false => GuardOutcome::Deny(HttpError::new(StatusCode::FORBIDDEN, "Forbidden")),
+//! };
+//!
+//! # fn run(internal: impl Guard) -> Router<Body, Infallible> {
+//! let router = Router::builder()
+//!     .get("/admin/stats", |_| async move { Ok(Response::new(Body::from("Stats"))) })
+//!     .guard("/admin/*", Authenticated.or(internal))
+//!     .build()
+//!     .unwrap();
+//! # router
+//! # }
+//! # run(internal);
+//! ```
+
+use crate::HttpError;
+use http::request::Parts;
+use hyper::StatusCode;
+use std::future::Future;
+use std::pin::Pin;
+
+/// The future returned by the [`Guard::check`](./trait.Guard.html#tymethod.check) method.
+pub type GuardFuture<'a> = Pin<Box<dyn Future<Output = GuardOutcome> + Send + 'a>>;
+
+/// The outcome of a guard check.
+#[derive(Debug)]
+pub enum GuardOutcome {
+    /// Lets the request reach the handler.
+    Allow,
+    /// Denies the request with the error.
+    Deny(HttpError),
+}
+
+impl GuardOutcome {
+    /// Returns `true` if the request is allowed.
+    pub fn is_allowed(&self) -> bool {
+        matches!(self, GuardOutcome::Allow)
+    }
+}
+
+/// A check of the requests of the routes it's attached to.
+///
+/// It's implemented for the synchronous functions of the form `Fn(&Parts) -> GuardOutcome`. Please refer to the
+/// [module](./index.html) documentation for more info.
+pub trait Guard: Send + Sync + 'static {
+    /// Checks the parts of a request.
+    fn check<'a>(&'a self, req: &'a Parts) -> GuardFuture<'a>;
+
+    /// Creates a guard which allows the requests allowed by both guards. The second guard isn't checked if the first
+    /// one denies the request.
+    fn and<G: Guard>(self, other: G) -> And<Self, G>
+    where
+        Self: Sized,
+    {
+        And(self, other)
+    }
+
+    /// Creates a guard which allows the requests allowed by either guard. The second guard isn't checked if the first
+    /// one allows the request, and the request is denied with the error of the second one.
+    fn or<G: Guard>(self, other: G) -> Or<Self, G>
+    where
+        Self: Sized,
+    {
+        Or(self, other)
+    }
+
+    /// Creates a guard which allows the requests denied by the guard, and denies the rest with `403 Forbidden`.
+    fn not(self) -> Not<Self>
+    where
+        Self: Sized,
+    {
+        Not(self)
+    }
+}
+
+impl<F> Guard for F
+where
+    F: Fn(&Parts) -> GuardOutcome + Send + Sync + 'static,
+{
+    fn check<'a>(&'a self, req: &'a Parts) -> GuardFuture<'a> {
+        let outcome = self(req);
+        Box::pin(async move { outcome })
+    }
+}
+
+/// The guard created by the [`Guard::and`](./trait.Guard.html#method.and) method.
+#[derive(Debug, Clone)]
+pub struct And<A, B>(A, B);
+
+impl<A: Guard, B: Guard> Guard for And<A, B> {
+    fn check<'a>(&'a self, req: &'a Parts) -> GuardFuture<'a> {
+        Box::pin(async move {
+            match self.0.check(req).await {
+                GuardOutcome::Allow => self.1.check(req).await,
+                denied => denied,
+            }
+        })
+    }
+}
+
+/// The guard created by the [`Guard::or`](./trait.Guard.html#method.or) method.
+#[derive(Debug, Clone)]
+pub struct Or<A, B>(A, B);
+
+impl<A: Guard, B: Guard> Guard for Or<A, B> {
+    fn check<'a>(&'a self, req: &'a Parts) -> GuardFuture<'a> {
+        Box::pin(async move {
+            match self.0.check(req).await {
+                GuardOutcome::Allow => GuardOutcome::Allow,
+                GuardOutcome::Deny(_) => self.1.check(req).await,
+            }
+        })
+    }
+}
+
+/// The guard created by the [`Guard::not`](./trait.Guard.html#method.not) method.
+#[derive(Debug, Clone)]
+pub struct Not<G>(G);
+
+impl<G: Guard> Guard for Not<G> {
+    fn check<'a>(&'a self, req: &'a Parts) -> GuardFuture<'a> {
+        Box::pin(async move {
+            match self.0.check(req).await {
+                GuardOutcome::Allow => GuardOutcome::Deny(HttpError::new(StatusCode::FORBIDDEN, "Forbidden")),
+                GuardOutcome::Deny(_) => GuardOutcome::Allow,
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::Request;
+
+    fn header(name: &'static str) -> impl Guard {
+        move |req: &Parts| {
+            if req.headers.contains_key(name) {
+                GuardOutcome::Allow
+            } else {
+                GuardOutcome::Deny(HttpError::new(StatusCode::FORBIDDEN, name))
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn combines_guards() {
+        let (parts, _) = Request::get("/").header("x-a", "1").body(()).unwrap().into_parts();
+
+        assert!(header("x-a").and(header("x-a")).check(&parts).await.is_allowed());
+        assert!(header("x-b").or(header("x-a")).check(&parts).await.is_allowed());
+        assert!(header("x-b").not().check(&parts).await.is_allowed());
+        assert!(!header("x-a").not().check(&parts).await.is_allowed());
+
+        match header("x-a").and(header("x-b")).or(header("x-c")).check(&parts).await {
+            GuardOutcome::Deny(err) => assert_eq!(err.message(), "x-c"),
+            GuardOutcome::Allow => panic!("should be denied"),
+        }
+    }
+}
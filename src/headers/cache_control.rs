@@ -0,0 +1,193 @@
+use hyper::header::HeaderValue;
+use std::fmt::{self, Display, Formatter};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Visibility {
+    Public,
+    Private,
+}
+
+/// A builder for the `Cache-Control` response header.
+///
+/// It can be set on the responses by the [`cache_control_for`](../middleware/fn.cache_control_for.html) post middleware
+/// or it can be converted into a [`HeaderValue`](https://docs.rs/http/0.2/http/header/struct.HeaderValue.html) directly.
+///
+/// # Examples
+///
+/// ```
+/// use routerify::headers::CacheControl;
+///
+/// assert_eq!(CacheControl::public().max_age(86400).immutable().to_string(), "public, max-age=86400, immutable");
+/// assert_eq!(CacheControl::private().no_cache().to_string(), "private, no-cache");
+/// assert_eq!(CacheControl::no_store().to_string(), "no-store");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CacheControl {
+    visibility: Option<Visibility>,
+    no_cache: bool,
+    no_store: bool,
+    no_transform: bool,
+    must_revalidate: bool,
+    proxy_revalidate: bool,
+    immutable: bool,
+    max_age: Option<u64>,
+    s_max_age: Option<u64>,
+    stale_while_revalidate: Option<u64>,
+    stale_if_error: Option<u64>,
+}
+
+impl CacheControl {
+    /// Creates an empty `CacheControl` without any directive.
+    pub fn new() -> CacheControl {
+        CacheControl::default()
+    }
+
+    /// Creates a `CacheControl` with the `public` directive i.e. the response can be stored by any cache.
+    pub fn public() -> CacheControl {
+        CacheControl {
+            visibility: Some(Visibility::Public),
+            ..CacheControl::default()
+        }
+    }
+
+    /// Creates a `CacheControl` with the `private` directive i.e. the response can be stored only by the browser cache.
+    pub fn private() -> CacheControl {
+        CacheControl {
+            visibility: Some(Visibility::Private),
+            ..CacheControl::default()
+        }
+    }
+
+    /// Creates a `CacheControl` with the `no-store` directive i.e. the response must not be stored by any cache.
+    pub fn no_store() -> CacheControl {
+        CacheControl {
+            no_store: true,
+            ..CacheControl::default()
+        }
+    }
+
+    /// Adds the `no-cache` directive i.e. the response must be revalidated before each reuse.
+    pub fn no_cache(mut self) -> Self {
+        self.no_cache = true;
+        self
+    }
+
+    /// Adds the `no-transform` directive.
+    pub fn no_transform(mut self) -> Self {
+        self.no_transform = true;
+        self
+    }
+
+    /// Adds the `must-revalidate` directive.
+    pub fn must_revalidate(mut self) -> Self {
+        self.must_revalidate = true;
+        self
+    }
+
+    /// Adds the `proxy-revalidate` directive.
+    pub fn proxy_revalidate(mut self) -> Self {
+        self.proxy_revalidate = true;
+        self
+    }
+
+    /// Adds the `immutable` directive i.e. the response will not change while it's fresh.
+    pub fn immutable(mut self) -> Self {
+        self.immutable = true;
+        self
+    }
+
+    /// Sets the `max-age` directive in seconds.
+    pub fn max_age(mut self, secs: u64) -> Self {
+        self.max_age = Some(secs);
+        self
+    }
+
+    /// Sets the `s-maxage` directive in seconds which applies only to the shared caches.
+    pub fn s_max_age(mut self, secs: u64) -> Self {
+        self.s_max_age = Some(secs);
+        self
+    }
+
+    /// Sets the `stale-while-revalidate` directive in seconds.
+    pub fn stale_while_revalidate(mut self, secs: u64) -> Self {
+        self.stale_while_revalidate = Some(secs);
+        self
+    }
+
+    /// Sets the `stale-if-error` directive in seconds.
+    pub fn stale_if_error(mut self, secs: u64) -> Self {
+        self.stale_if_error = Some(secs);
+        self
+    }
+
+    /// Converts the directives into a `Cache-Control` header value.
+    pub fn to_header_value(&self) -> HeaderValue {
+        HeaderValue::from_str(&self.to_string()).expect("Cache-Control directives are valid header characters")
+    }
+}
+
+impl Display for CacheControl {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut directives: Vec<String> = Vec::new();
+
+        match self.visibility {
+            Some(Visibility::Public) => directives.push("public".to_owned()),
+            Some(Visibility::Private) => directives.push("private".to_owned()),
+            None => {}
+        }
+
+        let flags = [
+            (self.no_cache, "no-cache"),
+            (self.no_store, "no-store"),
+            (self.no_transform, "no-transform"),
+            (self.must_revalidate, "must-revalidate"),
+            (self.proxy_revalidate, "proxy-revalidate"),
+        ];
+        directives.extend(flags.iter().filter(|(set, _)| *set).map(|(_, name)| (*name).to_owned()));
+
+        let values = [
+            (self.max_age, "max-age"),
+            (self.s_max_age, "s-maxage"),
+            (self.stale_while_revalidate, "stale-while-revalidate"),
+            (self.stale_if_error, "stale-if-error"),
+        ];
+        directives.extend(
+            values
+                .iter()
+                .filter_map(|(val, name)| val.map(|val| format!("{}={}", name, val))),
+        );
+
+        if self.immutable {
+            directives.push("immutable".to_owned());
+        }
+
+        write!(f, "{}", directives.join(", "))
+    }
+}
+
+impl From<CacheControl> for HeaderValue {
+    fn from(cc: CacheControl) -> Self {
+        cc.to_header_value()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_directives_in_order() {
+        let cc = CacheControl::public()
+            .immutable()
+            .max_age(60)
+            .s_max_age(120)
+            .must_revalidate()
+            .stale_if_error(30);
+        assert_eq!(
+            cc.to_string(),
+            "public, must-revalidate, max-age=60, s-maxage=120, stale-if-error=30, immutable"
+        );
+        assert_eq!(CacheControl::new().to_string(), "");
+        assert_eq!(HeaderValue::from(CacheControl::no_store()), "no-store");
+    }
+}
@@ -0,0 +1,20 @@
+//! Typed builders and parsers for common HTTP headers.
+//!
+//! With the `typed-headers` feature, the [`typed`](./typed/index.html) module decodes the common request headers.
+//!
+//! # Examples
+//!
+//! ```
+//! use routerify::headers::CacheControl;
+//!
+//! let cc = CacheControl::public().max_age(86400).immutable();
+//! assert_eq!(cc.to_string(), "public, max-age=86400, immutable");
+//! ```
+
+pub use cache_control::CacheControl;
+pub use preconditions::Preconditions;
+
+mod cache_control;
+mod preconditions;
+#[cfg(feature = "typed-headers")]
+pub mod typed;
@@ -0,0 +1,290 @@
+use crate::helpers;
+use crate::HttpError;
+use hyper::header::{self, HeaderMap};
+use hyper::StatusCode;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Tags {
+    Any,
+    // The entity tags with the quotes and the weak indicator, e.g. `W/"v1"`.
+    List(Vec<String>),
+}
+
+impl Tags {
+    fn parse(headers: &HeaderMap, name: header::HeaderName) -> Option<Tags> {
+        let mut tags = Vec::new();
+        for val in headers.get_all(name).iter() {
+            let mut val = val.to_str().ok()?.trim();
+            if val == "*" {
+                return Some(Tags::Any);
+            }
+
+            while !val.is_empty() {
+                let weak = val.starts_with("W/");
+                let rest = val.strip_prefix("W/").unwrap_or(val).strip_prefix('"')?;
+                let end = rest.find('"')?;
+                tags.push(format!("{}\"{}\"", if weak { "W/" } else { "" }, &rest[..end]));
+                val = rest[end + 1..].trim_start();
+                val = val.strip_prefix(',').unwrap_or(val).trim_start();
+            }
+        }
+
+        if tags.is_empty() {
+            None
+        } else {
+            Some(Tags::List(tags))
+        }
+    }
+
+    fn matches(&self, etag: Option<&str>, weak: bool) -> bool {
+        let etag = match etag {
+            Some(etag) => etag,
+            None => return false,
+        };
+
+        match self {
+            Tags::Any => true,
+            Tags::List(tags) => tags.iter().any(|tag| {
+                if weak {
+                    opaque(tag) == opaque(etag)
+                } else {
+                    !is_weak(tag) && !is_weak(etag) && tag == etag
+                }
+            }),
+        }
+    }
+}
+
+fn is_weak(tag: &str) -> bool {
+    tag.starts_with("W/")
+}
+
+fn opaque(tag: &str) -> &str {
+    tag.strip_prefix("W/").unwrap_or(tag)
+}
+
+/// The preconditions of a state changing request e.g. a `PUT` which updates a resource only if it wasn't modified
+/// since the client read it.
+///
+/// They are parsed from the `If-Match`, `If-None-Match` and `If-Unmodified-Since` request headers, and are evaluated
+/// against the current entity tag and the modification time of the resource by the order of RFC 9110. A failed
+/// precondition is reported as `412 Precondition Failed`, either as an [`HttpError`](../struct.HttpError.html) by the
+/// [`evaluate`](#method.evaluate) method or as a response by the
+/// [`precondition_failed`](../responses/fn.precondition_failed.html) function.
+///
+/// The conditional `GET` and `HEAD` requests, i.e. the `If-None-Match` and the `If-Modified-Since` headers, are
+/// evaluated by the [`is_not_modified`](#method.is_not_modified) method instead.
+///
+/// # Examples
+///
+/// ```
+/// use routerify::{Router, RouteError};
+/// use routerify::headers::Preconditions;
+/// use routerify::responses;
+/// use hyper::{Response, Body};
+///
+/// # fn current_etag() -> Option<String> { Some("\"v2\"".to_owned()) }
+/// # fn run() -> Router<Body, RouteError> {
+/// let router = Router::builder()
+///     .put("/documents/:id", |req| async move {
+///         let preconditions = Preconditions::from_headers(req.headers());
+///         preconditions.require()?;
+///
+///         let etag = current_etag();
+///         if !preconditions.passes(etag.as_deref(), None) {
+///             return Ok(responses::precondition_failed(etag.as_deref()));
+///         }
+///
+///         // Update the document.
+///         Ok(Response::new(Body::from("Updated")))
+///     })
+///     .build()
+///     .unwrap();
+/// # router
+/// # }
+/// # run();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Preconditions {
+    if_match: Option<Tags>,
+    if_none_match: Option<Tags>,
+    if_unmodified_since: Option<SystemTime>,
+    if_modified_since: Option<SystemTime>,
+}
+
+impl Preconditions {
+    /// Parses the preconditions of the request headers. The malformed headers are ignored.
+    pub fn from_headers(headers: &HeaderMap) -> Preconditions {
+        Preconditions {
+            if_match: Tags::parse(headers, header::IF_MATCH),
+            if_none_match: Tags::parse(headers, header::IF_NONE_MATCH),
+            if_unmodified_since: headers
+                .get(header::IF_UNMODIFIED_SINCE)
+                .and_then(|val| val.to_str().ok())
+                .and_then(helpers::parse_http_date),
+            if_modified_since: headers
+                .get(header::IF_MODIFIED_SINCE)
+                .and_then(|val| val.to_str().ok())
+                .and_then(helpers::parse_http_date),
+        }
+    }
+
+    /// Returns `true` if the request has no precondition of a state changing request, i.e. no `If-Match`,
+    /// `If-None-Match` or `If-Unmodified-Since` header.
+    pub fn is_empty(&self) -> bool {
+        self.if_match.is_none() && self.if_none_match.is_none() && self.if_unmodified_since.is_none()
+    }
+
+    /// Returns the time of the `If-Unmodified-Since` header.
+    pub fn if_unmodified_since(&self) -> Option<SystemTime> {
+        self.if_unmodified_since
+    }
+
+    /// Returns the time of the `If-Modified-Since` header.
+    pub fn if_modified_since(&self) -> Option<SystemTime> {
+        self.if_modified_since
+    }
+
+    /// Checks that the request has an `If-Match` or an `If-Unmodified-Since` precondition, so a lost update is
+    /// prevented. It fails with an [`HttpError`](../struct.HttpError.html) of status `428 Precondition Required`.
+    pub fn require(&self) -> Result<(), HttpError> {
+        if self.if_match.is_some() || self.if_unmodified_since.is_some() {
+            Ok(())
+        } else {
+            Err(HttpError::new(
+                StatusCode::PRECONDITION_REQUIRED,
+                "The request must have an If-Match or an If-Unmodified-Since header",
+            ))
+        }
+    }
+
+    /// Checks the preconditions against the current entity tag of the resource e.g. `"v2"` or `W/"v2"`, and its
+    /// modification time. The entity tag is `None` if the resource doesn't exist.
+    pub fn passes(&self, etag: Option<&str>, last_modified: Option<SystemTime>) -> bool {
+        if let Some(ref if_match) = self.if_match {
+            if !if_match.matches(etag, false) {
+                return false;
+            }
+        } else if let (Some(since), Some(last_modified)) = (self.if_unmodified_since, last_modified) {
+            // The HTTP dates have a precision of one second.
+            if truncate(last_modified) > since {
+                return false;
+            }
+        }
+
+        match self.if_none_match {
+            Some(ref if_none_match) => !if_none_match.matches(etag, true),
+            None => true,
+        }
+    }
+
+    /// Checks if the representation of a `GET` or a `HEAD` request, with the current entity tag and modification time,
+    /// is the one the client has, so it can be answered with `304 Not Modified`. The `If-Modified-Since` header is
+    /// ignored if the request has an `If-None-Match` header.
+    pub fn is_not_modified(&self, etag: Option<&str>, last_modified: Option<SystemTime>) -> bool {
+        if let Some(ref if_none_match) = self.if_none_match {
+            return if_none_match.matches(etag, true);
+        }
+
+        match (self.if_modified_since, last_modified) {
+            (Some(since), Some(last_modified)) => truncate(last_modified) <= since,
+            _ => false,
+        }
+    }
+
+    /// Checks the preconditions like the [`passes`](#method.passes) method, but fails with an
+    /// [`HttpError`](../struct.HttpError.html) of status `412 Precondition Failed`.
+    pub fn evaluate(&self, etag: Option<&str>, last_modified: Option<SystemTime>) -> Result<(), HttpError> {
+        if self.passes(etag, last_modified) {
+            Ok(())
+        } else {
+            Err(HttpError::new(StatusCode::PRECONDITION_FAILED, "Precondition Failed"))
+        }
+    }
+}
+
+fn truncate(time: SystemTime) -> SystemTime {
+    let secs = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::HeaderValue;
+
+    fn preconditions(name: &'static str, val: &'static str) -> Preconditions {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_static(val));
+        Preconditions::from_headers(&headers)
+    }
+
+    #[test]
+    fn evaluates_if_match() {
+        let pre = preconditions("if-match", r#""v1", W/"v2", "a,b""#);
+        assert!(pre.passes(Some(r#""v1""#), None));
+        assert!(pre.passes(Some(r#""a,b""#), None));
+        assert!(!pre.passes(Some(r#"W/"v2""#), None));
+        assert!(!pre.passes(Some(r#""v3""#), None));
+        assert!(!pre.passes(None, None));
+
+        let pre = preconditions("if-match", "*");
+        assert!(pre.passes(Some(r#""v1""#), None));
+        assert!(!pre.passes(None, None));
+
+        assert!(preconditions("if-match", "v1").is_empty());
+    }
+
+    #[test]
+    fn evaluates_if_none_match() {
+        let pre = preconditions("if-none-match", "*");
+        assert!(pre.passes(None, None));
+        assert!(!pre.passes(Some(r#""v1""#), None));
+
+        let pre = preconditions("if-none-match", r#"W/"v1""#);
+        assert!(!pre.passes(Some(r#""v1""#), None));
+        assert!(pre.passes(Some(r#""v2""#), None));
+    }
+
+    #[test]
+    fn evaluates_if_unmodified_since() {
+        let pre = preconditions("if-unmodified-since", "Sun, 06 Nov 1994 08:49:37 GMT");
+        let since = UNIX_EPOCH + Duration::from_secs(784_111_777);
+        assert_eq!(pre.if_unmodified_since(), Some(since));
+        assert!(pre.passes(None, Some(since + Duration::from_millis(500))));
+        assert!(!pre.passes(None, Some(since + Duration::from_secs(1))));
+        assert!(pre.require().is_ok());
+
+        assert_eq!(
+            pre.evaluate(None, Some(since + Duration::from_secs(1)))
+                .unwrap_err()
+                .status(),
+            StatusCode::PRECONDITION_FAILED
+        );
+        assert_eq!(
+            Preconditions::default().require().unwrap_err().status(),
+            StatusCode::PRECONDITION_REQUIRED
+        );
+    }
+
+    #[test]
+    fn evaluates_conditional_gets() {
+        let pre = preconditions("if-modified-since", "Sun, 06 Nov 1994 08:49:37 GMT");
+        let since = UNIX_EPOCH + Duration::from_secs(784_111_777);
+        assert!(pre.is_empty());
+        assert!(pre.is_not_modified(None, Some(since + Duration::from_millis(500))));
+        assert!(!pre.is_not_modified(None, Some(since + Duration::from_secs(1))));
+        assert!(!pre.is_not_modified(None, None));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("if-none-match", HeaderValue::from_static(r#"W/"v1""#));
+        headers.insert(
+            "if-modified-since",
+            HeaderValue::from_static("Sun, 06 Nov 1994 08:49:37 GMT"),
+        );
+        let pre = Preconditions::from_headers(&headers);
+        assert!(pre.is_not_modified(Some(r#""v1""#), None));
+        assert!(!pre.is_not_modified(Some(r#""v2""#), Some(since)));
+    }
+}
@@ -0,0 +1,110 @@
+//! Typed access to the common request headers, built on the [`headers`](https://docs.rs/headers/0.3) crate. It requires
+//! the `typed-headers` feature.
+//!
+//! The [`RequestExt::typed_header`](../../ext/trait.RequestExt.html#tymethod.typed_header) method decodes a header of
+//! any [`Header`](./trait.Header.html) type e.g. [`ContentType`](./struct.ContentType.html),
+//! [`Authorization`](./struct.Authorization.html), [`Range`](./struct.Range.html) or
+//! [`IfNoneMatch`](./struct.IfNoneMatch.html). It's `Ok(None)` if the request has no such header, and it fails with an
+//! [`HttpError`](../../struct.HttpError.html) of status `400 Bad Request` if the header is invalid, or
+//! `431 Request Header Fields Too Large` if the header has more than 32 lines or 8 KiB, so a request can't make the
+//! handlers decode arbitrarily long header lists.
+//!
+//! # Examples
+//!
+//! ```
+//! use routerify::{Router, RouteError};
+//! use routerify::ext::RequestExt;
+//! use routerify::headers::typed::{Authorization, Bearer, ContentType};
+//! use hyper::{Body, Response, StatusCode};
+//!
+//! # fn run() -> Router<Body, RouteError> {
+//! let router = Router::builder()
+//!     .post("/orders", |req| async move {
+//!         if req.typed_header::<Authorization<Bearer>>()?.is_none() {
+//!             return Ok(Response::builder().status(StatusCode::UNAUTHORIZED).body(Body::empty()).unwrap());
+//!         }
+//!         if req.typed_header::<ContentType>()? != Some(ContentType::json()) {
+//!             return Ok(Response::builder().status(StatusCode::UNSUPPORTED_MEDIA_TYPE).body(Body::empty()).unwrap());
+//!         }
+//!         Ok(Response::new(Body::from("Created")))
+//!     })
+//!     .build()
+//!     .unwrap();
+//! # router
+//! # }
+//! # run();
+//! ```
+
+use crate::HttpError;
+use hyper::header::HeaderMap;
+use hyper::StatusCode;
+
+pub use ::headers::authorization::{Basic, Bearer, Credentials};
+pub use ::headers::{Authorization, ContentType, Header, IfNoneMatch, Range};
+
+const MAX_LINES: usize = 32;
+const MAX_BYTES: usize = 8 * 1024;
+
+pub(crate) fn typed_header<H: Header>(headers: &HeaderMap) -> Result<Option<H>, HttpError> {
+    let values = headers.get_all(H::name());
+    let mut lines = 0;
+    let mut bytes = 0;
+    for val in values.iter() {
+        lines += 1;
+        bytes += val.len();
+        if lines > MAX_LINES || bytes > MAX_BYTES {
+            return Err(HttpError::new(
+                StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
+                format!("The {} header is too large", H::name()),
+            ));
+        }
+    }
+    if lines == 0 {
+        return Ok(None);
+    }
+
+    H::decode(&mut values.iter())
+        .map(Some)
+        .map_err(|_| HttpError::new(StatusCode::BAD_REQUEST, format!("The {} header is invalid", H::name())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::{self, HeaderValue};
+
+    #[test]
+    fn decodes_typed_headers() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(typed_header::<ContentType>(&headers).unwrap(), None);
+
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_static("Bearer abc"));
+        headers.insert(header::RANGE, HeaderValue::from_static("bytes=0-9"));
+        assert_eq!(
+            typed_header::<ContentType>(&headers).unwrap(),
+            Some(ContentType::json())
+        );
+        assert_eq!(
+            typed_header::<Authorization<Bearer>>(&headers)
+                .unwrap()
+                .unwrap()
+                .token(),
+            "abc"
+        );
+        assert_eq!(
+            typed_header::<Range>(&headers).unwrap(),
+            Some(Range::bytes(0..10).unwrap())
+        );
+
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("not a mime"));
+        let err = typed_header::<ContentType>(&headers).unwrap_err();
+        assert_eq!(err.status(), StatusCode::BAD_REQUEST);
+
+        for _ in 0..=MAX_LINES {
+            headers.append(header::IF_NONE_MATCH, HeaderValue::from_static("\"v1\""));
+        }
+        let err = typed_header::<IfNoneMatch>(&headers).unwrap_err();
+        assert_eq!(err.status(), StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE);
+    }
+}
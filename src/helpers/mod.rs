@@ -1,7 +1,10 @@
 use crate::types::RequestMeta;
 use crate::Error;
+use http::header::{self, HeaderMap, HeaderValue};
 use http::Extensions;
 use percent_encoding::percent_decode_str;
+use std::fmt::Write;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 pub(crate) fn update_req_meta_in_extensions(ext: &mut Extensions, new_req_meta: RequestMeta) {
     if let Some(existing_req_meta) = ext.get_mut::<RequestMeta>() {
@@ -11,6 +14,45 @@ pub(crate) fn update_req_meta_in_extensions(ext: &mut Extensions, new_req_meta:
     }
 }
 
+// Returns the path matched by the `*` segment of a route. The matched path always has a trailing slash, which only
+// names a directory if the request path has it too.
+#[cfg(any(feature = "fs", feature = "embed"))]
+pub(crate) fn wildcard_path(req: &hyper::Request<hyper::Body>) -> &str {
+    use crate::ext::RequestExt;
+
+    let path = req.param("*").map(String::as_str).unwrap_or("");
+    if req.uri().path().ends_with('/') {
+        path
+    } else {
+        path.trim_end_matches('/')
+    }
+}
+
+// Copies a bodiless request with its route params, context and shared data, so a handler can be run with it again in
+// the background. The copy gets its own cancellation token, as the client of the request may go away meanwhile.
+pub(crate) fn replay_request(req: &hyper::Request<hyper::Body>) -> hyper::Request<hyper::Body> {
+    let mut copy = hyper::Request::new(hyper::Body::empty());
+    *copy.method_mut() = req.method().clone();
+    *copy.uri_mut() = req.uri().clone();
+    *copy.version_mut() = req.version();
+    *copy.headers_mut() = req.headers().clone();
+
+    let ext = req.extensions();
+    if let Some(meta) = ext.get::<RequestMeta>() {
+        let meta = meta
+            .clone()
+            .with_cancellation_token(tokio_util::sync::CancellationToken::new());
+        copy.extensions_mut().insert(meta);
+    }
+    if let Some(context) = ext.get::<crate::types::RequestContext>() {
+        copy.extensions_mut().insert(context.clone());
+    }
+    if let Some(data_maps) = ext.get::<std::sync::Arc<[crate::data_map::SharedDataMap]>>() {
+        copy.extensions_mut().insert(data_maps.clone());
+    }
+    copy
+}
+
 pub(crate) fn percent_decode_request_path(val: &str) -> crate::Result<String> {
     percent_decode_str(val)
         .decode_utf8()
@@ -18,6 +60,211 @@ pub(crate) fn percent_decode_request_path(val: &str) -> crate::Result<String> {
         .map(|val| val.to_string())
 }
 
+// Strips the base path from a request path which ends with a slash. It's `None` if the path is outside of the base path.
+pub(crate) fn strip_base_path<'a>(path: &'a str, base_path: &str) -> Option<&'a str> {
+    match path.strip_prefix(base_path) {
+        Some(stripped_path) if stripped_path.starts_with('/') => Some(stripped_path),
+        _ => None,
+    }
+}
+
+// Returns the path which the routes are matched by, i.e. the percent decoded request path with a trailing slash and
+// without the base path, and whether the base path is stripped.
+pub(crate) fn route_target_path(path: &str, base_path: Option<&str>) -> crate::Result<(String, bool)> {
+    let mut target_path = percent_decode_request_path(path)
+        .map_err(|e| Error::new(format!("Couldn't percent decode request path: {}", e)))?;
+
+    if target_path.is_empty() || target_path.as_bytes()[target_path.len() - 1] != b'/' {
+        target_path.push('/');
+    }
+
+    if let Some(base_path) = base_path {
+        if let Some(stripped_path) = strip_base_path(&target_path, base_path) {
+            return Ok((stripped_path.to_owned(), true));
+        }
+    }
+    Ok((target_path, false))
+}
+
+// Removes the `h2c` upgrade offer, which isn't accepted, from the headers of an HTTP/1 request, so the request is served
+// over HTTP/1.1 and the handlers don't see the offer. The clients fall back to HTTP/1.1 when it's declined.
+pub(crate) fn decline_h2c_upgrade(headers: &mut HeaderMap) {
+    let upgrade = match headers.get(header::UPGRADE).and_then(|val| val.to_str().ok()) {
+        Some(upgrade) => upgrade,
+        None => return,
+    };
+
+    let protocols = upgrade
+        .split(',')
+        .map(str::trim)
+        .filter(|protocol| !protocol.is_empty())
+        .collect::<Vec<_>>();
+    if !protocols.iter().any(|protocol| protocol.eq_ignore_ascii_case("h2c")) {
+        return;
+    }
+
+    let rest = protocols
+        .into_iter()
+        .filter(|protocol| !protocol.eq_ignore_ascii_case("h2c"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let is_upgrade = !rest.is_empty();
+    match HeaderValue::from_str(&rest) {
+        Ok(val) if is_upgrade => {
+            headers.insert(header::UPGRADE, val);
+        }
+        _ => {
+            headers.remove(header::UPGRADE);
+        }
+    }
+    headers.remove("http2-settings");
+
+    let connection = headers
+        .get_all(header::CONNECTION)
+        .iter()
+        .filter_map(|val| val.to_str().ok())
+        .flat_map(|val| val.split(','))
+        .map(str::trim)
+        .filter(|option| !option.is_empty())
+        .filter(|option| !option.eq_ignore_ascii_case("http2-settings"))
+        .filter(|option| is_upgrade || !option.eq_ignore_ascii_case("upgrade"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    match HeaderValue::from_str(&connection) {
+        Ok(val) if !connection.is_empty() => {
+            headers.insert(header::CONNECTION, val);
+        }
+        _ => {
+            headers.remove(header::CONNECTION);
+        }
+    }
+}
+
+// Encodes a string as a JSON string literal.
+pub(crate) fn json_str(val: &str) -> String {
+    let mut out = String::with_capacity(val.len() + 2);
+    out.push('"');
+    for c in val.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// Escapes a string to be embedded into an HTML text or an attribute value.
+pub(crate) fn html_escape(val: &str) -> String {
+    let mut escaped = String::with_capacity(val.len());
+    for c in val.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+// Formats a time as an HTTP date, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`.
+pub(crate) fn http_date(time: SystemTime) -> String {
+    const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+
+    let secs = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+    let days = secs / 86_400;
+    let secs_of_day = secs % 86_400;
+
+    // The civil date of the days since the epoch, by Howard Hinnant's algorithm.
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        WEEKDAYS[(days % 7) as usize],
+        day,
+        MONTHS[(month - 1) as usize],
+        year,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60
+    )
+}
+
+// Parses an HTTP date in the preferred format, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`. The weekday isn't checked.
+pub(crate) fn parse_http_date(val: &str) -> Option<SystemTime> {
+    let mut parts = val.trim().split(' ');
+    let (_weekday, day, month, year, time, zone) = (
+        parts.next()?,
+        parts.next()?,
+        parts.next()?,
+        parts.next()?,
+        parts.next()?,
+        parts.next()?,
+    );
+    if parts.next().is_some() || zone != "GMT" || day.len() != 2 || year.len() != 4 {
+        return None;
+    }
+
+    let day = day.parse::<u64>().ok().filter(|day| (1..=31).contains(day))?;
+    let month = MONTHS.iter().position(|m| *m == month)? as u64 + 1;
+    let year = year.parse::<u64>().ok().filter(|year| *year >= 1970)?;
+
+    let mut time = time
+        .split(':')
+        .map(|val| val.parse::<u64>().ok().filter(|_| val.len() == 2));
+    let (hour, min, sec) = (time.next()??, time.next()??, time.next()??);
+    if time.next().is_some() || hour > 23 || min > 59 || sec > 60 {
+        return None;
+    }
+
+    // The days since the epoch of the civil date, by Howard Hinnant's algorithm.
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y / 400;
+    let yoe = y - era * 400;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = (era * 146_097 + doe).checked_sub(719_468)?;
+
+    Some(UNIX_EPOCH + Duration::from_secs(days * 86_400 + hour * 3600 + min * 60 + sec))
+}
+
+// Generates a hex encoded random string of `len` bytes.
+pub(crate) fn random_hex(len: usize) -> String {
+    let mut buf = vec![0_u8; len];
+    getrandom::getrandom(&mut buf).expect("The OS random number generator is unavailable");
+    buf.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// Generates a random number.
+pub(crate) fn random_u64() -> u64 {
+    let mut buf = [0_u8; 8];
+    getrandom::getrandom(&mut buf).expect("The OS random number generator is unavailable");
+    u64::from_le_bytes(buf)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -39,4 +286,67 @@ mod tests {
         let val = "go%crazy";
         assert_eq!(percent_decode_request_path(val).unwrap(), "go%crazy".to_owned());
     }
+
+    #[test]
+    fn test_strip_base_path() {
+        assert_eq!(strip_base_path("/myapp/", "/myapp"), Some("/"));
+        assert_eq!(strip_base_path("/myapp/users/", "/myapp"), Some("/users/"));
+        assert_eq!(strip_base_path("/myapplication/", "/myapp"), None);
+        assert_eq!(strip_base_path("/users/", "/myapp"), None);
+    }
+
+    #[test]
+    fn test_decline_h2c_upgrade() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONNECTION, HeaderValue::from_static("Upgrade, HTTP2-Settings"));
+        headers.insert(header::UPGRADE, HeaderValue::from_static("h2c"));
+        headers.insert("http2-settings", HeaderValue::from_static("AAMAAABkAARAAAAAAAIAAAAA"));
+        decline_h2c_upgrade(&mut headers);
+        assert!(headers.is_empty());
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONNECTION, HeaderValue::from_static("keep-alive, upgrade"));
+        headers.insert(header::UPGRADE, HeaderValue::from_static("h2c, websocket"));
+        decline_h2c_upgrade(&mut headers);
+        assert_eq!(headers[header::UPGRADE], "websocket");
+        assert_eq!(headers[header::CONNECTION], "keep-alive, upgrade");
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONNECTION, HeaderValue::from_static("upgrade"));
+        headers.insert(header::UPGRADE, HeaderValue::from_static("websocket"));
+        decline_h2c_upgrade(&mut headers);
+        assert_eq!(headers[header::UPGRADE], "websocket");
+        assert_eq!(headers[header::CONNECTION], "upgrade");
+    }
+
+    #[test]
+    fn test_http_date() {
+        use std::time::Duration;
+
+        assert_eq!(http_date(UNIX_EPOCH), "Thu, 01 Jan 1970 00:00:00 GMT");
+        assert_eq!(
+            http_date(UNIX_EPOCH + Duration::from_secs(784_111_777)),
+            "Sun, 06 Nov 1994 08:49:37 GMT"
+        );
+        assert_eq!(
+            http_date(UNIX_EPOCH + Duration::from_secs(1_709_251_199)),
+            "Thu, 29 Feb 2024 23:59:59 GMT"
+        );
+    }
+
+    #[test]
+    fn test_parse_http_date() {
+        assert_eq!(parse_http_date("Thu, 01 Jan 1970 00:00:00 GMT"), Some(UNIX_EPOCH));
+        assert_eq!(
+            parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"),
+            Some(UNIX_EPOCH + Duration::from_secs(784_111_777))
+        );
+        assert_eq!(
+            parse_http_date("Thu, 29 Feb 2024 23:59:59 GMT"),
+            Some(UNIX_EPOCH + Duration::from_secs(1_709_251_199))
+        );
+        assert_eq!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"), None);
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 PST"), None);
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 8:49:37 GMT"), None);
+    }
 }
@@ -203,6 +203,25 @@
 //! # run();
 //! ```
 //!
+//! A route path which can't be expressed by the path syntax can be given as a raw regex with the
+//! [`get_regex`](./struct.RouterBuilder.html#method.get_regex) and [`add_regex`](./struct.RouterBuilder.html#method.add_regex) methods,
+//! the named capture groups become the route parameters:
+//!
+//! ```
+//! use routerify::Router;
+//! use hyper::{Response, Body};
+//! # use std::convert::Infallible;
+//!
+//! # fn run() -> Router<Body, Infallible> {
+//! let router = Router::builder()
+//!     .get_regex(r"^/items/(?P<id>\d+)-(?P<slug>[a-z-]+)/$", |req| async move { Ok(Response::new(Body::from("It will match /items/42-some-item"))) })
+//!     .build()
+//!     .unwrap();
+//! # router
+//! # }
+//! # run();
+//! ```
+//!
 //! #### Handle 404 Pages
 //!
 //! Here is an example to handle 404 pages.
@@ -266,6 +285,15 @@
 //! # run();
 //! ```
 //!
+//! A route parameter can be made optional by adding a `?` to its name, optionally followed by a default value,
+//! e.g. `/archive/:year/:month?/:page?=1`. The value of an optional parameter can be accessed by the
+//! [`param_opt`](./ext/trait.RequestExt.html#tymethod.param_opt) method.
+//!
+//! Parameter values can be converted into custom types implementing the [`FromParam`](./trait.FromParam.html) trait by the
+//! [`param_typed`](./ext/trait.RequestExt.html#tymethod.param_typed) method. With the
+//! [`typed_param`](./struct.RouterBuilder.html#method.typed_param) builder method, a route only matches if the conversion
+//! succeeds, otherwise the request falls through to the next matching route.
+//!
 //! ### Scoping/Mounting Router
 //!
 //! The `routerify::Router` is a modular, lightweight and mountable router component. A router can be scoped in or mount to a
@@ -312,7 +340,7 @@
 //! - Execute any code.
 //! - Transform the request and the response object.
 //!
-//! Here, the `Routerify` categorizes the middlewares into two different types:
+//! Here, the `Routerify` categorizes the middlewares into three different types:
 //!
 //! ### Pre Middleware
 //!
@@ -471,6 +499,39 @@
 //! # run();
 //! ```
 //!
+//! ### Around Middleware
+//!
+//! The around Middlewares wrap the execution of the route handler: they're executed after the pre middlewares and they
+//! call the rest of the chain, i.e. the around middlewares after them and the route handler, by the
+//! [`Next::run`](./struct.Next.html#method.run) method. So they can keep the state across the handler call e.g. for
+//! timing, retries or transactions, and convert the errors of the handler into responses before they reach the error
+//! handler. The post middlewares are executed after them.
+//!
+//! The errors of the downstream chain are [`RouteError`](./type.RouteError.html)s, so an around middleware returns a
+//! `RouteError` too.
+//!
+//! Here is an around middleware which converts the timeouts of the handlers into `504 Gateway Timeout` responses:
+//!
+//! ```
+//! use routerify::{Router, Middleware, RouteError};
+//! use hyper::{Response, Body, StatusCode};
+//! use std::time::Duration;
+//!
+//! # fn run() -> Router<Body, RouteError> {
+//! let router = Router::builder()
+//!      .middleware(Middleware::around(|req, next| async move {
+//!          match tokio::time::timeout(Duration::from_secs(5), next.run(req)).await {
+//!              Ok(res) => res,
+//!              Err(_) => Ok(Response::builder().status(StatusCode::GATEWAY_TIMEOUT).body(Body::empty()).unwrap()),
+//!          }
+//!      }))
+//!      .build()
+//!      .unwrap();
+//! # router
+//! # }
+//! # run();
+//! ```
+//!
 //! ### The built-in Middleware
 //!
 //! Here is a list of some middlewares which are published in different crates:
@@ -478,6 +539,31 @@
 //! - [routerify-cors](https://github.com/routerify/routerify-cors): A post middleware which enables `CORS` to the routes.
 //! - [routerify-query](https://github.com/routerify/routerify-query): A pre middleware which parses the request query string.
 //!
+//! And some are shipped with this crate:
+//!
+//! - [admission](./middleware/admission/index.html): An around middleware which admits the requests by priority classes with separate concurrency budgets and queues.
+//! - [audit](./middleware/audit/index.html): A post middleware which emits a structured audit event for each mutating request.
+//! - [cache_control_for](./middleware/fn.cache_control_for.html): A post middleware which sets the `Cache-Control` header built by the
+//!   [`CacheControl`](./headers/struct.CacheControl.html) builder.
+//! - [chaos](./middleware/chaos/index.html): An around middleware which injects latency, errors, dropped connections and truncated bodies for the chaos testing.
+//! - [csp](./middleware/csp/index.html): A pair of middlewares which add a per-request `Content-Security-Policy` nonce.
+//! - [expect_continue](./middleware/expect_continue/index.html): A pre middleware which decides how the requests with the `Expect: 100-continue` header are answered.
+//! - [response_stats](./middleware/fn.response_stats.html): A post middleware which counts the response body bytes written to the client.
+//! - [response_throttle](./middleware/response_throttle/index.html): An around middleware which limits the response bodies to a rate of bytes per second.
+//! - [feature_flags](./middleware/feature_flags/index.html): A pre middleware which evaluates the feature flags of the requests by a pluggable provider.
+//! - [header_sanity](./middleware/header_sanity/index.html): A pre middleware which rejects the requests with the anomalies used for the request smuggling.
+//! - [host_filter](./middleware/host_filter/index.html): A pre middleware which validates the host of the requests against an allowlist.
+//! - [locale](./middleware/locale/index.html): A pair of middlewares which negotiate the locale of the requests and set the `Content-Language` header.
+//! - [throttle](./middleware/throttle/index.html): A pre middleware which throttles the requests per authenticated [`Principal`](./struct.Principal.html).
+//! - [transactional](./middleware/transactional/index.html): An around middleware which runs each request in a database transaction.
+//!
+//! And some behind feature flags:
+//!
+//! - [webhook_signature](./middleware/webhook_signature/index.html): A pre middleware which verifies HMAC signed webhook requests. Requires the `webhook` feature.
+//! - [checksum](./middleware/checksum/index.html): A pair of middlewares which verify the digests of the request bodies and add the digests of the response bodies. Requires the `checksum` feature.
+//! - [html_rewrite](./middleware/html_rewrite/index.html): A post middleware which rewrites the HTML responses as they are streamed. Requires the `html-rewrite` feature.
+//! - [otel](./middleware/otel/index.html): A pair of middlewares which trace the requests with OpenTelemetry spans. Requires the `opentelemetry` feature.
+//!
 //! ## Data and State Sharing
 //!
 //! `Routerify` also allows you to share data or app state across the route handlers, middlewares and the error handler via the [`RouterBuilder`](./struct.RouterBuilder.html) method
@@ -705,6 +791,13 @@
 //! See this [example](https://github.com/routerify/routerify/tree/master/examples/error_handling_with_custom_errors.rs)
 //! for handling custom errors.
 //!
+//! The [`RouteErrorExt`](./ext/trait.RouteErrorExt.html) trait helps to walk the error sources and to find a cause of a specific type.
+//!
+//! Handlers can also return `anyhow::Error` or `eyre::Report` directly e.g. `Router<Body, anyhow::Error>`. With the `anyhow` or the
+//! `eyre` feature, the original error can be found by [`find_cause`](./ext/trait.RouteErrorExt.html#tymethod.find_cause) and the
+//! detailed report with the backtrace is kept, which the default error handler shows when the
+//! [`debug_errors`](./struct.RouterBuilder.html#method.debug_errors) mode is on.
+//!
 //! Here is an basic example:
 //!
 //! ```
@@ -765,28 +858,77 @@
 //! # run();
 //! ```
 
-pub use self::error::{Error, RouteError};
-pub use self::middleware::{Middleware, PostMiddleware, PreMiddleware};
-pub use self::route::Route;
-pub use self::router::{Router, RouterBuilder};
+pub use self::body::{map_body, BodyError, BodyMapper, BufferedBody, ResponseStats, SpillConfig};
+pub use self::error::{Error, ErrorClass, HttpError, RouteError};
+pub use self::middleware::{
+    AroundMiddleware, Middleware, MiddlewareInfo, MiddlewareKind, Next, PostMiddleware, PreMiddleware,
+};
+#[cfg(feature = "protobuf")]
+pub use self::responses::proto;
+#[cfg(feature = "sample-paths")]
+pub use self::route::{ParamStrategies, SamplePaths};
+pub use self::route::{Route, RouteInfo};
+pub use self::router::{ExportFormat, RouteHandle, RouteMeta, Router, RouterBuilder, RouterHandle};
 #[doc(hidden)]
+pub use self::service::Lifecycle;
 pub use self::service::RequestService;
 pub use self::service::RequestServiceBuilder;
+pub use self::service::RouterFactory;
+pub use self::service::RouterSelector;
 pub use self::service::RouterService;
-pub use self::types::{RequestInfo, RouteParams};
+#[cfg(feature = "hyper1")]
+pub use self::service::{Hyper1Body, Hyper1Service};
+#[cfg(feature = "server")]
+pub use self::service::{Listener, ListenerIo, Serve, TlsAcceptor};
+pub use self::task::{spawn_scoped, TaskRegistry};
+pub use self::types::{
+    ConnectionInfo, Deadline, Deprecation, ErrorContext, FromParam, Principal, ProblemDetails, Redaction, RequestInfo,
+    RequestMemory, RouteParams, TlsInfo,
+};
+pub use tokio_util::sync::CancellationToken;
 
+mod body;
+pub mod cache;
+#[cfg(feature = "client")]
+pub mod client;
+#[cfg(feature = "codec")]
+pub mod codec;
+pub mod codegen;
+#[cfg(feature = "hyper1")]
+pub mod compat;
+#[cfg(feature = "config")]
+pub mod config;
 mod constants;
 mod data_map;
 mod error;
+pub mod events;
 pub mod ext;
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+pub mod guard;
+pub mod headers;
 mod helpers;
-mod middleware;
+pub mod middleware;
+pub mod pagination;
 pub mod prelude;
+pub mod realtime;
+#[cfg(feature = "record")]
+pub mod record;
 mod regex_generator;
+pub mod responses;
+pub mod revalidate;
 mod route;
 mod router;
 mod service;
+pub mod split;
+pub mod sse;
+mod task;
 mod types;
+pub mod uploads;
+pub mod warmup;
+pub mod ws;
 
 /// A Result type often returned from methods that can have routerify errors.
 pub type Result<T> = std::result::Result<T, RouteError>;
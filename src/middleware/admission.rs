@@ -0,0 +1,402 @@
+//! An around middleware which admits the requests by priority classes, so the health checks and the admin endpoints
+//! stay responsive while the bulk endpoints saturate the server.
+//!
+//! Each class has its own [`Budget`](./struct.Budget.html) of the requests handled at the same time, and a queue of the
+//! requests waiting for their turn. A request is classified by the [classifier](./struct.Admission.html#method.classifier),
+//! if any, otherwise by the first [route pattern](./struct.Admission.html#method.route) matching its path, otherwise it
+//! goes to the [default class](./struct.Admission.html#method.default_class). The requests which aren't classified are
+//! admitted without a budget.
+//!
+//! A request is rejected with an [`HttpError`](../../struct.HttpError.html) of status `503 Service Unavailable` if the
+//! queue of its class is full, or if it isn't admitted before the queue timeout of its class. It's rejected with
+//! `504 Gateway Timeout` instead if the [`Deadline`](../../struct.Deadline.html) put into the request context has
+//! passed, or passes before the request is admitted.
+//!
+//! # Examples
+//!
+//! ```
+//! use routerify::Router;
+//! use routerify::middleware::admission::{Admission, Budget};
+//! use hyper::{Response, Body};
+//! use std::convert::Infallible;
+//! use std::time::Duration;
+//!
+//! # fn run() -> Router<Body, Infallible> {
+//! let admission = Admission::new()
+//!     .class("ops", Budget::new(4))
+//!     .class("bulk", Budget::new(32).queue(128, Duration::from_secs(2)))
+//!     .class("api", Budget::new(256).queue(1024, Duration::from_millis(500)))
+//!     .route("ops", "/health")
+//!     .route("ops", "/admin/*")
+//!     .route("bulk", "/exports/*")
+//!     .default_class("api");
+//!
+//! let router = Router::builder()
+//!     .middleware(admission.middleware())
+//!     .get("/health", |_| async move { Ok(Response::new(Body::from("OK"))) })
+//!     .get("/exports/:id", |_| async move { Ok(Response::new(Body::from("Export"))) })
+//!     .build()
+//!     .unwrap();
+//! # router
+//! # }
+//! # run();
+//! ```
+
+use crate::ext::RequestExt;
+use crate::helpers;
+use crate::middleware::Middleware;
+use crate::regex_generator::generate_exact_match_regex;
+use crate::types::Deadline;
+use crate::HttpError;
+use hyper::{Body, Request, StatusCode};
+use regex::Regex;
+use std::fmt::{self, Debug, Formatter};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+type Classifier = Arc<dyn Fn(&Request<Body>) -> Option<String> + Send + Sync + 'static>;
+
+/// The concurrency budget and the queue of a priority class.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Budget {
+    concurrency: usize,
+    queue_len: usize,
+    queue_timeout: Option<Duration>,
+}
+
+impl Budget {
+    /// Creates a budget of `concurrency` requests handled at the same time. The other requests are rejected unless a
+    /// [queue](#method.queue) is set.
+    pub fn new(concurrency: usize) -> Budget {
+        Budget {
+            concurrency: concurrency.max(1),
+            queue_len: 0,
+            queue_timeout: None,
+        }
+    }
+
+    /// Lets up to `len` requests wait for their turn, each up to `timeout`.
+    pub fn queue(mut self, len: usize, timeout: Duration) -> Budget {
+        self.queue_len = len;
+        self.queue_timeout = Some(timeout);
+        self
+    }
+}
+
+struct Class {
+    name: String,
+    budget: Budget,
+    permits: Arc<Semaphore>,
+    queued: AtomicUsize,
+}
+
+/// The configuration and the state of the admission by priority classes.
+///
+/// It's cheap to clone and the clones share the budgets. Please refer to the [module](./index.html) documentation for
+/// more info.
+#[derive(Clone, Default)]
+pub struct Admission {
+    classes: Vec<Arc<Class>>,
+    routes: Vec<(String, Regex, String)>,
+    default_class: Option<String>,
+    classifier: Option<Classifier>,
+}
+
+impl Admission {
+    /// Creates a new configuration without any class.
+    pub fn new() -> Self {
+        Admission::default()
+    }
+
+    /// Adds a priority class with its budget.
+    pub fn class<N: Into<String>>(mut self, name: N, budget: Budget) -> Self {
+        self.classes.push(Arc::new(Class {
+            name: name.into(),
+            budget,
+            permits: Arc::new(Semaphore::new(budget.concurrency)),
+            queued: AtomicUsize::new(0),
+        }));
+        self
+    }
+
+    /// Puts the requests whose path matches the route path, e.g. `/admin/*`, into the class.
+    ///
+    /// # Panics
+    ///
+    /// It panics if the path is invalid or the class isn't added.
+    pub fn route<N: Into<String>, P: Into<String>>(mut self, class: N, path: P) -> Self {
+        let class = class.into();
+        assert!(self.find(&class).is_some(), "No admission class is named: {:?}", class);
+
+        let mut path = path.into();
+        if !path.ends_with('/') && !path.ends_with('*') {
+            path.push('/');
+        }
+        let (regex, _) = generate_exact_match_regex(&path).expect("Invalid admission route path");
+        self.routes.push((path, regex, class));
+        self
+    }
+
+    /// Puts the requests which aren't matched by the classifier or a route pattern into the class.
+    ///
+    /// # Panics
+    ///
+    /// It panics if the class isn't added.
+    pub fn default_class<N: Into<String>>(mut self, class: N) -> Self {
+        let class = class.into();
+        assert!(self.find(&class).is_some(), "No admission class is named: {:?}", class);
+        self.default_class = Some(class);
+        self
+    }
+
+    /// Sets a function which picks the class of a request e.g. by a header or the result of a guard, before the route
+    /// patterns. A name of a class which isn't added is ignored.
+    pub fn classifier<F>(mut self, classifier: F) -> Self
+    where
+        F: Fn(&Request<Body>) -> Option<String> + Send + Sync + 'static,
+    {
+        self.classifier = Some(Arc::new(classifier));
+        self
+    }
+
+    /// Returns the number of the requests of the class which are being handled and which are queued. It returns `None`
+    /// if the class isn't added.
+    pub fn load(&self, class: &str) -> Option<(usize, usize)> {
+        self.find(class).map(|class| {
+            (
+                class.budget.concurrency - class.permits.available_permits(),
+                class.queued.load(Ordering::Relaxed),
+            )
+        })
+    }
+
+    /// Creates an around middleware at the `/*` path.
+    pub fn middleware<E>(&self) -> Middleware<Body, E>
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        self.middleware_with_path("/*").unwrap()
+    }
+
+    /// Creates an around middleware at the specified path.
+    pub fn middleware_with_path<P, E>(&self, path: P) -> crate::Result<Middleware<Body, E>>
+    where
+        P: Into<String>,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        let admission = self.clone();
+        Middleware::around_with_path(path, move |req: Request<Body>, next| {
+            let admission = admission.clone();
+            async move {
+                let class = match admission.classify(&req) {
+                    Some(class) => class,
+                    None => return next.run(req).await,
+                };
+
+                let _permit = admit(&class, req.context::<Deadline>()).await?;
+                next.run(req).await
+            }
+        })
+    }
+
+    fn find(&self, name: &str) -> Option<&Arc<Class>> {
+        self.classes.iter().find(|class| class.name == name)
+    }
+
+    fn classify(&self, req: &Request<Body>) -> Option<Arc<Class>> {
+        if let Some(ref classifier) = self.classifier {
+            if let Some(class) = classifier(req).and_then(|name| self.find(&name)) {
+                return Some(class.clone());
+            }
+        }
+
+        if !self.routes.is_empty() {
+            if let Ok((target_path, _)) = helpers::route_target_path(req.uri().path(), req.base_path()) {
+                if let Some((_, _, class)) = self.routes.iter().find(|(_, regex, _)| regex.is_match(&target_path)) {
+                    return self.find(class).cloned();
+                }
+            }
+        }
+
+        self.default_class
+            .as_deref()
+            .and_then(|class| self.find(class))
+            .cloned()
+    }
+}
+
+impl Debug for Admission {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{{ classes: {:?}, routes: {:?}, default_class: {:?}, classifier: {:?} }}",
+            self.classes
+                .iter()
+                .map(|class| (&class.name, class.budget))
+                .collect::<Vec<_>>(),
+            self.routes
+                .iter()
+                .map(|(path, _, class)| (path, class))
+                .collect::<Vec<_>>(),
+            self.default_class,
+            self.classifier.as_ref().map(|_| "Fn")
+        )
+    }
+}
+
+async fn admit(class: &Class, deadline: Option<Deadline>) -> Result<OwnedSemaphorePermit, HttpError> {
+    let is_expired = |deadline: Option<Deadline>| matches!(deadline, Some(deadline) if deadline.remaining().is_zero());
+    if is_expired(deadline) {
+        return Err(deadline_exceeded(&class.name));
+    }
+
+    if let Ok(permit) = class.permits.clone().try_acquire_owned() {
+        return Ok(permit);
+    }
+
+    let timeout = match class.budget.queue_timeout {
+        Some(timeout) => deadline.map_or(timeout, |deadline| deadline.remaining().min(timeout)),
+        None => return Err(busy(&class.name)),
+    };
+    // The request leaves the queue even if its future is dropped while it waits, e.g. when the client disconnects.
+    let queued = QueuedGuard::new(&class.queued);
+    let permit = if queued.position < class.budget.queue_len {
+        tokio::time::timeout(timeout, class.permits.clone().acquire_owned())
+            .await
+            .ok()
+            .and_then(|permit| permit.ok())
+    } else {
+        None
+    };
+    drop(queued);
+
+    match permit {
+        Some(permit) => Ok(permit),
+        None if is_expired(deadline) => Err(deadline_exceeded(&class.name)),
+        None => Err(busy(&class.name)),
+    }
+}
+
+// Counts a request in the queue of a class while it's alive.
+struct QueuedGuard<'a> {
+    queued: &'a AtomicUsize,
+    // The number of the requests which were queued before this one.
+    position: usize,
+}
+
+impl<'a> QueuedGuard<'a> {
+    fn new(queued: &'a AtomicUsize) -> Self {
+        let position = queued.fetch_add(1, Ordering::Relaxed);
+        QueuedGuard { queued, position }
+    }
+}
+
+impl Drop for QueuedGuard<'_> {
+    fn drop(&mut self) {
+        self.queued.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+fn busy(class: &str) -> HttpError {
+    HttpError::new(
+        StatusCode::SERVICE_UNAVAILABLE,
+        format!("The server is busy with the {} requests", class),
+    )
+}
+
+fn deadline_exceeded(class: &str) -> HttpError {
+    HttpError::new(
+        StatusCode::GATEWAY_TIMEOUT,
+        format!("The deadline passed before the {} request was admitted", class),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(path: &str) -> Request<Body> {
+        Request::builder().uri(path).body(Body::empty()).unwrap()
+    }
+
+    #[test]
+    fn classifies_requests() {
+        let admission = Admission::new()
+            .class("ops", Budget::new(1))
+            .class("bulk", Budget::new(1))
+            .route("ops", "/admin/*")
+            .route("bulk", "/exports/:id")
+            .default_class("bulk")
+            .classifier(|req| req.headers().get("x-priority").map(|_| "ops".to_owned()));
+
+        let class_of = |req: &Request<Body>| admission.classify(req).map(|class| class.name.clone());
+        assert_eq!(class_of(&request("/admin/users")).as_deref(), Some("ops"));
+        assert_eq!(class_of(&request("/exports/1")).as_deref(), Some("bulk"));
+        assert_eq!(class_of(&request("/other")).as_deref(), Some("bulk"));
+
+        let mut req = request("/exports/1");
+        req.headers_mut().insert("x-priority", "1".parse().unwrap());
+        assert_eq!(class_of(&req).as_deref(), Some("ops"));
+    }
+
+    #[tokio::test]
+    async fn queues_requests_within_the_budget() {
+        let admission = Admission::new()
+            .class("bulk", Budget::new(1).queue(1, Duration::from_millis(50)))
+            .class("ops", Budget::new(1));
+        let bulk = admission.find("bulk").unwrap().clone();
+        let ops = admission.find("ops").unwrap().clone();
+
+        let first = admit(&bulk, None).await.unwrap();
+        let _ops = admit(&ops, None).await.unwrap();
+        assert_eq!(admission.load("bulk"), Some((1, 0)));
+        assert_eq!(
+            admit(&ops, None).await.unwrap_err().status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+
+        let err = admit(&bulk, None).await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let queued = tokio::spawn(async move { admit(&bulk, None).await.map(|_| ()) });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(admission.load("bulk"), Some((1, 1)));
+        drop(first);
+        queued.await.unwrap().unwrap();
+        assert_eq!(admission.load("bulk"), Some((0, 0)));
+    }
+
+    #[tokio::test]
+    async fn dequeues_dropped_requests() {
+        let admission = Admission::new().class("bulk", Budget::new(1).queue(1, Duration::from_secs(60)));
+        let bulk = admission.find("bulk").unwrap().clone();
+
+        let _first = admit(&bulk, None).await.unwrap();
+        let queued = tokio::spawn(async move { admit(&bulk, None).await.map(|_| ()) });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(admission.load("bulk"), Some((1, 1)));
+
+        // The future of the queued request is dropped, as when its client disconnects.
+        queued.abort();
+        let _ = queued.await;
+        assert_eq!(admission.load("bulk"), Some((1, 0)));
+    }
+
+    #[tokio::test]
+    async fn rejects_requests_past_their_deadline() {
+        let admission = Admission::new().class("bulk", Budget::new(1).queue(1, Duration::from_secs(60)));
+        let bulk = admission.find("bulk").unwrap().clone();
+
+        let err = admit(&bulk, Some(Deadline::after(Duration::ZERO))).await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::GATEWAY_TIMEOUT);
+
+        let _first = admit(&bulk, None).await.unwrap();
+        let err = admit(&bulk, Some(Deadline::after(Duration::from_millis(20))))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(admission.load("bulk"), Some((1, 0)));
+    }
+}
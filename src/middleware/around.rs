@@ -0,0 +1,173 @@
+use crate::middleware::dependency::Dependencies;
+use crate::regex_generator::generate_exact_match_regex;
+use crate::Error;
+use hyper::{Request, Response};
+use regex::Regex;
+use std::fmt::{self, Debug, Formatter};
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::Poll;
+use tokio::sync::{mpsc, oneshot};
+
+pub(crate) type Handler<B> = Box<dyn Fn(Request<hyper::Body>, Next<B>) -> HandlerReturn<B> + Send + Sync + 'static>;
+pub(crate) type HandlerReturn<B> = Box<dyn Future<Output = crate::Result<Response<B>>> + Send + 'static>;
+
+type Call<B> = (Request<hyper::Body>, oneshot::Sender<crate::Result<Response<B>>>);
+
+/// The around middleware type. Refer to [Around Middleware](./index.html#around-middleware) for more info.
+///
+/// This `AroundMiddleware<B, E>` type accepts two type parameters: `B` and `E`.
+///
+/// * The `B` represents the response body type which will be used by route handlers and the middlewares and this body type must implement
+///   the [HttpBody](https://docs.rs/hyper/0.14.4/hyper/body/trait.HttpBody.html) trait. For an instance, `B` could be [hyper::Body](https://docs.rs/hyper/0.14.4/hyper/body/struct.Body.html)
+///   type.
+/// * The `E` represents any error type which will be used by route handlers and the middlewares. This error type must implement the [std::error::Error](https://doc.rust-lang.org/std/error/trait.Error.html).
+pub struct AroundMiddleware<B, E> {
+    pub(crate) path: String,
+    pub(crate) regex: Regex,
+    // Make it an option so that when a router is used to scope in another router,
+    // It can be extracted out by 'opt.take()' without taking the whole router's ownership.
+    pub(crate) handler: Option<Handler<B>>,
+    // Scope depth with regards to the top level router.
+    pub(crate) scope_depth: u32,
+    // Middlewares with a higher priority are executed first, i.e. they wrap the ones with a lower priority.
+    pub(crate) priority: i32,
+    // The name by which the middleware can be skipped by the routes.
+    pub(crate) name: Option<String>,
+    // The runtime switch of the middleware which is shared with the router handles.
+    pub(crate) enabled: Arc<AtomicBool>,
+    // The types which the middleware requires from and provides to the other middlewares.
+    pub(crate) dependencies: Dependencies,
+    _error: PhantomData<fn() -> E>,
+}
+
+impl<B: Send + 'static, E> AroundMiddleware<B, E> {
+    pub(crate) fn new_with_boxed_handler<P: Into<String>>(
+        path: P,
+        handler: Handler<B>,
+        scope_depth: u32,
+    ) -> crate::Result<AroundMiddleware<B, E>> {
+        let path = path.into();
+        let (re, _) = generate_exact_match_regex(path.as_str()).map_err(|e| {
+            Error::new(format!(
+                "Could not create an exact match regex for the around middleware path: {}",
+                e
+            ))
+        })?;
+
+        Ok(AroundMiddleware {
+            path,
+            regex: re,
+            handler: Some(handler),
+            scope_depth,
+            priority: 0,
+            name: None,
+            enabled: Arc::new(AtomicBool::new(true)),
+            dependencies: Dependencies::default(),
+            _error: PhantomData,
+        })
+    }
+
+    /// Creates an around middleware with a handler at the specified path.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::{Router, Middleware, AroundMiddleware, RouteError};
+    /// use hyper::Body;
+    ///
+    /// # fn run() -> Router<Body, RouteError> {
+    /// let router = Router::builder()
+    ///      .middleware(Middleware::Around(AroundMiddleware::new("/abc", |req, next| async move { next.run(req).await }).unwrap()))
+    ///      .build()
+    ///      .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    pub fn new<P, H, R>(path: P, handler: H) -> crate::Result<AroundMiddleware<B, E>>
+    where
+        P: Into<String>,
+        H: Fn(Request<hyper::Body>, Next<B>) -> R + Send + Sync + 'static,
+        R: Future<Output = crate::Result<Response<B>>> + Send + 'static,
+    {
+        let handler: Handler<B> =
+            Box::new(move |req: Request<hyper::Body>, next: Next<B>| Box::new(handler(req, next)));
+        AroundMiddleware::new_with_boxed_handler(path, handler, 1)
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    // Runs the middleware while serving its calls of the downstream chain by the `downstream` function.
+    pub(crate) async fn process<F, R>(&self, req: Request<hyper::Body>, downstream: F) -> crate::Result<Response<B>>
+    where
+        F: Fn(Request<hyper::Body>) -> R,
+        R: Future<Output = crate::Result<Response<B>>>,
+    {
+        let handler = self
+            .handler
+            .as_ref()
+            .expect("A router can not be used after mounting into another router");
+
+        let (tx, mut rx) = mpsc::unbounded_channel::<Call<B>>();
+        let mut around = Pin::from(handler(req, Next { tx }));
+
+        // The downstream calls are served one by one until the middleware finishes, so the chain can borrow the router.
+        let serve = async move {
+            while let Some((req, res_tx)) = rx.recv().await {
+                let _ = res_tx.send(downstream(req).await);
+            }
+        };
+        let mut serve = Box::pin(serve);
+        let mut is_serving = true;
+
+        std::future::poll_fn(move |cx| {
+            if let Poll::Ready(res) = around.as_mut().poll(cx) {
+                return Poll::Ready(res);
+            }
+            if is_serving && serve.as_mut().poll(cx).is_ready() {
+                is_serving = false;
+            }
+            Poll::Pending
+        })
+        .await
+    }
+}
+
+impl<B, E> Debug for AroundMiddleware<B, E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{{ path: {:?}, regex: {:?} }}", self.path, self.regex)
+    }
+}
+
+/// The rest of the chain of an around middleware, i.e. the around middlewares after it and the route handler.
+///
+/// Please refer to the [Around Middleware](./index.html#around-middleware) for more info.
+pub struct Next<B> {
+    tx: mpsc::UnboundedSender<Call<B>>,
+}
+
+impl<B> Next<B> {
+    /// Runs the rest of the chain with the request and returns its response or error. It can be called more than
+    /// once, e.g. to retry a failed request, as long as the middleware is running.
+    pub async fn run(&self, req: Request<hyper::Body>) -> crate::Result<Response<B>> {
+        let (res_tx, res_rx) = oneshot::channel();
+        self.tx
+            .send((req, res_tx))
+            .map_err(|_| Error::new("The downstream chain can't be run after the around middleware has finished"))?;
+        res_rx
+            .await
+            .map_err(|_| Error::new("The downstream chain was dropped before it responded"))?
+    }
+}
+
+impl<B> Debug for Next<B> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Next")
+    }
+}
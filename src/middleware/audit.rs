@@ -0,0 +1,299 @@
+//! A post middleware which emits a structured audit event for each mutating request.
+//!
+//! Each event records the method, the matched route, the authenticated [`Principal`](../../struct.Principal.html), the
+//! route parameters, the response status, the duration and the request id, and it's passed to a pluggable
+//! [`AuditSink`](./trait.AuditSink.html). The [`StdoutJsonSink`](./struct.StdoutJsonSink.html) writes the events as JSON lines.
+//!
+//! The sensitive route parameters can be redacted before the events reach the sink, both by the
+//! [`redact_param`](./struct.Audit.html#method.redact_param) method and by the shared
+//! [`Redaction`](../../struct.Redaction.html) rules. The path segments of the redacted parameters are redacted as well.
+//!
+//! # Examples
+//!
+//! ```
+//! use routerify::Router;
+//! use routerify::middleware::audit::{Audit, StdoutJsonSink};
+//! use hyper::{Response, Body};
+//! use std::convert::Infallible;
+//!
+//! # fn run() -> Router<Body, Infallible> {
+//! let router = Router::builder()
+//!     .middleware(Audit::new(StdoutJsonSink).redact_param("token").middleware())
+//!     .post("/invites/:token", |_| async move { Ok(Response::new(Body::from("Accepted"))) })
+//!     .build()
+//!     .unwrap();
+//! # router
+//! # }
+//! # run();
+//! ```
+
+use crate::helpers::json_str;
+use crate::middleware::Middleware;
+use crate::types::{Principal, Redaction, RequestInfo, REDACTED};
+use hyper::{body::HttpBody, header::HeaderName, Method, Response, StatusCode};
+use std::fmt::Write;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+/// A structured audit event of a request.
+#[derive(Debug, Clone)]
+pub struct AuditEvent {
+    timestamp: SystemTime,
+    method: Method,
+    path: String,
+    route: Option<String>,
+    principal: Option<String>,
+    params: Vec<(String, String)>,
+    status: StatusCode,
+    duration: Duration,
+    request_id: Option<String>,
+}
+
+impl AuditEvent {
+    /// Returns the time when the event was emitted.
+    pub fn timestamp(&self) -> SystemTime {
+        self.timestamp
+    }
+
+    /// Returns the request method.
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
+    /// Returns the request path, with the path segments of the redacted parameters replaced.
+    pub fn path(&self) -> &str {
+        self.path.as_str()
+    }
+
+    /// Returns the path of the matched route e.g. `/users/:userId`, if any.
+    pub fn route(&self) -> Option<&str> {
+        self.route.as_deref()
+    }
+
+    /// Returns the id of the authenticated principal, if any.
+    pub fn principal(&self) -> Option<&str> {
+        self.principal.as_deref()
+    }
+
+    /// Returns the route parameters sorted by name, with the redacted values replaced.
+    pub fn params(&self) -> &[(String, String)] {
+        self.params.as_slice()
+    }
+
+    /// Returns the response status.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Returns the time elapsed from receiving the request to the response.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Returns the request id, if the request carries one.
+    pub fn request_id(&self) -> Option<&str> {
+        self.request_id.as_deref()
+    }
+
+    /// Serializes the event into a single line JSON object.
+    pub fn to_json(&self) -> String {
+        let timestamp = self
+            .timestamp
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or_default();
+
+        let mut json = String::new();
+        let _ = write!(
+            json,
+            "{{\"timestamp\":{},\"method\":{},\"path\":{},\"route\":{},\"principal\":{},\"params\":{{",
+            timestamp,
+            json_str(self.method.as_str()),
+            json_str(&self.path),
+            json_opt_str(self.route.as_deref()),
+            json_opt_str(self.principal.as_deref()),
+        );
+        for (idx, (name, val)) in self.params.iter().enumerate() {
+            if idx > 0 {
+                json.push(',');
+            }
+            let _ = write!(json, "{}:{}", json_str(name), json_str(val));
+        }
+        let _ = write!(
+            json,
+            "}},\"status\":{},\"duration_ms\":{:.3},\"request_id\":{}}}",
+            self.status.as_u16(),
+            self.duration.as_secs_f64() * 1000.0,
+            json_opt_str(self.request_id.as_deref()),
+        );
+        json
+    }
+}
+
+/// A destination of the audit events.
+///
+/// The `emit` method is called on the request path, so a sink which does slow IO should hand the events over to a
+/// background task.
+pub trait AuditSink: Send + Sync + 'static {
+    /// Receives an audit event.
+    fn emit(&self, event: &AuditEvent);
+}
+
+impl<F> AuditSink for F
+where
+    F: Fn(&AuditEvent) + Send + Sync + 'static,
+{
+    fn emit(&self, event: &AuditEvent) {
+        self(event)
+    }
+}
+
+/// An [`AuditSink`](./trait.AuditSink.html) which writes each event as a JSON line to the standard output.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdoutJsonSink;
+
+impl AuditSink for StdoutJsonSink {
+    fn emit(&self, event: &AuditEvent) {
+        println!("{}", event.to_json());
+    }
+}
+
+/// The configuration of the audit middleware.
+///
+/// Please refer to the [module](./index.html) documentation for more info.
+#[derive(Clone)]
+pub struct Audit {
+    sink: Arc<dyn AuditSink>,
+    methods: Vec<Method>,
+    redacted_params: Vec<String>,
+    request_id_header: HeaderName,
+}
+
+impl Audit {
+    /// Creates a new configuration which audits the `POST`, `PUT`, `PATCH` and `DELETE` requests to the sink.
+    pub fn new<S: AuditSink>(sink: S) -> Self {
+        Audit {
+            sink: Arc::new(sink),
+            methods: vec![Method::POST, Method::PUT, Method::PATCH, Method::DELETE],
+            redacted_params: Vec::new(),
+            request_id_header: HeaderName::from_static("x-request-id"),
+        }
+    }
+
+    /// Sets the methods of the audited requests.
+    pub fn methods(mut self, methods: Vec<Method>) -> Self {
+        self.methods = methods;
+        self
+    }
+
+    /// Replaces the value of the specified route parameter with `[REDACTED]` in the events.
+    pub fn redact_param<P: Into<String>>(mut self, param: P) -> Self {
+        self.redacted_params.push(param.into());
+        self
+    }
+
+    /// Reads the request id from a different header. Defaults to `x-request-id`.
+    pub fn request_id_header(mut self, header: HeaderName) -> Self {
+        self.request_id_header = header;
+        self
+    }
+
+    /// Creates a post middleware at the `/*` path.
+    pub fn middleware<B, E>(self) -> Middleware<B, E>
+    where
+        B: HttpBody + Send + Sync + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        self.middleware_with_path("/*").unwrap()
+    }
+
+    /// Creates a post middleware at the specified path.
+    pub fn middleware_with_path<P, B, E>(self, path: P) -> crate::Result<Middleware<B, E>>
+    where
+        P: Into<String>,
+        B: HttpBody + Send + Sync + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        let config = Arc::new(self);
+        Middleware::post_with_info_with_path(path, move |res: Response<B>, req_info: RequestInfo| {
+            let config = config.clone();
+            async move {
+                if config.methods.contains(req_info.method()) {
+                    config.sink.emit(&config.event(&req_info, res.status()));
+                }
+                Ok(res)
+            }
+        })
+    }
+
+    fn event(&self, req_info: &RequestInfo, status: StatusCode) -> AuditEvent {
+        let redaction = req_info
+            .data::<Redaction>()
+            .cloned()
+            .unwrap_or_default()
+            .redact_params(self.redacted_params.iter().cloned());
+
+        let mut params = req_info
+            .params()
+            .map(|params| {
+                params
+                    .iter()
+                    .map(|(name, val)| {
+                        let val = if redaction.is_param_redacted(name) {
+                            REDACTED.to_owned()
+                        } else {
+                            val.clone()
+                        };
+                        (name.clone(), val)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        params.sort();
+
+        AuditEvent {
+            timestamp: SystemTime::now(),
+            method: req_info.method().clone(),
+            path: redaction.redact_path(req_info.uri().path(), req_info.params()),
+            route: req_info.route_path().map(ToOwned::to_owned),
+            principal: req_info.context::<Principal>().map(|p| p.id().to_owned()),
+            params,
+            status,
+            duration: req_info.received_at().elapsed(),
+            request_id: req_info
+                .headers()
+                .get(&self.request_id_header)
+                .and_then(|val| val.to_str().ok())
+                .map(ToOwned::to_owned),
+        }
+    }
+}
+
+fn json_opt_str(val: Option<&str>) -> String {
+    val.map(json_str).unwrap_or_else(|| "null".to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serializes_events_to_json() {
+        let event = AuditEvent {
+            timestamp: SystemTime::UNIX_EPOCH + Duration::from_millis(1500),
+            method: Method::POST,
+            path: "/users/1".to_owned(),
+            route: Some("/users/:id/".to_owned()),
+            principal: None,
+            params: vec![("id".to_owned(), "1\"\n".to_owned())],
+            status: StatusCode::CREATED,
+            duration: Duration::from_micros(1250),
+            request_id: Some("abc".to_owned()),
+        };
+
+        assert_eq!(
+            event.to_json(),
+            r#"{"timestamp":1500,"method":"POST","path":"/users/1","route":"/users/:id/","principal":null,"params":{"id":"1\"\n"},"status":201,"duration_ms":1.250,"request_id":"abc"}"#
+        );
+    }
+}
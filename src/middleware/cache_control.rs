@@ -0,0 +1,50 @@
+use crate::headers::CacheControl;
+use crate::middleware::Middleware;
+use hyper::{body::HttpBody, header, StatusCode};
+
+/// Creates a post middleware which sets the `Cache-Control` header on the responses at the specified path.
+///
+/// The header is set only on the successful and the `304 Not Modified` responses which don't have a `Cache-Control`
+/// header yet, so the error pages are never cached and a route handler can still override the directives.
+///
+/// # Examples
+///
+/// ```
+/// use routerify::Router;
+/// use routerify::headers::CacheControl;
+/// use routerify::middleware::cache_control_for;
+/// use hyper::{Response, Body};
+/// use std::convert::Infallible;
+///
+/// # fn run() -> Router<Body, Infallible> {
+/// let router = Router::builder()
+///     .middleware(cache_control_for("/static/*", CacheControl::public().max_age(86400).immutable()).unwrap())
+///     .middleware(cache_control_for("/api/*", CacheControl::no_store()).unwrap())
+///     .get("/static/app.js", |_| async move { Ok(Response::new(Body::from("console.log(1)"))) })
+///     .build()
+///     .unwrap();
+/// # router
+/// # }
+/// # run();
+/// ```
+pub fn cache_control_for<P, B, E>(path: P, cache_control: CacheControl) -> crate::Result<Middleware<B, E>>
+where
+    P: Into<String>,
+    B: HttpBody + Send + Sync + 'static,
+    E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+{
+    let value = cache_control.to_header_value();
+
+    Middleware::post_with_path(path, move |mut res| {
+        let value = value.clone();
+        async move {
+            let status = res.status();
+            if (status.is_success() || status == StatusCode::NOT_MODIFIED)
+                && !res.headers().contains_key(header::CACHE_CONTROL)
+            {
+                res.headers_mut().insert(header::CACHE_CONTROL, value);
+            }
+            Ok(res)
+        }
+    })
+}
@@ -0,0 +1,264 @@
+//! An around middleware which injects faults into the requests, to validate the retry and the resilience behavior of
+//! the clients e.g. in a staging environment.
+//!
+//! The [`Chaos`](./struct.Chaos.html) configuration sets the percentage of the requests which get each fault:
+//!
+//! * [`latency`](./struct.Chaos.html#method.latency): The request is delayed before it's handled.
+//! * [`error`](./struct.Chaos.html#method.error): The request fails with an [`HttpError`](../../struct.HttpError.html)
+//!   without being handled, so the error handler creates the response.
+//! * [`drop_connection`](./struct.Chaos.html#method.drop_connection): The request isn't handled and the response body
+//!   fails immediately, so the connection is closed without a complete response.
+//! * [`truncate_body`](./struct.Chaos.html#method.truncate_body): The request is handled, but only the first half of the
+//!   response body is sent before the connection is closed. The `Content-Length` header is set to the whole length, so
+//!   the clients can detect the truncation.
+//!
+//! Each fault is rolled independently. The latency is added before the other faults, and at most one of the error, the
+//! dropped connection and the truncated body is injected, in that order. The faults can be limited to the request
+//! paths which match the [`path`](./struct.Chaos.html#method.path) filters and to the requests with the
+//! [`only_with_header`](./struct.Chaos.html#method.only_with_header) header, so the other clients aren't affected.
+//!
+//! # Examples
+//!
+//! ```
+//! use routerify::Router;
+//! use routerify::middleware::chaos::Chaos;
+//! use hyper::{Response, Body, StatusCode};
+//! use std::convert::Infallible;
+//! use std::time::Duration;
+//!
+//! # fn run() -> Router<Body, Infallible> {
+//! let chaos = Chaos::new()
+//!     .latency(10.0, Duration::from_millis(500))
+//!     .error(5.0, StatusCode::SERVICE_UNAVAILABLE)
+//!     .drop_connection(1.0)
+//!     .truncate_body(1.0)
+//!     .path("/api/orders/*")
+//!     .only_with_header("x-chaos");
+//!
+//! let router = Router::builder()
+//!     .middleware(chaos.middleware())
+//!     .get("/api/orders/:id", |_| async move { Ok(Response::new(Body::from("Order"))) })
+//!     .build()
+//!     .unwrap();
+//! # router
+//! # }
+//! # run();
+//! ```
+
+use crate::ext::RequestExt;
+use crate::helpers;
+use crate::middleware::Middleware;
+use crate::regex_generator::generate_exact_match_regex;
+use crate::{Error, HttpError};
+use futures_core::Stream;
+use hyper::body::Bytes;
+use hyper::header::{self, HeaderName, HeaderValue};
+use hyper::{Body, Request, Response, StatusCode};
+use regex::Regex;
+use std::fmt::{self, Debug, Formatter};
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// The fault injection configuration.
+///
+/// It's cheap to clone. Please refer to the [module](./index.html) documentation for more info.
+#[derive(Clone, Default)]
+pub struct Chaos {
+    latency: Option<(f64, Duration)>,
+    error: Option<(f64, StatusCode)>,
+    drop_connection: f64,
+    truncate_body: f64,
+    paths: Vec<(String, Regex)>,
+    header: Option<HeaderName>,
+}
+
+impl Chaos {
+    /// Creates a new configuration which doesn't inject any fault.
+    pub fn new() -> Self {
+        Chaos::default()
+    }
+
+    /// Delays the percentage of the requests, from `0.0` to `100.0`, by the specified duration.
+    pub fn latency(mut self, percent: f64, delay: Duration) -> Self {
+        self.latency = Some((clamp_percent(percent), delay));
+        self
+    }
+
+    /// Fails the percentage of the requests, from `0.0` to `100.0`, with the specified status code, e.g.
+    /// `500 Internal Server Error`.
+    pub fn error(mut self, percent: f64, status: StatusCode) -> Self {
+        self.error = Some((clamp_percent(percent), status));
+        self
+    }
+
+    /// Closes the connection of the percentage of the requests, from `0.0` to `100.0`, without a complete response.
+    pub fn drop_connection(mut self, percent: f64) -> Self {
+        self.drop_connection = clamp_percent(percent);
+        self
+    }
+
+    /// Truncates the response bodies of the percentage of the requests, from `0.0` to `100.0`.
+    pub fn truncate_body(mut self, percent: f64) -> Self {
+        self.truncate_body = clamp_percent(percent);
+        self
+    }
+
+    /// Limits the faults to the request paths which match the route path, e.g. `/orders/:id` or `/orders/*`. The faults
+    /// are injected into the requests which match any of the paths, or all the requests if there is none.
+    ///
+    /// # Panics
+    ///
+    /// It panics if the path is invalid.
+    pub fn path<P: Into<String>>(mut self, path: P) -> Self {
+        let mut path = path.into();
+        if !path.ends_with('/') && !path.ends_with('*') {
+            path.push('/');
+        }
+        let (regex, _) = generate_exact_match_regex(&path).expect("Invalid chaos path");
+        self.paths.push((path, regex));
+        self
+    }
+
+    /// Limits the faults to the requests with the specified header, whatever its value is.
+    ///
+    /// # Panics
+    ///
+    /// It panics if the header name is invalid.
+    pub fn only_with_header<N: AsRef<str>>(mut self, name: N) -> Self {
+        self.header = Some(HeaderName::from_bytes(name.as_ref().as_bytes()).expect("Invalid chaos header name"));
+        self
+    }
+
+    /// Creates an around middleware at the `/*` path.
+    pub fn middleware<E>(&self) -> Middleware<Body, E>
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        self.middleware_with_path("/*").unwrap()
+    }
+
+    /// Creates an around middleware at the specified path.
+    pub fn middleware_with_path<P, E>(&self, path: P) -> crate::Result<Middleware<Body, E>>
+    where
+        P: Into<String>,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        let chaos = self.clone();
+        Middleware::around_with_path(path, move |req: Request<Body>, next| {
+            let chaos = chaos.clone();
+            async move {
+                if !chaos.is_targeted(&req) {
+                    return next.run(req).await;
+                }
+
+                if let Some((percent, delay)) = chaos.latency {
+                    if roll(percent) {
+                        tokio::time::sleep(delay).await;
+                    }
+                }
+
+                if let Some((percent, status)) = chaos.error {
+                    if roll(percent) {
+                        return Err(HttpError::new(status, "Injected fault").into());
+                    }
+                }
+
+                if roll(chaos.drop_connection) {
+                    return Ok(Response::new(Body::wrap_stream(AbortingStream::new(None))));
+                }
+
+                let res = next.run(req).await?;
+                if !roll(chaos.truncate_body) {
+                    return Ok(res);
+                }
+
+                let (mut parts, body) = res.into_parts();
+                let body = hyper::body::to_bytes(body).await.map_err(Error::wrap)?;
+                parts
+                    .headers
+                    .insert(header::CONTENT_LENGTH, HeaderValue::from(body.len()));
+                let chunk = body.slice(..body.len() / 2);
+                Ok(Response::from_parts(
+                    parts,
+                    Body::wrap_stream(AbortingStream::new(Some(chunk))),
+                ))
+            }
+        })
+    }
+
+    fn is_targeted(&self, req: &Request<Body>) -> bool {
+        if let Some(ref header) = self.header {
+            if !req.headers().contains_key(header) {
+                return false;
+            }
+        }
+        if self.paths.is_empty() {
+            return true;
+        }
+
+        match helpers::route_target_path(req.uri().path(), req.base_path()) {
+            Ok((target_path, _)) => self.paths.iter().any(|(_, regex)| regex.is_match(&target_path)),
+            Err(_) => false,
+        }
+    }
+}
+
+impl Debug for Chaos {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{{ latency: {:?}, error: {:?}, drop_connection: {:?}, truncate_body: {:?}, paths: {:?}, header: {:?} }}",
+            self.latency,
+            self.error,
+            self.drop_connection,
+            self.truncate_body,
+            self.paths.iter().map(|(path, _)| path).collect::<Vec<_>>(),
+            self.header
+        )
+    }
+}
+
+fn clamp_percent(percent: f64) -> f64 {
+    percent.clamp(0.0, 100.0)
+}
+
+fn roll(percent: f64) -> bool {
+    percent >= 100.0 || (percent > 0.0 && (helpers::random_u64() as f64 / u64::MAX as f64) * 100.0 < percent)
+}
+
+// Yields the chunk, if any, and then fails, so hyper closes the connection. It's pending once after the chunk, so
+// hyper flushes the chunk before it's failed.
+struct AbortingStream {
+    chunk: Option<Bytes>,
+    flushed: bool,
+}
+
+impl AbortingStream {
+    fn new(chunk: Option<Bytes>) -> AbortingStream {
+        AbortingStream {
+            flushed: chunk.is_none(),
+            chunk,
+        }
+    }
+}
+
+impl Stream for AbortingStream {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Some(chunk) = self.chunk.take() {
+            return Poll::Ready(Some(Ok(chunk)));
+        }
+        if !self.flushed {
+            self.flushed = true;
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+        Poll::Ready(Some(Err(io::Error::new(
+            io::ErrorKind::ConnectionAborted,
+            "Injected fault",
+        ))))
+    }
+}
@@ -0,0 +1,381 @@
+//! A pair of middlewares which verify the digests of the request bodies and add the digests of the response bodies.
+//!
+//! The [`verify_middleware`](./struct.Checksum.html#method.verify_middleware) checks the request body against the
+//! `Content-MD5` header, the `Digest` header of RFC 3230 and the `Content-Digest` header of RFC 9530, for the `md5`,
+//! `sha-256` and `sha-512` algorithms. The other algorithms are ignored. The body is hashed as it's streamed to the
+//! handler, so it isn't buffered, and a mismatch is found only once the body ends: reading the body then fails with an
+//! [`HttpError`](../../struct.HttpError.html) of status `400 Bad Request`, which becomes the response status with the
+//! [`classify_errors`](../../struct.RouterBuilder.html#method.classify_errors) option or if the handler propagates it.
+//! A malformed digest header is rejected with `400 Bad Request` before the handler runs.
+//!
+//! The [`digest_middleware`](./struct.Checksum.html#method.digest_middleware) adds the `Digest` header to the
+//! responses whose body length is known and within the limit, as the body has to be buffered to hash it before the
+//! headers are sent. The streamed responses and the responses which already have the header are left as they are.
+//!
+//! # Examples
+//!
+//! ```
+//! use routerify::Router;
+//! use routerify::middleware::checksum::{Algorithm, Checksum};
+//! use hyper::{Response, Body};
+//! use std::convert::Infallible;
+//!
+//! # fn run() -> Router<Body, Infallible> {
+//! let checksum = Checksum::new().algorithm(Algorithm::Sha512).limit(512 * 1024);
+//!
+//! let router = Router::builder()
+//!     .classify_errors()
+//!     .middleware(checksum.verify_middleware_with_path("/uploads/*").unwrap())
+//!     .middleware(checksum.digest_middleware())
+//!     .put("/uploads/:name", |_| async move { Ok(Response::new(Body::from("Uploaded"))) })
+//!     .build()
+//!     .unwrap();
+//! # router
+//! # }
+//! # run();
+//! ```
+
+use crate::body::{map_body, BodyMapper};
+use crate::middleware::pre::Handler;
+use crate::middleware::{Middleware, PreMiddleware};
+use crate::HttpError;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hyper::body::{Bytes, HttpBody};
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
+use hyper::{Request, Response, StatusCode};
+use md5::Md5;
+use sha2::{Digest, Sha256, Sha512};
+use std::sync::Arc;
+
+const DEFAULT_LIMIT: usize = 1024 * 1024;
+
+/// A digest algorithm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    /// MD5, only for the compatibility with the old clients.
+    Md5,
+    /// SHA-256.
+    Sha256,
+    /// SHA-512.
+    Sha512,
+}
+
+impl Algorithm {
+    fn parse(name: &str) -> Option<Algorithm> {
+        [Algorithm::Md5, Algorithm::Sha256, Algorithm::Sha512]
+            .iter()
+            .copied()
+            .find(|algorithm| name.trim().eq_ignore_ascii_case(algorithm.name()))
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Algorithm::Md5 => "md5",
+            Algorithm::Sha256 => "sha-256",
+            Algorithm::Sha512 => "sha-512",
+        }
+    }
+
+    fn hasher(self) -> Hasher {
+        match self {
+            Algorithm::Md5 => Hasher::Md5(Md5::new()),
+            Algorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+            Algorithm::Sha512 => Hasher::Sha512(Sha512::new()),
+        }
+    }
+
+    fn digest(self, data: &[u8]) -> Vec<u8> {
+        let mut hasher = self.hasher();
+        hasher.update(data);
+        hasher.finalize()
+    }
+}
+
+enum Hasher {
+    Md5(Md5),
+    Sha256(Sha256),
+    Sha512(Sha512),
+}
+
+impl Hasher {
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Md5(hasher) => hasher.update(data),
+            Hasher::Sha256(hasher) => hasher.update(data),
+            Hasher::Sha512(hasher) => hasher.update(data),
+        }
+    }
+
+    fn finalize(self) -> Vec<u8> {
+        match self {
+            Hasher::Md5(hasher) => hasher.finalize().to_vec(),
+            Hasher::Sha256(hasher) => hasher.finalize().to_vec(),
+            Hasher::Sha512(hasher) => hasher.finalize().to_vec(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    algorithm: Algorithm,
+    limit: usize,
+}
+
+/// The configuration of the checksum middlewares.
+///
+/// It's cheap to clone. Please refer to the [module](./index.html) documentation for more info.
+#[derive(Debug, Clone)]
+pub struct Checksum {
+    inner: Arc<Inner>,
+}
+
+impl Default for Checksum {
+    fn default() -> Self {
+        Checksum::new()
+    }
+}
+
+impl Checksum {
+    /// Creates a new configuration which adds the `sha-256` digests of the responses up to `1 MiB`.
+    pub fn new() -> Self {
+        Checksum {
+            inner: Arc::new(Inner {
+                algorithm: Algorithm::Sha256,
+                limit: DEFAULT_LIMIT,
+            }),
+        }
+    }
+
+    /// Sets the algorithm of the response digests.
+    ///
+    /// It should be called before the middlewares are created.
+    pub fn algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.inner_mut().algorithm = algorithm;
+        self
+    }
+
+    /// Sets the maximum length of a response body which gets a digest.
+    ///
+    /// It should be called before the middlewares are created.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.inner_mut().limit = limit;
+        self
+    }
+
+    /// Creates a pre middleware which verifies the request bodies at the `/*` path.
+    pub fn verify_middleware<B, E>(&self) -> Middleware<B, E>
+    where
+        B: HttpBody + Send + Sync + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        self.verify_middleware_with_path("/*").unwrap()
+    }
+
+    /// Creates a pre middleware which verifies the request bodies at the specified path.
+    pub fn verify_middleware_with_path<P, B, E>(&self, path: P) -> crate::Result<Middleware<B, E>>
+    where
+        P: Into<String>,
+        B: HttpBody + Send + Sync + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        let handler: Handler = Box::new(move |req: Request<hyper::Body>| {
+            let res = expected_digests(req.headers()).map(|expected| {
+                if expected.is_empty() {
+                    return req;
+                }
+
+                let (parts, body) = req.into_parts();
+                let verifier = Verifier {
+                    hashers: expected
+                        .into_iter()
+                        .map(|(algorithm, digest)| (algorithm.hasher(), digest))
+                        .collect(),
+                };
+                Request::from_parts(parts, map_body(body, verifier))
+            });
+            Box::new(async move { res })
+        });
+
+        Ok(Middleware::Pre(PreMiddleware::new_with_boxed_handler(
+            path, handler, 1,
+        )?))
+    }
+
+    /// Creates a post middleware which adds the `Digest` header to the responses at the `/*` path.
+    pub fn digest_middleware<E>(&self) -> Middleware<hyper::Body, E>
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        self.digest_middleware_with_path("/*").unwrap()
+    }
+
+    /// Creates a post middleware which adds the `Digest` header to the responses at the specified path.
+    pub fn digest_middleware_with_path<P, E>(&self, path: P) -> crate::Result<Middleware<hyper::Body, E>>
+    where
+        P: Into<String>,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        let config = self.clone();
+        Middleware::post_with_path(path, move |res: Response<hyper::Body>| {
+            let config = config.clone();
+            async move { Ok(config.add_digest(res).await) }
+        })
+    }
+
+    fn inner_mut(&mut self) -> &mut Inner {
+        Arc::get_mut(&mut self.inner).expect("Checksum must be configured before creating the middlewares")
+    }
+
+    async fn add_digest(&self, res: Response<hyper::Body>) -> Response<hyper::Body> {
+        let digest = HeaderName::from_static("digest");
+        let is_buffered = res
+            .body()
+            .size_hint()
+            .exact()
+            .map(|len| len <= self.inner.limit as u64)
+            .unwrap_or(false);
+        if !is_buffered || res.headers().contains_key(&digest) {
+            return res;
+        }
+
+        let (mut parts, body) = res.into_parts();
+        let body = match hyper::body::to_bytes(body).await {
+            Ok(body) => body,
+            Err(_) => {
+                // The body is gone, so the response is aborted like it would be if the body failed while it's sent.
+                let (sender, body) = hyper::Body::channel();
+                sender.abort();
+                return Response::from_parts(parts, body);
+            }
+        };
+
+        let algorithm = self.inner.algorithm;
+        let val = format!("{}={}", algorithm.name(), STANDARD.encode(algorithm.digest(&body)));
+        if let Ok(val) = HeaderValue::from_str(&val) {
+            parts.headers.insert(digest, val);
+        }
+        Response::from_parts(parts, hyper::Body::from(body))
+    }
+}
+
+// Collects the digests of the supported algorithms from the `Content-MD5`, `Digest` and `Content-Digest` headers.
+fn expected_digests(headers: &HeaderMap) -> crate::Result<Vec<(Algorithm, Vec<u8>)>> {
+    let mut expected = Vec::new();
+
+    let content_md5 = headers.get_all("content-md5").iter().map(|val| ("md5", val));
+    let digests = headers
+        .get_all("digest")
+        .iter()
+        .chain(headers.get_all("content-digest").iter())
+        .filter_map(|val| val.to_str().ok())
+        .flat_map(|val| val.split(','))
+        .filter_map(|item| item.split_once('='));
+
+    let pairs = content_md5
+        .filter_map(|(name, val)| val.to_str().ok().map(|val| (name, val)))
+        .chain(digests);
+
+    for (name, val) in pairs {
+        let algorithm = match Algorithm::parse(name) {
+            Some(algorithm) => algorithm,
+            None => continue,
+        };
+
+        // The `Content-Digest` values are byte sequences of the structured fields e.g. `:<base64>:`, possibly with
+        // parameters.
+        let val = val.split(';').next().unwrap_or_default().trim();
+        let val = val
+            .strip_prefix(':')
+            .and_then(|val| val.strip_suffix(':'))
+            .unwrap_or(val);
+
+        match STANDARD.decode(val) {
+            Ok(digest) if digest.len() == algorithm.digest(&[]).len() => expected.push((algorithm, digest)),
+            _ => {
+                return Err(HttpError::new(
+                    StatusCode::BAD_REQUEST,
+                    format!("The request has an invalid {} digest", algorithm.name()),
+                )
+                .into())
+            }
+        }
+    }
+
+    Ok(expected)
+}
+
+struct Verifier {
+    hashers: Vec<(Hasher, Vec<u8>)>,
+}
+
+impl BodyMapper for Verifier {
+    fn map_chunk(&mut self, chunk: Bytes) -> crate::Result<Bytes> {
+        for (hasher, _) in self.hashers.iter_mut() {
+            hasher.update(&chunk);
+        }
+        Ok(chunk)
+    }
+
+    fn finish(&mut self) -> crate::Result<Bytes> {
+        for (hasher, expected) in self.hashers.drain(..) {
+            if hasher.finalize() != expected {
+                return Err(
+                    HttpError::new(StatusCode::BAD_REQUEST, "The request body doesn't match its digest").into(),
+                );
+            }
+        }
+        Ok(Bytes::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, val) in pairs {
+            headers.append(*name, HeaderValue::from_static(val));
+        }
+        headers
+    }
+
+    #[test]
+    fn parses_digest_headers() {
+        let expected = expected_digests(&headers(&[
+            ("content-md5", "XrY7u+Ae7tCTyyK7j1rNww=="),
+            (
+                "digest",
+                "unixsum=30637, SHA-256=uU0nuZNNPgilLlLX2n2r+sSE7+N6U4DukIj3rOLvzek=",
+            ),
+            (
+                "content-digest",
+                "sha-512=:MJ7MSJwS1utMxA9QyQLytNDtd+5RGnx6m808qG1M2G+YndNbxf9JlnDaNCVbRbDP2DDoH2Bdz33FVC6TrpzXbw==:",
+            ),
+        ]))
+        .unwrap();
+
+        let algorithms = expected.iter().map(|(algorithm, _)| *algorithm).collect::<Vec<_>>();
+        assert_eq!(algorithms, [Algorithm::Md5, Algorithm::Sha256, Algorithm::Sha512]);
+        assert!(expected
+            .iter()
+            .all(|(algorithm, digest)| algorithm.digest(b"hello world") == *digest));
+
+        assert!(expected_digests(&headers(&[("digest", "sha-256=abc")])).is_err());
+        assert!(expected_digests(&headers(&[("content-md5", "not base64")])).is_err());
+    }
+
+    #[tokio::test]
+    async fn verifies_the_body() {
+        let verify = |body: &'static str| {
+            let verifier = Verifier {
+                hashers: vec![(Algorithm::Sha256.hasher(), Algorithm::Sha256.digest(b"hello world"))],
+            };
+            hyper::body::to_bytes(map_body(hyper::Body::from(body), verifier))
+        };
+
+        assert_eq!(verify("hello world").await.unwrap(), "hello world");
+        assert!(verify("hello there").await.is_err());
+    }
+}
@@ -0,0 +1,266 @@
+//! An around middleware which coalesces the concurrent identical `GET` requests, so the route handler runs once for
+//! all of them.
+//!
+//! The first request of a key runs the rest of the chain, while the identical requests which arrive before it has
+//! responded wait for it. Its response is buffered and every waiting request gets a copy of it, which protects an
+//! expensive endpoint from a thundering herd, e.g. when a cache entry expires. If it fails, the waiting requests fail
+//! with the same message, and an [`HttpError`](../../struct.HttpError.html) keeps its status. If it's cancelled, e.g.
+//! the client disconnects, the waiting requests run the chain on their own.
+//!
+//! The key is the path and the query of the request and the values of the `Authorization` and `Cookie` headers, so
+//! the responses aren't shared between the users. More headers which the responses depend on, e.g. `Accept-Language`,
+//! can be added with the [`vary`](./struct.Coalesce.html#method.vary) method. The other methods aren't coalesced.
+//!
+//! # Examples
+//!
+//! ```
+//! use routerify::{Router, RouteError};
+//! use routerify::middleware::coalesce::Coalesce;
+//! use hyper::{Response, Body};
+//!
+//! # fn run() -> Router<Body, RouteError> {
+//! let router = Router::builder()
+//!     .middleware(Coalesce::new().vary("accept-language").middleware_with_path("/reports/*").unwrap())
+//!     .get("/reports/sales", |_| async move {
+//!         // An expensive query goes here.
+//!         Ok(Response::new(Body::from("{\"total\": 42}")))
+//!     })
+//!     .build()
+//!     .unwrap();
+//! # router
+//! # }
+//! # run();
+//! ```
+
+use crate::middleware::Middleware;
+use crate::{Error, HttpError};
+use hyper::body::Bytes;
+use hyper::header::{self, HeaderMap, HeaderName};
+use hyper::{Method, Request, Response, StatusCode, Version};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+
+/// The configuration and the in-flight requests of the coalescing middleware.
+///
+/// It's cheap to clone and the clones share the in-flight requests. Please refer to the [module](./index.html)
+/// documentation for more info.
+#[derive(Debug, Clone)]
+pub struct Coalesce {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    vary: Vec<HeaderName>,
+    in_flight: Mutex<HashMap<String, watch::Receiver<Option<Arc<Outcome>>>>>,
+}
+
+// The buffered outcome of a request, which is copied to the coalesced requests.
+#[derive(Debug)]
+enum Outcome {
+    Response {
+        status: StatusCode,
+        version: Version,
+        headers: HeaderMap,
+        body: Bytes,
+    },
+    Failed {
+        status: Option<StatusCode>,
+        msg: String,
+    },
+}
+
+// Removes the in-flight entry of the leading request once it has finished or it's cancelled.
+struct InFlight {
+    inner: Arc<Inner>,
+    key: String,
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.inner.in_flight.lock().unwrap().remove(&self.key);
+    }
+}
+
+impl Default for Coalesce {
+    fn default() -> Self {
+        Coalesce::new()
+    }
+}
+
+impl Coalesce {
+    /// Creates a new configuration whose key has the `Authorization` and `Cookie` headers.
+    pub fn new() -> Self {
+        Coalesce {
+            inner: Arc::new(Inner {
+                vary: vec![header::AUTHORIZATION, header::COOKIE],
+                in_flight: Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+
+    /// Adds a request header to the key, so only the requests with the same value of it are coalesced.
+    ///
+    /// It should be called before the middlewares are created.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the header name is invalid.
+    pub fn vary(mut self, name: &str) -> Self {
+        let name = HeaderName::from_bytes(name.as_bytes()).expect("Invalid header name");
+        Arc::get_mut(&mut self.inner)
+            .expect("Coalesce must be configured before creating the middlewares")
+            .vary
+            .push(name);
+        self
+    }
+
+    /// Creates an around middleware at the `/*` path.
+    pub fn middleware<E>(&self) -> Middleware<hyper::Body, E>
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        self.middleware_with_path("/*").unwrap()
+    }
+
+    /// Creates an around middleware at the specified path.
+    pub fn middleware_with_path<P, E>(&self, path: P) -> crate::Result<Middleware<hyper::Body, E>>
+    where
+        P: Into<String>,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        let inner = self.inner.clone();
+        Middleware::around_with_path(path, move |req: Request<hyper::Body>, next| {
+            let inner = inner.clone();
+            async move {
+                if req.method() != Method::GET {
+                    return next.run(req).await;
+                }
+
+                let key = key(&inner.vary, &req);
+                let (sender, mut receiver) = {
+                    let mut in_flight = inner.in_flight.lock().unwrap();
+                    match in_flight.get(&key) {
+                        Some(receiver) => (None, receiver.clone()),
+                        None => {
+                            let (sender, receiver) = watch::channel(None);
+                            in_flight.insert(key.clone(), receiver.clone());
+                            (Some(sender), receiver)
+                        }
+                    }
+                };
+
+                let sender = match sender {
+                    Some(sender) => sender,
+                    None => {
+                        let outcome = receiver.wait_for(Option::is_some).await.map(|outcome| outcome.clone());
+                        return match outcome {
+                            Ok(Some(outcome)) => outcome.to_result(),
+                            // The leading request was cancelled.
+                            _ => next.run(req).await,
+                        };
+                    }
+                };
+
+                let _in_flight = InFlight { inner, key };
+                let (outcome, result) = match next.run(req).await {
+                    Ok(res) => {
+                        let (parts, body) = res.into_parts();
+                        let body = hyper::body::to_bytes(body).await.map_err(Error::wrap)?;
+                        let outcome = Outcome::Response {
+                            status: parts.status,
+                            version: parts.version,
+                            headers: parts.headers,
+                            body,
+                        };
+                        let result = outcome.to_result();
+                        (outcome, result)
+                    }
+                    Err(err) => {
+                        let outcome = match err.downcast_ref::<HttpError>() {
+                            Some(http_err) => Outcome::Failed {
+                                status: Some(http_err.status()),
+                                msg: http_err.message().to_owned(),
+                            },
+                            None => Outcome::Failed {
+                                status: None,
+                                msg: err.to_string(),
+                            },
+                        };
+                        (outcome, Err(err))
+                    }
+                };
+                sender.send_replace(Some(Arc::new(outcome)));
+                result
+            }
+        })
+    }
+}
+
+impl Outcome {
+    fn to_result(&self) -> crate::Result<Response<hyper::Body>> {
+        match self {
+            Outcome::Response {
+                status,
+                version,
+                headers,
+                body,
+            } => {
+                let mut res = Response::new(hyper::Body::from(body.clone()));
+                *res.status_mut() = *status;
+                *res.version_mut() = *version;
+                *res.headers_mut() = headers.clone();
+                Ok(res)
+            }
+            Outcome::Failed {
+                status: Some(status),
+                msg,
+            } => Err(HttpError::new(*status, msg.as_str()).into()),
+            Outcome::Failed { status: None, msg } => Err(Error::new(msg.as_str()).into()),
+        }
+    }
+}
+
+fn key(vary: &[HeaderName], req: &Request<hyper::Body>) -> String {
+    let mut key = req
+        .uri()
+        .path_and_query()
+        .map(|path| path.as_str().to_owned())
+        .unwrap_or_default();
+    for name in vary {
+        for val in req.headers().get_all(name) {
+            let _ = write!(key, "\n{}: {}", name, String::from_utf8_lossy(val.as_bytes()));
+        }
+    }
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_requests_by_the_varying_headers() {
+        let vary = Coalesce::new().vary("accept-language").inner.vary.clone();
+        let request = |path: &str, headers: &[(&str, &str)]| {
+            let mut req = Request::get(path);
+            for (name, val) in headers {
+                req = req.header(*name, *val);
+            }
+            req.body(hyper::Body::empty()).unwrap()
+        };
+
+        let plain = key(&vary, &request("/reports?year=2024", &[("accept", "text/html")]));
+        assert_eq!(plain, "/reports?year=2024");
+        assert_ne!(plain, key(&vary, &request("/reports?year=2025", &[])));
+        assert_eq!(
+            key(
+                &vary,
+                &request("/reports", &[("cookie", "a=1"), ("accept-language", "de")])
+            ),
+            "/reports\ncookie: a=1\naccept-language: de"
+        );
+    }
+}
@@ -0,0 +1,157 @@
+//! A pair of middlewares which secure the inline scripts and styles by a per-request `Content-Security-Policy` nonce.
+//!
+//! The pre middleware generates a random nonce for each request and stores it in the request context, so the route
+//! handlers can render it into the `nonce` attributes of the inline `<script>` and `<style>` tags through the
+//! [`RequestExt::csp_nonce`](../../ext/trait.RequestExt.html#tymethod.csp_nonce) method. The post middleware appends the
+//! `Content-Security-Policy` header where each `{nonce}` placeholder of the policy is replaced with the same nonce.
+//!
+//! # Examples
+//!
+//! ```
+//! use routerify::Router;
+//! use routerify::middleware::csp::ContentSecurityPolicy;
+//! use routerify::prelude::*;
+//! use hyper::{Response, Body};
+//! use std::convert::Infallible;
+//!
+//! # fn run() -> Router<Body, Infallible> {
+//! let csp = ContentSecurityPolicy::new("default-src 'self'; script-src 'self' 'nonce-{nonce}'");
+//!
+//! let router = Router::builder()
+//!     .middleware(csp.pre_middleware())
+//!     .middleware(csp.post_middleware())
+//!     .get("/", |req| async move {
+//!         let nonce = req.csp_nonce().unwrap();
+//!         let html = format!("<script nonce=\"{}\">console.log('hello')</script>", nonce);
+//!         Ok(Response::new(Body::from(html)))
+//!     })
+//!     .build()
+//!     .unwrap();
+//! # router
+//! # }
+//! # run();
+//! ```
+
+use crate::ext::RequestExt;
+use crate::helpers;
+use crate::middleware::Middleware;
+use hyper::{
+    body::HttpBody,
+    header::{self, HeaderName, HeaderValue},
+    Request,
+};
+use std::sync::Arc;
+
+const NONCE_PLACEHOLDER: &str = "{nonce}";
+const NONCE_LEN: usize = 16;
+
+/// The per-request nonce stored in the request context.
+#[derive(Debug, Clone)]
+pub(crate) struct CspNonce(pub(crate) String);
+
+/// The configuration of the `Content-Security-Policy` nonce middlewares.
+///
+/// Please refer to the [module](./index.html) documentation for more info.
+#[derive(Debug, Clone)]
+pub struct ContentSecurityPolicy {
+    policy: Arc<String>,
+    report_only: bool,
+}
+
+impl ContentSecurityPolicy {
+    /// Creates a new configuration with a policy. Each `{nonce}` placeholder in the policy is replaced with the
+    /// nonce of the request.
+    pub fn new<P: Into<String>>(policy: P) -> Self {
+        ContentSecurityPolicy {
+            policy: Arc::new(policy.into()),
+            report_only: false,
+        }
+    }
+
+    /// Sends the policy in the `Content-Security-Policy-Report-Only` header, so the violations are only reported.
+    pub fn report_only(mut self) -> Self {
+        self.report_only = true;
+        self
+    }
+
+    /// Creates the pre middleware at the `/*` path which generates the nonce.
+    pub fn pre_middleware<B, E>(&self) -> Middleware<B, E>
+    where
+        B: HttpBody + Send + Sync + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        self.pre_middleware_with_path("/*").unwrap()
+    }
+
+    /// Creates the pre middleware at the specified path which generates the nonce.
+    pub fn pre_middleware_with_path<P, B, E>(&self, path: P) -> crate::Result<Middleware<B, E>>
+    where
+        P: Into<String>,
+        B: HttpBody + Send + Sync + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        Middleware::pre_with_path(path, |req: Request<hyper::Body>| async move {
+            if req.csp_nonce().is_none() {
+                req.set_context(CspNonce(generate_nonce()));
+            }
+            Ok(req)
+        })
+    }
+
+    /// Creates the post middleware at the `/*` path which appends the policy header.
+    pub fn post_middleware<B, E>(&self) -> Middleware<B, E>
+    where
+        B: HttpBody + Send + Sync + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        self.post_middleware_with_path("/*").unwrap()
+    }
+
+    /// Creates the post middleware at the specified path which appends the policy header.
+    ///
+    /// The header is not added to the responses of the requests which didn't pass through the pre middleware.
+    pub fn post_middleware_with_path<P, B, E>(&self, path: P) -> crate::Result<Middleware<B, E>>
+    where
+        P: Into<String>,
+        B: HttpBody + Send + Sync + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        let config = self.clone();
+        Middleware::post_with_info_with_path(path, move |mut res, req_info| {
+            let config = config.clone();
+            async move {
+                if let Some(CspNonce(nonce)) = req_info.context::<CspNonce>() {
+                    let policy = config.policy.replace(NONCE_PLACEHOLDER, &nonce);
+                    if let Ok(value) = HeaderValue::from_str(&policy) {
+                        res.headers_mut().append(config.header_name(), value);
+                    }
+                }
+                Ok(res)
+            }
+        })
+    }
+
+    fn header_name(&self) -> HeaderName {
+        if self.report_only {
+            header::CONTENT_SECURITY_POLICY_REPORT_ONLY
+        } else {
+            header::CONTENT_SECURITY_POLICY
+        }
+    }
+}
+
+fn generate_nonce() -> String {
+    helpers::random_hex(NONCE_LEN)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generates_unique_nonces() {
+        let first = generate_nonce();
+        assert_eq!(first.len(), NONCE_LEN * 2);
+        assert_ne!(first, generate_nonce());
+    }
+}
@@ -0,0 +1,24 @@
+use std::any::{type_name, TypeId};
+
+// A type which a middleware puts into or expects in the request, e.g. in the request context.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Dependency {
+    type_id: TypeId,
+    pub(crate) type_name: &'static str,
+}
+
+impl Dependency {
+    pub(crate) fn of<T: 'static>() -> Dependency {
+        Dependency {
+            type_id: TypeId::of::<T>(),
+            type_name: type_name::<T>(),
+        }
+    }
+}
+
+// The declared dependencies of a middleware, which are verified against the execution order when the router is served.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Dependencies {
+    pub(crate) requires: Vec<Dependency>,
+    pub(crate) provides: Vec<Dependency>,
+}
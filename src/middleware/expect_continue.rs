@@ -0,0 +1,214 @@
+//! A pre middleware which decides how the requests with the `Expect: 100-continue` header are answered.
+//!
+//! A client which sends such a request waits for the `100 Continue` interim response before it sends the body, so an
+//! upload which would fail anyway, e.g. it's unauthorized or too large, can be rejected before the body is sent. By
+//! default the `100 Continue` response is sent only once the handler starts reading the body, and the decision function
+//! of the [`ExpectContinue`](./struct.ExpectContinue.html) middleware can instead:
+//!
+//! * [`Continue`](./enum.ContinueDecision.html#variant.Continue): Send the `100 Continue` response right away, so the
+//!   client starts sending the body while the rest of the middlewares and the handler run.
+//! * [`Reject`](./enum.ContinueDecision.html#variant.Reject): Reject the request with an
+//!   [`HttpError`](../../struct.HttpError.html) without the body being sent.
+//! * [`Delegate`](./enum.ContinueDecision.html#variant.Delegate): Keep the default behavior.
+//!
+//! The function is called only for the requests with the `Expect: 100-continue` header. It runs where the middleware is
+//! placed, so it should come after the middlewares whose results it depends on e.g. the authentication, and the
+//! decision can differ per route by using a middleware per route path.
+//!
+//! # Examples
+//!
+//! ```
+//! use routerify::{HttpError, Router};
+//! use routerify::middleware::expect_continue::{ContinueDecision, ExpectContinue};
+//! use hyper::{header, Response, Body, StatusCode};
+//! use std::convert::Infallible;
+//!
+//! # fn run() -> Router<Body, Infallible> {
+//! let uploads = ExpectContinue::new(|req| {
+//!     let length = req
+//!         .headers()
+//!         .get(header::CONTENT_LENGTH)
+//!         .and_then(|val| val.to_str().ok())
+//!         .and_then(|val| val.parse::<u64>().ok());
+//!
+//!     if !req.headers().contains_key(header::AUTHORIZATION) {
+//!         ContinueDecision::Reject(HttpError::new(StatusCode::UNAUTHORIZED, "Unauthorized"))
+//!     } else if length.map(|length| length > 100 * 1024 * 1024).unwrap_or(false) {
+//!         ContinueDecision::Reject(HttpError::new(StatusCode::PAYLOAD_TOO_LARGE, "The upload is too large"))
+//!     } else {
+//!         ContinueDecision::Continue
+//!     }
+//! });
+//!
+//! let router = Router::builder()
+//!     .middleware(uploads.middleware_with_path("/uploads/*").unwrap())
+//!     .post("/uploads/:name", |_| async move { Ok(Response::new(Body::from("Uploaded"))) })
+//!     .build()
+//!     .unwrap();
+//! # router
+//! # }
+//! # run();
+//! ```
+
+use crate::middleware::pre::Handler;
+use crate::middleware::{Middleware, PreMiddleware};
+use crate::HttpError;
+use futures_core::Stream;
+use hyper::body::{Bytes, HttpBody};
+use hyper::header::{self, HeaderMap};
+use hyper::Request;
+use std::fmt::{self, Debug, Formatter};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+
+type Decide = dyn Fn(&Request<hyper::Body>) -> ContinueDecision + Send + Sync + 'static;
+
+/// The decision on a request with the `Expect: 100-continue` header.
+#[derive(Debug)]
+pub enum ContinueDecision {
+    /// Sends the `100 Continue` response right away.
+    Continue,
+    /// Rejects the request with the error before its body is sent.
+    Reject(HttpError),
+    /// Sends the `100 Continue` response once the handler starts reading the body.
+    Delegate,
+}
+
+/// The `Expect: 100-continue` handling policy.
+///
+/// Please refer to the [module](./index.html) documentation for more info.
+#[derive(Clone)]
+pub struct ExpectContinue {
+    decide: Arc<Decide>,
+}
+
+impl ExpectContinue {
+    /// Creates a new policy with the function which decides on the requests with the `Expect: 100-continue` header.
+    pub fn new<F>(decide: F) -> Self
+    where
+        F: Fn(&Request<hyper::Body>) -> ContinueDecision + Send + Sync + 'static,
+    {
+        ExpectContinue {
+            decide: Arc::new(decide),
+        }
+    }
+
+    /// Creates a pre middleware at the `/*` path.
+    pub fn middleware<B, E>(&self) -> Middleware<B, E>
+    where
+        B: HttpBody + Send + Sync + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        self.middleware_with_path("/*").unwrap()
+    }
+
+    /// Creates a pre middleware at the specified path.
+    pub fn middleware_with_path<P, B, E>(&self, path: P) -> crate::Result<Middleware<B, E>>
+    where
+        P: Into<String>,
+        B: HttpBody + Send + Sync + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        let policy = self.clone();
+        let handler: Handler = Box::new(move |req: Request<hyper::Body>| {
+            let decision = if expects_continue(req.headers()) {
+                (policy.decide)(&req)
+            } else {
+                ContinueDecision::Delegate
+            };
+
+            Box::new(async move {
+                match decision {
+                    ContinueDecision::Continue => Ok(send_continue(req)),
+                    ContinueDecision::Reject(err) => Err(err.into()),
+                    ContinueDecision::Delegate => Ok(req),
+                }
+            })
+        });
+
+        Ok(Middleware::Pre(PreMiddleware::new_with_boxed_handler(
+            path, handler, 1,
+        )?))
+    }
+}
+
+impl Debug for ExpectContinue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "ExpectContinue")
+    }
+}
+
+fn expects_continue(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::EXPECT)
+        .map(|val| val.as_bytes().eq_ignore_ascii_case(b"100-continue"))
+        .unwrap_or(false)
+}
+
+struct NoopWaker;
+
+impl Wake for NoopWaker {
+    fn wake(self: Arc<Self>) {}
+}
+
+// Hyper sends the `100 Continue` response once the body is polled, so it's polled once here. The handler polls it
+// again with its own waker.
+fn send_continue(req: Request<hyper::Body>) -> Request<hyper::Body> {
+    let (parts, mut body) = req.into_parts();
+    let waker = Waker::from(Arc::new(NoopWaker));
+    let mut cx = Context::from_waker(&waker);
+
+    let body = match Pin::new(&mut body).poll_data(&mut cx) {
+        Poll::Pending => body,
+        Poll::Ready(None) => hyper::Body::empty(),
+        Poll::Ready(Some(first)) => hyper::Body::wrap_stream(Prepended {
+            first: Some(first),
+            body,
+        }),
+    };
+
+    Request::from_parts(parts, body)
+}
+
+// The body with its first chunk which was already polled.
+struct Prepended {
+    first: Option<Result<Bytes, hyper::Error>>,
+    body: hyper::Body,
+}
+
+impl Stream for Prepended {
+    type Item = Result<Bytes, hyper::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Some(first) = self.first.take() {
+            return Poll::Ready(Some(first));
+        }
+        Pin::new(&mut self.body).poll_data(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::HeaderValue;
+
+    #[test]
+    fn detects_expect_continue() {
+        let mut headers = HeaderMap::new();
+        assert!(!expects_continue(&headers));
+
+        headers.insert(header::EXPECT, HeaderValue::from_static("100-Continue"));
+        assert!(expects_continue(&headers));
+
+        headers.insert(header::EXPECT, HeaderValue::from_static("something-else"));
+        assert!(!expects_continue(&headers));
+    }
+
+    #[tokio::test]
+    async fn keeps_the_polled_body() {
+        let req = Request::new(hyper::Body::from("Hello world"));
+        let body = send_continue(req).into_body();
+        assert_eq!(hyper::body::to_bytes(body).await.unwrap(), "Hello world");
+    }
+}
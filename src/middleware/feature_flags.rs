@@ -0,0 +1,141 @@
+//! A pre middleware which evaluates the feature flags of each request once, for gradual rollouts.
+//!
+//! The flags are evaluated by a pluggable [`FeatureFlags`](./trait.FeatureFlags.html) provider, e.g. a client of a flag
+//! service, and the resulting [`FlagSet`](./struct.FlagSet.html) is stored in the request context. The route handlers
+//! check a flag through the [`RequestExt::flag`](../../ext/trait.RequestExt.html#tymethod.flag) method and the post
+//! middlewares and the error handler through the [`RequestInfo::context`](../../struct.RequestInfo.html#method.context)
+//! method.
+//!
+//! A request is evaluated only by the first flag middleware it passes through, so the same flags are seen by all the
+//! handlers of a request. Flags targeting users need the authentication middleware to run before this middleware and
+//! put the [`Principal`](../../struct.Principal.html) into the request context.
+//!
+//! # Examples
+//!
+//! ```
+//! use routerify::{Router, RequestInfo};
+//! use routerify::middleware::feature_flags::{self, FeatureFlags, FlagFuture, FlagSet};
+//! use routerify::prelude::*;
+//! use hyper::{Response, Body};
+//! use std::convert::Infallible;
+//!
+//! struct BetaHeader;
+//!
+//! impl FeatureFlags for BetaHeader {
+//!     fn evaluate<'a>(&'a self, req_info: &'a RequestInfo) -> FlagFuture<'a> {
+//!         Box::pin(async move {
+//!             let beta = req_info.headers().contains_key("x-beta");
+//!             FlagSet::new().with("new_checkout", beta)
+//!         })
+//!     }
+//! }
+//!
+//! # fn run() -> Router<Body, Infallible> {
+//! let router = Router::builder()
+//!     .middleware(feature_flags::middleware(BetaHeader))
+//!     .get("/checkout", |req| async move {
+//!         let page = if req.flag("new_checkout") { "New checkout" } else { "Checkout" };
+//!         Ok(Response::new(Body::from(page)))
+//!     })
+//!     .build()
+//!     .unwrap();
+//! # router
+//! # }
+//! # run();
+//! ```
+
+use crate::data_map::SharedDataMap;
+use crate::ext::RequestExt;
+use crate::middleware::Middleware;
+use crate::types::{RequestContext, RequestInfo};
+use hyper::{body::HttpBody, Request};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+/// The future returned by the [`FeatureFlags::evaluate`](./trait.FeatureFlags.html#tymethod.evaluate) method.
+pub type FlagFuture<'a> = Pin<Box<dyn Future<Output = FlagSet> + Send + 'a>>;
+
+/// A provider of the feature flags of the requests.
+pub trait FeatureFlags: Send + Sync + 'static {
+    /// Evaluates the flags of a request. The route isn't matched yet, so the route path and the params aren't
+    /// available.
+    ///
+    /// The provider should fall back to the default flags on failures, as the request is served anyway.
+    fn evaluate<'a>(&'a self, req_info: &'a RequestInfo) -> FlagFuture<'a>;
+}
+
+/// A static flag set is a provider which returns itself for every request.
+impl FeatureFlags for FlagSet {
+    fn evaluate<'a>(&'a self, _req_info: &'a RequestInfo) -> FlagFuture<'a> {
+        Box::pin(async move { self.clone() })
+    }
+}
+
+/// The evaluated feature flags of a request. A flag which isn't in the set is off.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FlagSet {
+    flags: Arc<HashMap<String, bool>>,
+}
+
+impl FlagSet {
+    /// Creates an empty flag set.
+    pub fn new() -> Self {
+        FlagSet::default()
+    }
+
+    /// Adds a flag with the specified state.
+    pub fn with<N: Into<String>>(mut self, name: N, enabled: bool) -> Self {
+        Arc::make_mut(&mut self.flags).insert(name.into(), enabled);
+        self
+    }
+
+    /// Checks if the specified flag is on.
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.flags.get(name).copied().unwrap_or(false)
+    }
+
+    /// Returns an iterator over the flags and their states.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, bool)> {
+        self.flags.iter().map(|(name, enabled)| (name.as_str(), *enabled))
+    }
+}
+
+/// Creates a pre middleware at the `/*` path which evaluates the flags by the provider.
+pub fn middleware<F, B, E>(flags: F) -> Middleware<B, E>
+where
+    F: FeatureFlags,
+    B: HttpBody + Send + Sync + 'static,
+    E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+{
+    middleware_with_path("/*", flags).unwrap()
+}
+
+/// Creates a pre middleware at the specified path which evaluates the flags by the provider.
+pub fn middleware_with_path<P, F, B, E>(path: P, flags: F) -> crate::Result<Middleware<B, E>>
+where
+    P: Into<String>,
+    F: FeatureFlags,
+    B: HttpBody + Send + Sync + 'static,
+    E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+{
+    let flags = Arc::new(flags);
+    Middleware::pre_with_path(path, move |req: Request<hyper::Body>| {
+        let flags = flags.clone();
+        async move {
+            if req.context::<FlagSet>().is_none() {
+                let ctx = req
+                    .extensions()
+                    .get::<RequestContext>()
+                    .cloned()
+                    .expect("Context must be present");
+                let mut req_info = RequestInfo::new_from_req(&req, ctx);
+                req_info.shared_data_maps = req.extensions().get::<Arc<[SharedDataMap]>>().cloned();
+
+                req.set_context(flags.evaluate(&req_info).await);
+            }
+            Ok(req)
+        }
+    })
+}
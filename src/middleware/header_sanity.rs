@@ -0,0 +1,358 @@
+//! A pre middleware which rejects the requests with the anomalies used for the request smuggling and the header based
+//! attacks.
+//!
+//! The [`HeaderSanity`](./struct.HeaderSanity.html) middleware detects the following [`Anomaly`](./enum.Anomaly.html)s:
+//!
+//! * Both the `Content-Length` and the `Transfer-Encoding` headers, multiple different or invalid `Content-Length`
+//!   values, or a `Transfer-Encoding` whose last coding isn't `chunked`.
+//! * More headers than the limit, or a header or all the headers together larger than the limits.
+//! * A NUL byte or another control character in the path, also if it's percent encoded.
+//! * Multiple `Host` headers.
+//!
+//! The parser of hyper already refuses some of these, but a request can reach the app with them through a proxy which
+//! parses it differently. The requests with too many or too large headers are rejected with
+//! [`HttpError`](../../struct.HttpError.html)s of status `431 Request Header Fields Too Large` and the rest with
+//! `400 Bad Request`. Each check can be turned off, and the rejects are counted per anomaly.
+//!
+//! # Examples
+//!
+//! ```
+//! use routerify::Router;
+//! use routerify::middleware::header_sanity::{Anomaly, HeaderSanity};
+//! use hyper::{Response, Body};
+//! use std::convert::Infallible;
+//!
+//! # fn run() -> Router<Body, Infallible> {
+//! let sanity = HeaderSanity::new().max_headers(50).allow(Anomaly::DuplicateHost);
+//!
+//! let router = Router::builder()
+//!     .middleware(sanity.middleware())
+//!     .get("/", |_| async move { Ok(Response::new(Body::from("Home"))) })
+//!     .build()
+//!     .unwrap();
+//!
+//! // E.g. in a metrics endpoint.
+//! let rejects = sanity.rejects(Anomaly::ConflictingLength);
+//! # router
+//! # }
+//! # run();
+//! ```
+
+use crate::middleware::pre::Handler;
+use crate::middleware::{Middleware, PreMiddleware};
+use crate::HttpError;
+use hyper::body::HttpBody;
+use hyper::header::{self, HeaderMap};
+use hyper::{Request, StatusCode};
+use percent_encoding::percent_decode_str;
+use std::fmt::{self, Display, Formatter};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+const ANOMALIES: [Anomaly; 6] = [
+    Anomaly::ConflictingLength,
+    Anomaly::TooManyHeaders,
+    Anomaly::HeaderTooLarge,
+    Anomaly::HeadersTooLarge,
+    Anomaly::ControlCharInPath,
+    Anomaly::DuplicateHost,
+];
+
+/// An anomaly of a request detected by the [`HeaderSanity`](./struct.HeaderSanity.html) middleware.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Anomaly {
+    /// Conflicting or invalid `Content-Length` and `Transfer-Encoding` headers.
+    ConflictingLength,
+    /// More headers than the limit.
+    TooManyHeaders,
+    /// A header larger than the limit.
+    HeaderTooLarge,
+    /// All the headers together larger than the limit.
+    HeadersTooLarge,
+    /// A NUL byte or another control character in the path.
+    ControlCharInPath,
+    /// Multiple `Host` headers.
+    DuplicateHost,
+}
+
+impl Anomaly {
+    fn idx(self) -> usize {
+        ANOMALIES.iter().position(|anomaly| *anomaly == self).unwrap()
+    }
+
+    fn status(self) -> StatusCode {
+        match self {
+            Anomaly::TooManyHeaders | Anomaly::HeaderTooLarge | Anomaly::HeadersTooLarge => {
+                StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE
+            }
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+impl Display for Anomaly {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            Anomaly::ConflictingLength => "The request has conflicting Content-Length and Transfer-Encoding headers",
+            Anomaly::TooManyHeaders => "The request has too many headers",
+            Anomaly::HeaderTooLarge => "The request has a too large header",
+            Anomaly::HeadersTooLarge => "The request headers are too large",
+            Anomaly::ControlCharInPath => "The request path has a control character",
+            Anomaly::DuplicateHost => "The request has multiple Host headers",
+        };
+        write!(f, "{}", msg)
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    max_headers: usize,
+    max_header_size: usize,
+    max_headers_size: usize,
+    allowed: Vec<Anomaly>,
+    rejects: [AtomicU64; ANOMALIES.len()],
+}
+
+/// The configuration and the reject counters of the header sanity middleware.
+///
+/// It's cheap to clone and the clones share the counters. Please refer to the [module](./index.html) documentation
+/// for more info.
+#[derive(Debug, Clone)]
+pub struct HeaderSanity {
+    inner: Arc<Inner>,
+}
+
+impl Default for HeaderSanity {
+    fn default() -> Self {
+        HeaderSanity::new()
+    }
+}
+
+impl HeaderSanity {
+    /// Creates a new configuration with all the checks on, at most `100` headers, `8 KiB` per header and `64 KiB` for
+    /// all the headers.
+    pub fn new() -> Self {
+        HeaderSanity {
+            inner: Arc::new(Inner {
+                max_headers: 100,
+                max_header_size: 8 * 1024,
+                max_headers_size: 64 * 1024,
+                allowed: Vec::new(),
+                rejects: Default::default(),
+            }),
+        }
+    }
+
+    /// Sets the maximum number of the headers.
+    ///
+    /// It should be called before the middlewares are created.
+    pub fn max_headers(mut self, max: usize) -> Self {
+        self.inner_mut().max_headers = max;
+        self
+    }
+
+    /// Sets the maximum size of a header, i.e. its name and its value.
+    ///
+    /// It should be called before the middlewares are created.
+    pub fn max_header_size(mut self, max: usize) -> Self {
+        self.inner_mut().max_header_size = max;
+        self
+    }
+
+    /// Sets the maximum size of all the headers together.
+    ///
+    /// It should be called before the middlewares are created.
+    pub fn max_headers_size(mut self, max: usize) -> Self {
+        self.inner_mut().max_headers_size = max;
+        self
+    }
+
+    /// Turns off the check of the anomaly.
+    ///
+    /// It should be called before the middlewares are created.
+    pub fn allow(mut self, anomaly: Anomaly) -> Self {
+        self.inner_mut().allowed.push(anomaly);
+        self
+    }
+
+    /// Returns the number of the requests rejected for the anomaly.
+    pub fn rejects(&self, anomaly: Anomaly) -> u64 {
+        self.inner.rejects[anomaly.idx()].load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of the requests rejected for any anomaly.
+    pub fn total_rejects(&self) -> u64 {
+        self.inner
+            .rejects
+            .iter()
+            .map(|count| count.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    /// Creates a pre middleware at the `/*` path.
+    pub fn middleware<B, E>(&self) -> Middleware<B, E>
+    where
+        B: HttpBody + Send + Sync + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        self.middleware_with_path("/*").unwrap()
+    }
+
+    /// Creates a pre middleware at the specified path.
+    pub fn middleware_with_path<P, B, E>(&self, path: P) -> crate::Result<Middleware<B, E>>
+    where
+        P: Into<String>,
+        B: HttpBody + Send + Sync + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        let sanity = self.clone();
+        let handler: Handler = Box::new(move |req: Request<hyper::Body>| {
+            let res = match sanity.detect(&req) {
+                Some(anomaly) => {
+                    sanity.inner.rejects[anomaly.idx()].fetch_add(1, Ordering::Relaxed);
+                    Err(HttpError::new(anomaly.status(), anomaly.to_string()).into())
+                }
+                None => Ok(req),
+            };
+            Box::new(async move { res })
+        });
+
+        Ok(Middleware::Pre(PreMiddleware::new_with_boxed_handler(
+            path, handler, 1,
+        )?))
+    }
+
+    fn inner_mut(&mut self) -> &mut Inner {
+        Arc::get_mut(&mut self.inner).expect("HeaderSanity must be configured before creating the middlewares")
+    }
+
+    fn detect(&self, req: &Request<hyper::Body>) -> Option<Anomaly> {
+        let headers = req.headers();
+        let checks: [(Anomaly, &dyn Fn() -> bool); ANOMALIES.len()] = [
+            (Anomaly::ConflictingLength, &|| has_conflicting_length(headers)),
+            (Anomaly::TooManyHeaders, &|| headers.len() > self.inner.max_headers),
+            (Anomaly::HeaderTooLarge, &|| {
+                headers
+                    .iter()
+                    .any(|(name, val)| name.as_str().len() + val.len() > self.inner.max_header_size)
+            }),
+            (Anomaly::HeadersTooLarge, &|| {
+                headers
+                    .iter()
+                    .map(|(name, val)| name.as_str().len() + val.len())
+                    .sum::<usize>()
+                    > self.inner.max_headers_size
+            }),
+            (Anomaly::ControlCharInPath, &|| has_control_char(req.uri().path())),
+            (Anomaly::DuplicateHost, &|| {
+                headers.get_all(header::HOST).iter().count() > 1
+            }),
+        ];
+
+        checks
+            .iter()
+            .filter(|(anomaly, _)| !self.inner.allowed.contains(anomaly))
+            .find(|(_, check)| check())
+            .map(|(anomaly, _)| *anomaly)
+    }
+}
+
+fn has_conflicting_length(headers: &HeaderMap) -> bool {
+    let mut lengths = headers
+        .get_all(header::CONTENT_LENGTH)
+        .iter()
+        .flat_map(|val| val.to_str().unwrap_or("invalid").split(','))
+        .map(|val| val.trim().parse::<u64>().ok());
+    let length = lengths.next();
+    if length == Some(None) || lengths.any(|other| Some(other) != length) {
+        return true;
+    }
+
+    let codings = headers
+        .get_all(header::TRANSFER_ENCODING)
+        .iter()
+        .map(|val| val.to_str().ok())
+        .collect::<Option<Vec<_>>>();
+    let codings = match codings {
+        Some(codings) if codings.is_empty() => return false,
+        Some(codings) => codings,
+        None => return true,
+    };
+
+    let is_chunked = codings
+        .iter()
+        .flat_map(|val| val.split(','))
+        .last()
+        .map(|coding| coding.trim().eq_ignore_ascii_case("chunked"))
+        .unwrap_or(false);
+    length.is_some() || !is_chunked
+}
+
+fn has_control_char(path: &str) -> bool {
+    path.bytes().any(|b| b.is_ascii_control()) || percent_decode_str(path).any(|b| b.is_ascii_control())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::HeaderValue;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, val) in pairs {
+            headers.append(*name, HeaderValue::from_static(val));
+        }
+        headers
+    }
+
+    #[test]
+    fn detects_conflicting_length() {
+        assert!(!has_conflicting_length(&headers(&[])));
+        assert!(!has_conflicting_length(&headers(&[("content-length", "5")])));
+        assert!(!has_conflicting_length(&headers(&[
+            ("content-length", "5"),
+            ("content-length", "5")
+        ])));
+        assert!(!has_conflicting_length(&headers(&[(
+            "transfer-encoding",
+            "gzip, chunked"
+        )])));
+        assert!(has_conflicting_length(&headers(&[
+            ("content-length", "5"),
+            ("content-length", "6")
+        ])));
+        assert!(has_conflicting_length(&headers(&[("content-length", "5, 6")])));
+        assert!(has_conflicting_length(&headers(&[("content-length", "-1")])));
+        assert!(has_conflicting_length(&headers(&[
+            ("content-length", "5"),
+            ("transfer-encoding", "chunked")
+        ])));
+        assert!(has_conflicting_length(&headers(&[(
+            "transfer-encoding",
+            "chunked, gzip"
+        )])));
+    }
+
+    #[test]
+    fn detects_control_chars() {
+        assert!(!has_control_char("/users/100%25"));
+        assert!(has_control_char("/users/a%00.json"));
+        assert!(has_control_char("/users/a%0d%0aSet-Cookie"));
+    }
+
+    #[test]
+    fn skips_allowed_anomalies() {
+        let req = Request::builder()
+            .uri("/a%00b")
+            .header("x-a", "1")
+            .header("x-b", "2")
+            .body(hyper::Body::empty())
+            .unwrap();
+
+        let sanity = HeaderSanity::new().max_headers(1);
+        assert_eq!(sanity.detect(&req), Some(Anomaly::TooManyHeaders));
+
+        let sanity = HeaderSanity::new().max_headers(1).allow(Anomaly::TooManyHeaders);
+        assert_eq!(sanity.detect(&req), Some(Anomaly::ControlCharInPath));
+    }
+}
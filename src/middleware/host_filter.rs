@@ -0,0 +1,215 @@
+//! A pre middleware which validates the host of the requests against an allowlist, e.g. against the DNS rebinding
+//! attacks.
+//!
+//! The host is taken from the `Host` header, or from the authority of the request URI if there is no such header e.g.
+//! for the HTTP/2 requests. The port and a trailing dot are ignored and the hosts are compared case insensitively. An
+//! allowed host which starts with `*.` matches any subdomain of the rest, but not the rest itself, e.g. `*.example.com`
+//! matches `api.example.com` and `a.b.example.com` but not `example.com`.
+//!
+//! The requests without a valid host are rejected with [`HttpError`](../../struct.HttpError.html)s of status
+//! `400 Bad Request` and the requests for the hosts which aren't allowed with `421 Misdirected Request`.
+//!
+//! # Examples
+//!
+//! ```
+//! use routerify::Router;
+//! use routerify::middleware::host_filter::HostFilter;
+//! use hyper::{Response, Body};
+//! use std::convert::Infallible;
+//!
+//! # fn run() -> Router<Body, Infallible> {
+//! let hosts = HostFilter::new().allow("example.com").allow("*.example.com").allow("localhost");
+//!
+//! let router = Router::builder()
+//!     .middleware(hosts.middleware())
+//!     .get("/", |_| async move { Ok(Response::new(Body::from("Home"))) })
+//!     .build()
+//!     .unwrap();
+//! # router
+//! # }
+//! # run();
+//! ```
+
+use crate::middleware::pre::Handler;
+use crate::middleware::{Middleware, PreMiddleware};
+use crate::HttpError;
+use hyper::body::HttpBody;
+use hyper::{header, Request, StatusCode};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Pattern {
+    Exact(String),
+    // The suffix including the leading dot, e.g. `.example.com`.
+    Subdomain(String),
+}
+
+impl Pattern {
+    fn parse(host: &str) -> Pattern {
+        let host = normalize(host);
+        match host.strip_prefix('*') {
+            Some(suffix) if suffix.starts_with('.') => Pattern::Subdomain(suffix.to_owned()),
+            _ => Pattern::Exact(host),
+        }
+    }
+
+    fn matches(&self, host: &str) -> bool {
+        match self {
+            Pattern::Exact(allowed) => allowed == host,
+            Pattern::Subdomain(suffix) => host.len() > suffix.len() && host.ends_with(suffix.as_str()),
+        }
+    }
+}
+
+/// The allowlist of the hosts.
+///
+/// Please refer to the [module](./index.html) documentation for more info.
+#[derive(Debug, Clone, Default)]
+pub struct HostFilter {
+    patterns: Arc<Vec<Pattern>>,
+}
+
+impl HostFilter {
+    /// Creates a new filter which allows no host.
+    pub fn new() -> Self {
+        HostFilter::default()
+    }
+
+    /// Allows the host, or any subdomain of a host if it starts with `*.`.
+    pub fn allow<H: AsRef<str>>(mut self, host: H) -> Self {
+        Arc::make_mut(&mut self.patterns).push(Pattern::parse(host.as_ref()));
+        self
+    }
+
+    /// Checks if the host, which may include a port, is allowed.
+    pub fn is_allowed(&self, host: &str) -> bool {
+        match strip_port(host) {
+            Some(host) => {
+                let host = normalize(host);
+                self.patterns.iter().any(|pattern| pattern.matches(&host))
+            }
+            None => false,
+        }
+    }
+
+    /// Creates a pre middleware at the `/*` path.
+    pub fn middleware<B, E>(&self) -> Middleware<B, E>
+    where
+        B: HttpBody + Send + Sync + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        self.middleware_with_path("/*").unwrap()
+    }
+
+    /// Creates a pre middleware at the specified path.
+    pub fn middleware_with_path<P, B, E>(&self, path: P) -> crate::Result<Middleware<B, E>>
+    where
+        P: Into<String>,
+        B: HttpBody + Send + Sync + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        let filter = self.clone();
+        let handler: Handler = Box::new(move |req: Request<hyper::Body>| {
+            let res = filter.check(&req).map(|_| req);
+            Box::new(async move { res })
+        });
+
+        Ok(Middleware::Pre(PreMiddleware::new_with_boxed_handler(
+            path, handler, 1,
+        )?))
+    }
+
+    fn check(&self, req: &Request<hyper::Body>) -> crate::Result<()> {
+        let host = match req.headers().get(header::HOST) {
+            Some(host) => host.to_str().ok(),
+            None => req.uri().authority().map(|authority| authority.as_str()),
+        };
+
+        let host = match host.filter(|host| strip_port(host).is_some()) {
+            Some(host) => host,
+            None => return Err(HttpError::new(StatusCode::BAD_REQUEST, "The request has no valid host").into()),
+        };
+
+        if self.is_allowed(host) {
+            Ok(())
+        } else {
+            Err(HttpError::new(
+                StatusCode::MISDIRECTED_REQUEST,
+                format!("The host {:?} is not allowed", host),
+            )
+            .into())
+        }
+    }
+}
+
+// Strips the port of a host, e.g. `example.com:8080` or `[::1]:8080`. It's `None` if the host is malformed.
+fn strip_port(host: &str) -> Option<&str> {
+    if host.starts_with('[') {
+        let end = host.find(']')?;
+        match &host[end + 1..] {
+            "" => {}
+            port => {
+                port.strip_prefix(':')?.parse::<u16>().ok()?;
+            }
+        }
+
+        let is_valid = end > 1
+            && host[1..end]
+                .bytes()
+                .all(|b| b.is_ascii_hexdigit() || matches!(b, b':' | b'.'));
+        return Some(&host[..=end]).filter(|_| is_valid);
+    }
+
+    let host = match host.rsplit_once(':') {
+        Some((host, port)) => {
+            port.parse::<u16>().ok()?;
+            host
+        }
+        None => host,
+    };
+
+    let is_valid = !host.is_empty()
+        && host
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_'));
+    Some(host).filter(|_| is_valid)
+}
+
+fn normalize(host: &str) -> String {
+    host.trim_end_matches('.').to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_port() {
+        assert_eq!(strip_port("example.com"), Some("example.com"));
+        assert_eq!(strip_port("example.com:8080"), Some("example.com"));
+        assert_eq!(strip_port("[::1]:8080"), Some("[::1]"));
+        assert_eq!(strip_port("[::1]"), Some("[::1]"));
+        assert_eq!(strip_port("example.com:port"), None);
+        assert_eq!(strip_port("evil.com/x"), None);
+        assert_eq!(strip_port("a:b:80"), None);
+        assert_eq!(strip_port(""), None);
+    }
+
+    #[test]
+    fn matches_allowed_hosts() {
+        let filter = HostFilter::new()
+            .allow("Example.com")
+            .allow("*.api.example.com")
+            .allow("[::1]");
+
+        assert!(filter.is_allowed("example.com"));
+        assert!(filter.is_allowed("EXAMPLE.com.:443"));
+        assert!(filter.is_allowed("v1.api.example.com"));
+        assert!(filter.is_allowed("a.b.api.example.com"));
+        assert!(filter.is_allowed("[::1]:3000"));
+        assert!(!filter.is_allowed("api.example.com"));
+        assert!(!filter.is_allowed("www.example.com"));
+        assert!(!filter.is_allowed("example.com.evil.com"));
+        assert!(!filter.is_allowed("evilapi.example.com"));
+    }
+}
@@ -0,0 +1,285 @@
+//! A post middleware which rewrites the HTML responses as they are streamed. It requires the `html-rewrite` feature.
+//!
+//! The rewriting is built on [`lol_html`](https://docs.rs/lol_html) and the [`map_body`](../../fn.map_body.html)
+//! facility, so the documents are never buffered as a whole. It can:
+//!
+//! * Inject HTML at the end of the `<head>` element, e.g. a stylesheet.
+//! * Inject HTML before the `</body>` tag, e.g. a live reload or an analytics script.
+//! * Prefix the root relative links, e.g. `/assets/app.js` becomes `/app/assets/app.js`, when the app is served behind a
+//!   path prefix by a reverse proxy.
+//!
+//! Only the `text/html` responses in UTF-8, without a `Content-Encoding`, are rewritten. The rewritten responses lose
+//! their `Content-Length` header.
+//!
+//! # Examples
+//!
+//! ```
+//! use routerify::Router;
+//! use routerify::middleware::html_rewrite::HtmlRewrite;
+//! use hyper::{Response, Body};
+//! use std::convert::Infallible;
+//!
+//! # fn run() -> Router<Body, Infallible> {
+//! let rewrite = HtmlRewrite::new()
+//!     .inject_body_end(r#"<script src="/livereload.js"></script>"#)
+//!     .prefix_links("/app");
+//!
+//! let router = Router::builder()
+//!     .middleware(rewrite.middleware())
+//!     .get("/", |_| async move {
+//!         let html = r#"<html><body><a href="/about">About</a></body></html>"#;
+//!         Ok(Response::builder().header("content-type", "text/html").body(Body::from(html)).unwrap())
+//!     })
+//!     .build()
+//!     .unwrap();
+//! # router
+//! # }
+//! # run();
+//! ```
+
+use crate::body::{map_body, BodyMapper};
+use crate::middleware::Middleware;
+use crate::Error;
+use hyper::body::Bytes;
+use hyper::header::{self, HeaderMap};
+use hyper::Response;
+use lol_html::html_content::ContentType;
+use lol_html::send::{Element, HtmlRewriter, Settings};
+use lol_html::{element, OutputSink};
+use std::sync::{Arc, Mutex};
+
+// The elements and their attributes holding the links to be prefixed.
+const LINK_ATTRIBUTES: &[(&str, &str)] = &[
+    ("a[href]", "href"),
+    ("link[href]", "href"),
+    ("img[src]", "src"),
+    ("script[src]", "src"),
+    ("source[src]", "src"),
+    ("iframe[src]", "src"),
+    ("form[action]", "action"),
+];
+
+/// The configuration of the HTML rewriting middleware.
+///
+/// Please refer to the [module](./index.html) documentation for more info.
+#[derive(Debug, Clone, Default)]
+pub struct HtmlRewrite {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Inner {
+    head: Vec<String>,
+    body_end: Vec<String>,
+    link_prefix: Option<String>,
+}
+
+impl HtmlRewrite {
+    /// Creates a new configuration which doesn't rewrite anything.
+    pub fn new() -> Self {
+        HtmlRewrite::default()
+    }
+
+    /// Injects the HTML at the end of the `<head>` element.
+    pub fn inject_head<H: Into<String>>(mut self, html: H) -> Self {
+        Arc::make_mut(&mut self.inner).head.push(html.into());
+        self
+    }
+
+    /// Injects the HTML before the `</body>` tag.
+    pub fn inject_body_end<H: Into<String>>(mut self, html: H) -> Self {
+        Arc::make_mut(&mut self.inner).body_end.push(html.into());
+        self
+    }
+
+    /// Prefixes the root relative links of the `href`, `src` and `action` attributes with the path prefix. The links
+    /// which already have the prefix and the injected HTML are left as is.
+    pub fn prefix_links<P: Into<String>>(mut self, prefix: P) -> Self {
+        let prefix = prefix.into().trim_end_matches('/').to_owned();
+        Arc::make_mut(&mut self.inner).link_prefix = Some(prefix).filter(|prefix| !prefix.is_empty());
+        self
+    }
+
+    /// Creates a post middleware at the `/*` path.
+    pub fn middleware<E>(&self) -> Middleware<hyper::Body, E>
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        self.middleware_with_path("/*").unwrap()
+    }
+
+    /// Creates a post middleware at the specified path.
+    pub fn middleware_with_path<P, E>(&self, path: P) -> crate::Result<Middleware<hyper::Body, E>>
+    where
+        P: Into<String>,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        let config = self.clone();
+        Middleware::post_with_path(path, move |res: Response<hyper::Body>| {
+            let config = config.clone();
+            async move {
+                if !is_rewritable(res.headers()) {
+                    return Ok(res);
+                }
+
+                let (mut parts, body) = res.into_parts();
+                parts.headers.remove(header::CONTENT_LENGTH);
+                Ok(Response::from_parts(parts, map_body(body, config.mapper())))
+            }
+        })
+    }
+
+    fn mapper(&self) -> HtmlMapper {
+        let mut handlers = Vec::new();
+
+        let head = self.inner.head.concat();
+        if !head.is_empty() {
+            handlers.push(element!("head", move |el: &mut Element<'_, '_>| {
+                el.append(&head, ContentType::Html);
+                Ok(())
+            }));
+        }
+
+        let body_end = self.inner.body_end.concat();
+        if !body_end.is_empty() {
+            handlers.push(element!("body", move |el: &mut Element<'_, '_>| {
+                el.append(&body_end, ContentType::Html);
+                Ok(())
+            }));
+        }
+
+        if let Some(ref prefix) = self.inner.link_prefix {
+            for &(selector, attr) in LINK_ATTRIBUTES {
+                let prefix = prefix.clone();
+                handlers.push(element!(selector, move |el: &mut Element<'_, '_>| {
+                    if let Some(link) = el.get_attribute(attr) {
+                        if let Some(prefixed) = prefix_link(&prefix, &link) {
+                            el.set_attribute(attr, &prefixed)?;
+                        }
+                    }
+                    Ok(())
+                }));
+            }
+        }
+
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let rewriter = HtmlRewriter::new(
+            Settings {
+                element_content_handlers: handlers,
+                ..Settings::new_send()
+            },
+            SharedSink(output.clone()),
+        );
+
+        HtmlMapper {
+            rewriter: Some(rewriter),
+            output,
+        }
+    }
+}
+
+// Collects the output of the rewriter, which is taken after each written chunk.
+struct SharedSink(Arc<Mutex<Vec<u8>>>);
+
+impl OutputSink for SharedSink {
+    fn handle_chunk(&mut self, chunk: &[u8]) {
+        self.0.lock().unwrap().extend_from_slice(chunk);
+    }
+}
+
+struct HtmlMapper {
+    rewriter: Option<HtmlRewriter<'static, SharedSink>>,
+    output: Arc<Mutex<Vec<u8>>>,
+}
+
+impl HtmlMapper {
+    fn take_output(&self) -> Bytes {
+        Bytes::from(std::mem::take(&mut *self.output.lock().unwrap()))
+    }
+}
+
+impl BodyMapper for HtmlMapper {
+    fn map_chunk(&mut self, chunk: Bytes) -> crate::Result<Bytes> {
+        if let Some(ref mut rewriter) = self.rewriter {
+            rewriter
+                .write(&chunk)
+                .map_err(|err| Error::new(format!("Couldn't rewrite the HTML response: {}", err)))?;
+        }
+        Ok(self.take_output())
+    }
+
+    fn finish(&mut self) -> crate::Result<Bytes> {
+        if let Some(rewriter) = self.rewriter.take() {
+            rewriter
+                .end()
+                .map_err(|err| Error::new(format!("Couldn't rewrite the HTML response: {}", err)))?;
+        }
+        Ok(self.take_output())
+    }
+}
+
+fn is_rewritable(headers: &HeaderMap) -> bool {
+    let content_type = match headers.get(header::CONTENT_TYPE).and_then(|val| val.to_str().ok()) {
+        Some(content_type) => content_type,
+        None => return false,
+    };
+
+    let mut params = content_type.split(';');
+    let is_html = params
+        .next()
+        .map(|media_type| media_type.trim().eq_ignore_ascii_case("text/html"))
+        .unwrap_or(false);
+    let is_utf8 = params
+        .filter_map(|param| param.trim().split_once('='))
+        .filter(|(name, _)| name.trim().eq_ignore_ascii_case("charset"))
+        .all(|(_, charset)| charset.trim().trim_matches('"').eq_ignore_ascii_case("utf-8"));
+    let is_identity = match headers.get(header::CONTENT_ENCODING) {
+        Some(encoding) => encoding.as_bytes().eq_ignore_ascii_case(b"identity"),
+        None => true,
+    };
+
+    is_html && is_utf8 && is_identity
+}
+
+fn prefix_link(prefix: &str, link: &str) -> Option<String> {
+    if !link.starts_with('/') || link.starts_with("//") {
+        return None;
+    }
+    if link == prefix || link.starts_with(&format!("{}/", prefix)) {
+        return None;
+    }
+    Some(format!("{}{}", prefix, link))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefixes_root_relative_links() {
+        assert_eq!(prefix_link("/app", "/about"), Some("/app/about".to_owned()));
+        assert_eq!(prefix_link("/app", "/app/about"), None);
+        assert_eq!(prefix_link("/app", "/apple"), Some("/app/apple".to_owned()));
+        assert_eq!(prefix_link("/app", "//cdn.example.com/app.js"), None);
+        assert_eq!(prefix_link("/app", "https://example.com/"), None);
+        assert_eq!(prefix_link("/app", "about"), None);
+    }
+
+    #[test]
+    fn rewrites_only_plain_html() {
+        let headers = |content_type: &'static str, encoding: Option<&'static str>| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::CONTENT_TYPE, content_type.parse().unwrap());
+            if let Some(encoding) = encoding {
+                headers.insert(header::CONTENT_ENCODING, encoding.parse().unwrap());
+            }
+            headers
+        };
+
+        assert!(is_rewritable(&headers("text/html", None)));
+        assert!(is_rewritable(&headers("Text/HTML; charset=UTF-8", None)));
+        assert!(!is_rewritable(&headers("text/html; charset=iso-8859-1", None)));
+        assert!(!is_rewritable(&headers("text/html", Some("gzip"))));
+        assert!(!is_rewritable(&headers("application/json", None)));
+    }
+}
@@ -0,0 +1,49 @@
+/// The kind of a middleware.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MiddlewareKind {
+    /// A pre middleware.
+    Pre,
+    /// A post middleware.
+    Post,
+    /// An around middleware.
+    Around,
+}
+
+/// Describes a middleware in the computed execution order of a router.
+///
+/// It's returned by the [`Router::middleware_order`](../struct.Router.html#method.middleware_order) method.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MiddlewareInfo {
+    pub(crate) kind: MiddlewareKind,
+    pub(crate) name: Option<String>,
+    pub(crate) path: String,
+    pub(crate) priority: i32,
+    pub(crate) scope_depth: u32,
+}
+
+impl MiddlewareInfo {
+    /// Returns the kind of the middleware.
+    pub fn kind(&self) -> MiddlewareKind {
+        self.kind
+    }
+
+    /// Returns the name of the middleware if it's [named](./enum.Middleware.html#method.named).
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Returns the path of the middleware including the paths of the scopes it is mounted on.
+    pub fn path(&self) -> &str {
+        self.path.as_str()
+    }
+
+    /// Returns the priority of the middleware.
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    /// Returns the scope depth of the middleware with regards to the top level router.
+    pub fn scope_depth(&self) -> u32 {
+        self.scope_depth
+    }
+}
@@ -0,0 +1,276 @@
+//! A pair of middlewares which negotiate the locale of each request against the supported locales.
+//!
+//! The pre middleware picks the locale from, in order:
+//!
+//! 1. The query parameter override, if it's configured by [`LocaleNegotiation::query_param`](./struct.LocaleNegotiation.html#method.query_param).
+//! 2. The cookie override, if it's configured by [`LocaleNegotiation::cookie`](./struct.LocaleNegotiation.html#method.cookie).
+//! 3. The `Accept-Language` header, by the quality values.
+//! 4. The first supported locale.
+//!
+//! A requested locale matches a supported one ASCII case-insensitively, or by the primary language subtag e.g. `de-AT`
+//! matches `de` and `de` matches `de-DE`. The chosen [`Locale`](./struct.Locale.html) is stored in the request
+//! context. The post middleware sets the `Content-Language` response header, unless the handler has set it, and adds
+//! `Accept-Language` to the `Vary` header.
+//!
+//! # Examples
+//!
+//! ```
+//! use routerify::Router;
+//! use routerify::middleware::locale::{Locale, LocaleNegotiation};
+//! use routerify::prelude::*;
+//! use hyper::{Response, Body};
+//! use std::convert::Infallible;
+//!
+//! # fn run() -> Router<Body, Infallible> {
+//! let locales = LocaleNegotiation::new(vec!["en", "de", "pt-BR"]).cookie("lang").query_param("lang");
+//!
+//! let router = Router::builder()
+//!     .middleware(locales.pre_middleware())
+//!     .middleware(locales.post_middleware())
+//!     .get("/", |req| async move {
+//!         let greeting = match req.context::<Locale>().unwrap().as_str() {
+//!             "de" => "Hallo",
+//!             "pt-BR" => "Olá",
+//!             _ => "Hello",
+//!         };
+//!         Ok(Response::new(Body::from(greeting)))
+//!     })
+//!     .build()
+//!     .unwrap();
+//! # router
+//! # }
+//! # run();
+//! ```
+
+use crate::ext::RequestExt;
+use crate::middleware::Middleware;
+use hyper::{
+    body::HttpBody,
+    header::{self, HeaderValue},
+    Request,
+};
+use percent_encoding::percent_decode_str;
+use std::fmt::{self, Display, Formatter};
+use std::sync::Arc;
+
+/// The negotiated locale of a request, stored in the request context.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Locale(String);
+
+impl Locale {
+    /// Returns the language tag of the locale as configured in the supported locales e.g. `pt-BR`.
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl Display for Locale {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// The configuration of the locale negotiation middlewares.
+///
+/// Please refer to the [module](./index.html) documentation for more info.
+#[derive(Debug, Clone)]
+pub struct LocaleNegotiation {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Clone)]
+struct Inner {
+    supported: Vec<String>,
+    cookie: Option<String>,
+    query_param: Option<String>,
+}
+
+impl LocaleNegotiation {
+    /// Creates a new configuration with the supported locales, as language tags. The first one is the default.
+    ///
+    /// # Panics
+    ///
+    /// It panics if there are no supported locales.
+    pub fn new<L: Into<String>>(supported: Vec<L>) -> Self {
+        let supported = supported.into_iter().map(Into::into).collect::<Vec<_>>();
+        assert!(!supported.is_empty(), "At least one supported locale is required");
+
+        LocaleNegotiation {
+            inner: Arc::new(Inner {
+                supported,
+                cookie: None,
+                query_param: None,
+            }),
+        }
+    }
+
+    /// Lets the specified cookie override the `Accept-Language` header.
+    pub fn cookie<N: Into<String>>(mut self, name: N) -> Self {
+        Arc::make_mut(&mut self.inner).cookie = Some(name.into());
+        self
+    }
+
+    /// Lets the specified query parameter override the cookie and the `Accept-Language` header.
+    pub fn query_param<N: Into<String>>(mut self, name: N) -> Self {
+        Arc::make_mut(&mut self.inner).query_param = Some(name.into());
+        self
+    }
+
+    /// Creates the pre middleware at the `/*` path which negotiates the locale.
+    pub fn pre_middleware<B, E>(&self) -> Middleware<B, E>
+    where
+        B: HttpBody + Send + Sync + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        self.pre_middleware_with_path("/*").unwrap()
+    }
+
+    /// Creates the pre middleware at the specified path which negotiates the locale.
+    pub fn pre_middleware_with_path<P, B, E>(&self, path: P) -> crate::Result<Middleware<B, E>>
+    where
+        P: Into<String>,
+        B: HttpBody + Send + Sync + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        let config = self.clone();
+        Middleware::pre_with_path(path, move |req: Request<hyper::Body>| {
+            let config = config.clone();
+            async move {
+                req.set_context(config.negotiate(&req));
+                Ok(req)
+            }
+        })
+    }
+
+    /// Creates the post middleware at the `/*` path which sets the `Content-Language` header.
+    pub fn post_middleware<B, E>(&self) -> Middleware<B, E>
+    where
+        B: HttpBody + Send + Sync + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        self.post_middleware_with_path("/*").unwrap()
+    }
+
+    /// Creates the post middleware at the specified path which sets the `Content-Language` header.
+    ///
+    /// The header is not added to the responses of the requests which didn't pass through the pre middleware.
+    pub fn post_middleware_with_path<P, B, E>(&self, path: P) -> crate::Result<Middleware<B, E>>
+    where
+        P: Into<String>,
+        B: HttpBody + Send + Sync + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        Middleware::post_with_info_with_path(path, |mut res, req_info| async move {
+            if let Some(locale) = req_info.context::<Locale>() {
+                let headers = res.headers_mut();
+                if !headers.contains_key(header::CONTENT_LANGUAGE) {
+                    if let Ok(value) = HeaderValue::from_str(locale.as_str()) {
+                        headers.insert(header::CONTENT_LANGUAGE, value);
+                    }
+                }
+                headers.append(header::VARY, HeaderValue::from_static("accept-language"));
+            }
+            Ok(res)
+        })
+    }
+
+    fn negotiate(&self, req: &Request<hyper::Body>) -> Locale {
+        let from_query = self.inner.query_param.as_deref().and_then(|name| {
+            req.uri()
+                .query()
+                .unwrap_or("")
+                .split('&')
+                .filter_map(|pair| pair.split_once('='))
+                .find(|(key, _)| *key == name)
+                .and_then(|(_, val)| percent_decode_str(val).decode_utf8().ok())
+                .and_then(|val| self.find(&val))
+        });
+
+        let from_cookie = || {
+            self.inner.cookie.as_deref().and_then(|name| {
+                req.headers()
+                    .get_all(header::COOKIE)
+                    .iter()
+                    .filter_map(|val| val.to_str().ok())
+                    .flat_map(|val| val.split(';'))
+                    .filter_map(|pair| pair.trim().split_once('='))
+                    .find(|(key, _)| *key == name)
+                    .and_then(|(_, val)| self.find(val.trim_matches('"')))
+            })
+        };
+
+        let from_header = || {
+            req.headers()
+                .get(header::ACCEPT_LANGUAGE)
+                .and_then(|val| val.to_str().ok())
+                .and_then(|val| self.negotiate_accept_language(val))
+        };
+
+        let locale = from_query
+            .or_else(from_cookie)
+            .or_else(from_header)
+            .unwrap_or(&self.inner.supported[0]);
+        Locale(locale.clone())
+    }
+
+    // Picks the supported locale of the highest quality requested one, preferring the earlier ones on a tie.
+    fn negotiate_accept_language(&self, accept_language: &str) -> Option<&String> {
+        let mut requested = accept_language
+            .split(',')
+            .filter_map(|item| {
+                let mut parts = item.split(';');
+                let tag = parts.next()?.trim();
+                let quality = parts
+                    .filter_map(|param| param.trim().strip_prefix("q="))
+                    .find_map(|q| q.trim().parse::<f32>().ok())
+                    .map(|q| (q.clamp(0.0, 1.0) * 1000.0) as u32)
+                    .unwrap_or(1000);
+                if tag.is_empty() || quality == 0 {
+                    None
+                } else {
+                    Some((tag, quality))
+                }
+            })
+            .collect::<Vec<_>>();
+        requested.sort_by_key(|(_, quality)| std::cmp::Reverse(*quality));
+
+        requested.into_iter().find_map(|(tag, _)| match tag {
+            "*" => Some(&self.inner.supported[0]),
+            tag => self.find(tag),
+        })
+    }
+
+    fn find(&self, tag: &str) -> Option<&String> {
+        let supported = &self.inner.supported;
+        supported
+            .iter()
+            .find(|locale| locale.eq_ignore_ascii_case(tag))
+            .or_else(|| {
+                let language = primary_language(tag);
+                supported
+                    .iter()
+                    .find(|locale| primary_language(locale).eq_ignore_ascii_case(language))
+            })
+    }
+}
+
+fn primary_language(tag: &str) -> &str {
+    tag.split(['-', '_']).next().unwrap_or(tag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negotiates_accept_language() {
+        let locales = LocaleNegotiation::new(vec!["en", "de-DE", "pt-BR"]);
+        let negotiate = |val| locales.negotiate_accept_language(val).map(|l| l.as_str());
+
+        assert_eq!(negotiate("fr, de;q=0.8, en;q=0.5"), Some("de-DE"));
+        assert_eq!(negotiate("PT-br"), Some("pt-BR"));
+        assert_eq!(negotiate("de-AT;q=0.9, en;q=0.95"), Some("en"));
+        assert_eq!(negotiate("fr, *;q=0.1"), Some("en"));
+        assert_eq!(negotiate("fr, de;q=0"), None);
+    }
+}
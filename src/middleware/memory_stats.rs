@@ -0,0 +1,22 @@
+use crate::ext::RequestExt;
+use crate::middleware::Middleware;
+use crate::types::RequestMemory;
+use hyper::{body::HttpBody, Request};
+
+/// Creates a pre middleware which instruments the memory attributed to the request and puts a
+/// [`RequestMemory`](../struct.RequestMemory.html) into the request context.
+///
+/// It should be added before the middlewares which buffer the request body or put data into the context, so their
+/// memory is counted. Please refer to the [`RequestMemory`](../struct.RequestMemory.html) for an example.
+pub fn memory_stats<B, E>() -> Middleware<B, E>
+where
+    B: HttpBody + Send + Sync + 'static,
+    E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+{
+    Middleware::pre(|req: Request<hyper::Body>| async move {
+        if req.context::<RequestMemory>().is_none() {
+            req.set_context(RequestMemory::new());
+        }
+        Ok(req)
+    })
+}
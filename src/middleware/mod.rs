@@ -1,12 +1,49 @@
+use self::dependency::{Dependencies, Dependency};
 use crate::types::RequestInfo;
+use crate::RouteError;
 use hyper::{body::HttpBody, Request, Response};
 use std::future::Future;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
 
+pub use self::around::{AroundMiddleware, Next};
+pub use self::cache_control::cache_control_for;
+pub use self::info::{MiddlewareInfo, MiddlewareKind};
+pub use self::memory_stats::memory_stats;
 pub use self::post::PostMiddleware;
 pub use self::pre::PreMiddleware;
+pub use self::response_stats::response_stats;
 
+pub mod admission;
+mod around;
+pub mod audit;
+mod cache_control;
+pub mod chaos;
+#[cfg(feature = "checksum")]
+pub mod checksum;
+pub mod coalesce;
+pub mod csp;
+mod dependency;
+pub mod expect_continue;
+pub mod feature_flags;
+pub mod header_sanity;
+pub mod host_filter;
+#[cfg(feature = "html-rewrite")]
+pub mod html_rewrite;
+mod info;
+pub mod locale;
+mod memory_stats;
+#[cfg(feature = "opentelemetry")]
+pub mod otel;
 mod post;
 mod pre;
+mod response_stats;
+pub mod response_throttle;
+pub mod rewrite;
+pub mod throttle;
+pub mod transactional;
+#[cfg(feature = "webhook")]
+pub mod webhook_signature;
 
 /// Enum type for all the middleware types. Please refer to the [Middleware](./index.html#middleware) for more info.
 ///
@@ -23,6 +60,9 @@ pub enum Middleware<B, E> {
 
     /// Variant for the post middleware. Refer to [Post Middleware](./index.html#post-middleware) for more info.
     Post(PostMiddleware<B, E>),
+
+    /// Variant for the around middleware. Refer to [Around Middleware](./index.html#around-middleware) for more info.
+    Around(AroundMiddleware<B, E>),
 }
 
 impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static>
@@ -115,6 +155,42 @@ impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Se
         Middleware::post_with_info_with_path("/*", handler).unwrap()
     }
 
+    /// Creates a post middleware at the `/*` path which gets the outcome of the route, i.e. its response or its error,
+    /// and the time elapsed since the router got the request, right before the error handler runs. It's meant for the
+    /// logging and the metrics which must tell the failures apart, even if the error handler answers them with a
+    /// successful status.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::{Router, Middleware};
+    /// use hyper::{Response, Body};
+    /// use std::convert::Infallible;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// # fn run() -> Router<Body, Infallible> {
+    /// let errors = Arc::new(AtomicUsize::new(0));
+    ///
+    /// let router = Router::builder()
+    ///      .middleware(Middleware::post_with_result(move |result, _elapsed| {
+    ///          if result.is_err() {
+    ///              errors.fetch_add(1, Ordering::Relaxed);
+    ///          }
+    ///      }))
+    ///      .build()
+    ///      .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    pub fn post_with_result<H>(handler: H) -> Middleware<B, E>
+    where
+        H: Fn(Result<&Response<B>, &RouteError>, Duration) + Send + Sync + 'static,
+    {
+        Middleware::post_with_result_with_path("/*", handler).unwrap()
+    }
+
     /// Create a pre middleware with a handler at the specified path.
     ///
     /// # Examples
@@ -204,4 +280,204 @@ impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Se
     {
         Ok(Middleware::Post(PostMiddleware::new_with_info(path, handler)?))
     }
+
+    /// Creates a post middleware at the specified path which gets the outcome of the route right before the error
+    /// handler runs.
+    ///
+    /// Please refer to the [`post_with_result`](#method.post_with_result) method for more info.
+    pub fn post_with_result_with_path<P, H>(path: P, handler: H) -> crate::Result<Middleware<B, E>>
+    where
+        P: Into<String>,
+        H: Fn(Result<&Response<B>, &RouteError>, Duration) + Send + Sync + 'static,
+    {
+        Ok(Middleware::Post(PostMiddleware::new_with_result(path, handler)?))
+    }
+
+    /// Creates an around middleware with a handler at the `/*` path. The handler wraps the rest of the chain, which it
+    /// runs by the [`Next::run`](./struct.Next.html#method.run) method.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::{Router, Middleware, RouteError};
+    /// use hyper::Body;
+    /// use std::time::Instant;
+    ///
+    /// # fn run() -> Router<Body, RouteError> {
+    /// let router = Router::builder()
+    ///      .middleware(Middleware::around(|req, next| async move {
+    ///          let started_at = Instant::now();
+    ///          let res = next.run(req).await;
+    ///          println!("Handled in {:?}", started_at.elapsed());
+    ///          res
+    ///      }))
+    ///      .build()
+    ///      .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    pub fn around<H, R>(handler: H) -> Middleware<B, E>
+    where
+        H: Fn(Request<hyper::Body>, Next<B>) -> R + Send + Sync + 'static,
+        R: Future<Output = crate::Result<Response<B>>> + Send + 'static,
+    {
+        Middleware::around_with_path("/*", handler).unwrap()
+    }
+
+    /// Creates an around middleware with a handler at the specified path.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::{Router, Middleware, RouteError};
+    /// use hyper::Body;
+    ///
+    /// # fn run() -> Router<Body, RouteError> {
+    /// let router = Router::builder()
+    ///      .middleware(Middleware::around_with_path("/my-path", |req, next| async move { next.run(req).await }).unwrap())
+    ///      .build()
+    ///      .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    pub fn around_with_path<P, H, R>(path: P, handler: H) -> crate::Result<Middleware<B, E>>
+    where
+        P: Into<String>,
+        H: Fn(Request<hyper::Body>, Next<B>) -> R + Send + Sync + 'static,
+        R: Future<Output = crate::Result<Response<B>>> + Send + 'static,
+    {
+        Ok(Middleware::Around(AroundMiddleware::new(path, handler)?))
+    }
+
+    /// Gives the middleware a name, so that it can be skipped for specific routes by the
+    /// [`RouterBuilder::get_skipping`](./struct.RouterBuilder.html#method.get_skipping) method.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::{Router, Middleware};
+    /// use hyper::{Request, Body};
+    /// use std::convert::Infallible;
+    ///
+    /// # fn run() -> Router<Body, Infallible> {
+    /// let router = Router::builder()
+    ///      .middleware(Middleware::pre(|req| async move { /* Do some operations */ Ok(req) }).named("access_log"))
+    ///      .build()
+    ///      .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    pub fn named<N: Into<String>>(self, name: N) -> Middleware<B, E> {
+        match self {
+            Middleware::Pre(mut middleware) => {
+                middleware.name = Some(name.into());
+                Middleware::Pre(middleware)
+            }
+            Middleware::Post(mut middleware) => {
+                middleware.name = Some(name.into());
+                Middleware::Post(middleware)
+            }
+            Middleware::Around(mut middleware) => {
+                middleware.name = Some(name.into());
+                Middleware::Around(middleware)
+            }
+        }
+    }
+
+    /// Declares that the middleware needs a value of the type `T` from the middlewares executed before it, e.g. the
+    /// [`Principal`](./struct.Principal.html) of the authentication middleware in the request context.
+    ///
+    /// The declared dependencies are verified when the router is served, i.e. by the
+    /// [`RequestServiceBuilder::new`](./struct.RequestServiceBuilder.html#method.new) method, which fails if no middleware
+    /// covering the path of this middleware and [providing](#method.provides) the type is executed before it. The pre
+    /// middlewares are executed before the around middlewares, which are executed before the post middlewares.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::{Router, Middleware, Principal, RequestInfo, RequestServiceBuilder};
+    /// use routerify::prelude::*;
+    /// use hyper::{Request, Body};
+    /// use std::convert::Infallible;
+    ///
+    /// # fn run() -> Router<Body, Infallible> {
+    /// let router = Router::builder()
+    ///      .middleware(
+    ///          Middleware::post_with_info(|res, req_info: RequestInfo| async move {
+    ///              println!("Served {:?}", req_info.context::<Principal>());
+    ///              Ok(res)
+    ///          })
+    ///          .requires::<Principal>(),
+    ///      )
+    ///      .middleware(
+    ///          Middleware::pre(|req: Request<Body>| async move {
+    ///              req.set_context(Principal::new("anonymous"));
+    ///              Ok(req)
+    ///          })
+    ///          .provides::<Principal>(),
+    ///      )
+    ///      .build()
+    ///      .unwrap();
+    /// # router
+    /// # }
+    /// # let router = run();
+    /// assert!(RequestServiceBuilder::new(router).is_ok());
+    /// ```
+    pub fn requires<T: 'static>(mut self) -> Middleware<B, E> {
+        self.dependencies_mut().requires.push(Dependency::of::<T>());
+        self
+    }
+
+    /// Declares that the middleware provides a value of the type `T` to the middlewares executed after it.
+    ///
+    /// Please refer to the [`requires`](#method.requires) method for more info.
+    pub fn provides<T: 'static>(mut self) -> Middleware<B, E> {
+        self.dependencies_mut().provides.push(Dependency::of::<T>());
+        self
+    }
+
+    fn dependencies_mut(&mut self) -> &mut Dependencies {
+        match self {
+            Middleware::Pre(ref mut middleware) => &mut middleware.dependencies,
+            Middleware::Post(ref mut middleware) => &mut middleware.dependencies,
+            Middleware::Around(ref mut middleware) => &mut middleware.dependencies,
+        }
+    }
+
+    /// Sets whether the middleware is initially enabled. Middlewares are enabled by default.
+    ///
+    /// A [named](#method.named) middleware can be enabled or disabled at runtime through a
+    /// [`RouterHandle`](./struct.RouterHandle.html).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::{Router, Middleware};
+    /// use hyper::{Request, Body};
+    /// use std::convert::Infallible;
+    ///
+    /// # fn run() -> Router<Body, Infallible> {
+    /// let router = Router::builder()
+    ///      .middleware(Middleware::pre(|req| async move { /* Log the request */ Ok(req) }).named("debug_log").enabled(false))
+    ///      .build()
+    ///      .unwrap();
+    ///
+    /// let handle = router.handle();
+    /// // Later, without redeploying.
+    /// handle.set_middleware_enabled("debug_log", true).unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    pub fn enabled(self, enabled: bool) -> Middleware<B, E> {
+        match self {
+            Middleware::Pre(ref middleware) => middleware.enabled.store(enabled, Ordering::Relaxed),
+            Middleware::Post(ref middleware) => middleware.enabled.store(enabled, Ordering::Relaxed),
+            Middleware::Around(ref middleware) => middleware.enabled.store(enabled, Ordering::Relaxed),
+        }
+        self
+    }
 }
@@ -0,0 +1,266 @@
+//! A pair of middlewares which trace the requests with [OpenTelemetry](https://docs.rs/opentelemetry). It requires the
+//! `opentelemetry` feature.
+//!
+//! The pre middleware extracts the [W3C trace context](https://www.w3.org/TR/trace-context/) from the `traceparent` and
+//! `tracestate` request headers and starts a server span as its child, through the tracer of the globally installed
+//! tracer provider. The span context is stored in the request context, so the route handlers can read it through the
+//! [`RequestExt::trace_context`](../../ext/trait.RequestExt.html#method.trace_context) method. The post middleware names
+//! the span after the matched route, records the path with the parameters redacted by the shared
+//! [`Redaction`](../../struct.Redaction.html) rules and the response status, and ends the span.
+//!
+//! With the `client` feature, the [`RequestExt::http_client`](../../ext/trait.RequestExt.html#method.http_client) method
+//! propagates the span to the outbound requests.
+//!
+//! # Examples
+//!
+//! ```
+//! use routerify::Router;
+//! use routerify::middleware::otel::OpenTelemetry;
+//! use hyper::{Response, Body};
+//! use std::convert::Infallible;
+//!
+//! # fn run() -> Router<Body, Infallible> {
+//! let otel = OpenTelemetry::new();
+//!
+//! let router = Router::builder()
+//!     .middleware(otel.pre_middleware())
+//!     .middleware(otel.post_middleware())
+//!     .get("/users/:id", |_| async move { Ok(Response::new(Body::from("User"))) })
+//!     .build()
+//!     .unwrap();
+//! # router
+//! # }
+//! # run();
+//! ```
+
+use crate::ext::RequestExt;
+use crate::middleware::Middleware;
+use crate::types::Redaction;
+use hyper::{
+    body::HttpBody,
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Request,
+};
+use opentelemetry::trace::{
+    SpanContext, SpanId, SpanKind, Status, TraceContextExt, TraceFlags, TraceId, TraceState, Tracer,
+};
+use opentelemetry::{global, Context, KeyValue};
+use std::borrow::Cow;
+use std::str::FromStr;
+
+const TRACEPARENT_HEADER: &str = "traceparent";
+const TRACESTATE_HEADER: &str = "tracestate";
+
+/// The trace context of the request span stored in the request context.
+#[derive(Debug, Clone)]
+pub(crate) struct TraceContext(pub(crate) Context);
+
+/// The configuration of the OpenTelemetry middlewares.
+///
+/// Please refer to the [module](./index.html) documentation for more info.
+#[derive(Debug, Clone)]
+pub struct OpenTelemetry {
+    tracer_name: Cow<'static, str>,
+}
+
+impl OpenTelemetry {
+    /// Creates a new configuration which traces with the `routerify` tracer.
+    pub fn new() -> Self {
+        OpenTelemetry {
+            tracer_name: Cow::Borrowed("routerify"),
+        }
+    }
+
+    /// Sets the name of the tracer obtained from the global tracer provider.
+    pub fn tracer_name<N: Into<Cow<'static, str>>>(mut self, name: N) -> Self {
+        self.tracer_name = name.into();
+        self
+    }
+
+    /// Creates the pre middleware at the `/*` path which starts the span.
+    pub fn pre_middleware<B, E>(&self) -> Middleware<B, E>
+    where
+        B: HttpBody + Send + Sync + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        self.pre_middleware_with_path("/*").unwrap()
+    }
+
+    /// Creates the pre middleware at the specified path which starts the span.
+    pub fn pre_middleware_with_path<P, B, E>(&self, path: P) -> crate::Result<Middleware<B, E>>
+    where
+        P: Into<String>,
+        B: HttpBody + Send + Sync + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        let config = self.clone();
+        Middleware::pre_with_path(path, move |req: Request<hyper::Body>| {
+            let config = config.clone();
+            async move {
+                if req.trace_context().is_none() {
+                    let parent_cx = extract(req.headers());
+                    let tracer = global::tracer(config.tracer_name.clone());
+                    let span = tracer
+                        .span_builder(req.method().to_string())
+                        .with_kind(SpanKind::Server)
+                        .with_attributes(vec![KeyValue::new("http.request.method", req.method().to_string())])
+                        .start_with_context(&tracer, &parent_cx);
+                    req.set_context(TraceContext(parent_cx.with_span(span)));
+                }
+                Ok(req)
+            }
+        })
+    }
+
+    /// Creates the post middleware at the `/*` path which ends the span.
+    pub fn post_middleware<B, E>(&self) -> Middleware<B, E>
+    where
+        B: HttpBody + Send + Sync + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        self.post_middleware_with_path("/*").unwrap()
+    }
+
+    /// Creates the post middleware at the specified path which ends the span.
+    ///
+    /// The `5xx` responses set the span status to error.
+    pub fn post_middleware_with_path<P, B, E>(&self, path: P) -> crate::Result<Middleware<B, E>>
+    where
+        P: Into<String>,
+        B: HttpBody + Send + Sync + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        Middleware::post_with_info_with_path(path, |res, req_info| async move {
+            if let Some(TraceContext(cx)) = req_info.context::<TraceContext>() {
+                let span = cx.span();
+                if let Some(route) = req_info.route_path() {
+                    span.update_name(format!("{} {}", req_info.method(), route));
+                    span.set_attribute(KeyValue::new("http.route", route.to_owned()));
+                }
+                // The path is recorded once the route parameters are known, so the redacted ones can be replaced.
+                let path = match req_info.data::<Redaction>() {
+                    Some(redaction) => redaction.redact_path(req_info.uri().path(), req_info.params()),
+                    None => req_info.uri().path().to_owned(),
+                };
+                span.set_attribute(KeyValue::new("url.path", path));
+                span.set_attribute(KeyValue::new("http.response.status_code", res.status().as_u16() as i64));
+                if res.status().is_server_error() {
+                    span.set_status(Status::error(res.status().to_string()));
+                }
+                span.end();
+            }
+            Ok(res)
+        })
+    }
+}
+
+impl Default for OpenTelemetry {
+    fn default() -> Self {
+        OpenTelemetry::new()
+    }
+}
+
+/// Extracts the W3C trace context from the `traceparent` and `tracestate` headers.
+///
+/// It returns an empty context if the `traceparent` header is missing or invalid.
+pub fn extract(headers: &HeaderMap) -> Context {
+    let traceparent = headers.get(TRACEPARENT_HEADER).and_then(|val| val.to_str().ok());
+    let tracestate = headers
+        .get(TRACESTATE_HEADER)
+        .and_then(|val| val.to_str().ok())
+        .unwrap_or("");
+
+    match traceparent.and_then(|traceparent| parse_traceparent(traceparent, tracestate)) {
+        Some(span_context) => Context::new().with_remote_span_context(span_context),
+        None => Context::new(),
+    }
+}
+
+/// Injects the span of the context as the W3C `traceparent` and `tracestate` headers.
+///
+/// The headers are left untouched if the context has no valid span.
+pub fn inject(cx: &Context, headers: &mut HeaderMap) {
+    let span = cx.span();
+    let span_context = span.span_context();
+    if !span_context.is_valid() {
+        return;
+    }
+
+    let traceparent = format!(
+        "00-{}-{}-{:02x}",
+        span_context.trace_id(),
+        span_context.span_id(),
+        span_context.trace_flags().to_u8() & TraceFlags::SAMPLED.to_u8()
+    );
+    if let Ok(value) = HeaderValue::from_str(&traceparent) {
+        headers.insert(HeaderName::from_static(TRACEPARENT_HEADER), value);
+    }
+
+    let tracestate = span_context.trace_state().header();
+    match HeaderValue::from_str(&tracestate) {
+        Ok(value) if !tracestate.is_empty() => {
+            headers.insert(HeaderName::from_static(TRACESTATE_HEADER), value);
+        }
+        _ => {
+            headers.remove(TRACESTATE_HEADER);
+        }
+    }
+}
+
+fn parse_traceparent(traceparent: &str, tracestate: &str) -> Option<SpanContext> {
+    let parts = traceparent.trim().split('-').collect::<Vec<_>>();
+    if parts.len() < 4 {
+        return None;
+    }
+
+    let (version, trace_id, span_id, flags) = (parts[0], parts[1], parts[2], parts[3]);
+    let is_hex =
+        |val: &str, len: usize| val.len() == len && val.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'));
+    if !is_hex(version, 2) || version == "ff" || (version == "00" && parts.len() != 4) {
+        return None;
+    }
+    if !is_hex(trace_id, 32) || !is_hex(span_id, 16) || !is_hex(flags, 2) {
+        return None;
+    }
+
+    let trace_id = TraceId::from_hex(trace_id).ok()?;
+    let span_id = SpanId::from_hex(span_id).ok()?;
+    if trace_id == TraceId::INVALID || span_id == SpanId::INVALID {
+        return None;
+    }
+    let flags = TraceFlags::new(u8::from_str_radix(flags, 16).ok()?) & TraceFlags::SAMPLED;
+    let trace_state = TraceState::from_str(tracestate).unwrap_or_default();
+
+    Some(SpanContext::new(trace_id, span_id, flags, true, trace_state))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_trace_context() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            TRACEPARENT_HEADER,
+            HeaderValue::from_static("00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01"),
+        );
+        headers.insert(TRACESTATE_HEADER, HeaderValue::from_static("congo=t61rcWkgMzE"));
+
+        let cx = extract(&headers);
+        assert!(cx.span().span_context().is_remote());
+
+        let mut injected = HeaderMap::new();
+        inject(&cx, &mut injected);
+        assert_eq!(injected, headers);
+    }
+
+    #[test]
+    fn rejects_invalid_traceparents() {
+        assert!(parse_traceparent("00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01", "").is_some());
+        assert!(parse_traceparent("00-00000000000000000000000000000000-b7ad6b7169203331-01", "").is_none());
+        assert!(parse_traceparent("00-0AF7651916CD43DD8448EB211C80319C-b7ad6b7169203331-01", "").is_none());
+        assert!(parse_traceparent("ff-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01", "").is_none());
+        assert!(parse_traceparent("00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331", "").is_none());
+    }
+}
@@ -1,11 +1,16 @@
+use crate::error::into_route_error;
+use crate::middleware::dependency::Dependencies;
 use crate::regex_generator::generate_exact_match_regex;
 use crate::types::RequestInfo;
-use crate::Error;
+use crate::{Error, RouteError};
 use hyper::{body::HttpBody, Response};
 use regex::Regex;
 use std::fmt::{self, Debug, Formatter};
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 
 type HandlerWithoutInfo<B, E> = Box<dyn Fn(Response<B>) -> HandlerWithoutInfoReturn<B, E> + Send + Sync + 'static>;
 type HandlerWithoutInfoReturn<B, E> = Box<dyn Future<Output = Result<Response<B>, E>> + Send + 'static>;
@@ -14,6 +19,8 @@ type HandlerWithInfo<B, E> =
     Box<dyn Fn(Response<B>, RequestInfo) -> HandlerWithInfoReturn<B, E> + Send + Sync + 'static>;
 type HandlerWithInfoReturn<B, E> = Box<dyn Future<Output = Result<Response<B>, E>> + Send + 'static>;
 
+type HandlerWithResult<B> = Box<dyn Fn(Result<&Response<B>, &RouteError>, Duration) + Send + Sync + 'static>;
+
 /// The post middleware type. Refer to [Post Middleware](./index.html#post-middleware) for more info.
 ///
 /// This `PostMiddleware<B, E>` type accepts two type parameters: `B` and `E`.
@@ -30,11 +37,21 @@ pub struct PostMiddleware<B, E> {
     pub(crate) handler: Option<Handler<B, E>>,
     // Scope depth with regards to the top level router.
     pub(crate) scope_depth: u32,
+    // Middlewares with a higher priority are executed first.
+    pub(crate) priority: i32,
+    // The name by which the middleware can be skipped by the routes.
+    pub(crate) name: Option<String>,
+    // The runtime switch of the middleware which is shared with the router handles.
+    pub(crate) enabled: Arc<AtomicBool>,
+    // The types which the middleware requires from and provides to the other middlewares.
+    pub(crate) dependencies: Dependencies,
 }
 
 pub(crate) enum Handler<B, E> {
     WithoutInfo(HandlerWithoutInfo<B, E>),
     WithInfo(HandlerWithInfo<B, E>),
+    // It's called with the outcome of the route before the error handler runs, and it passes the response through.
+    WithResult(HandlerWithResult<B>),
 }
 
 impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static>
@@ -58,6 +75,10 @@ impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Se
             regex: re,
             handler: Some(handler),
             scope_depth,
+            priority: 0,
+            name: None,
+            enabled: Arc::new(AtomicBool::new(true)),
+            dependencies: Dependencies::default(),
         })
     }
 
@@ -127,11 +148,49 @@ impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Se
         PostMiddleware::new_with_boxed_handler(path, Handler::WithInfo(handler), 1)
     }
 
+    /// Creates a post middleware which gets the outcome of the route at the specified path, i.e. its response or its
+    /// error, and the time elapsed since the router got the request. It runs right before the error handler converts
+    /// an error into a response, so the error rates are counted right even if the error handler answers the errors
+    /// with a successful status.
+    ///
+    /// The outcome is the one of the pre middlewares and the route handler; the errors of the other post middlewares
+    /// aren't reported. The handler doesn't change the response.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::{Router, Middleware, PostMiddleware};
+    /// use hyper::{Response, Body};
+    /// use std::convert::Infallible;
+    ///
+    /// # fn run() -> Router<Body, Infallible> {
+    /// let router = Router::builder()
+    ///      .middleware(Middleware::Post(PostMiddleware::new_with_result("/api/*", |result, elapsed| {
+    ///          println!("Failed: {}, elapsed: {:?}", result.is_err(), elapsed);
+    ///      }).unwrap()))
+    ///      .build()
+    ///      .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    pub fn new_with_result<P, H>(path: P, handler: H) -> crate::Result<PostMiddleware<B, E>>
+    where
+        P: Into<String>,
+        H: Fn(Result<&Response<B>, &RouteError>, Duration) + Send + Sync + 'static,
+    {
+        PostMiddleware::new_with_boxed_handler(path, Handler::WithResult(Box::new(handler)), 1)
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
     pub(crate) fn should_require_req_meta(&self) -> bool {
         if let Some(ref handler) = self.handler {
             match handler {
                 Handler::WithInfo(_) => true,
-                Handler::WithoutInfo(_) => false,
+                Handler::WithoutInfo(_) | Handler::WithResult(_) => false,
             }
         } else {
             false
@@ -145,10 +204,22 @@ impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Se
             .expect("A router can not be used after mounting into another router");
 
         match handler {
-            Handler::WithoutInfo(ref handler) => Pin::from(handler(res)).await.map_err(Into::into),
+            Handler::WithoutInfo(ref handler) => Pin::from(handler(res)).await.map_err(into_route_error),
             Handler::WithInfo(ref handler) => Pin::from(handler(res, req_info.expect("No RequestInfo is provided")))
                 .await
-                .map_err(Into::into),
+                .map_err(into_route_error),
+            Handler::WithResult(_) => Ok(res),
+        }
+    }
+
+    pub(crate) fn is_result_handler(&self) -> bool {
+        matches!(self.handler, Some(Handler::WithResult(_)))
+    }
+
+    // Reports the outcome of the route if it's an enabled result handler.
+    pub(crate) fn report_result(&self, result: Result<&Response<B>, &RouteError>, elapsed: Duration) {
+        if let (Some(Handler::WithResult(ref handler)), true) = (&self.handler, self.is_enabled()) {
+            handler(result, elapsed);
         }
     }
 }
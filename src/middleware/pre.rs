@@ -1,13 +1,20 @@
+use crate::error::into_route_error;
+use crate::middleware::dependency::Dependencies;
 use crate::regex_generator::generate_exact_match_regex;
 use crate::Error;
 use hyper::Request;
 use regex::Regex;
 use std::fmt::{self, Debug, Formatter};
 use std::future::Future;
+use std::marker::PhantomData;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
-type Handler<E> = Box<dyn Fn(Request<hyper::Body>) -> HandlerReturn<E> + Send + Sync + 'static>;
-type HandlerReturn<E> = Box<dyn Future<Output = Result<Request<hyper::Body>, E>> + Send + 'static>;
+// The handler error is converted into `RouteError` right away, so that the built-in middleware
+// can be created for any error type `E`.
+pub(crate) type Handler = Box<dyn Fn(Request<hyper::Body>) -> HandlerReturn + Send + Sync + 'static>;
+pub(crate) type HandlerReturn = Box<dyn Future<Output = crate::Result<Request<hyper::Body>>> + Send + 'static>;
 
 /// The pre middleware type. Refer to [Pre Middleware](./index.html#pre-middleware) for more info.
 ///
@@ -19,15 +26,24 @@ pub struct PreMiddleware<E> {
     pub(crate) regex: Regex,
     // Make it an option so that when a router is used to scope in another router,
     // It can be extracted out by 'opt.take()' without taking the whole router's ownership.
-    pub(crate) handler: Option<Handler<E>>,
+    pub(crate) handler: Option<Handler>,
     // Scope depth with regards to the top level router.
     pub(crate) scope_depth: u32,
+    // Middlewares with a higher priority are executed first.
+    pub(crate) priority: i32,
+    // The name by which the middleware can be skipped by the routes.
+    pub(crate) name: Option<String>,
+    // The runtime switch of the middleware which is shared with the router handles.
+    pub(crate) enabled: Arc<AtomicBool>,
+    // The types which the middleware requires from and provides to the other middlewares.
+    pub(crate) dependencies: Dependencies,
+    _error: PhantomData<fn() -> E>,
 }
 
 impl<E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static> PreMiddleware<E> {
     pub(crate) fn new_with_boxed_handler<P: Into<String>>(
         path: P,
-        handler: Handler<E>,
+        handler: Handler,
         scope_depth: u32,
     ) -> crate::Result<PreMiddleware<E>> {
         let path = path.into();
@@ -43,6 +59,11 @@ impl<E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static> PreMiddleware<
             regex: re,
             handler: Some(handler),
             scope_depth,
+            priority: 0,
+            name: None,
+            enabled: Arc::new(AtomicBool::new(true)),
+            dependencies: Dependencies::default(),
+            _error: PhantomData,
         })
     }
 
@@ -70,17 +91,24 @@ impl<E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static> PreMiddleware<
         H: Fn(Request<hyper::Body>) -> R + Send + Sync + 'static,
         R: Future<Output = Result<Request<hyper::Body>, E>> + Send + 'static,
     {
-        let handler: Handler<E> = Box::new(move |req: Request<hyper::Body>| Box::new(handler(req)));
+        let handler: Handler = Box::new(move |req: Request<hyper::Body>| {
+            let fut = handler(req);
+            Box::new(async move { fut.await.map_err(into_route_error) })
+        });
         PreMiddleware::new_with_boxed_handler(path, handler, 1)
     }
 
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
     pub(crate) async fn process(&self, req: Request<hyper::Body>) -> crate::Result<Request<hyper::Body>> {
         let handler = self
             .handler
             .as_ref()
             .expect("A router can not be used after mounting into another router");
 
-        Pin::from(handler(req)).await.map_err(Into::into)
+        Pin::from(handler(req)).await
     }
 }
 
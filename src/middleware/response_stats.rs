@@ -0,0 +1,38 @@
+use crate::body::{count_body, ResponseStats};
+use crate::middleware::Middleware;
+use hyper::{body::HttpBody, header, header::HeaderValue, Response};
+
+/// Creates a post middleware which counts the response body bytes actually written to the client and puts a
+/// [`ResponseStats`](../struct.ResponseStats.html) into the response extensions.
+///
+/// The body is wrapped into a counting adapter, so the middleware is available only for the `hyper::Body` responses.
+/// An exact body size is preserved in the `Content-Length` header but the body trailers are dropped. The post middlewares
+/// which read the stats must run after this middleware.
+///
+/// Please refer to the [`ResponseStats`](../struct.ResponseStats.html) for an example.
+pub fn response_stats<E>() -> Middleware<hyper::Body, E>
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+{
+    Middleware::post(|res: Response<hyper::Body>| async move {
+        let (mut parts, body) = res.into_parts();
+
+        if let Some(len) = body.size_hint().exact() {
+            parts
+                .headers
+                .entry(header::CONTENT_LENGTH)
+                .or_insert_with(|| HeaderValue::from(len));
+        }
+
+        let expected_len = parts
+            .headers
+            .get(header::CONTENT_LENGTH)
+            .and_then(|val| val.to_str().ok())
+            .and_then(|val| val.parse::<u64>().ok());
+
+        let stats = ResponseStats::new();
+        parts.extensions.insert(stats.clone());
+
+        Ok(Response::from_parts(parts, count_body(body, stats, expected_len)))
+    })
+}
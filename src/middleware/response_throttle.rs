@@ -0,0 +1,324 @@
+//! An around middleware which limits the rate the response bodies are sent at, in bytes per second, to protect the
+//! upstream bandwidth e.g. of the large downloads.
+//!
+//! The [`throttle_response`](./fn.throttle_response.html) function creates the
+//! [`ResponseThrottle`](./struct.ResponseThrottle.html) configuration with the rate of each response. A
+//! [`per_client`](./struct.ResponseThrottle.html#method.per_client) rate can also be shared by all the responses to a
+//! remote address. The middlewares can be limited to a route pattern with the
+//! [`middleware_with_path`](./struct.ResponseThrottle.html#method.middleware_with_path) method, and the throttling can
+//! be turned off and on at runtime with the [`set_enabled`](./struct.ResponseThrottle.html#method.set_enabled) method,
+//! which also applies to the responses being sent.
+//!
+//! The body is wrapped into a throttling adapter, so the middleware is available only for the `hyper::Body` responses.
+//! An exact body size is preserved in the `Content-Length` header but the body trailers are dropped.
+//!
+//! # Examples
+//!
+//! ```
+//! use routerify::Router;
+//! use routerify::middleware::response_throttle::throttle_response;
+//! use hyper::{Response, Body};
+//! use std::convert::Infallible;
+//!
+//! # fn run() -> Router<Body, Infallible> {
+//! let throttle = throttle_response(512 * 1024).per_client(1024 * 1024);
+//!
+//! let router = Router::builder()
+//!     .middleware(throttle.middleware_with_path("/downloads/*").unwrap())
+//!     .get("/downloads/:name", |_| async move { Ok(Response::new(Body::from("A large file"))) })
+//!     .build()
+//!     .unwrap();
+//!
+//! // Later, e.g. from an admin endpoint.
+//! throttle.set_enabled(false);
+//! # router
+//! # }
+//! # run();
+//! ```
+
+use crate::ext::RequestExt;
+use crate::middleware::Middleware;
+use futures_core::Stream;
+use hyper::body::{Bytes, HttpBody};
+use hyper::header::{self, HeaderValue};
+use hyper::{Request, Response};
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::time::Sleep;
+
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Creates a configuration which limits each response to `bytes_per_sec` bytes per second.
+///
+/// Please refer to the [module](./index.html) documentation for more info.
+pub fn throttle_response(bytes_per_sec: u64) -> ResponseThrottle {
+    ResponseThrottle {
+        rate: bytes_per_sec,
+        inner: Arc::new(Inner {
+            per_client: None,
+            enabled: AtomicBool::new(true),
+            clients: Mutex::new(HashMap::new()),
+        }),
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    per_client: Option<u64>,
+    enabled: AtomicBool,
+    clients: Mutex<HashMap<IpAddr, Arc<Mutex<Bucket>>>>,
+}
+
+/// The configuration and the state of the response throttling.
+///
+/// It's cheap to clone and the clones share the per client rates and the enabled state. Please refer to the
+/// [module](./index.html) documentation for more info.
+#[derive(Debug, Clone)]
+pub struct ResponseThrottle {
+    rate: u64,
+    inner: Arc<Inner>,
+}
+
+impl ResponseThrottle {
+    /// Limits all the responses to each remote address together to `bytes_per_sec` bytes per second, on top of the
+    /// rate of each response.
+    ///
+    /// It should be called before the middlewares are created.
+    pub fn per_client(mut self, bytes_per_sec: u64) -> Self {
+        Arc::get_mut(&mut self.inner)
+            .expect("ResponseThrottle must be configured before creating the middlewares")
+            .per_client = Some(bytes_per_sec);
+        self
+    }
+
+    /// Turns the throttling off or on, for the next responses and the responses being sent.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.inner.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Checks if the throttling is on.
+    pub fn is_enabled(&self) -> bool {
+        self.inner.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Creates an around middleware at the `/*` path.
+    pub fn middleware<E>(&self) -> Middleware<hyper::Body, E>
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        self.middleware_with_path("/*").unwrap()
+    }
+
+    /// Creates an around middleware at the specified path.
+    pub fn middleware_with_path<P, E>(&self, path: P) -> crate::Result<Middleware<hyper::Body, E>>
+    where
+        P: Into<String>,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        let throttle = self.clone();
+        Middleware::around_with_path(path, move |req: Request<hyper::Body>, next| {
+            let throttle = throttle.clone();
+            async move {
+                if !throttle.is_enabled() {
+                    return next.run(req).await;
+                }
+
+                let ip = req.remote_addr().ip();
+                let res = next.run(req).await?;
+
+                let (mut parts, body) = res.into_parts();
+                if let Some(len) = HttpBody::size_hint(&body).exact() {
+                    parts
+                        .headers
+                        .entry(header::CONTENT_LENGTH)
+                        .or_insert_with(|| HeaderValue::from(len));
+                }
+
+                let client = throttle.inner.per_client.map(|rate| throttle.client_bucket(ip, rate));
+                let body = hyper::Body::wrap_stream(ThrottledStream {
+                    body,
+                    pending: None,
+                    sleep: None,
+                    bucket: Bucket::new(throttle.rate),
+                    client,
+                    inner: throttle.inner.clone(),
+                });
+                Ok(Response::from_parts(parts, body))
+            }
+        })
+    }
+
+    fn client_bucket(&self, ip: IpAddr, rate: u64) -> Arc<Mutex<Bucket>> {
+        let mut clients = self.inner.clients.lock().unwrap();
+        if clients.len() >= PRUNE_THRESHOLD {
+            // The buckets which aren't used by a response being sent are dropped.
+            clients.retain(|_, bucket| Arc::strong_count(bucket) > 1);
+        }
+        clients
+            .entry(ip)
+            .or_insert_with(|| Arc::new(Mutex::new(Bucket::new(rate))))
+            .clone()
+    }
+}
+
+// A token bucket which can go into debt: a chunk is sent once the balance isn't negative, and then its length is
+// drawn from the balance.
+#[derive(Debug)]
+struct Bucket {
+    rate: f64,
+    balance: f64,
+    updated_at: Instant,
+}
+
+impl Bucket {
+    fn new(bytes_per_sec: u64) -> Bucket {
+        Bucket {
+            rate: (bytes_per_sec as f64).max(1.0),
+            balance: 0.0,
+            updated_at: Instant::now(),
+        }
+    }
+
+    // The chunks are split so a response is sent evenly, four times a second.
+    fn slice_len(&self) -> usize {
+        ((self.rate / 4.0) as usize).max(1)
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+        // At most a second of rate is saved up for a burst.
+        self.balance = (self.balance + elapsed * self.rate).min(self.rate);
+        self.updated_at = now;
+    }
+
+    fn delay(&mut self, now: Instant) -> Duration {
+        self.refill(now);
+        if self.balance >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.balance / self.rate)
+        }
+    }
+
+    fn draw(&mut self, len: usize, now: Instant) {
+        self.refill(now);
+        self.balance -= len as f64;
+    }
+}
+
+struct ThrottledStream {
+    body: hyper::Body,
+    // The rest of a chunk which is split.
+    pending: Option<Bytes>,
+    sleep: Option<Pin<Box<Sleep>>>,
+    bucket: Bucket,
+    client: Option<Arc<Mutex<Bucket>>>,
+    inner: Arc<Inner>,
+}
+
+impl Stream for ThrottledStream {
+    type Item = Result<Bytes, hyper::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(sleep) = self.sleep.as_mut() {
+                if sleep.as_mut().poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+                self.sleep = None;
+            }
+
+            let mut chunk = match self.pending.take() {
+                Some(chunk) => chunk,
+                None => match Pin::new(&mut self.body).poll_data(cx) {
+                    Poll::Pending => return Poll::Pending,
+                    Poll::Ready(Some(Ok(chunk))) => chunk,
+                    Poll::Ready(Some(Err(err))) => return Poll::Ready(Some(Err(err))),
+                    Poll::Ready(None) => return Poll::Ready(None),
+                },
+            };
+            if !self.inner.enabled.load(Ordering::Relaxed) || chunk.is_empty() {
+                return Poll::Ready(Some(Ok(chunk)));
+            }
+
+            let now = Instant::now();
+            let mut delay = self.bucket.delay(now);
+            if let Some(ref client) = self.client {
+                delay = delay.max(client.lock().unwrap().delay(now));
+            }
+            if delay > Duration::ZERO {
+                self.pending = Some(chunk);
+                self.sleep = Some(Box::pin(tokio::time::sleep(delay)));
+                continue;
+            }
+
+            let mut slice_len = self.bucket.slice_len();
+            if let Some(ref client) = self.client {
+                slice_len = slice_len.min(client.lock().unwrap().slice_len());
+            }
+            if chunk.len() > slice_len {
+                self.pending = Some(chunk.split_off(slice_len));
+            }
+
+            self.bucket.draw(chunk.len(), now);
+            if let Some(ref client) = self.client {
+                client.lock().unwrap().draw(chunk.len(), now);
+            }
+            return Poll::Ready(Some(Ok(chunk)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn throttled(throttle: &ResponseThrottle, body: String) -> hyper::Body {
+        hyper::Body::wrap_stream(ThrottledStream {
+            body: hyper::Body::from(body),
+            pending: None,
+            sleep: None,
+            bucket: Bucket::new(throttle.rate),
+            client: None,
+            inner: throttle.inner.clone(),
+        })
+    }
+
+    #[tokio::test]
+    async fn limits_the_rate_of_the_body() {
+        let throttle = throttle_response(40);
+        let body = "0123456789".repeat(2);
+
+        let started_at = Instant::now();
+        let sent = hyper::body::to_bytes(throttled(&throttle, body.clone())).await.unwrap();
+        assert_eq!(sent, body);
+        // The 20 bytes are sent in slices of 10 bytes, the second one after a quarter of a second.
+        assert!(started_at.elapsed() >= Duration::from_millis(200));
+
+        throttle.set_enabled(false);
+        let started_at = Instant::now();
+        let sent = hyper::body::to_bytes(throttled(&throttle, "0123456789".repeat(100)))
+            .await
+            .unwrap();
+        assert_eq!(sent.len(), 1000);
+        assert!(started_at.elapsed() < Duration::from_millis(200));
+    }
+
+    #[test]
+    fn shares_the_client_buckets() {
+        let throttle = throttle_response(1000).per_client(500);
+        let ip: IpAddr = "127.0.0.1".parse().unwrap();
+        let first = throttle.client_bucket(ip, 500);
+        let second = throttle.client_bucket(ip, 500);
+        assert!(Arc::ptr_eq(&first, &second));
+
+        first.lock().unwrap().draw(1000, Instant::now());
+        assert!(second.lock().unwrap().delay(Instant::now()) > Duration::from_millis(500));
+    }
+}
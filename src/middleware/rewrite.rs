@@ -0,0 +1,280 @@
+//! The URL rewriting before the route matching, so the legacy URL schemes are served by the current routes without
+//! duplicate handlers.
+//!
+//! A [`Rewrite`](./struct.Rewrite.html) holds the rules, which are either a route path and a target path with the same
+//! params e.g. `rewrite("/old/:id", "/new/:id")`, or a regex and its substitution e.g.
+//! `regex(r"^/blog/(\d{4})/(.+)$", "/posts/$2?year=$1")`. It's added to a router by the
+//! [`RouterBuilder::rewrite`](../../struct.RouterBuilder.html#method.rewrite) method, which runs it before the routes
+//! are matched.
+//!
+//! The rules are matched by the percent decoded request path after the base path, in the order they are added, and the
+//! first matching one rewrites the path. The base path and the query string of the request are kept, unless the
+//! substitution has its own query string.
+//!
+//! # Examples
+//!
+//! ```
+//! use routerify::Router;
+//! use routerify::middleware::rewrite::Rewrite;
+//! use hyper::{Response, Body};
+//! use std::convert::Infallible;
+//!
+//! # fn run() -> Router<Body, Infallible> {
+//! let rewrite = Rewrite::new()
+//!     .rewrite("/users/:id/profile", "/profiles/:id")
+//!     .rewrite("/static/*", "/assets/*")
+//!     .regex(r"^/blog/(\d{4})/(.+)$", "/posts/$2?year=$1");
+//!
+//! let router = Router::builder()
+//!     .rewrite(rewrite)
+//!     .get("/profiles/:id", |_| async move { Ok(Response::new(Body::from("Profile"))) })
+//!     .get("/posts/:slug", |_| async move { Ok(Response::new(Body::from("Post"))) })
+//!     .build()
+//!     .unwrap();
+//! # router
+//! # }
+//! # run();
+//! ```
+
+use crate::ext::RequestExt;
+use crate::helpers;
+use crate::regex_generator::{generate_exact_match_regex, split_path_parts, PathPart};
+use hyper::http::uri::PathAndQuery;
+use hyper::{Body, Request, Uri};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use regex::Regex;
+use std::convert::TryFrom;
+use std::fmt::{self, Debug, Formatter};
+use std::sync::Arc;
+
+// The characters which are encoded in a rewritten path, as in the route URLs.
+const PATH: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~')
+    .remove(b'/');
+
+struct Rule {
+    from: String,
+    to: String,
+    regex: Regex,
+    // The substitution of the regex, with the `${N}` groups for the params of a route path.
+    replacement: String,
+    is_route_path: bool,
+}
+
+/// The URL rewriting rules.
+///
+/// It's cheap to clone and the clones share the rules. Please refer to the [module](./index.html) documentation for
+/// more info.
+#[derive(Clone, Default)]
+pub struct Rewrite {
+    rules: Arc<Vec<Rule>>,
+}
+
+impl Rewrite {
+    /// Creates a new configuration without any rule.
+    pub fn new() -> Self {
+        Rewrite::default()
+    }
+
+    /// Rewrites the paths matching the route path, e.g. `/old/:id` or `/old/*`, into the target path whose params and
+    /// `*` segment are replaced by the ones of the request path, e.g. `/new/:id` or `/new/*`.
+    ///
+    /// # Panics
+    ///
+    /// It panics if the route path is invalid or the target path has a param which the route path hasn't.
+    pub fn rewrite<F: Into<String>, T: Into<String>>(self, from: F, to: T) -> Self {
+        let from = from.into();
+        let to = to.into();
+
+        let mut path = from.clone();
+        if !path.ends_with('/') && !path.ends_with('*') {
+            path.push('/');
+        }
+        let (regex, params) = generate_exact_match_regex(&path).expect("Invalid rewrite path");
+
+        let group_of = |name: &str| match params.iter().position(|param| param == name) {
+            Some(idx) => format!("${{{}}}", idx + 1),
+            None => panic!(
+                "The rewrite target {:?} has a param which {:?} hasn't: {}",
+                to, from, name
+            ),
+        };
+        let mut replacement = String::with_capacity(to.len());
+        for part in split_path_parts(&to) {
+            match part {
+                PathPart::Literal(literal) => replacement.push_str(&literal.replace('$', "$$")),
+                PathPart::Param { name, .. } => replacement.push_str(&group_of(name)),
+                PathPart::Wildcard => replacement.push_str(&group_of("*")),
+            }
+        }
+        if !replacement.ends_with('/') && !to.ends_with('*') {
+            replacement.push('/');
+        }
+
+        self.push(Rule {
+            from,
+            to,
+            regex,
+            replacement,
+            is_route_path: true,
+        })
+    }
+
+    /// Rewrites the paths matching the regex by its substitution, which may refer to the groups as `$1` or `$name`.
+    ///
+    /// # Panics
+    ///
+    /// It panics if the regex is invalid.
+    pub fn regex<R: Into<String>, T: Into<String>>(self, regex: R, substitution: T) -> Self {
+        let from = regex.into();
+        let to = substitution.into();
+        let regex = Regex::new(&from).expect("Invalid rewrite regex");
+
+        self.push(Rule {
+            from,
+            replacement: to.clone(),
+            to,
+            regex,
+            is_route_path: false,
+        })
+    }
+
+    /// Rewrites the URI of the request by the first matching rule, if any, and returns whether it's rewritten.
+    ///
+    /// It's called by the router before the route matching.
+    pub fn apply(&self, req: &mut Request<Body>) -> bool {
+        let path = match helpers::percent_decode_request_path(req.uri().path()) {
+            Ok(path) => path,
+            Err(_) => return false,
+        };
+        let base_path = req.base_path().unwrap_or("");
+        let path = helpers::strip_base_path(&path, base_path).unwrap_or(&path);
+
+        let rewritten = match self.rules.iter().find_map(|rule| rule.rewrite(path)) {
+            Some(rewritten) => rewritten,
+            None => return false,
+        };
+        let (rewritten_path, query) = match rewritten.split_once('?') {
+            Some((rewritten_path, query)) => (rewritten_path, Some(query)),
+            None => (rewritten.as_str(), req.uri().query()),
+        };
+
+        let mut path_and_query = format!("{}{}", base_path, utf8_percent_encode(rewritten_path, PATH));
+        if let Some(query) = query {
+            path_and_query.push('?');
+            path_and_query.push_str(query);
+        }
+
+        let mut parts = req.uri().clone().into_parts();
+        parts.path_and_query = match PathAndQuery::try_from(path_and_query) {
+            Ok(path_and_query) => Some(path_and_query),
+            Err(_) => return false,
+        };
+        match Uri::from_parts(parts) {
+            Ok(uri) => {
+                *req.uri_mut() = uri;
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    fn push(mut self, rule: Rule) -> Self {
+        Arc::get_mut(&mut self.rules)
+            .expect("Rewrite must be configured before it's added to a router")
+            .push(rule);
+        self
+    }
+}
+
+impl Rule {
+    fn rewrite(&self, path: &str) -> Option<String> {
+        if !self.is_route_path {
+            return match self.regex.is_match(path) {
+                true => Some(self.regex.replace(path, self.replacement.as_str()).into_owned()),
+                false => None,
+            };
+        }
+
+        // The route paths are matched with a trailing slash, which is dropped again unless the request has it.
+        let has_trailing_slash = path.ends_with('/');
+        let path = if has_trailing_slash {
+            path.to_owned()
+        } else {
+            format!("{}/", path)
+        };
+        if !self.regex.is_match(&path) {
+            return None;
+        }
+
+        let mut rewritten = self.regex.replace(&path, self.replacement.as_str()).into_owned();
+        // The missing optional params leave empty segments.
+        while rewritten.contains("//") {
+            rewritten = rewritten.replace("//", "/");
+        }
+        if !has_trailing_slash && rewritten.len() > 1 {
+            rewritten.pop();
+        }
+        Some(rewritten)
+    }
+}
+
+impl Debug for Rewrite {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{{ rules: {:?} }}",
+            self.rules.iter().map(|rule| (&rule.from, &rule.to)).collect::<Vec<_>>()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rewritten(rewrite: &Rewrite, uri: &str) -> Option<String> {
+        let mut req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        match rewrite.apply(&mut req) {
+            true => Some(req.uri().to_string()),
+            false => None,
+        }
+    }
+
+    #[test]
+    fn rewrites_by_the_first_matching_rule() {
+        let rewrite = Rewrite::new()
+            .rewrite("/users/:id/profile", "/profiles/:id")
+            .rewrite("/archive/:year/:month?", "/posts/:month/:year")
+            .rewrite("/static/*", "/assets/*")
+            .regex(r"^/blog/(\d{4})/(.+)$", "/posts/$2?year=$1");
+
+        assert_eq!(
+            rewritten(&rewrite, "/users/7/profile?tab=1").as_deref(),
+            Some("/profiles/7?tab=1")
+        );
+        assert_eq!(
+            rewritten(&rewrite, "/users/7/profile/").as_deref(),
+            Some("/profiles/7/")
+        );
+        assert_eq!(rewritten(&rewrite, "/archive/2020").as_deref(), Some("/posts/2020"));
+        assert_eq!(
+            rewritten(&rewrite, "/static/css/app.css").as_deref(),
+            Some("/assets/css/app.css")
+        );
+        assert_eq!(
+            rewritten(&rewrite, "/blog/2024/hello%20world").as_deref(),
+            Some("/posts/hello%20world?year=2024")
+        );
+        assert_eq!(rewritten(&rewrite, "/users/7"), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "has a param")]
+    fn rejects_unknown_target_params() {
+        let _ = Rewrite::new().rewrite("/old/:id", "/new/:name");
+    }
+}
@@ -0,0 +1,257 @@
+//! A pre middleware which throttles the requests per authenticated [`Principal`](../../struct.Principal.html).
+//!
+//! Each principal gets its own token bucket, so a client can burst up to the quota limit and then it's refilled
+//! evenly over the quota period. The quota depends on the roles of the principal and the requests without a principal
+//! are keyed by the remote address with the anonymous quota. Throttled requests are rejected with
+//! [`HttpError`](../../struct.HttpError.html)s of status `429 Too Many Requests`.
+//!
+//! The authentication middleware must run before this middleware and put the [`Principal`](../../struct.Principal.html)
+//! into the request context.
+//!
+//! The buckets are grouped by name and the middlewares of the same [`Throttle`](./struct.Throttle.html) which use the same
+//! bucket name share their quota, e.g. a search and a suggest route can draw from a single quota.
+//!
+//! # Examples
+//!
+//! ```
+//! use routerify::Router;
+//! use routerify::middleware::throttle::{Quota, Throttle};
+//! use hyper::{Response, Body};
+//! use std::convert::Infallible;
+//! use std::time::Duration;
+//!
+//! # fn run() -> Router<Body, Infallible> {
+//! let throttle = Throttle::new(Quota::new(100, Duration::from_secs(60)))
+//!     .role("admin", Quota::new(1000, Duration::from_secs(60)))
+//!     .anonymous(Quota::new(10, Duration::from_secs(60)));
+//!
+//! let router = Router::builder()
+//!     // The authentication middleware goes here.
+//!     .middleware(throttle.middleware_with_path("search", "/search/*").unwrap())
+//!     .middleware(throttle.middleware_with_path("search", "/suggest/*").unwrap())
+//!     .get("/search", |_| async move { Ok(Response::new(Body::from("Results"))) })
+//!     .get("/suggest", |_| async move { Ok(Response::new(Body::from("Suggestions"))) })
+//!     .build()
+//!     .unwrap();
+//! # router
+//! # }
+//! # run();
+//! ```
+
+use crate::ext::RequestExt;
+use crate::middleware::pre::Handler;
+use crate::middleware::{Middleware, PreMiddleware};
+use crate::types::Principal;
+use crate::HttpError;
+use hyper::{body::HttpBody, Request, StatusCode};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// The number of requests allowed in a period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quota {
+    limit: u32,
+    period: Duration,
+}
+
+impl Quota {
+    /// Creates a new quota which allows `limit` requests per `period`.
+    pub fn new(limit: u32, period: Duration) -> Quota {
+        Quota { limit, period }
+    }
+
+    fn per_sec(&self) -> f64 {
+        self.limit as f64 / self.period.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+#[derive(Debug)]
+struct Inner {
+    default: Quota,
+    anonymous: Option<Quota>,
+    roles: Vec<(String, Quota)>,
+    buckets: Mutex<HashMap<(String, String), Bucket>>,
+}
+
+/// The configuration and the state of the per principal throttling.
+///
+/// It's cheap to clone and the clones share the buckets. Please refer to the [module](./index.html) documentation for more info.
+#[derive(Debug, Clone)]
+pub struct Throttle {
+    inner: Arc<Inner>,
+}
+
+impl Throttle {
+    /// Creates a new throttle with the quota for the principals without any of the configured roles.
+    ///
+    /// The requests without a principal also use this quota unless an [anonymous](#method.anonymous) quota is set.
+    pub fn new(default: Quota) -> Self {
+        Throttle {
+            inner: Arc::new(Inner {
+                default,
+                anonymous: None,
+                roles: Vec::new(),
+                buckets: Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+
+    /// Sets the quota for the principals with the specified role. A principal with multiple configured roles gets
+    /// the most generous quota of them.
+    ///
+    /// It should be called before the middlewares are created.
+    pub fn role<R: Into<String>>(mut self, role: R, quota: Quota) -> Self {
+        Arc::get_mut(&mut self.inner)
+            .expect("Throttle must be configured before creating the middlewares")
+            .roles
+            .push((role.into(), quota));
+        self
+    }
+
+    /// Sets the quota for the requests without a principal. They are keyed by the remote address.
+    ///
+    /// It should be called before the middlewares are created.
+    pub fn anonymous(mut self, quota: Quota) -> Self {
+        Arc::get_mut(&mut self.inner)
+            .expect("Throttle must be configured before creating the middlewares")
+            .anonymous = Some(quota);
+        self
+    }
+
+    /// Creates a pre middleware at the `/*` path which draws from the specified bucket.
+    pub fn middleware<B, E>(&self, bucket: &str) -> Middleware<B, E>
+    where
+        B: HttpBody + Send + Sync + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        self.middleware_with_path(bucket, "/*").unwrap()
+    }
+
+    /// Creates a pre middleware at the specified path which draws from the specified bucket.
+    pub fn middleware_with_path<P, B, E>(&self, bucket: &str, path: P) -> crate::Result<Middleware<B, E>>
+    where
+        P: Into<String>,
+        B: HttpBody + Send + Sync + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        let throttle = self.clone();
+        let bucket = Arc::new(bucket.to_owned());
+        let handler: Handler = Box::new(move |req: Request<hyper::Body>| {
+            let throttle = throttle.clone();
+            let bucket = bucket.clone();
+            Box::new(async move { throttle.process(&bucket, req) })
+        });
+
+        Ok(Middleware::Pre(PreMiddleware::new_with_boxed_handler(
+            path, handler, 1,
+        )?))
+    }
+
+    fn process(&self, bucket: &str, req: Request<hyper::Body>) -> crate::Result<Request<hyper::Body>> {
+        let (key, quota) = match req.context::<Principal>() {
+            Some(principal) => (format!("principal:{}", principal.id()), self.quota_of(&principal)),
+            None => (
+                format!("addr:{}", req.remote_addr().ip()),
+                self.inner.anonymous.unwrap_or(self.inner.default),
+            ),
+        };
+
+        match self.acquire(bucket, key, quota, Instant::now()) {
+            Ok(()) => Ok(req),
+            Err(retry_after) => Err(HttpError::new(
+                StatusCode::TOO_MANY_REQUESTS,
+                format!(
+                    "Too many requests, retry after {} seconds",
+                    retry_after.as_secs().max(1)
+                ),
+            )
+            .into()),
+        }
+    }
+
+    fn quota_of(&self, principal: &Principal) -> Quota {
+        self.inner
+            .roles
+            .iter()
+            .filter(|(role, _)| principal.has_role(role))
+            .map(|(_, quota)| *quota)
+            .fold(None, |best: Option<Quota>, quota| match best {
+                Some(best) if best.per_sec() >= quota.per_sec() => Some(best),
+                _ => Some(quota),
+            })
+            .unwrap_or(self.inner.default)
+    }
+
+    fn acquire(&self, bucket: &str, key: String, quota: Quota, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.inner.buckets.lock().unwrap();
+
+        if buckets.len() >= PRUNE_THRESHOLD {
+            // The idle buckets are refilled anyway, so they can be dropped.
+            buckets.retain(|_, b| now.saturating_duration_since(b.updated_at) < quota.period);
+        }
+
+        let capacity = quota.limit as f64;
+        let b = buckets.entry((bucket.to_owned(), key)).or_insert(Bucket {
+            tokens: capacity,
+            updated_at: now,
+        });
+
+        let elapsed = now.saturating_duration_since(b.updated_at).as_secs_f64();
+        b.tokens = (b.tokens + elapsed * quota.per_sec()).min(capacity);
+        b.updated_at = now;
+
+        if b.tokens >= 1.0 {
+            b.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - b.tokens) / quota.per_sec()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refills_buckets_over_time() {
+        let throttle = Throttle::new(Quota::new(2, Duration::from_secs(10)));
+        let now = Instant::now();
+        let quota = throttle.inner.default;
+
+        assert!(throttle.acquire("api", "a".into(), quota, now).is_ok());
+        assert!(throttle.acquire("api", "a".into(), quota, now).is_ok());
+        assert_eq!(
+            throttle.acquire("api", "a".into(), quota, now),
+            Err(Duration::from_secs(5))
+        );
+        assert!(throttle.acquire("other", "a".into(), quota, now).is_ok());
+        assert!(throttle
+            .acquire("api", "a".into(), quota, now + Duration::from_secs(5))
+            .is_ok());
+    }
+
+    #[test]
+    fn picks_the_most_generous_role_quota() {
+        let throttle = Throttle::new(Quota::new(10, Duration::from_secs(60)))
+            .role("staff", Quota::new(100, Duration::from_secs(60)))
+            .role("admin", Quota::new(50, Duration::from_secs(1)));
+
+        let user = Principal::new("u");
+        let staff = Principal::new("s").with_role("staff");
+        let both = Principal::new("b").with_role("staff").with_role("admin");
+
+        assert_eq!(throttle.quota_of(&user).limit, 10);
+        assert_eq!(throttle.quota_of(&staff).limit, 100);
+        assert_eq!(throttle.quota_of(&both).limit, 50);
+    }
+}
@@ -0,0 +1,149 @@
+//! An around middleware which runs each request in a database transaction.
+//!
+//! The [`Transactional`](./struct.Transactional.html) middleware begins a transaction by a
+//! [`TransactionManager`](./trait.TransactionManager.html) before the route handler and puts its handle into the request
+//! extensions, where the handler finds it. The transaction is committed if the handler responds with a `2xx` or a `3xx`
+//! status code, and it's rolled back if the handler fails or responds with any other status code.
+//!
+//! The manager is usually a thin adapter over the connection pool of a database driver. The handle must be `Clone`, so
+//! it's typically an `Arc` of a mutex around the driver's transaction.
+//!
+//! # Examples
+//!
+//! ```
+//! use routerify::{Router, RouteError};
+//! use routerify::middleware::transactional::{Transactional, TransactionManager, TxFuture};
+//! use hyper::{Response, Body};
+//! use std::sync::{Arc, Mutex};
+//!
+//! // A stand-in for the transaction of a database driver.
+//! #[derive(Clone, Default)]
+//! struct Tx(Arc<Mutex<Vec<String>>>);
+//!
+//! struct Db;
+//!
+//! impl TransactionManager for Db {
+//!     type Transaction = Tx;
+//!
+//!     fn begin(&self) -> TxFuture<'_, Tx> {
+//!         Box::pin(async move { Ok(Tx::default()) })
+//!     }
+//!
+//!     fn commit(&self, tx: Tx) -> TxFuture<'_, ()> {
+//!         Box::pin(async move {
+//!             println!("Committed: {:?}", tx.0.lock().unwrap());
+//!             Ok(())
+//!         })
+//!     }
+//!
+//!     fn rollback(&self, _tx: Tx) -> TxFuture<'_, ()> {
+//!         Box::pin(async move { Ok(()) })
+//!     }
+//! }
+//!
+//! # fn run() -> Router<Body, RouteError> {
+//! let router = Router::builder()
+//!     .middleware(Transactional::new(Db).middleware_with_path("/orders/*").unwrap())
+//!     .post("/orders", |req| async move {
+//!         let tx = req.extensions().get::<Tx>().unwrap();
+//!         tx.0.lock().unwrap().push("INSERT INTO orders ...".to_owned());
+//!         Ok(Response::new(Body::from("Created")))
+//!     })
+//!     .build()
+//!     .unwrap();
+//! # router
+//! # }
+//! # run();
+//! ```
+
+use crate::middleware::Middleware;
+use hyper::body::HttpBody;
+use hyper::Request;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+/// The future returned by the methods of the [`TransactionManager`](./trait.TransactionManager.html).
+pub type TxFuture<'a, T> = Pin<Box<dyn Future<Output = crate::Result<T>> + Send + 'a>>;
+
+/// A source of the transactions, e.g. an adapter over a connection pool.
+pub trait TransactionManager: Send + Sync + 'static {
+    /// The handle of a transaction which is put into the request extensions.
+    type Transaction: Clone + Send + Sync + 'static;
+
+    /// Begins a transaction. The request fails with the error if it can't be started.
+    fn begin(&self) -> TxFuture<'_, Self::Transaction>;
+
+    /// Commits the transaction. The response is replaced by the error if it can't be committed.
+    fn commit(&self, tx: Self::Transaction) -> TxFuture<'_, ()>;
+
+    /// Rolls back the transaction. If the handler has failed, its error takes precedence over the error of the
+    /// rollback.
+    fn rollback(&self, tx: Self::Transaction) -> TxFuture<'_, ()>;
+}
+
+/// The transaction per request middleware.
+///
+/// Please refer to the [module](./index.html) documentation for more info.
+#[derive(Debug)]
+pub struct Transactional<M> {
+    manager: Arc<M>,
+}
+
+impl<M> Clone for Transactional<M> {
+    fn clone(&self) -> Self {
+        Transactional {
+            manager: self.manager.clone(),
+        }
+    }
+}
+
+impl<M: TransactionManager> Transactional<M> {
+    /// Creates the middleware which uses the manager for the transactions.
+    pub fn new(manager: M) -> Self {
+        Transactional {
+            manager: Arc::new(manager),
+        }
+    }
+
+    /// Creates an around middleware at the `/*` path.
+    pub fn middleware<B, E>(&self) -> Middleware<B, E>
+    where
+        B: HttpBody + Send + Sync + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        self.middleware_with_path("/*").unwrap()
+    }
+
+    /// Creates an around middleware at the specified path.
+    pub fn middleware_with_path<P, B, E>(&self, path: P) -> crate::Result<Middleware<B, E>>
+    where
+        P: Into<String>,
+        B: HttpBody + Send + Sync + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        let manager = self.manager.clone();
+        Middleware::around_with_path(path, move |mut req: Request<hyper::Body>, next| {
+            let manager = manager.clone();
+            async move {
+                let tx = manager.begin().await?;
+                req.extensions_mut().insert(tx.clone());
+
+                match next.run(req).await {
+                    Ok(res) if res.status().is_success() || res.status().is_redirection() => {
+                        manager.commit(tx).await?;
+                        Ok(res)
+                    }
+                    Ok(res) => {
+                        manager.rollback(tx).await?;
+                        Ok(res)
+                    }
+                    Err(err) => {
+                        let _ = manager.rollback(tx).await;
+                        Err(err)
+                    }
+                }
+            }
+        })
+    }
+}
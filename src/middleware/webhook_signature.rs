@@ -0,0 +1,315 @@
+//! A pre middleware which verifies HMAC signed webhook requests.
+//!
+//! The request body is buffered up to a limit, its HMAC-SHA256 signature is verified against the configured secret
+//! and invalid requests are rejected with [`HttpError`](../../struct.HttpError.html)s of status `401 Unauthorized`
+//! before the route handler runs. The buffered body is put back into the request, so the handler can still read it.
+//!
+//! Two signature schemes are supported:
+//!
+//! * [`WebhookSignature::stripe`](./struct.WebhookSignature.html#method.stripe): `Stripe-Signature: t=<timestamp>,v1=<hex>`
+//!   where the signed payload is `<timestamp>.<body>`. Requests with a timestamp outside of the tolerance window are rejected.
+//! * [`WebhookSignature::github`](./struct.WebhookSignature.html#method.github): `X-Hub-Signature-256: sha256=<hex>`
+//!   where the signed payload is the body.
+//!
+//! # Examples
+//!
+//! ```
+//! use routerify::Router;
+//! use routerify::middleware::webhook_signature::WebhookSignature;
+//! use hyper::{Response, Body};
+//! use std::convert::Infallible;
+//! use std::time::Duration;
+//!
+//! # fn run() -> Router<Body, Infallible> {
+//! let router = Router::builder()
+//!     .middleware(
+//!         WebhookSignature::stripe("whsec_secret")
+//!             .limit(512 * 1024)
+//!             .tolerance(Duration::from_secs(60))
+//!             .middleware_with_path("/webhooks/stripe")
+//!             .unwrap(),
+//!     )
+//!     .post("/webhooks/stripe", |_| async move { Ok(Response::new(Body::from("Received"))) })
+//!     .build()
+//!     .unwrap();
+//! # router
+//! # }
+//! # run();
+//! ```
+
+use crate::body::BodyError;
+use crate::ext::RequestBodyExt;
+use crate::middleware::pre::Handler;
+use crate::middleware::{Middleware, PreMiddleware};
+use crate::HttpError;
+use hmac::{Hmac, Mac};
+use hyper::{body::HttpBody, header::HeaderName, HeaderMap, Request, StatusCode};
+use sha2::Sha256;
+use std::fmt::{self, Debug, Formatter};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+type HmacSha256 = Hmac<Sha256>;
+
+const DEFAULT_LIMIT: usize = 1024 * 1024;
+const DEFAULT_TOLERANCE: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scheme {
+    Stripe,
+    GitHub,
+}
+
+/// The configuration of the webhook signature verification middleware.
+///
+/// Please refer to the [module](./index.html) documentation for more info.
+#[derive(Clone)]
+pub struct WebhookSignature {
+    secret: Arc<Vec<u8>>,
+    scheme: Scheme,
+    header: HeaderName,
+    limit: usize,
+    tolerance: Duration,
+}
+
+impl WebhookSignature {
+    /// Verifies Stripe style signatures from the `Stripe-Signature` header.
+    pub fn stripe<S: Into<Vec<u8>>>(secret: S) -> Self {
+        WebhookSignature::new(secret, Scheme::Stripe, HeaderName::from_static("stripe-signature"))
+    }
+
+    /// Verifies GitHub style signatures from the `X-Hub-Signature-256` header.
+    pub fn github<S: Into<Vec<u8>>>(secret: S) -> Self {
+        WebhookSignature::new(secret, Scheme::GitHub, HeaderName::from_static("x-hub-signature-256"))
+    }
+
+    fn new<S: Into<Vec<u8>>>(secret: S, scheme: Scheme, header: HeaderName) -> Self {
+        WebhookSignature {
+            secret: Arc::new(secret.into()),
+            scheme,
+            header,
+            limit: DEFAULT_LIMIT,
+            tolerance: DEFAULT_TOLERANCE,
+        }
+    }
+
+    /// Reads the signature from a different header.
+    pub fn header(mut self, header: HeaderName) -> Self {
+        self.header = header;
+        self
+    }
+
+    /// Sets the maximum body size in bytes. Larger bodies are rejected with `413 Payload Too Large`. Defaults to 1 MiB.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Sets the maximum allowed age of a signature timestamp. Defaults to 5 minutes.
+    ///
+    /// Only used by the schemes which sign a timestamp.
+    pub fn tolerance(mut self, tolerance: Duration) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Creates a pre middleware at the `/*` path.
+    pub fn middleware<B, E>(self) -> Middleware<B, E>
+    where
+        B: HttpBody + Send + Sync + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        self.middleware_with_path("/*").unwrap()
+    }
+
+    /// Creates a pre middleware at the specified path.
+    pub fn middleware_with_path<P, B, E>(self, path: P) -> crate::Result<Middleware<B, E>>
+    where
+        P: Into<String>,
+        B: HttpBody + Send + Sync + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        let config = Arc::new(self);
+        let handler: Handler = Box::new(move |req: Request<hyper::Body>| {
+            let config = config.clone();
+            Box::new(async move { config.process(req).await })
+        });
+
+        Ok(Middleware::Pre(PreMiddleware::new_with_boxed_handler(
+            path, handler, 1,
+        )?))
+    }
+
+    async fn process(&self, mut req: Request<hyper::Body>) -> crate::Result<Request<hyper::Body>> {
+        let body = req.buffer_body(self.limit, None).await.map_err(|err| match err {
+            BodyError::TooLarge { .. } => HttpError::new(StatusCode::PAYLOAD_TOO_LARGE, err.to_string()),
+            _ => HttpError::new(StatusCode::BAD_REQUEST, err.to_string()),
+        })?;
+        let payload = body.as_bytes().expect("A body within the limit is kept in memory");
+
+        self.verify(req.headers(), payload, unix_now()).map_err(|reason| {
+            HttpError::new(
+                StatusCode::UNAUTHORIZED,
+                format!("Invalid webhook signature: {}", reason),
+            )
+        })?;
+
+        Ok(req)
+    }
+
+    fn verify(&self, headers: &HeaderMap, payload: &[u8], now: u64) -> Result<(), &'static str> {
+        let value = headers
+            .get(&self.header)
+            .ok_or("missing signature header")?
+            .to_str()
+            .map_err(|_| "malformed signature header")?;
+
+        match self.scheme {
+            Scheme::Stripe => {
+                let mut timestamp = None;
+                let mut signatures = Vec::new();
+                for item in value.split(',') {
+                    match item.trim().split_once('=') {
+                        Some(("t", t)) => timestamp = t.parse::<u64>().ok(),
+                        Some(("v1", sig)) => signatures.push(sig),
+                        _ => {}
+                    }
+                }
+
+                let timestamp = timestamp.ok_or("missing timestamp")?;
+                if now.abs_diff(timestamp) > self.tolerance.as_secs() {
+                    return Err("timestamp outside of the tolerance window");
+                }
+
+                let prefix = format!("{}.", timestamp);
+                if signatures
+                    .into_iter()
+                    .any(|sig| self.verify_hex(&[prefix.as_bytes(), payload], sig))
+                {
+                    Ok(())
+                } else {
+                    Err("signature mismatch")
+                }
+            }
+            Scheme::GitHub => {
+                let sig = value.strip_prefix("sha256=").ok_or("malformed signature header")?;
+                if self.verify_hex(&[payload], sig) {
+                    Ok(())
+                } else {
+                    Err("signature mismatch")
+                }
+            }
+        }
+    }
+
+    fn verify_hex(&self, parts: &[&[u8]], hex_sig: &str) -> bool {
+        let sig = match decode_hex(hex_sig) {
+            Some(sig) => sig,
+            None => return false,
+        };
+
+        let mut mac = HmacSha256::new_from_slice(&self.secret).expect("HMAC can take a key of any size");
+        for part in parts {
+            mac.update(part);
+        }
+        mac.verify_slice(&sig).is_ok()
+    }
+}
+
+impl Debug for WebhookSignature {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{{ scheme: {:?}, header: {:?}, limit: {:?}, tolerance: {:?} }}",
+            self.scheme, self.header, self.limit, self.tolerance
+        )
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn decode_hex(val: &str) -> Option<Vec<u8>> {
+    // `u8::from_str_radix` accepts a leading sign, so the digits are checked first.
+    let pairs = val.as_bytes().chunks_exact(2);
+    if !pairs.remainder().is_empty() || !val.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    pairs
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::HeaderValue;
+
+    fn sign(secret: &[u8], parts: &[&[u8]]) -> String {
+        let mut mac = HmacSha256::new_from_slice(secret).unwrap();
+        for part in parts {
+            mac.update(part);
+        }
+        mac.finalize()
+            .into_bytes()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    fn headers(name: &str, val: String) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            HeaderName::from_bytes(name.as_bytes()).unwrap(),
+            HeaderValue::from_str(&val).unwrap(),
+        );
+        headers
+    }
+
+    #[test]
+    fn verifies_github_signatures() {
+        let config = WebhookSignature::github("secret");
+        let sig = sign(b"secret", &[b"payload"]);
+
+        let valid = headers("x-hub-signature-256", format!("sha256={}", sig));
+        assert_eq!(config.verify(&valid, b"payload", 0), Ok(()));
+        assert_eq!(config.verify(&valid, b"tampered", 0), Err("signature mismatch"));
+        assert_eq!(
+            config.verify(&HeaderMap::new(), b"payload", 0),
+            Err("missing signature header")
+        );
+    }
+
+    #[test]
+    fn verifies_stripe_signatures_within_tolerance() {
+        let config = WebhookSignature::stripe("secret").tolerance(Duration::from_secs(60));
+        let sig = sign(b"secret", &[b"1000.", b"payload"]);
+        let valid = headers("stripe-signature", format!("t=1000,v1=deadbeef,v1={}", sig));
+
+        assert_eq!(config.verify(&valid, b"payload", 1030), Ok(()));
+        assert_eq!(
+            config.verify(&valid, b"payload", 1100),
+            Err("timestamp outside of the tolerance window")
+        );
+        assert_eq!(config.verify(&valid, b"tampered", 1030), Err("signature mismatch"));
+    }
+
+    #[test]
+    fn decodes_hex() {
+        assert_eq!(decode_hex("00ff10"), Some(vec![0x00, 0xff, 0x10]));
+        assert_eq!(decode_hex("0"), None);
+        assert_eq!(decode_hex("zz"), None);
+    }
+
+    #[test]
+    fn rejects_signs_in_hex() {
+        assert_eq!(decode_hex("+f"), None);
+        assert_eq!(decode_hex("00+f"), None);
+        assert_eq!(decode_hex("-f"), None);
+    }
+}
@@ -0,0 +1,281 @@
+//! Pagination of the collection endpoints.
+//!
+//! The [`Paginator`](./struct.Paginator.html) extracts the [`Pagination`](./struct.Pagination.html) of a request from
+//! its `page` and `per_page` query parameters, or its `cursor` and `per_page` parameters for the cursor based
+//! pagination. The `per_page` parameter is bounded by a maximum, and the invalid parameters e.g. `page=0` are
+//! rejected with [`HttpError`](../struct.HttpError.html)s of status `400 Bad Request`.
+//!
+//! The `Link` response header of RFC 8288 (formerly RFC 5988) with the `first`, `prev`, `next` and `last` relations is
+//! built from the total count of the items by the [`link_header`](./struct.Pagination.html#method.link_header) method,
+//! or from the cursor of the next page by the
+//! [`cursor_link_header`](./struct.Pagination.html#method.cursor_link_header) method. The links keep the path and the
+//! other query parameters of the request.
+//!
+//! # Examples
+//!
+//! ```
+//! use routerify::{Router, RouteError};
+//! use routerify::pagination::Paginator;
+//! use hyper::{header, Response, Body};
+//!
+//! # fn run() -> Router<Body, RouteError> {
+//! let paginator = Paginator::new().default_per_page(25).max_per_page(200);
+//!
+//! let router = Router::builder()
+//!     .get("/users", move |req| async move {
+//!         let pagination = paginator.extract(&req)?;
+//!
+//!         // E.g. `SELECT * FROM users LIMIT $1 OFFSET $2` with `pagination.limit()` and `pagination.offset()`.
+//!         let total = 1000;
+//!
+//!         let mut resp = Response::new(Body::from("[]"));
+//!         resp.headers_mut().insert(header::LINK, pagination.link_header(total));
+//!         Ok(resp)
+//!     })
+//!     .build()
+//!     .unwrap();
+//! # router
+//! # }
+//! # run();
+//! ```
+
+use crate::HttpError;
+use hyper::header::HeaderValue;
+use hyper::{Request, StatusCode};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
+
+const PAGE: &str = "page";
+const PER_PAGE: &str = "per_page";
+const CURSOR: &str = "cursor";
+
+/// The configuration of the pagination parameters.
+///
+/// Please refer to the [module](./index.html) documentation for more info.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Paginator {
+    default_per_page: u64,
+    max_per_page: u64,
+}
+
+impl Default for Paginator {
+    fn default() -> Self {
+        Paginator::new()
+    }
+}
+
+impl Paginator {
+    /// Creates a new configuration with `20` items per page by default and at most `100`.
+    pub fn new() -> Self {
+        Paginator {
+            default_per_page: 20,
+            max_per_page: 100,
+        }
+    }
+
+    /// Sets the number of the items per page if the request has no `per_page` parameter.
+    pub fn default_per_page(mut self, per_page: u64) -> Self {
+        self.default_per_page = per_page.max(1);
+        self
+    }
+
+    /// Sets the maximum number of the items per page. A larger `per_page` parameter is lowered to it.
+    pub fn max_per_page(mut self, per_page: u64) -> Self {
+        self.max_per_page = per_page.max(1);
+        self
+    }
+
+    /// Extracts the pagination of the request from its query parameters.
+    pub fn extract<B>(&self, req: &Request<B>) -> Result<Pagination, HttpError> {
+        let query = req.uri().query().unwrap_or("");
+        let param = |name: &str| {
+            query
+                .split('&')
+                .filter_map(|pair| pair.split_once('='))
+                .find(|(key, _)| *key == name)
+                .map(|(_, val)| {
+                    percent_decode_str(&val.replace('+', " "))
+                        .decode_utf8_lossy()
+                        .into_owned()
+                })
+        };
+        let number = |name: &str| match param(name) {
+            Some(val) => val
+                .trim()
+                .parse::<u64>()
+                .ok()
+                .filter(|n| *n > 0)
+                .map(Some)
+                .ok_or_else(|| {
+                    HttpError::new(
+                        StatusCode::BAD_REQUEST,
+                        format!("The {} parameter must be a positive integer", name),
+                    )
+                }),
+            None => Ok(None),
+        };
+
+        let page = number(PAGE)?.unwrap_or(1);
+        let per_page = number(PER_PAGE)?
+            .unwrap_or(self.default_per_page)
+            .min(self.max_per_page);
+        let cursor = param(CURSOR).filter(|cursor| !cursor.is_empty());
+
+        Ok(Pagination {
+            page,
+            per_page,
+            cursor,
+            path: req.uri().path().to_owned(),
+            query: query
+                .split('&')
+                .filter(|pair| {
+                    let key = pair.split('=').next().unwrap_or_default();
+                    !pair.is_empty() && ![PAGE, PER_PAGE, CURSOR].contains(&key)
+                })
+                .map(str::to_owned)
+                .collect(),
+        })
+    }
+}
+
+/// The pagination of a request, which is extracted by the [`Paginator`](./struct.Paginator.html).
+///
+/// Please refer to the [module](./index.html) documentation for more info.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pagination {
+    page: u64,
+    per_page: u64,
+    cursor: Option<String>,
+    path: String,
+    // The other query parameters, which are kept in the links.
+    query: Vec<String>,
+}
+
+impl Pagination {
+    /// Returns the requested page, starting from `1`.
+    pub fn page(&self) -> u64 {
+        self.page
+    }
+
+    /// Returns the number of the items per page.
+    pub fn per_page(&self) -> u64 {
+        self.per_page
+    }
+
+    /// Returns the cursor of the cursor based pagination.
+    pub fn cursor(&self) -> Option<&str> {
+        self.cursor.as_deref()
+    }
+
+    /// Returns the number of the items to return, i.e. the number of the items per page.
+    pub fn limit(&self) -> u64 {
+        self.per_page
+    }
+
+    /// Returns the number of the items to skip for the requested page.
+    pub fn offset(&self) -> u64 {
+        (self.page - 1).saturating_mul(self.per_page)
+    }
+
+    /// Builds the `Link` header with the `first`, `prev`, `next` and `last` relations from the total count of the items.
+    /// The `prev` and `next` links are left out on the first and the last page.
+    pub fn link_header(&self, total: u64) -> HeaderValue {
+        let last = total.div_ceil(self.per_page).max(1);
+
+        let mut links = vec![(1, "first")];
+        if self.page > 1 {
+            links.push((self.page.min(last + 1) - 1, "prev"));
+        }
+        if self.page < last {
+            links.push((self.page + 1, "next"));
+        }
+        links.push((last, "last"));
+
+        let links = links
+            .into_iter()
+            .map(|(page, rel)| self.link(&format!("{}={}", PAGE, page), rel))
+            .collect::<Vec<_>>();
+        self.header_value(&links)
+    }
+
+    /// Builds the `Link` header with the `first` relation and the `next` relation if there is a next page, for the cursor
+    /// based pagination.
+    pub fn cursor_link_header(&self, next_cursor: Option<&str>) -> HeaderValue {
+        let mut links = vec![self.link("", "first")];
+        if let Some(cursor) = next_cursor {
+            let param = format!("{}={}", CURSOR, utf8_percent_encode(cursor, NON_ALPHANUMERIC));
+            links.push(self.link(&param, "next"));
+        }
+        self.header_value(&links)
+    }
+
+    fn link(&self, param: &str, rel: &str) -> String {
+        let per_page = format!("{}={}", PER_PAGE, self.per_page);
+        let query = self
+            .query
+            .iter()
+            .map(String::as_str)
+            .chain(Some(param).filter(|param| !param.is_empty()))
+            .chain(Some(per_page.as_str()))
+            .collect::<Vec<_>>()
+            .join("&");
+        format!("<{}?{}>; rel=\"{}\"", self.path, query, rel)
+    }
+
+    fn header_value(&self, links: &[String]) -> HeaderValue {
+        // The path and the query come from a valid URI, so they are valid header characters.
+        HeaderValue::from_str(&links.join(", ")).expect("The Link header is invalid")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extract(paginator: Paginator, uri: &str) -> Result<Pagination, HttpError> {
+        paginator.extract(&Request::get(uri).body(()).unwrap())
+    }
+
+    #[test]
+    fn extracts_pagination() {
+        let pagination = extract(Paginator::new(), "/users").unwrap();
+        assert_eq!(
+            (pagination.page(), pagination.per_page(), pagination.offset()),
+            (1, 20, 0)
+        );
+
+        let pagination = extract(Paginator::new().max_per_page(50), "/users?page=3&per_page=500").unwrap();
+        assert_eq!(
+            (pagination.page(), pagination.limit(), pagination.offset()),
+            (3, 50, 100)
+        );
+
+        let pagination = extract(Paginator::new(), "/users?cursor=abc%3D%3D").unwrap();
+        assert_eq!(pagination.cursor(), Some("abc=="));
+
+        assert!(extract(Paginator::new(), "/users?page=0").is_err());
+        assert!(extract(Paginator::new(), "/users?per_page=ten").is_err());
+    }
+
+    #[test]
+    fn builds_link_headers() {
+        let pagination = extract(Paginator::new(), "/users?sort=name&page=2&per_page=10").unwrap();
+        assert_eq!(
+            pagination.link_header(35),
+            "</users?sort=name&page=1&per_page=10>; rel=\"first\", \
+             </users?sort=name&page=1&per_page=10>; rel=\"prev\", \
+             </users?sort=name&page=3&per_page=10>; rel=\"next\", \
+             </users?sort=name&page=4&per_page=10>; rel=\"last\""
+        );
+
+        let pagination = extract(Paginator::new(), "/users").unwrap();
+        assert_eq!(
+            pagination.link_header(0),
+            "</users?page=1&per_page=20>; rel=\"first\", </users?page=1&per_page=20>; rel=\"last\""
+        );
+
+        assert_eq!(
+            pagination.cursor_link_header(Some("a b")),
+            "</users?per_page=20>; rel=\"first\", </users?cursor=a%20b&per_page=20>; rel=\"next\""
+        );
+    }
+}
@@ -0,0 +1,192 @@
+use hyper::header::{self, HeaderValue};
+use hyper::{Body, Request, Response, StatusCode};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+
+type LongPollFuture<E> = Pin<Box<dyn Future<Output = Result<Response<Body>, E>> + Send>>;
+
+/// A source of change notifications with an increasing version, which is awaited by the
+/// [`long_poll`](./fn.long_poll.html) handlers.
+///
+/// The clones of a source share the same version.
+#[derive(Debug, Clone)]
+pub struct ChangeSource {
+    version: Arc<watch::Sender<u64>>,
+}
+
+impl Default for ChangeSource {
+    fn default() -> Self {
+        ChangeSource::new()
+    }
+}
+
+impl ChangeSource {
+    /// Creates a new source at the version `0`.
+    pub fn new() -> Self {
+        ChangeSource {
+            version: Arc::new(watch::channel(0).0),
+        }
+    }
+
+    /// Notifies the waiting requests of a change, and returns the new version.
+    pub fn notify(&self) -> u64 {
+        let mut version = 0;
+        self.version.send_modify(|current| {
+            *current += 1;
+            version = *current;
+        });
+        version
+    }
+
+    /// Returns the current version.
+    pub fn version(&self) -> u64 {
+        *self.version.borrow()
+    }
+
+    /// Waits until the version differs from `since`, or the timeout elapses. It returns the current version, or
+    /// `None` on timeout.
+    pub async fn changed(&self, since: u64, timeout: Duration) -> Option<u64> {
+        let mut receiver = self.version.subscribe();
+        let changed = receiver.wait_for(|version| *version != since);
+        let version = match tokio::time::timeout(timeout, changed).await {
+            Ok(Ok(version)) => Some(*version),
+            _ => None,
+        };
+        version
+    }
+}
+
+/// Wraps a route handler, so the requests with an up to date version token wait for a change of the source before
+/// the handler is called.
+///
+/// The version token is the entity tag of the response e.g. `"5"`, which the client sends back in the `If-None-Match`
+/// header of its next request. If the source is still at that version, the request waits until the source is notified
+/// or the timeout elapses, and it's answered with `304 Not Modified` on timeout, so the client polls again. The
+/// requests without a token, or with an outdated one, are handled immediately.
+///
+/// # Examples
+///
+/// ```
+/// use routerify::{Router, RouteError};
+/// use routerify::realtime::{long_poll, ChangeSource};
+/// use hyper::{Response, Body};
+/// use std::time::Duration;
+///
+/// # fn run() -> Router<Body, RouteError> {
+/// let source = ChangeSource::new();
+///
+/// let dashboard = source.clone();
+/// let router = Router::builder()
+///     .get(
+///         "/dashboard",
+///         long_poll(dashboard, Duration::from_secs(30), |_| async move {
+///             Ok(Response::new(Body::from("{\"orders\": 42}")))
+///         }),
+///     )
+///     .post("/orders", move |_| {
+///         let source = source.clone();
+///         async move {
+///             // Create the order, then wake up the dashboards.
+///             source.notify();
+///             Ok(Response::new(Body::from("Created")))
+///         }
+///     })
+///     .build()
+///     .unwrap();
+/// # router
+/// # }
+/// # run();
+/// ```
+pub fn long_poll<H, R, E>(
+    source: ChangeSource,
+    timeout: Duration,
+    handler: H,
+) -> impl Fn(Request<Body>) -> LongPollFuture<E> + Send + Sync + 'static
+where
+    H: Fn(Request<Body>) -> R + Send + Sync + 'static,
+    R: Future<Output = Result<Response<Body>, E>> + Send + 'static,
+    E: 'static,
+{
+    let handler = Arc::new(handler);
+    move |req: Request<Body>| {
+        let source = source.clone();
+        let handler = handler.clone();
+        Box::pin(async move {
+            let mut version = source.version();
+            if version_token(&req) == Some(version) {
+                version = match source.changed(version, timeout).await {
+                    Some(version) => version,
+                    None => return Ok(with_version(not_modified(), version)),
+                };
+            }
+
+            // The version is read before the handler, so a change during the handler isn't missed by the client.
+            let resp = handler(req).await?;
+            Ok(with_version(resp, version))
+        })
+    }
+}
+
+fn version_token(req: &Request<Body>) -> Option<u64> {
+    let val = req.headers().get(header::IF_NONE_MATCH)?.to_str().ok()?.trim();
+    let val = val.strip_prefix("W/").unwrap_or(val);
+    val.strip_prefix('"')?.strip_suffix('"')?.parse().ok()
+}
+
+fn with_version(mut resp: Response<Body>, version: u64) -> Response<Body> {
+    let etag = HeaderValue::from_str(&format!("\"{}\"", version)).expect("The version token is invalid");
+    resp.headers_mut().insert(header::ETAG, etag);
+    resp.headers_mut()
+        .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    resp
+}
+
+fn not_modified() -> Response<Body> {
+    let mut resp = Response::new(Body::empty());
+    *resp.status_mut() = StatusCode::NOT_MODIFIED;
+    resp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::Infallible;
+
+    #[tokio::test]
+    async fn waits_for_changes() {
+        let source = ChangeSource::new();
+        let handler = long_poll(source.clone(), Duration::from_millis(50), |_| async move {
+            Ok::<_, Infallible>(Response::new(Body::from("state")))
+        });
+        let request = |token: Option<&str>| {
+            let mut req = Request::get("/");
+            if let Some(token) = token {
+                req = req.header("if-none-match", token);
+            }
+            req.body(Body::empty()).unwrap()
+        };
+
+        let resp = handler(request(None)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["etag"], "\"0\"");
+
+        let resp = handler(request(Some("\"0\""))).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(resp.headers()["etag"], "\"0\"");
+
+        let notifier = source.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            notifier.notify();
+        });
+        let resp = handler(request(Some("\"0\""))).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["etag"], "\"1\"");
+
+        let resp = handler(request(Some("W/\"0\""))).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+}
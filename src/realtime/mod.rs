@@ -0,0 +1,383 @@
+//! A bidirectional message channel between the server and a client with transport negotiation.
+//!
+//! The [`Realtime`](./struct.Realtime.html) router is mounted as a [scope](../struct.RouterBuilder.html#method.scope)
+//! and calls the connection handler with a [`Channel`](./struct.Channel.html) for every negotiated session. The handler
+//! sends and receives text messages through the channel regardless of the transport the client uses.
+//!
+//! The client negotiates a session first:
+//!
+//! * `POST <scope>/negotiate?transports=websocket,polling` responds with
+//!   `{"sid":"<session id>","transport":"polling","pollTimeout":<ms>}`, picking the most preferred transport the server
+//!   and the client both support.
+//!
+//! Then, with the long polling transport:
+//!
+//! * `GET <scope>/poll/<sid>` waits until there are messages for the client, or the poll timeout, and responds with a
+//!   JSON array of the messages. It responds with `410 Gone` once the server closes the channel.
+//! * `POST <scope>/poll/<sid>` sends the request body as a message to the server.
+//! * `DELETE <scope>/poll/<sid>` closes the session.
+//!
+//! A session which isn't polled for the session timeout is closed. The WebSocket transport isn't available yet, so the
+//! clients always fall back to long polling, which also works behind the proxies blocking the connection upgrades.
+//!
+//! For the dashboards which only need to know when some state changes, the [`long_poll`](./fn.long_poll.html) handler
+//! wrapper holds a request until its [`ChangeSource`](./struct.ChangeSource.html) is notified.
+//!
+//! # Examples
+//!
+//! ```
+//! use routerify::{Router, RouteError};
+//! use routerify::realtime::Realtime;
+//! use hyper::Body;
+//!
+//! # fn run() -> Router<Body, RouteError> {
+//! let realtime = Realtime::new(|mut channel| async move {
+//!     // Echo the messages back.
+//!     while let Some(msg) = channel.recv().await {
+//!         channel.send(msg);
+//!     }
+//! });
+//!
+//! let router = Router::builder()
+//!     .scope("/realtime", realtime.into_router())
+//!     .build()
+//!     .unwrap();
+//! # router
+//! # }
+//! # run();
+//! ```
+
+pub use long_poll::{long_poll, ChangeSource};
+
+mod long_poll;
+
+use crate::ext::RequestExt;
+use crate::helpers::{self, json_str};
+use crate::Router;
+use hyper::{header, Body, Request, Response, StatusCode};
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Formatter};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+const DEFAULT_POLL_TIMEOUT: Duration = Duration::from_secs(25);
+const DEFAULT_SESSION_TIMEOUT: Duration = Duration::from_secs(60);
+const SESSION_ID_LEN: usize = 16;
+
+type ConnectHandler = Arc<dyn Fn(Channel) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+
+/// The transport of a [`Channel`](./struct.Channel.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Transport {
+    /// HTTP long polling.
+    LongPolling,
+}
+
+impl Transport {
+    /// Returns the name of the transport used in the negotiation e.g. `polling`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Transport::LongPolling => "polling",
+        }
+    }
+}
+
+// The transports supported by the server, in the order of preference.
+const TRANSPORTS: &[Transport] = &[Transport::LongPolling];
+
+/// A bidirectional message channel with a client.
+///
+/// The channel is closed for the client once it's dropped, and [`recv`](#method.recv) returns `None` once the client
+/// closes the session or it times out.
+#[derive(Debug)]
+pub struct Channel {
+    id: String,
+    transport: Transport,
+    outgoing: UnboundedSender<String>,
+    incoming: UnboundedReceiver<String>,
+}
+
+impl Channel {
+    /// Returns the session id.
+    pub fn id(&self) -> &str {
+        self.id.as_str()
+    }
+
+    /// Returns the negotiated transport.
+    pub fn transport(&self) -> Transport {
+        self.transport
+    }
+
+    /// Queues a message to the client. It returns `false` if the session is closed.
+    pub fn send<M: Into<String>>(&self, msg: M) -> bool {
+        self.outgoing.send(msg.into()).is_ok()
+    }
+
+    /// Receives the next message from the client.
+    pub async fn recv(&mut self) -> Option<String> {
+        self.incoming.recv().await
+    }
+
+    pub(crate) fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<String>> {
+        self.incoming.poll_recv(cx)
+    }
+
+    // A sender of the messages to the client, which keeps the session open until it's dropped.
+    pub(crate) fn sender(&self) -> UnboundedSender<String> {
+        self.outgoing.clone()
+    }
+
+    // Creates a channel without a session, with the sender of the incoming messages and the receiver of the outgoing
+    // ones.
+    #[cfg(test)]
+    pub(crate) fn detached(id: &str) -> (Channel, UnboundedSender<String>, UnboundedReceiver<String>) {
+        let (incoming_tx, incoming_rx) = mpsc::unbounded_channel();
+        let (outgoing_tx, outgoing_rx) = mpsc::unbounded_channel();
+        let channel = Channel {
+            id: id.to_owned(),
+            transport: Transport::LongPolling,
+            outgoing: outgoing_tx,
+            incoming: incoming_rx,
+        };
+        (channel, incoming_tx, outgoing_rx)
+    }
+}
+
+struct Session {
+    incoming: UnboundedSender<String>,
+    outgoing: tokio::sync::Mutex<UnboundedReceiver<String>>,
+    last_seen: Mutex<Instant>,
+}
+
+impl Session {
+    fn touch(&self) {
+        *self.last_seen.lock().unwrap() = Instant::now();
+    }
+}
+
+/// The configuration of the realtime endpoint.
+///
+/// Please refer to the [module](./index.html) documentation for more info.
+#[derive(Clone)]
+pub struct Realtime {
+    on_connect: ConnectHandler,
+    poll_timeout: Duration,
+    session_timeout: Duration,
+    sessions: Arc<Mutex<HashMap<String, Arc<Session>>>>,
+}
+
+impl Realtime {
+    /// Creates a new endpoint which calls `on_connect` with the channel of every new session, in a new task.
+    pub fn new<F, R>(on_connect: F) -> Self
+    where
+        F: Fn(Channel) -> R + Send + Sync + 'static,
+        R: Future<Output = ()> + Send + 'static,
+    {
+        Realtime {
+            on_connect: Arc::new(move |channel| Box::pin(on_connect(channel))),
+            poll_timeout: DEFAULT_POLL_TIMEOUT,
+            session_timeout: DEFAULT_SESSION_TIMEOUT,
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Sets how long a poll request waits for messages. Defaults to 25 seconds.
+    pub fn poll_timeout(mut self, poll_timeout: Duration) -> Self {
+        self.poll_timeout = poll_timeout;
+        self
+    }
+
+    /// Sets how long a session is kept without being polled. Defaults to 60 seconds.
+    pub fn session_timeout(mut self, session_timeout: Duration) -> Self {
+        self.session_timeout = session_timeout;
+        self
+    }
+
+    /// Creates a router to be mounted as a scope.
+    pub fn into_router<E>(self) -> Router<Body, E>
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        let config = Arc::new(self);
+        let negotiate_config = config.clone();
+        let poll_config = config.clone();
+        let send_config = config.clone();
+        let close_config = config;
+
+        Router::builder()
+            .post("/negotiate", move |req| {
+                let config = negotiate_config.clone();
+                async move { Ok(config.negotiate(req)) }
+            })
+            .get("/poll/:sid", move |req| {
+                let config = poll_config.clone();
+                async move { Ok(config.poll(req).await) }
+            })
+            .post("/poll/:sid", move |req| {
+                let config = send_config.clone();
+                async move { Ok(config.receive(req).await) }
+            })
+            .delete("/poll/:sid", move |req| {
+                let config = close_config.clone();
+                async move { Ok(config.close(req)) }
+            })
+            .build()
+            .expect("Couldn't create the realtime router")
+    }
+
+    fn negotiate(&self, req: Request<Body>) -> Response<Body> {
+        self.prune_sessions();
+
+        let client_transports = req
+            .uri()
+            .query()
+            .unwrap_or("")
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("transports="))
+            .map(|val| val.split(',').map(str::to_owned).collect::<Vec<_>>());
+        let transport = TRANSPORTS.iter().copied().find(|transport| match client_transports {
+            Some(ref names) => names.iter().any(|name| name == transport.name()),
+            None => true,
+        });
+        let transport = match transport {
+            Some(transport) => transport,
+            None => return text_response(StatusCode::BAD_REQUEST, "No supported transport"),
+        };
+
+        let (incoming_tx, incoming_rx) = mpsc::unbounded_channel();
+        let (outgoing_tx, outgoing_rx) = mpsc::unbounded_channel();
+        let sid = helpers::random_hex(SESSION_ID_LEN);
+
+        self.sessions.lock().unwrap().insert(
+            sid.clone(),
+            Arc::new(Session {
+                incoming: incoming_tx,
+                outgoing: tokio::sync::Mutex::new(outgoing_rx),
+                last_seen: Mutex::new(Instant::now()),
+            }),
+        );
+
+        tokio::spawn((self.on_connect)(Channel {
+            id: sid.clone(),
+            transport,
+            outgoing: outgoing_tx,
+            incoming: incoming_rx,
+        }));
+
+        let body = format!(
+            "{{\"sid\":{},\"transport\":{},\"pollTimeout\":{}}}",
+            json_str(&sid),
+            json_str(transport.name()),
+            self.poll_timeout.as_millis()
+        );
+        Response::builder()
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body))
+            .expect("Couldn't create the negotiation response")
+    }
+
+    async fn poll(&self, req: Request<Body>) -> Response<Body> {
+        let (sid, session) = match self.session(&req) {
+            Some(session) => session,
+            None => return text_response(StatusCode::NOT_FOUND, "Unknown session"),
+        };
+
+        session.touch();
+        let mut outgoing = session.outgoing.lock().await;
+        let mut messages = Vec::new();
+        match tokio::time::timeout(self.poll_timeout, outgoing.recv()).await {
+            Ok(Some(msg)) => {
+                messages.push(msg);
+                while let Ok(msg) = outgoing.try_recv() {
+                    messages.push(msg);
+                }
+            }
+            Ok(None) => {
+                self.sessions.lock().unwrap().remove(&sid);
+                return text_response(StatusCode::GONE, "Session closed");
+            }
+            Err(_) => {}
+        }
+        session.touch();
+
+        let body = format!(
+            "[{}]",
+            messages.iter().map(|msg| json_str(msg)).collect::<Vec<_>>().join(",")
+        );
+        Response::builder()
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::CACHE_CONTROL, "no-store")
+            .body(Body::from(body))
+            .expect("Couldn't create the poll response")
+    }
+
+    async fn receive(&self, req: Request<Body>) -> Response<Body> {
+        let (_, session) = match self.session(&req) {
+            Some(session) => session,
+            None => return text_response(StatusCode::NOT_FOUND, "Unknown session"),
+        };
+
+        let body = match hyper::body::to_bytes(req.into_body()).await {
+            Ok(body) => body,
+            Err(err) => return text_response(StatusCode::BAD_REQUEST, &err.to_string()),
+        };
+        let msg = match String::from_utf8(body.to_vec()) {
+            Ok(msg) => msg,
+            Err(_) => return text_response(StatusCode::BAD_REQUEST, "Message isn't valid UTF-8"),
+        };
+
+        session.touch();
+        if session.incoming.send(msg).is_err() {
+            return text_response(StatusCode::GONE, "Session closed");
+        }
+        text_response(StatusCode::NO_CONTENT, "")
+    }
+
+    fn close(&self, req: Request<Body>) -> Response<Body> {
+        match self.session(&req) {
+            Some((sid, _)) => {
+                self.sessions.lock().unwrap().remove(&sid);
+                text_response(StatusCode::NO_CONTENT, "")
+            }
+            None => text_response(StatusCode::NOT_FOUND, "Unknown session"),
+        }
+    }
+
+    fn session(&self, req: &Request<Body>) -> Option<(String, Arc<Session>)> {
+        let sid = req.param("sid")?;
+        let session = self.sessions.lock().unwrap().get(sid).cloned()?;
+        Some((sid.clone(), session))
+    }
+
+    // Closes the sessions which weren't polled for the session timeout. Dropping a session ends the `recv` loop of
+    // its channel.
+    fn prune_sessions(&self) {
+        let session_timeout = self.session_timeout;
+        self.sessions
+            .lock()
+            .unwrap()
+            .retain(|_, session| session.last_seen.lock().unwrap().elapsed() < session_timeout);
+    }
+}
+
+impl Debug for Realtime {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{{ poll_timeout: {:?}, session_timeout: {:?} }}",
+            self.poll_timeout, self.session_timeout
+        )
+    }
+}
+
+fn text_response(status: StatusCode, msg: &str) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .body(Body::from(msg.to_owned()))
+        .expect("Couldn't create the realtime response")
+}
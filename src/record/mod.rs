@@ -0,0 +1,518 @@
+//! Records sampled request/response pairs to a file and replays them against a router, e.g. to regression test it
+//! against the shapes of the production traffic. It requires the `record` feature.
+//!
+//! The [`Recorder`](./struct.Recorder.html) around middleware writes each sampled [`Exchange`](./struct.Exchange.html)
+//! as a JSON line to the capture file. The bodies stream through unchanged to the route handler and the client, while up
+//! to [`max_body_size`](./struct.Recorder.html#method.max_body_size) bytes of each are copied into the recording, so the
+//! streaming responses aren't held back and the bodies aren't buffered. The request body is recorded as far as the route
+//! handler reads it, and the exchange is written once the response body is sent or dropped. The query parameters and the headers are redacted by the
+//! [`Redaction`](../struct.Redaction.html) rules of the router, and the redacted values are recorded as `[REDACTED]`.
+//! The requests which fail without a response, e.g. with an error that is turned into a response by the error handler,
+//! aren't recorded.
+//!
+//! The [`Replayer`](./struct.Replayer.html) sends the recorded requests to a router in the recorded order, and reports
+//! the responses whose status or body differs from the recorded one.
+//!
+//! # Examples
+//!
+//! Recording in production:
+//!
+//! ```no_run
+//! use routerify::{Router, RouteError};
+//! use routerify::record::Recorder;
+//! use hyper::{Response, Body};
+//!
+//! # fn run() -> Router<Body, RouteError> {
+//! let recorder = Recorder::new("/var/log/app/capture.jsonl").unwrap().sample_rate(0.01);
+//!
+//! let router = Router::builder()
+//!     .middleware(recorder.middleware())
+//!     .get("/users/:id", |_| async move { Ok(Response::new(Body::from("{\"name\":\"Alice\"}"))) })
+//!     .build()
+//!     .unwrap();
+//! # router
+//! # }
+//! # run();
+//! ```
+//!
+//! Replaying in a test:
+//!
+//! ```no_run
+//! use routerify::{Router, RouteError};
+//! use routerify::record::Replayer;
+//! use hyper::Body;
+//!
+//! # fn app() -> Router<Body, RouteError> { Router::builder().build().unwrap() }
+//! # async fn run() {
+//! let report = Replayer::run(app(), "tests/fixtures/capture.jsonl").await.unwrap();
+//! assert!(report.is_success(), "{:?}", report.mismatches());
+//! # }
+//! ```
+
+use crate::body::{map_body, BodyMapper};
+use crate::ext::RequestExt;
+use crate::middleware::Middleware;
+use crate::types::Redaction;
+use crate::{helpers, Error, RequestServiceBuilder, Router};
+use hyper::body::{Bytes, HttpBody};
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
+use hyper::{Body, Method, Request, Response, StatusCode, Uri};
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Debug, Formatter};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex as StdMutex};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+const DEFAULT_MAX_BODY_SIZE: usize = 64 * 1024;
+
+/// A recorded request/response pair, which is a line of the capture file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Exchange {
+    method: String,
+    uri: String,
+    request_headers: Vec<(String, String)>,
+    #[serde(with = "base64_body")]
+    request_body: Vec<u8>,
+    request_truncated: bool,
+    status: u16,
+    response_headers: Vec<(String, String)>,
+    #[serde(with = "base64_body")]
+    response_body: Vec<u8>,
+    response_truncated: bool,
+}
+
+impl Exchange {
+    /// Reads all the exchanges of a capture file.
+    pub fn read_all<P: AsRef<Path>>(capture_file: P) -> crate::Result<Vec<Exchange>> {
+        let path = capture_file.as_ref();
+        let src = std::fs::read_to_string(path)
+            .map_err(|err| Error::new(format!("Couldn't read the capture file {:?}: {}", path, err)))?;
+
+        src.lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(idx, line)| {
+                serde_json::from_str(line).map_err(|err| {
+                    Error::new(format!(
+                        "Couldn't parse the exchange at the line {} of {:?}: {}",
+                        idx + 1,
+                        path,
+                        err
+                    ))
+                    .into()
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the request method.
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    /// Returns the request path and query.
+    pub fn uri(&self) -> &str {
+        &self.uri
+    }
+
+    /// Returns the request headers.
+    pub fn request_headers(&self) -> &[(String, String)] {
+        &self.request_headers
+    }
+
+    /// Returns the recorded request body.
+    pub fn request_body(&self) -> &[u8] {
+        &self.request_body
+    }
+
+    /// Returns the response status code.
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// Returns the response headers.
+    pub fn response_headers(&self) -> &[(String, String)] {
+        &self.response_headers
+    }
+
+    /// Returns the recorded response body.
+    pub fn response_body(&self) -> &[u8] {
+        &self.response_body
+    }
+
+    /// Checks if the request or the response body is truncated.
+    pub fn is_truncated(&self) -> bool {
+        self.request_truncated || self.response_truncated
+    }
+
+    fn to_request(&self) -> crate::Result<Request<Body>> {
+        let method = Method::from_bytes(self.method.as_bytes())
+            .map_err(|_| Error::new(format!("Invalid recorded method: {:?}", self.method)))?;
+        let uri = self
+            .uri
+            .parse::<Uri>()
+            .map_err(|_| Error::new(format!("Invalid recorded URI: {:?}", self.uri)))?;
+
+        let mut req = Request::new(Body::from(self.request_body.clone()));
+        *req.method_mut() = method;
+        *req.uri_mut() = uri;
+        for (name, val) in self.request_headers.iter() {
+            if let (Ok(name), Ok(val)) = (HeaderName::from_bytes(name.as_bytes()), HeaderValue::from_str(val)) {
+                req.headers_mut().append(name, val);
+            }
+        }
+        Ok(req)
+    }
+}
+
+mod base64_body {
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(body: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(body))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        STANDARD.decode(encoded).map_err(serde::de::Error::custom)
+    }
+}
+
+/// The recording middleware configuration and its capture file.
+///
+/// It's cheap to clone and the clones append to the same file. Please refer to the [module](./index.html)
+/// documentation for more info.
+#[derive(Clone)]
+pub struct Recorder {
+    file: Arc<Mutex<tokio::fs::File>>,
+    sample_rate: f64,
+    max_body_size: usize,
+}
+
+impl Recorder {
+    /// Creates a new recorder which appends all the requests to the capture file, creating it if it doesn't exist.
+    pub fn new<P: AsRef<Path>>(capture_file: P) -> crate::Result<Self> {
+        let path = capture_file.as_ref();
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|err| Error::new(format!("Couldn't open the capture file {:?}: {}", path, err)))?;
+
+        Ok(Recorder {
+            file: Arc::new(Mutex::new(tokio::fs::File::from_std(file))),
+            sample_rate: 1.0,
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+        })
+    }
+
+    /// Sets the fraction of the requests which are recorded, from `0.0` to `1.0`. Defaults to `1.0`.
+    pub fn sample_rate(mut self, rate: f64) -> Self {
+        self.sample_rate = rate.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Sets the number of the body bytes which are recorded. Defaults to 64 KiB.
+    pub fn max_body_size(mut self, size: usize) -> Self {
+        self.max_body_size = size;
+        self
+    }
+
+    /// Creates an around middleware at the `/*` path.
+    pub fn middleware<E>(&self) -> Middleware<hyper::Body, E>
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        self.middleware_with_path("/*").unwrap()
+    }
+
+    /// Creates an around middleware at the specified path.
+    pub fn middleware_with_path<P, E>(&self, path: P) -> crate::Result<Middleware<hyper::Body, E>>
+    where
+        P: Into<String>,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        let recorder = self.clone();
+        Middleware::around_with_path(path, move |req: Request<Body>, next| {
+            let recorder = recorder.clone();
+            async move {
+                if !recorder.is_sampled() {
+                    return next.run(req).await;
+                }
+
+                let redaction = req.data::<Redaction>().cloned().unwrap_or_default();
+                let (parts, body) = req.into_parts();
+                let request_capture = Arc::new(StdMutex::new(BodyCapture::new(&body)));
+                let mut exchange = Exchange {
+                    method: parts.method.to_string(),
+                    uri: redaction.redact_uri(&parts.uri, None),
+                    request_headers: redact_headers(&redaction, &parts.headers),
+                    request_body: Vec::new(),
+                    request_truncated: false,
+                    status: 0,
+                    response_headers: Vec::new(),
+                    response_body: Vec::new(),
+                    response_truncated: false,
+                };
+
+                let tap = Tap {
+                    capture: request_capture.clone(),
+                    max_body_size: recorder.max_body_size,
+                };
+                let res = next.run(Request::from_parts(parts, map_body(body, tap))).await?;
+                let (parts, body) = res.into_parts();
+                exchange.status = parts.status.as_u16();
+                exchange.response_headers = redact_headers(&redaction, &parts.headers);
+
+                let recording = Recording {
+                    tap: Tap {
+                        capture: Arc::new(StdMutex::new(BodyCapture::new(&body))),
+                        max_body_size: recorder.max_body_size,
+                    },
+                    request_capture,
+                    exchange: Some(exchange),
+                    recorder,
+                };
+                Ok(Response::from_parts(parts, map_body(body, recording)))
+            }
+        })
+    }
+
+    fn is_sampled(&self) -> bool {
+        self.sample_rate >= 1.0 || (helpers::random_u64() as f64 / u64::MAX as f64) < self.sample_rate
+    }
+
+    // A failed write loses the exchange but not the response.
+    async fn write(&self, exchange: &Exchange) {
+        let mut line = match serde_json::to_vec(exchange) {
+            Ok(line) => line,
+            Err(_) => return,
+        };
+        line.push(b'\n');
+
+        let mut file = self.file.lock().await;
+        if file.write_all(&line).await.is_ok() {
+            let _ = file.flush().await;
+        }
+    }
+}
+
+impl Debug for Recorder {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{{ sample_rate: {:?}, max_body_size: {:?} }}",
+            self.sample_rate, self.max_body_size
+        )
+    }
+}
+
+// The recorded part of a body, which is copied as the body streams through.
+struct BodyCapture {
+    bytes: Vec<u8>,
+    truncated: bool,
+    ended: bool,
+}
+
+impl BodyCapture {
+    fn new(body: &Body) -> BodyCapture {
+        BodyCapture {
+            bytes: Vec::new(),
+            truncated: false,
+            ended: body.is_end_stream(),
+        }
+    }
+
+    // A body which wasn't read to its end is recorded as truncated.
+    fn take(&mut self) -> (Vec<u8>, bool) {
+        (std::mem::take(&mut self.bytes), self.truncated || !self.ended)
+    }
+}
+
+// Copies up to `max_body_size` bytes of a body into its capture, and passes the chunks through unchanged.
+struct Tap {
+    capture: Arc<StdMutex<BodyCapture>>,
+    max_body_size: usize,
+}
+
+impl BodyMapper for Tap {
+    fn map_chunk(&mut self, chunk: Bytes) -> crate::Result<Bytes> {
+        let mut capture = self.capture.lock().unwrap();
+        let len = self.max_body_size.saturating_sub(capture.bytes.len()).min(chunk.len());
+        capture.bytes.extend_from_slice(&chunk[..len]);
+        capture.truncated |= len < chunk.len();
+        Ok(chunk)
+    }
+
+    fn finish(&mut self) -> crate::Result<Bytes> {
+        self.capture.lock().unwrap().ended = true;
+        Ok(Bytes::new())
+    }
+}
+
+// Taps the response body, and writes the exchange once the body is sent or dropped.
+struct Recording {
+    tap: Tap,
+    request_capture: Arc<StdMutex<BodyCapture>>,
+    exchange: Option<Exchange>,
+    recorder: Recorder,
+}
+
+impl BodyMapper for Recording {
+    fn map_chunk(&mut self, chunk: Bytes) -> crate::Result<Bytes> {
+        self.tap.map_chunk(chunk)
+    }
+
+    fn finish(&mut self) -> crate::Result<Bytes> {
+        self.tap.finish()
+    }
+}
+
+impl Drop for Recording {
+    fn drop(&mut self) {
+        let mut exchange = match self.exchange.take() {
+            Some(exchange) => exchange,
+            None => return,
+        };
+        let (request_body, request_truncated) = self.request_capture.lock().unwrap().take();
+        let (response_body, response_truncated) = self.tap.capture.lock().unwrap().take();
+        exchange.request_body = request_body;
+        exchange.request_truncated = request_truncated;
+        exchange.response_body = response_body;
+        exchange.response_truncated = response_truncated;
+
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            let recorder = self.recorder.clone();
+            handle.spawn(async move { recorder.write(&exchange).await });
+        }
+    }
+}
+
+fn redact_headers(redaction: &Redaction, headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(name, val)| (name.to_string(), redaction.header_value(name, val).into_owned()))
+        .collect()
+}
+
+/// A recorded exchange whose replayed response differs from the recorded one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    /// The index of the exchange in the capture file.
+    pub index: usize,
+    /// The request method.
+    pub method: String,
+    /// The request path and query.
+    pub uri: String,
+    /// The recorded status code.
+    pub expected_status: u16,
+    /// The replayed status code.
+    pub actual_status: u16,
+    /// Whether the replayed body differs from the recorded one. The truncated bodies aren't compared.
+    pub body_differs: bool,
+}
+
+/// The outcome of a replay.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplayReport {
+    total: usize,
+    mismatches: Vec<Mismatch>,
+}
+
+impl ReplayReport {
+    /// Returns the number of the replayed exchanges.
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Returns the exchanges whose replayed responses differ from the recorded ones.
+    pub fn mismatches(&self) -> &[Mismatch] {
+        &self.mismatches
+    }
+
+    /// Checks if all the replayed responses match the recorded ones.
+    pub fn is_success(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+/// Replays a capture file against a router.
+///
+/// Please refer to the [module](./index.html) documentation for more info.
+#[derive(Debug, Clone, Copy)]
+pub struct Replayer;
+
+impl Replayer {
+    /// Sends the recorded requests to the router one by one, and compares the responses with the recorded ones.
+    ///
+    /// The startup and the shutdown hooks of the router are run around the replay. It fails if the capture file can't
+    /// be read, or the router fails to handle a request.
+    pub async fn run<E, P>(router: Router<Body, E>, capture_file: P) -> crate::Result<ReplayReport>
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+        P: AsRef<Path>,
+    {
+        let exchanges = Exchange::read_all(capture_file)?;
+        let builder = RequestServiceBuilder::new(router)?;
+        let lifecycle = builder.lifecycle();
+        lifecycle.startup().await?;
+
+        let service = builder.build(SocketAddr::from(([127, 0, 0, 1], 0)));
+        let mut report = ReplayReport::default();
+        for (index, exchange) in exchanges.iter().enumerate() {
+            let res = service.clone().call_owned(exchange.to_request()?).await?;
+            let status = res.status();
+            let body = hyper::body::to_bytes(res.into_body()).await.map_err(Error::wrap)?;
+
+            let body_differs = !exchange.response_truncated && body.as_ref() != exchange.response_body.as_slice();
+            if status != StatusCode::from_u16(exchange.status).unwrap_or_default() || body_differs {
+                report.mismatches.push(Mismatch {
+                    index,
+                    method: exchange.method.clone(),
+                    uri: exchange.uri.clone(),
+                    expected_status: exchange.status,
+                    actual_status: status.as_u16(),
+                    body_differs,
+                });
+            }
+            report.total += 1;
+        }
+
+        lifecycle.shutdown().await?;
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::REDACTED;
+
+    #[test]
+    fn round_trips_exchanges() {
+        let exchange = Exchange {
+            method: "POST".to_owned(),
+            uri: "/users?token=[REDACTED]".to_owned(),
+            request_headers: vec![("authorization".to_owned(), REDACTED.to_owned())],
+            request_body: vec![0, 159, 146, 150],
+            request_truncated: false,
+            status: 201,
+            response_headers: Vec::new(),
+            response_body: b"Created".to_vec(),
+            response_truncated: true,
+        };
+
+        let line = serde_json::to_string(&exchange).unwrap();
+        assert!(line.contains("\"request_body\":\"AJ+Slg==\""));
+        assert_eq!(serde_json::from_str::<Exchange>(&line).unwrap(), exchange);
+
+        let req = exchange.to_request().unwrap();
+        assert_eq!(req.method(), Method::POST);
+        assert_eq!(req.headers()["authorization"], REDACTED);
+        assert!(exchange.is_truncated());
+    }
+}
@@ -5,6 +5,18 @@ lazy_static! {
     static ref PATH_PARAMS_RE: Regex = Regex::new(r"(?s)(?::([^/\.]+))|(?:\*)").unwrap();
 }
 
+// Splits a raw param spec e.g. `month`, `month?` or `month?=01` into the param name,
+// whether it's optional and its default value.
+fn parse_param_spec(spec: &str) -> (&str, bool, Option<&str>) {
+    match spec.find('?') {
+        Some(idx) => {
+            let default = spec[idx + 1..].strip_prefix('=');
+            (&spec[..idx], true, default)
+        }
+        None => (spec, false, None),
+    }
+}
+
 fn generate_common_regex_str(path: &str) -> (String, Vec<String>) {
     let mut regex_str = String::with_capacity(path.len());
     let mut param_names = Vec::new();
@@ -17,15 +29,26 @@ fn generate_common_regex_str(path: &str) -> (String, Vec<String>) {
         let path_s = &path[pos..whole.start()];
         regex_str += &regex::escape(path_s);
 
+        pos = whole.end();
+
         if whole.as_str() == "*" {
             regex_str += r"(.*)";
             param_names.push("*".to_owned());
         } else {
-            regex_str += r"([^/]+)";
-            param_names.push(caps.get(1).unwrap().as_str().to_owned());
-        }
+            let (name, optional, _) = parse_param_spec(caps.get(1).unwrap().as_str());
 
-        pos = whole.end();
+            if !optional {
+                regex_str += r"([^/]+)";
+            } else if path[pos..].starts_with('/') {
+                // An optional segment must be skipped along with its trailing slash.
+                regex_str += r"(?:([^/]+)/)?";
+                pos += 1;
+            } else {
+                regex_str += r"([^/]+)?";
+            }
+
+            param_names.push(name.to_owned());
+        }
     }
 
     let left_over_path_s = &path[pos..];
@@ -41,6 +64,99 @@ pub(crate) fn generate_exact_match_regex(path: &str) -> crate::Result<(Regex, Ve
     Ok((re, params))
 }
 
+// Generates the regex of a route path for the external gateways, which matches the request paths with or without the
+// trailing slash.
+pub(crate) fn generate_gateway_regex_str(path: &str) -> String {
+    let (common_regex_str, _) = generate_common_regex_str(path.trim_end_matches('/'));
+    format!("^{}/?$", common_regex_str)
+}
+
+// Checks if a middleware or a data map path matches all or none of the paths of a route, so that it can be resolved
+// once instead of per request. All the paths of a route start with the literal part of the route path before its first
+// param, which decides the result for a `<prefix>/*` path unless the prefix is longer. It's `None` when it depends on
+// the request path.
+pub(crate) fn path_covers_route(path: &str, route_path: &str) -> Option<bool> {
+    let route_prefix = &route_path[..route_path.find([':', '*']).unwrap_or(route_path.len())];
+    let is_static_route = route_prefix.len() == route_path.len();
+
+    match path.strip_suffix('*') {
+        Some(prefix) if !prefix.contains([':', '*']) => {
+            if route_prefix.starts_with(prefix) {
+                Some(true)
+            } else if !prefix.starts_with(route_prefix) {
+                Some(false)
+            } else {
+                None
+            }
+        }
+        None if !path.contains([':', '*']) => {
+            if is_static_route {
+                Some(path == route_path)
+            } else if !path.starts_with(route_prefix) {
+                Some(false)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+// Returns the default values of the optional params in the path e.g. `/posts/:page?=1`.
+pub(crate) fn generate_param_defaults(path: &str) -> Vec<(String, String)> {
+    PATH_PARAMS_RE
+        .captures_iter(path)
+        .filter_map(|caps| {
+            let (name, _, default) = parse_param_spec(caps.get(1)?.as_str());
+            default.map(|default| (name.to_owned(), default.to_owned()))
+        })
+        .collect()
+}
+
+// A part of a route path in the path syntax.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PathPart<'a> {
+    Literal(&'a str),
+    Param { name: &'a str, optional: bool },
+    Wildcard,
+}
+
+// Splits a route path into its literal parts, params and `*` segments, in the same way as its regex is generated.
+pub(crate) fn split_path_parts(path: &str) -> Vec<PathPart<'_>> {
+    let mut parts = Vec::new();
+    let mut pos: usize = 0;
+
+    for caps in PATH_PARAMS_RE.captures_iter(path) {
+        let whole = caps.get(0).unwrap();
+        if whole.start() > pos {
+            parts.push(PathPart::Literal(&path[pos..whole.start()]));
+        }
+        pos = whole.end();
+
+        if whole.as_str() == "*" {
+            parts.push(PathPart::Wildcard);
+        } else {
+            let (name, optional, _) = parse_param_spec(caps.get(1).unwrap().as_str());
+            parts.push(PathPart::Param { name, optional });
+        }
+    }
+
+    if pos < path.len() {
+        parts.push(PathPart::Literal(&path[pos..]));
+    }
+    parts
+}
+
+// Generates the regex for a route defined by a raw regex pattern. The pattern is always anchored at the start of
+// the path, after the scope prefix, and the named capture groups are used as the route parameters.
+pub(crate) fn generate_raw_regex(prefix: &str, pattern: &str) -> crate::Result<(Regex, Vec<String>)> {
+    let pattern = pattern.strip_prefix('^').unwrap_or(pattern);
+    let re_str = format!("^{}(?:{})", regex::escape(prefix), pattern);
+    let re = Regex::new(re_str.as_str())?;
+    let params = re.capture_names().flatten().map(|name| name.to_owned()).collect();
+    Ok((re, params))
+}
+
 #[allow(dead_code)]
 pub(crate) fn generate_prefix_match_regex(path: &str) -> crate::Result<(Regex, Vec<String>)> {
     let (common_regex_str, params) = generate_common_regex_str(path);
@@ -124,4 +240,67 @@ mod tests {
         let r = generate_common_regex_str(path);
         assert_eq!(r, (r"/users/(.*)(.*)".to_owned(), vec!["*".to_owned(), "*".to_owned()]));
     }
+
+    #[test]
+    fn test_generate_common_regex_str_optional_params() {
+        let path = "/archive/:year/:month?/:day?/";
+        let r = generate_common_regex_str(path);
+        assert_eq!(
+            r,
+            (
+                r"/archive/([^/]+)/(?:([^/]+)/)?(?:([^/]+)/)?".to_owned(),
+                vec!["year".to_owned(), "month".to_owned(), "day".to_owned()]
+            )
+        );
+
+        let path = "/posts/:page?=1";
+        let r = generate_common_regex_str(path);
+        assert_eq!(r, (r"/posts/([^/]+)?".to_owned(), vec!["page".to_owned()]));
+
+        let (re, _) = generate_exact_match_regex("/archive/:year/:month?/:day?/").unwrap();
+        assert!(re.is_match("/archive/2020/"));
+        assert!(re.is_match("/archive/2020/05/"));
+        assert!(re.is_match("/archive/2020/05/17/"));
+        assert!(!re.is_match("/archive/"));
+        assert!(!re.is_match("/archive/2020/05/17/extra/"));
+    }
+
+    #[test]
+    fn test_generate_param_defaults() {
+        assert_eq!(
+            generate_param_defaults("/posts/:category?/:page?=1/"),
+            vec![("page".to_owned(), "1".to_owned())]
+        );
+        assert!(generate_param_defaults("/users/:id/").is_empty());
+    }
+
+    #[test]
+    fn test_generate_raw_regex() {
+        let (re, params) = generate_raw_regex("", r"^/items/(?P<id>\d+)-(?P<slug>[a-z-]+)/$").unwrap();
+        assert_eq!(re.as_str(), r"^(?:/items/(?P<id>\d+)-(?P<slug>[a-z-]+)/$)");
+        assert_eq!(params, vec!["id".to_owned(), "slug".to_owned()]);
+        assert!(re.is_match("/items/42-hello-world/"));
+        assert!(!re.is_match("/items/abc-hello/"));
+
+        let (re, _) = generate_raw_regex("/api.v1", r"/items/(\d+)/$").unwrap();
+        assert_eq!(re.as_str(), r"^/api\.v1(?:/items/(\d+)/$)");
+        assert!(re.is_match("/api.v1/items/42/"));
+        assert!(!re.is_match("/items/42/"));
+    }
+
+    #[test]
+    fn test_path_covers_route() {
+        assert_eq!(path_covers_route("/*", "/:id/"), Some(true));
+        assert_eq!(path_covers_route("/api/*", "/api/users/:id/"), Some(true));
+        assert_eq!(path_covers_route("/api/*", "/about/"), Some(false));
+        assert_eq!(path_covers_route("/api/*", "/apx/:id/"), Some(false));
+        assert_eq!(path_covers_route("/api/*", "/*"), None);
+        assert_eq!(path_covers_route("/api/*", "/:section/"), None);
+        assert_eq!(path_covers_route("/users/:id/*", "/users/:id/posts/"), None);
+
+        assert_eq!(path_covers_route("/about/", "/about/"), Some(true));
+        assert_eq!(path_covers_route("/about", "/about/"), Some(false));
+        assert_eq!(path_covers_route("/about/", "/users/:id/"), Some(false));
+        assert_eq!(path_covers_route("/users/me/", "/users/:id/"), None);
+    }
 }
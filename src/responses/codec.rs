@@ -0,0 +1,38 @@
+use crate::codec::{self, Codec};
+use crate::HttpError;
+use hyper::header::{self, HeaderMap, HeaderValue};
+use hyper::{Body, Response, StatusCode};
+use serde::Serialize;
+
+/// Creates a response with the value encoded by the specified [codec](../codec/index.html) and its content type.
+///
+/// It fails with an [`HttpError`](../struct.HttpError.html) of status `500 Internal Server Error` if the value can't be
+/// encoded.
+pub fn encoded<C: Codec, T: Serialize + ?Sized>(codec: &C, value: &T) -> Result<Response<Body>, HttpError> {
+    let bytes = codec
+        .encode(value)
+        .map_err(|err| HttpError::new(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+
+    let mut resp = Response::new(Body::from(bytes));
+    resp.headers_mut()
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static(codec.media_type()));
+    Ok(resp)
+}
+
+/// Creates a response with the value encoded by the [codec](../codec/index.html) picked by the `Accept` header of the
+/// request headers.
+///
+/// It fails with an [`HttpError`](../struct.HttpError.html) of status `406 Not Acceptable` if no enabled codec is
+/// accepted.
+///
+/// Please refer to the [codec](../codec/index.html) module documentation for an example.
+pub fn negotiated<T: Serialize + ?Sized>(headers: &HeaderMap, value: &T) -> Result<Response<Body>, HttpError> {
+    let accept = headers.get(header::ACCEPT).and_then(|val| val.to_str().ok());
+    let codec = codec::negotiate(accept)
+        .ok_or_else(|| HttpError::new(StatusCode::NOT_ACCEPTABLE, "No acceptable response content type"))?;
+
+    let mut resp = encoded(&codec, value)?;
+    resp.headers_mut()
+        .insert(header::VARY, HeaderValue::from_static("accept"));
+    Ok(resp)
+}
@@ -0,0 +1,173 @@
+use crate::HttpError;
+use hyper::header::{self, HeaderMap};
+use hyper::{Body, Response, StatusCode};
+use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::Hasher;
+use std::sync::Mutex;
+
+// The pre-compressed variants in the order of preference, by their content coding and file extension.
+const VARIANTS: [(&str, &str); 2] = [("br", ".br"), ("gzip", ".gz")];
+
+lazy_static::lazy_static! {
+    // The entity tags of the static contents, by their address and length.
+    static ref ETAGS: Mutex<HashMap<(usize, usize), String>> = Mutex::new(HashMap::new());
+}
+
+/// A set of files compiled into the binary, which are served by the
+/// [`serve_embedded`](../struct.RouterBuilder.html#method.serve_embedded) routes and the
+/// [`embedded`](./fn.embedded.html) function.
+///
+/// It's implemented for the directories of the [`include_dir`](https://docs.rs/include_dir) crate. The assets of the
+/// `rust-embed` crate are served by implementing it with the `get` function of the `RustEmbed` trait.
+pub trait EmbeddedAssets: Send + Sync + 'static {
+    /// Returns the content of the file at the path relative to the root, e.g. `css/site.css`.
+    fn get(&self, path: &str) -> Option<Cow<'static, [u8]>>;
+}
+
+impl EmbeddedAssets for include_dir::Dir<'static> {
+    fn get(&self, path: &str) -> Option<Cow<'static, [u8]>> {
+        self.get_file(path).map(|file| Cow::Borrowed(file.contents()))
+    }
+}
+
+/// Creates the response of an embedded file, with the `Content-Type` guessed from its extension and an `ETag` of its
+/// content hash.
+///
+/// The `index.html` file is served for a directory path. If the request headers accept it, a pre-compressed
+/// `<path>.br` or `<path>.gz` variant of the file is served with the matching `Content-Encoding` instead. A request
+/// whose `If-None-Match` header matches the entity tag is answered with `304 Not Modified`.
+///
+/// It fails with an [`HttpError`](../struct.HttpError.html) of status `404 Not Found` if the file doesn't exist.
+///
+/// # Examples
+///
+/// ```
+/// use routerify::{Router, RouteError};
+/// use routerify::prelude::*;
+/// use routerify::responses;
+/// use include_dir::{include_dir, Dir};
+/// use hyper::Body;
+///
+/// static ASSETS: Dir = include_dir!("$CARGO_MANIFEST_DIR/examples");
+///
+/// # fn run() -> Router<Body, RouteError> {
+/// let router = Router::builder()
+///     .get("/examples/:name", |req| async move {
+///         let path = format!("{}.rs", req.param("name").unwrap());
+///         Ok(responses::embedded(&ASSETS, &path, req.headers())?)
+///     })
+///     .build()
+///     .unwrap();
+/// # router
+/// # }
+/// # run();
+/// ```
+pub fn embedded<A: EmbeddedAssets + ?Sized>(
+    assets: &A,
+    path: &str,
+    headers: &HeaderMap,
+) -> Result<Response<Body>, HttpError> {
+    let mut path = path.trim_start_matches('/').to_owned();
+    if path.is_empty() || path.ends_with('/') {
+        path.push_str("index.html");
+    }
+    if path.split('/').any(|segment| segment == "..") {
+        return Err(not_found());
+    }
+
+    let content = assets.get(&path).ok_or_else(not_found)?;
+    let accepted = accepted_encodings(headers);
+    let (encoding, content) = VARIANTS
+        .iter()
+        .filter(|(encoding, _)| accepted.iter().any(|accepted| accepted == encoding))
+        .find_map(|(encoding, ext)| {
+            assets
+                .get(&format!("{}{}", path, ext))
+                .map(|variant| (Some(*encoding), variant))
+        })
+        .unwrap_or((None, content));
+
+    let etag = etag(&content, matches!(content, Cow::Borrowed(_)));
+    let mime = mime_guess::from_path(&path).first_or_octet_stream();
+    let mut builder = Response::builder()
+        .header(header::CONTENT_TYPE, mime.as_ref())
+        .header(header::ETAG, etag.as_str())
+        .header(header::VARY, "Accept-Encoding");
+    if let Some(encoding) = encoding {
+        builder = builder.header(header::CONTENT_ENCODING, encoding);
+    }
+
+    let is_fresh = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|val| val.to_str().ok())
+        .map(|val| {
+            val.split(',')
+                .map(|tag| tag.trim())
+                .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag)
+        })
+        .unwrap_or(false);
+    let resp = if is_fresh {
+        builder.status(StatusCode::NOT_MODIFIED).body(Body::empty())
+    } else {
+        let body = match content {
+            Cow::Borrowed(content) => Body::from(content),
+            Cow::Owned(content) => Body::from(content),
+        };
+        builder.body(body)
+    };
+    Ok(resp.expect("Couldn't create the embedded file response"))
+}
+
+// Returns the content codings of the `Accept-Encoding` header which aren't refused by a zero quality value.
+fn accepted_encodings(headers: &HeaderMap) -> Vec<String> {
+    let val = match headers.get(header::ACCEPT_ENCODING).and_then(|val| val.to_str().ok()) {
+        Some(val) => val,
+        None => return Vec::new(),
+    };
+
+    let mut accepted = Vec::new();
+    for item in val.split(',') {
+        let mut parts = item.split(';');
+        let coding = parts.next().unwrap_or("").trim().to_ascii_lowercase();
+        let is_refused = parts
+            .filter_map(|param| param.trim().strip_prefix("q="))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .map(|q| q <= 0.0)
+            .unwrap_or(false);
+        if is_refused || coding.is_empty() {
+            continue;
+        }
+
+        if coding == "*" {
+            accepted.extend(VARIANTS.iter().map(|(encoding, _)| encoding.to_string()));
+        } else {
+            accepted.push(coding);
+        }
+    }
+    accepted
+}
+
+// Hashes the content into a strong entity tag. The tags of the static contents are computed once.
+fn etag(content: &[u8], is_static: bool) -> String {
+    let hash = |content: &[u8]| {
+        let mut hasher = DefaultHasher::new();
+        hasher.write(content);
+        format!("\"{:016x}\"", hasher.finish())
+    };
+
+    if !is_static {
+        return hash(content);
+    }
+    ETAGS
+        .lock()
+        .unwrap()
+        .entry((content.as_ptr() as usize, content.len()))
+        .or_insert_with(|| hash(content))
+        .clone()
+}
+
+fn not_found() -> HttpError {
+    HttpError::new(StatusCode::NOT_FOUND, "File not found")
+}
@@ -0,0 +1,179 @@
+use super::ranged::{parse_range_spec, Range};
+use crate::HttpError;
+use hyper::header::{self, HeaderMap, HeaderValue};
+use hyper::{Body, Response, StatusCode};
+use std::io;
+use std::path::PathBuf;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, SeekFrom};
+use tokio_util::io::ReaderStream;
+
+/// Creates a streaming response of the file at the specified path.
+///
+/// The `Content-Type` is guessed from the file extension, and the `Content-Length` and `Accept-Ranges` headers
+/// are set. A single byte range request is answered with `206 Partial Content` if the request headers are passed
+/// by the [`with_range`](./struct.FileResponse.html#method.with_range) method. It requires the `fs` feature.
+///
+/// # Examples
+///
+/// ```
+/// use routerify::{Router, RouteError};
+/// use routerify::responses;
+/// use hyper::Body;
+///
+/// # fn run() -> Router<Body, RouteError> {
+/// let router = Router::builder()
+///     .get("/report", |req| async move {
+///         let resp = responses::file("reports/latest.pdf")
+///             .with_range(req.headers())
+///             .into_response()
+///             .await?;
+///         Ok(resp)
+///     })
+///     .build()
+///     .unwrap();
+/// # router
+/// # }
+/// # run();
+/// ```
+pub fn file<P: Into<PathBuf>>(path: P) -> FileResponse {
+    FileResponse {
+        path: path.into(),
+        range: None,
+    }
+}
+
+/// A builder of a streaming file response. It's created by the [`file`](./fn.file.html) function.
+#[derive(Debug, Clone)]
+pub struct FileResponse {
+    path: PathBuf,
+    range: Option<String>,
+}
+
+impl FileResponse {
+    /// Takes the `Range` header from the request headers, if any.
+    ///
+    /// Only a single `bytes` range is supported, requests with multiple ranges get the whole file.
+    pub fn with_range(mut self, headers: &HeaderMap) -> Self {
+        self.range = headers
+            .get(header::RANGE)
+            .and_then(|val| val.to_str().ok())
+            .map(|val| val.to_owned());
+        self
+    }
+
+    /// Opens the file and creates the response.
+    ///
+    /// It fails with an [`HttpError`](../struct.HttpError.html) of status `404 Not Found` if the file doesn't exist.
+    pub async fn into_response(self) -> Result<Response<Body>, HttpError> {
+        let mut file = tokio::fs::File::open(&self.path).await.map_err(io_error)?;
+        let metadata = file.metadata().await.map_err(io_error)?;
+        if !metadata.is_file() {
+            return Err(HttpError::new(StatusCode::NOT_FOUND, "File not found"));
+        }
+
+        let len = metadata.len();
+        let mime = mime_guess::from_path(&self.path).first_or_octet_stream();
+
+        let builder = Response::builder()
+            .header(header::CONTENT_TYPE, mime.as_ref())
+            .header(header::ACCEPT_RANGES, "bytes");
+
+        let range = match self.range.as_deref().map(|range| parse_range(range, len)) {
+            Some(Range::Satisfiable(start, end)) => Some((start, end)),
+            Some(Range::Unsatisfiable) => {
+                return builder
+                    .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                    .header(header::CONTENT_RANGE, format!("bytes */{}", len))
+                    .body(Body::empty())
+                    .map_err(build_error);
+            }
+            Some(Range::Ignored) | None => None,
+        };
+
+        match range {
+            Some((start, end)) => {
+                file.seek(SeekFrom::Start(start)).await.map_err(io_error)?;
+                let part_len = end - start + 1;
+                let stream = ReaderStream::new(file.take(part_len));
+
+                builder
+                    .status(StatusCode::PARTIAL_CONTENT)
+                    .header(header::CONTENT_LENGTH, HeaderValue::from(part_len))
+                    .header(header::CONTENT_RANGE, format!("bytes {}-{}/{}", start, end, len))
+                    .body(Body::wrap_stream(stream))
+                    .map_err(build_error)
+            }
+            None => builder
+                .status(StatusCode::OK)
+                .header(header::CONTENT_LENGTH, HeaderValue::from(len))
+                .body(Body::wrap_stream(ReaderStream::new(file)))
+                .map_err(build_error),
+        }
+    }
+}
+
+fn parse_range(val: &str, len: u64) -> Range {
+    match val.trim().strip_prefix("bytes=") {
+        Some(spec) if !spec.contains(',') => parse_range_spec(spec, len),
+        _ => Range::Ignored,
+    }
+}
+
+fn io_error(err: io::Error) -> HttpError {
+    match err.kind() {
+        io::ErrorKind::NotFound => HttpError::new(StatusCode::NOT_FOUND, "File not found"),
+        io::ErrorKind::PermissionDenied => HttpError::new(StatusCode::FORBIDDEN, "Permission denied"),
+        _ => HttpError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Could not read the file: {}", err),
+        ),
+    }
+}
+
+fn build_error(err: http::Error) -> HttpError {
+    HttpError::new(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        format!("Could not create the file response: {}", err),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_single_byte_ranges() {
+        assert_eq!(parse_range("bytes=0-4", 10), Range::Satisfiable(0, 4));
+        assert_eq!(parse_range("bytes=5-", 10), Range::Satisfiable(5, 9));
+        assert_eq!(parse_range("bytes=-3", 10), Range::Satisfiable(7, 9));
+        assert_eq!(parse_range("bytes=-30", 10), Range::Satisfiable(0, 9));
+        assert_eq!(parse_range("bytes=8-100", 10), Range::Satisfiable(8, 9));
+        assert_eq!(parse_range("bytes=10-", 10), Range::Unsatisfiable);
+        assert_eq!(parse_range("bytes=0-1,4-5", 10), Range::Ignored);
+        assert_eq!(parse_range("items=0-1", 10), Range::Ignored);
+        assert_eq!(parse_range("bytes=5-1", 10), Range::Ignored);
+    }
+
+    #[tokio::test]
+    async fn streams_files_and_ranges() {
+        let path = std::env::temp_dir().join(format!("routerify-file-{}.txt", std::process::id()));
+        std::fs::write(&path, "hello world").unwrap();
+
+        let resp = file(&path).into_response().await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()[header::CONTENT_TYPE], "text/plain");
+        assert_eq!(resp.headers()[header::CONTENT_LENGTH], "11");
+        assert_eq!(hyper::body::to_bytes(resp.into_body()).await.unwrap(), "hello world");
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::RANGE, HeaderValue::from_static("bytes=6-"));
+        let resp = file(&path).with_range(&headers).into_response().await.unwrap();
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(resp.headers()[header::CONTENT_RANGE], "bytes 6-10/11");
+        assert_eq!(hyper::body::to_bytes(resp.into_body()).await.unwrap(), "world");
+
+        std::fs::remove_file(&path).unwrap();
+        let err = file(&path).into_response().await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,340 @@
+use crate::guard::{Guard, GuardOutcome};
+use crate::helpers::{self, html_escape, json_str};
+use crate::HttpError;
+use hyper::header::{self, HeaderValue};
+use hyper::{Body, Request, Response, StatusCode};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use std::cmp::Ordering;
+use std::fmt::{self, Debug, Formatter};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+// The characters which are percent encoded in the links of the entries.
+const SEGMENT: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
+
+/// A static file server of a directory, which optionally renders the listings of its subdirectories.
+///
+/// It's served by the [`serve_dir`](../struct.RouterBuilder.html#method.serve_dir) routes, or by calling its
+/// [`handle`](#method.handle) method from a route with a `*` segment. The files are served like the
+/// [`file`](./fn.file.html) responses. The listings are disabled by default; once
+/// [enabled](#method.listings), a directory path ending with a slash gets a listing of its entries, as HTML or as JSON
+/// if the request accepts `application/json` or has the `format=json` query parameter, and a directory path without
+/// the slash is redirected to it. The hidden entries, whose names start with a dot, are left out.
+///
+/// The entries are sorted by the `sort` query parameter, which is `name`, `size` or `modified`, in the `order` of
+/// `asc` or `desc`, with the directories first. The HTML listing has the breadcrumbs of the path, and all of its links
+/// are relative, so the server can be mounted at any path.
+///
+/// A [guard](#method.guard) can restrict the listings, e.g. to the authenticated users, without restricting the files.
+///
+/// # Examples
+///
+/// ```
+/// use routerify::{Router, RouteError};
+/// use routerify::responses::StaticDir;
+/// use http::request::Parts;
+/// use routerify::guard::GuardOutcome;
+/// use routerify::HttpError;
+/// use hyper::{Body, StatusCode};
+///
+/// # fn run() -> Router<Body, RouteError> {
+/// let files = StaticDir::new("public").listings(true).guard(|req: &Parts| {
+///     match req.headers.contains_key("authorization") {
+///         true => GuardOutcome::Allow,
+///         false => GuardOutcome::Deny(HttpError::new(StatusCode::UNAUTHORIZED, "Unauthorized")),
+///     }
+/// });
+///
+/// let router = Router::builder()
+///     .get("/files/*", move |req| {
+///         let files = files.clone();
+///         async move { Ok(files.handle(req).await?) }
+///     })
+///     .build()
+///     .unwrap();
+/// # router
+/// # }
+/// # run();
+/// ```
+#[derive(Clone)]
+pub struct StaticDir {
+    dir: PathBuf,
+    listings: bool,
+    guard: Option<Arc<dyn Guard>>,
+}
+
+struct Entry {
+    name: String,
+    is_dir: bool,
+    size: u64,
+    modified: Option<SystemTime>,
+}
+
+impl StaticDir {
+    /// Creates a new server of the files in the directory, without the listings.
+    pub fn new<D: Into<PathBuf>>(dir: D) -> Self {
+        StaticDir {
+            dir: dir.into(),
+            listings: false,
+            guard: None,
+        }
+    }
+
+    /// Enables or disables the listings of the directories.
+    pub fn listings(mut self, enabled: bool) -> Self {
+        self.listings = enabled;
+        self
+    }
+
+    /// Sets a guard which is checked before a listing is rendered. A denied request fails with the error of the guard.
+    pub fn guard<G: Guard>(mut self, guard: G) -> Self {
+        self.guard = Some(Arc::new(guard));
+        self
+    }
+
+    /// Serves the file or the listing of the directory at the path matched by the `*` segment of the route.
+    ///
+    /// It fails with an [`HttpError`](../struct.HttpError.html) of status `404 Not Found` if there is no such file, or
+    /// it's a directory and the listings are disabled.
+    pub async fn handle(&self, req: Request<Body>) -> Result<Response<Body>, HttpError> {
+        let rel_path = helpers::wildcard_path(&req).to_owned();
+        if rel_path.split('/').any(|segment| segment == "..") {
+            return Err(not_found());
+        }
+
+        let path = self.dir.join(rel_path.trim_start_matches('/'));
+        let metadata = tokio::fs::metadata(&path).await.map_err(|_| not_found())?;
+        if !metadata.is_dir() {
+            return super::file(path).with_range(req.headers()).into_response().await;
+        }
+        if !self.listings {
+            return Err(not_found());
+        }
+
+        let (parts, _) = req.into_parts();
+        if let Some(ref guard) = self.guard {
+            if let GuardOutcome::Deny(err) = guard.check(&parts).await {
+                return Err(err);
+            }
+        }
+
+        let req_path = parts.uri.path();
+        if !req_path.ends_with('/') {
+            let name = req_path.rsplit('/').next().unwrap_or_default();
+            return Ok(Response::builder()
+                .status(StatusCode::MOVED_PERMANENTLY)
+                .header(header::LOCATION, format!("{}/", name))
+                .body(Body::empty())
+                .expect("Couldn't create the directory redirect response"));
+        }
+
+        let mut entries = read_entries(path).await.map_err(|err| {
+            HttpError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Couldn't list the directory: {}", err),
+            )
+        })?;
+
+        let query = parts.uri.query().unwrap_or("");
+        let param = |name: &str| {
+            query
+                .split('&')
+                .filter_map(|pair| pair.split_once('='))
+                .find(|(key, _)| *key == name)
+                .map(|(_, val)| val)
+        };
+        sort_entries(
+            &mut entries,
+            param("sort").unwrap_or("name"),
+            param("order") == Some("desc"),
+        );
+
+        let wants_json = parts
+            .headers
+            .get(header::ACCEPT)
+            .and_then(|val| val.to_str().ok())
+            .map(|val| val.contains("application/json"))
+            .unwrap_or(false)
+            || param("format") == Some("json");
+        let (content_type, body) = if wants_json {
+            ("application/json", render_json(&rel_path, &entries))
+        } else {
+            ("text/html; charset=utf-8", render_html(&rel_path, &entries))
+        };
+
+        let mut resp = Response::new(Body::from(body));
+        resp.headers_mut()
+            .insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+        resp.headers_mut()
+            .insert(header::VARY, HeaderValue::from_static("Accept"));
+        Ok(resp)
+    }
+}
+
+impl Debug for StaticDir {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{{ dir: {:?}, listings: {:?}, guard: {:?} }}",
+            self.dir,
+            self.listings,
+            self.guard.is_some()
+        )
+    }
+}
+
+async fn read_entries(path: PathBuf) -> std::io::Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    let mut dir = tokio::fs::read_dir(path).await?;
+    while let Some(entry) = dir.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') {
+            continue;
+        }
+
+        let metadata = entry.metadata().await?;
+        entries.push(Entry {
+            name,
+            is_dir: metadata.is_dir(),
+            size: if metadata.is_dir() { 0 } else { metadata.len() },
+            modified: metadata.modified().ok(),
+        });
+    }
+    Ok(entries)
+}
+
+fn sort_entries(entries: &mut [Entry], sort: &str, is_desc: bool) {
+    entries.sort_by(|a, b| {
+        let order = match sort {
+            "size" => a.size.cmp(&b.size),
+            "modified" => a.modified.cmp(&b.modified),
+            _ => Ordering::Equal,
+        }
+        .then_with(|| a.name.cmp(&b.name));
+        let order = if is_desc { order.reverse() } else { order };
+        b.is_dir.cmp(&a.is_dir).then(order)
+    });
+}
+
+fn href(entry: &Entry) -> String {
+    let name = utf8_percent_encode(&entry.name, SEGMENT).to_string();
+    if entry.is_dir {
+        format!("{}/", name)
+    } else {
+        name
+    }
+}
+
+fn render_json(rel_path: &str, entries: &[Entry]) -> String {
+    let entries = entries
+        .iter()
+        .map(|entry| {
+            format!(
+                "{{\"name\":{},\"type\":{},\"size\":{},\"modified\":{}}}",
+                json_str(&entry.name),
+                json_str(if entry.is_dir { "directory" } else { "file" }),
+                entry.size,
+                entry
+                    .modified
+                    .map(|modified| json_str(&helpers::http_date(modified)))
+                    .unwrap_or_else(|| "null".to_owned())
+            )
+        })
+        .collect::<Vec<_>>();
+    format!(
+        "{{\"path\":{},\"entries\":[{}]}}",
+        json_str(&format!("/{}", rel_path.trim_start_matches('/'))),
+        entries.join(",")
+    )
+}
+
+fn render_html(rel_path: &str, entries: &[Entry]) -> String {
+    let segments = rel_path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>();
+
+    // Each breadcrumb links up by the number of the segments after it.
+    let mut breadcrumbs = vec![format!("<a href=\"./{}\">/</a>", "../".repeat(segments.len()))];
+    for (idx, segment) in segments.iter().enumerate() {
+        breadcrumbs.push(format!(
+            "<a href=\"./{}\">{}</a>/",
+            "../".repeat(segments.len() - idx - 1),
+            html_escape(segment)
+        ));
+    }
+
+    let rows = entries
+        .iter()
+        .map(|entry| {
+            format!(
+                "<tr><td><a href=\"{}\">{}{}</a></td><td>{}</td><td>{}</td></tr>",
+                html_escape(&href(entry)),
+                html_escape(&entry.name),
+                if entry.is_dir { "/" } else { "" },
+                if entry.is_dir {
+                    "-".to_owned()
+                } else {
+                    entry.size.to_string()
+                },
+                entry.modified.map(helpers::http_date).unwrap_or_default()
+            )
+        })
+        .collect::<String>();
+
+    format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>Index of /{title}</title></head><body>\
+         <h1>Index of {breadcrumbs}</h1><table><thead><tr>\
+         <th><a href=\"?sort=name\">Name</a></th><th><a href=\"?sort=size\">Size</a></th>\
+         <th><a href=\"?sort=modified&amp;order=desc\">Modified</a></th>\
+         </tr></thead><tbody>{rows}</tbody></table></body></html>",
+        title = html_escape(&segments.join("/")),
+        breadcrumbs = breadcrumbs.join(""),
+        rows = rows
+    )
+}
+
+fn not_found() -> HttpError {
+    HttpError::new(StatusCode::NOT_FOUND, "File not found")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, is_dir: bool, size: u64) -> Entry {
+        Entry {
+            name: name.to_owned(),
+            is_dir,
+            size,
+            modified: None,
+        }
+    }
+
+    #[test]
+    fn sorts_and_renders_entries() {
+        let mut entries = vec![
+            entry("b.txt", false, 1),
+            entry("docs", true, 0),
+            entry("a b.txt", false, 20),
+        ];
+        sort_entries(&mut entries, "size", true);
+        assert_eq!(
+            entries.iter().map(|entry| entry.name.as_str()).collect::<Vec<_>>(),
+            ["docs", "a b.txt", "b.txt"]
+        );
+
+        assert_eq!(
+            render_json("docs/", &entries[1..2]),
+            r#"{"path":"/docs/","entries":[{"name":"a b.txt","type":"file","size":20,"modified":null}]}"#
+        );
+
+        let html = render_html("docs/2024/", &entries);
+        assert!(html.contains(
+            "<h1>Index of <a href=\"./../../\">/</a><a href=\"./../\">docs</a>/<a href=\"./\">2024</a>/</h1>"
+        ));
+        assert!(html.contains("<a href=\"a%20b.txt\">a b.txt</a>"));
+        assert!(html.contains("<a href=\"docs/\">docs/</a>"));
+    }
+}
@@ -0,0 +1,55 @@
+//! Helpers to create common responses from the route handlers.
+//!
+//! The file responses, the [`StaticDir`](./struct.StaticDir.html) and the [`Spa`](./struct.Spa.html) read the files
+//! from the disk, so they require the `fs` feature.
+//!
+//! # Examples
+//!
+//! ```
+//! use routerify::{Router, RouteError};
+//! use routerify::responses;
+//! use hyper::Body;
+//!
+//! # fn run() -> Router<Body, RouteError> {
+//! let router = Router::builder()
+//!     .get("/home", |req| async move { Ok(responses::redirect(&req, "/")?) })
+//!     .build()
+//!     .unwrap();
+//! # router
+//! # }
+//! # run();
+//! ```
+
+#[cfg(feature = "codec")]
+pub use codec::{encoded, negotiated};
+#[cfg(feature = "embed")]
+pub use embedded::{embedded, EmbeddedAssets};
+#[cfg(feature = "fs")]
+pub use file::{file, FileResponse};
+#[cfg(feature = "fs")]
+pub use listing::StaticDir;
+pub use precondition::precondition_failed;
+#[cfg(feature = "protobuf")]
+pub use proto::proto;
+pub use ranged::{ranged, RangeSource, RangedResponse};
+pub use redirect::{redirect, redirect_with_status};
+#[cfg(feature = "fs")]
+pub use spa::Spa;
+pub use templates::ResponseTemplates;
+
+#[cfg(feature = "codec")]
+mod codec;
+#[cfg(feature = "embed")]
+mod embedded;
+#[cfg(feature = "fs")]
+mod file;
+#[cfg(feature = "fs")]
+mod listing;
+mod precondition;
+#[cfg(feature = "protobuf")]
+mod proto;
+mod ranged;
+mod redirect;
+#[cfg(feature = "fs")]
+mod spa;
+mod templates;
@@ -0,0 +1,29 @@
+use hyper::header::{self, HeaderValue};
+use hyper::{Body, Response, StatusCode};
+
+/// Creates a `412 Precondition Failed` response with the current entity tag of the resource, if it exists, so the
+/// client can fetch the resource again and retry its update.
+///
+/// Please refer to the [`Preconditions`](../headers/struct.Preconditions.html) for more info.
+///
+/// # Examples
+///
+/// ```
+/// use routerify::responses;
+/// use hyper::StatusCode;
+///
+/// let resp = responses::precondition_failed(Some("\"v2\""));
+/// assert_eq!(resp.status(), StatusCode::PRECONDITION_FAILED);
+/// assert_eq!(resp.headers()["etag"], "\"v2\"");
+/// ```
+pub fn precondition_failed(etag: Option<&str>) -> Response<Body> {
+    let mut resp = Response::new(Body::from(
+        StatusCode::PRECONDITION_FAILED.canonical_reason().unwrap_or_default(),
+    ));
+    *resp.status_mut() = StatusCode::PRECONDITION_FAILED;
+
+    if let Some(etag) = etag.and_then(|etag| HeaderValue::from_str(etag).ok()) {
+        resp.headers_mut().insert(header::ETAG, etag);
+    }
+    resp
+}
@@ -0,0 +1,16 @@
+use crate::constants::PROTOBUF_CONTENT_TYPE;
+use hyper::header::{self, HeaderValue};
+use hyper::{Body, Response};
+
+/// Creates a response with the encoded protobuf message and the `application/x-protobuf` content type. It requires
+/// the `protobuf` feature.
+///
+/// Please refer to the [`RequestBodyExt::proto`](../ext/trait.RequestBodyExt.html#method.proto) method for an example.
+pub fn proto<M: prost::Message>(msg: &M) -> Response<Body> {
+    let bytes = msg.encode_to_vec();
+
+    let mut resp = Response::new(Body::from(bytes));
+    resp.headers_mut()
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static(PROTOBUF_CONTENT_TYPE));
+    resp
+}
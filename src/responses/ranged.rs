@@ -0,0 +1,421 @@
+use crate::helpers;
+use crate::HttpError;
+use hyper::header::{self, HeaderMap, HeaderValue};
+use hyper::{Body, Response, StatusCode};
+use std::io;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, SeekFrom};
+use tokio_util::io::ReaderStream;
+
+// The requests with more ranges get the whole object, so a request can't make the server read it over and over.
+const MAX_RANGES: usize = 16;
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// A seekable source of an object e.g. a file, an in-memory buffer or an adapter of an S3-compatible storage which
+/// fetches the object by ranges.
+///
+/// It's implemented for any `AsyncRead + AsyncSeek` type.
+pub trait RangeSource: AsyncRead + AsyncSeek + Send + Unpin + 'static {}
+
+impl<T: AsyncRead + AsyncSeek + Send + Unpin + 'static> RangeSource for T {}
+
+/// Creates a streaming response of an object of `len` bytes read from the source.
+///
+/// The `Content-Length` and `Accept-Ranges` headers are set. If the request headers are passed by the
+/// [`with_range`](./struct.RangedResponse.html#method.with_range) method, a byte range request is answered with
+/// `206 Partial Content`, and a request of multiple ranges with a `multipart/byteranges` body. The range is ignored
+/// unless the `If-Range` header, if any, matches the entity tag or the modification time of the object.
+///
+/// # Examples
+///
+/// ```
+/// use routerify::{Router, RouteError};
+/// use routerify::responses;
+/// use hyper::Body;
+/// use std::io::Cursor;
+///
+/// # fn run() -> Router<Body, RouteError> {
+/// let router = Router::builder()
+///     .get("/videos/:id", |req| async move {
+///         let video = Cursor::new(vec![0_u8; 4096]);
+///         let resp = responses::ranged(video, 4096)
+///             .content_type("video/mp4")
+///             .etag("\"v1\"")
+///             .with_range(req.headers())
+///             .into_response()
+///             .await?;
+///         Ok(resp)
+///     })
+///     .build()
+///     .unwrap();
+/// # router
+/// # }
+/// # run();
+/// ```
+pub fn ranged<S: RangeSource>(source: S, len: u64) -> RangedResponse<S> {
+    RangedResponse {
+        source,
+        len,
+        content_type: None,
+        etag: None,
+        last_modified: None,
+        range: None,
+        if_range: None,
+    }
+}
+
+/// A builder of a streaming response of a seekable object. It's created by the [`ranged`](./fn.ranged.html) function.
+#[derive(Debug)]
+pub struct RangedResponse<S> {
+    source: S,
+    len: u64,
+    content_type: Option<String>,
+    etag: Option<String>,
+    last_modified: Option<SystemTime>,
+    range: Option<String>,
+    if_range: Option<String>,
+}
+
+impl<S: RangeSource> RangedResponse<S> {
+    /// Sets the `Content-Type` of the object, which is `application/octet-stream` by default.
+    pub fn content_type<T: Into<String>>(mut self, content_type: T) -> Self {
+        self.content_type = Some(content_type.into());
+        self
+    }
+
+    /// Sets the entity tag of the object e.g. `"v1"`, which is sent as the `ETag` header and matched against the
+    /// `If-Range` header.
+    pub fn etag<T: Into<String>>(mut self, etag: T) -> Self {
+        self.etag = Some(etag.into());
+        self
+    }
+
+    /// Sets the modification time of the object, which is sent as the `Last-Modified` header and matched against the
+    /// `If-Range` header.
+    pub fn last_modified(mut self, time: SystemTime) -> Self {
+        self.last_modified = Some(time);
+        self
+    }
+
+    /// Takes the `Range` and the `If-Range` headers from the request headers, if any.
+    pub fn with_range(mut self, headers: &HeaderMap) -> Self {
+        let get = |name| {
+            headers
+                .get(name)
+                .and_then(|val: &HeaderValue| val.to_str().ok())
+                .map(|val| val.to_owned())
+        };
+        self.range = get(header::RANGE);
+        self.if_range = get(header::IF_RANGE);
+        self
+    }
+
+    /// Creates the response.
+    ///
+    /// It fails with an [`HttpError`](../struct.HttpError.html) of status `500 Internal Server Error` if the source
+    /// can't be seeked.
+    pub async fn into_response(mut self) -> Result<Response<Body>, HttpError> {
+        let len = self.len;
+        let content_type = self
+            .content_type
+            .take()
+            .unwrap_or_else(|| "application/octet-stream".to_owned());
+
+        let mut builder = Response::builder().header(header::ACCEPT_RANGES, "bytes");
+        if let Some(ref etag) = self.etag {
+            builder = builder.header(header::ETAG, etag.as_str());
+        }
+        if let Some(last_modified) = self.last_modified {
+            builder = builder.header(header::LAST_MODIFIED, helpers::http_date(last_modified));
+        }
+
+        let ranges = match self.range.as_deref().filter(|_| self.if_range_matches()) {
+            Some(range) => parse_ranges(range, len),
+            None => Ranges::Ignored,
+        };
+
+        match ranges {
+            Ranges::Ignored => {
+                self.source.seek(SeekFrom::Start(0)).await.map_err(io_error)?;
+                builder
+                    .status(StatusCode::OK)
+                    .header(header::CONTENT_TYPE, content_type)
+                    .header(header::CONTENT_LENGTH, HeaderValue::from(len))
+                    .body(Body::wrap_stream(ReaderStream::new(self.source.take(len))))
+                    .map_err(build_error)
+            }
+            Ranges::Unsatisfiable => builder
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(header::CONTENT_RANGE, format!("bytes */{}", len))
+                .body(Body::empty())
+                .map_err(build_error),
+            Ranges::Satisfiable(ranges) if ranges.len() == 1 => {
+                let (start, end) = ranges[0];
+                self.source.seek(SeekFrom::Start(start)).await.map_err(io_error)?;
+                let part_len = end - start + 1;
+
+                builder
+                    .status(StatusCode::PARTIAL_CONTENT)
+                    .header(header::CONTENT_TYPE, content_type)
+                    .header(header::CONTENT_LENGTH, HeaderValue::from(part_len))
+                    .header(header::CONTENT_RANGE, format!("bytes {}-{}/{}", start, end, len))
+                    .body(Body::wrap_stream(ReaderStream::new(self.source.take(part_len))))
+                    .map_err(build_error)
+            }
+            Ranges::Satisfiable(ranges) => {
+                let boundary = helpers::random_hex(16);
+                let parts = ranges
+                    .into_iter()
+                    .map(|(start, end)| {
+                        let head = format!(
+                            "\r\n--{}\r\nContent-Type: {}\r\nContent-Range: bytes {}-{}/{}\r\n\r\n",
+                            boundary, content_type, start, end, len
+                        );
+                        (head, start, end)
+                    })
+                    .collect::<Vec<_>>();
+                let tail = format!("\r\n--{}--\r\n", boundary);
+                let body_len = parts
+                    .iter()
+                    .map(|(head, start, end)| head.len() as u64 + end - start + 1)
+                    .sum::<u64>()
+                    + tail.len() as u64;
+
+                let (mut sender, body) = Body::channel();
+                let mut source = self.source;
+                tokio::spawn(async move {
+                    let mut buf = vec![0_u8; CHUNK_SIZE];
+                    for (head, start, end) in parts {
+                        if sender.send_data(head.into()).await.is_err() {
+                            return;
+                        }
+                        if let Err(err) = copy_range(&mut source, &mut sender, &mut buf, start, end).await {
+                            if err.kind() != io::ErrorKind::BrokenPipe {
+                                sender.abort();
+                            }
+                            return;
+                        }
+                    }
+                    let _ = sender.send_data(tail.into()).await;
+                });
+
+                builder
+                    .status(StatusCode::PARTIAL_CONTENT)
+                    .header(
+                        header::CONTENT_TYPE,
+                        format!("multipart/byteranges; boundary={}", boundary),
+                    )
+                    .header(header::CONTENT_LENGTH, HeaderValue::from(body_len))
+                    .body(body)
+                    .map_err(build_error)
+            }
+        }
+    }
+
+    // The `If-Range` header matches a strong entity tag or the exact modification time of the object.
+    fn if_range_matches(&self) -> bool {
+        let if_range = match self.if_range.as_deref() {
+            Some(if_range) => if_range.trim(),
+            None => return true,
+        };
+
+        if if_range.starts_with('"') || if_range.starts_with("W/") {
+            return matches!(self.etag.as_deref(), Some(etag) if !etag.starts_with("W/") && etag == if_range);
+        }
+
+        match (helpers::parse_http_date(if_range), self.last_modified) {
+            (Some(date), Some(last_modified)) => date == truncate(last_modified),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub(super) enum Range {
+    Satisfiable(u64, u64),
+    Unsatisfiable,
+    Ignored,
+}
+
+#[derive(Debug, PartialEq)]
+enum Ranges {
+    Satisfiable(Vec<(u64, u64)>),
+    Unsatisfiable,
+    Ignored,
+}
+
+fn parse_ranges(val: &str, len: u64) -> Ranges {
+    let specs = match val.trim().strip_prefix("bytes=") {
+        Some(specs) => specs.split(','),
+        None => return Ranges::Ignored,
+    };
+
+    let mut ranges = Vec::new();
+    for spec in specs {
+        match parse_range_spec(spec, len) {
+            Range::Satisfiable(start, end) => ranges.push((start, end)),
+            Range::Unsatisfiable => {}
+            Range::Ignored => return Ranges::Ignored,
+        }
+        if ranges.len() > MAX_RANGES {
+            return Ranges::Ignored;
+        }
+    }
+
+    if ranges.is_empty() {
+        Ranges::Unsatisfiable
+    } else {
+        Ranges::Satisfiable(ranges)
+    }
+}
+
+// Parses a single range of a `bytes` range header e.g. `0-499`, `500-` or `-500`.
+pub(super) fn parse_range_spec(spec: &str, len: u64) -> Range {
+    let (start, end) = match spec.trim().split_once('-') {
+        Some(bounds) => bounds,
+        None => return Range::Ignored,
+    };
+
+    let bounds = match (start.parse::<u64>(), end.parse::<u64>()) {
+        // bytes=-500: the last 500 bytes.
+        (Err(_), Ok(suffix)) if start.is_empty() => {
+            if suffix == 0 {
+                return Range::Unsatisfiable;
+            }
+            (len.saturating_sub(suffix), len.wrapping_sub(1))
+        }
+        // bytes=500-: from the offset to the end.
+        (Ok(start), Err(_)) if end.is_empty() => (start, len.wrapping_sub(1)),
+        (Ok(start), Ok(end)) if start <= end => (start, end.min(len.wrapping_sub(1))),
+        _ => return Range::Ignored,
+    };
+
+    if len == 0 || bounds.0 >= len {
+        Range::Unsatisfiable
+    } else {
+        Range::Satisfiable(bounds.0, bounds.1)
+    }
+}
+
+async fn copy_range<S: RangeSource>(
+    source: &mut S,
+    sender: &mut hyper::body::Sender,
+    buf: &mut [u8],
+    start: u64,
+    end: u64,
+) -> io::Result<()> {
+    source.seek(SeekFrom::Start(start)).await?;
+    let mut remaining = end - start + 1;
+    while remaining > 0 {
+        let max = remaining.min(buf.len() as u64) as usize;
+        let n = source.read(&mut buf[..max]).await?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        sender
+            .send_data(hyper::body::Bytes::copy_from_slice(&buf[..n]))
+            .await
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+        remaining -= n as u64;
+    }
+    Ok(())
+}
+
+fn truncate(time: SystemTime) -> SystemTime {
+    let secs = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+fn io_error(err: io::Error) -> HttpError {
+    HttpError::new(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        format!("Could not read the object: {}", err),
+    )
+}
+
+fn build_error(err: http::Error) -> HttpError {
+    HttpError::new(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        format!("Could not create the ranged response: {}", err),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn request(range: &'static str, if_range: Option<&'static str>) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::RANGE, HeaderValue::from_static(range));
+        if let Some(if_range) = if_range {
+            headers.insert(header::IF_RANGE, HeaderValue::from_static(if_range));
+        }
+        headers
+    }
+
+    async fn send(headers: &HeaderMap) -> Response<Body> {
+        ranged(Cursor::new(b"hello world".to_vec()), 11)
+            .content_type("text/plain")
+            .etag("\"v1\"")
+            .with_range(headers)
+            .into_response()
+            .await
+            .unwrap()
+    }
+
+    #[test]
+    fn parses_multiple_byte_ranges() {
+        assert_eq!(
+            parse_ranges("bytes=0-1, 4-5", 10),
+            Ranges::Satisfiable(vec![(0, 1), (4, 5)])
+        );
+        assert_eq!(parse_ranges("bytes=0-1,20-30", 10), Ranges::Satisfiable(vec![(0, 1)]));
+        assert_eq!(parse_ranges("bytes=20-30", 10), Ranges::Unsatisfiable);
+        assert_eq!(parse_ranges("bytes=0-1,x", 10), Ranges::Ignored);
+        assert_eq!(
+            parse_ranges(&format!("bytes={}", vec!["0-0"; 17].join(",")), 10),
+            Ranges::Ignored
+        );
+    }
+
+    #[tokio::test]
+    async fn streams_ranges() {
+        let resp = send(&HeaderMap::new()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()[header::CONTENT_LENGTH], "11");
+        assert_eq!(hyper::body::to_bytes(resp.into_body()).await.unwrap(), "hello world");
+
+        let resp = send(&request("bytes=-5", Some("\"v1\""))).await;
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(resp.headers()[header::CONTENT_RANGE], "bytes 6-10/11");
+        assert_eq!(hyper::body::to_bytes(resp.into_body()).await.unwrap(), "world");
+
+        let resp = send(&request("bytes=-5", Some("\"v0\""))).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let resp = send(&request("bytes=20-", None)).await;
+        assert_eq!(resp.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+
+        let resp = send(&request("bytes=0-1,6-7", None)).await;
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+        let content_type = resp.headers()[header::CONTENT_TYPE].to_str().unwrap().to_owned();
+        let boundary = content_type.strip_prefix("multipart/byteranges; boundary=").unwrap();
+        let len = resp.headers()[header::CONTENT_LENGTH]
+            .to_str()
+            .unwrap()
+            .parse::<usize>()
+            .unwrap();
+        let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        assert_eq!(body.len(), len);
+        assert_eq!(
+            body,
+            format!(
+                "\r\n--{b}\r\nContent-Type: text/plain\r\nContent-Range: bytes 0-1/11\r\n\r\nhe\
+                 \r\n--{b}\r\nContent-Type: text/plain\r\nContent-Range: bytes 6-7/11\r\n\r\nwo\
+                 \r\n--{b}--\r\n",
+                b = boundary
+            )
+        );
+    }
+}
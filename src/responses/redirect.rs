@@ -0,0 +1,53 @@
+use crate::ext::RequestExt;
+use crate::Error;
+use hyper::header::{self, HeaderValue};
+use hyper::{Body, Response, StatusCode};
+
+/// Creates a `302 Found` response which redirects to the location.
+///
+/// A root relative location is prefixed with the [`base_path`](../ext/trait.RequestExt.html#tymethod.base_path) of the
+/// request, so `/login` redirects to `/myapp/login` when the app is served under the `/myapp` base path.
+///
+/// # Examples
+///
+/// ```
+/// use routerify::{Router, RouteError};
+/// use routerify::responses;
+/// use hyper::Body;
+///
+/// # fn run() -> Router<Body, RouteError> {
+/// let router = Router::builder()
+///     .base_path("/myapp")
+///     // Redirects to `/myapp/login`.
+///     .get("/account", |req| async move { responses::redirect(&req, "/login") })
+///     .build()
+///     .unwrap();
+/// # router
+/// # }
+/// # run();
+/// ```
+pub fn redirect<R: RequestExt>(req: &R, location: &str) -> crate::Result<Response<Body>> {
+    redirect_with_status(req, StatusCode::FOUND, location)
+}
+
+/// Creates a redirect response with the status code e.g. `308 Permanent Redirect`, which redirects to the location.
+///
+/// Please refer to the [`redirect`](./fn.redirect.html) function for more info.
+pub fn redirect_with_status<R: RequestExt>(
+    req: &R,
+    status: StatusCode,
+    location: &str,
+) -> crate::Result<Response<Body>> {
+    if !status.is_redirection() {
+        return Err(Error::new(format!("The status code is not a redirect: {}", status)).into());
+    }
+
+    let location = HeaderValue::from_str(&req.url_for(location))
+        .map_err(|e| Error::new(format!("Couldn't create the Location header: {}", e)))?;
+
+    Response::builder()
+        .status(status)
+        .header(header::LOCATION, location)
+        .body(Body::empty())
+        .map_err(|e| Error::wrap(e).into())
+}
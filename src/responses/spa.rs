@@ -0,0 +1,86 @@
+use crate::helpers;
+use crate::HttpError;
+use hyper::header::{self, HeaderValue};
+use hyper::{Body, Request, Response, StatusCode};
+use std::path::PathBuf;
+
+/// The configuration of a single page application, whose static assets are served from a directory and whose client
+/// side routes are all answered with its index file.
+///
+/// It's served by the [`spa`](../struct.RouterBuilder.html#method.spa) routes. A request for a file of the directory
+/// gets the file, any other path under the prefix gets the index file with `Cache-Control: no-cache`, so a new
+/// deployment is picked up, except the paths under the excluded prefixes e.g. of an API, which fail with an
+/// [`HttpError`](../struct.HttpError.html) of status `404 Not Found` instead of the HTML page.
+///
+/// # Examples
+///
+/// ```
+/// use routerify::{Router, RouteError};
+/// use routerify::responses::Spa;
+/// use hyper::{Response, Body};
+///
+/// # fn run() -> Router<Body, RouteError> {
+/// let router = Router::builder()
+///     .get("/app/api/users", |_| async move { Ok(Response::new(Body::from("[]"))) })
+///     .spa("/app", Spa::new("dist").exclude("/app/api"))
+///     .build()
+///     .unwrap();
+/// # router
+/// # }
+/// # run();
+/// ```
+#[derive(Debug, Clone)]
+pub struct Spa {
+    dir: PathBuf,
+    index: String,
+    excluded: Vec<String>,
+}
+
+impl Spa {
+    /// Creates a new configuration which serves the files of the directory with its `index.html` file as the fallback.
+    pub fn new<D: Into<PathBuf>>(dir: D) -> Self {
+        Spa {
+            dir: dir.into(),
+            index: "index.html".to_owned(),
+            excluded: Vec::new(),
+        }
+    }
+
+    /// Sets the name of the index file in the directory. Defaults to `index.html`.
+    pub fn index<I: Into<String>>(mut self, index: I) -> Self {
+        self.index = index.into();
+        self
+    }
+
+    /// Excludes the request paths under the prefix, e.g. `/app/api`, from the fallback to the index file.
+    pub fn exclude<P: Into<String>>(mut self, prefix: P) -> Self {
+        self.excluded.push(prefix.into().trim_end_matches('/').to_owned());
+        self
+    }
+
+    /// Serves the file at the path matched by the `*` segment of the route, or the index file if there is none.
+    pub async fn handle(&self, req: &Request<Body>) -> Result<Response<Body>, HttpError> {
+        let path = helpers::wildcard_path(req);
+        let req_path = req.uri().path();
+        let is_excluded = self.excluded.iter().any(|prefix| {
+            req_path == prefix || (req_path.starts_with(prefix.as_str()) && req_path[prefix.len()..].starts_with('/'))
+        });
+        if is_excluded || path.split('/').any(|segment| segment == "..") {
+            return Err(HttpError::new(StatusCode::NOT_FOUND, "Not Found"));
+        }
+
+        let path = self.dir.join(path.trim_start_matches('/'));
+        let is_file = tokio::fs::metadata(&path)
+            .await
+            .map(|metadata| metadata.is_file())
+            .unwrap_or(false);
+        if is_file {
+            return super::file(path).with_range(req.headers()).into_response().await;
+        }
+
+        let mut resp = super::file(self.dir.join(&self.index)).into_response().await?;
+        resp.headers_mut()
+            .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+        Ok(resp)
+    }
+}
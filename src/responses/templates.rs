@@ -0,0 +1,106 @@
+use crate::helpers;
+use hyper::header::{self, HeaderValue};
+use hyper::{Body, Response, StatusCode};
+use std::fmt::{self, Debug, Formatter};
+use std::sync::Arc;
+
+type Render = dyn Fn(StatusCode, &str) -> Response<Body> + Send + Sync;
+
+/// The templates of the responses generated by the default `404` route and the default error handler, e.g. the
+/// `413 Payload Too Large` and the `500 Internal Server Error` responses.
+///
+/// The templates are shared as a [data](../struct.RouterBuilder.html#method.data) entry, so a scope can render its
+/// errors differently from the rest of the app. The templates of the innermost scope of the request path are used. They
+/// have no effect if the body type is not `hyper::Body` or the
+/// [`problem_details`](../struct.RouterBuilder.html#method.problem_details) option is enabled, and the error handler
+/// doesn't use them if the detailed error pages are enabled by the
+/// [`debug_errors`](../struct.RouterBuilder.html#method.debug_errors) option or an error handler is added to the router.
+///
+/// # Examples
+///
+/// ```
+/// use routerify::Router;
+/// use routerify::responses::ResponseTemplates;
+/// use hyper::{Response, Body};
+///
+/// # fn run() -> Router<Body, routerify::Error> {
+/// let api = Router::builder()
+///     // Responds with `{"error":"Not Found"}` to the unknown API paths.
+///     .data(ResponseTemplates::json())
+///     .get("/users", |_| async move { Ok(Response::new(Body::from("[]"))) })
+///     .build()
+///     .unwrap();
+///
+/// let router = Router::builder()
+///     .data(ResponseTemplates::html(|status, message| {
+///         format!("<h1>{}</h1><p>{}</p><a href=\"/\">Home</a>", status.as_u16(), message)
+///     }))
+///     .scope("/api", api)
+///     .build()
+///     .unwrap();
+/// # router
+/// # }
+/// # run();
+/// ```
+#[derive(Clone)]
+pub struct ResponseTemplates {
+    render: Arc<Render>,
+}
+
+impl ResponseTemplates {
+    /// Creates the templates from a function which renders the response of a status code and a message e.g.
+    /// `Not Found` or the error message.
+    pub fn new<F>(render: F) -> Self
+    where
+        F: Fn(StatusCode, &str) -> Response<Body> + Send + Sync + 'static,
+    {
+        ResponseTemplates {
+            render: Arc::new(render),
+        }
+    }
+
+    /// Creates the templates which respond with a `{"error": "<message>"}` JSON body.
+    pub fn json() -> Self {
+        ResponseTemplates::new(|status, message| {
+            respond(
+                status,
+                "application/json",
+                format!("{{\"error\":{}}}", helpers::json_str(message)),
+            )
+        })
+    }
+
+    /// Creates the templates which respond with an HTML page, rendered by a function from the status code and the HTML
+    /// escaped message.
+    pub fn html<F>(render: F) -> Self
+    where
+        F: Fn(StatusCode, &str) -> String + Send + Sync + 'static,
+    {
+        ResponseTemplates::new(move |status, message| {
+            respond(
+                status,
+                "text/html; charset=utf-8",
+                render(status, &helpers::html_escape(message)),
+            )
+        })
+    }
+
+    /// Renders the response of a status code and a message.
+    pub fn render(&self, status: StatusCode, message: &str) -> Response<Body> {
+        (self.render)(status, message)
+    }
+}
+
+impl Debug for ResponseTemplates {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "ResponseTemplates")
+    }
+}
+
+fn respond(status: StatusCode, content_type: &'static str, body: String) -> Response<Body> {
+    let mut res = Response::new(Body::from(body));
+    *res.status_mut() = status;
+    res.headers_mut()
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    res
+}
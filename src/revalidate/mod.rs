@@ -0,0 +1,174 @@
+//! Conditional `GET` requests for the dynamic content, answered before the expensive handler runs.
+//!
+//! A [`Revalidator`](./trait.Revalidator.html) is a cheap check of a request, e.g. a lookup of the version of a
+//! document, which returns the current [`Validator`](./struct.Validator.html) of the representation, i.e. its entity
+//! tag and its modification time. It's attached to the routes at a path by the
+//! [`RouterBuilder::revalidate`](../struct.RouterBuilder.html#method.revalidate) method, and it runs after the guards
+//! of the route for the `GET` and the `HEAD` requests only.
+//!
+//! If the `If-None-Match` or the `If-Modified-Since` header of the request matches the validator, the request is
+//! answered with `304 Not Modified` and the validator headers without calling the handler. Otherwise the handler runs,
+//! and the `ETag` and the `Last-Modified` headers of the validator are added to its successful response unless it sets
+//! them.
+//!
+//! # Examples
+//!
+//! ```
+//! use routerify::Router;
+//! use routerify::prelude::*;
+//! use routerify::revalidate::Validator;
+//! use http::request::Parts;
+//! use hyper::{Response, Body};
+//! use std::convert::Infallible;
+//!
+//! # fn version_of(id: &str) -> Option<u64> { Some(7) }
+//! # fn run() -> Router<Body, Infallible> {
+//! let router = Router::builder()
+//!     .get("/documents/:id", |_| async move {
+//!         // Render the document, which is expensive.
+//!         Ok(Response::new(Body::from("A document")))
+//!     })
+//!     .revalidate("/documents/:id", |req: &Parts| {
+//!         let version = version_of(req.param("id")?)?;
+//!         Some(Validator::new().with_etag(format!("\"v{}\"", version)))
+//!     })
+//!     .build()
+//!     .unwrap();
+//! # router
+//! # }
+//! # run();
+//! ```
+
+use crate::headers::Preconditions;
+use crate::helpers;
+use http::request::Parts;
+use hyper::header::{self, HeaderMap, HeaderValue};
+use hyper::{Body, Response, StatusCode};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// The future returned by the [`Revalidator::revalidate`](./trait.Revalidator.html#tymethod.revalidate) method.
+pub type RevalidateFuture<'a> = Pin<Box<dyn Future<Output = Option<Validator>> + Send + 'a>>;
+
+/// The entity tag and the modification time of the current representation of a resource.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Validator {
+    etag: Option<String>,
+    last_modified: Option<SystemTime>,
+}
+
+impl Validator {
+    /// Creates a validator without an entity tag or a modification time.
+    pub fn new() -> Validator {
+        Validator::default()
+    }
+
+    /// Sets the entity tag with its quotes e.g. `"v2"`, or a weak one e.g. `W/"v2"`.
+    pub fn with_etag<T: Into<String>>(mut self, etag: T) -> Self {
+        self.etag = Some(etag.into());
+        self
+    }
+
+    /// Sets the modification time.
+    pub fn with_last_modified(mut self, last_modified: SystemTime) -> Self {
+        self.last_modified = Some(last_modified);
+        self
+    }
+
+    /// Returns the entity tag.
+    pub fn etag(&self) -> Option<&str> {
+        self.etag.as_deref()
+    }
+
+    /// Returns the modification time.
+    pub fn last_modified(&self) -> Option<SystemTime> {
+        self.last_modified
+    }
+
+    /// Checks if the client has the current representation, by the `If-None-Match` and the `If-Modified-Since`
+    /// headers of its request.
+    pub fn is_fresh(&self, headers: &HeaderMap) -> bool {
+        Preconditions::from_headers(headers).is_not_modified(self.etag(), self.last_modified)
+    }
+
+    // Adds the `ETag` and the `Last-Modified` headers which aren't set yet.
+    pub(crate) fn apply_headers(&self, headers: &mut HeaderMap) {
+        if let Some(val) = self.etag.as_deref().and_then(|etag| HeaderValue::from_str(etag).ok()) {
+            headers.entry(header::ETAG).or_insert(val);
+        }
+        if let Some(val) = self
+            .last_modified
+            .and_then(|time| HeaderValue::from_str(&helpers::http_date(time)).ok())
+        {
+            headers.entry(header::LAST_MODIFIED).or_insert(val);
+        }
+    }
+}
+
+/// A cheap check of a conditional request which returns the current validator of the representation, or `None` to
+/// let the handler answer the request.
+///
+/// It's implemented for the synchronous functions of the form `Fn(&Parts) -> Option<Validator>`. Please refer to the
+/// [module](./index.html) documentation for more info.
+pub trait Revalidator: Send + Sync + 'static {
+    /// Returns the validator of the current representation of the requested resource.
+    fn revalidate<'a>(&'a self, req: &'a Parts) -> RevalidateFuture<'a>;
+}
+
+impl<F> Revalidator for F
+where
+    F: Fn(&Parts) -> Option<Validator> + Send + Sync + 'static,
+{
+    fn revalidate<'a>(&'a self, req: &'a Parts) -> RevalidateFuture<'a> {
+        let validator = self(req);
+        Box::pin(async move { validator })
+    }
+}
+
+// A revalidator attached to a route, with the builder of the `304 Not Modified` responses of its body type.
+pub(crate) struct RouteRevalidator<B> {
+    pub(crate) revalidator: Arc<dyn Revalidator>,
+    pub(crate) not_modified: fn(&Validator) -> Response<B>,
+}
+
+impl<B> Clone for RouteRevalidator<B> {
+    fn clone(&self) -> Self {
+        RouteRevalidator {
+            revalidator: self.revalidator.clone(),
+            not_modified: self.not_modified,
+        }
+    }
+}
+
+pub(crate) fn not_modified(validator: &Validator) -> Response<Body> {
+    let mut res = Response::new(Body::empty());
+    *res.status_mut() = StatusCode::NOT_MODIFIED;
+    validator.apply_headers(res.headers_mut());
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn answers_not_modified() {
+        let validator = Validator::new()
+            .with_etag("\"v7\"")
+            .with_last_modified(UNIX_EPOCH + Duration::from_secs(784_111_777));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, HeaderValue::from_static("\"v6\", \"v7\""));
+        assert!(validator.is_fresh(&headers));
+        assert!(!Validator::new().with_etag("\"v8\"").is_fresh(&headers));
+        assert!(!validator.is_fresh(&HeaderMap::new()));
+
+        let res = not_modified(&validator);
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(res.headers()[header::ETAG], "\"v7\"");
+        assert_eq!(res.headers()[header::LAST_MODIFIED], "Sun, 06 Nov 1994 08:49:37 GMT");
+    }
+}
@@ -0,0 +1,401 @@
+use super::{check_param_guards, generate_route_params, ParamGuard, Route};
+use crate::helpers;
+#[cfg(feature = "sample-paths")]
+use crate::regex_generator::{split_path_parts, PathPart};
+use hyper::Method;
+#[cfg(feature = "sample-paths")]
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+#[cfg(feature = "sample-paths")]
+use rand_core::RngCore;
+use regex::Regex;
+#[cfg(feature = "sample-paths")]
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Formatter};
+use std::sync::Arc;
+
+/// Describes a route of a router.
+///
+/// It's returned by the [`Router::routes`](./struct.Router.html#method.routes) method, and it matches the request paths
+/// the same way as the router does, so it can be used to test the route table without serving it. With the
+/// `sample-paths` feature, it generates the request paths which do and don't match the route to property-test the
+/// handlers and the guards across the route pattern.
+#[derive(Clone)]
+pub struct RouteInfo {
+    pub(crate) path: String,
+    pub(crate) methods: Vec<Method>,
+    pub(crate) params: Vec<String>,
+    regex: Regex,
+    is_raw_regex: bool,
+    param_defaults: Vec<(String, String)>,
+    param_guards: Vec<ParamGuard>,
+    base_path: Option<Arc<str>>,
+}
+
+impl RouteInfo {
+    pub(crate) fn new<B, E>(route: &Route<B, E>, base_path: Option<Arc<str>>) -> RouteInfo {
+        RouteInfo {
+            path: route.path.clone(),
+            methods: route.methods.clone(),
+            params: route.route_params.clone(),
+            regex: route.regex.clone(),
+            is_raw_regex: route.raw_regex.is_some(),
+            param_defaults: route.param_defaults.clone(),
+            param_guards: route.param_guards.clone(),
+            base_path,
+        }
+    }
+
+    /// Returns the path of the route including the paths of the scopes it is mounted on, or its regex pattern if it's
+    /// defined by a [raw regex](./struct.RouterBuilder.html#method.get_regex).
+    pub fn path(&self) -> &str {
+        self.path.as_str()
+    }
+
+    /// Returns the methods of the route.
+    pub fn methods(&self) -> &[Method] {
+        self.methods.as_slice()
+    }
+
+    /// Returns the names of the route params, where a `*` segment is named `*`.
+    pub fn params(&self) -> &[String] {
+        self.params.as_slice()
+    }
+
+    /// Returns `true` if the route is defined by a raw regex pattern instead of the path syntax.
+    pub fn is_raw_regex(&self) -> bool {
+        self.is_raw_regex
+    }
+
+    /// Checks if a percent-encoded request path, including the base path of the router, matches the route and its
+    /// [typed params](./struct.RouterBuilder.html#method.typed_param). The methods aren't checked.
+    pub fn is_match(&self, path: &str) -> bool {
+        let target_path = match helpers::route_target_path(path, self.base_path.as_deref()) {
+            Ok((target_path, _)) => target_path,
+            Err(_) => return false,
+        };
+
+        if !self.regex.is_match(&target_path) {
+            return false;
+        }
+        let route_params = generate_route_params(
+            &self.regex,
+            &self.params,
+            self.is_raw_regex,
+            &self.param_defaults,
+            &target_path,
+        );
+        check_param_guards(&self.param_guards, &route_params)
+    }
+}
+
+impl Debug for RouteInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{{ path: {:?}, methods: {:?}, params: {:?} }}",
+            self.path, self.methods, self.params
+        )
+    }
+}
+
+#[cfg(feature = "sample-paths")]
+const SEGMENT: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
+#[cfg(feature = "sample-paths")]
+const LITERAL: &AsciiSet = &SEGMENT.remove(b'/');
+
+// The characters of the generated param values, including some which have to be percent-encoded.
+#[cfg(feature = "sample-paths")]
+const PARAM_CHARS: &[char] = &[
+    'a', 'b', 'c', 'x', 'y', 'z', 'A', 'Z', '0', '1', '7', '9', '-', '_', '~', ' ', '%', '+', 'é', '日',
+];
+
+// The number of the paths of each kind which are generated by a call.
+#[cfg(feature = "sample-paths")]
+const SAMPLES: usize = 4;
+
+/// The request paths generated by the [`RouteInfo::sample_paths`](./struct.RouteInfo.html#method.sample_paths) method.
+///
+/// The paths are percent-encoded and include the base path of the router. It requires the `sample-paths` feature.
+#[cfg(feature = "sample-paths")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SamplePaths {
+    /// The paths which match the route.
+    pub matching: Vec<String>,
+    /// The paths which are close to the matching ones but don't match the route.
+    pub non_matching: Vec<String>,
+}
+
+#[cfg(feature = "sample-paths")]
+type Strategy = Arc<dyn Fn(&mut dyn RngCore) -> String + Send + Sync + 'static>;
+
+/// The generators of the route param values for the
+/// [`RouteInfo::sample_paths_with`](./struct.RouteInfo.html#method.sample_paths_with) method. It requires the
+/// `sample-paths` feature.
+///
+/// The values of the params without a generator are random strings of one to eight characters, some of which need to be
+/// percent-encoded, and the `*` segments are up to three such segments. The values are percent-encoded in the paths.
+#[cfg(feature = "sample-paths")]
+#[derive(Clone, Default)]
+pub struct ParamStrategies {
+    strategies: HashMap<String, Strategy>,
+}
+
+#[cfg(feature = "sample-paths")]
+impl ParamStrategies {
+    /// Creates an empty set of generators.
+    pub fn new() -> Self {
+        ParamStrategies::default()
+    }
+
+    /// Sets the generator of the values of a param, where the `*` segments are named `*`.
+    pub fn param<N, F>(mut self, name: N, strategy: F) -> Self
+    where
+        N: Into<String>,
+        F: Fn(&mut dyn RngCore) -> String + Send + Sync + 'static,
+    {
+        self.strategies.insert(name.into(), Arc::new(strategy));
+        self
+    }
+
+    fn generate(&self, name: &str, rng: &mut dyn RngCore) -> String {
+        if let Some(strategy) = self.strategies.get(name) {
+            return strategy(rng);
+        }
+
+        let segment = |rng: &mut dyn RngCore| {
+            let len = 1 + rng.next_u32() as usize % 8;
+            let value = (0..len)
+                .map(|_| PARAM_CHARS[rng.next_u32() as usize % PARAM_CHARS.len()])
+                .collect::<String>();
+            utf8_percent_encode(&value, SEGMENT).to_string()
+        };
+        match name {
+            "*" => (0..rng.next_u32() % 4)
+                .map(|_| segment(rng))
+                .collect::<Vec<_>>()
+                .join("/"),
+            _ => segment(rng),
+        }
+    }
+}
+
+#[cfg(feature = "sample-paths")]
+impl Debug for ParamStrategies {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut names = self.strategies.keys().collect::<Vec<_>>();
+        names.sort();
+        write!(f, "{{ params: {:?} }}", names)
+    }
+}
+
+#[cfg(feature = "sample-paths")]
+impl RouteInfo {
+    /// Generates up to four request paths which match the route, with random param values, and up to four which don't
+    /// match it, by adding, removing and altering the segments of the matching ones. It requires the `sample-paths`
+    /// feature.
+    ///
+    /// Each generated path is checked with the [`is_match`](#method.is_match) method, so the values which the typed
+    /// params reject end up in the non-matching paths. No paths are generated for the raw regex routes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::Router;
+    /// use hyper::{Response, Body};
+    /// use rand::rngs::StdRng;
+    /// use rand::{RngCore, SeedableRng};
+    /// use std::convert::Infallible;
+    ///
+    /// # fn run() {
+    /// let router: Router<Body, Infallible> = Router::builder()
+    ///     .get("/users/:id/posts/:page?", |_| async move { Ok(Response::new(Body::from("Posts"))) })
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// // Any `RngCore` works, e.g. the `TestRng` of proptest.
+    /// let mut rng = StdRng::seed_from_u64(42);
+    /// for route in router.routes() {
+    ///     let samples = route.sample_paths(&mut rng);
+    ///     for path in samples.matching {
+    ///         assert!(route.is_match(&path));
+    ///     }
+    ///     for path in samples.non_matching {
+    ///         assert!(!route.is_match(&path));
+    ///     }
+    /// }
+    /// # }
+    /// # run();
+    /// ```
+    pub fn sample_paths<R: RngCore>(&self, rng: &mut R) -> SamplePaths {
+        self.sample_paths_with(rng, &ParamStrategies::new())
+    }
+
+    /// Generates the request paths like the [`sample_paths`](#method.sample_paths) method, with the param values from
+    /// the strategies. The generated values are expected to be percent-encoded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::{ParamStrategies, Router};
+    /// use hyper::{Response, Body};
+    /// use rand::rngs::StdRng;
+    /// use rand::{RngCore, SeedableRng};
+    /// use std::convert::Infallible;
+    ///
+    /// # fn run() {
+    /// let router: Router<Body, Infallible> = Router::builder()
+    ///     .typed_param::<u32, _>("id")
+    ///     .get("/users/:id", |_| async move { Ok(Response::new(Body::from("User"))) })
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// let strategies = ParamStrategies::new().param("id", |rng| (rng.next_u32() % 1000).to_string());
+    /// let mut rng = StdRng::seed_from_u64(42);
+    /// let samples = router.routes()[0].sample_paths_with(&mut rng, &strategies);
+    /// assert!(!samples.matching.is_empty());
+    /// # }
+    /// # run();
+    /// ```
+    pub fn sample_paths_with<R: RngCore>(&self, rng: &mut R, strategies: &ParamStrategies) -> SamplePaths {
+        let mut samples = SamplePaths::default();
+        if self.is_raw_regex {
+            return samples;
+        }
+
+        let mut generated = Vec::new();
+        for _ in 0..SAMPLES * 4 {
+            let path = self.generate_path(rng, strategies);
+            if self.is_match(&path) {
+                push_unique(&mut samples.matching, path.clone());
+            } else {
+                push_unique(&mut samples.non_matching, path.clone());
+            }
+            generated.push(path);
+            if samples.matching.len() >= SAMPLES {
+                break;
+            }
+        }
+
+        for path in generated.iter() {
+            for candidate in self.mutate_path(path, rng) {
+                if !self.is_match(&candidate) {
+                    push_unique(&mut samples.non_matching, candidate);
+                }
+            }
+        }
+
+        samples.matching.truncate(SAMPLES);
+        samples.non_matching.truncate(SAMPLES);
+        samples
+    }
+
+    fn generate_path<R: RngCore>(&self, rng: &mut R, strategies: &ParamStrategies) -> String {
+        let mut path = self.base_path.as_deref().unwrap_or("").to_owned();
+        // An omitted optional param takes its trailing slash with it.
+        let mut skip_slash = false;
+
+        for part in split_path_parts(&self.path) {
+            match part {
+                PathPart::Literal(literal) => {
+                    let literal = match skip_slash {
+                        true => literal.strip_prefix('/').unwrap_or(literal),
+                        false => literal,
+                    };
+                    path.extend(utf8_percent_encode(literal, LITERAL));
+                    skip_slash = false;
+                }
+                PathPart::Param { optional: true, .. } if rng.next_u32() & 1 == 0 => skip_slash = true,
+                PathPart::Param { name, .. } => path.push_str(&strategies.generate(name, rng)),
+                PathPart::Wildcard => path.push_str(&strategies.generate("*", rng)),
+            }
+        }
+
+        trim_trailing_slash(path)
+    }
+
+    // Returns the paths with a segment added, the last segment removed and a character inserted into the literal part
+    // of the route path.
+    fn mutate_path<R: RngCore>(&self, path: &str, rng: &mut R) -> Vec<String> {
+        let base_len = self.base_path.as_deref().map(str::len).unwrap_or(0);
+        let segment = PARAM_CHARS[rng.next_u32() as usize % 10];
+        let mut mutations = vec![format!("{}/{}", path.trim_end_matches('/'), segment)];
+
+        if let Some(idx) = path.rfind('/').filter(|idx| *idx > base_len) {
+            mutations.push(path[..idx].to_owned());
+        }
+
+        let literal_len = self.path.find([':', '*']).unwrap_or(self.path.len());
+        if literal_len > 1 {
+            let idx = base_len + 1 + rng.next_u32() as usize % (literal_len - 1);
+            if path.is_char_boundary(idx) {
+                let mut mutation = path.to_owned();
+                mutation.insert(idx, segment);
+                mutations.push(mutation);
+            }
+        }
+
+        mutations.into_iter().map(trim_trailing_slash).collect()
+    }
+}
+
+#[cfg(feature = "sample-paths")]
+fn trim_trailing_slash(mut path: String) -> String {
+    while path.len() > 1 && path.ends_with('/') {
+        path.pop();
+    }
+    if path.is_empty() {
+        path.push('/');
+    }
+    path
+}
+
+#[cfg(feature = "sample-paths")]
+fn push_unique(paths: &mut Vec<String>, path: String) {
+    if !paths.contains(&path) {
+        paths.push(path);
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "sample-paths")]
+mod tests {
+    use crate::Router;
+    use hyper::{Body, Response};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::convert::Infallible;
+
+    #[test]
+    fn samples_matching_and_non_matching_paths() {
+        let router: Router<Body, Infallible> = Router::builder()
+            .base_path("/api")
+            .typed_param::<u32, _>("page")
+            .get("/", |_| async move { Ok(Response::new(Body::empty())) })
+            .get("/users/:id/posts/:page?", |_| async move {
+                Ok(Response::new(Body::empty()))
+            })
+            .get("/files/:name.:ext", |_| async move { Ok(Response::new(Body::empty())) })
+            .get("/static/*", |_| async move { Ok(Response::new(Body::empty())) })
+            .get_regex(
+                r"/v(?P<version>\d+)/",
+                |_| async move { Ok(Response::new(Body::empty())) },
+            )
+            .build()
+            .unwrap();
+
+        let mut rng = StdRng::seed_from_u64(7);
+        for route in router.routes() {
+            for _ in 0..32 {
+                let samples = route.sample_paths(&mut rng);
+                assert_eq!(samples.matching.is_empty(), route.is_raw_regex());
+                for path in samples.matching.iter() {
+                    assert!(path.starts_with("/api"), "{}", path);
+                    assert!(route.is_match(path), "{} {}", route.path(), path);
+                }
+                for path in samples.non_matching.iter() {
+                    assert!(!route.is_match(path), "{} {}", route.path(), path);
+                }
+            }
+        }
+    }
+}
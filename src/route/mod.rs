@@ -1,12 +1,24 @@
+use crate::codegen::RouteTypes;
+use crate::data_map::SharedDataMap;
+use crate::error::into_route_error;
+use crate::guard::{Guard, GuardOutcome};
 use crate::helpers;
-use crate::regex_generator::generate_exact_match_regex;
-use crate::types::{RequestMeta, RouteParams};
+use crate::regex_generator::{generate_exact_match_regex, generate_param_defaults, generate_raw_regex};
+use crate::revalidate::RouteRevalidator;
+use crate::types::{Deprecation, FromParam, RequestMemory, RequestMeta, RouteParams};
 use crate::Error;
 use hyper::{body::HttpBody, Method, Request, Response};
 use regex::Regex;
 use std::fmt::{self, Debug, Formatter};
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::Arc;
+
+pub use self::info::RouteInfo;
+#[cfg(feature = "sample-paths")]
+pub use self::info::{ParamStrategies, SamplePaths};
+
+mod info;
 
 type Handler<B, E> = Box<dyn Fn(Request<hyper::Body>) -> HandlerReturn<B, E> + Send + Sync + 'static>;
 type HandlerReturn<B, E> = Box<dyn Future<Output = Result<Response<B>, E>> + Send + 'static>;
@@ -46,13 +58,68 @@ type HandlerReturn<B, E> = Box<dyn Future<Output = Result<Response<B>, E>> + Sen
 pub struct Route<B, E> {
     pub(crate) path: String,
     pub(crate) regex: Regex,
-    route_params: Vec<String>,
+    pub(crate) route_params: Vec<String>,
+    // Default values of the optional route params which are used when the segment is missing.
+    param_defaults: Vec<(String, String)>,
     // Make it an option so that when a router is used to scope in another router,
     // It can be extracted out by 'opt.take()' without taking the whole router's ownership.
     pub(crate) handler: Option<Handler<B, E>>,
     pub(crate) methods: Vec<Method>,
     // Scope depth with regards to the top level router.
     pub(crate) scope_depth: u32,
+    // Set if the route is defined by a raw regex pattern instead of the path syntax.
+    pub(crate) raw_regex: Option<RawRegex>,
+    // The route only matches if all of these params can be converted into their types.
+    pub(crate) param_guards: Vec<ParamGuard>,
+    // The names of the middlewares which are not executed for this route.
+    pub(crate) skipped_middlewares: Vec<String>,
+    // The middlewares from the scopes shallower than this depth are not executed for this route.
+    pub(crate) isolation_depth: Option<u32>,
+    // The deprecation whose headers are added to the responses of the route.
+    pub(crate) deprecation: Option<Arc<Deprecation>>,
+    // The guards which are checked before the handler, in the order they are attached.
+    pub(crate) guards: Vec<Arc<dyn Guard>>,
+    // The revalidators which may answer the conditional `GET` requests before the handler, in the order they are
+    // attached.
+    pub(crate) revalidators: Vec<RouteRevalidator<B>>,
+    // The client types of the route per method, which are used by the code generators.
+    pub(crate) route_types: Vec<(Method, Arc<RouteTypes>)>,
+    // The indexes of the skipped middlewares in the top level router, resolved when the router is served.
+    pub(crate) skipped_pre_middleware_idxs: Vec<usize>,
+    pub(crate) skipped_post_middleware_idxs: Vec<usize>,
+    pub(crate) skipped_around_middleware_idxs: Vec<usize>,
+    // The data maps of the route if they don't depend on the request path.
+    pub(crate) shared_data_maps: Option<Arc<[SharedDataMap]>>,
+    // The scoped configs of the route if they don't depend on the request path.
+    pub(crate) scope_configs: Option<Arc<[SharedDataMap]>>,
+    // The indexes of the middlewares to execute for the route if they don't depend on the request path.
+    pub(crate) pre_middleware_plan: Option<Vec<usize>>,
+    pub(crate) post_middleware_plan: Option<Vec<usize>>,
+    pub(crate) around_middleware_plan: Option<Vec<usize>>,
+    // The indexes of the static response headers of the route if they don't depend on the request path.
+    pub(crate) response_headers_plan: Option<Vec<usize>>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct RawRegex {
+    // The path of the scope(s) the route is mounted on.
+    pub(crate) prefix: String,
+    pub(crate) pattern: String,
+}
+
+#[derive(Clone)]
+pub(crate) struct ParamGuard {
+    pub(crate) name: String,
+    check: Arc<dyn Fn(&str) -> bool + Send + Sync>,
+}
+
+impl ParamGuard {
+    pub(crate) fn new<T: FromParam + 'static>(name: String) -> ParamGuard {
+        ParamGuard {
+            name,
+            check: Arc::new(|val: &str| T::from_param(val).is_ok()),
+        }
+    }
 }
 
 impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static> Route<B, E> {
@@ -70,13 +137,74 @@ impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Se
             ))
         })?;
 
+        let param_defaults = generate_param_defaults(path.as_str());
+
         Ok(Route {
             path,
             regex: re,
             route_params: params,
+            param_defaults,
             handler: Some(handler),
             methods,
             scope_depth,
+            raw_regex: None,
+            param_guards: Vec::new(),
+            skipped_middlewares: Vec::new(),
+            isolation_depth: None,
+            deprecation: None,
+            guards: Vec::new(),
+            revalidators: Vec::new(),
+            route_types: Vec::new(),
+            skipped_pre_middleware_idxs: Vec::new(),
+            skipped_post_middleware_idxs: Vec::new(),
+            skipped_around_middleware_idxs: Vec::new(),
+            shared_data_maps: None,
+            scope_configs: None,
+            pre_middleware_plan: None,
+            post_middleware_plan: None,
+            around_middleware_plan: None,
+            response_headers_plan: None,
+        })
+    }
+
+    pub(crate) fn new_raw_with_boxed_handler(
+        raw_regex: RawRegex,
+        methods: Vec<Method>,
+        handler: Handler<B, E>,
+        scope_depth: u32,
+    ) -> crate::Result<Route<B, E>> {
+        let (re, params) = generate_raw_regex(raw_regex.prefix.as_str(), raw_regex.pattern.as_str()).map_err(|e| {
+            Error::new(format!(
+                "Could not create a regex for the route pattern {:?}: {}",
+                raw_regex.pattern, e
+            ))
+        })?;
+
+        Ok(Route {
+            path: format!("{}{}", raw_regex.prefix, raw_regex.pattern),
+            regex: re,
+            route_params: params,
+            param_defaults: Vec::new(),
+            handler: Some(handler),
+            methods,
+            scope_depth,
+            raw_regex: Some(raw_regex),
+            param_guards: Vec::new(),
+            skipped_middlewares: Vec::new(),
+            isolation_depth: None,
+            deprecation: None,
+            guards: Vec::new(),
+            revalidators: Vec::new(),
+            route_types: Vec::new(),
+            skipped_pre_middleware_idxs: Vec::new(),
+            skipped_post_middleware_idxs: Vec::new(),
+            skipped_around_middleware_idxs: Vec::new(),
+            shared_data_maps: None,
+            scope_configs: None,
+            pre_middleware_plan: None,
+            post_middleware_plan: None,
+            around_middleware_plan: None,
+            response_headers_plan: None,
         })
     }
 
@@ -90,10 +218,32 @@ impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Se
         Route::new_with_boxed_handler(path, methods, handler, 1)
     }
 
+    pub(crate) fn new_raw<P, H, R>(pattern: P, methods: Vec<Method>, handler: H) -> crate::Result<Route<B, E>>
+    where
+        P: Into<String>,
+        H: Fn(Request<hyper::Body>) -> R + Send + Sync + 'static,
+        R: Future<Output = Result<Response<B>, E>> + Send + 'static,
+    {
+        let handler: Handler<B, E> = Box::new(move |req: Request<hyper::Body>| Box::new(handler(req)));
+        let raw_regex = RawRegex {
+            prefix: String::new(),
+            pattern: pattern.into(),
+        };
+        Route::new_raw_with_boxed_handler(raw_regex, methods, handler, 1)
+    }
+
     pub(crate) fn is_match_method(&self, method: &Method) -> bool {
         self.methods.contains(method)
     }
 
+    pub(crate) fn has_param(&self, param_name: &str) -> bool {
+        self.route_params.iter().any(|param| param == param_name)
+    }
+
+    pub(crate) fn is_match_params(&self, target_path: &str) -> bool {
+        self.param_guards.is_empty() || check_param_guards(&self.param_guards, &self.generate_route_params(target_path))
+    }
+
     pub(crate) async fn process(&self, target_path: &str, mut req: Request<hyper::Body>) -> crate::Result<Response<B>> {
         self.push_req_meta(target_path, &mut req);
 
@@ -102,11 +252,65 @@ impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Se
             .as_ref()
             .expect("A router can not be used after mounting into another router");
 
-        Pin::from(handler(req)).await.map_err(Into::into)
+        if !self.guards.is_empty() {
+            let (parts, body) = req.into_parts();
+            for guard in self.guards.iter() {
+                if let GuardOutcome::Deny(err) = guard.check(&parts).await {
+                    return Err(err.into());
+                }
+            }
+            req = Request::from_parts(parts, body);
+        }
+
+        let mut validator = None;
+        if !self.revalidators.is_empty() && (req.method() == Method::GET || req.method() == Method::HEAD) {
+            let (parts, body) = req.into_parts();
+            for revalidator in self.revalidators.iter() {
+                if let Some(found) = revalidator.revalidator.revalidate(&parts).await {
+                    if found.is_fresh(&parts.headers) {
+                        let mut res = (revalidator.not_modified)(&found);
+                        self.apply_deprecation_headers(&mut res);
+                        return Ok(res);
+                    }
+                    validator = Some(found);
+                    break;
+                }
+            }
+            req = Request::from_parts(parts, body);
+        }
+
+        let mut res = Pin::from(handler(req)).await.map_err(into_route_error)?;
+        if let Some(validator) = validator.filter(|_| res.status().is_success()) {
+            validator.apply_headers(res.headers_mut());
+        }
+        self.apply_deprecation_headers(&mut res);
+        Ok(res)
+    }
+
+    fn apply_deprecation_headers(&self, res: &mut Response<B>) {
+        if let Some(ref deprecation) = self.deprecation {
+            deprecation.apply_headers(res.headers_mut());
+        }
     }
 
     fn push_req_meta(&self, target_path: &str, req: &mut Request<hyper::Body>) {
-        self.update_req_meta(req, self.generate_req_meta(target_path));
+        // The params modified by a pre middleware are kept instead of the ones of the path.
+        let modified_req_meta = req
+            .extensions()
+            .get::<RequestMeta>()
+            .filter(|req_meta| req_meta.is_route_params_modified());
+        if let Some(req_meta) = modified_req_meta {
+            if let (Some(memory), Some(params)) = (RequestMemory::of(req.extensions()), req_meta.route_params()) {
+                memory.record_params(params);
+            }
+            return;
+        }
+
+        let req_meta = self.generate_req_meta(target_path);
+        if let (Some(memory), Some(params)) = (RequestMemory::of(req.extensions()), req_meta.route_params()) {
+            memory.record_params(params);
+        }
+        self.update_req_meta(req, req_meta);
     }
 
     fn update_req_meta(&self, req: &mut Request<hyper::Body>, req_meta: RequestMeta) {
@@ -114,26 +318,72 @@ impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Se
     }
 
     fn generate_req_meta(&self, target_path: &str) -> RequestMeta {
-        let route_params_list = &self.route_params;
-        let ln = route_params_list.len();
+        RequestMeta::with_route_params(self.generate_route_params(target_path))
+    }
 
-        let mut route_params = RouteParams::with_capacity(ln);
+    pub(crate) fn generate_route_params(&self, target_path: &str) -> RouteParams {
+        generate_route_params(
+            &self.regex,
+            &self.route_params,
+            self.raw_regex.is_some(),
+            &self.param_defaults,
+            target_path,
+        )
+    }
+}
+
+// Generates the params of a route from a target path, which is shared by the routes and their infos.
+pub(crate) fn generate_route_params(
+    regex: &Regex,
+    route_params_list: &[String],
+    is_raw_regex: bool,
+    param_defaults: &[(String, String)],
+    target_path: &str,
+) -> RouteParams {
+    let ln = route_params_list.len();
 
-        if ln > 0 {
-            if let Some(caps) = self.regex.captures(target_path) {
-                let mut iter = caps.iter();
-                // Skip the first match because it's the whole path.
-                iter.next();
+    let mut route_params = RouteParams::with_capacity(ln);
+
+    if ln > 0 {
+        if let Some(caps) = regex.captures(target_path) {
+            // Raw regex routes may have unnamed groups, so their params are looked up by name.
+            if is_raw_regex {
                 for param in route_params_list {
-                    if let Some(Some(g)) = iter.next() {
+                    if let Some(g) = caps.name(param) {
                         route_params.set(param.clone(), g.as_str());
                     }
                 }
+
+                return route_params;
+            }
+
+            let mut iter = caps.iter();
+            // Skip the first match because it's the whole path.
+            iter.next();
+            for param in route_params_list {
+                if let Some(Some(g)) = iter.next() {
+                    route_params.set(param.clone(), g.as_str());
+                }
             }
         }
+    }
 
-        RequestMeta::with_route_params(route_params)
+    for (param, default) in param_defaults {
+        if !route_params.has(param.as_str()) {
+            route_params.set(param.clone(), default.clone());
+        }
     }
+
+    route_params
+}
+
+pub(crate) fn check_param_guards(param_guards: &[ParamGuard], route_params: &RouteParams) -> bool {
+    param_guards
+        .iter()
+        .all(|guard| match route_params.get(guard.name.as_str()) {
+            Some(val) => (guard.check)(val),
+            None => true,
+        })
 }
 
 impl<B, E> Debug for Route<B, E> {
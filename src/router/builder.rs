@@ -1,11 +1,30 @@
+use crate::codegen::RouteTypes;
+#[cfg(feature = "config")]
+use crate::config::{HandlerRegistry, RouteManifest};
 use crate::constants;
 use crate::data_map::{DataMap, ScopedDataMap};
-use crate::middleware::{Middleware, PostMiddleware, PreMiddleware};
-use crate::route::Route;
+use crate::error::into_route_error;
+use crate::events::Observer;
+use crate::guard::Guard;
+use crate::middleware::rewrite::Rewrite;
+use crate::middleware::{AroundMiddleware, Middleware, PostMiddleware, PreMiddleware};
+use crate::regex_generator::path_covers_route;
+use crate::revalidate::{self, Revalidator, RouteRevalidator};
+use crate::route::{ParamGuard, RawRegex, Route};
+use crate::router::fingerprint;
+use crate::router::match_cache::MatchCache;
 use crate::router::Router;
-use crate::router::{ErrHandler, ErrHandlerWithInfo, ErrHandlerWithoutInfo};
-use crate::types::RequestInfo;
-use hyper::{body::HttpBody, Method, Request, Response};
+use crate::router::{
+    ErrHandler, ErrHandlerWithInfo, ErrHandlerWithoutInfo, ErrResponseMapper, ErrResponseMapperHandler, MethodTable,
+    PreMatchHook, ResponseHeaders, RouteMeta, StatusMapper, StatusMapperHandler,
+};
+use crate::service::LifecycleHook;
+use crate::split::Split;
+use crate::types::{Deprecation, ErrorContext, FromParam, RequestInfo};
+use crate::warmup::Warmup;
+use hyper::header::{HeaderName, HeaderValue};
+use hyper::{body::HttpBody, Method, Request, Response, StatusCode};
+use std::cmp::Reverse;
 use std::collections::HashMap;
 use std::future::Future;
 use std::sync::Arc;
@@ -58,8 +77,32 @@ struct BuilderInner<B, E> {
     pre_middlewares: Vec<PreMiddleware<E>>,
     routes: Vec<Route<B, E>>,
     post_middlewares: Vec<PostMiddleware<B, E>>,
+    around_middlewares: Vec<AroundMiddleware<B, E>>,
     data_maps: HashMap<String, Vec<DataMap>>,
+    config_maps: HashMap<String, Vec<DataMap>>,
     err_handler: Option<ErrHandler<B>>,
+    param_guards: Vec<ParamGuard>,
+    deprecations: Vec<(String, Arc<Deprecation>)>,
+    guards: Vec<(String, Arc<dyn Guard>)>,
+    revalidators: Vec<(String, RouteRevalidator<B>)>,
+    route_types: Vec<(Method, String, Arc<RouteTypes>)>,
+    classify_errors: bool,
+    debug_errors: bool,
+    problem_details: bool,
+    match_cache: Option<usize>,
+    #[cfg(feature = "fast-match")]
+    fast_match: bool,
+    base_path: Option<Arc<str>>,
+    isolate_middleware: bool,
+    fingerprint_header: Option<HeaderName>,
+    response_headers: Vec<ResponseHeaders>,
+    err_response_mappers: Vec<ErrResponseMapper<B>>,
+    status_mappers: Vec<StatusMapper>,
+    observer: Option<Observer>,
+    catch_panics: bool,
+    pre_match_hooks: Vec<PreMatchHook>,
+    startup_hooks: Vec<LifecycleHook>,
+    shutdown_hooks: Vec<LifecycleHook>,
 }
 
 impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static>
@@ -72,26 +115,125 @@ impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Se
 
     /// Creates a new [Router](./struct.Router.html) instance from the added configuration.
     pub fn build(self) -> crate::Result<Router<B, E>> {
-        self.inner.and_then(|inner| {
-            let scoped_data_maps = inner
-                .data_maps
-                .into_iter()
-                .map(|(path, data_map_arr)| {
-                    data_map_arr
-                        .into_iter()
-                        .map(|data_map| ScopedDataMap::new(path.clone(), Arc::new(data_map)))
-                        .collect::<Vec<crate::Result<ScopedDataMap>>>()
-                })
-                .flatten()
-                .collect::<Result<Vec<ScopedDataMap>, crate::RouteError>>()?;
-
-            Ok(Router::new(
+        self.inner.and_then(|mut inner| {
+            for route in inner.routes.iter_mut() {
+                for guard in inner.param_guards.iter() {
+                    if route.has_param(guard.name.as_str()) {
+                        route.param_guards.push(guard.clone());
+                    }
+                }
+            }
+
+            if inner.isolate_middleware {
+                for route in inner.routes.iter_mut() {
+                    route.isolation_depth.get_or_insert(1);
+                }
+            }
+
+            for (path, deprecation) in inner.deprecations.iter() {
+                let mut is_found = false;
+                for route in inner.routes.iter_mut().filter(|route| route.path == *path) {
+                    route.deprecation = Some(deprecation.clone());
+                    is_found = true;
+                }
+                if !is_found {
+                    return Err(crate::Error::new(format!(
+                        "Couldn't deprecate the path {:?}: no route is added at it",
+                        path
+                    ))
+                    .into());
+                }
+            }
+
+            for (path, guard) in inner.guards.iter() {
+                let mut is_found = false;
+                for route in inner.routes.iter_mut().filter(|route| {
+                    route.path == *path || path_covers_route(path.as_str(), route.path.as_str()) == Some(true)
+                }) {
+                    route.guards.push(guard.clone());
+                    is_found = true;
+                }
+                if !is_found {
+                    return Err(crate::Error::new(format!(
+                        "Couldn't guard the path {:?}: no route is added at it",
+                        path
+                    ))
+                    .into());
+                }
+            }
+
+            for (path, revalidator) in inner.revalidators.iter() {
+                let mut is_found = false;
+                for route in inner.routes.iter_mut().filter(|route| {
+                    route.path == *path || path_covers_route(path.as_str(), route.path.as_str()) == Some(true)
+                }) {
+                    route.revalidators.push(revalidator.clone());
+                    is_found = true;
+                }
+                if !is_found {
+                    return Err(crate::Error::new(format!(
+                        "Couldn't revalidate the path {:?}: no route is added at it",
+                        path
+                    ))
+                    .into());
+                }
+            }
+
+            for (method, path, types) in inner.route_types.iter() {
+                let mut is_found = false;
+                for route in inner
+                    .routes
+                    .iter_mut()
+                    .filter(|route| route.path == *path && route.is_match_method(method))
+                {
+                    route.route_types.retain(|(route_method, _)| route_method != method);
+                    route.route_types.push((method.clone(), types.clone()));
+                    is_found = true;
+                }
+                if !is_found {
+                    return Err(crate::Error::new(format!(
+                        "Couldn't set the types of the route {} {:?}: no route is added at it",
+                        method, path
+                    ))
+                    .into());
+                }
+            }
+
+            let scoped_data_maps = into_scoped_data_maps(inner.data_maps)?;
+
+            let mut router = Router::new(
                 inner.pre_middlewares,
                 inner.routes,
                 inner.post_middlewares,
+                inner.around_middlewares,
                 scoped_data_maps,
                 inner.err_handler,
-            ))
+            );
+            router.scoped_config_maps = into_scoped_data_maps(inner.config_maps)?;
+            router.classify_errors = inner.classify_errors;
+            router.debug_errors = inner.debug_errors;
+            router.problem_details = inner.problem_details;
+            router.match_cache = inner.match_cache.map(MatchCache::new);
+            #[cfg(feature = "fast-match")]
+            {
+                router.fast_match = inner.fast_match;
+            }
+            router.base_path = inner.base_path;
+            router.fingerprint = fingerprint::compute(&router).into();
+            router.method_table = Arc::new(MethodTable::new(&router.routes)?);
+            router.fingerprint_header = inner.fingerprint_header;
+            router.response_headers = inner.response_headers;
+            router.err_response_mappers = inner.err_response_mappers;
+            // The sort is stable, so the mappers of the same scope keep the registration order.
+            router.status_mappers = inner.status_mappers;
+            router.status_mappers.sort_by_key(|mapper| Reverse(mapper.scope_depth));
+            router.observer = inner.observer;
+            router.catch_panics = inner.catch_panics;
+            router.pre_match_hooks = inner.pre_match_hooks;
+            router.startup_hooks = inner.startup_hooks;
+            router.shutdown_hooks = inner.shutdown_hooks;
+
+            Ok(router)
         })
     }
 
@@ -520,7 +662,11 @@ impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Se
         })
     }
 
-    /// It mounts a router onto another router. It can be very useful when you want to write modular routing logic.
+    /// Adds a new route with `GET` method at the specified path which splits the traffic between two handlers by their
+    /// weights, e.g. to send a small fraction of the requests to a new implementation.
+    ///
+    /// The variants are named `control` and `canary`, and the chosen [`Variant`](./split/struct.Variant.html) is stored
+    /// in the request context. Please refer to the [`split`](./split/index.html) module for more info.
     ///
     /// # Examples
     ///
@@ -528,201 +674,1336 @@ impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Se
     /// use routerify::Router;
     /// use hyper::{Response, Request, Body};
     ///
-    /// mod api {
-    ///     use routerify::Router;
-    ///     use hyper::{Response, Request, Body};
+    /// async fn search_handler(req: Request<Body>) -> Result<Response<Body>, hyper::Error> {
+    ///     Ok(Response::new(Body::from("Search results")))
+    /// }
     ///
-    ///     pub fn router() -> Router<Body, hyper::Error> {
-    ///         Router::builder()
-    ///          .get("/users", |req| async move { Ok(Response::new(Body::from("User list"))) })
-    ///          .get("/books", |req| async move { Ok(Response::new(Body::from("Book list"))) })
-    ///          .build()
-    ///          .unwrap()
-    ///     }
+    /// async fn new_search_handler(req: Request<Body>) -> Result<Response<Body>, hyper::Error> {
+    ///     Ok(Response::new(Body::from("New search results")))
     /// }
     ///
     /// # fn run() -> Router<Body, hyper::Error> {
     /// let router = Router::builder()
-    ///     // Now, mount the api router at `/api` path.
-    ///     .scope("/api", api::router())
+    ///     .get_split("/search", 90, search_handler, 10, new_search_handler)
     ///     .build()
     ///     .unwrap();
     /// # router
     /// # }
     /// # run();
     /// ```
-    ///
-    /// Now, the app can handle requests on: `/api/users` and `/api/books` paths.
-    pub fn scope<P>(self, path: P, mut router: Router<B, E>) -> Self
+    pub fn get_split<P, H1, R1, H2, R2>(
+        self,
+        path: P,
+        control_weight: u32,
+        control_handler: H1,
+        canary_weight: u32,
+        canary_handler: H2,
+    ) -> Self
     where
         P: Into<String>,
+        H1: Fn(Request<hyper::Body>) -> R1 + Send + Sync + 'static,
+        R1: Future<Output = Result<Response<B>, E>> + Send + 'static,
+        H2: Fn(Request<hyper::Body>) -> R2 + Send + Sync + 'static,
+        R2: Future<Output = Result<Response<B>, E>> + Send + 'static,
     {
-        let mut path = path.into();
-
-        if path.ends_with('/') {
-            path = (&path[..path.len() - 1]).to_string();
-        }
-
-        let mut builder = self;
-
-        for pre_middleware in router.pre_middlewares.iter_mut() {
-            let new_pre_middleware = PreMiddleware::new_with_boxed_handler(
-                format!("{}{}", path.as_str(), pre_middleware.path.as_str()),
-                pre_middleware
-                    .handler
-                    .take()
-                    .expect("No handler found in one of the pre-middlewares"),
-                pre_middleware.scope_depth + 1,
-            );
-            builder = builder.and_then(move |mut inner| {
-                inner.pre_middlewares.push(new_pre_middleware?);
-                crate::Result::Ok(inner)
-            });
-        }
-
-        for route in router.routes.iter_mut() {
-            let new_route = Route::new_with_boxed_handler(
-                format!("{}{}", path.as_str(), route.path.as_str()),
-                route.methods.clone(),
-                route.handler.take().expect("No handler found in one of the routes"),
-                route.scope_depth + 1,
-            );
-            builder = builder.and_then(move |mut inner| {
-                inner.routes.push(new_route?);
-                crate::Result::Ok(inner)
-            });
-        }
+        let split = Split::new()
+            .variant("control", control_weight, control_handler)
+            .variant("canary", canary_weight, canary_handler);
+        self.add_split(path, vec![Method::GET], split)
+    }
 
-        for post_middleware in router.post_middlewares.iter_mut() {
-            let new_post_middleware = PostMiddleware::new_with_boxed_handler(
-                format!("{}{}", path.as_str(), post_middleware.path.as_str()),
-                post_middleware
-                    .handler
-                    .take()
-                    .expect("No handler found in one of the post-middlewares"),
-                post_middleware.scope_depth + 1,
-            );
-            builder = builder.and_then(move |mut inner| {
-                inner.post_middlewares.push(new_post_middleware?);
-                crate::Result::Ok(inner)
-            });
-        }
+    /// Adds a new route with the specified method(s) at the specified path which splits the traffic between the
+    /// variants of the [`Split`](./split/struct.Split.html).
+    ///
+    /// Please refer to the [`split`](./split/index.html) module for more info.
+    pub fn add_split<P>(self, path: P, methods: Vec<Method>, split: Split<B, E>) -> Self
+    where
+        P: Into<String>,
+    {
+        self.and_then(move |mut inner| {
+            split.validate()?;
 
-        for scoped_data_map in router.scoped_data_maps.iter_mut() {
-            let new_path = format!("{}{}", path.as_str(), scoped_data_map.path.as_str());
-            let data_map = Arc::try_unwrap(
-                scoped_data_map
-                    .data_map
-                    .take()
-                    .expect("No data map found in one of the scoped data maps"),
-            )
-            .expect("Non-zero owner of the shared data map in one of the scoped data maps");
+            let mut path = path.into();
 
-            builder = builder.and_then(move |mut inner| {
-                let data_maps = &mut inner.data_maps;
+            if !path.ends_with('/') && !path.ends_with('*') {
+                path.push('/');
+            }
 
-                let data_map_arr = data_maps.get_mut(&new_path);
-                if let Some(data_map_arr) = data_map_arr {
-                    data_map_arr.push(data_map);
-                } else {
-                    data_maps.insert(new_path, vec![data_map]);
-                }
+            let split = Arc::new(split);
+            let route = Route::new(path, methods, move |req| {
+                let split = split.clone();
+                async move { split.handle(req).await }
+            })?;
+            inner.routes.push(route);
 
-                crate::Result::Ok(inner)
-            });
-        }
+            crate::Result::Ok(inner)
+        })
+    }
 
-        builder
+    /// Adds a new route with `GET` method and the handler at the path matched by a raw regex pattern.
+    ///
+    /// It's useful for the URL schemes which can't be expressed by the path syntax. The pattern bypasses the path
+    /// syntax entirely and the named capture groups are available as the route parameters. Please note that the
+    /// request path being matched always ends with a `/`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::Router;
+    /// use routerify::ext::RequestExt;
+    /// use hyper::{Response, Request, Body};
+    ///
+    /// async fn item_handler(req: Request<Body>) -> Result<Response<Body>, hyper::Error> {
+    ///     let id = req.param("id").unwrap();
+    ///     let slug = req.param("slug").unwrap();
+    ///     Ok(Response::new(Body::from(format!("Item {}: {}", id, slug))))
+    /// }
+    ///
+    /// # fn run() -> Router<Body, hyper::Error> {
+    /// let router = Router::builder()
+    ///     .get_regex(r"^/items/(?P<id>\d+)-(?P<slug>[a-z-]+)/$", item_handler)
+    ///     .build()
+    ///     .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    pub fn get_regex<P, H, R>(self, pattern: P, handler: H) -> Self
+    where
+        P: Into<String>,
+        H: Fn(Request<hyper::Body>) -> R + Send + Sync + 'static,
+        R: Future<Output = Result<Response<B>, E>> + Send + 'static,
+    {
+        self.add_regex(pattern, vec![Method::GET], handler)
     }
-}
 
-impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static>
-    RouterBuilder<B, E>
-{
-    /// Adds a single middleware. A pre middleware can be created by [`Middleware::pre`](./enum.Middleware.html#method.pre) method and a post
-    /// middleware can be created by [`Middleware::post`](./enum.Middleware.html#method.post) method.
+    /// Adds a new route with `GET` method and the handler at the specified path which skips the
+    /// [named](./enum.Middleware.html#method.named) middlewares, e.g. to keep the high-frequency endpoints
+    /// away from the expensive middlewares.
+    ///
+    /// The names are resolved when the router is served and unknown names are reported as an error.
     ///
     /// # Examples
     ///
     /// ```
     /// use routerify::{Router, Middleware};
     /// use hyper::{Response, Request, Body};
-    /// use std::convert::Infallible;
     ///
-    /// # fn run() -> Router<Body, Infallible> {
+    /// async fn metrics_handler(req: Request<Body>) -> Result<Response<Body>, hyper::Error> {
+    ///     Ok(Response::new(Body::from("Metrics")))
+    /// }
+    ///
+    /// # fn run() -> Router<Body, hyper::Error> {
     /// let router = Router::builder()
-    ///      // Create and attach a pre middleware.
-    ///      .middleware(Middleware::pre(|req| async move { /* Do some operations */ Ok(req) }))
-    ///      // Create and attach a post middleware.
-    ///      .middleware(Middleware::post(|res| async move { /* Do some operations */ Ok(res) }))
-    ///      .build()
-    ///      .unwrap();
+    ///     .middleware(Middleware::pre(|req| async move { /* Log */ Ok(req) }).named("access_log"))
+    ///     .middleware(Middleware::pre(|req| async move { /* Auth */ Ok(req) }).named("auth"))
+    ///     .get_skipping("/metrics", &["access_log", "auth"], metrics_handler)
+    ///     .build()
+    ///     .unwrap();
     /// # router
     /// # }
     /// # run();
     /// ```
-    pub fn middleware(self, m: Middleware<B, E>) -> Self {
+    pub fn get_skipping<P, H, R>(self, path: P, skip: &[&str], handler: H) -> Self
+    where
+        P: Into<String>,
+        H: Fn(Request<hyper::Body>) -> R + Send + Sync + 'static,
+        R: Future<Output = Result<Response<B>, E>> + Send + 'static,
+    {
+        self.add_skipping(path, vec![Method::GET], skip, handler)
+    }
+
+    /// Adds a new route with the specified method(s) and the handler at the specified path which skips the
+    /// [named](./enum.Middleware.html#method.named) middlewares.
+    ///
+    /// Please refer to the [`get_skipping`](./struct.RouterBuilder.html#method.get_skipping) method for more info.
+    pub fn add_skipping<P, H, R>(self, path: P, methods: Vec<Method>, skip: &[&str], handler: H) -> Self
+    where
+        P: Into<String>,
+        H: Fn(Request<hyper::Body>) -> R + Send + Sync + 'static,
+        R: Future<Output = Result<Response<B>, E>> + Send + 'static,
+    {
+        let skipped_middlewares = skip.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+
         self.and_then(move |mut inner| {
-            match m {
-                Middleware::Pre(middleware) => {
-                    inner.pre_middlewares.push(middleware);
-                }
-                Middleware::Post(middleware) => {
-                    inner.post_middlewares.push(middleware);
-                }
+            let mut path = path.into();
+
+            if !path.ends_with('/') && !path.ends_with('*') {
+                path.push('/');
             }
+
+            let mut route = Route::new(path, methods, handler)?;
+            route.skipped_middlewares = skipped_middlewares;
+            inner.routes.push(route);
+
             crate::Result::Ok(inner)
         })
     }
 
-    /// Specify app data to be shared across route handlers, middlewares and the error handler.
+    /// Adds the routes of a manifest, whose handlers and middlewares are looked up by name in the registry. It requires
+    /// the `config` feature.
     ///
-    /// Please refer to the [Data and State Sharing](./index.html#data-and-state-sharing) for more info.
-    pub fn data<T: Send + Sync + 'static>(self, data: T) -> Self {
-        self.and_then(move |mut inner| {
-            let data_maps = &mut inner.data_maps;
+    /// The middlewares of a route are created at its path, so they run for the other routes at the same path too.
+    /// The build fails if a route refers to a handler or a middleware which isn't registered, or has an invalid method.
+    ///
+    /// Please refer to the [`config`](./config/index.html) module for an example.
+    #[cfg(feature = "config")]
+    pub fn routes_from(self, manifest: &RouteManifest, registry: &HandlerRegistry<B, E>) -> Self {
+        let fail = |builder: Self, msg: String| builder.and_then(move |_| Err(crate::Error::new(msg).into()));
 
-            let data_map_arr = data_maps.get_mut(&"/*".to_owned());
-            if let Some(data_map_arr) = data_map_arr {
-                let first_data_map = data_map_arr.get_mut(0).unwrap();
-                first_data_map.insert(data);
-            } else {
-                let mut data_map = DataMap::new();
-                data_map.insert(data);
-                data_maps.insert("/*".to_owned(), vec![data_map]);
+        let mut builder = self;
+        for route in &manifest.routes {
+            let handler = match registry.handlers.get(&route.handler) {
+                Some(handler) => handler.clone(),
+                None => {
+                    let msg = format!("Unknown handler {:?} of the route {:?}", route.handler, route.path);
+                    return fail(builder, msg);
+                }
+            };
+            let methods = match route.parse_methods() {
+                Ok(methods) => methods,
+                Err(err) => return fail(builder, err.to_string()),
+            };
+
+            let mut path = route.path.clone();
+            if !path.ends_with('/') && !path.ends_with('*') {
+                path.push('/');
+            }
+            for name in &route.middleware {
+                let middleware = match registry.middlewares.get(name) {
+                    Some(factory) => factory(&path),
+                    None => {
+                        let msg = format!("Unknown middleware {:?} of the route {:?}", name, route.path);
+                        return fail(builder, msg);
+                    }
+                };
+                match middleware {
+                    Ok(middleware) => builder = builder.middleware(middleware),
+                    Err(err) => return builder.and_then(move |_| Err(err)),
+                }
             }
 
-            crate::Result::Ok(inner)
-        })
+            let skip = route.skip.iter().map(String::as_str).collect::<Vec<_>>();
+            builder = builder.add_skipping(path, methods, &skip, move |req| handler(req));
+        }
+        builder
     }
 
-    /// Adds a handler to handle any error raised by the routes or any middlewares. Please refer to [Error Handling](./index.html#error-handling) section
-    /// for more info.
-    pub fn err_handler<H, R>(self, handler: H) -> Self
+    /// Adds a new route with the specified method(s) and the handler at the path matched by a raw regex pattern.
+    ///
+    /// Please refer to the [`get_regex`](./struct.RouterBuilder.html#method.get_regex) method for more info.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::Router;
+    /// use hyper::{Response, Request, Body, Method};
+    ///
+    /// async fn archive_handler(req: Request<Body>) -> Result<Response<Body>, hyper::Error> {
+    ///     Ok(Response::new(Body::from("Archive")))
+    /// }
+    ///
+    /// # fn run() -> Router<Body, hyper::Error> {
+    /// let router = Router::builder()
+    ///     .add_regex(r"^/archive/(?P<year>\d{4})/$", vec![Method::GET, Method::HEAD], archive_handler)
+    ///     .build()
+    ///     .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    pub fn add_regex<P, H, R>(self, pattern: P, methods: Vec<Method>, handler: H) -> Self
     where
-        H: Fn(crate::RouteError) -> R + Send + Sync + 'static,
-        R: Future<Output = Response<B>> + Send + 'static,
+        P: Into<String>,
+        H: Fn(Request<hyper::Body>) -> R + Send + Sync + 'static,
+        R: Future<Output = Result<Response<B>, E>> + Send + 'static,
     {
-        let handler: ErrHandlerWithoutInfo<B> = Box::new(move |err: crate::RouteError| Box::new(handler(err)));
-
         self.and_then(move |mut inner| {
-            inner.err_handler = Some(ErrHandler::WithoutInfo(handler));
+            let route = Route::new_raw(pattern, methods, handler)?;
+            inner.routes.push(route);
+
             crate::Result::Ok(inner)
         })
     }
 
-    /// Adds a handler to handle any error raised by the routes or any middlewares.
+    /// Makes the routes of this router with the specified parameter match only if the parameter value can be
+    /// converted into the type `T` through the [`FromParam`](./trait.FromParam.html) trait.
     ///
-    /// Here, the handler also access [request info](./struct.RequestInfo.html) e.g. headers, method, uri etc to generate response based on the request information.
+    /// If the conversion fails, the request falls through to the next matching route, e.g. the default 404 route.
+    /// The converted value can be accessed by the [`param_typed`](./ext/trait.RequestExt.html#tymethod.param_typed)
+    /// method.
     ///
-    /// Please refer to [Error Handling](./index.html#error-handling) section
-    /// for more info.
-    pub fn err_handler_with_info<H, R>(self, handler: H) -> Self
-    where
-        H: Fn(crate::RouteError, RequestInfo) -> R + Send + Sync + 'static,
-        R: Future<Output = Response<B>> + Send + 'static,
-    {
-        let handler: ErrHandlerWithInfo<B> =
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::{Router, FromParam};
+    /// use routerify::ext::RequestExt;
+    /// use hyper::{Response, Body};
+    /// # use std::convert::Infallible;
+    ///
+    /// #[derive(Debug)]
+    /// enum OrderStatus {
+    ///     Pending,
+    ///     Shipped,
+    /// }
+    ///
+    /// impl FromParam for OrderStatus {
+    ///     type Error = String;
+    ///
+    ///     fn from_param(param: &str) -> Result<Self, Self::Error> {
+    ///         match param {
+    ///             "pending" => Ok(OrderStatus::Pending),
+    ///             "shipped" => Ok(OrderStatus::Shipped),
+    ///             _ => Err(format!("Unknown order status: {}", param)),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// # fn run() -> Router<Body, Infallible> {
+    /// let router = Router::builder()
+    ///     .typed_param::<OrderStatus, _>("status")
+    ///     .get("/orders/:status", |req| async move {
+    ///         let status = req.param_typed::<OrderStatus, _>("status").unwrap().unwrap();
+    ///         Ok(Response::new(Body::from(format!("Orders: {:?}", status))))
+    ///     })
+    ///     // Any other value, e.g. `/orders/recent`, is handled here.
+    ///     .get("/orders/:name", |req| async move {
+    ///         Ok(Response::new(Body::from(format!("Order list: {}", req.param("name").unwrap()))))
+    ///     })
+    ///     .build()
+    ///     .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    pub fn typed_param<T, P>(self, param_name: P) -> Self
+    where
+        T: FromParam + 'static,
+        P: Into<String>,
+    {
+        self.and_then(move |mut inner| {
+            inner.param_guards.push(ParamGuard::new::<T>(param_name.into()));
+            crate::Result::Ok(inner)
+        })
+    }
+
+    /// Marks the routes at the specified path, of any method, as deprecated. Their responses get the `Deprecation`,
+    /// the `Sunset` and the `Link` headers, and the [`Router::deprecation`](./struct.Router.html#method.deprecation)
+    /// method reports them. The routes can be added before or after this call, but the build fails if there is none.
+    ///
+    /// Please refer to the [`Deprecation`](./struct.Deprecation.html) for more info.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::{Deprecation, Router};
+    /// use hyper::{Response, Body};
+    /// use std::convert::Infallible;
+    /// use std::time::{Duration, UNIX_EPOCH};
+    ///
+    /// # fn run() -> Router<Body, Infallible> {
+    /// let router = Router::builder()
+    ///     .get("/v1/users", |_| async move { Ok(Response::new(Body::from("User list"))) })
+    ///     .get("/v2/users", |_| async move { Ok(Response::new(Body::from("User list"))) })
+    ///     .deprecated(
+    ///         "/v1/users",
+    ///         Deprecation::new(UNIX_EPOCH + Duration::from_secs(1_688_169_599))
+    ///             .with_sunset(UNIX_EPOCH + Duration::from_secs(1_719_791_999))
+    ///             .with_link("https://example.com/docs/migrate-to-v2"),
+    ///     )
+    ///     .build()
+    ///     .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    pub fn deprecated<P: Into<String>>(self, path: P, deprecation: Deprecation) -> Self {
+        self.and_then(move |mut inner| {
+            let mut path = path.into();
+
+            if !path.ends_with('/') && !path.ends_with('*') {
+                path.push('/');
+            }
+
+            inner.deprecations.push((path, Arc::new(deprecation)));
+            crate::Result::Ok(inner)
+        })
+    }
+
+    /// Sets the name of the client method and the request and the response body types of the route with the specified
+    /// method and path, which are used by the [`codegen`](./codegen/index.html) generators. The route can be added
+    /// before or after this call, but the build fails if there is none.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::Router;
+    /// use routerify::codegen::RouteTypes;
+    /// use hyper::{Response, Body, Method};
+    /// use std::convert::Infallible;
+    ///
+    /// # fn run() -> Router<Body, Infallible> {
+    /// let router = Router::builder()
+    ///     .post("/users", |_| async move { Ok(Response::new(Body::from("{}"))) })
+    ///     .route_types(
+    ///         Method::POST,
+    ///         "/users",
+    ///         RouteTypes::new().name("create_user").request("NewUser").response("User"),
+    ///     )
+    ///     .build()
+    ///     .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    pub fn route_types<P: Into<String>>(self, method: Method, path: P, types: RouteTypes) -> Self {
+        self.and_then(move |mut inner| {
+            let mut path = path.into();
+
+            if !path.ends_with('/') && !path.ends_with('*') {
+                path.push('/');
+            }
+
+            inner.route_types.push((method, path, Arc::new(types)));
+            crate::Result::Ok(inner)
+        })
+    }
+
+    /// Attaches a guard to the routes at the specified path, of any method, which is either a route path or a
+    /// `<prefix>/*` path covering all the routes under the prefix. The guards are checked in the order they are
+    /// attached, before the handler and after the pre middlewares. The routes can be added before or after this call,
+    /// but the build fails if there is none.
+    ///
+    /// Please refer to the [`guard`](./guard/index.html) module for more info.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::{HttpError, Router};
+    /// use routerify::guard::GuardOutcome;
+    /// use http::request::Parts;
+    /// use hyper::{Response, Body, StatusCode};
+    /// use std::convert::Infallible;
+    ///
+    /// # fn run() -> Router<Body, Infallible> {
+    /// let router = Router::builder()
+    ///     .get("/internal/health", |_| async move { Ok(Response::new(Body::from("OK"))) })
+    ///     .guard("/internal/*", |req: &Parts| match req.headers.contains_key("x-internal") {
+    ///         true => GuardOutcome::Allow,
+    ///         false => GuardOutcome::Deny(HttpError::new(StatusCode::NOT_FOUND, "Not Found")),
+    ///     })
+    ///     .build()
+    ///     .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    pub fn guard<P: Into<String>, G: Guard>(self, path: P, guard: G) -> Self {
+        self.and_then(move |mut inner| {
+            let mut path = path.into();
+
+            if !path.ends_with('/') && !path.ends_with('*') {
+                path.push('/');
+            }
+
+            inner.guards.push((path, Arc::new(guard)));
+            crate::Result::Ok(inner)
+        })
+    }
+
+    /// Adds static headers to the responses of the routes matching the path, e.g. `/admin/*`, unless they're already set
+    /// by the route handlers or the middlewares. It's useful for the security headers of a scope.
+    ///
+    /// The headers are validated when the router is built and they're resolved per route once the router is served, so
+    /// no closure runs and nothing is allocated per request, unlike a post middleware.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::Router;
+    /// use hyper::{Response, Body};
+    ///
+    /// # fn run() -> Router<Body, routerify::Error> {
+    /// let router = Router::builder()
+    ///     .get("/admin/users", |_| async move { Ok(Response::new(Body::from("Users"))) })
+    ///     .with_headers("/admin/*", [("x-frame-options", "DENY"), ("cache-control", "no-store")])
+    ///     .build()
+    ///     .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    pub fn with_headers<P, I, N, V>(self, path: P, headers: I) -> Self
+    where
+        P: Into<String>,
+        I: IntoIterator<Item = (N, V)>,
+        N: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let mut path = path.into();
+        if !path.ends_with('/') && !path.ends_with('*') {
+            path.push('/');
+        }
+
+        let headers = headers
+            .into_iter()
+            .map(|(name, value)| {
+                let (name, value) = (name.as_ref(), value.as_ref());
+                let name = HeaderName::from_bytes(name.as_bytes())
+                    .map_err(|_| crate::Error::new(format!("Invalid response header name: {:?}", name)))?;
+                let value = HeaderValue::from_str(value)
+                    .map_err(|_| crate::Error::new(format!("Invalid value of the response header {}", name)))?;
+                Ok((name, value))
+            })
+            .collect::<Result<Arc<[_]>, crate::Error>>();
+
+        self.and_then(move |mut inner| {
+            inner.response_headers.push(ResponseHeaders::new(path, headers?, 0)?);
+            crate::Result::Ok(inner)
+        })
+    }
+
+    /// It mounts a router onto another router. It can be very useful when you want to write modular routing logic.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::Router;
+    /// use hyper::{Response, Request, Body};
+    ///
+    /// mod api {
+    ///     use routerify::Router;
+    ///     use hyper::{Response, Request, Body};
+    ///
+    ///     pub fn router() -> Router<Body, hyper::Error> {
+    ///         Router::builder()
+    ///          .get("/users", |req| async move { Ok(Response::new(Body::from("User list"))) })
+    ///          .get("/books", |req| async move { Ok(Response::new(Body::from("Book list"))) })
+    ///          .build()
+    ///          .unwrap()
+    ///     }
+    /// }
+    ///
+    /// # fn run() -> Router<Body, hyper::Error> {
+    /// let router = Router::builder()
+    ///     // Now, mount the api router at `/api` path.
+    ///     .scope("/api", api::router())
+    ///     .build()
+    ///     .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    ///
+    /// Now, the app can handle requests on: `/api/users` and `/api/books` paths.
+    pub fn scope<P>(self, path: P, mut router: Router<B, E>) -> Self
+    where
+        P: Into<String>,
+    {
+        let mut path = path.into();
+
+        if path.ends_with('/') {
+            path = (&path[..path.len() - 1]).to_string();
+        }
+
+        let mut builder = self;
+
+        for pre_middleware in router.pre_middlewares.iter_mut() {
+            let new_pre_middleware = PreMiddleware::new_with_boxed_handler(
+                format!("{}{}", path.as_str(), pre_middleware.path.as_str()),
+                pre_middleware
+                    .handler
+                    .take()
+                    .expect("No handler found in one of the pre-middlewares"),
+                pre_middleware.scope_depth + 1,
+            );
+            let priority = pre_middleware.priority;
+            let name = pre_middleware.name.clone();
+            let enabled = pre_middleware.enabled.clone();
+            let dependencies = pre_middleware.dependencies.clone();
+            builder = builder.and_then(move |mut inner| {
+                let mut new_pre_middleware = new_pre_middleware?;
+                new_pre_middleware.priority = priority;
+                new_pre_middleware.name = name;
+                new_pre_middleware.enabled = enabled;
+                new_pre_middleware.dependencies = dependencies;
+                inner.pre_middlewares.push(new_pre_middleware);
+                crate::Result::Ok(inner)
+            });
+        }
+
+        for route in router.routes.iter_mut() {
+            let handler = route.handler.take().expect("No handler found in one of the routes");
+            let new_route = match route.raw_regex {
+                Some(ref raw_regex) => Route::new_raw_with_boxed_handler(
+                    RawRegex {
+                        prefix: format!("{}{}", path.as_str(), raw_regex.prefix.as_str()),
+                        pattern: raw_regex.pattern.clone(),
+                    },
+                    route.methods.clone(),
+                    handler,
+                    route.scope_depth + 1,
+                ),
+                None => Route::new_with_boxed_handler(
+                    format!("{}{}", path.as_str(), route.path.as_str()),
+                    route.methods.clone(),
+                    handler,
+                    route.scope_depth + 1,
+                ),
+            };
+            let param_guards = route.param_guards.clone();
+            let skipped_middlewares = route.skipped_middlewares.clone();
+            let isolation_depth = route.isolation_depth.map(|depth| depth + 1);
+            let deprecation = route.deprecation.clone();
+            let guards = route.guards.clone();
+            let revalidators = route.revalidators.clone();
+            let route_types = route.route_types.clone();
+            builder = builder.and_then(move |mut inner| {
+                let mut new_route = new_route?;
+                new_route.param_guards = param_guards;
+                new_route.skipped_middlewares = skipped_middlewares;
+                new_route.isolation_depth = isolation_depth;
+                new_route.deprecation = deprecation;
+                new_route.guards = guards;
+                new_route.revalidators = revalidators;
+                new_route.route_types = route_types;
+                inner.routes.push(new_route);
+                crate::Result::Ok(inner)
+            });
+        }
+
+        for post_middleware in router.post_middlewares.iter_mut() {
+            let new_post_middleware = PostMiddleware::new_with_boxed_handler(
+                format!("{}{}", path.as_str(), post_middleware.path.as_str()),
+                post_middleware
+                    .handler
+                    .take()
+                    .expect("No handler found in one of the post-middlewares"),
+                post_middleware.scope_depth + 1,
+            );
+            let priority = post_middleware.priority;
+            let name = post_middleware.name.clone();
+            let enabled = post_middleware.enabled.clone();
+            let dependencies = post_middleware.dependencies.clone();
+            builder = builder.and_then(move |mut inner| {
+                let mut new_post_middleware = new_post_middleware?;
+                new_post_middleware.priority = priority;
+                new_post_middleware.name = name;
+                new_post_middleware.enabled = enabled;
+                new_post_middleware.dependencies = dependencies;
+                inner.post_middlewares.push(new_post_middleware);
+                crate::Result::Ok(inner)
+            });
+        }
+
+        for around_middleware in router.around_middlewares.iter_mut() {
+            let new_around_middleware = AroundMiddleware::new_with_boxed_handler(
+                format!("{}{}", path.as_str(), around_middleware.path.as_str()),
+                around_middleware
+                    .handler
+                    .take()
+                    .expect("No handler found in one of the around-middlewares"),
+                around_middleware.scope_depth + 1,
+            );
+            let priority = around_middleware.priority;
+            let name = around_middleware.name.clone();
+            let enabled = around_middleware.enabled.clone();
+            let dependencies = around_middleware.dependencies.clone();
+            builder = builder.and_then(move |mut inner| {
+                let mut new_around_middleware = new_around_middleware?;
+                new_around_middleware.priority = priority;
+                new_around_middleware.name = name;
+                new_around_middleware.enabled = enabled;
+                new_around_middleware.dependencies = dependencies;
+                inner.around_middlewares.push(new_around_middleware);
+                crate::Result::Ok(inner)
+            });
+        }
+
+        for response_headers in std::mem::take(&mut router.response_headers) {
+            let new_response_headers = ResponseHeaders::new(
+                format!("{}{}", path.as_str(), response_headers.path.as_str()),
+                response_headers.headers,
+                response_headers.scope_depth + 1,
+            );
+            builder = builder.and_then(move |mut inner| {
+                inner.response_headers.push(new_response_headers?);
+                crate::Result::Ok(inner)
+            });
+        }
+
+        for err_response_mapper in std::mem::take(&mut router.err_response_mappers) {
+            let new_err_response_mapper = ErrResponseMapper::new(
+                format!("{}{}", path.as_str(), err_response_mapper.path.as_str()),
+                err_response_mapper.handler,
+                err_response_mapper.scope_depth + 1,
+            );
+            builder = builder.and_then(move |mut inner| {
+                inner.err_response_mappers.push(new_err_response_mapper?);
+                crate::Result::Ok(inner)
+            });
+        }
+
+        for status_mapper in std::mem::take(&mut router.status_mappers) {
+            let new_status_mapper = StatusMapper::new(
+                format!("{}{}", path.as_str(), status_mapper.path.as_str()),
+                status_mapper.handler,
+                status_mapper.scope_depth + 1,
+            );
+            builder = builder.and_then(move |mut inner| {
+                inner.status_mappers.push(new_status_mapper?);
+                crate::Result::Ok(inner)
+            });
+        }
+
+        let startup_hooks = std::mem::take(&mut router.startup_hooks);
+        let shutdown_hooks = std::mem::take(&mut router.shutdown_hooks);
+        builder = builder.and_then(move |mut inner| {
+            inner.startup_hooks.extend(startup_hooks);
+            inner.shutdown_hooks.extend(shutdown_hooks);
+            crate::Result::Ok(inner)
+        });
+
+        for scoped_data_map in router.scoped_data_maps.iter_mut() {
+            let new_path = format!("{}{}", path.as_str(), scoped_data_map.path.as_str());
+            let data_map = Arc::try_unwrap(
+                scoped_data_map
+                    .data_map
+                    .take()
+                    .expect("No data map found in one of the scoped data maps"),
+            )
+            .expect("Non-zero owner of the shared data map in one of the scoped data maps");
+
+            builder = builder.and_then(move |mut inner| {
+                let data_maps = &mut inner.data_maps;
+
+                let data_map_arr = data_maps.get_mut(&new_path);
+                if let Some(data_map_arr) = data_map_arr {
+                    data_map_arr.push(data_map);
+                } else {
+                    data_maps.insert(new_path, vec![data_map]);
+                }
+
+                crate::Result::Ok(inner)
+            });
+        }
+
+        for scoped_config_map in router.scoped_config_maps.iter_mut() {
+            let new_path = format!("{}{}", path.as_str(), scoped_config_map.path.as_str());
+            let config_map = Arc::try_unwrap(
+                scoped_config_map
+                    .data_map
+                    .take()
+                    .expect("No config map found in one of the scoped config maps"),
+            )
+            .expect("Non-zero owner of the shared config map in one of the scoped config maps");
+
+            builder = builder.and_then(move |mut inner| {
+                inner.config_maps.entry(new_path).or_default().push(config_map);
+                crate::Result::Ok(inner)
+            });
+        }
+
+        builder
+    }
+}
+
+impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static>
+    RouterBuilder<B, E>
+{
+    /// Adds a single middleware. A pre middleware can be created by [`Middleware::pre`](./enum.Middleware.html#method.pre) method, a post
+    /// middleware can be created by [`Middleware::post`](./enum.Middleware.html#method.post) method and an around middleware can be
+    /// created by [`Middleware::around`](./enum.Middleware.html#method.around) method.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::{Router, Middleware};
+    /// use hyper::{Response, Request, Body};
+    /// use std::convert::Infallible;
+    ///
+    /// # fn run() -> Router<Body, Infallible> {
+    /// let router = Router::builder()
+    ///      // Create and attach a pre middleware.
+    ///      .middleware(Middleware::pre(|req| async move { /* Do some operations */ Ok(req) }))
+    ///      // Create and attach a post middleware.
+    ///      .middleware(Middleware::post(|res| async move { /* Do some operations */ Ok(res) }))
+    ///      .build()
+    ///      .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    pub fn middleware(self, m: Middleware<B, E>) -> Self {
+        self.and_then(move |mut inner| {
+            match m {
+                Middleware::Pre(middleware) => {
+                    inner.pre_middlewares.push(middleware);
+                }
+                Middleware::Post(middleware) => {
+                    inner.post_middlewares.push(middleware);
+                }
+                Middleware::Around(middleware) => {
+                    inner.around_middlewares.push(middleware);
+                }
+            }
+            crate::Result::Ok(inner)
+        })
+    }
+
+    /// Adds a single middleware with the specified priority. A pre middleware is denoted by [Middleware::Pre](./enum.Middleware.html#variant.Pre),
+    /// a post middleware is denoted by [Middleware::Post](./enum.Middleware.html#variant.Post) and an around middleware is denoted by
+    /// [Middleware::Around](./enum.Middleware.html#variant.Around).
+    ///
+    /// Middlewares are executed in the descending order of their priorities, and the middlewares with the same priority are
+    /// executed in the registration order. The middlewares added by the [`middleware`](#method.middleware) method have
+    /// the priority `0`. Priorities are kept when a router is mounted by the [`scope`](#method.scope) method, so they
+    /// also order the middlewares across the scopes.
+    ///
+    /// The computed execution order can be inspected by the [`Router::middleware_order`](./struct.Router.html#method.middleware_order) method.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::{Router, Middleware};
+    /// use hyper::{Request, Body, Method};
+    /// use std::convert::Infallible;
+    ///
+    /// # fn run() -> Router<Body, Infallible> {
+    /// let router = Router::builder()
+    ///      .middleware(Middleware::pre_with_path("/api/*", |req| async move { /* Auth */ Ok(req) }).unwrap())
+    ///      // Runs before the auth middleware although it's added later.
+    ///      .middleware_with_priority(Middleware::pre(|req| async move { /* CORS */ Ok(req) }), 10)
+    ///      .build()
+    ///      .unwrap();
+    ///
+    /// let order = router.middleware_order(&Method::GET, "/api/users");
+    /// assert_eq!(order[0].path(), "/*");
+    /// assert_eq!(order[1].path(), "/api/*");
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    pub fn middleware_with_priority(self, m: Middleware<B, E>, priority: i32) -> Self {
+        let m = match m {
+            Middleware::Pre(mut middleware) => {
+                middleware.priority = priority;
+                Middleware::Pre(middleware)
+            }
+            Middleware::Post(mut middleware) => {
+                middleware.priority = priority;
+                Middleware::Post(middleware)
+            }
+            Middleware::Around(mut middleware) => {
+                middleware.priority = priority;
+                Middleware::Around(middleware)
+            }
+        };
+        self.middleware(m)
+    }
+
+    /// Isolates the routes of this router from the middlewares of the routers it's [scoped](#method.scope) into, e.g.
+    /// so a mounted metrics or webhook scope isn't subject to the auth and the logging middlewares of the app.
+    ///
+    /// Only the middlewares added to this router and to the routers scoped into it are executed for its routes. The
+    /// requests to its prefix which don't match any of its routes still go through the middlewares of the parent
+    /// routers. It has no effect on the root router.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::{Router, Middleware};
+    /// use hyper::{Response, Body};
+    /// use std::convert::Infallible;
+    ///
+    /// # fn run() -> Router<Body, Infallible> {
+    /// let metrics: Router<Body, Infallible> = Router::builder()
+    ///     .isolate_middleware()
+    ///     .get("/", |_| async move { Ok(Response::new(Body::from("requests_total 42"))) })
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// let router = Router::builder()
+    ///     // Isn't executed for the `/metrics` route.
+    ///     .middleware(Middleware::pre(|req| async move { /* Authenticate */ Ok(req) }))
+    ///     .scope("/metrics", metrics)
+    ///     .build()
+    ///     .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    pub fn isolate_middleware(self) -> Self {
+        self.and_then(move |mut inner| {
+            inner.isolate_middleware = true;
+            crate::Result::Ok(inner)
+        })
+    }
+
+    /// Sets a config of this router's scope, which the middlewares get by the
+    /// [`RequestExt::scope_config`](./ext/trait.RequestExt.html#tymethod.scope_config) method, e.g. to apply the same
+    /// rate limiter middleware with a different limit per scope.
+    ///
+    /// Unlike the [`data`](#method.data), a request only sees the config of its innermost scope which has one of the
+    /// type. The configs are kept apart from the data, and they are resolved per route once the router is served.
+    ///
+    /// Please refer to the [`RequestExt::scope_config`](./ext/trait.RequestExt.html#tymethod.scope_config) method for
+    /// an example.
+    pub fn config<T: Send + Sync + 'static>(self, config: T) -> Self {
+        self.and_then(move |mut inner| {
+            let config_map_arr = inner.config_maps.entry("/*".to_owned()).or_default();
+            match config_map_arr.first_mut() {
+                Some(config_map) => config_map.insert(config),
+                None => {
+                    let mut config_map = DataMap::new();
+                    config_map.insert(config);
+                    config_map_arr.push(config_map);
+                }
+            }
+            crate::Result::Ok(inner)
+        })
+    }
+
+    /// Specify app data to be shared across route handlers, middlewares and the error handler.
+    ///
+    /// Please refer to the [Data and State Sharing](./index.html#data-and-state-sharing) for more info.
+    pub fn data<T: Send + Sync + 'static>(self, data: T) -> Self {
+        self.and_then(move |mut inner| {
+            let data_maps = &mut inner.data_maps;
+
+            let data_map_arr = data_maps.get_mut(&"/*".to_owned());
+            if let Some(data_map_arr) = data_map_arr {
+                let first_data_map = data_map_arr.get_mut(0).unwrap();
+                first_data_map.insert(data);
+            } else {
+                let mut data_map = DataMap::new();
+                data_map.insert(data);
+                data_maps.insert("/*".to_owned(), vec![data_map]);
+            }
+
+            crate::Result::Ok(inner)
+        })
+    }
+
+    /// Adds a hook which runs before the router starts serving requests, e.g. to warm the caches.
+    ///
+    /// The hooks run in the registration order, and the hooks of a scoped router run at the position where it's mounted
+    /// by the [`scope`](#method.scope) method. They are run by the [`Lifecycle`](./struct.Lifecycle.html) handle of the
+    /// [`RouterService`](./struct.RouterService.html) or the [`RequestServiceBuilder`](./struct.RequestServiceBuilder.html).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::Router;
+    /// use hyper::Body;
+    ///
+    /// # fn run() -> Router<Body, routerify::Error> {
+    /// let router = Router::builder()
+    ///     .on_startup(|| async move {
+    ///         println!("Warming the caches");
+    ///         Ok(())
+    ///     })
+    ///     .build()
+    ///     .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    pub fn on_startup<H, R>(self, hook: H) -> Self
+    where
+        H: Fn() -> R + Send + Sync + 'static,
+        R: Future<Output = Result<(), E>> + Send + 'static,
+    {
+        let hook: LifecycleHook = Box::new(move || {
+            let fut = hook();
+            Box::pin(async move { fut.await.map_err(into_route_error) })
+        });
+
+        self.and_then(move |mut inner| {
+            inner.startup_hooks.push(hook);
+            crate::Result::Ok(inner)
+        })
+    }
+
+    /// Adds a hook which runs after the router stops serving requests, e.g. to close the database pools.
+    ///
+    /// The shutdown hooks run in the reverse order of the startup hooks i.e. the hook which is added last runs first.
+    /// Please refer to the [`on_startup`](#method.on_startup) method for more info.
+    pub fn on_shutdown<H, R>(self, hook: H) -> Self
+    where
+        H: Fn() -> R + Send + Sync + 'static,
+        R: Future<Output = Result<(), E>> + Send + 'static,
+    {
+        let hook: LifecycleHook = Box::new(move || {
+            let fut = hook();
+            Box::pin(async move { fut.await.map_err(into_route_error) })
+        });
+
+        self.and_then(move |mut inner| {
+            inner.shutdown_hooks.push(hook);
+            crate::Result::Ok(inner)
+        })
+    }
+
+    /// Makes the default error handler respond with the status code of the error's
+    /// [class](./ext/trait.RouteErrorExt.html#tymethod.classify) e.g. `413 Payload Too Large` for a too large body
+    /// or `400 Bad Request` for a deserialize failure, instead of `500 Internal Server Error`.
+    ///
+    /// It has no effect if an error handler is added to the router or the body type is not `hyper::Body`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::Router;
+    /// use hyper::{Response, Body};
+    ///
+    /// # fn run() -> Router<Body, std::num::ParseIntError> {
+    /// let router = Router::builder()
+    ///     .classify_errors()
+    ///     // Responds with `400 Bad Request` for the non-numeric ids.
+    ///     .get("/users/:id", |req| async move {
+    ///         let id = "abc".parse::<u64>()?;
+    ///         Ok(Response::new(Body::from(format!("User: {}", id))))
+    ///     })
+    ///     .build()
+    ///     .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    pub fn classify_errors(self) -> Self {
+        self.and_then(move |mut inner| {
+            inner.classify_errors = true;
+            crate::Result::Ok(inner)
+        })
+    }
+
+    /// Makes the default error handler render a detailed HTML page with the error chain, the report of `anyhow` and `eyre`
+    /// errors, the matched route and the request metadata. It should only be enabled during development, as the page
+    /// may leak sensitive information. Without it, the default error handler responds with a terse plain text message.
+    ///
+    /// It has no effect if an error handler is added to the router or the body type is not `hyper::Body`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::Router;
+    /// use hyper::{Response, Body};
+    ///
+    /// # fn run() -> Router<Body, routerify::Error> {
+    /// let router = Router::builder()
+    ///     .debug_errors(cfg!(debug_assertions))
+    ///     .get("/", |_| async move { Ok(Response::new(Body::from("Home"))) })
+    ///     .build()
+    ///     .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    pub fn debug_errors(self, enabled: bool) -> Self {
+        self.and_then(move |mut inner| {
+            inner.debug_errors = enabled;
+            crate::Result::Ok(inner)
+        })
+    }
+
+    /// Makes the default `404` route and the default error handler respond with the `application/problem+json` bodies
+    /// of RFC 7807.
+    ///
+    /// The problem details of an [`HttpError`](./struct.HttpError.html) created from a
+    /// [`ProblemDetails`](./struct.ProblemDetails.html) are sent as they are. The other errors get a problem with their
+    /// status code, and with their message as the detail only if it's a client error, so the internal errors aren't
+    /// leaked. If the problem has no `instance` and the request has an `x-request-id` header, the instance is set to
+    /// `urn:request-id:<id>`, so the problem can be correlated with the logs.
+    ///
+    /// It takes precedence over the [`ResponseTemplates`](./responses/struct.ResponseTemplates.html) but not over the
+    /// [`debug_errors`](#method.debug_errors) option. It has no effect on the errors if an error handler is added to the
+    /// router, or at all if the body type is not `hyper::Body`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::{HttpError, ProblemDetails, Router, RouteError};
+    /// use hyper::{Response, Body, StatusCode};
+    ///
+    /// # fn run() -> Router<Body, RouteError> {
+    /// let router = Router::builder()
+    ///     .problem_details()
+    ///     .post("/transfers", |_| async move {
+    ///         let problem = ProblemDetails::new(StatusCode::FORBIDDEN)
+    ///             .with_type("https://example.com/probs/out-of-credit")
+    ///             .with_json_extension("balance", "30");
+    ///         Err::<Response<Body>, _>(HttpError::from(problem).into())
+    ///     })
+    ///     .build()
+    ///     .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    pub fn problem_details(self) -> Self {
+        self.and_then(move |mut inner| {
+            inner.problem_details = true;
+            crate::Result::Ok(inner)
+        })
+    }
+
+    /// Enables a bounded cache of the route matches, keyed by the request method and path.
+    ///
+    /// Repeated requests to the same URL, typical of health checks and static assets, skip the regex evaluation. Once
+    /// `capacity` entries are cached, the least recently used one is evicted. The cache belongs to the built router, so
+    /// swapping the router discards it. It only takes effect on the root router.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::Router;
+    /// use hyper::{Response, Body};
+    ///
+    /// # fn run() -> Router<Body, routerify::Error> {
+    /// let router = Router::builder()
+    ///     .match_cache(1024)
+    ///     .get("/health", |_| async move { Ok(Response::new(Body::from("OK"))) })
+    ///     .build()
+    ///     .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    pub fn match_cache(self, capacity: usize) -> Self {
+        self.and_then(move |mut inner| {
+            inner.match_cache = Some(capacity);
+            crate::Result::Ok(inner)
+        })
+    }
+
+    /// Serves the app under a path prefix e.g. when it's deployed behind an ingress controller which routes by the path
+    /// and doesn't strip it.
+    ///
+    /// The prefix is stripped from the request path before the routes and the middlewares are matched, so they're added
+    /// without it. The requests outside of the prefix, e.g. the health checks of the orchestrator which bypass the
+    /// ingress, are matched by their full path. The request URI is left as is.
+    ///
+    /// The prefix of a request is returned by the [`RequestExt::base_path`](./ext/trait.RequestExt.html#tymethod.base_path)
+    /// method and it's prepended to the links by the [`RequestExt::url_for`](./ext/trait.RequestExt.html#tymethod.url_for)
+    /// method and the [`responses::redirect`](./responses/fn.redirect.html) helper. It only takes effect on the root
+    /// router.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::Router;
+    /// use routerify::prelude::*;
+    /// use hyper::{Response, Body};
+    ///
+    /// # fn run() -> Router<Body, routerify::Error> {
+    /// let router = Router::builder()
+    ///     .base_path("/myapp")
+    ///     // Serves the `/myapp/users` path.
+    ///     .get("/users", |req| async move {
+    ///         Ok(Response::new(Body::from(format!("<a href=\"{}\">Home</a>", req.url_for("/")))))
+    ///     })
+    ///     .build()
+    ///     .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    pub fn base_path<P: Into<String>>(self, path: P) -> Self {
+        self.and_then(move |mut inner| {
+            let path = path.into();
+            if !path.starts_with('/') {
+                return Err(crate::Error::new(format!("The base path must start with a slash: {:?}", path)).into());
+            }
+            let path = path.trim_end_matches('/');
+            inner.base_path = if path.is_empty() { None } else { Some(Arc::from(path)) };
+            crate::Result::Ok(inner)
+        })
+    }
+
+    /// Sends the [fingerprint](./struct.Router.html#method.fingerprint) of the route table in the specified header of
+    /// every response, e.g. `x-router-fingerprint`, so the operators can verify which route configuration a running
+    /// instance serves. It only takes effect on the root router.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::Router;
+    /// use hyper::{Response, Body};
+    ///
+    /// # fn run() -> Router<Body, routerify::Error> {
+    /// let router = Router::builder()
+    ///     .fingerprint_header("x-router-fingerprint")
+    ///     .get("/", |_| async move { Ok(Response::new(Body::from("Home"))) })
+    ///     .build()
+    ///     .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    pub fn fingerprint_header(self, name: &str) -> Self {
+        self.and_then(move |mut inner| {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| crate::Error::new(format!("Invalid fingerprint header name: {:?}", name)))?;
+            inner.fingerprint_header = Some(name);
+            crate::Result::Ok(inner)
+        })
+    }
+
+    /// Sends the structured events of the requests, e.g. the matched routes and the sent responses, to the observer.
+    /// It also catches the panics of the middlewares and the route handlers, as the [`catch_panics`](#method.catch_panics)
+    /// method does, and reports them. It only takes effect on the root router.
+    ///
+    /// Please refer to the [`events`](./events/index.html) module documentation for an example.
+    pub fn observer(self, observer: Observer) -> Self {
+        self.and_then(move |mut inner| {
+            inner.observer = Some(observer);
+            crate::Result::Ok(inner)
+        })
+    }
+
+    /// Catches the panics of the middlewares and the route handlers, and answers them by the error handler as internal
+    /// errors instead of closing the connection. It's disabled by default, unless an [`observer`](#method.observer) is
+    /// added. It only takes effect on the root router.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::Router;
+    /// use hyper::Body;
+    ///
+    /// # fn run() -> Router<Body, routerify::Error> {
+    /// let router = Router::builder()
+    ///     .catch_panics(true)
+    ///     .get("/", |_| async move { panic!("Not implemented") })
+    ///     .build()
+    ///     .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    pub fn catch_panics(self, enabled: bool) -> Self {
+        self.and_then(move |mut inner| {
+            inner.catch_panics = enabled;
+            crate::Result::Ok(inner)
+        })
+    }
+
+    /// Adds a hook which runs before the route matching, e.g. to rewrite the request URI so the request is routed by
+    /// its new path. The hooks run in the order they are added. It only takes effect on the root router.
+    ///
+    /// The hooks get the request as it's received, so its URI still has the
+    /// [base path](#method.base_path) if the router has one. The base path is returned by the
+    /// [`RequestExt::base_path`](./ext/trait.RequestExt.html#tymethod.base_path) method, and a rewritten URI must keep
+    /// it to match the routes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::Router;
+    /// use routerify::prelude::*;
+    /// use hyper::{Response, Body, Uri};
+    /// use std::convert::Infallible;
+    ///
+    /// # fn run() -> Router<Body, Infallible> {
+    /// let router = Router::builder()
+    ///     .base_path("/app")
+    ///     // Serves the requests of the old index page by the home page.
+    ///     .pre_match(|req| {
+    ///         let base_path = req.base_path().unwrap_or("").to_owned();
+    ///         if req.uri().path().strip_prefix(base_path.as_str()) == Some("/index.html") {
+    ///             *req.uri_mut() = format!("{}/", base_path).parse::<Uri>().unwrap();
+    ///         }
+    ///     })
+    ///     .get("/", |_| async move { Ok(Response::new(Body::from("Home"))) })
+    ///     .build()
+    ///     .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    pub fn pre_match<F>(self, hook: F) -> Self
+    where
+        F: Fn(&mut Request<hyper::Body>) + Send + Sync + 'static,
+    {
+        self.and_then(move |mut inner| {
+            inner.pre_match_hooks.push(Box::new(hook));
+            crate::Result::Ok(inner)
+        })
+    }
+
+    /// Rewrites the request URIs by the rules before the route matching, so the legacy URL schemes are served by the
+    /// current routes without duplicate handlers. The rules match the path after the base path, which the rewritten
+    /// URI keeps. It only takes effect on the root router.
+    ///
+    /// Please refer to the [`rewrite`](./middleware/rewrite/index.html) module documentation for an example.
+    pub fn rewrite(self, rewrite: Rewrite) -> Self {
+        self.pre_match(move |req| {
+            rewrite.apply(req);
+        })
+    }
+
+    /// Matches the routes with a `matchit` radix tree instead of the regex backend.
+    ///
+    /// Only the routes whose path segments are either literal or a single required param e.g. `/users/:id` are moved to
+    /// the tree, the others such as the wildcard, the optional param and the raw regex routes are still matched by the
+    /// regex backend, and so are the routes whose paths overlap another one e.g. `/users/me` and `/users/:id`, so the
+    /// routes are tried in the registration order as usual and match the same requests as with the regex backend. The
+    /// middlewares and the data maps are always matched by the regex backend.
+    ///
+    /// It only takes effect on the root router.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::Router;
+    /// use hyper::{Response, Body};
+    ///
+    /// # fn run() -> Router<Body, routerify::Error> {
+    /// let router = Router::builder()
+    ///     .fast_match(true)
+    ///     .get("/users/:id", |_| async move { Ok(Response::new(Body::from("User"))) })
+    ///     .build()
+    ///     .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    #[cfg(feature = "fast-match")]
+    pub fn fast_match(self, enabled: bool) -> Self {
+        self.and_then(move |mut inner| {
+            inner.fast_match = enabled;
+            crate::Result::Ok(inner)
+        })
+    }
+
+    /// Adds a handler to handle any error raised by the routes or any middlewares. Please refer to [Error Handling](./index.html#error-handling) section
+    /// for more info.
+    pub fn err_handler<H, R>(self, handler: H) -> Self
+    where
+        H: Fn(crate::RouteError) -> R + Send + Sync + 'static,
+        R: Future<Output = Response<B>> + Send + 'static,
+    {
+        let handler: ErrHandlerWithoutInfo<B> = Box::new(move |err: crate::RouteError| Box::new(handler(err)));
+
+        self.and_then(move |mut inner| {
+            inner.err_handler = Some(ErrHandler::WithoutInfo(handler));
+            crate::Result::Ok(inner)
+        })
+    }
+
+    /// Adds a handler to handle any error raised by the routes or any middlewares.
+    ///
+    /// Here, the handler also access [request info](./struct.RequestInfo.html) e.g. headers, method, uri etc to generate response based on the request information.
+    ///
+    /// Please refer to [Error Handling](./index.html#error-handling) section
+    /// for more info.
+    pub fn err_handler_with_info<H, R>(self, handler: H) -> Self
+    where
+        H: Fn(crate::RouteError, RequestInfo) -> R + Send + Sync + 'static,
+        R: Future<Output = Response<B>> + Send + 'static,
+    {
+        let handler: ErrHandlerWithInfo<B> =
             Box::new(move |err: crate::RouteError, req_info: RequestInfo| Box::new(handler(err, req_info)));
 
         self.and_then(move |mut inner| {
@@ -730,6 +2011,341 @@ impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Se
             crate::Result::Ok(inner)
         })
     }
+
+    /// Rewrites the error responses, i.e. the `4xx` and the `5xx` ones, of all the routes of the router into a consistent
+    /// envelope. It applies to the responses built by the route handlers and the middlewares as well as the ones of the
+    /// error handler and the default `404 Not Found` route, once the post middlewares ran.
+    ///
+    /// It's meant for the routers mounted by the [`scope`](#method.scope) method: only the mapper of the innermost scope
+    /// matching the request path is called, with the status code and the request info, and its response is sent instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::Router;
+    /// use hyper::{header, Response, Body, StatusCode};
+    ///
+    /// # fn run() -> Router<Body, routerify::Error> {
+    /// let api = Router::builder()
+    ///     .get("/users/:id", |_| async move {
+    ///         Ok(Response::builder().status(StatusCode::BAD_REQUEST).body(Body::from("Bad id")).unwrap())
+    ///     })
+    ///     .map_err_responses(|status: StatusCode, req_info| async move {
+    ///         let body = format!(r#"{{"error":{{"status":{},"path":"{}"}}}}"#, status.as_u16(), req_info.uri().path());
+    ///         Response::builder()
+    ///             .status(status)
+    ///             .header(header::CONTENT_TYPE, "application/json")
+    ///             .body(Body::from(body))
+    ///             .unwrap()
+    ///     })
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// let router = Router::builder()
+    ///     .scope("/api", api)
+    ///     .build()
+    ///     .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    pub fn map_err_responses<H, R>(self, handler: H) -> Self
+    where
+        H: Fn(StatusCode, RequestInfo) -> R + Send + Sync + 'static,
+        R: Future<Output = Response<B>> + Send + 'static,
+    {
+        let handler: ErrResponseMapperHandler<B> =
+            Box::new(move |status: StatusCode, req_info: RequestInfo| Box::new(handler(status, req_info)));
+
+        self.and_then(move |mut inner| {
+            inner
+                .err_response_mappers
+                .push(ErrResponseMapper::new("/*".to_owned(), handler, 0)?);
+            crate::Result::Ok(inner)
+        })
+    }
+
+    /// Maps the response statuses of the routes of this router, after the post middlewares and before the error
+    /// responses are mapped, e.g. to answer the unknown paths of a private scope with `403 Forbidden` instead of
+    /// `404 Not Found`. The mapper gets the status and the [`RouteMeta`](./struct.RouteMeta.html) of the request.
+    ///
+    /// The mappers of the inner scopes run first and each one gets the status of the previous one, so a policy of the
+    /// root router is applied last.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::Router;
+    /// use hyper::{Response, Body, StatusCode};
+    ///
+    /// # fn run() -> Router<Body, routerify::Error> {
+    /// let private = Router::builder()
+    ///     .get("/reports", |_| async move { Ok(Response::new(Body::from("Reports"))) })
+    ///     .map_status(|status, _| match status {
+    ///         StatusCode::NOT_FOUND => StatusCode::FORBIDDEN,
+    ///         status => status,
+    ///     })
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// let router = Router::builder()
+    ///     .scope("/private", private)
+    ///     .build()
+    ///     .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    pub fn map_status<F>(self, mapper: F) -> Self
+    where
+        F: Fn(StatusCode, &RouteMeta<'_>) -> StatusCode + Send + Sync + 'static,
+    {
+        let handler: StatusMapperHandler = Arc::new(mapper);
+        self.and_then(move |mut inner| {
+            inner
+                .status_mappers
+                .push(StatusMapper::new("/*".to_owned(), handler, 0)?);
+            crate::Result::Ok(inner)
+        })
+    }
+
+    /// Adds a handler to handle any error raised by the routes or any middlewares.
+    ///
+    /// Here, the handler also access the [error context](./struct.ErrorContext.html) e.g. the request id or the
+    /// authenticated principal put into the request context by the middlewares, to generate response based on them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::{Router, ErrorContext, Principal};
+    /// use hyper::{Response, Body, StatusCode};
+    /// use std::convert::Infallible;
+    ///
+    /// #[derive(Clone)]
+    /// struct RequestId(String);
+    ///
+    /// # fn run() -> Router<Body, Infallible> {
+    /// let router = Router::builder()
+    ///     .err_handler_with_ctx(|err, ctx: ErrorContext| async move {
+    ///         let request_id = ctx.get::<RequestId>().map(|id| id.0).unwrap_or_default();
+    ///         let user = ctx.get::<Principal>().map(|p| p.id().to_owned());
+    ///         eprintln!("{} failed after {:?} for {:?}: {}", request_id, ctx.elapsed(), user, err);
+    ///
+    ///         Response::builder()
+    ///             .status(StatusCode::INTERNAL_SERVER_ERROR)
+    ///             .header("x-request-id", request_id)
+    ///             .body(Body::from("Something went wrong"))
+    ///             .unwrap()
+    ///     })
+    ///     .build()
+    ///     .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    pub fn err_handler_with_ctx<H, R>(self, handler: H) -> Self
+    where
+        H: Fn(crate::RouteError, ErrorContext) -> R + Send + Sync + 'static,
+        R: Future<Output = Response<B>> + Send + 'static,
+    {
+        let handler: ErrHandlerWithInfo<B> = Box::new(move |err: crate::RouteError, req_info: RequestInfo| {
+            Box::new(handler(err, ErrorContext::new(req_info)))
+        });
+
+        self.and_then(move |mut inner| {
+            inner.err_handler = Some(ErrHandler::WithInfo(handler));
+            crate::Result::Ok(inner)
+        })
+    }
+}
+
+impl<E: From<crate::HttpError> + Into<Box<dyn std::error::Error + Send + Sync>> + 'static>
+    RouterBuilder<hyper::Body, E>
+{
+    /// Adds a new route with `POST` method at the specified path which stores the files of the `multipart/form-data`
+    /// requests into the storage of the config, and responds with their metadata as JSON.
+    ///
+    /// The rejected requests fail with [`HttpError`](./struct.HttpError.html)s, which are handled by the error handler.
+    /// Please refer to the [`UploadConfig`](./uploads/struct.UploadConfig.html) for more info.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::{Router, RouteError};
+    /// use routerify::uploads::{LocalStorage, UploadConfig};
+    /// use hyper::Body;
+    /// use std::sync::Arc;
+    ///
+    /// # fn run() -> Router<Body, RouteError> {
+    /// let router = Router::builder()
+    ///     .upload(
+    ///         "/files",
+    ///         UploadConfig {
+    ///             max_size: 10 * 1024 * 1024,
+    ///             allowed_types: vec!["image/*".to_owned(), "application/pdf".to_owned()],
+    ///             storage: Arc::new(LocalStorage::new(std::env::temp_dir())),
+    ///         },
+    ///     )
+    ///     .build()
+    ///     .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    #[cfg(feature = "multipart")]
+    pub fn upload<P: Into<String>>(self, path: P, config: crate::uploads::UploadConfig) -> Self {
+        let config = Arc::new(config);
+        self.post(path, move |req| {
+            let config = config.clone();
+            async move { config.handle(req).await.map_err(E::from) }
+        })
+    }
+
+    /// Adds a new route with `GET` and `HEAD` methods which serves the embedded assets under the specified path, e.g.
+    /// the `css/site.css` asset at `/assets/css/site.css` for the `/assets` path.
+    ///
+    /// The responses have the content types guessed from the file names and the entity tags of the content hashes,
+    /// and the pre-compressed `.br` and `.gz` variants of the assets are served to the clients accepting them. A
+    /// missing asset fails with an [`HttpError`](./struct.HttpError.html) of status `404 Not Found`. Please refer to the
+    /// [`embedded`](./responses/fn.embedded.html) function for more info.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::{Router, RouteError};
+    /// use include_dir::{include_dir, Dir};
+    /// use hyper::Body;
+    ///
+    /// static ASSETS: Dir = include_dir!("$CARGO_MANIFEST_DIR/examples");
+    ///
+    /// # fn run() -> Router<Body, RouteError> {
+    /// let router = Router::builder()
+    ///     .serve_embedded("/assets", &ASSETS)
+    ///     .build()
+    ///     .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    #[cfg(feature = "embed")]
+    pub fn serve_embedded<P, A>(self, path: P, assets: &'static A) -> Self
+    where
+        P: Into<String>,
+        A: crate::responses::EmbeddedAssets,
+    {
+        let path = format!("{}/*", path.into().trim_end_matches('/'));
+        self.add(path, vec![Method::GET, Method::HEAD], move |req| async move {
+            let path = crate::helpers::wildcard_path(&req);
+            crate::responses::embedded(assets, path, req.headers()).map_err(E::from)
+        })
+    }
+
+    /// Adds a new route with `GET` and `HEAD` methods which serves a single page application under the specified path,
+    /// i.e. the files of its directory and the index file for any other path, so the client side routes can be
+    /// reloaded and shared. It requires the `fs` feature.
+    ///
+    /// The routes of an API under the same path must be added to the router too, as they take precedence over the
+    /// fallback, and their prefix excluded from it. Please refer to the [`Spa`](./responses/struct.Spa.html) for more
+    /// info.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::{Router, RouteError};
+    /// use routerify::responses::Spa;
+    /// use hyper::Body;
+    ///
+    /// # fn run() -> Router<Body, RouteError> {
+    /// let router = Router::builder()
+    ///     .spa("/app", Spa::new("dist").index("index.html").exclude("/app/api"))
+    ///     .build()
+    ///     .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    #[cfg(feature = "fs")]
+    pub fn spa<P: Into<String>>(self, path: P, spa: crate::responses::Spa) -> Self {
+        let spa = Arc::new(spa);
+        let path = format!("{}/*", path.into().trim_end_matches('/'));
+        self.add(path, vec![Method::GET, Method::HEAD], move |req| {
+            let spa = spa.clone();
+            async move { spa.handle(&req).await.map_err(E::from) }
+        })
+    }
+
+    /// Adds a new route with `GET` and `HEAD` methods which serves the files of a directory under the specified path,
+    /// and the listings of its subdirectories if they are enabled. It requires the `fs` feature. Please refer to the
+    /// [`StaticDir`](./responses/struct.StaticDir.html) for more info.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::{Router, RouteError};
+    /// use routerify::responses::StaticDir;
+    /// use hyper::Body;
+    ///
+    /// # fn run() -> Router<Body, RouteError> {
+    /// let router = Router::builder()
+    ///     .serve_dir("/downloads", StaticDir::new("public/downloads").listings(true))
+    ///     .build()
+    ///     .unwrap();
+    /// # router
+    /// # }
+    /// # run();
+    /// ```
+    #[cfg(feature = "fs")]
+    pub fn serve_dir<P: Into<String>>(self, path: P, dir: crate::responses::StaticDir) -> Self {
+        let dir = Arc::new(dir);
+        let path = format!("{}/*", path.into().trim_end_matches('/'));
+        self.add(path, vec![Method::GET, Method::HEAD], move |req| {
+            let dir = dir.clone();
+            async move { dir.handle(req).await.map_err(E::from) }
+        })
+    }
+}
+
+impl<E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static> RouterBuilder<hyper::Body, E> {
+    /// Keeps the router unavailable until the tasks of the warmup complete. It adds a middleware which responds with
+    /// `503 Service Unavailable` to the requests meanwhile, and a startup hook which starts the tasks in the background.
+    ///
+    /// Please refer to the [`warmup`](./warmup/index.html) module documentation for an example.
+    pub fn warmup(self, warmup: Warmup) -> Self {
+        let hook_warmup = warmup.clone();
+        let hook: LifecycleHook = Box::new(move || {
+            hook_warmup.start();
+            Box::pin(async move { Ok(()) })
+        });
+
+        self.middleware(warmup.middleware()).and_then(move |mut inner| {
+            inner.startup_hooks.push(hook);
+            crate::Result::Ok(inner)
+        })
+    }
+
+    /// Attaches a revalidator to the routes at the specified path, of any method, which is either a route path or a
+    /// `<prefix>/*` path covering all the routes under the prefix. For a `GET` or a `HEAD` request, the revalidators
+    /// run in the order they are attached after the guards, and the first one which returns a validator answers the
+    /// request with `304 Not Modified` if the client has the current representation, without calling the handler.
+    /// The routes can be added before or after this call, but the build fails if there is none.
+    ///
+    /// Please refer to the [`revalidate`](./revalidate/index.html) module documentation for an example.
+    pub fn revalidate<P: Into<String>, R: Revalidator>(self, path: P, revalidator: R) -> Self {
+        self.and_then(move |mut inner| {
+            let mut path = path.into();
+
+            if !path.ends_with('/') && !path.ends_with('*') {
+                path.push('/');
+            }
+
+            let revalidator = RouteRevalidator {
+                revalidator: Arc::new(revalidator),
+                not_modified: revalidate::not_modified,
+            };
+            inner.revalidators.push((path, revalidator));
+            crate::Result::Ok(inner)
+        })
+    }
 }
 
 impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static> Default
@@ -741,9 +2357,47 @@ impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Se
                 pre_middlewares: Vec::new(),
                 routes: Vec::new(),
                 post_middlewares: Vec::new(),
+                around_middlewares: Vec::new(),
                 data_maps: HashMap::new(),
+                config_maps: HashMap::new(),
                 err_handler: None,
+                param_guards: Vec::new(),
+                deprecations: Vec::new(),
+                guards: Vec::new(),
+                revalidators: Vec::new(),
+                route_types: Vec::new(),
+                classify_errors: false,
+                debug_errors: false,
+                problem_details: false,
+                match_cache: None,
+                #[cfg(feature = "fast-match")]
+                fast_match: false,
+                base_path: None,
+                isolate_middleware: false,
+                fingerprint_header: None,
+                response_headers: Vec::new(),
+                err_response_mappers: Vec::new(),
+                status_mappers: Vec::new(),
+                observer: None,
+                catch_panics: false,
+                pre_match_hooks: Vec::new(),
+                startup_hooks: Vec::new(),
+                shutdown_hooks: Vec::new(),
             }),
         }
     }
 }
+
+fn into_scoped_data_maps(data_maps: HashMap<String, Vec<DataMap>>) -> crate::Result<Vec<ScopedDataMap>> {
+    let mut scoped_data_maps = data_maps
+        .into_iter()
+        .flat_map(|(path, data_map_arr)| {
+            data_map_arr
+                .into_iter()
+                .map(move |data_map| ScopedDataMap::new(path.clone(), Arc::new(data_map)))
+        })
+        .collect::<Result<Vec<ScopedDataMap>, crate::RouteError>>()?;
+    // The data of an inner scope takes precedence over the data of the outer ones.
+    scoped_data_maps.sort_by_key(|scoped_data_map| Reverse(scoped_data_map.path.len()));
+    Ok(scoped_data_maps)
+}
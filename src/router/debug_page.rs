@@ -0,0 +1,88 @@
+use crate::ext::RouteErrorExt;
+use crate::helpers::html_escape as escape;
+use crate::types::{Redaction, RequestInfo};
+use crate::RouteError;
+use hyper::StatusCode;
+use std::fmt::Write;
+
+// Renders the detailed error page of the default error handler in the debug mode.
+pub(crate) fn render(err: &RouteError, status: StatusCode, req_info: &RequestInfo, fingerprint: &str) -> String {
+    let mut page = String::new();
+    let title = format!("{} {}", status.as_u16(), status.canonical_reason().unwrap_or_default());
+
+    let _ = write!(
+        page,
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{}</title></head>\n<body>\n<h1>{}</h1>\n",
+        escape(&title),
+        escape(&title)
+    );
+
+    page.push_str("<h2>Error</h2>\n<ol>\n");
+    for cause in err.chain() {
+        let _ = writeln!(page, "<li>{}</li>", escape(&cause.to_string()));
+    }
+    page.push_str("</ol>\n");
+
+    if let Some(report) = err.report() {
+        let _ = write!(page, "<h2>Report</h2>\n<pre>{}</pre>\n", escape(report));
+    }
+
+    let default_redaction = Redaction::default();
+    let redaction = req_info.data::<Redaction>().unwrap_or(&default_redaction);
+
+    page.push_str("<h2>Request</h2>\n<table>\n");
+    let route_path = req_info.route_path().unwrap_or("-");
+    for (name, val) in [
+        ("Route", route_path.to_owned()),
+        ("Method", req_info.method().to_string()),
+        ("URI", redaction.redact_uri(req_info.uri(), req_info.params())),
+        ("Version", format!("{:?}", req_info.version())),
+        ("Router", fingerprint.to_owned()),
+    ] {
+        let _ = writeln!(page, "<tr><th>{}</th><td>{}</td></tr>", name, escape(&val));
+    }
+    page.push_str("</table>\n");
+
+    page.push_str("<h2>Headers</h2>\n<table>\n");
+    for (name, val) in req_info.headers() {
+        let _ = writeln!(
+            page,
+            "<tr><th>{}</th><td>{}</td></tr>",
+            escape(name.as_str()),
+            escape(&redaction.header_value(name, val))
+        );
+    }
+    page.push_str("</table>\n</body>\n</html>\n");
+
+    page
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::RequestContext;
+    use hyper::{Body, Request};
+
+    #[test]
+    fn renders_escaped_error_details() {
+        let req = Request::builder()
+            .uri("/users/1")
+            .header("x-trace", "<script>")
+            .header("authorization", "Bearer secret")
+            .body(Body::empty())
+            .unwrap();
+        let mut req_info = RequestInfo::new_from_req(&req, RequestContext::new());
+        req_info.route_path = Some("/users/:id".to_owned());
+
+        let err: RouteError = crate::Error::new("Invalid <id>").into();
+        let page = render(&err, StatusCode::INTERNAL_SERVER_ERROR, &req_info, "0123456789abcdef");
+
+        assert!(page.contains("<h1>500 Internal Server Error</h1>"));
+        assert!(page.contains("Invalid &lt;id&gt;"));
+        assert!(page.contains("/users/:id"));
+        assert!(page.contains("<tr><th>Router</th><td>0123456789abcdef</td></tr>"));
+        assert!(page.contains("<tr><th>x-trace</th><td>&lt;script&gt;</td></tr>"));
+        assert!(!page.contains("<script>"));
+        assert!(page.contains("<tr><th>authorization</th><td>[REDACTED]</td></tr>"));
+    }
+}
@@ -0,0 +1,256 @@
+use crate::constants;
+use crate::helpers::{http_date, json_str};
+use crate::regex_generator::generate_gateway_regex_str;
+use crate::route::Route;
+use hyper::Method;
+use std::fmt::Write;
+
+/// The format of the route manifest produced by the [`Router::export`](./struct.Router.html#method.export) method.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// A JSON document with the path, the methods, the params, the gateway regex and the deprecation of each route.
+    Json,
+    /// The same document as the `Json` format in YAML.
+    Yaml,
+    /// The `location` blocks of an nginx server, which proxy the routes to the upstream e.g. `http://backend`.
+    Nginx {
+        /// The address which the requests are proxied to.
+        upstream: String,
+    },
+    /// The `routes` of an Envoy virtual host, which route the routes to the cluster.
+    Envoy {
+        /// The name of the upstream cluster.
+        cluster: String,
+    },
+}
+
+// The exported attributes of a route.
+struct RouteEntry {
+    path: String,
+    methods: Vec<Method>,
+    params: Vec<String>,
+    regex: String,
+    is_guarded: bool,
+    deprecation: Option<(String, Option<String>, Option<String>)>,
+}
+
+pub(crate) fn export<B, E>(routes: &[Route<B, E>], format: &ExportFormat) -> String {
+    let entries = routes.iter().map(entry).collect::<Vec<_>>();
+    match format {
+        ExportFormat::Json => export_json(&entries),
+        ExportFormat::Yaml => export_yaml(&entries),
+        ExportFormat::Nginx { upstream } => export_nginx(&entries, upstream),
+        ExportFormat::Envoy { cluster } => export_envoy(&entries, cluster),
+    }
+}
+
+fn entry<B, E>(route: &Route<B, E>) -> RouteEntry {
+    let (path, regex) = match route.raw_regex {
+        Some(_) => (route.path.clone(), route.regex.as_str().to_owned()),
+        None => {
+            let path = match route.path.strip_suffix('/') {
+                Some(path) if !path.is_empty() => path.to_owned(),
+                _ => route.path.clone(),
+            };
+            let regex = generate_gateway_regex_str(&path);
+            (path, regex)
+        }
+    };
+
+    RouteEntry {
+        path,
+        methods: route.methods.clone(),
+        params: route.route_params.clone(),
+        regex,
+        is_guarded: !route.guards.is_empty(),
+        deprecation: route.deprecation.as_ref().map(|deprecation| {
+            (
+                http_date(deprecation.since()),
+                deprecation.sunset().map(http_date),
+                deprecation.link().map(str::to_owned),
+            )
+        }),
+    }
+}
+
+fn json_list<T: AsRef<str>>(items: impl Iterator<Item = T>) -> String {
+    let items = items.map(|item| json_str(item.as_ref())).collect::<Vec<_>>();
+    format!("[{}]", items.join(", "))
+}
+
+fn json_opt(val: &Option<String>) -> String {
+    val.as_deref().map(json_str).unwrap_or_else(|| "null".to_owned())
+}
+
+fn export_json(entries: &[RouteEntry]) -> String {
+    let routes = entries
+        .iter()
+        .map(|entry| {
+            let deprecation = match entry.deprecation {
+                Some((ref since, ref sunset, ref link)) => format!(
+                    "{{\"since\": {}, \"sunset\": {}, \"link\": {}}}",
+                    json_str(since),
+                    json_opt(sunset),
+                    json_opt(link)
+                ),
+                None => "null".to_owned(),
+            };
+            format!(
+                "    {{\"path\": {}, \"methods\": {}, \"params\": {}, \"regex\": {}, \"guarded\": {}, \"deprecation\": {}}}",
+                json_str(&entry.path),
+                json_list(entry.methods.iter()),
+                json_list(entry.params.iter()),
+                json_str(&entry.regex),
+                entry.is_guarded,
+                deprecation
+            )
+        })
+        .collect::<Vec<_>>();
+    format!("{{\n  \"routes\": [\n{}\n  ]\n}}\n", routes.join(",\n"))
+}
+
+// The strings are double quoted with the JSON escapes, which YAML accepts too.
+fn export_yaml(entries: &[RouteEntry]) -> String {
+    let mut out = String::from("routes:\n");
+    for entry in entries {
+        let _ = writeln!(out, "  - path: {}", json_str(&entry.path));
+        let _ = writeln!(out, "    methods: {}", json_list(entry.methods.iter()));
+        let _ = writeln!(out, "    params: {}", json_list(entry.params.iter()));
+        let _ = writeln!(out, "    regex: {}", json_str(&entry.regex));
+        let _ = writeln!(out, "    guarded: {}", entry.is_guarded);
+        match entry.deprecation {
+            Some((ref since, ref sunset, ref link)) => {
+                out.push_str("    deprecation:\n");
+                let _ = writeln!(out, "      since: {}", json_str(since));
+                let _ = writeln!(out, "      sunset: {}", json_opt(sunset));
+                let _ = writeln!(out, "      link: {}", json_opt(link));
+            }
+            None => out.push_str("    deprecation: null\n"),
+        }
+    }
+    out
+}
+
+// The routes with the same regex, as a gateway takes the first matching location for all the methods. The methods
+// are `None` if any method is allowed.
+struct Group<'a> {
+    regex: &'a str,
+    methods: Option<Vec<&'a str>>,
+    paths: Vec<&'a str>,
+}
+
+fn group_by_regex(entries: &[RouteEntry]) -> Vec<Group<'_>> {
+    let mut groups: Vec<Group<'_>> = Vec::new();
+    for entry in entries {
+        let is_any = constants::ALL_POSSIBLE_HTTP_METHODS
+            .iter()
+            .all(|method| entry.methods.contains(method));
+        let group = match groups.iter().position(|group| group.regex == entry.regex) {
+            Some(idx) => &mut groups[idx],
+            None => {
+                groups.push(Group {
+                    regex: &entry.regex,
+                    methods: Some(Vec::new()),
+                    paths: Vec::new(),
+                });
+                groups.last_mut().unwrap()
+            }
+        };
+
+        if is_any {
+            group.methods = None;
+        } else if let Some(ref mut methods) = group.methods {
+            for method in &entry.methods {
+                if !methods.contains(&method.as_str()) {
+                    methods.push(method.as_str());
+                }
+            }
+        }
+        if !group.paths.contains(&entry.path.as_str()) {
+            group.paths.push(&entry.path);
+        }
+    }
+    groups
+}
+
+fn export_nginx(entries: &[RouteEntry], upstream: &str) -> String {
+    let mut out = String::new();
+    for group in group_by_regex(entries) {
+        let _ = writeln!(out, "# {}", group.paths.join(", "));
+        let _ = writeln!(out, "location ~ \"{}\" {{", group.regex.replace('"', "\\\""));
+        if let Some(methods) = group.methods {
+            let _ = writeln!(out, "    limit_except {} {{ deny all; }}", methods.join(" "));
+        }
+        let _ = writeln!(out, "    proxy_pass {};", upstream);
+        out.push_str("}\n\n");
+    }
+    out
+}
+
+fn export_envoy(entries: &[RouteEntry], cluster: &str) -> String {
+    let mut out = String::from("routes:\n");
+    for group in group_by_regex(entries) {
+        out.push_str("  - match:\n      safe_regex:\n");
+        let _ = writeln!(out, "        regex: {}", json_str(group.regex));
+        if let Some(methods) = group.methods {
+            out.push_str(
+                "      headers:\n        - name: \":method\"\n          string_match:\n            safe_regex:\n",
+            );
+            let _ = writeln!(out, "              regex: \"^({})$\"", methods.join("|"));
+        }
+        let _ = writeln!(out, "    route:\n      cluster: {}", json_str(cluster));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RouteError, Router};
+    use hyper::{Body, Response};
+
+    #[test]
+    fn exports_route_manifests() {
+        let api: Router<Body, RouteError> = Router::builder()
+            .get("/users/:id", |_| async move { Ok(Response::new(Body::from("User"))) })
+            .delete(
+                "/users/:id",
+                |_| async move { Ok(Response::new(Body::from("Deleted"))) },
+            )
+            .build()
+            .unwrap();
+        let router: Router<Body, RouteError> = Router::builder()
+            .scope("/api", api)
+            .any_method("/health", |_| async move { Ok(Response::new(Body::from("OK"))) })
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            router.export(ExportFormat::Json),
+            "{\n  \"routes\": [\n    \
+             {\"path\": \"/api/users/:id\", \"methods\": [\"GET\"], \"params\": [\"id\"], \
+             \"regex\": \"^/api/users/([^/]+)/?$\", \"guarded\": false, \"deprecation\": null},\n    \
+             {\"path\": \"/api/users/:id\", \"methods\": [\"DELETE\"], \"params\": [\"id\"], \
+             \"regex\": \"^/api/users/([^/]+)/?$\", \"guarded\": false, \"deprecation\": null},\n    \
+             {\"path\": \"/health\", \"methods\": [\"GET\", \"POST\", \"PUT\", \"PATCH\", \"DELETE\", \"CONNECT\", \
+             \"HEAD\", \"OPTIONS\", \"TRACE\"], \"params\": [], \"regex\": \"^/health/?$\", \"guarded\": false, \
+             \"deprecation\": null}\n  ]\n}\n"
+        );
+        assert!(router
+            .export(ExportFormat::Yaml)
+            .starts_with("routes:\n  - path: \"/api/users/:id\"\n    methods: [\"GET\"]\n"));
+
+        assert_eq!(
+            router.export(ExportFormat::Nginx {
+                upstream: "http://backend".to_owned()
+            }),
+            "# /api/users/:id\nlocation ~ \"^/api/users/([^/]+)/?$\" {\n    limit_except GET DELETE { deny all; }\n    \
+             proxy_pass http://backend;\n}\n\n# /health\nlocation ~ \"^/health/?$\" {\n    proxy_pass http://backend;\n}\n\n"
+        );
+        assert!(router
+            .export(ExportFormat::Envoy {
+                cluster: "backend".to_owned()
+            })
+            .contains("            safe_regex:\n              regex: \"^(GET|DELETE)$\"\n    route:\n      cluster: \"backend\"\n"));
+    }
+}
@@ -0,0 +1,140 @@
+use crate::route::Route;
+use std::collections::HashMap;
+
+// The route matcher of the `fast-match` backend, built on a `matchit` radix tree. It only knows the routes whose paths
+// can be translated into the `matchit` syntax, the rest are left to the regex backend.
+pub(crate) struct FastMatcher {
+    tree: matchit::Router<usize>,
+    // The route indexes per distinct path, in the registration order.
+    nodes: Vec<Vec<usize>>,
+}
+
+impl FastMatcher {
+    // Returns the matcher and the indexes of the routes which have to be matched by the regex backend.
+    pub(crate) fn new<B, E>(routes: &[Route<B, E>]) -> (FastMatcher, Vec<usize>) {
+        let mut matcher = FastMatcher {
+            tree: matchit::Router::new(),
+            nodes: Vec::new(),
+        };
+        let mut node_idxs = HashMap::<String, usize>::new();
+        let mut regex_route_idxs = Vec::new();
+
+        let paths = routes
+            .iter()
+            .map(|route| match route.raw_regex {
+                None => translate_path(route.path.as_str()),
+                Some(_) => None,
+            })
+            .collect::<Vec<_>>();
+
+        for (idx, path) in paths.iter().enumerate() {
+            // The tree only returns the path it prefers, which is a literal segment over a param, so the paths which
+            // overlap another one e.g. `/users/new/` and `/users/:id/` stay with the regex backend to keep the
+            // registration order between them.
+            let path = match path {
+                Some(path) if !paths.iter().flatten().any(|other| overlaps(path, other)) => path.clone(),
+                _ => {
+                    regex_route_idxs.push(idx);
+                    continue;
+                }
+            };
+
+            if let Some(node_idx) = node_idxs.get(&path) {
+                matcher.nodes[*node_idx].push(idx);
+                continue;
+            }
+
+            // A path which the tree rejects e.g. `/users/:uid/posts/` after `/users/:id/` stays with the regex backend.
+            let node_idx = matcher.nodes.len();
+            if matcher.tree.insert(path.clone(), node_idx).is_err() {
+                regex_route_idxs.push(idx);
+                continue;
+            }
+
+            matcher.nodes.push(vec![idx]);
+            node_idxs.insert(path, node_idx);
+        }
+
+        (matcher, regex_route_idxs)
+    }
+
+    // Returns the indexes of the routes whose path matches the target path.
+    pub(crate) fn find(&self, target_path: &str) -> &[usize] {
+        match self.tree.at(target_path) {
+            Ok(matched) => self.nodes[*matched.value].as_slice(),
+            Err(_) => &[],
+        }
+    }
+}
+
+// Translates a routerify path into the `matchit` syntax. Only the paths whose segments are either literal or a single
+// required param e.g. `/users/:id/` are supported.
+fn translate_path(path: &str) -> Option<String> {
+    let is_supported = path.split('/').all(|segment| match segment.strip_prefix(':') {
+        Some(name) => !name.is_empty() && !name.contains([':', '*', '?', '=', '.']),
+        None => !segment.contains([':', '*']),
+    });
+
+    if is_supported {
+        Some(path.to_owned())
+    } else {
+        None
+    }
+}
+
+// Checks if some target path is matched by both of the distinct paths.
+fn overlaps(path: &str, other: &str) -> bool {
+    if path == other || path.split('/').count() != other.split('/').count() {
+        return false;
+    }
+
+    path.split('/')
+        .zip(other.split('/'))
+        .all(|(segment, other)| segment == other || segment.starts_with(':') || other.starts_with(':'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::{Body, Method, Response};
+    use std::convert::Infallible;
+
+    fn route(path: &str, method: Method) -> Route<Body, Infallible> {
+        Route::new(path, vec![method], |_| async move { Ok(Response::new(Body::empty())) }).unwrap()
+    }
+
+    #[test]
+    fn translates_supported_paths_only() {
+        assert_eq!(translate_path("/"), Some("/".to_owned()));
+        assert_eq!(translate_path("/users/:id/"), Some("/users/:id/".to_owned()));
+        assert_eq!(translate_path("/users/:id?/"), None);
+        assert_eq!(translate_path("/files/:name.json/"), None);
+        assert_eq!(translate_path("/files/*"), None);
+        assert_eq!(translate_path("/v:version/"), None);
+    }
+
+    #[test]
+    fn detects_overlapping_paths() {
+        assert!(overlaps("/users/new/", "/users/:id/"));
+        assert!(overlaps("/users/:id/posts/", "/users/me/:tab/"));
+        assert!(!overlaps("/users/:id/", "/users/:id/"));
+        assert!(!overlaps("/users/:id/", "/users/:id/posts/"));
+        assert!(!overlaps("/users/new/edit/", "/users/:id/view/"));
+    }
+
+    #[test]
+    fn leaves_overlapping_routes_to_the_regex_backend() {
+        let routes: Vec<Route<Body, Infallible>> = vec![
+            route("/users/:id/", Method::GET),
+            route("/users/new/", Method::POST),
+            route("/users/new/edit/", Method::GET),
+            route("/users/:id/view/", Method::GET),
+        ];
+        let (matcher, regex_route_idxs) = FastMatcher::new(&routes);
+
+        assert_eq!(regex_route_idxs, vec![0, 1]);
+        assert_eq!(matcher.find("/users/new/edit/"), &[2]);
+        assert_eq!(matcher.find("/users/new/view/"), &[3]);
+        assert!(matcher.find("/users/new/").is_empty());
+    }
+}
@@ -0,0 +1,46 @@
+use super::Router;
+use hyper::body::HttpBody;
+
+// The offset basis and the prime of the 64-bit FNV-1a hash, which is stable across the Rust versions and platforms
+// unlike the hashers of the standard library.
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+struct Fnv(u64);
+
+impl Fnv {
+    // Hashes the field followed by a separator, so the adjacent fields can't be confused.
+    fn write(&mut self, field: &str) {
+        for byte in field.bytes().chain(std::iter::once(0)) {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+}
+
+// Hashes the route paths and methods and the middleware kinds, paths and names in their matching order.
+pub(crate) fn compute<B, E>(router: &Router<B, E>) -> String
+where
+    B: HttpBody + Send + Sync + 'static,
+    E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+{
+    let mut hash = Fnv(FNV_OFFSET_BASIS);
+    for route in router.routes.iter() {
+        hash.write("route");
+        hash.write(&route.path);
+        for method in route.methods.iter() {
+            hash.write(method.as_str());
+        }
+    }
+
+    let pre = router.pre_middlewares.iter().map(|m| ("pre", &m.path, &m.name));
+    let around = router.around_middlewares.iter().map(|m| ("around", &m.path, &m.name));
+    let post = router.post_middlewares.iter().map(|m| ("post", &m.path, &m.name));
+    for (kind, path, name) in pre.chain(around).chain(post) {
+        hash.write(kind);
+        hash.write(path);
+        hash.write(name.as_deref().unwrap_or(""));
+    }
+
+    format!("{:016x}", hash.0)
+}
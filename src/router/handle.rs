@@ -0,0 +1,179 @@
+use crate::Error;
+use hyper::{Request, Response};
+use std::fmt::{self, Debug, Formatter};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+
+type HandlerFuture<B, E> = Pin<Box<dyn Future<Output = Result<Response<B>, E>> + Send>>;
+type Handler<B, E> = Arc<dyn Fn(Request<hyper::Body>) -> HandlerFuture<B, E> + Send + Sync + 'static>;
+
+/// A handle to enable or disable the [named](./enum.Middleware.html#method.named) middlewares of a router at runtime.
+///
+/// It's created by the [`Router::handle`](./struct.Router.html#method.handle) method and can be cloned and shared
+/// freely, e.g. with an admin endpoint.
+#[derive(Clone)]
+pub struct RouterHandle {
+    switches: Arc<Vec<(String, Arc<AtomicBool>)>>,
+}
+
+impl RouterHandle {
+    pub(crate) fn new(switches: Vec<(String, Arc<AtomicBool>)>) -> RouterHandle {
+        RouterHandle {
+            switches: Arc::new(switches),
+        }
+    }
+
+    /// Enables or disables all the middlewares with the specified name.
+    ///
+    /// It fails if there's no middleware with the name.
+    pub fn set_middleware_enabled(&self, name: &str, enabled: bool) -> crate::Result<()> {
+        let mut found = false;
+        for (_, switch) in self.switches.iter().filter(|(n, _)| n == name) {
+            switch.store(enabled, Ordering::Relaxed);
+            found = true;
+        }
+
+        if found {
+            Ok(())
+        } else {
+            Err(Error::new(format!("No middleware is named: {:?}", name)).into())
+        }
+    }
+
+    /// Checks if the middlewares with the specified name are enabled. It returns `None` if there's no middleware with the name.
+    pub fn is_middleware_enabled(&self, name: &str) -> Option<bool> {
+        self.switches
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, switch)| switch.load(Ordering::Relaxed))
+    }
+}
+
+impl Debug for RouterHandle {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let switches = self
+            .switches
+            .iter()
+            .map(|(name, switch)| (name.as_str(), switch.load(Ordering::Relaxed)))
+            .collect::<Vec<_>>();
+        write!(f, "{{ middlewares: {:?} }}", switches)
+    }
+}
+
+/// A handle to replace the handler of a route at runtime, e.g. to switch it to a maintenance stub, without rebuilding
+/// the router.
+///
+/// The route is added with the handler returned by the [`handler`](#method.handler) method. A replacement takes effect
+/// atomically for the next requests, while the requests in flight finish with the handler they started with. The
+/// handle can be cloned and shared freely, e.g. with an admin endpoint, and the clones replace the same handler.
+///
+/// # Examples
+///
+/// ```
+/// use routerify::{Router, RouteHandle};
+/// use hyper::{Response, Body, StatusCode};
+/// use std::convert::Infallible;
+///
+/// # fn run() -> Router<Body, Infallible> {
+/// let checkout = RouteHandle::new(|_| async move { Ok(Response::new(Body::from("Checkout"))) });
+///
+/// let router = Router::builder()
+///     .post("/checkout", checkout.handler())
+///     .build()
+///     .unwrap();
+///
+/// // Later, during an incident.
+/// checkout.replace(|_| async move {
+///     Ok(Response::builder()
+///         .status(StatusCode::SERVICE_UNAVAILABLE)
+///         .body(Body::from("Down for maintenance"))
+///         .unwrap())
+/// });
+/// // And back to the original handler.
+/// checkout.restore();
+/// # router
+/// # }
+/// # run();
+/// ```
+pub struct RouteHandle<B, E> {
+    inner: Arc<RouteHandleInner<B, E>>,
+}
+
+struct RouteHandleInner<B, E> {
+    original: Handler<B, E>,
+    current: RwLock<Option<Handler<B, E>>>,
+}
+
+impl<B: 'static, E: 'static> RouteHandle<B, E> {
+    /// Creates a new handle with the original handler of the route.
+    pub fn new<H, R>(handler: H) -> Self
+    where
+        H: Fn(Request<hyper::Body>) -> R + Send + Sync + 'static,
+        R: Future<Output = Result<Response<B>, E>> + Send + 'static,
+    {
+        RouteHandle {
+            inner: Arc::new(RouteHandleInner {
+                original: boxed(handler),
+                current: RwLock::new(None),
+            }),
+        }
+    }
+
+    /// Returns the route handler, which calls the current handler of the handle. It can be added to multiple routes,
+    /// which are swapped together.
+    pub fn handler(&self) -> impl Fn(Request<hyper::Body>) -> HandlerFuture<B, E> + Send + Sync + 'static {
+        let inner = self.inner.clone();
+        move |req| {
+            let handler = inner
+                .current
+                .read()
+                .unwrap()
+                .clone()
+                .unwrap_or_else(|| inner.original.clone());
+            handler(req)
+        }
+    }
+
+    /// Replaces the handler for the next requests.
+    pub fn replace<H, R>(&self, handler: H)
+    where
+        H: Fn(Request<hyper::Body>) -> R + Send + Sync + 'static,
+        R: Future<Output = Result<Response<B>, E>> + Send + 'static,
+    {
+        *self.inner.current.write().unwrap() = Some(boxed(handler));
+    }
+
+    /// Switches back to the original handler for the next requests.
+    pub fn restore(&self) {
+        *self.inner.current.write().unwrap() = None;
+    }
+
+    /// Checks if the original handler is replaced.
+    pub fn is_replaced(&self) -> bool {
+        self.inner.current.read().unwrap().is_some()
+    }
+}
+
+impl<B, E> Clone for RouteHandle<B, E> {
+    fn clone(&self) -> Self {
+        RouteHandle {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<B, E> Debug for RouteHandle<B, E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{{ replaced: {:?} }}", self.inner.current.read().unwrap().is_some())
+    }
+}
+
+fn boxed<B, E, H, R>(handler: H) -> Handler<B, E>
+where
+    H: Fn(Request<hyper::Body>) -> R + Send + Sync + 'static,
+    R: Future<Output = Result<Response<B>, E>> + Send + 'static,
+{
+    Arc::new(move |req| Box::pin(handler(req)))
+}
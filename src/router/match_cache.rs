@@ -0,0 +1,164 @@
+use hyper::Method;
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Formatter};
+use std::sync::{Arc, Mutex};
+
+// The indexes of the middlewares, the routes and the data maps whose paths match a request path, and the index of the
+// route which handles the request with the method.
+#[derive(Debug, Default)]
+pub(crate) struct RegexMatches {
+    pub(crate) pre_middlewares: Vec<usize>,
+    pub(crate) routes: Vec<usize>,
+    pub(crate) post_middlewares: Vec<usize>,
+    pub(crate) around_middlewares: Vec<usize>,
+    pub(crate) scoped_data_maps: Vec<usize>,
+    pub(crate) matched_route: Option<usize>,
+}
+
+type Key = (Method, String);
+
+const NIL: usize = usize::MAX;
+
+struct Entry {
+    key: Key,
+    matches: Arc<RegexMatches>,
+    prev: usize,
+    next: usize,
+}
+
+// A least recently used cache of the route matches. The entries form a doubly linked list from the most recently used
+// one at the head to the least recently used one at the tail.
+struct Lru {
+    map: HashMap<Key, usize>,
+    entries: Vec<Entry>,
+    head: usize,
+    tail: usize,
+}
+
+impl Lru {
+    fn unlink(&mut self, idx: usize) {
+        let (prev, next) = (self.entries[idx].prev, self.entries[idx].next);
+        if prev != NIL {
+            self.entries[prev].next = next;
+        } else {
+            self.head = next;
+        }
+        if next != NIL {
+            self.entries[next].prev = prev;
+        } else {
+            self.tail = prev;
+        }
+    }
+
+    fn push_front(&mut self, idx: usize) {
+        self.entries[idx].prev = NIL;
+        self.entries[idx].next = self.head;
+        if self.head != NIL {
+            self.entries[self.head].prev = idx;
+        }
+        self.head = idx;
+        if self.tail == NIL {
+            self.tail = idx;
+        }
+    }
+}
+
+pub(crate) struct MatchCache {
+    capacity: usize,
+    lru: Mutex<Lru>,
+}
+
+impl MatchCache {
+    pub(crate) fn new(capacity: usize) -> MatchCache {
+        MatchCache {
+            capacity,
+            lru: Mutex::new(Lru {
+                map: HashMap::with_capacity(capacity),
+                entries: Vec::with_capacity(capacity),
+                head: NIL,
+                tail: NIL,
+            }),
+        }
+    }
+
+    pub(crate) fn get(&self, method: &Method, path: &str) -> Option<Arc<RegexMatches>> {
+        let mut lru = self.lru.lock().unwrap();
+        let idx = *lru.map.get(&(method.clone(), path.to_owned()))?;
+        lru.unlink(idx);
+        lru.push_front(idx);
+        Some(lru.entries[idx].matches.clone())
+    }
+
+    pub(crate) fn insert(&self, method: &Method, path: &str, matches: Arc<RegexMatches>) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let key = (method.clone(), path.to_owned());
+        let mut lru = self.lru.lock().unwrap();
+        if lru.map.contains_key(&key) {
+            return;
+        }
+
+        let idx = if lru.entries.len() < self.capacity {
+            lru.entries.push(Entry {
+                key: key.clone(),
+                matches,
+                prev: NIL,
+                next: NIL,
+            });
+            lru.entries.len() - 1
+        } else {
+            // Reuse the slot of the least recently used entry.
+            let idx = lru.tail;
+            lru.unlink(idx);
+            let old_key = std::mem::replace(&mut lru.entries[idx].key, key.clone());
+            lru.map.remove(&old_key);
+            lru.entries[idx].matches = matches;
+            idx
+        };
+
+        lru.push_front(idx);
+        lru.map.insert(key, idx);
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.lru.lock().unwrap().map.len()
+    }
+}
+
+impl Debug for MatchCache {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{{ capacity: {} }}", self.capacity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(route: usize) -> Arc<RegexMatches> {
+        Arc::new(RegexMatches {
+            matched_route: Some(route),
+            ..RegexMatches::default()
+        })
+    }
+
+    #[test]
+    fn evicts_the_least_recently_used_entry() {
+        let cache = MatchCache::new(2);
+        cache.insert(&Method::GET, "/a/", matches(1));
+        cache.insert(&Method::GET, "/b/", matches(2));
+
+        // Touch `/a/`, so `/b/` becomes the least recently used one.
+        assert_eq!(cache.get(&Method::GET, "/a/").unwrap().matched_route, Some(1));
+        cache.insert(&Method::GET, "/c/", matches(3));
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(&Method::GET, "/b/").is_none());
+        assert_eq!(cache.get(&Method::GET, "/a/").unwrap().matched_route, Some(1));
+        assert_eq!(cache.get(&Method::GET, "/c/").unwrap().matched_route, Some(3));
+        assert!(cache.get(&Method::POST, "/c/").is_none());
+    }
+}
@@ -0,0 +1,116 @@
+use crate::constants;
+use crate::route::Route;
+use crate::Error;
+use hyper::Method;
+use regex::RegexSet;
+
+// The methods of the routes per path pattern, across the scopes. It's computed once the router is built, so the
+// `OPTIONS` and the `405 Method Not Allowed` responses don't run the route handlers.
+pub(crate) struct MethodTable {
+    regex_set: RegexSet,
+    methods: Vec<Vec<Method>>,
+}
+
+impl MethodTable {
+    pub(crate) fn new<B, E>(routes: &[Route<B, E>]) -> crate::Result<MethodTable> {
+        let mut patterns: Vec<&str> = Vec::new();
+        let mut methods: Vec<Vec<Method>> = Vec::new();
+
+        // The catch-all routes of the root router answer any path, so they don't tell the methods of a path.
+        for route in routes.iter().filter(|route| route.path != "/*") {
+            let idx = match patterns.iter().position(|pattern| *pattern == route.regex.as_str()) {
+                Some(idx) => idx,
+                None => {
+                    patterns.push(route.regex.as_str());
+                    methods.push(Vec::new());
+                    methods.len() - 1
+                }
+            };
+            for method in route.methods.iter() {
+                if !methods[idx].contains(method) {
+                    methods[idx].push(method.clone());
+                }
+            }
+        }
+
+        let regex_set = RegexSet::new(patterns)
+            .map_err(|e| Error::new(format!("Couldn't create the RegexSet of the method table: {}", e)))?;
+
+        Ok(MethodTable { regex_set, methods })
+    }
+
+    // Returns the methods of the routes at the target path, in the order of the known methods and then the
+    // extension ones.
+    pub(crate) fn allowed_methods(&self, target_path: &str) -> Vec<Method> {
+        let mut allowed_methods = Vec::new();
+        for idx in self.regex_set.matches(target_path).into_iter() {
+            for method in self.methods[idx].iter() {
+                if !allowed_methods.contains(method) {
+                    allowed_methods.push(method.clone());
+                }
+            }
+        }
+
+        allowed_methods.sort_by_key(|method| {
+            constants::ALL_POSSIBLE_HTTP_METHODS
+                .iter()
+                .position(|known| known == method)
+                .unwrap_or(constants::ALL_POSSIBLE_HTTP_METHODS.len())
+        });
+        allowed_methods
+    }
+}
+
+impl Default for MethodTable {
+    fn default() -> Self {
+        MethodTable {
+            regex_set: RegexSet::empty(),
+            methods: Vec::new(),
+        }
+    }
+}
+
+// Formats the methods as the value of the `Allow` header, with the `OPTIONS` method which is always answered.
+pub(crate) fn allow_header_value(methods: &[Method]) -> String {
+    let mut value = methods.iter().map(Method::as_str).collect::<Vec<_>>();
+    if !methods.contains(&Method::OPTIONS) {
+        value.push(Method::OPTIONS.as_str());
+    }
+    value.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::{Body, Response};
+    use std::convert::Infallible;
+
+    fn route(path: &str, methods: Vec<Method>) -> Route<Body, Infallible> {
+        Route::new(path, methods, |_| async move { Ok(Response::new(Body::empty())) }).unwrap()
+    }
+
+    #[test]
+    fn merges_the_methods_of_a_path() {
+        let table = MethodTable::new(&[
+            route("/users/:id/", vec![Method::PUT]),
+            route("/users/:userId/", vec![Method::GET, Method::DELETE]),
+            route("/users/me/", vec![Method::PATCH]),
+            route("/*", vec![Method::OPTIONS]),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            table.allowed_methods("/users/me/"),
+            vec![Method::GET, Method::PUT, Method::PATCH, Method::DELETE]
+        );
+        assert_eq!(
+            table.allowed_methods("/users/1/"),
+            vec![Method::GET, Method::PUT, Method::DELETE]
+        );
+        assert!(table.allowed_methods("/posts/").is_empty());
+        assert_eq!(
+            allow_header_value(&table.allowed_methods("/users/1/")),
+            "GET, PUT, DELETE, OPTIONS"
+        );
+    }
+}
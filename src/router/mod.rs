@@ -1,20 +1,51 @@
+#[cfg(feature = "fast-match")]
+use self::fast_match::FastMatcher;
+use self::match_cache::{MatchCache, RegexMatches};
+pub(crate) use self::method_table::MethodTable;
+pub(crate) use self::response_headers::ResponseHeaders;
+pub(crate) use self::status_mapper::{StatusMapper, StatusMapperHandler};
+use crate::body::BodyError;
 use crate::constants;
-use crate::data_map::ScopedDataMap;
-use crate::middleware::{PostMiddleware, PreMiddleware};
-use crate::route::Route;
-use crate::types::RequestInfo;
+use crate::data_map::{ScopedDataMap, SharedConfigMaps, SharedDataMap};
+use crate::events::{MiddlewareStage, Observer, RouterEvent};
+use crate::ext::{RequestExt, RouteErrorExt};
+use crate::helpers;
+use crate::middleware::{AroundMiddleware, MiddlewareInfo, MiddlewareKind, PostMiddleware, PreMiddleware};
+use crate::regex_generator::{generate_exact_match_regex, path_covers_route};
+use crate::responses::ResponseTemplates;
+use crate::route::{Route, RouteInfo};
+use crate::service::LifecycleHook;
+use crate::types::{Deprecation, Forwarded, ProblemDetails, RequestInfo, RequestMeta};
 use crate::Error;
+use crate::HttpError;
 use crate::RouteError;
-use hyper::{body::HttpBody, header, Method, Request, Response, StatusCode};
-use regex::RegexSet;
+use hyper::{body::HttpBody, header, HeaderMap, Method, Request, Response, StatusCode};
+use regex::{Regex, RegexSet};
 use std::any::Any;
+use std::borrow::Cow;
+use std::cmp::Reverse;
 use std::fmt::{self, Debug, Formatter};
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Instant;
 
 pub use self::builder::RouterBuilder;
+pub use self::export::ExportFormat;
+pub use self::handle::{RouteHandle, RouterHandle};
+pub use self::status_mapper::RouteMeta;
 
 mod builder;
+mod debug_page;
+mod export;
+#[cfg(feature = "fast-match")]
+mod fast_match;
+mod fingerprint;
+mod handle;
+mod match_cache;
+mod method_table;
+mod response_headers;
+mod status_mapper;
 
 pub(crate) type ErrHandlerWithoutInfo<B> =
     Box<dyn Fn(RouteError) -> ErrHandlerWithoutInfoReturn<B> + Send + Sync + 'static>;
@@ -24,6 +55,31 @@ pub(crate) type ErrHandlerWithInfo<B> =
     Box<dyn Fn(RouteError, RequestInfo) -> ErrHandlerWithInfoReturn<B> + Send + Sync + 'static>;
 pub(crate) type ErrHandlerWithInfoReturn<B> = Box<dyn Future<Output = Response<B>> + Send + 'static>;
 
+pub(crate) type ErrResponseMapperHandler<B> =
+    Box<dyn Fn(StatusCode, RequestInfo) -> ErrHandlerWithInfoReturn<B> + Send + Sync + 'static>;
+
+pub(crate) type PreMatchHook = Box<dyn Fn(&mut Request<hyper::Body>) + Send + Sync + 'static>;
+
+// Rewrites the error responses of the routes under its path, which is the path of the router it's added to.
+pub(crate) struct ErrResponseMapper<B> {
+    pub(crate) path: String,
+    regex: Regex,
+    pub(crate) scope_depth: u32,
+    pub(crate) handler: ErrResponseMapperHandler<B>,
+}
+
+impl<B> ErrResponseMapper<B> {
+    pub(crate) fn new(path: String, handler: ErrResponseMapperHandler<B>, scope_depth: u32) -> crate::Result<Self> {
+        let (regex, _) = generate_exact_match_regex(&path)?;
+        Ok(ErrResponseMapper {
+            path,
+            regex,
+            scope_depth,
+            handler,
+        })
+    }
+}
+
 /// Represents a modular, lightweight and mountable router type.
 ///
 /// A router consists of some routes, some pre-middlewares and some post-middlewares.
@@ -63,8 +119,13 @@ pub struct Router<B, E> {
     pub(crate) pre_middlewares: Vec<PreMiddleware<E>>,
     pub(crate) routes: Vec<Route<B, E>>,
     pub(crate) post_middlewares: Vec<PostMiddleware<B, E>>,
+    pub(crate) around_middlewares: Vec<AroundMiddleware<B, E>>,
     pub(crate) scoped_data_maps: Vec<ScopedDataMap>,
 
+    // The configs of the scopes, from the innermost scope to the outermost one. They are resolved per route once the
+    // router is served.
+    pub(crate) scoped_config_maps: Vec<ScopedDataMap>,
+
     // This handler should be added only on root Router.
     // Any error handler attached to scoped router will be ignored.
     pub(crate) err_handler: Option<ErrHandler<B>>,
@@ -72,8 +133,66 @@ pub struct Router<B, E> {
     // We'll initialize it from the RouterService via Router::init_regex_set() method.
     regex_set: Option<RegexSet>,
 
+    // The indexes of the routes in the RegexSet, which are all the routes unless the fast-match backend is used.
+    regex_route_idxs: Vec<usize>,
+
+    // Whether the routes are matched by the fast-match backend. It's only used on the root Router.
+    #[cfg(feature = "fast-match")]
+    pub(crate) fast_match: bool,
+
+    // We'll initialize it from the RouterService via Router::init_regex_set() method.
+    #[cfg(feature = "fast-match")]
+    fast_matcher: Option<FastMatcher>,
+
+    // The cache of the route matches, keyed by the method and the path. It's only used on the root Router.
+    pub(crate) match_cache: Option<MatchCache>,
+
     // We'll initialize it from the RouterService via Router::init_req_info_gen() method.
     pub(crate) should_gen_req_info: Option<bool>,
+
+    // Whether the default error handler picks the status code by the error class.
+    pub(crate) classify_errors: bool,
+
+    // Whether the default error handler renders the detailed error pages.
+    pub(crate) debug_errors: bool,
+
+    // Whether the default 404 route and the default error handler respond with the problem details.
+    pub(crate) problem_details: bool,
+
+    // The path prefix which is stripped from the request paths. It's only used on the root Router.
+    pub(crate) base_path: Option<Arc<str>>,
+
+    // The hash of the route table which is computed once the router is built.
+    pub(crate) fingerprint: Arc<str>,
+
+    // The methods of the routes per path, which is computed once the router is built.
+    pub(crate) method_table: Arc<MethodTable>,
+
+    // The response header which the fingerprint is sent in. It's only used on the root Router.
+    pub(crate) fingerprint_header: Option<header::HeaderName>,
+
+    // The static response headers, which are moved to the root Router by the scopes.
+    pub(crate) response_headers: Vec<ResponseHeaders>,
+
+    // The mappers of the error responses, which are moved to the root Router by the scopes.
+    pub(crate) err_response_mappers: Vec<ErrResponseMapper<B>>,
+
+    // The mappers of the response statuses, which are moved to the root Router by the scopes, from the innermost
+    // scope to the outermost one.
+    pub(crate) status_mappers: Vec<StatusMapper>,
+
+    // The receiver of the router events. It's only used on the root Router.
+    pub(crate) observer: Option<Observer>,
+
+    // Whether the panics of the request handling are caught without an observer. It's only used on the root Router.
+    pub(crate) catch_panics: bool,
+
+    // The hooks which may rewrite the requests before the route matching. It's only used on the root Router.
+    pub(crate) pre_match_hooks: Vec<PreMatchHook>,
+
+    // The lifecycle hooks which are taken out by the RequestServiceBuilder.
+    pub(crate) startup_hooks: Vec<LifecycleHook>,
+    pub(crate) shutdown_hooks: Vec<LifecycleHook>,
 }
 
 pub(crate) enum ErrHandler<B> {
@@ -97,28 +216,74 @@ impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Se
         pre_middlewares: Vec<PreMiddleware<E>>,
         routes: Vec<Route<B, E>>,
         post_middlewares: Vec<PostMiddleware<B, E>>,
+        around_middlewares: Vec<AroundMiddleware<B, E>>,
         scoped_data_maps: Vec<ScopedDataMap>,
         err_handler: Option<ErrHandler<B>>,
     ) -> Self {
+        let mut pre_middlewares = pre_middlewares;
+        let mut post_middlewares = post_middlewares;
+        let mut around_middlewares = around_middlewares;
+
+        // The sort is stable, so the middlewares with the same priority keep the registration order.
+        pre_middlewares.sort_by_key(|m| Reverse(m.priority));
+        post_middlewares.sort_by_key(|m| Reverse(m.priority));
+        around_middlewares.sort_by_key(|m| Reverse(m.priority));
+
         Router {
             pre_middlewares,
             routes,
             post_middlewares,
+            around_middlewares,
             scoped_data_maps,
+            scoped_config_maps: Vec::new(),
             err_handler,
             regex_set: None,
+            regex_route_idxs: Vec::new(),
+            #[cfg(feature = "fast-match")]
+            fast_match: false,
+            #[cfg(feature = "fast-match")]
+            fast_matcher: None,
+            match_cache: None,
             should_gen_req_info: None,
+            classify_errors: false,
+            debug_errors: false,
+            problem_details: false,
+            base_path: None,
+            fingerprint: Arc::from(""),
+            method_table: Arc::default(),
+            fingerprint_header: None,
+            response_headers: Vec::new(),
+            err_response_mappers: Vec::new(),
+            status_mappers: Vec::new(),
+            observer: None,
+            catch_panics: false,
+            pre_match_hooks: Vec::new(),
+            startup_hooks: Vec::new(),
+            shutdown_hooks: Vec::new(),
         }
     }
 
     pub(crate) fn init_regex_set(&mut self) -> crate::Result<()> {
+        self.regex_route_idxs = (0..self.routes.len()).collect();
+
+        #[cfg(feature = "fast-match")]
+        {
+            self.fast_matcher = None;
+            if self.fast_match {
+                let (fast_matcher, regex_route_idxs) = FastMatcher::new(&self.routes);
+                self.fast_matcher = Some(fast_matcher);
+                self.regex_route_idxs = regex_route_idxs;
+            }
+        }
+
         let regex_iter = self
             .pre_middlewares
             .iter()
             .map(|m| m.regex.as_str())
-            .chain(self.routes.iter().map(|r| r.regex.as_str()))
+            .chain(self.regex_route_idxs.iter().map(|idx| self.routes[*idx].regex.as_str()))
             .chain(self.post_middlewares.iter().map(|m| m.regex.as_str()))
-            .chain(self.scoped_data_maps.iter().map(|d| d.regex.as_str()));
+            .chain(self.scoped_data_maps.iter().map(|d| d.regex.as_str()))
+            .chain(self.around_middlewares.iter().map(|m| m.regex.as_str()));
 
         self.regex_set =
             Some(RegexSet::new(regex_iter).map_err(|e| Error::new(format!("Couldn't create router RegexSet: {}", e)))?);
@@ -126,12 +291,203 @@ impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Se
         Ok(())
     }
 
+    pub(crate) fn init_route_data_maps(&mut self) {
+        for route in self.routes.iter_mut() {
+            route.shared_data_maps = None;
+            route.scope_configs = None;
+            if route.raw_regex.is_some() {
+                continue;
+            }
+
+            route.shared_data_maps = Router::<B, E>::route_data_maps(&self.scoped_data_maps, route.path.as_str());
+            route.scope_configs = Router::<B, E>::route_data_maps(&self.scoped_config_maps, route.path.as_str());
+        }
+    }
+
+    // Returns the data maps which cover the route, or `None` if they depend on the request path.
+    fn route_data_maps(scoped_data_maps: &[ScopedDataMap], route_path: &str) -> Option<Arc<[SharedDataMap]>> {
+        let mut shared_data_maps = Vec::new();
+        for scoped_data_map in scoped_data_maps.iter() {
+            match path_covers_route(scoped_data_map.path.as_str(), route_path) {
+                Some(true) => shared_data_maps.push(scoped_data_map.clone_data_map()),
+                Some(false) => {}
+                None => return None,
+            }
+        }
+
+        Some(Arc::from(shared_data_maps))
+    }
+
+    // Resolves the ordered middlewares of each route once, so that a request to the route only walks them. The skipped
+    // middlewares must be resolved before.
+    pub(crate) fn init_middleware_plans(&mut self) {
+        for route in self.routes.iter_mut() {
+            route.pre_middleware_plan = None;
+            route.post_middleware_plan = None;
+            route.around_middleware_plan = None;
+            route.response_headers_plan = None;
+            if route.raw_regex.is_some() {
+                continue;
+            }
+
+            let pre_middlewares = self.pre_middlewares.iter().map(|m| (m.path.as_str(), m.scope_depth));
+            route.pre_middleware_plan =
+                Router::<B, E>::middleware_plan(route, pre_middlewares, &route.skipped_pre_middleware_idxs);
+
+            let post_middlewares = self.post_middlewares.iter().map(|m| (m.path.as_str(), m.scope_depth));
+            route.post_middleware_plan =
+                Router::<B, E>::middleware_plan(route, post_middlewares, &route.skipped_post_middleware_idxs);
+
+            let around_middlewares = self.around_middlewares.iter().map(|m| (m.path.as_str(), m.scope_depth));
+            route.around_middleware_plan =
+                Router::<B, E>::middleware_plan(route, around_middlewares, &route.skipped_around_middleware_idxs);
+
+            let response_headers = self.response_headers.iter().map(|h| (h.path.as_str(), h.scope_depth));
+            route.response_headers_plan = Router::<B, E>::middleware_plan(route, response_headers, &[]);
+        }
+    }
+
+    fn middleware_plan<'a>(
+        route: &Route<B, E>,
+        middlewares: impl Iterator<Item = (&'a str, u32)>,
+        skipped_idxs: &[usize],
+    ) -> Option<Vec<usize>> {
+        let mut plan = Vec::new();
+        for (idx, (path, scope_depth)) in middlewares.enumerate() {
+            if skipped_idxs.contains(&idx) || scope_depth > route.scope_depth {
+                continue;
+            }
+
+            if path_covers_route(path, route.path.as_str())? {
+                plan.push(idx);
+            }
+        }
+        Some(plan)
+    }
+
+    pub(crate) fn init_skipped_middlewares(&mut self) -> crate::Result<()> {
+        for route in self.routes.iter_mut() {
+            route.skipped_pre_middleware_idxs.clear();
+            route.skipped_post_middleware_idxs.clear();
+            route.skipped_around_middleware_idxs.clear();
+
+            if let Some(depth) = route.isolation_depth {
+                let is_outer = |scope_depth: u32| scope_depth < depth;
+                route.skipped_pre_middleware_idxs.extend(
+                    self.pre_middlewares
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, m)| is_outer(m.scope_depth))
+                        .map(|(idx, _)| idx),
+                );
+                route.skipped_post_middleware_idxs.extend(
+                    self.post_middlewares
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, m)| is_outer(m.scope_depth))
+                        .map(|(idx, _)| idx),
+                );
+                route.skipped_around_middleware_idxs.extend(
+                    self.around_middlewares
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, m)| is_outer(m.scope_depth))
+                        .map(|(idx, _)| idx),
+                );
+            }
+
+            for name in route.skipped_middlewares.iter() {
+                let is_named = |m_name: &Option<String>| m_name.as_deref() == Some(name.as_str());
+
+                let pre_idxs = self
+                    .pre_middlewares
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, m)| is_named(&m.name))
+                    .map(|(idx, _)| idx)
+                    .collect::<Vec<_>>();
+                let post_idxs = self
+                    .post_middlewares
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, m)| is_named(&m.name))
+                    .map(|(idx, _)| idx)
+                    .collect::<Vec<_>>();
+                let around_idxs = self
+                    .around_middlewares
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, m)| is_named(&m.name))
+                    .map(|(idx, _)| idx)
+                    .collect::<Vec<_>>();
+
+                if pre_idxs.is_empty() && post_idxs.is_empty() && around_idxs.is_empty() {
+                    return Err(Error::new(format!(
+                        "The route {:?} skips an unknown middleware: {:?}",
+                        route.path, name
+                    ))
+                    .into());
+                }
+
+                route.skipped_pre_middleware_idxs.extend(pre_idxs);
+                route.skipped_post_middleware_idxs.extend(post_idxs);
+                route.skipped_around_middleware_idxs.extend(around_idxs);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Verifies that the middlewares which require a type are executed after a middleware which provides it on their path.
+    pub(crate) fn check_middleware_dependencies(&self) -> crate::Result<()> {
+        let pre = self
+            .pre_middlewares
+            .iter()
+            .map(|m| ("pre", m.path.as_str(), &m.name, &m.dependencies));
+        let around = self
+            .around_middlewares
+            .iter()
+            .map(|m| ("around", m.path.as_str(), &m.name, &m.dependencies));
+        let post = self
+            .post_middlewares
+            .iter()
+            .map(|m| ("post", m.path.as_str(), &m.name, &m.dependencies));
+        let middlewares = pre.chain(around).chain(post).collect::<Vec<_>>();
+
+        for (idx, (kind, path, name, dependencies)) in middlewares.iter().enumerate() {
+            for required in dependencies.requires.iter() {
+                let is_provided = middlewares[..idx]
+                    .iter()
+                    .any(|(_, provider_path, _, provider_dependencies)| {
+                        provider_dependencies.provides.contains(required)
+                            && path_covers_route(provider_path, path) == Some(true)
+                    });
+
+                if !is_provided {
+                    let name = name.as_ref().map(|name| format!(" {:?}", name)).unwrap_or_default();
+                    return Err(Error::new(format!(
+                        "The {} middleware{} at {:?} requires `{}`, but no middleware executed before it provides it on its path",
+                        kind, name, path, required.type_name
+                    ))
+                    .into());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     pub(crate) fn init_req_info_gen(&mut self) {
         if let Some(ErrHandler::WithInfo(_)) = self.err_handler {
             self.should_gen_req_info = Some(true);
             return;
         }
 
+        if !self.err_response_mappers.is_empty() {
+            self.should_gen_req_info = Some(true);
+            return;
+        }
+
         for post_middleware in self.post_middlewares.iter() {
             if post_middleware.should_require_req_meta() {
                 self.should_gen_req_info = Some(true);
@@ -164,12 +520,25 @@ impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Se
             return;
         }
 
+        let method_table = self.method_table.clone();
+        let base_path = self.base_path.clone();
         if let Some(router) = self.downcast_to_hyper_body_type() {
-            let options_route: Route<hyper::Body, E> = Route::new("/*", options_method, |_req| async move {
-                Ok(Response::builder()
-                    .status(StatusCode::NO_CONTENT)
-                    .body(hyper::Body::empty())
-                    .expect("Couldn't create the default OPTIONS response"))
+            let options_route: Route<hyper::Body, E> = Route::new("/*", options_method, move |req| {
+                let allowed_methods =
+                    method_table.allowed_methods(&target_path(req.uri().path(), base_path.as_deref()));
+                async move {
+                    let allow = method_table::allow_header_value(&allowed_methods);
+                    let mut builder = Response::builder()
+                        .status(StatusCode::NO_CONTENT)
+                        .header(header::ALLOW, allow.as_str());
+                    // The CORS preflight requests get the methods too, which a CORS middleware may override.
+                    if req.headers().contains_key(header::ACCESS_CONTROL_REQUEST_METHOD) {
+                        builder = builder.header(header::ACCESS_CONTROL_ALLOW_METHODS, allow.as_str());
+                    }
+                    Ok(builder
+                        .body(hyper::Body::empty())
+                        .expect("Couldn't create the default OPTIONS response"))
+                }
             })
             .unwrap();
 
@@ -192,14 +561,41 @@ impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Se
             return;
         }
 
+        let problem_details = self.problem_details;
+        let method_table = self.method_table.clone();
+        let base_path = self.base_path.clone();
         if let Some(router) = self.downcast_to_hyper_body_type() {
             let default_404_route: Route<hyper::Body, E> =
-                Route::new("/*", constants::ALL_POSSIBLE_HTTP_METHODS.to_vec(), |_req| async move {
-                    Ok(Response::builder()
-                        .status(StatusCode::NOT_FOUND)
-                        .header(header::CONTENT_TYPE, "text/plain")
-                        .body(hyper::Body::from(StatusCode::NOT_FOUND.canonical_reason().unwrap()))
-                        .expect("Couldn't create the default 404 response"))
+                Route::new("/*", constants::ALL_POSSIBLE_HTTP_METHODS.to_vec(), move |req| {
+                    // The path is answered with `405 Method Not Allowed` if it has the routes of the other methods.
+                    let allowed_methods =
+                        method_table.allowed_methods(&target_path(req.uri().path(), base_path.as_deref()));
+                    async move {
+                        let (status, allow) = match allowed_methods.is_empty() {
+                            true => (StatusCode::NOT_FOUND, None),
+                            false => (
+                                StatusCode::METHOD_NOT_ALLOWED,
+                                Some(method_table::allow_header_value(&allowed_methods)),
+                            ),
+                        };
+                        let reason = status.canonical_reason().unwrap();
+                        let mut res = if problem_details {
+                            let problem = ProblemDetails::new(status);
+                            with_request_instance(problem, req.headers()).into_response()
+                        } else if let Some(templates) = req.data::<ResponseTemplates>() {
+                            templates.render(status, reason)
+                        } else {
+                            Response::builder()
+                                .status(status)
+                                .header(header::CONTENT_TYPE, "text/plain")
+                                .body(hyper::Body::from(reason))
+                                .expect("Couldn't create the default 404 response")
+                        };
+                        if let Some(val) = allow.and_then(|allow| header::HeaderValue::from_str(&allow).ok()) {
+                            res.headers_mut().insert(header::ALLOW, val);
+                        }
+                        Ok(res)
+                    }
                 })
                 .unwrap();
             router.routes.push(default_404_route);
@@ -218,20 +614,70 @@ impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Se
             return;
         }
 
+        let classify_errors = self.classify_errors;
+        let debug_errors = self.debug_errors;
+        let fingerprint = self.fingerprint.clone();
+        let problem_details = self.problem_details;
+        // The request info is only generated for the templates if they're shared by a data map.
+        let has_templates = self.scoped_data_maps.iter().any(|scoped_data_map| {
+            scoped_data_map
+                .data_map
+                .as_ref()
+                .and_then(|data_map| data_map.get::<ResponseTemplates>())
+                .is_some()
+        });
+
         if let Some(router) = self.downcast_to_hyper_body_type() {
-            let handler: ErrHandler<hyper::Body> = ErrHandler::WithoutInfo(Box::new(move |err: RouteError| {
-                Box::new(async move {
-                    Response::builder()
-                        .status(StatusCode::INTERNAL_SERVER_ERROR)
-                        .header(header::CONTENT_TYPE, "text/plain")
-                        .body(hyper::Body::from(format!(
-                            "{}: {}",
-                            StatusCode::INTERNAL_SERVER_ERROR.canonical_reason().unwrap(),
-                            err
-                        )))
-                        .expect("Couldn't create a response while handling the server error")
-                })
-            }));
+            let handler: ErrHandler<hyper::Body> = if debug_errors {
+                ErrHandler::WithInfo(Box::new(move |err: RouteError, req_info: RequestInfo| {
+                    let fingerprint = fingerprint.clone();
+                    Box::new(async move {
+                        let status = default_err_status(&err, classify_errors);
+                        let page = debug_page::render(&err, status, &req_info, &fingerprint);
+
+                        Response::builder()
+                            .status(status)
+                            .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+                            .body(hyper::Body::from(page))
+                            .expect("Couldn't create a response while handling the server error")
+                    })
+                }))
+            } else if problem_details {
+                ErrHandler::WithInfo(Box::new(move |err: RouteError, req_info: RequestInfo| {
+                    Box::new(async move {
+                        let status = default_err_status(&err, classify_errors);
+                        let problem = match err.downcast_ref::<HttpError>() {
+                            Some(http_err) => match http_err.problem_details() {
+                                Some(problem) => problem.clone(),
+                                None => ProblemDetails::new(status).with_detail(http_err.message()),
+                            },
+                            None if status.is_client_error() => {
+                                ProblemDetails::new(status).with_detail(err.to_string())
+                            }
+                            None => ProblemDetails::new(status),
+                        };
+                        with_request_instance(problem, req_info.headers()).into_response()
+                    })
+                }))
+            } else if has_templates {
+                ErrHandler::WithInfo(Box::new(move |err: RouteError, req_info: RequestInfo| {
+                    Box::new(async move {
+                        let status = default_err_status(&err, classify_errors);
+
+                        match req_info.data::<ResponseTemplates>() {
+                            Some(templates) => templates.render(status, &err.to_string()),
+                            None => default_err_response(status, &err),
+                        }
+                    })
+                }))
+            } else {
+                ErrHandler::WithoutInfo(Box::new(move |err: RouteError| {
+                    Box::new(async move {
+                        let status = default_err_status(&err, classify_errors);
+                        default_err_response(status, &err)
+                    })
+                }))
+            };
             router.err_handler = Some(handler);
         } else {
             eprintln!(
@@ -257,43 +703,141 @@ impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Se
         mut req: Request<hyper::Body>,
         mut req_info: Option<RequestInfo>,
     ) -> crate::Result<Response<B>> {
-        let (
-            matched_pre_middleware_idxs,
-            matched_route_idxs,
-            matched_post_middleware_idxs,
-            matched_scoped_data_map_idxs,
-        ) = self.match_regex_set(target_path);
-
-        let mut route_scope_depth = None;
-        for idx in &matched_route_idxs {
-            let route = &self.routes[*idx];
-            // Middleware should be executed even if there's no route, e.g.
-            // logging. Before doing the depth check make sure that there's
-            // an actual route match, not a catch-all "/*".
-            if route.is_match_method(req.method()) && route.path != "/*" {
-                route_scope_depth = Some(route.scope_depth);
-                break;
-            }
+        let matches = self.match_request(req.method(), target_path);
+        let matched_route = matches.matched_route.map(|idx| &self.routes[idx]);
+
+        // The method and the path of the request are kept for the events, as the request is moved.
+        let request_line = self
+            .observer
+            .as_ref()
+            .map(|_| (req.method().clone(), req.uri().path().to_owned()));
+        if let (Some(observer), Some(route), Some((method, path))) = (&self.observer, matched_route, &request_line) {
+            observer.emit(|| RouterEvent::RouteMatched {
+                method: method.clone(),
+                path: path.clone(),
+                // The trailing slash which is added to the route paths is dropped, as in the route exports.
+                route_path: match route.path.strip_suffix('/') {
+                    Some(route_path) if !route_path.is_empty() && route.raw_regex.is_none() => route_path.to_owned(),
+                    _ => route.path.clone(),
+                },
+            });
         }
 
-        let shared_data_maps = matched_scoped_data_map_idxs
-            .into_iter()
-            .map(|idx| self.scoped_data_maps[idx].clone_data_map())
-            .collect::<Vec<_>>();
+        // The method is kept for the status mappers, as the request is moved.
+        let method = match self.status_mappers.is_empty() {
+            true => None,
+            false => Some(req.method().clone()),
+        };
+
+        let route_scope_depth = matched_route.map(|route| route.scope_depth);
+        let pre_middleware_idxs = match matched_route.and_then(|route| route.pre_middleware_plan.as_deref()) {
+            Some(plan) => Cow::Borrowed(plan),
+            None => Cow::Owned(Router::<B, E>::applicable_middleware_idxs(
+                &matches.pre_middlewares,
+                |idx| self.pre_middlewares[idx].scope_depth,
+                route_scope_depth,
+                matched_route.map_or(&[], |route| route.skipped_pre_middleware_idxs.as_slice()),
+            )),
+        };
+        let post_middleware_idxs = match matched_route.and_then(|route| route.post_middleware_plan.as_deref()) {
+            Some(plan) => Cow::Borrowed(plan),
+            None => Cow::Owned(Router::<B, E>::applicable_middleware_idxs(
+                &matches.post_middlewares,
+                |idx| self.post_middlewares[idx].scope_depth,
+                route_scope_depth,
+                matched_route.map_or(&[], |route| route.skipped_post_middleware_idxs.as_slice()),
+            )),
+        };
+
+        // The outcome of the route is timed for the result handlers of the post middlewares, if any.
+        let started_at = post_middleware_idxs
+            .iter()
+            .any(|idx| self.post_middlewares[*idx].is_result_handler())
+            .then(Instant::now);
+
+        let around_middleware_idxs = match matched_route.and_then(|route| route.around_middleware_plan.as_deref()) {
+            Some(plan) => Cow::Borrowed(plan),
+            None => Cow::Owned(Router::<B, E>::applicable_middleware_idxs(
+                &matches.around_middlewares,
+                |idx| self.around_middlewares[idx].scope_depth,
+                route_scope_depth,
+                matched_route.map_or(&[], |route| route.skipped_around_middleware_idxs.as_slice()),
+            )),
+        };
+
+        let shared_data_maps = match matched_route.and_then(|route| route.shared_data_maps.as_ref()) {
+            Some(shared_data_maps) => shared_data_maps.clone(),
+            None => matches
+                .scoped_data_maps
+                .iter()
+                .map(|idx| self.scoped_data_maps[*idx].clone_data_map())
+                .collect::<Arc<[_]>>(),
+        };
+
+        // The configs of the routes are resolved once, so only the other requests match the scope paths.
+        let scope_configs = match self.scoped_config_maps.is_empty() {
+            true => None,
+            false => Some(SharedConfigMaps(
+                match matched_route.and_then(|route| route.scope_configs.as_ref()) {
+                    Some(scope_configs) => scope_configs.clone(),
+                    None => self
+                        .scoped_config_maps
+                        .iter()
+                        .filter(|scoped_config_map| scoped_config_map.regex.is_match(target_path))
+                        .map(|scoped_config_map| scoped_config_map.clone_data_map())
+                        .collect::<Arc<[_]>>(),
+                },
+            )),
+        };
 
         if let Some(ref mut req_info) = req_info {
             if !shared_data_maps.is_empty() {
                 req_info.shared_data_maps.replace(shared_data_maps.clone());
             }
+            req_info.scope_configs = scope_configs.clone();
+
+            if let Some(route) = matched_route {
+                req_info.route_path = Some(route.path.clone());
+                req_info.route_params = Some(route.generate_route_params(target_path));
+            }
         }
 
         let ext = req.extensions_mut();
         ext.insert(shared_data_maps);
+        if let Some(scope_configs) = scope_configs {
+            ext.insert(scope_configs);
+        }
+
+        // The pre middlewares can read and modify the params of the matched route.
+        if let (Some(route), false) = (matched_route, pre_middleware_idxs.is_empty()) {
+            let route_params = match req_info.as_ref().and_then(|req_info| req_info.route_params.clone()) {
+                Some(route_params) => route_params,
+                None => route.generate_route_params(target_path),
+            };
+            helpers::update_req_meta_in_extensions(req.extensions_mut(), RequestMeta::with_route_params(route_params));
+        }
 
         let res_pre = self
-            .execute_pre_middleware(req, matched_pre_middleware_idxs, route_scope_depth, req_info.clone())
+            .execute_pre_middleware(
+                req,
+                &pre_middleware_idxs,
+                req_info.clone(),
+                request_line.as_ref(),
+                &post_middleware_idxs,
+                started_at,
+            )
             .await?;
 
+        if let (Ok(ref transformed_req), Some(ref mut req_info)) = (&res_pre, &mut req_info) {
+            if let Some(req_meta) = transformed_req
+                .extensions()
+                .get::<RequestMeta>()
+                .filter(|req_meta| req_meta.is_route_params_modified())
+            {
+                req_info.route_params = req_meta.route_params().cloned();
+            }
+        }
+
         // If pre middlewares succeed then execute the route handler.
         // If a pre middleware fails and is able to generate error response
         // (because Router.err_handler is set), then skip directly to post
@@ -301,12 +845,23 @@ impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Se
         let mut resp = None;
         match res_pre {
             Ok(transformed_req) => {
-                for idx in matched_route_idxs {
-                    let route = &self.routes[idx];
+                for idx in matches.routes.iter() {
+                    let route = &self.routes[*idx];
 
-                    if route.is_match_method(transformed_req.method()) {
-                        let route_resp_res = route.process(target_path, transformed_req).await;
+                    if route.is_match_method(transformed_req.method()) && route.is_match_params(target_path) {
+                        let route_resp_res = self
+                            .execute_around_middleware(route, target_path, transformed_req, &around_middleware_idxs)
+                            .await;
+
+                        // A forwarded request is answered by the route it's forwarded to, so its placeholder response
+                        // skips the post middlewares and the status mapping of this hop.
+                        if let Ok(ref route_resp) = route_resp_res {
+                            if route_resp.extensions().get::<Forwarded>().is_some() {
+                                return route_resp_res;
+                            }
+                        }
 
+                        self.report_result(&post_middleware_idxs, started_at, route_resp_res.as_ref());
                         let route_resp = match route_resp_res {
                             Ok(route_resp) => route_resp,
                             Err(err) => {
@@ -334,50 +889,117 @@ impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Se
         }
 
         let mut transformed_res = resp.unwrap();
-        for idx in matched_post_middleware_idxs {
-            let post_middleware = &self.post_middlewares[idx];
-            // Do not execute middleware with the same prefix but from a deeper scope.
-            if route_scope_depth.is_none() || post_middleware.scope_depth <= route_scope_depth.unwrap() {
-                match post_middleware.process(transformed_res, req_info.clone()).await {
-                    Ok(res_resp) => {
-                        transformed_res = res_resp;
-                    }
-                    Err(err) => {
-                        if let Some(ref err_handler) = self.err_handler {
-                            return Ok(err_handler.execute(err, req_info.clone()).await);
-                        } else {
-                            return Err(err);
-                        }
+        for idx in post_middleware_idxs.iter() {
+            let post_middleware = &self.post_middlewares[*idx];
+            if !post_middleware.is_enabled() {
+                continue;
+            }
+
+            match post_middleware.process(transformed_res, req_info.clone()).await {
+                Ok(res_resp) => {
+                    transformed_res = res_resp;
+                }
+                Err(err) => {
+                    self.emit_middleware_failed(request_line.as_ref(), MiddlewareStage::Post, &err);
+                    if let Some(ref err_handler) = self.err_handler {
+                        transformed_res = err_handler.execute(err, req_info.clone()).await;
+                        break;
+                    } else {
+                        return Err(err);
                     }
                 }
             }
         }
 
+        if let Some(ref method) = method {
+            let route_meta = RouteMeta {
+                method,
+                path: match target_path.strip_suffix('/') {
+                    Some(path) if !path.is_empty() => path,
+                    _ => target_path,
+                },
+                route_path: matched_route.map(|route| match route.path.strip_suffix('/') {
+                    Some(route_path) if !route_path.is_empty() && route.raw_regex.is_none() => route_path,
+                    _ => route.path.as_str(),
+                }),
+            };
+            let status = self
+                .status_mappers
+                .iter()
+                .filter(|mapper| route_scope_depth.is_none() || mapper.scope_depth <= route_scope_depth.unwrap())
+                .filter(|mapper| mapper.regex.is_match(target_path))
+                .fold(transformed_res.status(), |status, mapper| {
+                    (mapper.handler)(status, &route_meta)
+                });
+            *transformed_res.status_mut() = status;
+        }
+
+        let mut transformed_res = self.map_err_response(target_path, transformed_res, req_info).await;
+        if !self.response_headers.is_empty() {
+            let headers = transformed_res.headers_mut();
+            match matched_route.and_then(|route| route.response_headers_plan.as_deref()) {
+                Some(plan) => plan.iter().for_each(|idx| self.response_headers[*idx].apply(headers)),
+                None => self
+                    .response_headers
+                    .iter()
+                    .filter(|h| route_scope_depth.is_none() || h.scope_depth <= route_scope_depth.unwrap())
+                    .filter(|h| h.regex.is_match(target_path))
+                    .for_each(|h| h.apply(headers)),
+            }
+        }
         Ok(transformed_res)
     }
 
+    // Rewrites an error response by the mapper of the innermost scope matching the path, if any.
+    async fn map_err_response(
+        &self,
+        target_path: &str,
+        res: Response<B>,
+        req_info: Option<RequestInfo>,
+    ) -> Response<B> {
+        let status = res.status();
+        if !status.is_client_error() && !status.is_server_error() {
+            return res;
+        }
+
+        let mapper = self
+            .err_response_mappers
+            .iter()
+            .filter(|mapper| mapper.regex.is_match(target_path))
+            .max_by_key(|mapper| mapper.scope_depth);
+        match (mapper, req_info) {
+            (Some(mapper), Some(req_info)) => Pin::from((mapper.handler)(status, req_info)).await,
+            _ => res,
+        }
+    }
+
     async fn execute_pre_middleware(
         &self,
         req: Request<hyper::Body>,
-        matched_pre_middleware_idxs: Vec<usize>,
-        route_scope_depth: Option<u32>,
+        pre_middleware_idxs: &[usize],
         req_info: Option<RequestInfo>,
+        request_line: Option<&(Method, String)>,
+        post_middleware_idxs: &[usize],
+        started_at: Option<Instant>,
     ) -> crate::Result<Result<Request<hyper::Body>, Response<B>>> {
         let mut transformed_req = req;
-        for idx in matched_pre_middleware_idxs {
-            let pre_middleware = &self.pre_middlewares[idx];
-            // Do not execute middleware with the same prefix but from a deeper scope.
-            if route_scope_depth.is_none() || pre_middleware.scope_depth <= route_scope_depth.unwrap() {
-                match pre_middleware.process(transformed_req).await {
-                    Ok(res_req) => {
-                        transformed_req = res_req;
-                    }
-                    Err(err) => {
-                        if let Some(ref err_handler) = self.err_handler {
-                            return Ok(Err(err_handler.execute(err, req_info).await));
-                        } else {
-                            return Err(err);
-                        }
+        for idx in pre_middleware_idxs {
+            let pre_middleware = &self.pre_middlewares[*idx];
+            if !pre_middleware.is_enabled() {
+                continue;
+            }
+
+            match pre_middleware.process(transformed_req).await {
+                Ok(res_req) => {
+                    transformed_req = res_req;
+                }
+                Err(err) => {
+                    self.emit_middleware_failed(request_line, MiddlewareStage::Pre, &err);
+                    self.report_result(post_middleware_idxs, started_at, Err(&err));
+                    if let Some(ref err_handler) = self.err_handler {
+                        return Ok(Err(err_handler.execute(err, req_info).await));
+                    } else {
+                        return Err(err);
                     }
                 }
             }
@@ -385,7 +1007,345 @@ impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Se
         Ok(Ok(transformed_req))
     }
 
-    fn match_regex_set(&self, target_path: &str) -> (Vec<usize>, Vec<usize>, Vec<usize>, Vec<usize>) {
+    // Reports the outcome of the route to the result handlers of the post middlewares, before the error handler runs.
+    fn report_result(
+        &self,
+        post_middleware_idxs: &[usize],
+        started_at: Option<Instant>,
+        result: Result<&Response<B>, &RouteError>,
+    ) {
+        if let Some(started_at) = started_at {
+            let elapsed = started_at.elapsed();
+            for idx in post_middleware_idxs.iter() {
+                self.post_middlewares[*idx].report_result(result, elapsed);
+            }
+        }
+    }
+
+    fn emit_middleware_failed(
+        &self,
+        request_line: Option<&(Method, String)>,
+        stage: MiddlewareStage,
+        err: &RouteError,
+    ) {
+        if let (Some(observer), Some((method, path))) = (&self.observer, request_line) {
+            observer.emit(|| RouterEvent::MiddlewareFailed {
+                method: method.clone(),
+                path: path.clone(),
+                stage,
+                error: err.to_string(),
+            });
+        }
+    }
+
+    // Runs the enabled around middlewares, each one wrapping the next ones and the route handler.
+    fn execute_around_middleware<'a>(
+        &'a self,
+        route: &'a Route<B, E>,
+        target_path: &'a str,
+        req: Request<hyper::Body>,
+        around_middleware_idxs: &'a [usize],
+    ) -> Pin<Box<dyn Future<Output = crate::Result<Response<B>>> + Send + 'a>> {
+        let mut idxs = around_middleware_idxs.iter();
+        let around_middleware = idxs
+            .by_ref()
+            .map(|idx| &self.around_middlewares[*idx])
+            .find(|around_middleware| around_middleware.is_enabled());
+
+        match around_middleware {
+            Some(around_middleware) => {
+                let rest = idxs.as_slice();
+                Box::pin(around_middleware.process(req, move |req| {
+                    self.execute_around_middleware(route, target_path, req, rest)
+                }))
+            }
+            None => Box::pin(route.process(target_path, req)),
+        }
+    }
+
+    // Filters the matched middlewares of a route without a precomputed plan.
+    fn applicable_middleware_idxs(
+        matched_middleware_idxs: &[usize],
+        scope_depth_of: impl Fn(usize) -> u32,
+        route_scope_depth: Option<u32>,
+        skipped_idxs: &[usize],
+    ) -> Vec<usize> {
+        matched_middleware_idxs
+            .iter()
+            .copied()
+            .filter(|idx| !skipped_idxs.contains(idx))
+            // Do not execute middleware with the same prefix but from a deeper scope.
+            .filter(|idx| route_scope_depth.is_none() || scope_depth_of(*idx) <= route_scope_depth.unwrap())
+            .collect()
+    }
+
+    fn find_matched_route<'a>(
+        mut routes: impl Iterator<Item = &'a Route<B, E>>,
+        method: &Method,
+        target_path: &str,
+    ) -> Option<&'a Route<B, E>> {
+        // Middleware should be executed even if there's no route, e.g.
+        // logging. Before doing the depth check make sure that there's
+        // an actual route match, not a catch-all "/*".
+        routes.find(|route| Router::is_matched_route(route, method, target_path))
+    }
+
+    fn is_matched_route(route: &Route<B, E>, method: &Method, target_path: &str) -> bool {
+        route.is_match_method(method) && route.path != "/*" && route.is_match_params(target_path)
+    }
+
+    /// Returns a handle to enable or disable the [named](./enum.Middleware.html#method.named) middlewares of the router
+    /// at runtime.
+    ///
+    /// The handle stays valid after the router is served or mounted into another router.
+    /// Please refer to the [`Middleware::enabled`](./enum.Middleware.html#method.enabled) method for an example.
+    pub fn handle(&self) -> RouterHandle {
+        let pre_switches = self
+            .pre_middlewares
+            .iter()
+            .filter_map(|m| m.name.clone().map(|name| (name, m.enabled.clone())));
+        let post_switches = self
+            .post_middlewares
+            .iter()
+            .filter_map(|m| m.name.clone().map(|name| (name, m.enabled.clone())));
+
+        let around_switches = self
+            .around_middlewares
+            .iter()
+            .filter_map(|m| m.name.clone().map(|name| (name, m.enabled.clone())));
+
+        RouterHandle::new(pre_switches.chain(post_switches).chain(around_switches).collect())
+    }
+
+    /// Returns the pre, the around and the post middlewares which would be executed for a request with the specified method and path,
+    /// in the execution order.
+    ///
+    /// It can be used to assert the middleware ordering e.g. that a CORS middleware runs before an auth middleware.
+    /// Please refer to the [`RouterBuilder::middleware_with_priority`](./struct.RouterBuilder.html#method.middleware_with_priority)
+    /// method for an example.
+    pub fn middleware_order(&self, method: &Method, path: &str) -> Vec<MiddlewareInfo> {
+        let mut target_path = path.to_owned();
+        if !target_path.ends_with('/') {
+            target_path.push('/');
+        }
+
+        let matched_route = Router::find_matched_route(
+            self.routes.iter().filter(|route| route.regex.is_match(&target_path)),
+            method,
+            target_path.as_str(),
+        );
+        let route_scope_depth = matched_route.map(|route| route.scope_depth);
+        let is_skipped = |name: &Option<String>| match (matched_route, name) {
+            (Some(route), Some(name)) => route.skipped_middlewares.contains(name),
+            _ => false,
+        };
+        let is_isolated = |scope_depth: u32| matches!(matched_route.and_then(|route| route.isolation_depth), Some(depth) if scope_depth < depth);
+        let should_execute = |regex: &Regex, scope_depth: u32, name: &Option<String>| {
+            regex.is_match(&target_path)
+                && (route_scope_depth.is_none() || scope_depth <= route_scope_depth.unwrap())
+                && !is_skipped(name)
+                && !is_isolated(scope_depth)
+        };
+
+        let pre = self
+            .pre_middlewares
+            .iter()
+            .filter(|m| m.is_enabled() && should_execute(&m.regex, m.scope_depth, &m.name))
+            .map(|m| MiddlewareInfo {
+                kind: MiddlewareKind::Pre,
+                name: m.name.clone(),
+                path: m.path.clone(),
+                priority: m.priority,
+                scope_depth: m.scope_depth,
+            });
+        let post = self
+            .post_middlewares
+            .iter()
+            .filter(|m| m.is_enabled() && should_execute(&m.regex, m.scope_depth, &m.name))
+            .map(|m| MiddlewareInfo {
+                kind: MiddlewareKind::Post,
+                name: m.name.clone(),
+                path: m.path.clone(),
+                priority: m.priority,
+                scope_depth: m.scope_depth,
+            });
+
+        let around = self
+            .around_middlewares
+            .iter()
+            .filter(|m| m.is_enabled() && should_execute(&m.regex, m.scope_depth, &m.name))
+            .map(|m| MiddlewareInfo {
+                kind: MiddlewareKind::Around,
+                name: m.name.clone(),
+                path: m.path.clone(),
+                priority: m.priority,
+                scope_depth: m.scope_depth,
+            });
+
+        pre.chain(around).chain(post).collect()
+    }
+
+    /// Returns the methods of the routes at the specified path across the scopes, e.g. to assert the methods which the
+    /// `OPTIONS` and the `405 Method Not Allowed` responses list in the `Allow` header.
+    ///
+    /// The method table is computed once the router is built, and the catch-all routes of the root router e.g. the
+    /// [`any`](./struct.RouterBuilder.html#method.any) one aren't in it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::Router;
+    /// use hyper::{Response, Body, Method};
+    /// # use std::convert::Infallible;
+    ///
+    /// let api: Router<Body, Infallible> = Router::builder()
+    ///     .put("/users/:id", |_| async move { Ok(Response::new(Body::from("Updated"))) })
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// let router: Router<Body, Infallible> = Router::builder()
+    ///     .get("/api/users/:id", |_| async move { Ok(Response::new(Body::from("User"))) })
+    ///     .scope("/api", api)
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// assert_eq!(router.allowed_methods("/api/users/1"), vec![Method::GET, Method::PUT]);
+    /// assert!(router.allowed_methods("/unknown").is_empty());
+    /// ```
+    pub fn allowed_methods(&self, path: &str) -> Vec<Method> {
+        self.method_table.allowed_methods(&target_path(path, None))
+    }
+
+    /// Returns the deprecation of the route which would handle a request with the specified method and path, if it's
+    /// [deprecated](./struct.RouterBuilder.html#method.deprecated).
+    pub fn deprecation(&self, method: &Method, path: &str) -> Option<&Deprecation> {
+        let mut target_path = path.to_owned();
+        if !target_path.ends_with('/') {
+            target_path.push('/');
+        }
+
+        Router::find_matched_route(
+            self.routes.iter().filter(|route| route.regex.is_match(&target_path)),
+            method,
+            target_path.as_str(),
+        )
+        .and_then(|route| route.deprecation.as_deref())
+    }
+
+    /// Returns the routes of the router in their matching order, with the paths of the scopes they are mounted on.
+    ///
+    /// A [`RouteInfo`](./struct.RouteInfo.html) matches the request paths the same way as the router does, so the route
+    /// table can be tested without serving it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::Router;
+    /// use hyper::{Response, Body};
+    /// use std::convert::Infallible;
+    ///
+    /// let router: Router<Body, Infallible> = Router::builder()
+    ///     .base_path("/api")
+    ///     .typed_param::<u32, _>("id")
+    ///     .get("/users/:id", |_| async move { Ok(Response::new(Body::from("User"))) })
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// let routes = router.routes();
+    /// assert_eq!(routes[0].path(), "/users/:id/");
+    /// assert!(routes[0].is_match("/api/users/42"));
+    /// assert!(!routes[0].is_match("/api/users/alice"));
+    /// ```
+    pub fn routes(&self) -> Vec<RouteInfo> {
+        self.routes
+            .iter()
+            .map(|route| RouteInfo::new(route, self.base_path.clone()))
+            .collect()
+    }
+
+    /// Exports the route table as a machine-readable manifest, so the configuration of an API gateway or a reverse
+    /// proxy can be generated from the routes in code e.g. by a build script or a CLI flag.
+    ///
+    /// The routes are listed in their matching order with the paths of the scopes they are mounted on. The gateway
+    /// regex of a route matches its request paths with or without the trailing slash.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::{ExportFormat, Router};
+    /// use hyper::{Response, Body};
+    /// use std::convert::Infallible;
+    ///
+    /// let router: Router<Body, Infallible> = Router::builder()
+    ///     .get("/users/:id", |_| async move { Ok(Response::new(Body::from("User"))) })
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// let manifest = router.export(ExportFormat::Json);
+    /// assert!(manifest.contains(r#""path": "/users/:id""#));
+    ///
+    /// let nginx = router.export(ExportFormat::Nginx { upstream: "http://backend".to_owned() });
+    /// assert!(nginx.contains("location ~ \"^/users/([^/]+)/?$\" {"));
+    /// ```
+    pub fn export(&self, format: ExportFormat) -> String {
+        export::export(&self.routes, &format)
+    }
+
+    /// Returns a stable hash of the route paths and methods and the middleware kinds, paths and names, so the
+    /// operators can verify which route configuration a running instance serves.
+    ///
+    /// It's a hex string of 16 characters, which is the same across the builds, the platforms and the restarts as long
+    /// as the routes and the middlewares are added in the same order. It can be sent in a response header by the
+    /// [`RouterBuilder::fingerprint_header`](./struct.RouterBuilder.html#method.fingerprint_header) option, and it's
+    /// shown on the error pages of the [`debug_errors`](./struct.RouterBuilder.html#method.debug_errors) option.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use routerify::Router;
+    /// use hyper::{Response, Body};
+    /// use std::convert::Infallible;
+    ///
+    /// let build = || -> Router<Body, Infallible> {
+    ///     Router::builder()
+    ///         .get("/users/:id", |_| async move { Ok(Response::new(Body::from("User"))) })
+    ///         .build()
+    ///         .unwrap()
+    /// };
+    ///
+    /// assert_eq!(build().fingerprint(), build().fingerprint());
+    /// assert_eq!(build().fingerprint().len(), 16);
+    /// ```
+    pub fn fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+
+    // Returns the route which would handle a request with the target path, without executing anything.
+    #[cfg(feature = "fuzz")]
+    pub(crate) fn matched_route(&self, method: &Method, target_path: &str) -> Option<&Route<B, E>> {
+        self.match_request(method, target_path)
+            .matched_route
+            .map(|idx| &self.routes[idx])
+    }
+
+    // Looks up the match cache first, if enabled, and evaluates the regex set on a miss.
+
+    fn match_request(&self, method: &Method, target_path: &str) -> Arc<RegexMatches> {
+        let cache = match self.match_cache {
+            Some(ref cache) => cache,
+            None => return Arc::new(self.match_regex_set(method, target_path)),
+        };
+
+        if let Some(matches) = cache.get(method, target_path) {
+            return matches;
+        }
+
+        let matches = Arc::new(self.match_regex_set(method, target_path));
+        cache.insert(method, target_path, matches.clone());
+        matches
+    }
+
+    fn match_regex_set(&self, method: &Method, target_path: &str) -> RegexMatches {
         let matches = self
             .regex_set
             .as_ref()
@@ -394,37 +1354,103 @@ impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Se
             .into_iter();
 
         let pre_middlewares_len = self.pre_middlewares.len();
-        let routes_len = self.routes.len();
+        let routes_len = self.regex_route_idxs.len();
         let post_middlewares_len = self.post_middlewares.len();
         let scoped_data_maps_len = self.scoped_data_maps.len();
+        let data_maps_end = pre_middlewares_len + routes_len + post_middlewares_len + scoped_data_maps_len;
 
         let mut matched_pre_middleware_idxs = Vec::new();
         let mut matched_route_idxs = Vec::new();
         let mut matched_post_middleware_idxs = Vec::new();
         let mut matched_scoped_data_map_idxs = Vec::new();
+        let mut matched_around_middleware_idxs = Vec::new();
 
         for idx in matches {
             if idx < pre_middlewares_len {
                 matched_pre_middleware_idxs.push(idx);
             } else if idx >= pre_middlewares_len && idx < (pre_middlewares_len + routes_len) {
-                matched_route_idxs.push(idx - pre_middlewares_len);
+                matched_route_idxs.push(self.regex_route_idxs[idx - pre_middlewares_len]);
             } else if idx >= (pre_middlewares_len + routes_len)
                 && idx < (pre_middlewares_len + routes_len + post_middlewares_len)
             {
                 matched_post_middleware_idxs.push(idx - pre_middlewares_len - routes_len);
-            } else if idx >= (pre_middlewares_len + routes_len + post_middlewares_len)
-                && idx < (pre_middlewares_len + routes_len + post_middlewares_len + scoped_data_maps_len)
-            {
+            } else if idx >= (pre_middlewares_len + routes_len + post_middlewares_len) && idx < data_maps_end {
                 matched_scoped_data_map_idxs.push(idx - pre_middlewares_len - routes_len - post_middlewares_len);
+            } else if idx >= data_maps_end {
+                matched_around_middleware_idxs.push(idx - data_maps_end);
             }
         }
 
-        (
-            matched_pre_middleware_idxs,
-            matched_route_idxs,
-            matched_post_middleware_idxs,
-            matched_scoped_data_map_idxs,
-        )
+        // The routes are tried in the registration order, regardless of the backend which matched them.
+        #[cfg(feature = "fast-match")]
+        if let Some(ref fast_matcher) = self.fast_matcher {
+            matched_route_idxs.extend_from_slice(fast_matcher.find(target_path));
+            matched_route_idxs.sort_unstable();
+        }
+
+        let matched_route = matched_route_idxs
+            .iter()
+            .copied()
+            .find(|idx| Router::is_matched_route(&self.routes[*idx], method, target_path));
+
+        RegexMatches {
+            pre_middlewares: matched_pre_middleware_idxs,
+            routes: matched_route_idxs,
+            post_middlewares: matched_post_middleware_idxs,
+            around_middlewares: matched_around_middleware_idxs,
+            scoped_data_maps: matched_scoped_data_map_idxs,
+            matched_route,
+        }
+    }
+}
+
+// Returns the path which the routes are matched by, as the request service does. A path which can't be decoded is
+// returned as it is, so it matches no route.
+fn target_path(path: &str, base_path: Option<&str>) -> String {
+    match helpers::route_target_path(path, base_path) {
+        Ok((target_path, _)) => target_path,
+        Err(_) => path.to_owned(),
+    }
+}
+
+fn default_err_response(status: StatusCode, err: &RouteError) -> Response<hyper::Body> {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "text/plain")
+        .body(hyper::Body::from(format!(
+            "{}: {}",
+            status.canonical_reason().unwrap_or_default(),
+            err
+        )))
+        .expect("Couldn't create a response while handling the server error")
+}
+
+// Sets the instance of the problem to the request id, if the problem has no instance and the id is URI safe.
+fn with_request_instance(problem: ProblemDetails, headers: &HeaderMap) -> ProblemDetails {
+    let id = headers.get("x-request-id").map(|val| val.as_bytes()).filter(|id| {
+        !id.is_empty()
+            && id
+                .iter()
+                .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~'))
+    });
+
+    match id {
+        Some(id) if problem.instance().is_none() => {
+            let id = String::from_utf8_lossy(id);
+            problem.with_instance(format!("urn:request-id:{}", id))
+        }
+        _ => problem,
+    }
+}
+
+fn default_err_status(err: &RouteError, classify_errors: bool) -> StatusCode {
+    if classify_errors {
+        err.classify().status()
+    } else {
+        err.downcast_ref::<HttpError>()
+            .map(|err| err.status())
+            .or_else(|| err.downcast_ref::<BodyError>().map(|err| err.status()))
+            .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
     }
 }
 
@@ -432,10 +1458,11 @@ impl<B, E> Debug for Router<B, E> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "{{ Pre-Middlewares: {:?}, Routes: {:?}, Post-Middlewares: {:?}, ScopedDataMaps: {:?}, ErrHandler: {:?}, ShouldGenReqInfo: {:?} }}",
+            "{{ Pre-Middlewares: {:?}, Routes: {:?}, Post-Middlewares: {:?}, Around-Middlewares: {:?}, ScopedDataMaps: {:?}, ErrHandler: {:?}, ShouldGenReqInfo: {:?} }}",
             self.pre_middlewares,
             self.routes,
             self.post_middlewares,
+            self.around_middlewares,
             self.scoped_data_maps,
             self.err_handler.is_some(),
             self.should_gen_req_info
@@ -0,0 +1,39 @@
+use crate::regex_generator::generate_exact_match_regex;
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
+use regex::Regex;
+use std::sync::Arc;
+
+// The static response headers of the routes under a path, which are added by `RouterBuilder::with_headers`.
+#[derive(Debug, Clone)]
+pub(crate) struct ResponseHeaders {
+    pub(crate) path: String,
+    pub(crate) regex: Regex,
+    pub(crate) scope_depth: u32,
+    pub(crate) headers: Arc<[(HeaderName, HeaderValue)]>,
+}
+
+impl ResponseHeaders {
+    pub(crate) fn new(
+        path: String,
+        headers: Arc<[(HeaderName, HeaderValue)]>,
+        scope_depth: u32,
+    ) -> crate::Result<ResponseHeaders> {
+        let (regex, _) = generate_exact_match_regex(&path)?;
+        Ok(ResponseHeaders {
+            path,
+            regex,
+            scope_depth,
+            headers,
+        })
+    }
+
+    // Adds the headers which aren't set by the handlers or the middlewares. The names and the values are shared, so
+    // nothing is copied.
+    pub(crate) fn apply(&self, headers: &mut HeaderMap) {
+        for (name, value) in self.headers.iter() {
+            if !headers.contains_key(name) {
+                headers.insert(name.clone(), value.clone());
+            }
+        }
+    }
+}
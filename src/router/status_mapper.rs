@@ -0,0 +1,64 @@
+use crate::regex_generator::generate_exact_match_regex;
+use hyper::{Method, StatusCode};
+use regex::Regex;
+use std::fmt::{self, Debug, Formatter};
+use std::sync::Arc;
+
+pub(crate) type StatusMapperHandler = Arc<dyn Fn(StatusCode, &RouteMeta<'_>) -> StatusCode + Send + Sync + 'static>;
+
+// Rewrites the response statuses of the routes under its path, which is the path of the router it's added to.
+#[derive(Clone)]
+pub(crate) struct StatusMapper {
+    pub(crate) path: String,
+    pub(crate) regex: Regex,
+    pub(crate) scope_depth: u32,
+    pub(crate) handler: StatusMapperHandler,
+}
+
+impl StatusMapper {
+    pub(crate) fn new(path: String, handler: StatusMapperHandler, scope_depth: u32) -> crate::Result<StatusMapper> {
+        let (regex, _) = generate_exact_match_regex(&path)?;
+        Ok(StatusMapper {
+            path,
+            regex,
+            scope_depth,
+            handler,
+        })
+    }
+}
+
+/// Describes the request and its matched route to a status mapper, which is added by the
+/// [`RouterBuilder::map_status`](./struct.RouterBuilder.html#method.map_status) method.
+#[derive(Clone, Copy)]
+pub struct RouteMeta<'a> {
+    pub(crate) method: &'a Method,
+    pub(crate) path: &'a str,
+    pub(crate) route_path: Option<&'a str>,
+}
+
+impl<'a> RouteMeta<'a> {
+    /// Returns the request method.
+    pub fn method(&self) -> &'a Method {
+        self.method
+    }
+
+    /// Returns the percent decoded request path after the base path.
+    pub fn path(&self) -> &'a str {
+        self.path
+    }
+
+    /// Returns the path of the matched route e.g. `/users/:userId`, or `None` if no route is matched.
+    pub fn route_path(&self) -> Option<&'a str> {
+        self.route_path
+    }
+}
+
+impl Debug for RouteMeta<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{{ method: {:?}, path: {:?}, route_path: {:?} }}",
+            self.method, self.path, self.route_path
+        )
+    }
+}
@@ -0,0 +1,319 @@
+// The upgrade of an HTTP/1.1 connection to the cleartext HTTP/2, i.e. the `Upgrade: h2c` offers:
+// https://www.rfc-editor.org/rfc/rfc7540#section-3.2
+//
+// The upgraded request is answered as the stream 1 of the HTTP/2 connection. Hyper serves HTTP/2 only from the start of
+// a connection, so the request is replayed to it as a HEADERS frame of the stream 1 right after the preface of the
+// client. The header block is made of literal fields without indexing, so the HPACK state of the connection is kept.
+use hyper::header::{self, HeaderName, HeaderValue};
+use hyper::upgrade::OnUpgrade;
+use hyper::{body::HttpBody, Body, Method, Request, Response, StatusCode, Version};
+use std::any::Any;
+use std::io::{self, ErrorKind, IoSlice};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, ReadBuf};
+
+const PREFACE: &[u8; 24] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+const FRAME_HEADER_LEN: usize = 9;
+// The initial SETTINGS_MAX_FRAME_SIZE, which any server accepts.
+const MAX_FRAME_SIZE: usize = 16_384;
+
+const FRAME_HEADERS: u8 = 0x1;
+const FRAME_SETTINGS: u8 = 0x4;
+const FRAME_CONTINUATION: u8 = 0x9;
+const FLAG_END_STREAM: u8 = 0x1;
+const FLAG_END_HEADERS: u8 = 0x4;
+
+const HTTP2_SETTINGS: &str = "http2-settings";
+
+// The IO of the connection once it's handed over, and the frames replaying the upgraded request.
+type PendingUpgrade = (OnUpgrade, Vec<u8>);
+
+/// The pending h2c upgrade of a connection. It's accepted by the request service and carried out by the serving layer,
+/// once the HTTP/1.1 connection hands its IO over.
+#[derive(Clone, Default)]
+pub(crate) struct H2cUpgrade {
+    pending: Arc<Mutex<Option<PendingUpgrade>>>,
+}
+
+impl H2cUpgrade {
+    /// Accepts the h2c upgrade offer of the request and returns the `101 Switching Protocols` response. It returns `None`
+    /// if the request doesn't offer the upgrade or it can't be upgraded, i.e. it has a body or the response body type
+    /// isn't `hyper::Body`, so the request is served over HTTP/1.1.
+    pub(crate) fn accept<B: 'static>(&self, req: &mut Request<Body>) -> Option<Response<B>> {
+        let frames = request_frames(req)?;
+
+        let resp = Response::builder()
+            .status(StatusCode::SWITCHING_PROTOCOLS)
+            .header(header::CONNECTION, HeaderValue::from_static("Upgrade"))
+            .header(header::UPGRADE, HeaderValue::from_static("h2c"))
+            .body(Body::empty())
+            .ok()?;
+        let resp: Box<dyn Any> = Box::new(resp);
+        let resp = *resp.downcast::<Response<B>>().ok()?;
+
+        *self.pending.lock().unwrap() = Some((hyper::upgrade::on(req), frames));
+        Some(resp)
+    }
+
+    /// Takes the accepted upgrade.
+    pub(crate) fn take(&self) -> Option<PendingUpgrade> {
+        self.pending.lock().unwrap().take()
+    }
+}
+
+// Returns the frames which replay the request as the stream 1, if it offers the upgrade and it has no body.
+fn request_frames(req: &Request<Body>) -> Option<Vec<u8>> {
+    let headers = req.headers();
+    let offers_h2c = headers
+        .get_all(header::UPGRADE)
+        .iter()
+        .filter_map(|val| val.to_str().ok())
+        .flat_map(|val| val.split(','))
+        .any(|protocol| protocol.trim().eq_ignore_ascii_case("h2c"));
+    if req.version() != Version::HTTP_11
+        || *req.method() == Method::CONNECT
+        || !offers_h2c
+        || headers.get_all(HTTP2_SETTINGS).iter().count() != 1
+        || req.body().size_hint().exact() != Some(0)
+    {
+        return None;
+    }
+
+    let path = req
+        .uri()
+        .path_and_query()
+        .map_or("/", |path_and_query| path_and_query.as_str());
+    let mut fields: Vec<(&str, &[u8])> = vec![
+        (":method", req.method().as_str().as_bytes()),
+        (":scheme", b"http"),
+        (":path", path.as_bytes()),
+    ];
+    let authority = match req.uri().authority() {
+        Some(authority) => Some(authority.as_str().as_bytes()),
+        None => headers.get(header::HOST).map(HeaderValue::as_bytes),
+    };
+    if let Some(authority) = authority {
+        fields.push((":authority", authority));
+    }
+
+    // The connection-specific headers aren't allowed in HTTP/2.
+    let connection_options = headers
+        .get_all(header::CONNECTION)
+        .iter()
+        .filter_map(|val| val.to_str().ok())
+        .flat_map(|val| val.split(','))
+        .map(|option| option.trim().to_ascii_lowercase())
+        .collect::<Vec<_>>();
+    let is_connection_specific = |name: &HeaderName, val: &HeaderValue| match *name {
+        header::CONNECTION | header::HOST | header::UPGRADE | header::TRANSFER_ENCODING => true,
+        header::TE => val != "trailers",
+        _ => {
+            matches!(name.as_str(), HTTP2_SETTINGS | "keep-alive" | "proxy-connection")
+                || connection_options.iter().any(|option| option == name.as_str())
+        }
+    };
+    fields.extend(
+        headers
+            .iter()
+            .filter(|(name, val)| !is_connection_specific(name, val))
+            .map(|(name, val)| (name.as_str(), val.as_bytes())),
+    );
+
+    Some(header_frames(1, &fields))
+}
+
+// Encodes the fields as a HEADERS frame which ends the stream, followed by the CONTINUATION frames if they don't fit.
+pub(crate) fn header_frames(stream_id: u32, fields: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut block = Vec::new();
+    for (name, val) in fields {
+        // A literal header field without indexing, with a new name.
+        block.push(0);
+        encode_string(name.as_bytes(), &mut block);
+        encode_string(val, &mut block);
+    }
+
+    let mut frames = Vec::with_capacity(block.len() + FRAME_HEADER_LEN);
+    let chunks = block.chunks(MAX_FRAME_SIZE).collect::<Vec<_>>();
+    for (idx, chunk) in chunks.iter().enumerate() {
+        let (kind, mut flags) = match idx {
+            0 => (FRAME_HEADERS, FLAG_END_STREAM),
+            _ => (FRAME_CONTINUATION, 0),
+        };
+        if idx == chunks.len() - 1 {
+            flags |= FLAG_END_HEADERS;
+        }
+        frames.extend_from_slice(&(chunk.len() as u32).to_be_bytes()[1..]);
+        frames.push(kind);
+        frames.push(flags);
+        frames.extend_from_slice(&stream_id.to_be_bytes());
+        frames.extend_from_slice(chunk);
+    }
+    frames
+}
+
+// Encodes a string literal without the Huffman coding, i.e. its length as an integer with a 7-bit prefix.
+fn encode_string(val: &[u8], buf: &mut Vec<u8>) {
+    let mut len = val.len();
+    if len < 0x7f {
+        buf.push(len as u8);
+    } else {
+        buf.push(0x7f);
+        len -= 0x7f;
+        while len >= 0x80 {
+            buf.push((len % 0x80) as u8 | 0x80);
+            len /= 0x80;
+        }
+        buf.push(len as u8);
+    }
+    buf.extend_from_slice(val);
+}
+
+/// Reads the preface of the client and its first SETTINGS frame, and returns the IO which replays them to the HTTP/2
+/// server followed by the frames of the upgraded request.
+pub(crate) async fn handshake<I>(mut io: I, frames: Vec<u8>) -> io::Result<H2cIo<I>>
+where
+    I: AsyncRead + Unpin,
+{
+    let mut prefix = vec![0u8; PREFACE.len() + FRAME_HEADER_LEN];
+    io.read_exact(&mut prefix).await?;
+
+    let frame_header = &prefix[PREFACE.len()..];
+    if &prefix[..PREFACE.len()] != PREFACE || frame_header[3] != FRAME_SETTINGS {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "The upgraded connection doesn't start with the HTTP/2 preface",
+        ));
+    }
+    let len = u32::from_be_bytes([0, frame_header[0], frame_header[1], frame_header[2]]) as usize;
+    if len > MAX_FRAME_SIZE {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "The SETTINGS frame is too large",
+        ));
+    }
+
+    let start = prefix.len();
+    prefix.resize(start + len, 0);
+    io.read_exact(&mut prefix[start..]).await?;
+    prefix.extend_from_slice(&frames);
+
+    Ok(H2cIo { prefix, pos: 0, io })
+}
+
+/// The IO of an upgraded connection, which replays the frames read by the handshake before the rest of the connection.
+pub(crate) struct H2cIo<I> {
+    prefix: Vec<u8>,
+    pos: usize,
+    io: I,
+}
+
+impl<I: AsyncRead + Unpin> AsyncRead for H2cIo<I> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        if self.pos < self.prefix.len() {
+            let len = buf.remaining().min(self.prefix.len() - self.pos);
+            buf.put_slice(&self.prefix[self.pos..self.pos + len]);
+            self.pos += len;
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut self.io).poll_read(cx, buf)
+    }
+}
+
+impl<I: AsyncWrite + Unpin> AsyncWrite for H2cIo<I> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.io).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.io).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.io.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_long_strings() {
+        let mut buf = Vec::new();
+        encode_string(&[b'a'; 300], &mut buf);
+        // 300 = 127 + 173, i.e. 0x7f followed by 173 % 128 with the continuation bit and 173 / 128.
+        assert_eq!(&buf[..3], &[0x7f, 0xad, 0x01]);
+        assert_eq!(buf.len(), 303);
+    }
+
+    #[test]
+    fn replays_requests_as_the_first_stream() {
+        let req = Request::builder()
+            .uri("/users/7?active=true")
+            .header(header::HOST, "localhost")
+            .header(header::CONNECTION, "Upgrade, HTTP2-Settings, x-hop")
+            .header(header::UPGRADE, "h2c")
+            .header(HTTP2_SETTINGS, "AAMAAABkAARAAAAAAAIAAAAA")
+            .header("x-hop", "1")
+            .header(header::ACCEPT, "text/plain")
+            .body(Body::empty())
+            .unwrap();
+
+        let frames = request_frames(&req).unwrap();
+        let expected = header_frames(
+            1,
+            &[
+                (":method", b"GET"),
+                (":scheme", b"http"),
+                (":path", b"/users/7?active=true"),
+                (":authority", b"localhost"),
+                ("accept", b"text/plain"),
+            ],
+        );
+        assert_eq!(frames, expected);
+        assert_eq!(
+            &frames[3..9],
+            &[FRAME_HEADERS, FLAG_END_STREAM | FLAG_END_HEADERS, 0, 0, 0, 1]
+        );
+    }
+
+    #[test]
+    fn splits_large_header_blocks() {
+        let val = vec![b'a'; MAX_FRAME_SIZE];
+        let frames = header_frames(1, &[("x-large", &val)]);
+
+        assert_eq!(
+            &frames[..9],
+            &[0x00, 0x40, 0x00, FRAME_HEADERS, FLAG_END_STREAM, 0, 0, 0, 1]
+        );
+        let rest = &frames[FRAME_HEADER_LEN + MAX_FRAME_SIZE..];
+        assert_eq!(&rest[3..9], &[FRAME_CONTINUATION, FLAG_END_HEADERS, 0, 0, 0, 1]);
+        // The block has 12 more bytes, i.e. the representation type, the name with its length and the length of the value.
+        assert_eq!(rest.len(), FRAME_HEADER_LEN + 12);
+    }
+
+    #[test]
+    fn declines_requests_with_bodies() {
+        let req = Request::builder()
+            .method(Method::POST)
+            .header(header::UPGRADE, "h2c")
+            .header(HTTP2_SETTINGS, "")
+            .body(Body::from("hello"))
+            .unwrap();
+        assert!(request_frames(&req).is_none());
+    }
+}
@@ -0,0 +1,282 @@
+use crate::service::request_service::RequestService;
+use crate::Error;
+use futures_core::Stream;
+use hyper::body::{Bytes, HttpBody};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// A hyper 1.x [`Service`](https://docs.rs/hyper/1/hyper/service/trait.Service.html) to process the requests of a
+/// connection. It requires the `hyper1` feature.
+///
+/// It translates the hyper 1.x requests into the hyper 0.14 ones the router works with and the responses back, so it
+/// can be passed to the connection builders of hyper 1.x and `hyper-util`. Request trailers are dropped and the
+/// extensions aren't carried over in either direction.
+///
+/// It's created by the [`RouterService::hyper1_service`](./struct.RouterService.html#method.hyper1_service) or the
+/// [`RequestServiceBuilder::build_hyper1`](./struct.RequestServiceBuilder.html#method.build_hyper1) methods.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hyper::{Body, Response};
+/// use hyper_util::rt::TokioIo;
+/// use routerify::{ConnectionInfo, Router, RouterService};
+/// use std::convert::Infallible;
+/// use tokio::net::TcpListener;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let router: Router<Body, Infallible> = Router::builder()
+///         .get("/", |_| async move { Ok(Response::new(Body::from("Home page"))) })
+///         .build()
+///         .unwrap();
+///     let service = RouterService::new(router).unwrap();
+///
+///     let listener = TcpListener::bind("127.0.0.1:3000").await.unwrap();
+///     loop {
+///         let (stream, remote_addr) = listener.accept().await.unwrap();
+///         let req_service = service.hyper1_service(ConnectionInfo::new(remote_addr));
+///
+///         tokio::spawn(async move {
+///             let conn = hyper1::server::conn::http1::Builder::new().serve_connection(TokioIo::new(stream), req_service);
+///             if let Err(err) = conn.await {
+///                 eprintln!("Connection error: {}", err);
+///             }
+///         });
+///     }
+/// }
+/// ```
+pub struct Hyper1Service<B, E> {
+    inner: RequestService<B, E>,
+}
+
+impl<B, E> Hyper1Service<B, E> {
+    pub(crate) fn new(inner: RequestService<B, E>) -> Self {
+        Hyper1Service { inner }
+    }
+}
+
+impl<B, E> Clone for Hyper1Service<B, E> {
+    fn clone(&self) -> Self {
+        Hyper1Service {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<B, E, In> hyper1::service::Service<http1::Request<In>> for Hyper1Service<B, E>
+where
+    B: HttpBody + Send + Sync + 'static,
+    E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    In: http_body1::Body<Data = Bytes> + Send + 'static,
+    In::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    type Response = http1::Response<Hyper1Body<B>>;
+    type Error = crate::RouteError;
+    #[allow(clippy::type_complexity)]
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send + 'static>>;
+
+    fn call(&self, req: http1::Request<In>) -> Self::Future {
+        let req = match into_hyper0_request(req) {
+            Ok(req) => req,
+            Err(err) => return Box::pin(async move { Err(err) }),
+        };
+        let fut = self.inner.clone().call_owned(req);
+
+        Box::pin(async move {
+            let resp = fut.await?;
+            from_hyper0_response(resp)
+        })
+    }
+}
+
+/// The response body of the [`Hyper1Service`](./struct.Hyper1Service.html), which implements the hyper 1.x body trait
+/// over a hyper 0.14 body.
+pub struct Hyper1Body<B> {
+    body: Pin<Box<B>>,
+    is_data_done: bool,
+}
+
+impl<B> Hyper1Body<B> {
+    pub(crate) fn new(body: B) -> Self {
+        Hyper1Body {
+            body: Box::pin(body),
+            is_data_done: false,
+        }
+    }
+}
+
+impl<B: HttpBody> http_body1::Body for Hyper1Body<B> {
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<http_body1::Frame<Self::Data>, Self::Error>>> {
+        if !self.is_data_done {
+            match self.body.as_mut().poll_data(cx) {
+                Poll::Ready(Some(Ok(data))) => return Poll::Ready(Some(Ok(http_body1::Frame::data(data)))),
+                Poll::Ready(Some(Err(err))) => return Poll::Ready(Some(Err(err))),
+                Poll::Ready(None) => self.is_data_done = true,
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+
+        match self.body.as_mut().poll_trailers(cx) {
+            Poll::Ready(Ok(Some(trailers))) => {
+                Poll::Ready(Some(Ok(http_body1::Frame::trailers(convert_headers(&trailers)))))
+            }
+            Poll::Ready(Ok(None)) => Poll::Ready(None),
+            Poll::Ready(Err(err)) => Poll::Ready(Some(Err(err))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> http_body1::SizeHint {
+        let hint = self.body.size_hint();
+        let mut size_hint = http_body1::SizeHint::new();
+        size_hint.set_lower(hint.lower());
+        if let Some(upper) = hint.upper() {
+            size_hint.set_upper(upper);
+        }
+        size_hint
+    }
+}
+
+// Streams the data frames of a hyper 1.x body.
+pub(crate) struct DataStream<In> {
+    pub(crate) body: Pin<Box<In>>,
+}
+
+impl<In> Stream for DataStream<In>
+where
+    In: http_body1::Body<Data = Bytes>,
+{
+    type Item = Result<Bytes, In::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            match self.body.as_mut().poll_frame(cx) {
+                Poll::Ready(Some(Ok(frame))) => {
+                    if let Ok(data) = frame.into_data() {
+                        return Poll::Ready(Some(Ok(data)));
+                    }
+                }
+                Poll::Ready(Some(Err(err))) => return Poll::Ready(Some(Err(err))),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+pub(crate) fn into_hyper0_request<In>(req: http1::Request<In>) -> crate::Result<hyper::Request<hyper::Body>>
+where
+    In: http_body1::Body<Data = Bytes> + Send + 'static,
+    In::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    let (parts, body) = req.into_parts();
+
+    let mut builder = hyper::Request::builder()
+        .method(parts.method.as_str())
+        .uri(parts.uri.to_string())
+        .version(match parts.version {
+            http1::Version::HTTP_09 => hyper::Version::HTTP_09,
+            http1::Version::HTTP_10 => hyper::Version::HTTP_10,
+            http1::Version::HTTP_2 => hyper::Version::HTTP_2,
+            http1::Version::HTTP_3 => hyper::Version::HTTP_3,
+            _ => hyper::Version::HTTP_11,
+        });
+    for (name, value) in parts.headers.iter() {
+        builder = builder.header(name.as_str(), value.as_bytes());
+    }
+
+    let body = hyper::Body::wrap_stream(DataStream { body: Box::pin(body) });
+    builder
+        .body(body)
+        .map_err(|e| Error::new(format!("Couldn't convert the hyper 1.x request: {}", e)).into())
+}
+
+pub(crate) fn from_hyper0_response<B>(resp: hyper::Response<B>) -> crate::Result<http1::Response<Hyper1Body<B>>> {
+    let (parts, body) = resp.into_parts();
+
+    let mut builder = http1::Response::builder()
+        .status(parts.status.as_u16())
+        .version(match parts.version {
+            hyper::Version::HTTP_09 => http1::Version::HTTP_09,
+            hyper::Version::HTTP_10 => http1::Version::HTTP_10,
+            hyper::Version::HTTP_2 => http1::Version::HTTP_2,
+            hyper::Version::HTTP_3 => http1::Version::HTTP_3,
+            _ => http1::Version::HTTP_11,
+        });
+    if let Some(headers) = builder.headers_mut() {
+        *headers = convert_headers(&parts.headers);
+    }
+
+    builder
+        .body(Hyper1Body::new(body))
+        .map_err(|e| Error::new(format!("Couldn't convert the response into a hyper 1.x one: {}", e)).into())
+}
+
+pub(crate) fn convert_headers(headers: &hyper::HeaderMap) -> http1::HeaderMap {
+    let mut converted = http1::HeaderMap::with_capacity(headers.len());
+    for (name, value) in headers.iter() {
+        if let (Ok(name), Ok(value)) = (
+            http1::HeaderName::from_bytes(name.as_str().as_bytes()),
+            http1::HeaderValue::from_bytes(value.as_bytes()),
+        ) {
+            converted.append(name, value);
+        }
+    }
+    converted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RequestServiceBuilder, Router};
+    use http_body_util::{BodyExt, Full};
+    use hyper::Response;
+    use hyper1::service::Service;
+    use std::convert::Infallible;
+
+    #[tokio::test]
+    async fn should_serve_hyper1_requests() {
+        let router: Router<hyper::Body, Infallible> = Router::builder()
+            .post("/echo", |req| async move {
+                let (parts, body) = req.into_parts();
+                let body = hyper::body::to_bytes(body).await.unwrap();
+                let resp = Response::builder()
+                    .header("x-method", parts.method.as_str())
+                    .header("x-header", parts.headers["x-header"].clone())
+                    .body(hyper::Body::from(body))
+                    .unwrap();
+                Ok(resp)
+            })
+            .build()
+            .unwrap();
+        let service = RequestServiceBuilder::new(router)
+            .unwrap()
+            .build_hyper1(crate::ConnectionInfo::new(([127, 0, 0, 1], 8080).into()));
+
+        let req = http1::Request::builder()
+            .method("POST")
+            .uri("/echo")
+            .header("x-header", "value")
+            .body(Full::new(Bytes::from("Hello")))
+            .unwrap();
+        let resp = service.call(req).await.unwrap();
+
+        assert_eq!(resp.status(), http1::StatusCode::OK);
+        assert_eq!(resp.headers()["x-method"], "POST");
+        assert_eq!(resp.headers()["x-header"], "value");
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, "Hello");
+    }
+}
@@ -0,0 +1,101 @@
+use std::fmt::{self, Debug, Formatter};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+pub(crate) type LifecycleHook = Box<dyn Fn() -> LifecycleHookReturn + Send + Sync + 'static>;
+pub(crate) type LifecycleHookReturn = Pin<Box<dyn Future<Output = crate::Result<()>> + Send + 'static>>;
+
+/// A handle to run the startup and the shutdown hooks of a router.
+///
+/// The hooks are added by the [`RouterBuilder::on_startup`](./struct.RouterBuilder.html#method.on_startup) and the
+/// [`RouterBuilder::on_shutdown`](./struct.RouterBuilder.html#method.on_shutdown) methods. The handle is created by
+/// the [`RouterService::lifecycle`](./struct.RouterService.html#method.lifecycle) method and stays valid after the service
+/// is moved into the server.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hyper::{Body, Response, Server};
+/// use routerify::{Router, RouterService};
+/// use std::net::SocketAddr;
+///
+/// # async fn run() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+/// let router: Router<Body, routerify::Error> = Router::builder()
+///     .on_startup(|| async move { /* Warm the caches */ Ok(()) })
+///     .on_shutdown(|| async move { /* Close the database pool */ Ok(()) })
+///     .get("/", |_| async move { Ok(Response::new(Body::from("Home"))) })
+///     .build()
+///     .unwrap();
+///
+/// let service = RouterService::new(router).unwrap();
+/// let lifecycle = service.lifecycle();
+/// lifecycle.startup().await?;
+///
+/// let addr = SocketAddr::from(([127, 0, 0, 1], 3001));
+/// let server = Server::bind(&addr)
+///     .serve(service)
+///     .with_graceful_shutdown(async { tokio::signal::ctrl_c().await.unwrap() });
+/// server.await?;
+///
+/// lifecycle.shutdown().await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Lifecycle {
+    inner: Arc<LifecycleInner>,
+}
+
+struct LifecycleInner {
+    startup_hooks: Vec<LifecycleHook>,
+    shutdown_hooks: Vec<LifecycleHook>,
+}
+
+impl Lifecycle {
+    pub(crate) fn new(startup_hooks: Vec<LifecycleHook>, shutdown_hooks: Vec<LifecycleHook>) -> Lifecycle {
+        Lifecycle {
+            inner: Arc::new(LifecycleInner {
+                startup_hooks,
+                shutdown_hooks,
+            }),
+        }
+    }
+
+    /// Runs the startup hooks in the registration order. The hooks of a scoped router run at the position where
+    /// the router is mounted.
+    ///
+    /// It stops at the first failing hook and returns its error.
+    pub async fn startup(&self) -> crate::Result<()> {
+        for hook in self.inner.startup_hooks.iter() {
+            hook().await?;
+        }
+        Ok(())
+    }
+
+    /// Runs the shutdown hooks in the reverse registration order.
+    ///
+    /// All the hooks are run even if some of them fail, and the first error is returned.
+    pub async fn shutdown(&self) -> crate::Result<()> {
+        let mut result = Ok(());
+        for hook in self.inner.shutdown_hooks.iter().rev() {
+            if let Err(err) = hook().await {
+                if result.is_ok() {
+                    result = Err(err);
+                }
+            }
+        }
+        result
+    }
+}
+
+impl Debug for Lifecycle {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{{ startup_hooks: {}, shutdown_hooks: {} }}",
+            self.inner.startup_hooks.len(),
+            self.inner.shutdown_hooks.len()
+        )
+    }
+}
@@ -1,5 +1,28 @@
+#[cfg(feature = "hyper1")]
+pub(crate) use hyper1::{convert_headers, from_hyper0_response, into_hyper0_request, DataStream};
+#[cfg(feature = "hyper1")]
+pub use hyper1::{Hyper1Body, Hyper1Service};
+pub use lifecycle::Lifecycle;
+pub(crate) use lifecycle::LifecycleHook;
+#[cfg(feature = "server")]
+pub(crate) use request_service::serve_connection_with;
 pub use request_service::{RequestService, RequestServiceBuilder};
+pub use router_factory::RouterFactory;
+pub use router_selector::RouterSelector;
 pub use router_service::RouterService;
+#[cfg(feature = "server")]
+pub use serve::{Listener, ListenerIo, Serve, TlsAcceptor};
 
+#[cfg(all(feature = "server", feature = "hyper-http2"))]
+mod h2c;
+#[cfg(feature = "hyper1")]
+mod hyper1;
+mod lifecycle;
+#[cfg(feature = "server")]
+mod proxy_protocol;
 mod request_service;
+mod router_factory;
+mod router_selector;
 mod router_service;
+#[cfg(feature = "server")]
+mod serve;
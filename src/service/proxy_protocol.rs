@@ -0,0 +1,149 @@
+// The PROXY protocol headers sent by the load balancers before the connection data, to pass the addresses of the
+// client. Both versions are read exactly, so the data after the header is left in the stream:
+// https://www.haproxy.org/download/2.8/doc/proxy-protocol.txt
+use std::io::{self, ErrorKind};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+const V2_SIGNATURE: &[u8; 12] = b"\r\n\r\n\0\r\nQUIT\n";
+const V1_MAX_LEN: usize = 107;
+
+/// Reads the PROXY protocol header and returns the source and the destination addresses. It returns `None` for the
+/// health checks of the load balancer, i.e. the `UNKNOWN` and the `LOCAL` headers.
+pub(crate) async fn read_header<I>(io: &mut I) -> io::Result<Option<(SocketAddr, SocketAddr)>>
+where
+    I: AsyncRead + Unpin,
+{
+    // The shortest header, `PROXY UNKNOWN\r\n`, is longer than the signature of the version 2.
+    let mut prefix = [0u8; 12];
+    io.read_exact(&mut prefix).await?;
+
+    if &prefix == V2_SIGNATURE {
+        read_v2(io).await
+    } else if prefix.starts_with(b"PROXY ") {
+        read_v1(io, &prefix).await
+    } else {
+        Err(invalid("The connection doesn't start with a PROXY protocol header"))
+    }
+}
+
+async fn read_v1<I: AsyncRead + Unpin>(io: &mut I, prefix: &[u8]) -> io::Result<Option<(SocketAddr, SocketAddr)>> {
+    let mut line = prefix.to_vec();
+    while !line.ends_with(b"\r\n") {
+        if line.len() >= V1_MAX_LEN {
+            return Err(invalid("The PROXY protocol header is too long"));
+        }
+        line.push(io.read_u8().await?);
+    }
+
+    let line = std::str::from_utf8(&line[..line.len() - 2]).map_err(|_| invalid("Invalid PROXY protocol header"))?;
+    let parts = line.split(' ').collect::<Vec<_>>();
+    match parts.as_slice() {
+        ["PROXY", "UNKNOWN", ..] => Ok(None),
+        ["PROXY", "TCP4", src, dst, src_port, dst_port] | ["PROXY", "TCP6", src, dst, src_port, dst_port] => {
+            let parse_addr = |ip: &str, port: &str| -> io::Result<SocketAddr> {
+                let ip = ip
+                    .parse::<IpAddr>()
+                    .map_err(|_| invalid("Invalid PROXY protocol address"))?;
+                let port = port
+                    .parse::<u16>()
+                    .map_err(|_| invalid("Invalid PROXY protocol port"))?;
+                Ok(SocketAddr::new(ip, port))
+            };
+            Ok(Some((parse_addr(src, src_port)?, parse_addr(dst, dst_port)?)))
+        }
+        _ => Err(invalid("Invalid PROXY protocol header")),
+    }
+}
+
+async fn read_v2<I: AsyncRead + Unpin>(io: &mut I) -> io::Result<Option<(SocketAddr, SocketAddr)>> {
+    let version_command = io.read_u8().await?;
+    let family = io.read_u8().await?;
+    let len = io.read_u16().await? as usize;
+    let mut payload = vec![0u8; len];
+    io.read_exact(&mut payload).await?;
+
+    if version_command >> 4 != 2 {
+        return Err(invalid("Unsupported PROXY protocol version"));
+    }
+    match version_command & 0x0f {
+        0 => return Ok(None),
+        1 => {}
+        _ => return Err(invalid("Unsupported PROXY protocol command")),
+    }
+
+    // The TLVs after the addresses are skipped.
+    match family >> 4 {
+        1 if payload.len() >= 12 => {
+            let src = Ipv4Addr::new(payload[0], payload[1], payload[2], payload[3]);
+            let dst = Ipv4Addr::new(payload[4], payload[5], payload[6], payload[7]);
+            Ok(Some((
+                SocketAddr::new(src.into(), u16::from_be_bytes([payload[8], payload[9]])),
+                SocketAddr::new(dst.into(), u16::from_be_bytes([payload[10], payload[11]])),
+            )))
+        }
+        2 if payload.len() >= 36 => {
+            let mut src = [0u8; 16];
+            let mut dst = [0u8; 16];
+            src.copy_from_slice(&payload[0..16]);
+            dst.copy_from_slice(&payload[16..32]);
+            Ok(Some((
+                SocketAddr::new(
+                    Ipv6Addr::from(src).into(),
+                    u16::from_be_bytes([payload[32], payload[33]]),
+                ),
+                SocketAddr::new(
+                    Ipv6Addr::from(dst).into(),
+                    u16::from_be_bytes([payload[34], payload[35]]),
+                ),
+            )))
+        }
+        // The unix sockets and the unspecified families have no IP address.
+        0 | 3 => Ok(None),
+        _ => Err(invalid("Invalid PROXY protocol addresses")),
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn reads_v1_headers() {
+        let mut io: &[u8] = b"PROXY TCP4 192.0.2.7 10.0.0.1 56324 443\r\nGET / HTTP/1.1\r\n";
+        let (src, dst) = read_header(&mut io).await.unwrap().unwrap();
+        assert_eq!(src, "192.0.2.7:56324".parse().unwrap());
+        assert_eq!(dst, "10.0.0.1:443".parse().unwrap());
+        assert_eq!(io, b"GET / HTTP/1.1\r\n");
+
+        let mut io: &[u8] = b"PROXY UNKNOWN\r\n";
+        assert_eq!(read_header(&mut io).await.unwrap(), None);
+
+        let mut io: &[u8] = b"GET / HTTP/1.1\r\nhost: localhost\r\n\r\n";
+        assert_eq!(read_header(&mut io).await.unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn reads_v2_headers() {
+        let mut header = V2_SIGNATURE.to_vec();
+        header.extend_from_slice(&[0x21, 0x11, 0, 15]);
+        header.extend_from_slice(&[192, 0, 2, 7, 10, 0, 0, 1, 0xdc, 0x04, 0x01, 0xbb]);
+        // A TLV which is skipped.
+        header.extend_from_slice(&[0x04, 0, 0]);
+        header.extend_from_slice(b"GET");
+
+        let mut io = header.as_slice();
+        let (src, dst) = read_header(&mut io).await.unwrap().unwrap();
+        assert_eq!(src, "192.0.2.7:56324".parse().unwrap());
+        assert_eq!(dst, "10.0.0.1:443".parse().unwrap());
+        assert_eq!(io, b"GET");
+
+        let mut header = V2_SIGNATURE.to_vec();
+        header.extend_from_slice(&[0x20, 0x00, 0, 0]);
+        assert_eq!(read_header(&mut header.as_slice()).await.unwrap(), None);
+    }
+}
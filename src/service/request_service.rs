@@ -1,43 +1,135 @@
+use crate::events::{CatchUnwind, RouterEvent};
+use crate::ext::RequestExt;
 use crate::helpers;
 use crate::router::Router;
-use crate::types::{RequestContext, RequestInfo, RequestMeta};
-use crate::Error;
-use hyper::{body::HttpBody, service::Service, Request, Response};
+#[cfg(all(feature = "server", feature = "hyper-http2"))]
+use crate::service::h2c::{self, H2cUpgrade};
+#[cfg(feature = "hyper1")]
+use crate::service::Hyper1Service;
+#[cfg(feature = "server")]
+use crate::service::Serve;
+use crate::service::{Lifecycle, RouterFactory};
+use crate::types::{ConnectionInfo, Forwarded, RequestContext, RequestInfo, RequestMeta, MAX_FORWARDS};
+use crate::{Error, HttpError};
+use hyper::header::HeaderValue;
+use hyper::http::uri::PathAndQuery;
+#[cfg(feature = "server")]
+use hyper::server::conn::Http;
+use hyper::{body::HttpBody, service::Service, Method, Request, Response, StatusCode, Uri, Version};
+use regex::Regex;
+use std::convert::TryFrom;
 use std::future::Future;
 use std::net::SocketAddr;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::Instant;
+#[cfg(feature = "server")]
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_util::sync::CancellationToken;
 
 pub struct RequestService<B, E> {
     pub(crate) router: Arc<Router<B, E>>,
     pub(crate) remote_addr: SocketAddr,
+    pub(crate) connection_info: Option<ConnectionInfo>,
+    pub(crate) allowed_routes: Option<Arc<Vec<Regex>>>,
+    #[cfg(all(feature = "server", feature = "hyper-http2"))]
+    pub(crate) h2c_upgrade: Option<H2cUpgrade>,
+}
+
+#[allow(clippy::type_complexity)]
+type ResponseFuture<B> = Pin<Box<dyn Future<Output = Result<Response<B>, crate::RouteError>> + Send + 'static>>;
+
+impl<B, E> Clone for RequestService<B, E> {
+    fn clone(&self) -> Self {
+        RequestService {
+            router: self.router.clone(),
+            remote_addr: self.remote_addr,
+            connection_info: self.connection_info.clone(),
+            allowed_routes: self.allowed_routes.clone(),
+            #[cfg(all(feature = "server", feature = "hyper-http2"))]
+            h2c_upgrade: self.h2c_upgrade.clone(),
+        }
+    }
 }
 
 impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static>
-    Service<Request<hyper::Body>> for RequestService<B, E>
+    RequestService<B, E>
 {
-    type Response = Response<B>;
-    type Error = crate::RouteError;
-    #[allow(clippy::type_complexity)]
-    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send + 'static>>;
+    // Serves only the routes matching one of the patterns, for the listeners which are limited to some routes.
+    #[cfg(feature = "server")]
+    pub(crate) fn with_allowed_routes(mut self, allowed_routes: Option<Arc<Vec<Regex>>>) -> Self {
+        self.allowed_routes = allowed_routes;
+        self
+    }
 
-    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        Poll::Ready(Ok(()))
+    // Accepts the h2c upgrade offers, for the connections whose serving layer carries the upgrade out.
+    #[cfg(all(feature = "server", feature = "hyper-http2"))]
+    pub(crate) fn with_h2c_upgrade(mut self, h2c_upgrade: H2cUpgrade) -> Self {
+        self.h2c_upgrade = Some(h2c_upgrade);
+        self
     }
 
-    fn call(&mut self, mut req: Request<hyper::Body>) -> Self::Future {
-        let router = self.router.clone();
-        let remote_addr = self.remote_addr;
+    /// Handles a request by value, so the service can be moved into the response future without the `&mut self` of
+    /// [`Service::call`](https://docs.rs/hyper/0.14.4/hyper/service/trait.Service.html#tymethod.call).
+    pub fn call_owned(self, mut req: Request<hyper::Body>) -> ResponseFuture<B> {
+        // The upgraded request is served over HTTP/2 once the connection is upgraded.
+        #[cfg(all(feature = "server", feature = "hyper-http2"))]
+        if let Some(resp) = self.h2c_upgrade.as_ref().and_then(|upgrade| upgrade.accept(&mut req)) {
+            return Box::pin(async move { Ok(resp) });
+        }
+
+        let RequestService {
+            router,
+            remote_addr,
+            connection_info,
+            allowed_routes,
+            ..
+        } = self;
+
+        // Hyper drops the response future when the client disconnects, so the token is cancelled by
+        // the drop guard unless the future runs to completion.
+        let cancellation_token = CancellationToken::new();
+        let cancellation_guard = cancellation_token.clone().drop_guard();
 
         let fut = async move {
-            helpers::update_req_meta_in_extensions(req.extensions_mut(), RequestMeta::with_remote_addr(remote_addr));
+            let started_at = Instant::now();
+            // The method and the path of the request are kept for the events, as the request is moved.
+            let request_line = router.observer.as_ref().map(|observer| {
+                let method = req.method().clone();
+                let path = req.uri().path().to_owned();
+                observer.emit(|| RouterEvent::RequestStarted {
+                    method: method.clone(),
+                    path: path.clone(),
+                    remote_addr,
+                });
+                (method, path)
+            });
+
+            let mut req_meta = RequestMeta::with_remote_addr(remote_addr).with_cancellation_token(cancellation_token);
+            if let Some(connection_info) = connection_info {
+                req_meta = req_meta.with_connection_info(connection_info.with_version(req.version()));
+            }
+
+            let (mut target_path, is_base_path_stripped) =
+                helpers::route_target_path(req.uri().path(), router.base_path.as_deref())?;
+            if let (true, Some(base_path)) = (is_base_path_stripped, &router.base_path) {
+                req_meta = req_meta.with_base_path(base_path.clone());
+            }
 
-            let mut target_path = helpers::percent_decode_request_path(req.uri().path())
-                .map_err(|e| Error::new(format!("Couldn't percent decode request path: {}", e)))?;
+            if req.version() < Version::HTTP_2 {
+                helpers::decline_h2c_upgrade(req.headers_mut());
+            }
+
+            helpers::update_req_meta_in_extensions(req.extensions_mut(), req_meta);
 
-            if target_path.is_empty() || target_path.as_bytes()[target_path.len() - 1] != b'/' {
-                target_path.push('/');
+            // The pre match hooks may rewrite the request URI, so the routes are matched by its new path.
+            if !router.pre_match_hooks.is_empty() {
+                let uri = req.uri().clone();
+                router.pre_match_hooks.iter().for_each(|hook| hook(&mut req));
+                if *req.uri() != uri {
+                    target_path = helpers::route_target_path(req.uri().path(), router.base_path.as_deref())?.0;
+                }
             }
 
             let mut req_info = None;
@@ -53,16 +145,190 @@ impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Se
 
             req.extensions_mut().insert(context);
 
-            router.process(target_path.as_str(), req, req_info.clone()).await
+            // The routes which aren't served on the listener of the connection are answered as unknown ones.
+            let allowed = match allowed_routes {
+                Some(ref allowed_routes) => allowed_routes.iter().any(|regex| regex.is_match(&target_path)),
+                None => true,
+            };
+            let mut resp = if !allowed {
+                let err = HttpError::new(StatusCode::NOT_FOUND, "The route isn't served on this listener");
+                handle_err(&router, err.into(), req_info).await
+            } else {
+                process(&router, target_path, req, req_info, request_line.as_ref()).await
+            };
+
+            // A forwarded request is matched again by its new path.
+            let mut forwards = 0;
+            while let Some(forwarded) = resp
+                .as_mut()
+                .ok()
+                .and_then(|resp| resp.extensions_mut().remove::<Forwarded>())
+            {
+                forwards += 1;
+                // The request info is generated again, as the request URI is changed.
+                let req_info_of = |req: &Request<hyper::Body>| {
+                    req.extensions()
+                        .get::<RequestContext>()
+                        .filter(|_| should_gen_req_info)
+                        .map(|context| RequestInfo::new_from_req(req, context.clone()))
+                };
+                if forwards > MAX_FORWARDS {
+                    let err = HttpError::new(StatusCode::LOOP_DETECTED, "The request is forwarded too many times");
+                    resp = handle_err(&router, err.into(), req_info_of(&forwarded.req)).await;
+                    break;
+                }
+
+                let req_info = req_info_of(&forwarded.req);
+                resp = match forward_request(forwarded, router.base_path.as_deref()) {
+                    Ok((req, target_path)) => {
+                        let req_info = req_info_of(&req);
+                        process(&router, target_path, req, req_info, request_line.as_ref()).await
+                    }
+                    Err(err) => handle_err(&router, err, req_info).await,
+                };
+            }
+            cancellation_guard.disarm();
+
+            if let (Ok(ref mut resp), Some(ref name)) = (&mut resp, &router.fingerprint_header) {
+                let fingerprint =
+                    HeaderValue::from_str(&router.fingerprint).expect("The fingerprint is an invalid header value");
+                resp.headers_mut().insert(name.clone(), fingerprint);
+            }
+
+            if let (Some(observer), Some((method, path)), Ok(ref resp)) = (&router.observer, request_line, &resp) {
+                observer.emit(|| RouterEvent::ResponseSent {
+                    method,
+                    path,
+                    status: resp.status(),
+                    elapsed: started_at.elapsed(),
+                });
+            }
+            resp
         };
 
         Box::pin(fut)
     }
 }
 
+// Processes a request by the router. Its panics are caught once they can be reported or they're caught explicitly, and
+// are answered by the error handler.
+async fn process<B, E>(
+    router: &Router<B, E>,
+    target_path: String,
+    req: Request<hyper::Body>,
+    req_info: Option<RequestInfo>,
+    request_line: Option<&(Method, String)>,
+) -> Result<Response<B>, crate::RouteError>
+where
+    B: HttpBody + Send + Sync + 'static,
+    E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+{
+    if router.observer.is_none() && !router.catch_panics {
+        return router.process(target_path.as_str(), req, req_info).await;
+    }
+
+    match CatchUnwind::new(router.process(target_path.as_str(), req, req_info.clone())).await {
+        Ok(resp) => resp,
+        Err(message) => {
+            let err = Error::new(format!("The request handler panicked: {}", message));
+            if let (Some(observer), Some((method, path))) = (&router.observer, request_line) {
+                observer.emit(|| RouterEvent::PanicCaught {
+                    method: method.clone(),
+                    path: path.clone(),
+                    message,
+                });
+            }
+            handle_err(router, err.into(), req_info).await
+        }
+    }
+}
+
+// Points a forwarded request to its new path, and returns it with the path which the routes are matched by.
+fn forward_request(forwarded: Forwarded, base_path: Option<&str>) -> crate::Result<(Request<hyper::Body>, String)> {
+    let Forwarded { mut req, path } = forwarded;
+
+    let mut path_and_query = format!("{}{}", req.base_path().unwrap_or(""), path);
+    if let (false, Some(query)) = (path.contains('?'), req.uri().query()) {
+        path_and_query.push('?');
+        path_and_query.push_str(query);
+    }
+    let mut parts = req.uri().clone().into_parts();
+    parts.path_and_query = Some(
+        PathAndQuery::try_from(path_and_query)
+            .map_err(|e| Error::new(format!("Couldn't forward the request to {:?}: {}", path, e)))?,
+    );
+    *req.uri_mut() =
+        Uri::from_parts(parts).map_err(|e| Error::new(format!("Couldn't forward the request to {:?}: {}", path, e)))?;
+
+    // The params of the previous route don't apply to the new one.
+    if let Some(req_meta) = req.extensions_mut().get_mut::<RequestMeta>() {
+        req_meta.clear_route_params();
+    }
+
+    let (target_path, _) = helpers::route_target_path(req.uri().path(), base_path)?;
+    Ok((req, target_path))
+}
+
+// Answers the request by the error handler, if any.
+async fn handle_err<B, E>(
+    router: &Router<B, E>,
+    err: crate::RouteError,
+    req_info: Option<RequestInfo>,
+) -> Result<Response<B>, crate::RouteError>
+where
+    B: HttpBody + Send + Sync + 'static,
+{
+    match router.err_handler {
+        Some(ref err_handler) => Ok(err_handler.execute(err, req_info).await),
+        None => Err(err),
+    }
+}
+
+impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static>
+    Service<Request<hyper::Body>> for RequestService<B, E>
+{
+    type Response = Response<B>;
+    type Error = crate::RouteError;
+    type Future = ResponseFuture<B>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<hyper::Body>) -> Self::Future {
+        self.clone().call_owned(req)
+    }
+}
+
+impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static>
+    Service<Request<hyper::Body>> for &RequestService<B, E>
+{
+    type Response = Response<B>;
+    type Error = crate::RouteError;
+    type Future = ResponseFuture<B>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<hyper::Body>) -> Self::Future {
+        (*self).clone().call_owned(req)
+    }
+}
+
 #[derive(Debug)]
 pub struct RequestServiceBuilder<B, E> {
-    router: Arc<Router<B, E>>,
+    pub(crate) router: Arc<Router<B, E>>,
+    lifecycle: Lifecycle,
+}
+
+impl<B, E> Clone for RequestServiceBuilder<B, E> {
+    fn clone(&self) -> Self {
+        RequestServiceBuilder {
+            router: self.router.clone(),
+            lifecycle: self.lifecycle.clone(),
+        }
+    }
 }
 
 impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static>
@@ -76,30 +342,183 @@ impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Se
 
         router.init_err_handler();
 
+        router.init_skipped_middlewares()?;
+        router.check_middleware_dependencies()?;
         router.init_regex_set()?;
+        router.init_route_data_maps();
+        router.init_middleware_plans();
         router.init_req_info_gen();
+
+        let lifecycle = Lifecycle::new(
+            std::mem::take(&mut router.startup_hooks),
+            std::mem::take(&mut router.shutdown_hooks),
+        );
+
         Ok(Self {
             router: Arc::from(router),
+            lifecycle,
         })
     }
 
+    /// Creates a new builder from a freshly built router of the [`RouterFactory`](./struct.RouterFactory.html).
+    pub fn from_factory(factory: &RouterFactory<B, E>) -> crate::Result<Self> {
+        RequestServiceBuilder::new(factory.build()?)
+    }
+
+    /// Returns a handle to run the startup and the shutdown hooks of the router.
+    pub fn lifecycle(&self) -> Lifecycle {
+        self.lifecycle.clone()
+    }
+
     pub fn build(&self, remote_addr: SocketAddr) -> RequestService<B, E> {
         RequestService {
             router: self.router.clone(),
             remote_addr,
+            connection_info: None,
+            allowed_routes: None,
+            #[cfg(all(feature = "server", feature = "hyper-http2"))]
+            h2c_upgrade: None,
+        }
+    }
+
+    /// Creates a [`RequestService`](./struct.RequestService.html) for a connection which injects the connection info
+    /// into the requests. It's accessible by the [`RequestExt::connection_info`](./ext/trait.RequestExt.html#tymethod.connection_info) method.
+    ///
+    /// It should be used by the custom serving layers e.g. to expose the TLS details of the connection.
+    pub fn build_with_connection_info(&self, connection_info: ConnectionInfo) -> RequestService<B, E> {
+        RequestService {
+            router: self.router.clone(),
+            remote_addr: connection_info.remote_addr(),
+            connection_info: Some(connection_info),
+            allowed_routes: None,
+            #[cfg(all(feature = "server", feature = "hyper-http2"))]
+            h2c_upgrade: None,
         }
     }
+
+    /// Creates a hyper 1.x service for a connection. It requires the `hyper1` feature.
+    #[cfg(feature = "hyper1")]
+    pub fn build_hyper1(&self, connection_info: ConnectionInfo) -> Hyper1Service<B, E> {
+        Hyper1Service::new(self.build_with_connection_info(connection_info))
+    }
+}
+
+#[cfg(feature = "server")]
+impl<B, E> RequestServiceBuilder<B, E>
+where
+    B: HttpBody + Send + Sync + 'static,
+    <B as HttpBody>::Data: Send + Sync + 'static,
+    <B as HttpBody>::Error: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+{
+    /// Creates an accept loop with the production-grade listener options, e.g. the connection limits and the
+    /// slow-loris protection. Requires the `server` feature.
+    ///
+    /// Please refer to the [`Serve`](./struct.Serve.html) for more info.
+    pub fn serve(&self) -> Serve<B, E> {
+        Serve::new(self.clone())
+    }
+
+    /// Serves the requests of an accepted connection until it's closed. Requires the `server` feature.
+    ///
+    /// The HTTP version is negotiated automatically i.e. HTTP/2 is served if the client sends the HTTP/2 preface and the
+    /// `hyper-http2` feature is enabled, and HTTP/1 otherwise. The connection upgrades e.g. WebSockets are supported.
+    ///
+    /// The cleartext HTTP/2 (h2c) is served to the clients with prior knowledge e.g. the gRPC clients, and to the
+    /// clients which upgrade to it by the `Upgrade: h2c` offer of an HTTP/1.1 request. The upgraded request is answered
+    /// over HTTP/2 as its first stream. The offer is declined if the request has a body, the response body type isn't
+    /// `hyper::Body` or the `hyper-http2` feature isn't enabled, i.e. the request is served over HTTP/1.1 and the offer
+    /// is removed from its headers. The routes, the middlewares and the [`RequestInfo`](./struct.RequestInfo.html) work
+    /// the same for both versions, and `req.version()` tells them apart.
+    ///
+    /// It can be used with any IO type e.g. a TLS stream or a Unix socket, instead of a hand-written accept loop.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hyper::{Body, Response};
+    /// use routerify::{ConnectionInfo, RequestServiceBuilder, Router};
+    /// use std::convert::Infallible;
+    /// use tokio::net::TcpListener;
+    ///
+    /// # async fn run() -> routerify::Result<()> {
+    /// let router: Router<Body, Infallible> = Router::builder()
+    ///     .get("/", |_| async move { Ok(Response::new(Body::from("Hello world"))) })
+    ///     .build()?;
+    /// let builder = RequestServiceBuilder::new(router)?;
+    ///
+    /// let listener = TcpListener::bind("127.0.0.1:3000").await?;
+    /// loop {
+    ///     let (stream, remote_addr) = listener.accept().await?;
+    ///     let builder = builder.clone();
+    ///     tokio::spawn(async move {
+    ///         let info = ConnectionInfo::new(remote_addr);
+    ///         if let Err(err) = builder.serve_connection(stream, info).await {
+    ///             eprintln!("Connection error: {}", err);
+    ///         }
+    ///     });
+    /// }
+    /// # }
+    /// ```
+    pub async fn serve_connection<I>(&self, io: I, connection_info: ConnectionInfo) -> crate::Result<()>
+    where
+        I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        serve_connection_with(&Http::new(), io, self.build_with_connection_info(connection_info)).await
+    }
+}
+
+#[cfg(feature = "server")]
+pub(crate) async fn serve_connection_with<B, E, I>(
+    http: &Http,
+    io: I,
+    service: RequestService<B, E>,
+) -> crate::Result<()>
+where
+    B: HttpBody + Send + Sync + 'static,
+    <B as HttpBody>::Data: Send + Sync + 'static,
+    <B as HttpBody>::Error: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    #[cfg(feature = "hyper-http2")]
+    let (h2_service, h2c_upgrade) = (service.clone(), H2cUpgrade::default());
+    #[cfg(feature = "hyper-http2")]
+    let service = service.with_h2c_upgrade(h2c_upgrade.clone());
+
+    http.serve_connection(io, service)
+        .with_upgrades()
+        .await
+        .map_err(|err| Error::new(format!("Couldn't serve the connection: {}", err)))?;
+
+    // The HTTP/1.1 connection hands its IO over once the upgrade response is sent.
+    #[cfg(feature = "hyper-http2")]
+    if let Some((on_upgrade, frames)) = h2c_upgrade.take() {
+        let upgrade_err =
+            |err: &dyn std::fmt::Display| Error::new(format!("Couldn't upgrade the connection to h2c: {}", err));
+        let io = on_upgrade.await.map_err(|err| upgrade_err(&err))?;
+        let io = h2c::handshake(io, frames).await.map_err(|err| upgrade_err(&err))?;
+
+        let mut http = http.clone();
+        http.http2_only(true)
+            .serve_connection(io, h2_service)
+            .await
+            .map_err(|err| Error::new(format!("Couldn't serve the connection: {}", err)))?;
+    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{Error, RequestServiceBuilder, RouteError, Router};
+    use crate::ext::RequestExt;
+    use crate::{ConnectionInfo, Error, RequestServiceBuilder, RouteError, Router, TlsInfo};
     use futures::future::poll_fn;
     use http::Method;
     use hyper::service::Service;
     use hyper::{Body, Request, Response};
     use std::net::SocketAddr;
     use std::str::FromStr;
+    use std::sync::{Arc, Mutex};
     use std::task::Poll;
 
     #[tokio::test]
@@ -125,4 +544,316 @@ mod tests {
         let body = String::from_utf8(hyper::body::to_bytes(body).await.unwrap().to_vec()).unwrap();
         assert_eq!(RESPONSE_TEXT, body)
     }
+
+    #[tokio::test]
+    async fn should_route_request_by_reference_and_by_value() {
+        let router: Router<hyper::body::Body, Error> = Router::builder()
+            .get("/", |_| async move { Ok(Response::new(Body::from("Home"))) })
+            .build()
+            .unwrap();
+        let service = RequestServiceBuilder::new(router)
+            .unwrap()
+            .build(SocketAddr::from_str("0.0.0.0:8080").unwrap());
+        let req = || Request::builder().uri("/").body(hyper::Body::empty()).unwrap();
+
+        let resp = (&service).call(req()).await.unwrap();
+        assert_eq!(hyper::body::to_bytes(resp.into_body()).await.unwrap(), "Home");
+
+        let resp = service.clone().call_owned(req()).await.unwrap();
+        assert_eq!(hyper::body::to_bytes(resp.into_body()).await.unwrap(), "Home");
+    }
+
+    #[tokio::test]
+    async fn should_expose_connection_info() {
+        let router: Router<hyper::body::Body, Error> = Router::builder()
+            .get("/", |req| async move {
+                let info = req.connection_info().unwrap();
+                Ok(Response::new(Body::from(format!(
+                    "{} {:?} {:?} {:?}",
+                    info.remote_addr(),
+                    info.local_addr(),
+                    info.tls().and_then(|tls| tls.alpn_protocol()),
+                    info.version()
+                ))))
+            })
+            .build()
+            .unwrap();
+        let req = Request::builder()
+            .method(Method::GET)
+            .uri("/")
+            .body(hyper::Body::empty())
+            .unwrap();
+        let builder = RequestServiceBuilder::new(router).unwrap();
+        let info = ConnectionInfo::new(SocketAddr::from_str("10.0.0.7:52100").unwrap())
+            .with_local_addr(SocketAddr::from_str("0.0.0.0:443").unwrap())
+            .with_tls(TlsInfo::new().with_alpn_protocol("h2"));
+        let mut service = builder.build_with_connection_info(info);
+        let resp: Response<hyper::body::Body> = service.call(req).await.unwrap();
+        let body = String::from_utf8(hyper::body::to_bytes(resp.into_body()).await.unwrap().to_vec()).unwrap();
+        assert_eq!(body, "10.0.0.7:52100 Some(0.0.0.0:443) Some(\"h2\") Some(HTTP/1.1)");
+    }
+
+    #[cfg(feature = "server")]
+    #[tokio::test]
+    async fn should_serve_connection() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let router: Router<hyper::body::Body, Error> = Router::builder()
+            .get("/", |req| async move {
+                Ok(Response::new(Body::from(req.remote_addr().to_string())))
+            })
+            .build()
+            .unwrap();
+        let builder = RequestServiceBuilder::new(router).unwrap();
+
+        let (mut client, server) = tokio::io::duplex(1024);
+        let info = ConnectionInfo::new(SocketAddr::from_str("10.0.0.7:52100").unwrap());
+        let conn = tokio::spawn(async move { builder.serve_connection(server, info).await });
+
+        client
+            .write_all(b"GET / HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut resp = String::new();
+        client.read_to_string(&mut resp).await.unwrap();
+
+        assert!(resp.starts_with("HTTP/1.1 200 OK"));
+        assert!(resp.ends_with("10.0.0.7:52100"));
+        conn.await.unwrap().unwrap();
+    }
+
+    #[cfg(feature = "server")]
+    #[tokio::test]
+    async fn should_decline_h2c_upgrade() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let router: Router<hyper::body::Body, Error> = Router::builder()
+            .post("/", |req| async move {
+                let upgrade = req.headers().get(hyper::header::UPGRADE).cloned();
+                Ok(Response::new(Body::from(format!("{:?} {:?}", req.version(), upgrade))))
+            })
+            .build()
+            .unwrap();
+        let builder = RequestServiceBuilder::new(router).unwrap();
+
+        let (mut client, server) = tokio::io::duplex(1024);
+        let info = ConnectionInfo::new(SocketAddr::from_str("10.0.0.7:52100").unwrap());
+        let conn = tokio::spawn(async move { builder.serve_connection(server, info).await });
+
+        client
+            .write_all(
+                b"POST / HTTP/1.1\r\nhost: localhost\r\nconnection: Upgrade, HTTP2-Settings, close\r\n\
+                upgrade: h2c\r\nhttp2-settings: AAMAAABkAARAAAAAAAIAAAAA\r\ncontent-length: 5\r\n\r\nhello",
+            )
+            .await
+            .unwrap();
+        let mut resp = String::new();
+        client.read_to_string(&mut resp).await.unwrap();
+
+        assert!(resp.starts_with("HTTP/1.1 200 OK"));
+        assert!(resp.ends_with("HTTP/1.1 None"));
+        conn.await.unwrap().unwrap();
+    }
+
+    #[cfg(all(feature = "server", feature = "hyper-http2"))]
+    #[tokio::test]
+    async fn should_upgrade_to_h2c() {
+        use crate::service::h2c::header_frames;
+        use std::collections::HashMap;
+        use std::time::Duration;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let router: Router<hyper::body::Body, Error> = Router::builder()
+            .get("/users/:id", |req| async move {
+                let id = req.param("id").unwrap().to_owned();
+                Ok(Response::new(Body::from(format!("{:?} {}", req.version(), id))))
+            })
+            .build()
+            .unwrap();
+        let builder = RequestServiceBuilder::new(router).unwrap();
+
+        let (mut client, server) = tokio::io::duplex(64 * 1024);
+        let info = ConnectionInfo::new(SocketAddr::from_str("10.0.0.7:52100").unwrap());
+        let conn = tokio::spawn(async move { builder.serve_connection(server, info).await });
+
+        client
+            .write_all(
+                b"GET /users/7 HTTP/1.1\r\nhost: localhost\r\nconnection: Upgrade, HTTP2-Settings\r\n\
+                upgrade: h2c\r\nhttp2-settings: AAMAAABkAARAAAAAAAIAAAAA\r\n\r\n",
+            )
+            .await
+            .unwrap();
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            head.push(client.read_u8().await.unwrap());
+        }
+        let head = String::from_utf8(head).unwrap().to_lowercase();
+        assert!(head.starts_with("http/1.1 101 switching protocols"));
+        assert!(head.contains("upgrade: h2c"));
+
+        // The preface with an empty SETTINGS frame, and a request on the next stream of the client.
+        let mut frames = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n\0\0\0\x04\0\0\0\0\0".to_vec();
+        frames.extend(header_frames(
+            3,
+            &[
+                (":method", b"GET"),
+                (":scheme", b"http"),
+                (":path", b"/users/8"),
+                (":authority", b"localhost"),
+            ],
+        ));
+        client.write_all(&frames).await.unwrap();
+
+        // The bodies of the streams are collected until both of them end.
+        let mut bodies = HashMap::<u32, Vec<u8>>::new();
+        let mut ended = 0;
+        let read_frames = async {
+            while ended < 2 {
+                let mut header = [0u8; 9];
+                client.read_exact(&mut header).await.unwrap();
+                let len = u32::from_be_bytes([0, header[0], header[1], header[2]]) as usize;
+                let stream_id = u32::from_be_bytes([header[5], header[6], header[7], header[8]]);
+                let mut payload = vec![0u8; len];
+                client.read_exact(&mut payload).await.unwrap();
+
+                match header[3] {
+                    // The SETTINGS of the server are acknowledged.
+                    0x4 if header[4] == 0 => client.write_all(b"\0\0\0\x04\x01\0\0\0\0").await.unwrap(),
+                    0x0 => bodies.entry(stream_id).or_default().extend(payload),
+                    _ => {}
+                }
+                if header[3] <= 0x1 && header[4] & 0x1 != 0 {
+                    ended += 1;
+                }
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), read_frames).await.unwrap();
+
+        assert_eq!(bodies[&1], b"HTTP/2.0 7");
+        assert_eq!(bodies[&3], b"HTTP/2.0 8");
+
+        drop(client);
+        conn.await.unwrap().unwrap();
+    }
+
+    #[cfg(all(feature = "server", feature = "hyper-http2", feature = "client"))]
+    #[tokio::test]
+    async fn should_serve_h2_with_prior_knowledge() {
+        use crate::{Middleware, RequestInfo};
+        use hyper::Version;
+
+        let router: Router<hyper::body::Body, Error> = Router::builder()
+            .middleware(Middleware::pre(|req| async move {
+                req.set_context(req.version());
+                Ok(req)
+            }))
+            .middleware(Middleware::post_with_info(
+                |mut res, req_info: RequestInfo| async move {
+                    let version = format!("{:?} {:?}", req_info.version(), req_info.context::<Version>());
+                    res.headers_mut().insert("x-version", version.parse().unwrap());
+                    Ok(res)
+                },
+            ))
+            .get("/users/:id", |req| async move {
+                Ok(Response::new(Body::from(req.param("id").unwrap().to_owned())))
+            })
+            .build()
+            .unwrap();
+        let builder = RequestServiceBuilder::new(router).unwrap();
+
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let info = ConnectionInfo::new(SocketAddr::from_str("10.0.0.7:52100").unwrap());
+        tokio::spawn(async move { builder.serve_connection(server, info).await });
+
+        let (mut sender, conn) = hyper::client::conn::Builder::new()
+            .http2_only(true)
+            .handshake::<_, Body>(client)
+            .await
+            .unwrap();
+        tokio::spawn(conn);
+
+        let req = Request::builder()
+            .uri("http://localhost/users/42")
+            .version(Version::HTTP_2)
+            .body(Body::empty())
+            .unwrap();
+        let resp = sender.send_request(req).await.unwrap();
+        assert_eq!(resp.version(), Version::HTTP_2);
+        assert_eq!(resp.headers()["x-version"], "HTTP/2.0 Some(HTTP/2.0)");
+        assert_eq!(hyper::body::to_bytes(resp.into_body()).await.unwrap(), "42");
+    }
+
+    #[tokio::test]
+    async fn should_run_lifecycle_hooks_in_order() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let hook = |events: &Arc<Mutex<Vec<&'static str>>>, name: &'static str| {
+            let events = events.clone();
+            move || {
+                events.lock().unwrap().push(name);
+                async move { Ok(()) }
+            }
+        };
+
+        let child: Router<hyper::body::Body, Error> = Router::builder()
+            .on_startup(hook(&events, "start child"))
+            .on_shutdown(hook(&events, "stop child"))
+            .build()
+            .unwrap();
+        let router: Router<hyper::body::Body, Error> = Router::builder()
+            .on_startup(hook(&events, "start first"))
+            .on_shutdown(hook(&events, "stop first"))
+            .scope("/child", child)
+            .on_startup(hook(&events, "start last"))
+            .on_shutdown(hook(&events, "stop last"))
+            .build()
+            .unwrap();
+
+        let lifecycle = RequestServiceBuilder::new(router).unwrap().lifecycle();
+        lifecycle.startup().await.unwrap();
+        lifecycle.shutdown().await.unwrap();
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                "start first",
+                "start child",
+                "start last",
+                "stop last",
+                "stop child",
+                "stop first"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn should_cancel_token_when_request_is_dropped() {
+        let remote_addr = SocketAddr::from_str("0.0.0.0:8080").unwrap();
+        let (tx, rx) = std::sync::mpsc::channel();
+        let tx = std::sync::Mutex::new(tx);
+        let router: Router<hyper::body::Body, Error> = Router::builder()
+            .get("/", move |req| {
+                tx.lock().unwrap().send(req.cancellation_token()).unwrap();
+                async move {
+                    futures::future::pending::<()>().await;
+                    Ok(Response::new(Body::empty()))
+                }
+            })
+            .build()
+            .unwrap();
+        let req = Request::builder()
+            .method(Method::GET)
+            .uri("/")
+            .body(hyper::Body::empty())
+            .unwrap();
+        let builder = RequestServiceBuilder::new(router).unwrap();
+        let mut service = builder.build(remote_addr);
+
+        let mut fut = service.call(req);
+        assert!(futures::poll!(&mut fut).is_pending());
+        let token = rx.recv().unwrap();
+        assert!(!token.is_cancelled());
+
+        drop(fut);
+        assert!(token.is_cancelled());
+    }
 }
@@ -0,0 +1,179 @@
+use crate::router::{Router, RouterBuilder};
+#[cfg(feature = "server")]
+use crate::service::RequestServiceBuilder;
+#[cfg(feature = "server")]
+use crate::types::ConnectionInfo;
+#[cfg(feature = "server")]
+use crate::Error;
+use hyper::body::HttpBody;
+use std::fmt::{self, Debug, Formatter};
+#[cfg(feature = "server")]
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+/// A cloneable description of a router from which an independent router can be built any number of times.
+///
+/// Each worker thread can build its own router, so the shared data, the middleware state and the routes are not
+/// shared across the cores through `Arc`s. The [`serve_per_core`](#method.serve_per_core) method runs such workers.
+///
+/// # Examples
+///
+/// ```
+/// use routerify::{RequestServiceBuilder, Router, RouterFactory};
+/// use hyper::{Response, Body};
+/// use std::convert::Infallible;
+///
+/// let factory = RouterFactory::new(|| {
+///     Router::<Body, Infallible>::builder().get("/", |_| async move { Ok(Response::new(Body::from("Home"))) })
+/// });
+///
+/// // Build a request service builder per worker.
+/// let builder = RequestServiceBuilder::from_factory(&factory).unwrap();
+/// ```
+pub struct RouterFactory<B, E> {
+    make: Arc<dyn Fn() -> RouterBuilder<B, E> + Send + Sync + 'static>,
+}
+
+impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static>
+    RouterFactory<B, E>
+{
+    /// Creates a new factory from a function which describes the router.
+    pub fn new<F>(make: F) -> Self
+    where
+        F: Fn() -> RouterBuilder<B, E> + Send + Sync + 'static,
+    {
+        RouterFactory { make: Arc::new(make) }
+    }
+
+    /// Builds a new router.
+    pub fn build(&self) -> crate::Result<Router<B, E>> {
+        (self.make)().build()
+    }
+}
+
+#[cfg(feature = "server")]
+impl<B, E> RouterFactory<B, E>
+where
+    B: HttpBody + Send + Sync + 'static,
+    <B as HttpBody>::Data: Send + Sync + 'static,
+    <B as HttpBody>::Error: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+{
+    /// Serves the address with a worker per core, each with its own single threaded runtime and its own router.
+    /// Requires the `server` feature.
+    ///
+    /// The workers share the listening socket and accept the connections independently. The startup hooks of each
+    /// router run in its worker before it accepts any connection. It blocks until all the workers stop, which happens
+    /// only if a worker fails to start.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use routerify::{Router, RouterFactory};
+    /// use hyper::{Response, Body};
+    /// use std::convert::Infallible;
+    ///
+    /// let factory = RouterFactory::new(|| {
+    ///     Router::<Body, Infallible>::builder().get("/", |_| async move { Ok(Response::new(Body::from("Home"))) })
+    /// });
+    ///
+    /// factory.serve_per_core(([127, 0, 0, 1], 3000).into(), None).unwrap();
+    /// ```
+    pub fn serve_per_core(&self, addr: SocketAddr, workers: Option<usize>) -> crate::Result<()> {
+        let workers = workers
+            .or_else(|| std::thread::available_parallelism().ok().map(|n| n.get()))
+            .unwrap_or(1);
+
+        let listener = std::net::TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+
+        let mut threads = Vec::with_capacity(workers);
+        for idx in 0..workers {
+            let listener = listener.try_clone()?;
+            let factory = self.clone();
+            let thread = std::thread::Builder::new()
+                .name(format!("routerify-worker-{}", idx))
+                .spawn(move || factory.run_worker(listener))?;
+            threads.push(thread);
+        }
+
+        let mut result = Ok(());
+        for thread in threads {
+            let worker_result = thread
+                .join()
+                .unwrap_or_else(|_| Err(Error::new("A routerify worker panicked").into()));
+            if result.is_ok() {
+                result = worker_result;
+            }
+        }
+        result
+    }
+
+    fn run_worker(&self, listener: std::net::TcpListener) -> crate::Result<()> {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+
+        runtime.block_on(async move {
+            let builder = RequestServiceBuilder::from_factory(self)?;
+            builder.lifecycle().startup().await?;
+
+            let listener = tokio::net::TcpListener::from_std(listener)?;
+            loop {
+                let (stream, remote_addr) = match listener.accept().await {
+                    Ok(conn) => conn,
+                    // The errors of a single connection e.g. a reset one must not stop the worker.
+                    Err(_) => continue,
+                };
+
+                let info = ConnectionInfo::new(remote_addr).with_local_addr(stream.local_addr()?);
+                let builder = builder.clone();
+                tokio::spawn(async move {
+                    let _ = builder.serve_connection(stream, info).await;
+                });
+            }
+        })
+    }
+}
+
+impl<B, E> Clone for RouterFactory<B, E> {
+    fn clone(&self) -> Self {
+        RouterFactory {
+            make: self.make.clone(),
+        }
+    }
+}
+
+impl<B, E> Debug for RouterFactory<B, E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "RouterFactory")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+    use crate::RequestServiceBuilder;
+    use hyper::{Body, Response};
+    use std::convert::Infallible;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn builds_independent_routers() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let factory = RouterFactory::new(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Router::<Body, Infallible>::builder()
+                .data(Arc::new(AtomicUsize::new(0)))
+                .get("/", |req| async move {
+                    let hits = req.data::<Arc<AtomicUsize>>().unwrap().fetch_add(1, Ordering::SeqCst);
+                    Ok(Response::new(Body::from(hits.to_string())))
+                })
+        });
+
+        let first = RequestServiceBuilder::from_factory(&factory).unwrap();
+        let second = RequestServiceBuilder::from_factory(&factory.clone()).unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert!(!Arc::ptr_eq(&first.router, &second.router));
+    }
+}
@@ -0,0 +1,236 @@
+use crate::service::RequestServiceBuilder;
+use crate::types::ConnectionInfo;
+use hyper::body::HttpBody;
+use std::fmt::{self, Debug, Formatter};
+use std::sync::Arc;
+#[cfg(feature = "server")]
+use tokio::io::{AsyncRead, AsyncWrite};
+
+type SelectFn<B, E> = dyn Fn(&ConnectionInfo) -> Option<RequestServiceBuilder<B, E>> + Send + Sync + 'static;
+
+/// Selects the router of a TLS connection by the server name requested through SNI or the protocol negotiated through
+/// ALPN, so multiple routers can be served on one listener e.g. a gRPC router on `h2` and a web router on `http/1.1`.
+///
+/// The router is selected once per connection after the TLS handshake, from the [`TlsInfo`](./struct.TlsInfo.html) of
+/// the [`ConnectionInfo`](./struct.ConnectionInfo.html). The selection callback is evaluated first if there is one,
+/// then the server names are matched case insensitively, then the ALPN protocols, and the default router is used if
+/// nothing matches. The lifecycle hooks of each router must be run by its own
+/// [`lifecycle`](./struct.RequestServiceBuilder.html#method.lifecycle) handle.
+///
+/// # Examples
+///
+/// ```
+/// use routerify::{ConnectionInfo, RequestServiceBuilder, Router, RouterSelector, TlsInfo};
+/// use hyper::{Response, Body};
+/// use std::convert::Infallible;
+///
+/// # fn run() -> routerify::Result<()> {
+/// let web: Router<Body, Infallible> = Router::builder()
+///     .get("/", |_| async move { Ok(Response::new(Body::from("Web"))) })
+///     .build()?;
+/// let grpc: Router<Body, Infallible> = Router::builder()
+///     .post("/greeter.Greeter/SayHello", |_| async move { Ok(Response::new(Body::empty())) })
+///     .build()?;
+/// let admin: Router<Body, Infallible> = Router::builder()
+///     .get("/", |_| async move { Ok(Response::new(Body::from("Admin"))) })
+///     .build()?;
+///
+/// let selector = RouterSelector::new(RequestServiceBuilder::new(web)?)
+///     .alpn_protocol("h2", RequestServiceBuilder::new(grpc)?)
+///     .server_name("admin.example.com", RequestServiceBuilder::new(admin)?);
+///
+/// // After the handshake, e.g. in the accept loop:
+/// let info = ConnectionInfo::new(([10, 0, 0, 7], 52100).into())
+///     .with_tls(TlsInfo::new().with_server_name("example.com").with_alpn_protocol("h2"));
+/// let service = selector.select(&info).build_with_connection_info(info);
+/// # Ok(())
+/// # }
+/// # run().unwrap();
+/// ```
+pub struct RouterSelector<B, E> {
+    default: RequestServiceBuilder<B, E>,
+    server_names: Vec<(String, RequestServiceBuilder<B, E>)>,
+    alpn_protocols: Vec<(String, RequestServiceBuilder<B, E>)>,
+    select: Option<Arc<SelectFn<B, E>>>,
+}
+
+impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static>
+    RouterSelector<B, E>
+{
+    /// Creates a new selector with the router which is used when no other router matches the connection, e.g. the
+    /// plain text connections.
+    pub fn new(default: RequestServiceBuilder<B, E>) -> Self {
+        RouterSelector {
+            default,
+            server_names: Vec::new(),
+            alpn_protocols: Vec::new(),
+            select: None,
+        }
+    }
+
+    /// Serves the connections which request the server name through SNI by the router.
+    pub fn server_name<S: Into<String>>(mut self, server_name: S, builder: RequestServiceBuilder<B, E>) -> Self {
+        self.server_names.push((server_name.into(), builder));
+        self
+    }
+
+    /// Serves the connections which negotiate the protocol through ALPN e.g. `h2` by the router.
+    pub fn alpn_protocol<S: Into<String>>(mut self, alpn_protocol: S, builder: RequestServiceBuilder<B, E>) -> Self {
+        self.alpn_protocols.push((alpn_protocol.into(), builder));
+        self
+    }
+
+    /// Sets a callback which selects the router of a connection e.g. by both the server name and the ALPN protocol. If
+    /// it returns `None`, the router is selected by the server names and the ALPN protocols.
+    pub fn select_with<F>(mut self, select: F) -> Self
+    where
+        F: Fn(&ConnectionInfo) -> Option<RequestServiceBuilder<B, E>> + Send + Sync + 'static,
+    {
+        self.select = Some(Arc::new(select));
+        self
+    }
+
+    /// Selects the router of the connection.
+    pub fn select(&self, connection_info: &ConnectionInfo) -> RequestServiceBuilder<B, E> {
+        if let Some(builder) = self.select.as_ref().and_then(|select| select(connection_info)) {
+            return builder;
+        }
+
+        let tls = match connection_info.tls() {
+            Some(tls) => tls,
+            None => return self.default.clone(),
+        };
+
+        let by_server_name = tls.server_name().and_then(|server_name| {
+            self.server_names
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(server_name))
+        });
+        let by_alpn_protocol = || {
+            tls.alpn_protocol()
+                .and_then(|alpn_protocol| self.alpn_protocols.iter().find(|(proto, _)| proto == alpn_protocol))
+        };
+
+        by_server_name
+            .or_else(by_alpn_protocol)
+            .map(|(_, builder)| builder.clone())
+            .unwrap_or_else(|| self.default.clone())
+    }
+}
+
+#[cfg(feature = "server")]
+impl<B, E> RouterSelector<B, E>
+where
+    B: HttpBody + Send + Sync + 'static,
+    <B as HttpBody>::Data: Send + Sync + 'static,
+    <B as HttpBody>::Error: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+{
+    /// Serves the requests of an accepted connection by the selected router until it's closed. Requires the `server`
+    /// feature.
+    ///
+    /// The TLS handshake must be done before, and its details must be set to the connection info by the
+    /// [`ConnectionInfo::with_tls`](./struct.ConnectionInfo.html#method.with_tls) method. Please refer to the
+    /// [`RequestServiceBuilder::serve_connection`](./struct.RequestServiceBuilder.html#method.serve_connection) method
+    /// for more info.
+    pub async fn serve_connection<I>(&self, io: I, connection_info: ConnectionInfo) -> crate::Result<()>
+    where
+        I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        self.select(&connection_info)
+            .serve_connection(io, connection_info)
+            .await
+    }
+}
+
+impl<B, E> Clone for RouterSelector<B, E> {
+    fn clone(&self) -> Self {
+        RouterSelector {
+            default: self.default.clone(),
+            server_names: self.server_names.clone(),
+            alpn_protocols: self.alpn_protocols.clone(),
+            select: self.select.clone(),
+        }
+    }
+}
+
+impl<B, E> Debug for RouterSelector<B, E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let server_names = self.server_names.iter().map(|(name, _)| name).collect::<Vec<_>>();
+        let alpn_protocols = self.alpn_protocols.iter().map(|(proto, _)| proto).collect::<Vec<_>>();
+        write!(
+            f,
+            "{{ server_names: {:?}, alpn_protocols: {:?} }}",
+            server_names, alpn_protocols
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RequestServiceBuilder, Router, TlsInfo};
+    use hyper::service::Service;
+    use hyper::{Body, Request, Response};
+    use std::convert::Infallible;
+    use std::net::SocketAddr;
+
+    fn builder(name: &'static str) -> RequestServiceBuilder<Body, Infallible> {
+        let router = Router::builder()
+            .get("/", move |_| async move { Ok(Response::new(Body::from(name))) })
+            .build()
+            .unwrap();
+        RequestServiceBuilder::new(router).unwrap()
+    }
+
+    async fn respond(selector: &RouterSelector<Body, Infallible>, tls: Option<TlsInfo>) -> String {
+        let mut info = ConnectionInfo::new(SocketAddr::from(([10, 0, 0, 7], 52100)));
+        if let Some(tls) = tls {
+            info = info.with_tls(tls);
+        }
+        let mut service = selector.select(&info).build_with_connection_info(info);
+        let req = Request::builder().uri("/").body(Body::empty()).unwrap();
+        let resp = service.call(req).await.unwrap();
+        String::from_utf8(hyper::body::to_bytes(resp.into_body()).await.unwrap().to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn selects_router_by_server_name_and_alpn_protocol() {
+        let selector = RouterSelector::new(builder("web"))
+            .alpn_protocol("h2", builder("grpc"))
+            .server_name("admin.example.com", builder("admin"));
+
+        assert_eq!(respond(&selector, None).await, "web");
+        assert_eq!(
+            respond(&selector, Some(TlsInfo::new().with_alpn_protocol("http/1.1"))).await,
+            "web"
+        );
+        assert_eq!(
+            respond(&selector, Some(TlsInfo::new().with_alpn_protocol("h2"))).await,
+            "grpc"
+        );
+
+        let admin = TlsInfo::new()
+            .with_server_name("Admin.Example.com")
+            .with_alpn_protocol("h2");
+        assert_eq!(respond(&selector, Some(admin)).await, "admin");
+
+        let api = builder("api");
+        let selector = selector.select_with(move |info| {
+            let tls = info.tls()?;
+            if tls.server_name() == Some("api.example.com") && tls.alpn_protocol() == Some("h2") {
+                Some(api.clone())
+            } else {
+                None
+            }
+        });
+        let api = TlsInfo::new()
+            .with_server_name("api.example.com")
+            .with_alpn_protocol("h2");
+        assert_eq!(respond(&selector, Some(api)).await, "api");
+        let api = TlsInfo::new()
+            .with_server_name("api.example.com")
+            .with_alpn_protocol("http/1.1");
+        assert_eq!(respond(&selector, Some(api)).await, "web");
+    }
+}
@@ -1,5 +1,9 @@
 use crate::router::Router;
 use crate::service::request_service::{RequestService, RequestServiceBuilder};
+#[cfg(feature = "hyper1")]
+use crate::service::Hyper1Service;
+use crate::service::Lifecycle;
+use crate::types::ConnectionInfo;
 use hyper::{body::HttpBody, server::conn::AddrStream, service::Service};
 use std::convert::Infallible;
 use std::future::{ready, Ready};
@@ -66,6 +70,22 @@ impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Se
         let builder = RequestServiceBuilder::new(router)?;
         Ok(RouterService { builder })
     }
+
+    /// Returns a handle to run the startup and the shutdown hooks of the router.
+    ///
+    /// Please refer to the [`Lifecycle`](./struct.Lifecycle.html) for more info.
+    pub fn lifecycle(&self) -> Lifecycle {
+        self.builder.lifecycle()
+    }
+
+    /// Creates a hyper 1.x service to process the requests of a connection, which can be passed to the connection
+    /// builders of hyper 1.x and `hyper-util`. It requires the `hyper1` feature.
+    ///
+    /// Please refer to the [`Hyper1Service`](./struct.Hyper1Service.html) for an example.
+    #[cfg(feature = "hyper1")]
+    pub fn hyper1_service(&self, connection_info: ConnectionInfo) -> Hyper1Service<B, E> {
+        self.builder.build_hyper1(connection_info)
+    }
 }
 
 impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static>
@@ -80,7 +100,8 @@ impl<B: HttpBody + Send + Sync + 'static, E: Into<Box<dyn std::error::Error + Se
     }
 
     fn call(&mut self, conn: &AddrStream) -> Self::Future {
-        let req_service = self.builder.build(conn.remote_addr());
+        let connection_info = ConnectionInfo::new(conn.remote_addr()).with_local_addr(conn.local_addr());
+        let req_service = self.builder.build_with_connection_info(connection_info);
 
         ready(Ok(req_service))
     }
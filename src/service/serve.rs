@@ -0,0 +1,762 @@
+use crate::regex_generator::generate_exact_match_regex;
+use crate::service::{proxy_protocol, serve_connection_with, RequestServiceBuilder};
+use crate::types::{ConnectionInfo, TlsInfo};
+use crate::Error;
+use futures_core::future::BoxFuture;
+use hyper::body::HttpBody;
+use hyper::server::conn::Http;
+use regex::Regex;
+use socket2::{SockRef, TcpKeepalive};
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Formatter};
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+#[cfg(unix)]
+use tokio::net::UnixListener;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::task::JoinSet;
+
+// The delay before accepting again after an accept error, e.g. when the process runs out of file descriptors.
+const ACCEPT_ERROR_DELAY: Duration = Duration::from_millis(50);
+
+/// The accept loops of a router on one or more listeners, which are created by the
+/// [`RequestServiceBuilder::serve`](./struct.RequestServiceBuilder.html#method.serve) method. Requires the `server`
+/// feature.
+///
+/// It's configured with the following defaults:
+///
+/// * At most `10000` concurrent connections.
+/// * No limit of the concurrent connections per IP address.
+/// * The `TCP_NODELAY` option is set.
+/// * The TCP keepalive probes are sent after `60` seconds of idleness.
+/// * The accept backpressure is on, i.e. no connection is accepted while the maximum is reached, so the excess
+///   connections wait in the listen backlog of the OS.
+/// * The client must send the request headers within `30` seconds, against the slow-loris attacks. It applies to the
+///   HTTP/1 connections only.
+///
+/// The connection limits are shared by all the [listeners](./struct.Listener.html), and the startup hooks of the
+/// router run once before the first connection is accepted.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hyper::{Body, Response};
+/// use routerify::{RequestServiceBuilder, Router};
+/// use std::convert::Infallible;
+/// use std::time::Duration;
+///
+/// # async fn run() -> routerify::Result<()> {
+/// let router: Router<Body, Infallible> = Router::builder()
+///     .get("/", |_| async move { Ok(Response::new(Body::from("Hello world"))) })
+///     .build()?;
+///
+/// RequestServiceBuilder::new(router)?
+///     .serve()
+///     .max_connections(Some(50_000))
+///     .max_connections_per_ip(Some(100))
+///     .header_read_timeout(Some(Duration::from_secs(5)))
+///     .run(([127, 0, 0, 1], 3000).into())
+///     .await
+/// # }
+/// ```
+pub struct Serve<B, E> {
+    builder: RequestServiceBuilder<B, E>,
+    listeners: Vec<Listener>,
+    max_connections: Option<usize>,
+    max_connections_per_ip: Option<usize>,
+    tcp_nodelay: bool,
+    tcp_keepalive: Option<Duration>,
+    accept_backpressure: bool,
+    header_read_timeout: Option<Duration>,
+}
+
+impl<B, E> Serve<B, E>
+where
+    B: HttpBody + Send + Sync + 'static,
+    <B as HttpBody>::Data: Send + Sync + 'static,
+    <B as HttpBody>::Error: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+{
+    pub(crate) fn new(builder: RequestServiceBuilder<B, E>) -> Self {
+        Serve {
+            builder,
+            listeners: Vec::new(),
+            max_connections: Some(10_000),
+            max_connections_per_ip: None,
+            tcp_nodelay: true,
+            tcp_keepalive: Some(Duration::from_secs(60)),
+            accept_backpressure: true,
+            header_read_timeout: Some(Duration::from_secs(30)),
+        }
+    }
+
+    /// Sets the maximum number of the concurrent connections. `None` means no limit.
+    pub fn max_connections(mut self, max: Option<usize>) -> Self {
+        self.max_connections = max;
+        self
+    }
+
+    /// Sets the maximum number of the concurrent connections from an IP address. The excess connections are closed
+    /// right after they are accepted. `None` means no limit.
+    pub fn max_connections_per_ip(mut self, max: Option<usize>) -> Self {
+        self.max_connections_per_ip = max;
+        self
+    }
+
+    /// Sets the `TCP_NODELAY` option of the accepted connections.
+    pub fn tcp_nodelay(mut self, nodelay: bool) -> Self {
+        self.tcp_nodelay = nodelay;
+        self
+    }
+
+    /// Sets the idle time after which the TCP keepalive probes are sent. `None` disables the probes.
+    pub fn tcp_keepalive(mut self, time: Option<Duration>) -> Self {
+        self.tcp_keepalive = time;
+        self
+    }
+
+    /// Sets whether the accepting pauses while the maximum number of the connections is reached. Otherwise, the
+    /// excess connections are accepted and closed right away.
+    pub fn accept_backpressure(mut self, enabled: bool) -> Self {
+        self.accept_backpressure = enabled;
+        self
+    }
+
+    /// Sets the timeout of reading the request headers of the HTTP/1 connections. `None` disables the timeout.
+    ///
+    /// It also bounds the PROXY protocol header and the TLS handshake of the [listeners](./struct.Listener.html).
+    pub fn header_read_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.header_read_timeout = timeout;
+        self
+    }
+
+    /// Adds a listener with its own options, which is served along with the other ones by the
+    /// [`run_listeners`](#method.run_listeners) method.
+    pub fn listener(mut self, listener: Listener) -> Self {
+        self.listeners.push(listener);
+        self
+    }
+
+    /// Binds a listener to the address and serves it.
+    pub async fn run(self, addr: SocketAddr) -> crate::Result<()> {
+        let listener = TcpListener::bind(addr).await?;
+        self.run_with_listener(listener).await
+    }
+
+    /// Serves the connections of the listener. It runs until accepting fails with a listener error.
+    pub async fn run_with_listener(self, listener: TcpListener) -> crate::Result<()> {
+        self.listener(Listener::tcp(listener)).run_listeners().await
+    }
+
+    /// Serves the connections of all the listeners added by the [`listener`](#method.listener) method together. It
+    /// runs until accepting fails with a listener error on any of them.
+    pub async fn run_listeners(mut self) -> crate::Result<()> {
+        if self.listeners.is_empty() {
+            return Err(Error::new("No listener is added to serve").into());
+        }
+
+        self.builder.lifecycle().startup().await?;
+
+        let listeners = std::mem::take(&mut self.listeners);
+        let shared = Arc::new(Shared {
+            http: self.http(),
+            connections: self.max_connections.map(|max| Arc::new(Semaphore::new(max))),
+            connections_per_ip: ConnectionsPerIp::default(),
+            serve: self,
+        });
+
+        let mut accept_loops = JoinSet::new();
+        for listener in listeners {
+            accept_loops.spawn(shared.clone().accept_loop(listener));
+        }
+        while let Some(result) = accept_loops.join_next().await {
+            result.map_err(|err| Error::new(format!("The accept loop panicked: {}", err)))??;
+        }
+        Ok(())
+    }
+
+    fn http(&self) -> Http {
+        #[allow(unused_mut)]
+        let mut http = Http::new();
+        #[cfg(feature = "hyper-http1")]
+        if let Some(timeout) = self.header_read_timeout {
+            http.http1_header_read_timeout(timeout);
+        }
+        http
+    }
+
+    fn configure_stream(&self, stream: &TcpStream) {
+        // The options are best effort, a connection which can't be tuned is still served.
+        let _ = stream.set_nodelay(self.tcp_nodelay);
+        if let Some(time) = self.tcp_keepalive {
+            let _ = SockRef::from(stream).set_tcp_keepalive(&TcpKeepalive::new().with_time(time));
+        }
+    }
+}
+
+// The state shared by the accept loops of the listeners.
+struct Shared<B, E> {
+    serve: Serve<B, E>,
+    http: Http,
+    connections: Option<Arc<Semaphore>>,
+    connections_per_ip: ConnectionsPerIp,
+}
+
+impl<B, E> Shared<B, E>
+where
+    B: HttpBody + Send + Sync + 'static,
+    <B as HttpBody>::Data: Send + Sync + 'static,
+    <B as HttpBody>::Error: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+{
+    async fn accept_loop(self: Arc<Self>, listener: Listener) -> crate::Result<()> {
+        let Listener {
+            socket,
+            tls,
+            proxy_protocol,
+            allowed_routes,
+        } = listener;
+        let options = Arc::new(ConnectionOptions {
+            tls,
+            proxy_protocol,
+            allowed_routes: if allowed_routes.is_empty() {
+                None
+            } else {
+                Some(Arc::new(allowed_routes.into_iter().map(|(_, regex)| regex).collect()))
+            },
+        });
+
+        loop {
+            let permit = match self.connections {
+                Some(ref connections) if self.serve.accept_backpressure => Some(
+                    connections
+                        .clone()
+                        .acquire_owned()
+                        .await
+                        .expect("The connection semaphore is never closed"),
+                ),
+                _ => None,
+            };
+
+            let (io, remote_addr, local_addr) = match socket.accept(&self.serve).await {
+                Ok(conn) => conn,
+                Err(err) if is_connection_error(&err) => continue,
+                Err(err) if is_resource_error(&err) => {
+                    tokio::time::sleep(ACCEPT_ERROR_DELAY).await;
+                    continue;
+                }
+                Err(err) => return Err(err.into()),
+            };
+
+            let permit = match (permit, &self.connections) {
+                (Some(permit), _) => Some(permit),
+                (None, Some(connections)) => match connections.clone().try_acquire_owned() {
+                    Ok(permit) => Some(permit),
+                    Err(_) => continue,
+                },
+                (None, None) => None,
+            };
+
+            // The address of a proxied connection is known once the PROXY protocol header is read.
+            let ip_guard = match remote_addr {
+                Some(remote_addr) if !options.proxy_protocol => {
+                    match self
+                        .connections_per_ip
+                        .acquire(remote_addr.ip(), self.serve.max_connections_per_ip)
+                    {
+                        Some(ip_guard) => ip_guard,
+                        None => continue,
+                    }
+                }
+                _ => IpGuard(None),
+            };
+
+            let conn = Connection {
+                io,
+                remote_addr,
+                local_addr,
+                ip_guard,
+                permit,
+            };
+            tokio::spawn(self.clone().serve_connection(options.clone(), conn));
+        }
+    }
+
+    async fn serve_connection(self: Arc<Self>, options: Arc<ConnectionOptions>, conn: Connection) {
+        let Connection {
+            io,
+            mut remote_addr,
+            mut local_addr,
+            mut ip_guard,
+            permit,
+        } = conn;
+
+        let handshake = {
+            let options = options.clone();
+            async move {
+                let mut io = io;
+                let mut proxied = None;
+                if options.proxy_protocol {
+                    proxied = proxy_protocol::read_header(&mut io).await?;
+                }
+                let tls = match options.tls {
+                    Some(ref acceptor) => {
+                        let (tls_io, tls) = acceptor.accept(io).await?;
+                        io = tls_io;
+                        Some(tls)
+                    }
+                    None => None,
+                };
+                io::Result::Ok((io, proxied, tls))
+            }
+        };
+        let handshake = match self.serve.header_read_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, handshake)
+                .await
+                .unwrap_or_else(|_| Err(io::ErrorKind::TimedOut.into())),
+            None => handshake.await,
+        };
+        let (io, proxied, tls) = match handshake {
+            Ok(handshake) => handshake,
+            Err(_) => return,
+        };
+
+        if let Some((source, destination)) = proxied {
+            ip_guard = match self
+                .connections_per_ip
+                .acquire(source.ip(), self.serve.max_connections_per_ip)
+            {
+                Some(ip_guard) => ip_guard,
+                None => return,
+            };
+            remote_addr = Some(source);
+            local_addr = Some(destination);
+        }
+
+        // The Unix socket connections have no remote address unless it's passed by the PROXY protocol.
+        let mut info = ConnectionInfo::new(remote_addr.unwrap_or_else(|| SocketAddr::from(([0, 0, 0, 0], 0))));
+        if let Some(local_addr) = local_addr {
+            info = info.with_local_addr(local_addr);
+        }
+        if let Some(tls) = tls {
+            info = info.with_tls(tls);
+        }
+
+        let service = self
+            .serve
+            .builder
+            .build_with_connection_info(info)
+            .with_allowed_routes(options.allowed_routes.clone());
+        let _ = serve_connection_with(&self.http, io, service).await;
+        drop(ip_guard);
+        drop(permit);
+    }
+}
+
+// An accepted connection before the handshake.
+struct Connection {
+    io: Box<dyn ListenerIo>,
+    remote_addr: Option<SocketAddr>,
+    local_addr: Option<SocketAddr>,
+    ip_guard: IpGuard,
+    permit: Option<OwnedSemaphorePermit>,
+}
+
+struct ConnectionOptions {
+    tls: Option<Arc<dyn TlsAcceptor>>,
+    proxy_protocol: bool,
+    allowed_routes: Option<Arc<Vec<Regex>>>,
+}
+
+/// The IO stream of an accepted connection, e.g. a TCP stream, a Unix socket stream or a TLS stream. It's implemented
+/// for all the types which qualify.
+pub trait ListenerIo: AsyncRead + AsyncWrite + Send + Unpin + 'static {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin + 'static> ListenerIo for T {}
+
+/// The TLS handshake of the connections of a [`Listener`](./struct.Listener.html), e.g. a wrapper of a
+/// `tokio_rustls::TlsAcceptor`. Requires the `server` feature.
+///
+/// The details of the TLS session are exposed by the
+/// [`RequestExt::connection_info`](./ext/trait.RequestExt.html#tymethod.connection_info) method.
+///
+/// # Examples
+///
+/// ```
+/// use futures::future::BoxFuture;
+/// use routerify::{ListenerIo, TlsAcceptor, TlsInfo};
+///
+/// struct RustlsAcceptor;
+///
+/// impl TlsAcceptor for RustlsAcceptor {
+///     fn accept(&self, io: Box<dyn ListenerIo>) -> BoxFuture<'static, std::io::Result<(Box<dyn ListenerIo>, TlsInfo)>> {
+///         Box::pin(async move {
+///             // Run the handshake over `io` and return the TLS stream.
+///             let info = TlsInfo::new().with_alpn_protocol("http/1.1");
+///             Ok((io, info))
+///         })
+///     }
+/// }
+/// ```
+pub trait TlsAcceptor: Send + Sync + 'static {
+    /// Runs the TLS handshake of an accepted connection and returns the TLS stream with the details of the session.
+    fn accept(&self, io: Box<dyn ListenerIo>) -> BoxFuture<'static, io::Result<(Box<dyn ListenerIo>, TlsInfo)>>;
+}
+
+enum Socket {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+impl Socket {
+    async fn accept<B, E>(
+        &self,
+        serve: &Serve<B, E>,
+    ) -> io::Result<(Box<dyn ListenerIo>, Option<SocketAddr>, Option<SocketAddr>)>
+    where
+        B: HttpBody + Send + Sync + 'static,
+        <B as HttpBody>::Data: Send + Sync + 'static,
+        <B as HttpBody>::Error: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        match self {
+            Socket::Tcp(ref listener) => {
+                let (stream, remote_addr) = listener.accept().await?;
+                serve.configure_stream(&stream);
+                let local_addr = stream.local_addr().ok();
+                Ok((Box::new(stream), Some(remote_addr), local_addr))
+            }
+            #[cfg(unix)]
+            Socket::Unix(ref listener) => {
+                let (stream, _) = listener.accept().await?;
+                Ok((Box::new(stream), None, None))
+            }
+        }
+    }
+}
+
+/// A listener of a [`Serve`](./struct.Serve.html) with its own options, so a router can be served e.g. over HTTP,
+/// HTTPS and a Unix socket at the same time. Requires the `server` feature.
+///
+/// The options of a listener are:
+///
+/// * A [`TlsAcceptor`](./trait.TlsAcceptor.html) which runs the TLS handshake of the connections.
+/// * The PROXY protocol, version 1 or 2, which passes the address of the client through a load balancer.
+/// * The route patterns which are served on the listener, e.g. to expose the admin endpoints on a Unix socket only.
+///   The other routes are answered as unknown ones, i.e. by the error handler with a `404 Not Found` error.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hyper::{Body, Response};
+/// use routerify::{Listener, RequestServiceBuilder, Router};
+/// use std::convert::Infallible;
+/// use tokio::net::{TcpListener, UnixListener};
+///
+/// # async fn run() -> routerify::Result<()> {
+/// let router: Router<Body, Infallible> = Router::builder()
+///     .get("/", |_| async move { Ok(Response::new(Body::from("Hello world"))) })
+///     .get("/admin/stats", |_| async move { Ok(Response::new(Body::from("Stats"))) })
+///     .build()?;
+///
+/// RequestServiceBuilder::new(router)?
+///     .serve()
+///     .listener(Listener::tcp(TcpListener::bind("0.0.0.0:80").await?).allow_route("/"))
+///     .listener(Listener::tcp(TcpListener::bind("0.0.0.0:8080").await?).proxy_protocol(true).allow_route("/"))
+///     .listener(Listener::unix(UnixListener::bind("/run/app/admin.sock")?).allow_route("/admin/*"))
+///     .run_listeners()
+///     .await
+/// # }
+/// ```
+pub struct Listener {
+    socket: Socket,
+    tls: Option<Arc<dyn TlsAcceptor>>,
+    proxy_protocol: bool,
+    allowed_routes: Vec<(String, Regex)>,
+}
+
+impl Listener {
+    /// Creates a listener of the TCP connections.
+    pub fn tcp(listener: TcpListener) -> Listener {
+        Listener::new(Socket::Tcp(listener))
+    }
+
+    /// Creates a listener of the Unix socket connections. Their remote address is `0.0.0.0:0` unless it's passed by
+    /// the PROXY protocol, and they aren't limited by the maximum number of the connections per IP address.
+    #[cfg(unix)]
+    pub fn unix(listener: UnixListener) -> Listener {
+        Listener::new(Socket::Unix(listener))
+    }
+
+    fn new(socket: Socket) -> Listener {
+        Listener {
+            socket,
+            tls: None,
+            proxy_protocol: false,
+            allowed_routes: Vec::new(),
+        }
+    }
+
+    /// Runs the TLS handshake of the connections with the acceptor.
+    pub fn tls<A: TlsAcceptor>(mut self, acceptor: A) -> Self {
+        self.tls = Some(Arc::new(acceptor));
+        self
+    }
+
+    /// Sets whether the connections start with a PROXY protocol header, whose addresses replace the ones of the
+    /// socket. The connections without a valid header are closed.
+    ///
+    /// It must only be enabled behind a load balancer which sends the header, as a client could forge it otherwise.
+    pub fn proxy_protocol(mut self, enabled: bool) -> Self {
+        self.proxy_protocol = enabled;
+        self
+    }
+
+    /// Serves only the routes matching the route path, e.g. `/` or `/admin/*`, on the listener. It can be called
+    /// multiple times and all the routes are served by default.
+    ///
+    /// # Panics
+    ///
+    /// It panics if the path is invalid.
+    pub fn allow_route<P: Into<String>>(mut self, path: P) -> Self {
+        let mut path = path.into();
+        if !path.ends_with('/') && !path.ends_with('*') {
+            path.push('/');
+        }
+        let (regex, _) = generate_exact_match_regex(&path).expect("Invalid listener route path");
+        self.allowed_routes.push((path, regex));
+        self
+    }
+}
+
+impl Debug for Listener {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let socket = match self.socket {
+            Socket::Tcp(ref listener) => format!("tcp {:?}", listener.local_addr().ok()),
+            #[cfg(unix)]
+            Socket::Unix(ref listener) => format!("unix {:?}", listener.local_addr().ok()),
+        };
+        write!(
+            f,
+            "{{ socket: {}, tls: {:?}, proxy_protocol: {:?}, allowed_routes: {:?} }}",
+            socket,
+            self.tls.is_some(),
+            self.proxy_protocol,
+            self.allowed_routes.iter().map(|(path, _)| path).collect::<Vec<_>>()
+        )
+    }
+}
+
+impl<B, E> Debug for Serve<B, E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{{ listeners: {:?}, max_connections: {:?}, max_connections_per_ip: {:?}, tcp_nodelay: {:?}, tcp_keepalive: {:?}, accept_backpressure: {:?}, header_read_timeout: {:?} }}",
+            self.listeners,
+            self.max_connections,
+            self.max_connections_per_ip,
+            self.tcp_nodelay,
+            self.tcp_keepalive,
+            self.accept_backpressure,
+            self.header_read_timeout
+        )
+    }
+}
+
+// The errors of a single connection e.g. a reset one, which must not stop the accept loop.
+fn is_connection_error(err: &std::io::Error) -> bool {
+    matches!(
+        err.kind(),
+        std::io::ErrorKind::ConnectionRefused
+            | std::io::ErrorKind::ConnectionAborted
+            | std::io::ErrorKind::ConnectionReset
+            | std::io::ErrorKind::Interrupted
+    )
+}
+
+// The errors which go away once some connections are closed, e.g. `EMFILE`.
+fn is_resource_error(err: &std::io::Error) -> bool {
+    !matches!(
+        err.kind(),
+        std::io::ErrorKind::InvalidInput | std::io::ErrorKind::NotConnected | std::io::ErrorKind::PermissionDenied
+    )
+}
+
+#[derive(Clone, Default)]
+struct ConnectionsPerIp {
+    counts: Arc<Mutex<HashMap<IpAddr, usize>>>,
+}
+
+impl ConnectionsPerIp {
+    fn acquire(&self, ip: IpAddr, max: Option<usize>) -> Option<IpGuard> {
+        let max = match max {
+            Some(max) => max,
+            None => return Some(IpGuard(None)),
+        };
+
+        let mut counts = self.counts.lock().unwrap();
+        let count = counts.entry(ip).or_insert(0);
+        if *count >= max {
+            return None;
+        }
+        *count += 1;
+        Some(IpGuard(Some((self.clone(), ip))))
+    }
+
+    fn release(&self, ip: IpAddr) {
+        let mut counts = self.counts.lock().unwrap();
+        if let Some(count) = counts.get_mut(&ip) {
+            *count -= 1;
+            if *count == 0 {
+                counts.remove(&ip);
+            }
+        }
+    }
+}
+
+// Releases the connection slot of an IP address when the connection is closed.
+struct IpGuard(Option<(ConnectionsPerIp, IpAddr)>);
+
+impl Drop for IpGuard {
+    fn drop(&mut self) {
+        if let Some((ref connections_per_ip, ip)) = self.0 {
+            connections_per_ip.release(ip);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Router;
+    use hyper::{Body, Response};
+    use std::convert::Infallible;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[test]
+    fn limits_connections_per_ip() {
+        let connections_per_ip = ConnectionsPerIp::default();
+        let ip = IpAddr::from([10, 0, 0, 7]);
+
+        let first = connections_per_ip.acquire(ip, Some(1));
+        assert!(first.is_some());
+        assert!(connections_per_ip.acquire(ip, Some(1)).is_none());
+        assert!(connections_per_ip
+            .acquire(IpAddr::from([10, 0, 0, 8]), Some(1))
+            .is_some());
+
+        drop(first);
+        assert!(connections_per_ip.acquire(ip, Some(1)).is_some());
+        assert!(connections_per_ip.counts.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn closes_excess_and_slow_connections() {
+        let router: Router<Body, Infallible> = Router::builder()
+            .get("/", |_| async move { Ok(Response::new(Body::from("Home"))) })
+            .build()
+            .unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let serve = RequestServiceBuilder::new(router)
+            .unwrap()
+            .serve()
+            .max_connections_per_ip(Some(1))
+            .header_read_timeout(Some(Duration::from_millis(100)));
+        tokio::spawn(serve.run_with_listener(listener));
+
+        // The first connection sends the headers slowly, so it takes the slot of the IP address until it times out.
+        let mut slow = TcpStream::connect(addr).await.unwrap();
+        slow.write_all(b"GET / HTTP/1.1\r\n").await.unwrap();
+
+        let mut excess = TcpStream::connect(addr).await.unwrap();
+        let mut buf = Vec::new();
+        excess.read_to_end(&mut buf).await.unwrap();
+        assert!(buf.is_empty());
+
+        slow.read_to_end(&mut buf).await.unwrap();
+        assert!(!String::from_utf8_lossy(&buf).contains("Home"));
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        client
+            .write_all(b"GET / HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut resp = String::new();
+        client.read_to_string(&mut resp).await.unwrap();
+        assert!(resp.starts_with("HTTP/1.1 200 OK"));
+        assert!(resp.ends_with("Home"));
+    }
+
+    async fn get(mut stream: impl ListenerIo, prefix: &str, path: &str) -> String {
+        let req = format!(
+            "{}GET {} HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n",
+            prefix, path
+        );
+        stream.write_all(req.as_bytes()).await.unwrap();
+        // A connection which is closed before its request is read may be reset.
+        let mut resp = String::new();
+        let _ = stream.read_to_string(&mut resp).await;
+        resp
+    }
+
+    #[tokio::test]
+    async fn serves_multiple_listeners() {
+        use crate::ext::RequestExt;
+
+        let router: Router<Body, Infallible> = Router::builder()
+            .get("/", |req| async move {
+                Ok(Response::new(Body::from(req.remote_addr().to_string())))
+            })
+            .get(
+                "/admin/stats",
+                |_| async move { Ok(Response::new(Body::from("Stats"))) },
+            )
+            .build()
+            .unwrap();
+        let public = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let public_addr = public.local_addr().unwrap();
+        let proxied = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxied_addr = proxied.local_addr().unwrap();
+        #[allow(unused_mut)]
+        let mut serve = RequestServiceBuilder::new(router)
+            .unwrap()
+            .serve()
+            .listener(Listener::tcp(public).allow_route("/"))
+            .listener(Listener::tcp(proxied).proxy_protocol(true));
+        #[cfg(unix)]
+        let socket_path = std::env::temp_dir().join(format!("routerify-serve-{}.sock", std::process::id()));
+        #[cfg(unix)]
+        {
+            let _ = std::fs::remove_file(&socket_path);
+            serve = serve.listener(Listener::unix(UnixListener::bind(&socket_path).unwrap()).allow_route("/admin/*"));
+        }
+        tokio::spawn(serve.run_listeners());
+
+        let resp = get(TcpStream::connect(public_addr).await.unwrap(), "", "/").await;
+        assert!(resp.contains("\r\n\r\n127.0.0.1:"), "{}", resp);
+        let resp = get(TcpStream::connect(public_addr).await.unwrap(), "", "/admin/stats").await;
+        assert!(resp.starts_with("HTTP/1.1 404 Not Found"), "{}", resp);
+
+        let proxy_header = "PROXY TCP4 192.0.2.7 10.0.0.1 56324 443\r\n";
+        let resp = get(TcpStream::connect(proxied_addr).await.unwrap(), proxy_header, "/").await;
+        assert!(resp.ends_with("192.0.2.7:56324"), "{}", resp);
+        let resp = get(TcpStream::connect(proxied_addr).await.unwrap(), "", "/").await;
+        assert!(resp.is_empty());
+
+        #[cfg(unix)]
+        {
+            let stream = tokio::net::UnixStream::connect(&socket_path).await.unwrap();
+            assert!(get(stream, "", "/admin/stats").await.ends_with("Stats"));
+            let stream = tokio::net::UnixStream::connect(&socket_path).await.unwrap();
+            assert!(get(stream, "", "/").await.starts_with("HTTP/1.1 404 Not Found"));
+            let _ = std::fs::remove_file(&socket_path);
+        }
+    }
+}
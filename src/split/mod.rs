@@ -0,0 +1,262 @@
+//! Percentage based traffic splitting between the handlers of a route, e.g. for canary releases.
+//!
+//! A [`Split`](./struct.Split.html) holds the weighted variants of a route and a [`SplitDecider`](./trait.SplitDecider.html)
+//! picks the variant of each request, by the weights at random by default. The [`StickyCookie`](./struct.StickyCookie.html)
+//! decider keeps a client on the same variant across the requests.
+//!
+//! The chosen [`Variant`](./struct.Variant.html) is stored in the request context, so the post middlewares can record
+//! it e.g. as a metrics label.
+//!
+//! # Examples
+//!
+//! ```
+//! use routerify::Router;
+//! use routerify::split::{Split, StickyCookie, Variant};
+//! use hyper::{Body, Method, Response};
+//! use std::convert::Infallible;
+//!
+//! # fn run() -> Router<Body, Infallible> {
+//! let router = Router::builder()
+//!     // 90% of the requests are served by the old handler.
+//!     .get_split(
+//!         "/search",
+//!         90,
+//!         |_| async move { Ok(Response::new(Body::from("Old search"))) },
+//!         10,
+//!         |_| async move { Ok(Response::new(Body::from("New search"))) },
+//!     )
+//!     .add_split(
+//!         "/checkout",
+//!         vec![Method::POST],
+//!         Split::new()
+//!             .variant("v1", 50, |_| async move { Ok(Response::new(Body::from("Checkout v1"))) })
+//!             .variant("v2", 50, |_| async move { Ok(Response::new(Body::from("Checkout v2"))) })
+//!             .decider(StickyCookie::new("checkout_variant")),
+//!     )
+//!     .middleware(routerify::Middleware::post_with_info(|res, req_info| async move {
+//!         if let Some(variant) = req_info.context::<Variant>() {
+//!             println!("Served by the {} variant", variant.name());
+//!         }
+//!         Ok(res)
+//!     }))
+//!     .build()
+//!     .unwrap();
+//! # router
+//! # }
+//! # run();
+//! ```
+
+use crate::ext::RequestExt;
+use crate::helpers;
+use hyper::header::{self, HeaderMap, HeaderValue};
+use hyper::{Request, Response};
+use std::fmt::{self, Debug, Formatter};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+type Handler<B, E> =
+    Box<dyn Fn(Request<hyper::Body>) -> Pin<Box<dyn Future<Output = Result<Response<B>, E>> + Send>> + Send + Sync>;
+
+/// A weighted variant of a split route, which is stored in the request context of the requests it serves.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variant {
+    name: String,
+    weight: u32,
+}
+
+impl Variant {
+    /// Returns the name of the variant.
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    /// Returns the weight of the variant.
+    pub fn weight(&self) -> u32 {
+        self.weight
+    }
+}
+
+/// A source of the split decisions.
+pub trait SplitDecider: Send + Sync + 'static {
+    /// Picks the index of the variant which serves the request. The variants have a positive total weight.
+    fn decide(&self, req: &Request<hyper::Body>, variants: &[Variant]) -> usize;
+
+    /// Called with the response headers of the chosen variant, e.g. to persist the decision. Defaults to nothing.
+    fn persist(&self, _variant: &Variant, _headers: &mut HeaderMap) {}
+}
+
+/// The default decider, which picks the variants at random by their weights.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RandomDecider;
+
+impl SplitDecider for RandomDecider {
+    fn decide(&self, _req: &Request<hyper::Body>, variants: &[Variant]) -> usize {
+        pick_weighted(variants, helpers::random_u64())
+    }
+}
+
+/// A decider which keeps a client on the variant named by a cookie and otherwise picks one at random by the weights.
+///
+/// The chosen variant is stored in the cookie of the response.
+#[derive(Debug, Clone)]
+pub struct StickyCookie {
+    name: String,
+    max_age: u64,
+}
+
+impl StickyCookie {
+    /// Creates a decider which uses the cookie of the specified name, kept for 30 days.
+    pub fn new<N: Into<String>>(name: N) -> Self {
+        StickyCookie {
+            name: name.into(),
+            max_age: 30 * 24 * 60 * 60,
+        }
+    }
+
+    /// Sets how long the cookie is kept, in seconds.
+    pub fn max_age(mut self, max_age: u64) -> Self {
+        self.max_age = max_age;
+        self
+    }
+}
+
+impl SplitDecider for StickyCookie {
+    fn decide(&self, req: &Request<hyper::Body>, variants: &[Variant]) -> usize {
+        let sticky = req
+            .headers()
+            .get_all(header::COOKIE)
+            .iter()
+            .filter_map(|val| val.to_str().ok())
+            .flat_map(|val| val.split(';'))
+            .filter_map(|pair| pair.trim().split_once('='))
+            .find(|(key, _)| *key == self.name)
+            .and_then(|(_, val)| {
+                variants
+                    .iter()
+                    .position(|variant| variant.weight > 0 && variant.name == val.trim_matches('"'))
+            });
+
+        sticky.unwrap_or_else(|| RandomDecider.decide(req, variants))
+    }
+
+    fn persist(&self, variant: &Variant, headers: &mut HeaderMap) {
+        let cookie = format!(
+            "{}={}; Path=/; Max-Age={}; HttpOnly; SameSite=Lax",
+            self.name, variant.name, self.max_age
+        );
+        if let Ok(value) = HeaderValue::from_str(&cookie) {
+            headers.append(header::SET_COOKIE, value);
+        }
+    }
+}
+
+/// The variants of a split route and the decider picking between them.
+///
+/// Please refer to the [module](./index.html) documentation for more info.
+pub struct Split<B, E> {
+    variants: Vec<Variant>,
+    handlers: Vec<Handler<B, E>>,
+    decider: Arc<dyn SplitDecider>,
+}
+
+impl<B: Send + 'static, E: 'static> Split<B, E> {
+    /// Creates a split without variants, which uses the [`RandomDecider`](./struct.RandomDecider.html).
+    pub fn new() -> Self {
+        Split {
+            variants: Vec::new(),
+            handlers: Vec::new(),
+            decider: Arc::new(RandomDecider),
+        }
+    }
+
+    /// Adds a variant with the name, the weight and the handler.
+    pub fn variant<N, H, R>(mut self, name: N, weight: u32, handler: H) -> Self
+    where
+        N: Into<String>,
+        H: Fn(Request<hyper::Body>) -> R + Send + Sync + 'static,
+        R: Future<Output = Result<Response<B>, E>> + Send + 'static,
+    {
+        self.variants.push(Variant {
+            name: name.into(),
+            weight,
+        });
+        self.handlers.push(Box::new(move |req| Box::pin(handler(req))));
+        self
+    }
+
+    /// Sets the source of the split decisions.
+    pub fn decider<D: SplitDecider>(mut self, decider: D) -> Self {
+        self.decider = Arc::new(decider);
+        self
+    }
+
+    pub(crate) fn validate(&self) -> crate::Result<()> {
+        if self.variants.iter().map(|variant| variant.weight as u64).sum::<u64>() == 0 {
+            return Err(crate::Error::new("A split route needs a variant with a positive weight").into());
+        }
+        Ok(())
+    }
+
+    pub(crate) async fn handle(&self, req: Request<hyper::Body>) -> Result<Response<B>, E> {
+        let idx = self.decider.decide(&req, &self.variants).min(self.variants.len() - 1);
+        let variant = &self.variants[idx];
+        req.set_context(variant.clone());
+
+        let mut res = (self.handlers[idx])(req).await?;
+        self.decider.persist(variant, res.headers_mut());
+        Ok(res)
+    }
+}
+
+impl<B: Send + 'static, E: 'static> Default for Split<B, E> {
+    fn default() -> Self {
+        Split::new()
+    }
+}
+
+impl<B, E> Debug for Split<B, E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{{ variants: {:?} }}", self.variants)
+    }
+}
+
+// Maps a random number onto the variants proportionally to their weights.
+fn pick_weighted(variants: &[Variant], random: u64) -> usize {
+    let total = variants.iter().map(|variant| variant.weight as u64).sum::<u64>();
+    let mut point = random % total.max(1);
+    for (idx, variant) in variants.iter().enumerate() {
+        if point < variant.weight as u64 {
+            return idx;
+        }
+        point -= variant.weight as u64;
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_variants_by_weight() {
+        let variants = vec![
+            Variant {
+                name: "control".to_owned(),
+                weight: 90,
+            },
+            Variant {
+                name: "off".to_owned(),
+                weight: 0,
+            },
+            Variant {
+                name: "canary".to_owned(),
+                weight: 10,
+            },
+        ];
+        assert_eq!(pick_weighted(&variants, 0), 0);
+        assert_eq!(pick_weighted(&variants, 89), 0);
+        assert_eq!(pick_weighted(&variants, 90), 2);
+        assert_eq!(pick_weighted(&variants, 199), 2);
+    }
+}
@@ -0,0 +1,359 @@
+//! Server-Sent Events broadcast to many clients, with the resumption of the reconnected clients.
+//!
+//! The [`Broadcaster`](./struct.Broadcaster.html) creates a `text/event-stream` response for each client, and sends
+//! every [`Event`](./struct.Event.html) to all the connected clients. The events get increasing ids, and the recent
+//! ones are kept, so a reconnecting client which sends the `Last-Event-ID` header receives the events it missed
+//! before the new ones.
+//!
+//! Each client has a bounded queue of the events which aren't yet written to its connection. The
+//! [`DropPolicy`](./enum.DropPolicy.html) decides what happens once the queue of a slow client is full, so it doesn't
+//! hold back the other clients or grow the memory without a limit.
+//!
+//! # Examples
+//!
+//! ```
+//! use routerify::{Router, RouteError};
+//! use routerify::sse::{Broadcaster, DropPolicy, Event};
+//! use hyper::{Response, Body};
+//!
+//! # fn run() -> Router<Body, RouteError> {
+//! let broadcaster = Broadcaster::new().capacity(64).replay(100).drop_policy(DropPolicy::DropOldest);
+//!
+//! let events = broadcaster.clone();
+//! let router = Router::builder()
+//!     .get("/events", move |req| {
+//!         let events = events.clone();
+//!         async move { Ok(events.subscribe(&req)) }
+//!     })
+//!     .post("/messages", move |_| {
+//!         let broadcaster = broadcaster.clone();
+//!         async move {
+//!             broadcaster.send(Event::new("A new message").with_event("message"));
+//!             Ok(Response::new(Body::from("Sent")))
+//!         }
+//!     })
+//!     .build()
+//!     .unwrap();
+//! # router
+//! # }
+//! # run();
+//! ```
+
+use futures_core::Stream;
+use hyper::body::Bytes;
+use hyper::header::{self, HeaderValue};
+use hyper::{Body, Request, Response};
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::fmt::{self, Debug, Formatter};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+const LAST_EVENT_ID: &str = "last-event-id";
+
+/// An event which is sent to the clients of a [`Broadcaster`](./struct.Broadcaster.html).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    event: Option<String>,
+    data: String,
+}
+
+impl Event {
+    /// Creates a new event with the data. A multiline data is sent as multiple `data` fields.
+    pub fn new<D: Into<String>>(data: D) -> Self {
+        Event {
+            event: None,
+            data: data.into(),
+        }
+    }
+
+    /// Sets the event type, which is `message` by default on the client.
+    pub fn with_event<T: Into<String>>(mut self, event: T) -> Self {
+        self.event = Some(event.into());
+        self
+    }
+
+    fn encode(&self, id: u64) -> Bytes {
+        let mut frame = format!("id: {}\n", id);
+        if let Some(ref event) = self.event {
+            // A line break would end the field.
+            frame.push_str(&format!("event: {}\n", event.replace(['\r', '\n'], "")));
+        }
+        for line in self.data.split('\n') {
+            frame.push_str(&format!("data: {}\n", line.trim_end_matches('\r')));
+        }
+        frame.push('\n');
+        Bytes::from(frame)
+    }
+}
+
+/// What happens once the queue of a slow client is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DropPolicy {
+    /// Drops the oldest queued event to make room for the new one.
+    DropOldest,
+    /// Drops the new event.
+    DropNewest,
+    /// Closes the connection of the client, which can reconnect and resume from its last event.
+    Disconnect,
+}
+
+struct ClientState {
+    frames: VecDeque<Bytes>,
+    waker: Option<Waker>,
+    is_closed: bool,
+}
+
+struct Client {
+    state: Mutex<ClientState>,
+}
+
+impl Client {
+    // Queues a frame, and returns `false` if the client is closed.
+    fn push(&self, frame: Bytes, capacity: usize, policy: DropPolicy) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.is_closed {
+            return false;
+        }
+
+        if state.frames.len() >= capacity {
+            match policy {
+                DropPolicy::DropOldest => {
+                    state.frames.pop_front();
+                }
+                DropPolicy::DropNewest => return true,
+                DropPolicy::Disconnect => {
+                    state.is_closed = true;
+                    state.frames.clear();
+                }
+            }
+        }
+        if !state.is_closed {
+            state.frames.push_back(frame);
+        }
+
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+        !state.is_closed
+    }
+
+    fn close(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.is_closed = true;
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+struct ClientStream {
+    client: Arc<Client>,
+}
+
+impl Stream for ClientStream {
+    type Item = Result<Bytes, Infallible>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut state = self.client.state.lock().unwrap();
+        if let Some(frame) = state.frames.pop_front() {
+            return Poll::Ready(Some(Ok(frame)));
+        }
+        if state.is_closed {
+            return Poll::Ready(None);
+        }
+
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+struct BroadcastState {
+    next_id: u64,
+    // The recent events with their ids, which are replayed to the reconnecting clients.
+    history: VecDeque<(u64, Bytes)>,
+    clients: Vec<Arc<Client>>,
+}
+
+/// A Server-Sent Events channel to all of its clients.
+///
+/// The clones of a broadcaster share the same clients and events. Please refer to the [module](./index.html)
+/// documentation for more info.
+#[derive(Clone)]
+pub struct Broadcaster {
+    capacity: usize,
+    replay: usize,
+    drop_policy: DropPolicy,
+    state: Arc<Mutex<BroadcastState>>,
+}
+
+impl Default for Broadcaster {
+    fn default() -> Self {
+        Broadcaster::new()
+    }
+}
+
+impl Broadcaster {
+    /// Creates a new broadcaster which queues at most 32 events per client, drops the oldest ones of the slow clients,
+    /// and keeps the last 32 events to replay.
+    pub fn new() -> Self {
+        Broadcaster {
+            capacity: 32,
+            replay: 32,
+            drop_policy: DropPolicy::DropOldest,
+            state: Arc::new(Mutex::new(BroadcastState {
+                next_id: 1,
+                history: VecDeque::new(),
+                clients: Vec::new(),
+            })),
+        }
+    }
+
+    /// Sets the maximum number of the queued events per client.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// Sets the number of the recent events which are kept to replay to the reconnecting clients. Zero disables the
+    /// replay.
+    pub fn replay(mut self, replay: usize) -> Self {
+        self.replay = replay;
+        self
+    }
+
+    /// Sets what happens once the queue of a slow client is full.
+    pub fn drop_policy(mut self, drop_policy: DropPolicy) -> Self {
+        self.drop_policy = drop_policy;
+        self
+    }
+
+    /// Creates the `text/event-stream` response of a new client. If the request has the `Last-Event-ID` header, the
+    /// kept events after that id are sent first.
+    pub fn subscribe<B>(&self, req: &Request<B>) -> Response<Body> {
+        let last_id = req
+            .headers()
+            .get(LAST_EVENT_ID)
+            .and_then(|val| val.to_str().ok())
+            .and_then(|val| val.trim().parse::<u64>().ok());
+
+        let mut state = self.state.lock().unwrap();
+        let client = Arc::new(Client {
+            state: Mutex::new(ClientState {
+                frames: VecDeque::new(),
+                waker: None,
+                is_closed: false,
+            }),
+        });
+        if let Some(last_id) = last_id {
+            for (_, frame) in state.history.iter().filter(|(id, _)| *id > last_id) {
+                client.push(frame.clone(), self.capacity, self.drop_policy);
+            }
+        }
+        state.clients.push(client.clone());
+        drop(state);
+
+        let mut resp = Response::new(Body::wrap_stream(ClientStream { client }));
+        let headers = resp.headers_mut();
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("text/event-stream"));
+        headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+        resp
+    }
+
+    /// Sends the event to all the clients, and returns its id.
+    pub fn send(&self, event: Event) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+
+        let frame = event.encode(id);
+        if self.replay > 0 {
+            if state.history.len() >= self.replay {
+                state.history.pop_front();
+            }
+            state.history.push_back((id, frame.clone()));
+        }
+
+        // The clients whose responses are dropped are removed too.
+        let (capacity, drop_policy) = (self.capacity, self.drop_policy);
+        state
+            .clients
+            .retain(|client| Arc::strong_count(client) > 1 && client.push(frame.clone(), capacity, drop_policy));
+        id
+    }
+
+    /// Returns the number of the connected clients.
+    pub fn clients(&self) -> usize {
+        let mut state = self.state.lock().unwrap();
+        state.clients.retain(|client| Arc::strong_count(client) > 1);
+        state.clients.len()
+    }
+
+    /// Ends the responses of all the clients once their queued events are sent, e.g. on shutdown.
+    pub fn close(&self) {
+        let mut state = self.state.lock().unwrap();
+        for client in state.clients.drain(..) {
+            client.close();
+        }
+    }
+}
+
+impl Debug for Broadcaster {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{{ capacity: {}, replay: {}, drop_policy: {:?} }}",
+            self.capacity, self.replay, self.drop_policy
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn body_text(resp: Response<Body>) -> String {
+        let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn replays_missed_events() {
+        let broadcaster = Broadcaster::new().replay(2);
+        let first = broadcaster.subscribe(&Request::get("/").body(()).unwrap());
+
+        broadcaster.send(Event::new("one"));
+        broadcaster.send(Event::new("two\nlines").with_event("update"));
+        broadcaster.send(Event::new("three"));
+        let resumed = broadcaster.subscribe(&Request::get("/").header("last-event-id", "1").body(()).unwrap());
+        assert_eq!(broadcaster.clients(), 2);
+        broadcaster.close();
+
+        assert_eq!(
+            body_text(first).await,
+            "id: 1\ndata: one\n\nid: 2\nevent: update\ndata: two\ndata: lines\n\nid: 3\ndata: three\n\n"
+        );
+        assert_eq!(
+            body_text(resumed).await,
+            "id: 2\nevent: update\ndata: two\ndata: lines\n\nid: 3\ndata: three\n\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn applies_drop_policies() {
+        for (policy, expected) in [
+            (DropPolicy::DropOldest, "id: 2\ndata: b\n\nid: 3\ndata: c\n\n"),
+            (DropPolicy::DropNewest, "id: 1\ndata: a\n\nid: 2\ndata: b\n\n"),
+            (DropPolicy::Disconnect, ""),
+        ] {
+            let broadcaster = Broadcaster::new().capacity(2).drop_policy(policy);
+            let resp = broadcaster.subscribe(&Request::get("/").body(()).unwrap());
+            for data in ["a", "b", "c"] {
+                broadcaster.send(Event::new(data));
+            }
+            broadcaster.close();
+            assert_eq!(body_text(resp).await, expected, "{:?}", policy);
+        }
+    }
+}
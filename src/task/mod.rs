@@ -0,0 +1,179 @@
+use crate::ext::RequestExt;
+use crate::Error;
+use std::any::Any;
+use std::fmt::{self, Debug, Formatter};
+use std::future::Future;
+use std::sync::Arc;
+use tokio_util::task::TaskTracker;
+
+type PanicHandler = Arc<dyn Fn(&str) + Send + Sync + 'static>;
+
+/// A registry of the background tasks spawned by the route handlers and the middlewares.
+///
+/// Share it with the [`RouterBuilder::data`](./struct.RouterBuilder.html#method.data) method and spawn tasks
+/// with the [`spawn_scoped`](./fn.spawn_scoped.html) function instead of `tokio::spawn`, so that the tasks can be
+/// drained on shutdown and their panics are surfaced to a handler.
+///
+/// # Examples
+///
+/// ```
+/// use routerify::{Router, RouteError, TaskRegistry};
+/// use hyper::{Response, Body};
+///
+/// # async fn run() -> Router<Body, RouteError> {
+/// let tasks = TaskRegistry::new().on_panic(|msg| eprintln!("Background task panicked: {}", msg));
+///
+/// let router = Router::builder()
+///     .data(tasks.clone())
+///     .post("/signup", |req| async move {
+///         routerify::spawn_scoped(&req, async move {
+///             // Send the welcome email.
+///         })?;
+///         Ok(Response::new(Body::from("Signed up")))
+///     })
+///     .build()
+///     .unwrap();
+///
+/// // Later, once the server stops accepting connections.
+/// tasks.shutdown().await;
+/// # router
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct TaskRegistry {
+    tracker: TaskTracker,
+    panic_handler: PanicHandler,
+}
+
+impl TaskRegistry {
+    /// Creates a new `TaskRegistry` which logs the panics of its tasks to stderr.
+    pub fn new() -> TaskRegistry {
+        TaskRegistry::default()
+    }
+
+    /// Sets a handler which is called with the panic message whenever a task panics.
+    pub fn on_panic<H>(mut self, handler: H) -> Self
+    where
+        H: Fn(&str) + Send + Sync + 'static,
+    {
+        self.panic_handler = Arc::new(handler);
+        self
+    }
+
+    /// Spawns a task on the tokio runtime and tracks it until it finishes.
+    ///
+    /// It fails if the registry has been shut down.
+    pub fn spawn<F>(&self, fut: F) -> crate::Result<()>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        if self.tracker.is_closed() {
+            return Err(Error::new("Could not spawn a task as the task registry is shut down").into());
+        }
+
+        let panic_handler = self.panic_handler.clone();
+        self.tracker.spawn(async move {
+            if let Err(err) = tokio::spawn(fut).await {
+                if err.is_panic() {
+                    panic_handler(panic_message(err.into_panic().as_ref()));
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Returns the number of running tasks.
+    pub fn len(&self) -> usize {
+        self.tracker.len()
+    }
+
+    /// Checks if there are no running tasks.
+    pub fn is_empty(&self) -> bool {
+        self.tracker.is_empty()
+    }
+
+    /// Stops accepting new tasks and waits for the running ones to finish.
+    pub async fn shutdown(&self) {
+        self.tracker.close();
+        self.tracker.wait().await;
+    }
+}
+
+impl Default for TaskRegistry {
+    fn default() -> TaskRegistry {
+        TaskRegistry {
+            tracker: TaskTracker::new(),
+            panic_handler: Arc::new(|msg: &str| eprintln!("Routerify: A background task panicked: {}", msg)),
+        }
+    }
+}
+
+impl Debug for TaskRegistry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{{ tasks: {}, closed: {} }}",
+            self.tracker.len(),
+            self.tracker.is_closed()
+        )
+    }
+}
+
+/// Spawns a background task on the [`TaskRegistry`](./struct.TaskRegistry.html) shared with the request.
+///
+/// The `ctx` is a request or its parts. It fails if no `TaskRegistry` is shared by the
+/// [`RouterBuilder::data`](./struct.RouterBuilder.html#method.data) method or if the registry has been shut down.
+///
+/// Please refer to the [`TaskRegistry`](./struct.TaskRegistry.html) for an example.
+pub fn spawn_scoped<C, F>(ctx: &C, fut: F) -> crate::Result<()>
+where
+    C: RequestExt,
+    F: Future<Output = ()> + Send + 'static,
+{
+    ctx.data::<TaskRegistry>()
+        .ok_or_else(|| Error::new("No TaskRegistry is shared with the router"))?
+        .spawn(fut)
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.as_str()
+    } else {
+        "Box<dyn Any>"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn drains_tasks_and_reports_panics() {
+        let panics = Arc::new(Mutex::new(Vec::new()));
+        let done = Arc::new(AtomicUsize::new(0));
+
+        let panics_clone = panics.clone();
+        let tasks = TaskRegistry::new().on_panic(move |msg| panics_clone.lock().unwrap().push(msg.to_owned()));
+
+        let done_clone = done.clone();
+        tasks
+            .spawn(async move {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                done_clone.fetch_add(1, Ordering::SeqCst);
+            })
+            .unwrap();
+        tasks.spawn(async { panic!("boom") }).unwrap();
+
+        tasks.shutdown().await;
+        assert!(tasks.is_empty());
+        assert_eq!(done.load(Ordering::SeqCst), 1);
+        assert_eq!(*panics.lock().unwrap(), vec!["boom".to_owned()]);
+        assert!(tasks.spawn(async {}).is_err());
+    }
+}
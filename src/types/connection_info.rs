@@ -0,0 +1,157 @@
+use hyper::Version;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+/// Represents some information about the connection of the incoming request e.g. the local and the peer
+/// addresses and the TLS details.
+///
+/// The serving layer attaches it per connection by the
+/// [`RequestServiceBuilder::build_with_connection_info`](./struct.RequestServiceBuilder.html#method.build_with_connection_info)
+/// method and it's accessible by the [`RequestExt::connection_info`](./ext/trait.RequestExt.html#tymethod.connection_info) method.
+///
+/// # Examples
+///
+/// ```
+/// use routerify::{ConnectionInfo, TlsInfo};
+/// use std::net::SocketAddr;
+///
+/// let info = ConnectionInfo::new(SocketAddr::from(([10, 0, 0, 7], 52100)))
+///     .with_local_addr(SocketAddr::from(([0, 0, 0, 0], 443)))
+///     .with_tls(TlsInfo::new().with_server_name("example.com").with_alpn_protocol("h2"));
+///
+/// assert!(info.is_secure());
+/// assert_eq!(info.tls().unwrap().server_name(), Some("example.com"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConnectionInfo {
+    remote_addr: SocketAddr,
+    local_addr: Option<SocketAddr>,
+    tls: Option<Arc<TlsInfo>>,
+    version: Option<Version>,
+}
+
+impl ConnectionInfo {
+    /// Creates a new `ConnectionInfo` instance with the address of the peer.
+    pub fn new(remote_addr: SocketAddr) -> ConnectionInfo {
+        ConnectionInfo {
+            remote_addr,
+            local_addr: None,
+            tls: None,
+            version: None,
+        }
+    }
+
+    /// Sets the local address the connection is accepted on.
+    pub fn with_local_addr(mut self, local_addr: SocketAddr) -> Self {
+        self.local_addr = Some(local_addr);
+        self
+    }
+
+    /// Sets the TLS details of the connection.
+    pub fn with_tls(mut self, tls: TlsInfo) -> Self {
+        self.tls = Some(Arc::new(tls));
+        self
+    }
+
+    pub(crate) fn with_version(mut self, version: Version) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    /// Returns the address of the peer.
+    pub fn remote_addr(&self) -> SocketAddr {
+        self.remote_addr
+    }
+
+    /// Returns the local address the connection is accepted on, if known.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+
+    /// Returns the TLS details if the connection is secured.
+    pub fn tls(&self) -> Option<&TlsInfo> {
+        self.tls.as_deref()
+    }
+
+    /// Checks if the connection is secured by TLS.
+    pub fn is_secure(&self) -> bool {
+        self.tls.is_some()
+    }
+
+    /// Returns the HTTP version of the request.
+    pub fn version(&self) -> Option<Version> {
+        self.version
+    }
+}
+
+/// Represents the TLS details of a connection. Please refer to the [`ConnectionInfo`](./struct.ConnectionInfo.html) for more info.
+#[derive(Debug, Clone, Default)]
+pub struct TlsInfo {
+    server_name: Option<String>,
+    alpn_protocol: Option<String>,
+    protocol_version: Option<String>,
+    cipher_suite: Option<String>,
+    peer_certificates: Vec<Vec<u8>>,
+}
+
+impl TlsInfo {
+    /// Creates a new empty `TlsInfo` instance.
+    pub fn new() -> TlsInfo {
+        TlsInfo::default()
+    }
+
+    /// Sets the server name requested by the client through SNI.
+    pub fn with_server_name<S: Into<String>>(mut self, server_name: S) -> Self {
+        self.server_name = Some(server_name.into());
+        self
+    }
+
+    /// Sets the protocol negotiated through ALPN e.g. `h2`.
+    pub fn with_alpn_protocol<S: Into<String>>(mut self, alpn_protocol: S) -> Self {
+        self.alpn_protocol = Some(alpn_protocol.into());
+        self
+    }
+
+    /// Sets the TLS protocol version e.g. `TLSv1.3`.
+    pub fn with_protocol_version<S: Into<String>>(mut self, protocol_version: S) -> Self {
+        self.protocol_version = Some(protocol_version.into());
+        self
+    }
+
+    /// Sets the negotiated cipher suite.
+    pub fn with_cipher_suite<S: Into<String>>(mut self, cipher_suite: S) -> Self {
+        self.cipher_suite = Some(cipher_suite.into());
+        self
+    }
+
+    /// Sets the DER encoded certificate chain presented by the peer.
+    pub fn with_peer_certificates(mut self, peer_certificates: Vec<Vec<u8>>) -> Self {
+        self.peer_certificates = peer_certificates;
+        self
+    }
+
+    /// Returns the server name requested by the client through SNI.
+    pub fn server_name(&self) -> Option<&str> {
+        self.server_name.as_deref()
+    }
+
+    /// Returns the protocol negotiated through ALPN.
+    pub fn alpn_protocol(&self) -> Option<&str> {
+        self.alpn_protocol.as_deref()
+    }
+
+    /// Returns the TLS protocol version.
+    pub fn protocol_version(&self) -> Option<&str> {
+        self.protocol_version.as_deref()
+    }
+
+    /// Returns the negotiated cipher suite.
+    pub fn cipher_suite(&self) -> Option<&str> {
+        self.cipher_suite.as_deref()
+    }
+
+    /// Returns the DER encoded certificate chain presented by the peer. It's empty if the peer didn't present any.
+    pub fn peer_certificates(&self) -> &[Vec<u8>] {
+        self.peer_certificates.as_slice()
+    }
+}
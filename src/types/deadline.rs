@@ -0,0 +1,31 @@
+use std::time::{Duration, Instant};
+
+/// The deadline of the current request, which is put into the request context with the
+/// [`RequestExt::set_context`](./ext/trait.RequestExt.html#tymethod.set_context) method.
+///
+/// It's enforced by the [`RequestBodyExt::read_body_with_deadline`](./ext/trait.RequestBodyExt.html#tymethod.read_body_with_deadline)
+/// method and, with the `client` feature, propagated to the upstream services by the [client](./client/index.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Deadline(Instant);
+
+impl Deadline {
+    /// Creates a deadline at the specified instant.
+    pub fn at(instant: Instant) -> Self {
+        Deadline(instant)
+    }
+
+    /// Creates a deadline after the specified duration from now.
+    pub fn after(duration: Duration) -> Self {
+        Deadline(Instant::now() + duration)
+    }
+
+    /// Returns the instant of the deadline.
+    pub fn instant(&self) -> Instant {
+        self.0
+    }
+
+    /// Returns the time left until the deadline, which is zero once it has passed.
+    pub fn remaining(&self) -> Duration {
+        self.0.saturating_duration_since(Instant::now())
+    }
+}
@@ -0,0 +1,119 @@
+use crate::helpers;
+use hyper::header::{self, HeaderMap, HeaderName, HeaderValue};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Describes the deprecation of a route, which is attached by the
+/// [`RouterBuilder::deprecated`](./struct.RouterBuilder.html#method.deprecated) method.
+///
+/// The responses of a deprecated route get the `Deprecation` header of RFC 9745, the `Sunset` header of RFC 8594 if
+/// the sunset date is set, and a `Link` header with the `deprecation` relation if the link is set. The headers already
+/// set by the handler are kept.
+///
+/// # Examples
+///
+/// ```
+/// use routerify::Deprecation;
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// let deprecation = Deprecation::new(UNIX_EPOCH + Duration::from_secs(1_688_169_599))
+///     .with_sunset(UNIX_EPOCH + Duration::from_secs(1_719_791_999))
+///     .with_link("https://example.com/docs/deprecations/v1");
+///
+/// assert_eq!(deprecation.link(), Some("https://example.com/docs/deprecations/v1"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Deprecation {
+    since: SystemTime,
+    sunset: Option<SystemTime>,
+    link: Option<String>,
+}
+
+impl Deprecation {
+    /// Creates a new `Deprecation` instance with the time the route is deprecated since.
+    pub fn new(since: SystemTime) -> Deprecation {
+        Deprecation {
+            since,
+            sunset: None,
+            link: None,
+        }
+    }
+
+    /// Sets the time after which the route is expected to stop responding.
+    pub fn with_sunset(mut self, sunset: SystemTime) -> Self {
+        self.sunset = Some(sunset);
+        self
+    }
+
+    /// Sets the link of a document about the deprecation e.g. the migration guide.
+    pub fn with_link<L: Into<String>>(mut self, link: L) -> Self {
+        self.link = Some(link.into());
+        self
+    }
+
+    /// Returns the time the route is deprecated since.
+    pub fn since(&self) -> SystemTime {
+        self.since
+    }
+
+    /// Returns the time after which the route is expected to stop responding.
+    pub fn sunset(&self) -> Option<SystemTime> {
+        self.sunset
+    }
+
+    /// Returns the link of a document about the deprecation.
+    pub fn link(&self) -> Option<&str> {
+        self.link.as_deref()
+    }
+
+    pub(crate) fn apply_headers(&self, headers: &mut HeaderMap) {
+        let since = self
+            .since
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        insert_if_absent(headers, HeaderName::from_static("deprecation"), format!("@{}", since));
+
+        if let Some(sunset) = self.sunset {
+            insert_if_absent(headers, HeaderName::from_static("sunset"), helpers::http_date(sunset));
+        }
+
+        if let Some(ref link) = self.link {
+            if let Ok(val) = HeaderValue::from_str(&format!("<{}>; rel=\"deprecation\"", link)) {
+                headers.append(header::LINK, val);
+            }
+        }
+    }
+}
+
+fn insert_if_absent(headers: &mut HeaderMap, name: HeaderName, val: String) {
+    if headers.contains_key(&name) {
+        return;
+    }
+    if let Ok(val) = HeaderValue::from_str(&val) {
+        headers.insert(name, val);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn applies_deprecation_headers() {
+        let deprecation = Deprecation::new(UNIX_EPOCH + Duration::from_secs(1_688_169_599))
+            .with_sunset(UNIX_EPOCH + Duration::from_secs(1_719_791_999))
+            .with_link("https://example.com/deprecations");
+
+        let mut headers = HeaderMap::new();
+        headers.insert("sunset", HeaderValue::from_static("Wed, 31 Dec 2025 23:59:59 GMT"));
+        deprecation.apply_headers(&mut headers);
+
+        assert_eq!(headers["deprecation"], "@1688169599");
+        assert_eq!(headers["sunset"], "Wed, 31 Dec 2025 23:59:59 GMT");
+        assert_eq!(
+            headers[header::LINK],
+            "<https://example.com/deprecations>; rel=\"deprecation\""
+        );
+    }
+}
@@ -0,0 +1,45 @@
+use super::RequestInfo;
+use std::time::{Duration, Instant};
+
+/// The context of a failed request which is passed to the error handler added by the
+/// [`RouterBuilder::err_handler_with_ctx`](./struct.RouterBuilder.html#method.err_handler_with_ctx) method.
+///
+/// Besides the [request info](./struct.RequestInfo.html), it gives access to the request context, i.e. the values put
+/// by the [`RequestExt::set_context`](./ext/trait.RequestExt.html#tymethod.set_context) method e.g. the request id or the
+/// authenticated [`Principal`](./struct.Principal.html), and to the time it took the request to fail.
+#[derive(Debug, Clone)]
+pub struct ErrorContext {
+    req_info: RequestInfo,
+    failed_at: Instant,
+}
+
+impl ErrorContext {
+    pub(crate) fn new(req_info: RequestInfo) -> Self {
+        ErrorContext {
+            req_info,
+            failed_at: Instant::now(),
+        }
+    }
+
+    /// Returns a value of the request context which was set by the middlewares or the route handler before the
+    /// failure.
+    pub fn get<T: Send + Sync + Clone + 'static>(&self) -> Option<T> {
+        self.req_info.context::<T>()
+    }
+
+    /// Access data which was shared by the [`RouterBuilder`](./struct.RouterBuilder.html) method
+    /// [`data`](./struct.RouterBuilder.html#method.data).
+    pub fn data<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.req_info.data::<T>()
+    }
+
+    /// Returns the info of the failed request e.g. the headers, the method and the matched route.
+    pub fn req_info(&self) -> &RequestInfo {
+        &self.req_info
+    }
+
+    /// Returns the time from receiving the request to its failure.
+    pub fn elapsed(&self) -> Duration {
+        self.failed_at.saturating_duration_since(self.req_info.received_at())
+    }
+}
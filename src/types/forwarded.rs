@@ -0,0 +1,20 @@
+use hyper::{Body, Request, Response};
+
+// The number of times a request can be forwarded before it's answered as a loop.
+pub(crate) const MAX_FORWARDS: usize = 8;
+
+// A request which is forwarded to another path of the router. It's carried by the placeholder response of the route
+// handler, which the router returns as is without running the post middlewares, so the request service can match the
+// request again.
+pub(crate) struct Forwarded {
+    pub(crate) req: Request<Body>,
+    pub(crate) path: String,
+}
+
+impl Forwarded {
+    pub(crate) fn into_response<B: Default>(req: Request<Body>, path: String) -> Response<B> {
+        let mut res = Response::new(B::default());
+        res.extensions_mut().insert(Forwarded { req, path });
+        res
+    }
+}
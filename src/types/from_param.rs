@@ -0,0 +1,74 @@
+use std::convert::Infallible;
+
+/// A trait to convert a route parameter value into a typed value.
+///
+/// It's used by the [`RequestExt::param_typed`](./ext/trait.RequestExt.html#tymethod.param_typed) method and by the
+/// [`RouterBuilder::typed_param`](./struct.RouterBuilder.html#method.typed_param) method, which makes a route match only
+/// if its parameter can be converted.
+///
+/// # Examples
+///
+/// ```
+/// use routerify::FromParam;
+///
+/// enum OrderStatus {
+///     Pending,
+///     Shipped,
+/// }
+///
+/// impl FromParam for OrderStatus {
+///     type Error = String;
+///
+///     fn from_param(param: &str) -> Result<Self, Self::Error> {
+///         match param {
+///             "pending" => Ok(OrderStatus::Pending),
+///             "shipped" => Ok(OrderStatus::Shipped),
+///             _ => Err(format!("Unknown order status: {}", param)),
+///         }
+///     }
+/// }
+/// ```
+pub trait FromParam: Sized {
+    /// The error returned when the conversion fails.
+    type Error;
+
+    /// Converts the route parameter value.
+    fn from_param(param: &str) -> Result<Self, Self::Error>;
+}
+
+impl FromParam for String {
+    type Error = Infallible;
+
+    fn from_param(param: &str) -> Result<Self, Self::Error> {
+        Ok(param.to_owned())
+    }
+}
+
+macro_rules! impl_from_param_via_from_str {
+    ($($ty:ty),*) => {
+        $(
+            impl FromParam for $ty {
+                type Error = <$ty as std::str::FromStr>::Err;
+
+                fn from_param(param: &str) -> Result<Self, Self::Error> {
+                    param.parse::<$ty>()
+                }
+            }
+        )*
+    };
+}
+
+impl_from_param_via_from_str!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize, f32, f64, bool, char);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_primitive_params() {
+        assert_eq!(u32::from_param("42"), Ok(42));
+        assert!(u32::from_param("-42").is_err());
+        assert_eq!(bool::from_param("true"), Ok(true));
+        assert_eq!(String::from_param("abc"), Ok("abc".to_owned()));
+    }
+}
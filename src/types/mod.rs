@@ -1,9 +1,30 @@
+pub use connection_info::{ConnectionInfo, TlsInfo};
+pub use deadline::Deadline;
+pub use deprecation::Deprecation;
+pub use error_context::ErrorContext;
+pub(crate) use forwarded::{Forwarded, MAX_FORWARDS};
+pub use from_param::FromParam;
+pub use principal::Principal;
+pub use problem_details::ProblemDetails;
+pub use redaction::Redaction;
+pub(crate) use redaction::REDACTED;
 pub(crate) use request_context::RequestContext;
 pub use request_info::RequestInfo;
+pub use request_memory::RequestMemory;
 pub(crate) use request_meta::RequestMeta;
 pub use route_params::RouteParams;
 
+mod connection_info;
+mod deadline;
+mod deprecation;
+mod error_context;
+mod forwarded;
+mod from_param;
+mod principal;
+mod problem_details;
+mod redaction;
 mod request_context;
 mod request_info;
+mod request_memory;
 mod request_meta;
 mod route_params;
@@ -0,0 +1,65 @@
+/// Represents the authenticated identity of a request.
+///
+/// It's the coordination point between the authentication middlewares and the subsystems which act on the identity
+/// e.g. the [throttle](./middleware/throttle/index.html) middleware. An authentication middleware puts it into the
+/// request context and the other subsystems read it from there.
+///
+/// # Examples
+///
+/// ```
+/// use routerify::{Middleware, Principal, Router};
+/// use routerify::prelude::*;
+/// use hyper::{Body, Request};
+/// use std::convert::Infallible;
+///
+/// # fn run() -> Router<Body, Infallible> {
+/// let router = Router::builder()
+///     .middleware(Middleware::pre(|req: Request<Body>| async move {
+///         if req.headers().contains_key("authorization") {
+///             // Verify the credentials.
+///             req.set_context(Principal::new("alice").with_role("admin"));
+///         }
+///         Ok(req)
+///     }))
+///     .build()
+///     .unwrap();
+/// # router
+/// # }
+/// # run();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Principal {
+    id: String,
+    roles: Vec<String>,
+}
+
+impl Principal {
+    /// Creates a new `Principal` instance with the identifier of the authenticated user or client.
+    pub fn new<I: Into<String>>(id: I) -> Principal {
+        Principal {
+            id: id.into(),
+            roles: Vec::new(),
+        }
+    }
+
+    /// Adds a role to the principal.
+    pub fn with_role<R: Into<String>>(mut self, role: R) -> Self {
+        self.roles.push(role.into());
+        self
+    }
+
+    /// Returns the identifier of the principal.
+    pub fn id(&self) -> &str {
+        self.id.as_str()
+    }
+
+    /// Returns the roles of the principal.
+    pub fn roles(&self) -> &[String] {
+        self.roles.as_slice()
+    }
+
+    /// Checks if the principal has the specified role.
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.iter().any(|r| r == role)
+    }
+}
@@ -0,0 +1,189 @@
+use crate::helpers;
+use hyper::header::{self, HeaderValue};
+use hyper::{Body, Response, StatusCode};
+
+const MEMBERS: [&str; 5] = ["type", "title", "status", "detail", "instance"];
+
+/// A problem details object of RFC 7807, which is sent as an `application/problem+json` response.
+///
+/// It can be attached to an [`HttpError`](./struct.HttpError.html) by converting it into one, so the default error
+/// handler responds with it once the [`problem_details`](./struct.RouterBuilder.html#method.problem_details) option is
+/// enabled. It can also be turned into a response directly by the [`into_response`](#method.into_response) method.
+///
+/// # Examples
+///
+/// ```
+/// use routerify::{HttpError, ProblemDetails};
+/// use hyper::StatusCode;
+///
+/// let problem = ProblemDetails::new(StatusCode::FORBIDDEN)
+///     .with_type("https://example.com/probs/out-of-credit")
+///     .with_title("You do not have enough credit.")
+///     .with_detail("Your current balance is 30, but that costs 50.")
+///     .with_json_extension("balance", "30")
+///     .with_extension("currency", "EUR");
+///
+/// assert_eq!(
+///     problem.to_json(),
+///     "{\"type\":\"https://example.com/probs/out-of-credit\",\"title\":\"You do not have enough credit.\",\
+///      \"status\":403,\"detail\":\"Your current balance is 30, but that costs 50.\",\"balance\":30,\"currency\":\"EUR\"}"
+/// );
+///
+/// let err = HttpError::from(problem);
+/// assert_eq!(err.status(), StatusCode::FORBIDDEN);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProblemDetails {
+    status: StatusCode,
+    type_uri: Option<String>,
+    title: String,
+    detail: Option<String>,
+    instance: Option<String>,
+    // The extension members with their values as JSON texts.
+    extensions: Vec<(String, String)>,
+}
+
+impl ProblemDetails {
+    /// Creates a new problem with the status code, whose title defaults to the reason phrase of the status code and
+    /// type to `about:blank`.
+    pub fn new(status: StatusCode) -> ProblemDetails {
+        ProblemDetails {
+            status,
+            type_uri: None,
+            title: status.canonical_reason().unwrap_or_default().to_owned(),
+            detail: None,
+            instance: None,
+            extensions: Vec::new(),
+        }
+    }
+
+    /// Sets the URI reference which identifies the problem type.
+    pub fn with_type<T: Into<String>>(mut self, type_uri: T) -> Self {
+        self.type_uri = Some(type_uri.into());
+        self
+    }
+
+    /// Sets the short summary of the problem type.
+    pub fn with_title<T: Into<String>>(mut self, title: T) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    /// Sets the explanation specific to this occurrence of the problem.
+    pub fn with_detail<D: Into<String>>(mut self, detail: D) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    /// Sets the URI reference which identifies this occurrence of the problem.
+    pub fn with_instance<I: Into<String>>(mut self, instance: I) -> Self {
+        self.instance = Some(instance.into());
+        self
+    }
+
+    /// Adds an extension member with a string value. The standard members can't be overridden.
+    pub fn with_extension<K: Into<String>, V: AsRef<str>>(self, key: K, val: V) -> Self {
+        let val = helpers::json_str(val.as_ref());
+        self.with_json_extension(key, val)
+    }
+
+    /// Adds an extension member whose value is a JSON text e.g. `30`, `[1, 2]` or `{"a": true}`. The text isn't
+    /// validated. The standard members can't be overridden.
+    pub fn with_json_extension<K: Into<String>, V: Into<String>>(mut self, key: K, json: V) -> Self {
+        let key = key.into();
+        if !MEMBERS.contains(&key.as_str()) {
+            self.extensions.retain(|(other, _)| *other != key);
+            self.extensions.push((key, json.into()));
+        }
+        self
+    }
+
+    /// Returns the status code of the problem.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Returns the URI reference which identifies the problem type.
+    pub fn type_uri(&self) -> &str {
+        self.type_uri.as_deref().unwrap_or("about:blank")
+    }
+
+    /// Returns the short summary of the problem type.
+    pub fn title(&self) -> &str {
+        self.title.as_str()
+    }
+
+    /// Returns the explanation specific to this occurrence of the problem.
+    pub fn detail(&self) -> Option<&str> {
+        self.detail.as_deref()
+    }
+
+    /// Returns the URI reference which identifies this occurrence of the problem.
+    pub fn instance(&self) -> Option<&str> {
+        self.instance.as_deref()
+    }
+
+    /// Returns the JSON text of an extension member.
+    pub fn extension(&self, key: &str) -> Option<&str> {
+        self.extensions
+            .iter()
+            .find(|(other, _)| other == key)
+            .map(|(_, json)| json.as_str())
+    }
+
+    /// Serializes the problem as a JSON object. The `type` member is left out if it's `about:blank`.
+    pub fn to_json(&self) -> String {
+        let mut members = Vec::new();
+        if let Some(ref type_uri) = self.type_uri {
+            members.push(format!("\"type\":{}", helpers::json_str(type_uri)));
+        }
+        members.push(format!("\"title\":{}", helpers::json_str(&self.title)));
+        members.push(format!("\"status\":{}", self.status.as_u16()));
+        if let Some(ref detail) = self.detail {
+            members.push(format!("\"detail\":{}", helpers::json_str(detail)));
+        }
+        if let Some(ref instance) = self.instance {
+            members.push(format!("\"instance\":{}", helpers::json_str(instance)));
+        }
+        for (key, json) in self.extensions.iter() {
+            members.push(format!("{}:{}", helpers::json_str(key), json));
+        }
+
+        format!("{{{}}}", members.join(","))
+    }
+
+    /// Creates an `application/problem+json` response with the status code of the problem.
+    pub fn into_response(self) -> Response<Body> {
+        let mut res = Response::new(Body::from(self.to_json()));
+        *res.status_mut() = self.status;
+        res.headers_mut().insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/problem+json"),
+        );
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serializes_problems() {
+        let problem = ProblemDetails::new(StatusCode::NOT_FOUND);
+        assert_eq!(problem.type_uri(), "about:blank");
+        assert_eq!(problem.to_json(), r#"{"title":"Not Found","status":404}"#);
+
+        let problem = ProblemDetails::new(StatusCode::CONFLICT)
+            .with_detail("The \"name\" is taken")
+            .with_instance("urn:request-id:abc")
+            .with_json_extension("status", "200")
+            .with_extension("field", "old")
+            .with_extension("field", "name");
+        assert_eq!(problem.extension("field"), Some(r#""name""#));
+        assert_eq!(
+            problem.to_json(),
+            r#"{"title":"Conflict","status":409,"detail":"The \"name\" is taken","instance":"urn:request-id:abc","field":"name"}"#
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use crate::types::RouteParams;
+use hyper::header::{HeaderName, HeaderValue};
+use hyper::Uri;
+use percent_encoding::percent_decode_str;
+use std::borrow::Cow;
+
+pub(crate) const REDACTED: &str = "[REDACTED]";
+
+/// The redaction rules of the secrets in the request paths, queries and headers, which keep them out of the logs.
+///
+/// The rules are shared as a [data](./struct.RouterBuilder.html#method.data) entry, and are applied by the
+/// [`audit`](./middleware/audit/index.html) events, the [`otel`](./middleware/otel/index.html) spans and the detailed
+/// error pages of the [`debug_errors`](./struct.RouterBuilder.html#method.debug_errors) option. The rules of the
+/// innermost scope of the request path are used, and without any the `Authorization`, `Proxy-Authorization`, `Cookie`
+/// and `Set-Cookie` headers are redacted.
+///
+/// A redacted parameter is replaced with `[REDACTED]`, both as a route parameter, including the path segment it's
+/// matched from, and as a query parameter.
+///
+/// # Examples
+///
+/// ```
+/// use routerify::{Redaction, Router};
+/// use hyper::{Response, Body};
+///
+/// # fn run() -> Router<Body, routerify::Error> {
+/// let router = Router::builder()
+///     .data(Redaction::new().redact_params(["token", "password"]).redact_headers(["x-api-key"]))
+///     .post("/invites/:token", |_| async move { Ok(Response::new(Body::from("Accepted"))) })
+///     .build()
+///     .unwrap();
+/// # router
+/// # }
+/// # run();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redaction {
+    params: Vec<String>,
+    headers: Vec<HeaderName>,
+}
+
+impl Default for Redaction {
+    fn default() -> Self {
+        Redaction::new()
+    }
+}
+
+impl Redaction {
+    /// Creates new rules which redact the `Authorization`, `Proxy-Authorization`, `Cookie` and `Set-Cookie` headers.
+    pub fn new() -> Self {
+        Redaction {
+            params: Vec::new(),
+            headers: vec![
+                hyper::header::AUTHORIZATION,
+                hyper::header::PROXY_AUTHORIZATION,
+                hyper::header::COOKIE,
+                hyper::header::SET_COOKIE,
+            ],
+        }
+    }
+
+    /// Redacts the route parameters and the query parameters with the names.
+    pub fn redact_params<I, P>(mut self, params: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<String>,
+    {
+        self.params.extend(params.into_iter().map(Into::into));
+        self
+    }
+
+    /// Redacts the headers with the names. The invalid header names are ignored.
+    pub fn redact_headers<I, H>(mut self, headers: I) -> Self
+    where
+        I: IntoIterator<Item = H>,
+        H: AsRef<str>,
+    {
+        self.headers.extend(
+            headers
+                .into_iter()
+                .filter_map(|name| HeaderName::from_bytes(name.as_ref().as_bytes()).ok()),
+        );
+        self
+    }
+
+    /// Checks if the route or the query parameter is redacted.
+    pub fn is_param_redacted(&self, name: &str) -> bool {
+        self.params.iter().any(|param| param == name)
+    }
+
+    /// Checks if the header is redacted.
+    pub fn is_header_redacted(&self, name: &HeaderName) -> bool {
+        self.headers.contains(name)
+    }
+
+    /// Returns the value of the header to log, which is `[REDACTED]` if the header is redacted.
+    pub fn header_value<'a>(&self, name: &HeaderName, val: &'a HeaderValue) -> Cow<'a, str> {
+        if self.is_header_redacted(name) {
+            Cow::Borrowed(REDACTED)
+        } else {
+            String::from_utf8_lossy(val.as_bytes())
+        }
+    }
+
+    /// Returns the path and the query of the URI to log, with the redacted route parameters of the matched route and the
+    /// redacted query parameters replaced.
+    pub fn redact_uri(&self, uri: &Uri, params: Option<&RouteParams>) -> String {
+        let path = self.redact_path(uri.path(), params);
+        match uri.query() {
+            Some(query) => format!("{}?{}", path, self.redact_query(query)),
+            None => path,
+        }
+    }
+
+    // Replaces the path segments which contain the value of a redacted route parameter.
+    pub(crate) fn redact_path(&self, path: &str, params: Option<&RouteParams>) -> String {
+        let secrets = params
+            .map(|params| {
+                params
+                    .iter()
+                    .filter(|(name, val)| !val.is_empty() && self.is_param_redacted(name))
+                    .map(|(_, val)| val.as_str())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        if secrets.is_empty() {
+            return path.to_owned();
+        }
+
+        path.split('/')
+            .map(|segment| {
+                let decoded = percent_decode_str(segment).decode_utf8_lossy();
+                if secrets.iter().any(|secret| decoded.contains(secret)) {
+                    REDACTED
+                } else {
+                    segment
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    fn redact_query(&self, query: &str) -> String {
+        query
+            .split('&')
+            .map(|pair| match pair.split_once('=') {
+                Some((name, _)) if self.is_param_redacted(&percent_decode_str(name).decode_utf8_lossy()) => {
+                    format!("{}={}", name, REDACTED)
+                }
+                _ => pair.to_owned(),
+            })
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_uris_and_headers() {
+        let redaction = Redaction::new()
+            .redact_params(["token", "password"])
+            .redact_headers(["X-Api-Key"]);
+
+        let mut params = RouteParams::new();
+        params.set("token", "s3cr et");
+        params.set("id", "1");
+        let uri = "/users/1/invites/s3cr%20et?password=hunter2&page=2"
+            .parse::<Uri>()
+            .unwrap();
+        assert_eq!(
+            redaction.redact_uri(&uri, Some(&params)),
+            "/users/1/invites/[REDACTED]?password=[REDACTED]&page=2"
+        );
+        assert_eq!(redaction.redact_uri(&"/users/1".parse().unwrap(), None), "/users/1");
+
+        let val = HeaderValue::from_static("secret");
+        assert_eq!(
+            redaction.header_value(&HeaderName::from_static("x-api-key"), &val),
+            REDACTED
+        );
+        assert_eq!(redaction.header_value(&hyper::header::COOKIE, &val), REDACTED);
+        assert_eq!(redaction.header_value(&hyper::header::ACCEPT, &val), "secret");
+    }
+}
@@ -1,4 +1,6 @@
 use crate::data_map::DataMap;
+use crate::types::RequestMemory;
+use std::any::TypeId;
 use std::sync::{Arc, Mutex};
 
 #[derive(Clone)]
@@ -21,7 +23,13 @@ impl RequestContext {
     }
 
     pub(crate) fn set<T: Send + Sync + Clone + 'static>(&self, val: T) {
-        self.inner.lock().unwrap().insert(val);
+        let mut data_map = self.inner.lock().unwrap();
+        data_map.insert(val);
+        if TypeId::of::<T>() != TypeId::of::<RequestMemory>() {
+            if let Some(memory) = data_map.get::<RequestMemory>() {
+                memory.record_context_entry(std::mem::size_of::<T>());
+            }
+        }
     }
 
     pub(crate) fn get<T: Send + Sync + Clone + 'static>(&self) -> Option<T> {
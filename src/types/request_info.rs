@@ -1,8 +1,10 @@
-use super::RequestContext;
-use crate::data_map::SharedDataMap;
+use super::{RequestContext, RequestMeta, RouteParams};
+use crate::data_map::{SharedConfigMaps, SharedDataMap};
 use hyper::{Body, HeaderMap, Method, Request, Uri, Version};
 use std::fmt::{self, Debug, Formatter};
 use std::sync::Arc;
+use std::time::Instant;
+use tokio_util::sync::CancellationToken;
 
 /// Represents some information for the incoming request.
 ///
@@ -11,8 +13,11 @@ use std::sync::Arc;
 #[derive(Clone)]
 pub struct RequestInfo {
     pub(crate) req_info_inner: Arc<RequestInfoInner>,
-    pub(crate) shared_data_maps: Option<Vec<SharedDataMap>>,
+    pub(crate) shared_data_maps: Option<Arc<[SharedDataMap]>>,
+    pub(crate) scope_configs: Option<SharedConfigMaps>,
     pub(crate) context: RequestContext,
+    pub(crate) route_path: Option<String>,
+    pub(crate) route_params: Option<RouteParams>,
 }
 
 #[derive(Debug)]
@@ -21,6 +26,8 @@ pub(crate) struct RequestInfoInner {
     method: Method,
     uri: Uri,
     version: Version,
+    cancellation_token: Option<CancellationToken>,
+    received_at: Instant,
 }
 
 impl RequestInfo {
@@ -30,12 +37,21 @@ impl RequestInfo {
             method: req.method().clone(),
             uri: req.uri().clone(),
             version: req.version(),
+            cancellation_token: req
+                .extensions()
+                .get::<RequestMeta>()
+                .and_then(|meta| meta.cancellation_token())
+                .cloned(),
+            received_at: Instant::now(),
         };
 
         RequestInfo {
             req_info_inner: Arc::new(inner),
             shared_data_maps: None,
+            scope_configs: None,
             context: ctx,
+            route_path: None,
+            route_params: None,
         }
     }
 
@@ -59,6 +75,32 @@ impl RequestInfo {
         self.req_info_inner.version
     }
 
+    /// Returns the path of the matched route e.g. `/users/:userId`, if any.
+    pub fn route_path(&self) -> Option<&str> {
+        self.route_path.as_deref()
+    }
+
+    /// Returns the route parameters of the matched route, if any.
+    pub fn params(&self) -> Option<&RouteParams> {
+        self.route_params.as_ref()
+    }
+
+    /// Returns the instant when the request was received. It can be used to measure the request duration.
+    pub fn received_at(&self) -> Instant {
+        self.req_info_inner.received_at
+    }
+
+    /// Checks if the client disconnected before the response was sent.
+    ///
+    /// Please refer to the [`RequestExt::cancellation_token`](./ext/trait.RequestExt.html#tymethod.cancellation_token) method for more info.
+    pub fn is_cancelled(&self) -> bool {
+        self.req_info_inner
+            .cancellation_token
+            .as_ref()
+            .map(|token| token.is_cancelled())
+            .unwrap_or(false)
+    }
+
     /// Access data which was shared by the [`RouterBuilder`](./struct.RouterBuilder.html) method
     /// [`data`](./struct.RouterBuilder.html#method.data).
     ///
@@ -75,6 +117,14 @@ impl RequestInfo {
         None
     }
 
+    /// Access the config of the innermost scope of the request which was set by the
+    /// [`RouterBuilder::config`](./struct.RouterBuilder.html#method.config) method.
+    pub fn scope_config<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.scope_configs
+            .as_ref()
+            .and_then(|scope_configs| scope_configs.get::<T>())
+    }
+
     /// Access data from the request context.
     ///
     /// # Examples
@@ -0,0 +1,139 @@
+use crate::body::BufferedBody;
+use crate::types::{RequestContext, RouteParams};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// The memory attributed to a request: the request bodies buffered by the router, the route params and the request
+/// context entries.
+///
+/// It's put into the request context by the [`memory_stats`](./middleware/fn.memory_stats.html) pre middleware, so the
+/// numbers can be read by the route handler, the post middlewares with the [`RequestInfo`](./struct.RequestInfo.html)
+/// and the error handler, e.g. to record them as metrics for the capacity planning or to log the requests which hold
+/// unexpectedly much memory. The requests without the middleware aren't instrumented.
+///
+/// The body bytes are counted when a body is buffered by the [`RequestBodyExt`](./ext/trait.RequestBodyExt.html)
+/// methods or the [`uploads`](./uploads/index.html). The size of a context entry is the inline size of its type, so
+/// the heap data it owns isn't counted.
+///
+/// # Examples
+///
+/// ```
+/// use routerify::{Middleware, RequestInfo, RequestMemory, Router};
+/// use routerify::middleware::memory_stats;
+/// use hyper::{Response, Body};
+/// use std::convert::Infallible;
+///
+/// # fn run() -> Router<Body, Infallible> {
+/// let router = Router::builder()
+///     .middleware(memory_stats())
+///     .middleware(Middleware::post_with_info(|res: Response<Body>, req_info: RequestInfo| async move {
+///         if let Some(memory) = req_info.context::<RequestMemory>() {
+///             if memory.total_bytes() > 1024 * 1024 {
+///                 println!("{} {} held {} bytes", req_info.method(), req_info.uri(), memory.total_bytes());
+///             }
+///         }
+///         Ok(res)
+///     }))
+///     .build()
+///     .unwrap();
+/// # router
+/// # }
+/// # run();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RequestMemory {
+    inner: Arc<MemoryInner>,
+}
+
+#[derive(Debug, Default)]
+struct MemoryInner {
+    body_bytes: AtomicU64,
+    spilled_bytes: AtomicU64,
+    param_bytes: AtomicU64,
+    context_entries: AtomicU64,
+    context_bytes: AtomicU64,
+}
+
+impl RequestMemory {
+    pub(crate) fn new() -> RequestMemory {
+        RequestMemory::default()
+    }
+
+    /// Returns the number of the request body bytes buffered in memory.
+    pub fn body_bytes(&self) -> u64 {
+        self.inner.body_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of the request body bytes spilled into temporary files, which aren't held in memory.
+    pub fn spilled_bytes(&self) -> u64 {
+        self.inner.spilled_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of bytes of the names and the values of the route params.
+    pub fn param_bytes(&self) -> u64 {
+        self.inner.param_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of the entries put into the request context, excluding this one.
+    pub fn context_entries(&self) -> u64 {
+        self.inner.context_entries.load(Ordering::Relaxed)
+    }
+
+    /// Returns the total inline size of the entries put into the request context.
+    pub fn context_bytes(&self) -> u64 {
+        self.inner.context_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of bytes held in memory, which is the sum of the body, the param and the context bytes.
+    pub fn total_bytes(&self) -> u64 {
+        self.body_bytes() + self.param_bytes() + self.context_bytes()
+    }
+
+    pub(crate) fn record_body(&self, body: &BufferedBody) {
+        let counter = match body.is_in_memory() {
+            true => &self.inner.body_bytes,
+            false => &self.inner.spilled_bytes,
+        };
+        counter.fetch_add(body.len(), Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_params(&self, params: &RouteParams) {
+        let bytes = params.iter().map(|(name, val)| name.len() + val.len()).sum::<usize>();
+        self.inner.param_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_context_entry(&self, size: usize) {
+        self.inner.context_entries.fetch_add(1, Ordering::Relaxed);
+        self.inner.context_bytes.fetch_add(size as u64, Ordering::Relaxed);
+    }
+
+    // Returns the instrumentation of the request, if it's enabled.
+    pub(crate) fn of(ext: &http::Extensions) -> Option<RequestMemory> {
+        ext.get::<RequestContext>().and_then(|ctx| ctx.get::<RequestMemory>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_request_memory() {
+        let ctx = RequestContext::new();
+        ctx.set(RequestMemory::new());
+        ctx.set(42u64);
+        ctx.set("tenant".to_owned());
+
+        let memory = ctx.get::<RequestMemory>().unwrap();
+        let mut params = RouteParams::new();
+        params.set("id", "1234");
+        memory.record_params(&params);
+        memory.record_body(&BufferedBody::from(hyper::body::Bytes::from("hello")));
+
+        assert_eq!(memory.context_entries(), 2);
+        assert_eq!(memory.context_bytes(), 8 + std::mem::size_of::<String>() as u64);
+        assert_eq!(memory.param_bytes(), 6);
+        assert_eq!(memory.body_bytes(), 5);
+        assert_eq!(memory.total_bytes(), 19 + std::mem::size_of::<String>() as u64);
+    }
+}
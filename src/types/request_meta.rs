@@ -1,10 +1,18 @@
 use crate::types::route_params::RouteParams;
+use crate::types::ConnectionInfo;
 use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
 
 #[derive(Debug, Clone)]
 pub(crate) struct RequestMeta {
     route_params: Option<RouteParams>,
     remote_addr: Option<SocketAddr>,
+    cancellation_token: Option<CancellationToken>,
+    connection_info: Option<ConnectionInfo>,
+    base_path: Option<Arc<str>>,
+    // Set once the route params are modified e.g. by a pre middleware, so the route keeps them.
+    is_route_params_modified: bool,
 }
 
 impl RequestMeta {
@@ -12,6 +20,10 @@ impl RequestMeta {
         RequestMeta {
             route_params: Some(route_params),
             remote_addr: None,
+            cancellation_token: None,
+            connection_info: None,
+            base_path: None,
+            is_route_params_modified: false,
         }
     }
 
@@ -19,22 +31,81 @@ impl RequestMeta {
         RequestMeta {
             route_params: None,
             remote_addr: Some(remote_addr),
+            cancellation_token: None,
+            connection_info: None,
+            base_path: None,
+            is_route_params_modified: false,
         }
     }
 
+    pub fn with_cancellation_token(mut self, cancellation_token: CancellationToken) -> RequestMeta {
+        self.cancellation_token = Some(cancellation_token);
+        self
+    }
+
     pub fn route_params(&self) -> Option<&RouteParams> {
         self.route_params.as_ref()
     }
 
+    pub fn route_params_mut(&mut self) -> &mut RouteParams {
+        self.is_route_params_modified = true;
+        self.route_params.get_or_insert_with(RouteParams::new)
+    }
+
+    pub fn is_route_params_modified(&self) -> bool {
+        self.is_route_params_modified
+    }
+
+    pub fn clear_route_params(&mut self) {
+        self.route_params = None;
+        self.is_route_params_modified = false;
+    }
+
     pub fn remote_addr(&self) -> Option<&SocketAddr> {
         self.remote_addr.as_ref()
     }
 
+    pub fn with_connection_info(mut self, connection_info: ConnectionInfo) -> RequestMeta {
+        self.connection_info = Some(connection_info);
+        self
+    }
+
+    pub fn connection_info(&self) -> Option<&ConnectionInfo> {
+        self.connection_info.as_ref()
+    }
+
+    pub fn cancellation_token(&self) -> Option<&CancellationToken> {
+        self.cancellation_token.as_ref()
+    }
+
+    pub fn with_base_path(mut self, base_path: Arc<str>) -> RequestMeta {
+        self.base_path = Some(base_path);
+        self
+    }
+
+    pub fn base_path(&self) -> Option<&str> {
+        self.base_path.as_deref()
+    }
+
     pub fn extend(&mut self, other_req_meta: RequestMeta) {
         if let Some(other_ra) = other_req_meta.remote_addr {
             self.remote_addr = Some(other_ra)
         }
 
+        if let Some(other_ct) = other_req_meta.cancellation_token {
+            self.cancellation_token = Some(other_ct)
+        }
+
+        if let Some(other_ci) = other_req_meta.connection_info {
+            self.connection_info = Some(other_ci)
+        }
+
+        if let Some(other_bp) = other_req_meta.base_path {
+            self.base_path = Some(other_bp)
+        }
+
+        self.is_route_params_modified |= other_req_meta.is_route_params_modified;
+
         if let Some(other_pm) = other_req_meta.route_params {
             if let Some(ref mut existing_pm) = self.route_params {
                 existing_pm.extend(other_pm);
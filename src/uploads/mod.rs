@@ -0,0 +1,333 @@
+//! Resumable chunked uploads, and the `multipart/form-data` file uploads.
+//!
+//! A client sends an upload in chunks, each one as a request with a `Content-Range: bytes <start>-<end>/<total>` header
+//! where the total may be `*` until the last chunk. The chunks are written into a pluggable
+//! [`ChunkStore`](./trait.ChunkStore.html) and once all the bytes are received, the completion callback gets the
+//! assembled upload.
+//!
+//! The responses are:
+//!
+//! * `202 Accepted` with a `Range: bytes=0-<last>` header of the received bytes while the upload is incomplete.
+//! * `201 Created` once the upload is complete and the callback succeeds.
+//! * `409 Conflict` with the same `Range` header if a chunk doesn't start where the received bytes end, so the client
+//!   can resume from there.
+//!
+//! A request with a `Content-Range: bytes */<total>` header and an empty body queries the progress of an upload e.g.
+//! after a connection loss.
+//!
+//! With the `multipart` feature, the [`upload`](../struct.RouterBuilder.html#method.upload) routes store the files of
+//! the `multipart/form-data` requests into a pluggable [`Storage`](./trait.Storage.html) instead, as configured by an
+//! [`UploadConfig`](./struct.UploadConfig.html).
+//!
+//! The [`FileChunkStore`](./struct.FileChunkStore.html) keeps the partial uploads on the disk, so it requires the `fs`
+//! feature.
+//!
+//! # Examples
+//!
+//! ```
+//! use routerify::{Router, RouteError};
+//! use hyper::Body;
+//!
+//! # #[cfg(feature = "fs")]
+//! # fn run() -> Router<Body, RouteError> {
+//! use routerify::uploads::{FileChunkStore, Uploads};
+//!
+//! let uploads = Uploads::new(FileChunkStore::new(std::env::temp_dir()), |upload| async move {
+//!     let path = format!("/var/data/{}", upload.upload_id());
+//!     tokio::fs::rename(upload.body().path().unwrap(), path).await.ok();
+//!     Ok(())
+//! })
+//! .max_chunk_size(8 * 1024 * 1024);
+//!
+//! let router = Router::builder()
+//!     .put("/uploads/:upload_id", move |req| {
+//!         let uploads = uploads.clone();
+//!         async move { Ok(uploads.handle(req).await?) }
+//!     })
+//!     .build()
+//!     .unwrap();
+//! # router
+//! # }
+//! # #[cfg(feature = "fs")]
+//! # run();
+//! ```
+
+#[cfg(feature = "multipart")]
+pub use self::multipart::{FileStream, LocalStorage, Storage, UploadConfig};
+#[cfg(feature = "fs")]
+pub use self::store::FileChunkStore;
+pub use self::store::{ChunkStore, StoreFuture};
+
+use crate::body::{self, BodyError};
+use crate::ext::RequestExt;
+use crate::{BufferedBody, HttpError, RequestMemory};
+use hyper::{header, Body, Request, Response, StatusCode};
+use std::fmt::{self, Debug, Formatter};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+#[cfg(feature = "multipart")]
+mod multipart;
+mod store;
+
+const DEFAULT_MAX_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+
+type CompleteHandler =
+    Arc<dyn Fn(CompletedUpload) -> Pin<Box<dyn Future<Output = Result<(), HttpError>> + Send>> + Send + Sync>;
+
+/// An upload whose bytes are all received, which is passed to the completion callback.
+#[derive(Debug)]
+pub struct CompletedUpload {
+    upload_id: String,
+    body: BufferedBody,
+}
+
+impl CompletedUpload {
+    /// Returns the id of the upload.
+    pub fn upload_id(&self) -> &str {
+        self.upload_id.as_str()
+    }
+
+    /// Returns the assembled content of the upload.
+    pub fn body(&self) -> &BufferedBody {
+        &self.body
+    }
+
+    /// Consumes the upload and returns its content.
+    pub fn into_body(self) -> BufferedBody {
+        self.body
+    }
+}
+
+/// The configuration of the chunked upload handler.
+///
+/// Please refer to the [module](./index.html) documentation for more info.
+#[derive(Clone)]
+pub struct Uploads {
+    store: Arc<dyn ChunkStore>,
+    on_complete: CompleteHandler,
+    max_chunk_size: usize,
+    param: String,
+}
+
+impl Uploads {
+    /// Creates a new handler which stores the chunks in the `store` and calls `on_complete` with every completed upload.
+    ///
+    /// An error returned by the callback is sent as the response of the last chunk.
+    pub fn new<S, F, R>(store: S, on_complete: F) -> Self
+    where
+        S: ChunkStore,
+        F: Fn(CompletedUpload) -> R + Send + Sync + 'static,
+        R: Future<Output = Result<(), HttpError>> + Send + 'static,
+    {
+        Uploads {
+            store: Arc::new(store),
+            on_complete: Arc::new(move |upload| Box::pin(on_complete(upload))),
+            max_chunk_size: DEFAULT_MAX_CHUNK_SIZE,
+            param: "upload_id".to_owned(),
+        }
+    }
+
+    /// Sets the maximum size of a chunk in bytes. Larger chunks are rejected with `413 Payload Too Large`.
+    /// Defaults to 16 MiB.
+    pub fn max_chunk_size(mut self, max_chunk_size: usize) -> Self {
+        self.max_chunk_size = max_chunk_size;
+        self
+    }
+
+    /// Sets the name of the route param holding the upload id. Defaults to `upload_id`.
+    ///
+    /// The upload id may only contain ASCII letters, digits, `-` and `_`.
+    pub fn param<P: Into<String>>(mut self, param: P) -> Self {
+        self.param = param.into();
+        self
+    }
+
+    /// Handles a request carrying a chunk of an upload, or querying its progress.
+    ///
+    /// It fails with [`HttpError`](../struct.HttpError.html)s of status `400 Bad Request` for an invalid upload id or
+    /// `Content-Range` header, `413 Payload Too Large` for a too large chunk and `416 Range Not Satisfiable` for a
+    /// chunk beyond the total size.
+    pub async fn handle(&self, req: Request<Body>) -> Result<Response<Body>, HttpError> {
+        let upload_id = req
+            .param(self.param.as_str())
+            .filter(|id| is_valid_upload_id(id))
+            .ok_or_else(|| HttpError::new(StatusCode::BAD_REQUEST, "Invalid upload id"))?
+            .clone();
+
+        let range = req
+            .headers()
+            .get(header::CONTENT_RANGE)
+            .and_then(|val| val.to_str().ok())
+            .and_then(ContentRange::parse)
+            .ok_or_else(|| HttpError::new(StatusCode::BAD_REQUEST, "Missing or invalid Content-Range header"))?;
+
+        let received = self.store.received(&upload_id).await.map_err(io_error)?;
+
+        let (start, end) = match range.chunk {
+            Some(chunk) => chunk,
+            None => return Ok(progress_response(StatusCode::ACCEPTED, received)),
+        };
+
+        if start != received {
+            return Ok(progress_response(StatusCode::CONFLICT, received));
+        }
+        if matches!(range.total, Some(total) if end >= total) {
+            return Err(HttpError::new(
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                "Chunk exceeds the total size of the upload",
+            ));
+        }
+
+        let len = end - start + 1;
+        if len > self.max_chunk_size as u64 {
+            return Err(too_large(self.max_chunk_size));
+        }
+
+        let memory = RequestMemory::of(req.extensions());
+        let chunk = body::buffer(req.into_body(), self.max_chunk_size, None)
+            .await
+            .map_err(|err| match err {
+                BodyError::TooLarge { .. } => too_large(self.max_chunk_size),
+                _ => HttpError::new(StatusCode::BAD_REQUEST, err.to_string()),
+            })?;
+        if let Some(memory) = memory {
+            memory.record_body(&chunk);
+        }
+        let chunk = chunk
+            .as_bytes()
+            .expect("A chunk within the limit is kept in memory")
+            .clone();
+        if chunk.len() as u64 != len {
+            return Err(HttpError::new(
+                StatusCode::BAD_REQUEST,
+                "Chunk length doesn't match the Content-Range header",
+            ));
+        }
+
+        self.store.write(&upload_id, start, chunk).await.map_err(io_error)?;
+
+        let received = end + 1;
+        if range.total != Some(received) {
+            return Ok(progress_response(StatusCode::ACCEPTED, received));
+        }
+
+        let body = self.store.take(&upload_id).await.map_err(io_error)?;
+        (self.on_complete)(CompletedUpload { upload_id, body }).await?;
+
+        Ok(Response::builder()
+            .status(StatusCode::CREATED)
+            .body(Body::empty())
+            .expect("Couldn't create the upload response"))
+    }
+}
+
+impl Debug for Uploads {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{{ max_chunk_size: {:?}, param: {:?} }}",
+            self.max_chunk_size, self.param
+        )
+    }
+}
+
+// A parsed `Content-Range` header of a chunk, with an inclusive byte range or none for a progress query.
+#[derive(Debug, PartialEq)]
+struct ContentRange {
+    chunk: Option<(u64, u64)>,
+    total: Option<u64>,
+}
+
+impl ContentRange {
+    fn parse(val: &str) -> Option<ContentRange> {
+        let (range, total) = val.trim().strip_prefix("bytes ")?.split_once('/')?;
+
+        let total = match total.trim() {
+            "*" => None,
+            total => Some(total.parse::<u64>().ok()?),
+        };
+
+        let chunk = match range.trim() {
+            "*" => None,
+            range => {
+                let (start, end) = range.split_once('-')?;
+                let (start, end) = (start.parse::<u64>().ok()?, end.parse::<u64>().ok()?);
+                if start > end {
+                    return None;
+                }
+                Some((start, end))
+            }
+        };
+
+        Some(ContentRange { chunk, total })
+    }
+}
+
+fn is_valid_upload_id(id: &str) -> bool {
+    !id.is_empty() && id.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+fn progress_response(status: StatusCode, received: u64) -> Response<Body> {
+    let mut builder = Response::builder().status(status);
+    if received > 0 {
+        builder = builder.header(header::RANGE, format!("bytes=0-{}", received - 1));
+    }
+    builder
+        .body(Body::empty())
+        .expect("Couldn't create the upload response")
+}
+
+fn too_large(limit: usize) -> HttpError {
+    HttpError::new(
+        StatusCode::PAYLOAD_TOO_LARGE,
+        format!("Chunk exceeds the limit of {} bytes", limit),
+    )
+}
+
+fn io_error(err: std::io::Error) -> HttpError {
+    HttpError::new(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        format!("Couldn't store the upload: {}", err),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_content_range() {
+        assert_eq!(
+            ContentRange::parse("bytes 0-99/1000"),
+            Some(ContentRange {
+                chunk: Some((0, 99)),
+                total: Some(1000)
+            })
+        );
+        assert_eq!(
+            ContentRange::parse("bytes 100-199/*"),
+            Some(ContentRange {
+                chunk: Some((100, 199)),
+                total: None
+            })
+        );
+        assert_eq!(
+            ContentRange::parse("bytes */1000"),
+            Some(ContentRange {
+                chunk: None,
+                total: Some(1000)
+            })
+        );
+        assert_eq!(ContentRange::parse("bytes 9-0/10"), None);
+        assert_eq!(ContentRange::parse("items 0-9/10"), None);
+    }
+
+    #[test]
+    fn validates_upload_ids() {
+        assert!(is_valid_upload_id("abc-123_X"));
+        assert!(!is_valid_upload_id(""));
+        assert!(!is_valid_upload_id("../etc"));
+    }
+}
@@ -0,0 +1,302 @@
+use super::{io_error, StoreFuture};
+use crate::helpers::{self, json_str};
+use crate::HttpError;
+use futures_core::Stream;
+use hyper::body::Bytes;
+use hyper::{header, Body, Request, Response, StatusCode};
+use std::fmt::{self, Debug, Formatter};
+use std::io;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::io::AsyncWriteExt;
+
+const KEY_LEN: usize = 16;
+
+/// The content of an uploaded file, which is streamed chunk by chunk into a [`Storage`](./trait.Storage.html).
+pub type FileStream<'a> = Pin<Box<dyn Stream<Item = io::Result<Bytes>> + Send + 'a>>;
+
+/// A storage of the files uploaded to the [`upload`](../struct.RouterBuilder.html#method.upload) routes.
+pub trait Storage: Send + Sync + 'static {
+    /// Writes the file under the key as it's streamed, and returns its location e.g. a path or a URL. An error of the
+    /// stream, e.g. once the file exceeds the size limit, must fail the write and discard the partial file.
+    fn put<'a>(&'a self, key: &'a str, content_type: &'a str, file: FileStream<'a>) -> StoreFuture<'a, String>;
+
+    /// Removes a stored file, once a later file of the same request is rejected.
+    fn remove<'a>(&'a self, key: &'a str) -> StoreFuture<'a, ()>;
+}
+
+/// A [`Storage`](./trait.Storage.html) which keeps the uploaded files in a directory on the local disk. The location of
+/// a file is its path.
+#[derive(Clone)]
+pub struct LocalStorage {
+    dir: PathBuf,
+}
+
+impl LocalStorage {
+    /// Creates a storage in the specified directory, which must exist.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        LocalStorage { dir: dir.into() }
+    }
+}
+
+impl Storage for LocalStorage {
+    fn put<'a>(&'a self, key: &'a str, _: &'a str, mut file: FileStream<'a>) -> StoreFuture<'a, String> {
+        Box::pin(async move {
+            let path = self.dir.join(key);
+            let mut out = tokio::fs::File::create(&path).await?;
+
+            let mut written = Ok(());
+            while let Some(chunk) = std::future::poll_fn(|cx| file.as_mut().poll_next(cx)).await {
+                written = match chunk {
+                    Ok(chunk) => out.write_all(&chunk).await,
+                    Err(err) => Err(err),
+                };
+                if written.is_err() {
+                    break;
+                }
+            }
+            if let Err(err) = written.and(out.flush().await) {
+                drop(out);
+                tokio::fs::remove_file(&path).await.ok();
+                return Err(err);
+            }
+
+            Ok(path.to_string_lossy().into_owned())
+        })
+    }
+
+    fn remove<'a>(&'a self, key: &'a str) -> StoreFuture<'a, ()> {
+        Box::pin(async move { tokio::fs::remove_file(self.dir.join(key)).await })
+    }
+}
+
+impl Debug for LocalStorage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{{ dir: {:?} }}", self.dir)
+    }
+}
+
+/// The configuration of an [`upload`](../struct.RouterBuilder.html#method.upload) route, which stores the files of a
+/// `multipart/form-data` request.
+///
+/// The files are streamed into the storage, and the route responds with `201 Created` and their metadata as JSON:
+///
+/// ```json
+/// {"files":[{"field":"avatar","filename":"me.png","content_type":"image/png","size":1024,"key":"…","location":"…"}]}
+/// ```
+///
+/// The form fields which aren't files are ignored.
+///
+/// The request is rejected with an [`HttpError`](../struct.HttpError.html) of status `415 Unsupported Media Type` if
+/// it isn't a multipart request or a file has a type which isn't allowed, `413 Payload Too Large` if a file exceeds the
+/// size limit and `400 Bad Request` if it's malformed or has no file. The files stored before a rejected one are
+/// removed.
+#[derive(Clone)]
+pub struct UploadConfig {
+    /// The maximum size of a file in bytes.
+    pub max_size: u64,
+    /// The allowed media types e.g. `image/png` or `image/*`. Any type is allowed if it's empty.
+    pub allowed_types: Vec<String>,
+    /// The storage of the files.
+    pub storage: Arc<dyn Storage>,
+}
+
+struct StoredFile {
+    field: Option<String>,
+    filename: String,
+    content_type: String,
+    size: u64,
+    key: String,
+    location: String,
+}
+
+impl UploadConfig {
+    /// Stores the files of a `multipart/form-data` request and responds with their metadata.
+    pub async fn handle(&self, req: Request<Body>) -> Result<Response<Body>, HttpError> {
+        let boundary = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|val| val.to_str().ok())
+            .and_then(|val| multer::parse_boundary(val).ok())
+            .ok_or_else(|| {
+                HttpError::new(
+                    StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                    "Expected a multipart/form-data body",
+                )
+            })?;
+        let mut multipart = multer::Multipart::new(req.into_body(), boundary);
+
+        let mut files = Vec::new();
+        let stored = loop {
+            let field = match multipart.next_field().await {
+                Ok(Some(field)) => field,
+                Ok(None) => break Ok(()),
+                Err(err) => break Err(HttpError::new(StatusCode::BAD_REQUEST, err.to_string())),
+            };
+            match self.store(field).await {
+                Ok(Some(file)) => files.push(file),
+                Ok(None) => {}
+                Err(err) => break Err(err),
+            }
+        };
+
+        if let Err(err) = stored {
+            for file in files {
+                self.storage.remove(&file.key).await.ok();
+            }
+            return Err(err);
+        }
+        if files.is_empty() {
+            return Err(HttpError::new(StatusCode::BAD_REQUEST, "No file is uploaded"));
+        }
+
+        let files = files
+            .iter()
+            .map(|file| {
+                format!(
+                    "{{\"field\":{},\"filename\":{},\"content_type\":{},\"size\":{},\"key\":{},\"location\":{}}}",
+                    file.field.as_deref().map(json_str).unwrap_or_else(|| "null".to_owned()),
+                    json_str(&file.filename),
+                    json_str(&file.content_type),
+                    file.size,
+                    json_str(&file.key),
+                    json_str(&file.location)
+                )
+            })
+            .collect::<Vec<_>>();
+        Ok(Response::builder()
+            .status(StatusCode::CREATED)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(format!("{{\"files\":[{}]}}", files.join(","))))
+            .expect("Couldn't create the upload response"))
+    }
+
+    async fn store(&self, field: multer::Field<'static>) -> Result<Option<StoredFile>, HttpError> {
+        let filename = match field.file_name() {
+            Some(filename) => filename.to_owned(),
+            None => return Ok(None),
+        };
+        let content_type = field
+            .content_type()
+            .map(|mime| mime.essence_str().to_ascii_lowercase())
+            .unwrap_or_else(|| "application/octet-stream".to_owned());
+        if !self.is_allowed(&content_type) {
+            return Err(HttpError::new(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                format!("The content type {} isn't allowed", content_type),
+            ));
+        }
+
+        let key = format!("{}{}", helpers::random_hex(KEY_LEN), extension(&filename));
+        let field_name = field.name().map(str::to_owned);
+        let size = Arc::new(AtomicU64::new(0));
+        let file = Box::pin(LimitedStream {
+            field,
+            size: size.clone(),
+            max_size: self.max_size,
+        });
+
+        match self.storage.put(&key, &content_type, file).await {
+            Ok(location) => Ok(Some(StoredFile {
+                field: field_name,
+                filename,
+                content_type,
+                size: size.load(Ordering::SeqCst),
+                key,
+                location,
+            })),
+            Err(_) if size.load(Ordering::SeqCst) > self.max_size => Err(HttpError::new(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!("File exceeds the limit of {} bytes", self.max_size),
+            )),
+            Err(err) if err.kind() == io::ErrorKind::InvalidData => {
+                Err(HttpError::new(StatusCode::BAD_REQUEST, err.to_string()))
+            }
+            Err(err) => Err(io_error(err)),
+        }
+    }
+
+    fn is_allowed(&self, content_type: &str) -> bool {
+        self.allowed_types.is_empty()
+            || self.allowed_types.iter().any(|allowed| {
+                let allowed = allowed.trim().to_ascii_lowercase();
+                match allowed.strip_suffix("/*") {
+                    Some(prefix) => prefix == "*" || content_type.split('/').next() == Some(prefix),
+                    None => allowed == content_type,
+                }
+            })
+    }
+}
+
+impl Debug for UploadConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{{ max_size: {:?}, allowed_types: {:?} }}",
+            self.max_size, self.allowed_types
+        )
+    }
+}
+
+// The extension of the uploaded file name, if it's safe to keep in the key e.g. `.png`.
+fn extension(filename: &str) -> String {
+    match filename.rsplit_once('.') {
+        Some((_, ext)) if !ext.is_empty() && ext.len() <= 10 && ext.bytes().all(|b| b.is_ascii_alphanumeric()) => {
+            format!(".{}", ext.to_ascii_lowercase())
+        }
+        _ => String::new(),
+    }
+}
+
+// The content of a file field, which fails once it exceeds the size limit.
+struct LimitedStream {
+    field: multer::Field<'static>,
+    size: Arc<AtomicU64>,
+    max_size: u64,
+}
+
+impl Stream for LimitedStream {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let chunk = match Pin::new(&mut self.field).poll_next(cx) {
+            Poll::Ready(Some(Ok(chunk))) => chunk,
+            Poll::Ready(Some(Err(err))) => {
+                return Poll::Ready(Some(Err(io::Error::new(io::ErrorKind::InvalidData, err))));
+            }
+            Poll::Ready(None) => return Poll::Ready(None),
+            Poll::Pending => return Poll::Pending,
+        };
+
+        let size = self.size.fetch_add(chunk.len() as u64, Ordering::SeqCst) + chunk.len() as u64;
+        if size > self.max_size {
+            return Poll::Ready(Some(Err(io::Error::other("The file exceeds the size limit"))));
+        }
+        Poll::Ready(Some(Ok(chunk)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_allowed_types() {
+        let config = UploadConfig {
+            max_size: 1024,
+            allowed_types: vec!["image/*".to_owned(), "application/pdf".to_owned()],
+            storage: Arc::new(LocalStorage::new(std::env::temp_dir())),
+        };
+        assert!(config.is_allowed("image/png"));
+        assert!(config.is_allowed("application/pdf"));
+        assert!(!config.is_allowed("text/html"));
+
+        assert_eq!(extension("me.PNG"), ".png");
+        assert_eq!(extension("archive.tar.gz"), ".gz");
+        assert_eq!(extension("../../etc/passwd"), "");
+        assert_eq!(extension("a.p/ng"), "");
+    }
+}
@@ -0,0 +1,93 @@
+use crate::BufferedBody;
+use hyper::body::Bytes;
+#[cfg(feature = "fs")]
+use std::fmt::{self, Debug, Formatter};
+use std::future::Future;
+use std::io;
+#[cfg(feature = "fs")]
+use std::path::PathBuf;
+use std::pin::Pin;
+#[cfg(feature = "fs")]
+use tokio::io::{AsyncSeekExt, AsyncWriteExt, SeekFrom};
+
+/// The future returned by the [`ChunkStore`](./trait.ChunkStore.html) methods.
+pub type StoreFuture<'a, T> = Pin<Box<dyn Future<Output = io::Result<T>> + Send + 'a>>;
+
+/// A storage of the partially received uploads.
+///
+/// The chunks of an upload are written in order, each one starting where the previous one ended.
+pub trait ChunkStore: Send + Sync + 'static {
+    /// Returns the number of bytes received so far for the upload, which is `0` for an unknown upload.
+    fn received<'a>(&'a self, upload_id: &'a str) -> StoreFuture<'a, u64>;
+
+    /// Writes a chunk of the upload at the specified offset.
+    fn write<'a>(&'a self, upload_id: &'a str, offset: u64, chunk: Bytes) -> StoreFuture<'a, ()>;
+
+    /// Removes the complete upload from the store and returns its content.
+    fn take<'a>(&'a self, upload_id: &'a str) -> StoreFuture<'a, BufferedBody>;
+}
+
+/// A [`ChunkStore`](./trait.ChunkStore.html) which keeps the partial uploads as files in a directory.
+///
+/// A complete upload is handed over as a [`BufferedBody`](../struct.BufferedBody.html) backed by its file, which is
+/// removed once the body is dropped. Rename the file at [`BufferedBody::path`](../struct.BufferedBody.html#method.path)
+/// to keep it. It requires the `fs` feature.
+#[cfg(feature = "fs")]
+#[derive(Clone)]
+pub struct FileChunkStore {
+    dir: PathBuf,
+}
+
+#[cfg(feature = "fs")]
+impl FileChunkStore {
+    /// Creates a store in the specified directory, which must exist.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        FileChunkStore { dir: dir.into() }
+    }
+
+    fn path(&self, upload_id: &str) -> PathBuf {
+        self.dir.join(format!("routerify-upload-{}.part", upload_id))
+    }
+}
+
+#[cfg(feature = "fs")]
+impl ChunkStore for FileChunkStore {
+    fn received<'a>(&'a self, upload_id: &'a str) -> StoreFuture<'a, u64> {
+        Box::pin(async move {
+            match tokio::fs::metadata(self.path(upload_id)).await {
+                Ok(metadata) => Ok(metadata.len()),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(0),
+                Err(err) => Err(err),
+            }
+        })
+    }
+
+    fn write<'a>(&'a self, upload_id: &'a str, offset: u64, chunk: Bytes) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            let mut file = tokio::fs::OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(self.path(upload_id))
+                .await?;
+            file.seek(SeekFrom::Start(offset)).await?;
+            file.write_all(&chunk).await?;
+            file.flush().await
+        })
+    }
+
+    fn take<'a>(&'a self, upload_id: &'a str) -> StoreFuture<'a, BufferedBody> {
+        Box::pin(async move {
+            let path = self.path(upload_id);
+            let len = tokio::fs::metadata(&path).await?.len();
+            Ok(BufferedBody::from_file(path, len))
+        })
+    }
+}
+
+#[cfg(feature = "fs")]
+impl Debug for FileChunkStore {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{{ dir: {:?} }}", self.dir)
+    }
+}
@@ -0,0 +1,308 @@
+//! A warmup state machine which keeps the router unavailable until its warmup tasks complete, so a load balancer
+//! doesn't send the first requests to a cold instance.
+//!
+//! A [`Warmup`](./struct.Warmup.html) is added to a router by the
+//! [`RouterBuilder::warmup`](../struct.RouterBuilder.html#method.warmup) method. Its tasks, e.g. to fill the caches or
+//! to open the database pools, are started together by a startup hook of the router, without delaying the other
+//! startup hooks, so the server can answer the health checks meanwhile. Until all the tasks complete, the requests
+//! get a `503 Service Unavailable` response with a `Retry-After` header, except the requests whose path matches an
+//! [allowed](./struct.Warmup.html#method.allow) route pattern e.g. a liveness probe. Then the router atomically flips
+//! to ready.
+//!
+//! If a task fails, the warmup stays [`Failed`](./enum.WarmupState.html#variant.Failed) and the router keeps being
+//! unavailable, so the instance can be replaced.
+//!
+//! # Examples
+//!
+//! ```
+//! use routerify::Router;
+//! use routerify::warmup::Warmup;
+//! use hyper::{Response, Body};
+//! use std::time::Duration;
+//!
+//! # fn run() -> Router<Body, routerify::Error> {
+//! let warmup = Warmup::new()
+//!     .task("caches", || async move {
+//!         // Fill the caches.
+//!         Ok::<_, routerify::Error>(())
+//!     })
+//!     .allow("/healthz")
+//!     .retry_after(Duration::from_secs(2));
+//!
+//! let router = Router::builder()
+//!     .warmup(warmup.clone())
+//!     .get("/healthz", |_| async move { Ok(Response::new(Body::from("OK"))) })
+//!     .get("/orders", |_| async move { Ok(Response::new(Body::from("Orders"))) })
+//!     .build()
+//!     .unwrap();
+//! # router
+//! # }
+//! # run();
+//! ```
+
+use crate::ext::RequestExt;
+use crate::helpers;
+use crate::middleware::Middleware;
+use crate::regex_generator::generate_exact_match_regex;
+use futures_core::future::BoxFuture;
+use hyper::header::{self, HeaderValue};
+use hyper::{Body, Request, Response, StatusCode};
+use regex::Regex;
+use std::fmt::{self, Debug, Formatter};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+
+type Task = Box<dyn Fn() -> BoxFuture<'static, crate::Result<()>> + Send + Sync + 'static>;
+
+/// The state of a [`Warmup`](./struct.Warmup.html).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WarmupState {
+    /// The tasks aren't started yet.
+    Pending,
+    /// The tasks are running.
+    Warming,
+    /// All the tasks completed, so the router is available.
+    Ready,
+    /// A task failed with the error message.
+    Failed(String),
+}
+
+struct Inner {
+    tasks: Vec<(String, Task)>,
+    allowed: Vec<(String, Regex)>,
+    retry_after: Duration,
+    state: watch::Sender<WarmupState>,
+}
+
+/// The warmup tasks and their state.
+///
+/// It's cheap to clone and the clones share the state. Please refer to the [module](./index.html) documentation for
+/// more info.
+#[derive(Clone)]
+pub struct Warmup {
+    inner: Arc<Inner>,
+}
+
+impl Warmup {
+    /// Creates a new warmup without any task.
+    pub fn new() -> Self {
+        Warmup {
+            inner: Arc::new(Inner {
+                tasks: Vec::new(),
+                allowed: Vec::new(),
+                retry_after: Duration::from_secs(5),
+                state: watch::channel(WarmupState::Pending).0,
+            }),
+        }
+    }
+
+    /// Adds a task which must complete before the router is available.
+    ///
+    /// It should be called before the warmup is added to a router.
+    pub fn task<N, H, R, E>(mut self, name: N, task: H) -> Self
+    where
+        N: Into<String>,
+        H: Fn() -> R + Send + Sync + 'static,
+        R: Future<Output = Result<(), E>> + Send + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        let task: Task = Box::new(move || {
+            let fut = task();
+            Box::pin(async move { fut.await.map_err(Into::into) })
+        });
+        self.inner_mut().tasks.push((name.into(), task));
+        self
+    }
+
+    /// Lets the requests whose path matches the route path, e.g. `/healthz` or `/admin/*`, through during the warmup.
+    ///
+    /// It should be called before the warmup is added to a router.
+    ///
+    /// # Panics
+    ///
+    /// It panics if the path is invalid.
+    pub fn allow<P: Into<String>>(mut self, path: P) -> Self {
+        let mut path = path.into();
+        if !path.ends_with('/') && !path.ends_with('*') {
+            path.push('/');
+        }
+        let (regex, _) = generate_exact_match_regex(&path).expect("Invalid warmup path");
+        self.inner_mut().allowed.push((path, regex));
+        self
+    }
+
+    /// Sets the delay of the `Retry-After` header of the unavailable responses, which is 5 seconds by default.
+    ///
+    /// It should be called before the warmup is added to a router.
+    pub fn retry_after(mut self, delay: Duration) -> Self {
+        self.inner_mut().retry_after = delay;
+        self
+    }
+
+    /// Returns the current state.
+    pub fn state(&self) -> WarmupState {
+        self.inner.state.borrow().clone()
+    }
+
+    /// Checks if all the tasks completed.
+    pub fn is_ready(&self) -> bool {
+        *self.inner.state.borrow() == WarmupState::Ready
+    }
+
+    /// Starts the tasks together in the background, unless they are already started. It's called by the startup hook
+    /// of the router.
+    pub fn start(&self) {
+        let started = self.inner.state.send_if_modified(|state| match state {
+            WarmupState::Pending => {
+                *state = WarmupState::Warming;
+                true
+            }
+            _ => false,
+        });
+        if !started {
+            return;
+        }
+
+        let inner = self.inner.clone();
+        tokio::spawn(async move {
+            let mut tasks = inner
+                .tasks
+                .iter()
+                .map(|(name, task)| {
+                    let name = name.clone();
+                    let fut = task();
+                    tokio::spawn(async move { fut.await.map_err(|err| format!("{}: {}", name, err)) })
+                })
+                .collect::<Vec<_>>();
+
+            let mut state = WarmupState::Ready;
+            for task in tasks.drain(..) {
+                let result = match task.await {
+                    Ok(result) => result,
+                    Err(err) => Err(format!("The warmup task panicked: {}", err)),
+                };
+                if let (Err(msg), WarmupState::Ready) = (result, &state) {
+                    state = WarmupState::Failed(msg);
+                }
+            }
+            inner.state.send_replace(state);
+        });
+    }
+
+    /// Waits until the tasks complete or fail, and returns the final state.
+    pub async fn wait(&self) -> WarmupState {
+        let mut rx = self.inner.state.subscribe();
+        loop {
+            {
+                let state = rx.borrow_and_update();
+                if matches!(*state, WarmupState::Ready | WarmupState::Failed(_)) {
+                    return state.clone();
+                }
+            }
+            if rx.changed().await.is_err() {
+                return self.state();
+            }
+        }
+    }
+
+    /// Creates an around middleware at the `/*` path which responds with `503 Service Unavailable` until the warmup is
+    /// ready. It's added by the [`RouterBuilder::warmup`](../struct.RouterBuilder.html#method.warmup) method.
+    pub fn middleware<E>(&self) -> Middleware<Body, E>
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        let warmup = self.clone();
+        Middleware::around_with_path("/*", move |req: Request<Body>, next| {
+            let warmup = warmup.clone();
+            async move {
+                if warmup.is_ready() || warmup.is_allowed(&req) {
+                    return next.run(req).await;
+                }
+
+                let retry_after = warmup.inner.retry_after.as_secs().max(1);
+                let mut res = Response::new(Body::from("The server is warming up"));
+                *res.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+                res.headers_mut()
+                    .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+                Ok(res)
+            }
+        })
+        .unwrap()
+    }
+
+    fn is_allowed(&self, req: &Request<Body>) -> bool {
+        if self.inner.allowed.is_empty() {
+            return false;
+        }
+
+        match helpers::route_target_path(req.uri().path(), req.base_path()) {
+            Ok((target_path, _)) => self.inner.allowed.iter().any(|(_, regex)| regex.is_match(&target_path)),
+            Err(_) => false,
+        }
+    }
+
+    fn inner_mut(&mut self) -> &mut Inner {
+        Arc::get_mut(&mut self.inner).expect("Warmup must be configured before it's added to a router")
+    }
+}
+
+impl Default for Warmup {
+    fn default() -> Self {
+        Warmup::new()
+    }
+}
+
+impl Debug for Warmup {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{{ tasks: {:?}, allowed: {:?}, retry_after: {:?}, state: {:?} }}",
+            self.inner.tasks.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+            self.inner.allowed.iter().map(|(path, _)| path).collect::<Vec<_>>(),
+            self.inner.retry_after,
+            self.state()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn flips_to_ready_once_the_tasks_complete() {
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        let rx = std::sync::Mutex::new(Some(rx));
+        let warmup = Warmup::new()
+            .task("slow", move || {
+                let rx = rx.lock().unwrap().take();
+                async move {
+                    if let Some(rx) = rx {
+                        let _ = rx.await;
+                    }
+                    Ok::<_, crate::Error>(())
+                }
+            })
+            .task("fast", || async move { Ok::<_, crate::Error>(()) });
+        assert_eq!(warmup.state(), WarmupState::Pending);
+
+        warmup.start();
+        warmup.start();
+        assert_eq!(warmup.state(), WarmupState::Warming);
+        tokio::task::yield_now().await;
+        assert!(!warmup.is_ready());
+
+        tx.send(()).unwrap();
+        assert_eq!(warmup.wait().await, WarmupState::Ready);
+        assert!(warmup.is_ready());
+
+        let failing = Warmup::new().task("db", || async move { Err(crate::Error::new("Connection refused")) });
+        failing.start();
+        assert_eq!(
+            failing.wait().await,
+            WarmupState::Failed("db: routerify::Error: Connection refused".to_owned())
+        );
+    }
+}
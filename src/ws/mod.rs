@@ -0,0 +1,437 @@
+//! A registry of the connected clients with rooms and broadcasts, for the chat and the notification servers.
+//!
+//! The [`Hub`](./struct.Hub.html) keeps the connections which are [connected](./struct.Hub.html#method.connect) to
+//! it with their metadata e.g. the user name, and the rooms they joined. A message is sent to a single connection, to
+//! the members of a room or to every connection. A connection leaves its rooms and the hub once its
+//! [`HubConnection`](./struct.HubConnection.html) handle is dropped.
+//!
+//! The hub is shut down gracefully by its [`shutdown`](./struct.Hub.html#method.shutdown) method, e.g. in a
+//! [shutdown hook](../struct.RouterBuilder.html#method.on_shutdown), after which the
+//! [`recv`](./struct.HubConnection.html#method.recv) method of every connection returns `None`, so the connection
+//! handlers return and the connections are closed.
+//!
+//! The connections are the [`Channel`](../realtime/struct.Channel.html)s of the [`realtime`](../realtime/index.html)
+//! endpoint, which falls back to long polling until its WebSocket transport is available.
+//!
+//! # Examples
+//!
+//! ```
+//! use routerify::{Router, RouteError};
+//! use routerify::realtime::Realtime;
+//! use routerify::ws::Hub;
+//! use hyper::Body;
+//!
+//! # fn run() -> Router<Body, RouteError> {
+//! // The metadata of a connection is the name of its user.
+//! let hub: Hub<String> = Hub::new();
+//!
+//! let chat_hub = hub.clone();
+//! let realtime = Realtime::new(move |channel| {
+//!     let mut conn = chat_hub.connect(channel, "anonymous".to_owned());
+//!     async move {
+//!         conn.join("lobby");
+//!         while let Some(msg) = conn.recv().await {
+//!             let name = conn.metadata();
+//!             conn.broadcast("lobby", format!("{}: {}", name, msg));
+//!         }
+//!     }
+//! });
+//!
+//! let router = Router::builder()
+//!     .scope("/chat", realtime.into_router())
+//!     .on_shutdown(move || {
+//!         let hub = hub.clone();
+//!         async move {
+//!             hub.shutdown();
+//!             Ok(())
+//!         }
+//!     })
+//!     .build()
+//!     .unwrap();
+//! # router
+//! # }
+//! # run();
+//! ```
+
+use crate::realtime::Channel;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Debug, Formatter};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::task::Poll;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::watch;
+
+struct Connection<M> {
+    sender: UnboundedSender<String>,
+    meta: M,
+    rooms: HashSet<String>,
+}
+
+struct Registry<M> {
+    connections: HashMap<String, Connection<M>>,
+    // The ids of the members of each room.
+    rooms: HashMap<String, HashSet<String>>,
+}
+
+impl<M> Registry<M> {
+    fn leave(&mut self, id: &str, room: &str) -> bool {
+        let is_member = match self.connections.get_mut(id) {
+            Some(conn) => conn.rooms.remove(room),
+            None => false,
+        };
+        if let Some(members) = self.rooms.get_mut(room) {
+            members.remove(id);
+            if members.is_empty() {
+                self.rooms.remove(room);
+            }
+        }
+        is_member
+    }
+
+    fn remove(&mut self, id: &str) {
+        let rooms = match self.connections.get(id) {
+            Some(conn) => conn.rooms.iter().cloned().collect::<Vec<_>>(),
+            None => return,
+        };
+        for room in rooms {
+            self.leave(id, room.as_str());
+        }
+        self.connections.remove(id);
+    }
+}
+
+struct Inner<M> {
+    registry: Mutex<Registry<M>>,
+    shutdown: watch::Sender<bool>,
+}
+
+/// A registry of the connections with rooms and broadcasts.
+///
+/// The metadata of the connections is of the type `M`. The clones of a hub share the same connections. Please refer to
+/// the [module](./index.html) documentation for more info.
+pub struct Hub<M = ()> {
+    inner: Arc<Inner<M>>,
+}
+
+impl<M> Clone for Hub<M> {
+    fn clone(&self) -> Self {
+        Hub {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<M: Send + 'static> Default for Hub<M> {
+    fn default() -> Self {
+        Hub::new()
+    }
+}
+
+impl<M: Send + 'static> Hub<M> {
+    /// Creates a new hub without any connection.
+    pub fn new() -> Self {
+        Hub {
+            inner: Arc::new(Inner {
+                registry: Mutex::new(Registry {
+                    connections: HashMap::new(),
+                    rooms: HashMap::new(),
+                }),
+                shutdown: watch::channel(false).0,
+            }),
+        }
+    }
+
+    /// Adds the channel to the hub with its metadata. The connection is identified by the session id of the channel,
+    /// and it's removed from the hub once the returned handle is dropped.
+    pub fn connect(&self, channel: Channel, meta: M) -> HubConnection<M> {
+        let id = channel.id().to_owned();
+        self.inner.registry.lock().unwrap().connections.insert(
+            id.clone(),
+            Connection {
+                sender: channel.sender(),
+                meta,
+                rooms: HashSet::new(),
+            },
+        );
+
+        HubConnection {
+            id,
+            channel,
+            shutdown: self.inner.shutdown.subscribe(),
+            hub: self.clone(),
+        }
+    }
+
+    /// Adds the connection to the room. It returns `false` if the connection isn't in the hub.
+    pub fn join<R: Into<String>>(&self, id: &str, room: R) -> bool {
+        let room = room.into();
+        let mut registry = self.inner.registry.lock().unwrap();
+        match registry.connections.get_mut(id) {
+            Some(conn) => conn.rooms.insert(room.clone()),
+            None => return false,
+        };
+        registry.rooms.entry(room).or_default().insert(id.to_owned());
+        true
+    }
+
+    /// Removes the connection from the room. It returns `false` if the connection isn't a member of the room.
+    pub fn leave(&self, id: &str, room: &str) -> bool {
+        self.inner.registry.lock().unwrap().leave(id, room)
+    }
+
+    /// Sends a message to the connection. It returns `false` if the connection isn't in the hub or it's closed.
+    pub fn send<T: Into<String>>(&self, id: &str, msg: T) -> bool {
+        match self.inner.registry.lock().unwrap().connections.get(id) {
+            Some(conn) => conn.sender.send(msg.into()).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Sends a message to the members of the room, and returns the number of the members it's sent to.
+    pub fn broadcast<T: Into<String>>(&self, room: &str, msg: T) -> usize {
+        self.broadcast_except(room, msg, None)
+    }
+
+    /// Sends a message to every connection in the hub, and returns the number of the connections it's sent to.
+    pub fn broadcast_all<T: Into<String>>(&self, msg: T) -> usize {
+        let msg = msg.into();
+        self.inner
+            .registry
+            .lock()
+            .unwrap()
+            .connections
+            .values()
+            .filter(|conn| conn.sender.send(msg.clone()).is_ok())
+            .count()
+    }
+
+    fn broadcast_except<T: Into<String>>(&self, room: &str, msg: T, except: Option<&str>) -> usize {
+        let msg = msg.into();
+        let registry = self.inner.registry.lock().unwrap();
+        let members = match registry.rooms.get(room) {
+            Some(members) => members,
+            None => return 0,
+        };
+
+        members
+            .iter()
+            .filter(|id| Some(id.as_str()) != except)
+            .filter_map(|id| registry.connections.get(id))
+            .filter(|conn| conn.sender.send(msg.clone()).is_ok())
+            .count()
+    }
+
+    /// Returns the ids of the members of the room.
+    pub fn members(&self, room: &str) -> Vec<String> {
+        match self.inner.registry.lock().unwrap().rooms.get(room) {
+            Some(members) => members.iter().cloned().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Returns the rooms the connection is a member of.
+    pub fn rooms(&self, id: &str) -> Vec<String> {
+        match self.inner.registry.lock().unwrap().connections.get(id) {
+            Some(conn) => conn.rooms.iter().cloned().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Returns a copy of the metadata of the connection.
+    pub fn metadata(&self, id: &str) -> Option<M>
+    where
+        M: Clone,
+    {
+        self.inner
+            .registry
+            .lock()
+            .unwrap()
+            .connections
+            .get(id)
+            .map(|conn| conn.meta.clone())
+    }
+
+    /// Updates the metadata of the connection. It returns `false` if the connection isn't in the hub.
+    pub fn update_metadata<F: FnOnce(&mut M)>(&self, id: &str, update: F) -> bool {
+        match self.inner.registry.lock().unwrap().connections.get_mut(id) {
+            Some(conn) => {
+                update(&mut conn.meta);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the number of the connections in the hub.
+    pub fn len(&self) -> usize {
+        self.inner.registry.lock().unwrap().connections.len()
+    }
+
+    /// Returns `true` if there is no connection in the hub.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Shuts the hub down. The [`recv`](./struct.HubConnection.html#method.recv) method of the current and the new
+    /// connections returns `None` afterwards.
+    pub fn shutdown(&self) {
+        self.inner.shutdown.send_replace(true);
+    }
+
+    /// Returns `true` if the hub is shut down.
+    pub fn is_shutdown(&self) -> bool {
+        *self.inner.shutdown.borrow()
+    }
+}
+
+impl<M> Debug for Hub<M> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let registry = self.inner.registry.lock().unwrap();
+        write!(
+            f,
+            "{{ connections: {}, rooms: {}, shutdown: {} }}",
+            registry.connections.len(),
+            registry.rooms.len(),
+            *self.inner.shutdown.borrow()
+        )
+    }
+}
+
+/// A connection of a [`Hub`](./struct.Hub.html), which is removed from the hub once it's dropped.
+pub struct HubConnection<M: Send + 'static = ()> {
+    id: String,
+    channel: Channel,
+    shutdown: watch::Receiver<bool>,
+    hub: Hub<M>,
+}
+
+impl<M: Send + 'static> HubConnection<M> {
+    /// Returns the id of the connection, which is the session id of its channel.
+    pub fn id(&self) -> &str {
+        self.id.as_str()
+    }
+
+    /// Returns the hub of the connection.
+    pub fn hub(&self) -> &Hub<M> {
+        &self.hub
+    }
+
+    /// Receives the next message from the client. It returns `None` once the client closes the connection or the hub is
+    /// shut down.
+    pub async fn recv(&mut self) -> Option<String> {
+        let channel = &mut self.channel;
+        let shutdown = &mut self.shutdown;
+        let mut is_shutdown = Box::pin(shutdown.wait_for(|is_shutdown| *is_shutdown));
+
+        std::future::poll_fn(|cx| {
+            // An error means the hub is dropped, which ends the connection too.
+            if is_shutdown.as_mut().poll(cx).is_ready() {
+                return Poll::Ready(None);
+            }
+            channel.poll_recv(cx)
+        })
+        .await
+    }
+
+    /// Sends a message to the client. It returns `false` if the connection is closed.
+    pub fn send<T: Into<String>>(&self, msg: T) -> bool {
+        self.channel.send(msg)
+    }
+
+    /// Adds the connection to the room.
+    pub fn join<R: Into<String>>(&self, room: R) -> bool {
+        self.hub.join(&self.id, room)
+    }
+
+    /// Removes the connection from the room. It returns `false` if the connection isn't a member of the room.
+    pub fn leave(&self, room: &str) -> bool {
+        self.hub.leave(&self.id, room)
+    }
+
+    /// Sends a message to the other members of the room, and returns the number of the members it's sent to.
+    pub fn broadcast<T: Into<String>>(&self, room: &str, msg: T) -> usize {
+        self.hub.broadcast_except(room, msg, Some(&self.id))
+    }
+
+    /// Returns a copy of the metadata of the connection.
+    pub fn metadata(&self) -> M
+    where
+        M: Clone,
+    {
+        self.hub
+            .metadata(&self.id)
+            .expect("A hub connection is in the hub until it's dropped")
+    }
+
+    /// Updates the metadata of the connection.
+    pub fn update_metadata<F: FnOnce(&mut M)>(&self, update: F) {
+        self.hub.update_metadata(&self.id, update);
+    }
+}
+
+impl<M: Send + 'static> Drop for HubConnection<M> {
+    fn drop(&mut self) {
+        self.hub.inner.registry.lock().unwrap().remove(&self.id);
+    }
+}
+
+impl<M: Send + 'static> Debug for HubConnection<M> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{{ id: {:?} }}", self.id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn broadcasts_to_rooms() {
+        let hub: Hub<&'static str> = Hub::new();
+        let (alice, _, mut alice_rx) = Channel::detached("a");
+        let (bob, _, mut bob_rx) = Channel::detached("b");
+        let (carol, _, mut carol_rx) = Channel::detached("c");
+        let alice = hub.connect(alice, "alice");
+        let bob = hub.connect(bob, "bob");
+        let carol = hub.connect(carol, "carol");
+
+        assert!(alice.join("lobby"));
+        assert!(bob.join("lobby"));
+        assert_eq!(alice.broadcast("lobby", "hi"), 1);
+        assert_eq!(hub.broadcast("lobby", "welcome"), 2);
+        assert_eq!(hub.broadcast_all("bye"), 3);
+        assert!(hub.send("c", "psst"));
+
+        assert_eq!(bob_rx.recv().await.unwrap(), "hi");
+        assert_eq!(bob_rx.recv().await.unwrap(), "welcome");
+        assert_eq!(alice_rx.recv().await.unwrap(), "welcome");
+        assert_eq!(carol_rx.recv().await.unwrap(), "bye");
+        assert_eq!(carol_rx.recv().await.unwrap(), "psst");
+
+        carol.update_metadata(|name| *name = "caroline");
+        assert_eq!(hub.metadata("c"), Some("caroline"));
+
+        assert!(bob.leave("lobby"));
+        assert!(!bob.leave("lobby"));
+        drop(alice);
+        assert!(hub.members("lobby").is_empty());
+        assert_eq!(hub.len(), 2);
+        assert_eq!(hub.broadcast("lobby", "anyone?"), 0);
+    }
+
+    #[tokio::test]
+    async fn ends_connections_on_shutdown() {
+        let hub: Hub = Hub::new();
+        let (channel, incoming, _outgoing) = Channel::detached("a");
+        let mut conn = hub.connect(channel, ());
+
+        incoming.send("hello".to_owned()).unwrap();
+        assert_eq!(conn.recv().await.as_deref(), Some("hello"));
+
+        let shutdown_hub = hub.clone();
+        tokio::spawn(async move { shutdown_hub.shutdown() });
+        assert_eq!(conn.recv().await, None);
+        assert!(hub.is_shutdown());
+    }
+}
@@ -1,7 +1,7 @@
 use self::support::{into_text, serve};
-use hyper::{Body, Client, Request, Response, StatusCode};
+use hyper::{Body, Client, Method, Request, Response, StatusCode};
 use routerify::prelude::RequestExt;
-use routerify::{Middleware, RequestInfo, RouteError, Router};
+use routerify::{ErrorContext, Middleware, RequestInfo, RouteError, Router};
 use std::io;
 use std::sync::{Arc, Mutex};
 
@@ -496,3 +496,3375 @@ async fn can_handle_pre_middleware_errors() {
         .unwrap();
     serve.shutdown();
 }
+
+#[cfg(feature = "webhook")]
+#[tokio::test]
+async fn rejects_invalid_webhook_signatures() {
+    use routerify::middleware::webhook_signature::WebhookSignature;
+
+    let router: Router<Body, routerify::Error> = Router::builder()
+        .middleware(WebhookSignature::github("secret").middleware())
+        .post("/hook", |_| async { panic!("should not be executed") })
+        .build()
+        .unwrap();
+
+    let serve = serve(router).await;
+    let resp = Client::new()
+        .request(
+            serve
+                .new_request("POST", "/hook")
+                .header("x-hub-signature-256", "sha256=00")
+                .body(Body::from("payload"))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    serve.shutdown();
+}
+
+#[cfg(feature = "checksum")]
+#[tokio::test]
+async fn can_verify_and_add_body_digests() {
+    use routerify::middleware::checksum::Checksum;
+
+    let checksum = Checksum::new();
+    let router: Router<Body, RouteError> = Router::builder()
+        .classify_errors()
+        .middleware(checksum.verify_middleware())
+        .middleware(checksum.digest_middleware())
+        .put("/upload", |req| async move {
+            let body = hyper::body::to_bytes(req.into_body()).await?;
+            Ok(Response::new(Body::from(body)))
+        })
+        .build()
+        .unwrap();
+
+    let serve = serve(router).await;
+    let upload = |body: &'static str| {
+        Client::new().request(
+            serve
+                .new_request("PUT", "/upload")
+                .header("digest", "sha-256=uU0nuZNNPgilLlLX2n2r+sSE7+N6U4DukIj3rOLvzek=")
+                .body(Body::from(body))
+                .unwrap(),
+        )
+    };
+
+    let resp = upload("hello world").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        resp.headers()["digest"],
+        "sha-256=uU0nuZNNPgilLlLX2n2r+sSE7+N6U4DukIj3rOLvzek="
+    );
+
+    let resp = upload("hello there").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_evaluate_update_preconditions() {
+    use routerify::headers::Preconditions;
+
+    let router: Router<Body, RouteError> = Router::builder()
+        .put("/documents/1", |req| async move {
+            let preconditions = Preconditions::from_headers(req.headers());
+            preconditions.require()?;
+            preconditions.evaluate(Some("\"v2\""), None)?;
+            Ok(Response::new(Body::from("Updated")))
+        })
+        .build()
+        .unwrap();
+
+    let serve = serve(router).await;
+    let update = |if_match: Option<&'static str>| {
+        let mut req = serve.new_request("PUT", "/documents/1");
+        if let Some(if_match) = if_match {
+            req = req.header("if-match", if_match);
+        }
+        Client::new().request(req.body(Body::empty()).unwrap())
+    };
+
+    assert_eq!(update(Some("\"v2\"")).await.unwrap().status(), StatusCode::OK);
+    assert_eq!(
+        update(Some("\"v1\"")).await.unwrap().status(),
+        StatusCode::PRECONDITION_FAILED
+    );
+    assert_eq!(update(None).await.unwrap().status(), StatusCode::PRECONDITION_REQUIRED);
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_respond_with_problem_details() {
+    use routerify::{HttpError, ProblemDetails};
+
+    let router: Router<Body, RouteError> = Router::builder()
+        .problem_details()
+        .get("/credit", |_| async move {
+            let problem = ProblemDetails::new(StatusCode::FORBIDDEN)
+                .with_type("https://example.com/probs/out-of-credit")
+                .with_json_extension("balance", "30");
+            Err(HttpError::from(problem).into())
+        })
+        .get("/crash", |_| async move { Err(io::Error::other("secret").into()) })
+        .build()
+        .unwrap();
+
+    let serve = serve(router).await;
+    let get = |path: &'static str| {
+        Client::new().request(
+            serve
+                .new_request("GET", path)
+                .header("x-request-id", "abc-123")
+                .body(Body::empty())
+                .unwrap(),
+        )
+    };
+
+    let resp = get("/credit").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    assert_eq!(resp.headers()["content-type"], "application/problem+json");
+    assert_eq!(
+        into_text(resp.into_body()).await,
+        r#"{"type":"https://example.com/probs/out-of-credit","title":"Forbidden","status":403,"instance":"urn:request-id:abc-123","balance":30}"#
+    );
+
+    let resp = get("/crash").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(
+        into_text(resp.into_body()).await,
+        r#"{"title":"Internal Server Error","status":500,"instance":"urn:request-id:abc-123"}"#
+    );
+
+    let resp = get("/missing").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    assert_eq!(
+        into_text(resp.into_body()).await,
+        r#"{"title":"Not Found","status":404,"instance":"urn:request-id:abc-123"}"#
+    );
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_extract_raw_regex_path_params() {
+    let api: Router<Body, routerify::Error> = Router::builder()
+        .get_regex(r"^/items/(?P<id>\d+)-(?P<slug>[a-z-]+)/$", |req| async move {
+            let id = req.param("id").unwrap();
+            let slug = req.param("slug").unwrap();
+            Ok(Response::new(Body::from(format!("{}:{}", id, slug))))
+        })
+        .build()
+        .unwrap();
+    let router: Router<Body, routerify::Error> = Router::builder().scope("/api", api).build().unwrap();
+
+    let serve = serve(router).await;
+    let resp = Client::new()
+        .request(
+            serve
+                .new_request("GET", "/api/items/42-hello-world")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(into_text(resp.into_body()).await, "42:hello-world");
+
+    let resp = Client::new()
+        .request(
+            serve
+                .new_request("GET", "/items/42-hello-world")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_extract_optional_path_params() {
+    let router: Router<Body, routerify::Error> = Router::builder()
+        .get("/archive/:year/:month?/:page?=1", |req| async move {
+            let year = req.param("year").unwrap();
+            let month = req.param_opt("month").unwrap_or("none");
+            let page = req.param_opt("page").unwrap();
+            Ok(Response::new(Body::from(format!("{}/{}/{}", year, month, page))))
+        })
+        .build()
+        .unwrap();
+
+    let serve = serve(router).await;
+    for (path, expected) in [
+        ("/archive/2020", "2020/none/1"),
+        ("/archive/2020/05", "2020/05/1"),
+        ("/archive/2020/05/3/", "2020/05/3"),
+    ] {
+        let resp = Client::new()
+            .request(serve.new_request("GET", path).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(into_text(resp.into_body()).await, expected);
+    }
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_modify_path_params_in_pre_middleware() {
+    let router: Router<Body, routerify::Error> = Router::builder()
+        .middleware(Middleware::pre(|mut req| async move {
+            let slug = req.param("slug").unwrap().to_lowercase();
+            req.params_mut().set("slug", slug);
+            if req.param("tenant").map(String::as_str) == Some("legacy") {
+                req.set_param("tenant", "acme");
+            }
+            Ok(req)
+        }))
+        .middleware(Middleware::post_with_info(|mut res, req_info| async move {
+            let tenant = req_info.params().and_then(|params| params.get("tenant")).unwrap();
+            res.headers_mut().insert("x-tenant", tenant.parse().unwrap());
+            Ok(res)
+        }))
+        .get("/:tenant/posts/:slug", |req| async move {
+            Ok(Response::new(Body::from(format!(
+                "{}/{}",
+                req.param("tenant").unwrap(),
+                req.param("slug").unwrap()
+            ))))
+        })
+        .build()
+        .unwrap();
+
+    let serve = serve(router).await;
+    for (path, tenant, expected) in [
+        ("/legacy/posts/Hello-World", "acme", "acme/hello-world"),
+        ("/globex/posts/hello", "globex", "globex/hello"),
+    ] {
+        let resp = Client::new()
+            .request(serve.new_request("GET", path).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(resp.headers()["x-tenant"], tenant);
+        assert_eq!(into_text(resp.into_body()).await, expected);
+    }
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_fall_through_on_failed_typed_params() {
+    #[derive(Debug)]
+    enum OrderStatus {
+        Pending,
+        Shipped,
+    }
+
+    impl routerify::FromParam for OrderStatus {
+        type Error = String;
+
+        fn from_param(param: &str) -> Result<Self, Self::Error> {
+            match param {
+                "pending" => Ok(OrderStatus::Pending),
+                "shipped" => Ok(OrderStatus::Shipped),
+                _ => Err(format!("Unknown order status: {}", param)),
+            }
+        }
+    }
+
+    let router: Router<Body, routerify::Error> = Router::builder()
+        .typed_param::<OrderStatus, _>("status")
+        .get("/orders/:status", |req| async move {
+            let status = req.param_typed::<OrderStatus, _>("status").unwrap().unwrap();
+            Ok(Response::new(Body::from(format!("{:?}", status))))
+        })
+        .get("/orders/:name", |req| async move {
+            Ok(Response::new(Body::from(format!(
+                "fallback {}",
+                req.param("name").unwrap()
+            ))))
+        })
+        .build()
+        .unwrap();
+
+    let serve = serve(router).await;
+    for (path, expected) in [
+        ("/orders/pending", "Pending"),
+        ("/orders/shipped", "Shipped"),
+        ("/orders/recent", "fallback recent"),
+    ] {
+        let resp = Client::new()
+            .request(serve.new_request("GET", path).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(into_text(resp.into_body()).await, expected);
+    }
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn executes_middlewares_by_priority() {
+    let router: Router<Body, routerify::Error> = Router::builder()
+        .middleware(Middleware::pre(|req| async move {
+            let order = req.context::<String>().unwrap_or_default();
+            req.set_context(format!("{}auth,", order));
+            Ok(req)
+        }))
+        .middleware_with_priority(
+            Middleware::pre(|req| async move {
+                let order = req.context::<String>().unwrap_or_default();
+                req.set_context(format!("{}cors,", order));
+                Ok(req)
+            }),
+            10,
+        )
+        .get("/", |req| async move {
+            Ok(Response::new(Body::from(req.context::<String>().unwrap())))
+        })
+        .build()
+        .unwrap();
+
+    let order = router.middleware_order(&Method::GET, "/");
+    assert_eq!(order.iter().map(|m| m.priority()).collect::<Vec<_>>(), vec![10, 0]);
+
+    let serve = serve(router).await;
+    let resp = Client::new()
+        .request(serve.new_request("GET", "/").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(into_text(resp.into_body()).await, "cors,auth,");
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_skip_named_middlewares() {
+    let router: Router<Body, routerify::Error> = Router::builder()
+        .middleware(
+            Middleware::pre(|req| async move {
+                req.set_context("auth".to_owned());
+                Ok(req)
+            })
+            .named("auth"),
+        )
+        .middleware(
+            Middleware::post(|mut res| async move {
+                res.headers_mut().insert("x-logged", "1".parse().unwrap());
+                Ok(res)
+            })
+            .named("access_log"),
+        )
+        .get("/users", |req| async move {
+            Ok(Response::new(Body::from(req.context::<String>().unwrap_or_default())))
+        })
+        .get_skipping("/metrics", &["access_log", "auth"], |req| async move {
+            Ok(Response::new(Body::from(req.context::<String>().unwrap_or_default())))
+        })
+        .build()
+        .unwrap();
+
+    assert_eq!(router.middleware_order(&Method::GET, "/metrics"), vec![]);
+    assert_eq!(
+        router
+            .middleware_order(&Method::GET, "/users")
+            .iter()
+            .map(|m| m.name().unwrap())
+            .collect::<Vec<_>>(),
+        vec!["auth", "access_log"]
+    );
+
+    let serve = serve(router).await;
+    for (path, expected_body, logged) in [("/users", "auth", true), ("/metrics", "", false)] {
+        let resp = Client::new()
+            .request(serve.new_request("GET", path).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(resp.headers().contains_key("x-logged"), logged);
+        assert_eq!(into_text(resp.into_body()).await, expected_body);
+    }
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_toggle_named_middlewares_at_runtime() {
+    let router: Router<Body, routerify::Error> = Router::builder()
+        .middleware(
+            Middleware::post(|mut res| async move {
+                res.headers_mut().insert("x-debug", "1".parse().unwrap());
+                Ok(res)
+            })
+            .named("debug_log")
+            .enabled(false),
+        )
+        .get("/", |_| async move { Ok(Response::new(Body::empty())) })
+        .build()
+        .unwrap();
+
+    let handle = router.handle();
+    assert_eq!(handle.is_middleware_enabled("debug_log"), Some(false));
+    assert!(handle.set_middleware_enabled("unknown", true).is_err());
+
+    let serve = serve(router).await;
+    for enabled in [false, true, false] {
+        handle.set_middleware_enabled("debug_log", enabled).unwrap();
+        let resp = Client::new()
+            .request(serve.new_request("GET", "/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(resp.headers().contains_key("x-debug"), enabled);
+    }
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_classify_errors_in_default_error_handler() {
+    let router: Router<Body, RouteError> = Router::builder()
+        .classify_errors()
+        .get("/users/:id", |req| async move {
+            let id = req.param("id").unwrap().parse::<u64>()?;
+            Ok(Response::new(Body::from(id.to_string())))
+        })
+        .build()
+        .unwrap();
+
+    let serve = serve(router).await;
+    for (path, status) in [("/users/1", StatusCode::OK), ("/users/abc", StatusCode::BAD_REQUEST)] {
+        let resp = Client::new()
+            .request(serve.new_request("GET", path).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), status);
+    }
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_set_cache_control_for_paths() {
+    use routerify::headers::CacheControl;
+    use routerify::middleware::cache_control_for;
+
+    let router: Router<Body, RouteError> = Router::builder()
+        .middleware(cache_control_for("/static/*", CacheControl::public().max_age(86400).immutable()).unwrap())
+        .get(
+            "/static/app.js",
+            |_| async move { Ok(Response::new(Body::from("app"))) },
+        )
+        .get("/static/nocache.js", |_| async move {
+            Ok(Response::builder()
+                .header("cache-control", "no-cache")
+                .body(Body::from("nocache"))
+                .unwrap())
+        })
+        .get("/api", |_| async move { Ok(Response::new(Body::from("api"))) })
+        .build()
+        .unwrap();
+
+    let serve = serve(router).await;
+    for (path, expected) in [
+        ("/static/app.js", Some("public, max-age=86400, immutable")),
+        ("/static/nocache.js", Some("no-cache")),
+        ("/static/missing.js", None),
+        ("/api", None),
+    ] {
+        let resp = Client::new()
+            .request(serve.new_request("GET", path).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let cc = resp.headers().get("cache-control").map(|val| val.to_str().unwrap());
+        assert_eq!(cc, expected, "{}", path);
+    }
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_add_csp_nonce_per_request() {
+    use routerify::middleware::csp::ContentSecurityPolicy;
+
+    let csp = ContentSecurityPolicy::new("script-src 'self' 'nonce-{nonce}'");
+    let router: Router<Body, RouteError> = Router::builder()
+        .middleware(csp.pre_middleware())
+        .middleware(csp.post_middleware())
+        .get("/", |req| async move {
+            Ok(Response::new(Body::from(req.csp_nonce().unwrap())))
+        })
+        .build()
+        .unwrap();
+
+    let serve = serve(router).await;
+    let mut nonces = Vec::new();
+    for _ in 0..2 {
+        let resp = Client::new()
+            .request(serve.new_request("GET", "/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let policy = resp.headers()["content-security-policy"].to_str().unwrap().to_owned();
+        let nonce = into_text(resp.into_body()).await;
+        assert_eq!(policy, format!("script-src 'self' 'nonce-{}'", nonce));
+        nonces.push(nonce);
+    }
+    assert_ne!(nonces[0], nonces[1]);
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_throttle_requests_per_principal() {
+    use routerify::middleware::throttle::{Quota, Throttle};
+    use routerify::Principal;
+    use std::time::Duration;
+
+    let throttle =
+        Throttle::new(Quota::new(1, Duration::from_secs(60))).role("admin", Quota::new(3, Duration::from_secs(60)));
+    let router: Router<Body, RouteError> = Router::builder()
+        .middleware(Middleware::pre(|req: Request<Body>| async move {
+            if let Some(user) = req.headers().get("x-user") {
+                let principal = Principal::new(user.to_str().unwrap());
+                if user == "root" {
+                    req.set_context(principal.with_role("admin"));
+                } else {
+                    req.set_context(principal);
+                }
+            }
+            Ok(req)
+        }))
+        .middleware(throttle.middleware_with_path("search", "/search/*").unwrap())
+        .middleware(throttle.middleware_with_path("search", "/suggest/*").unwrap())
+        .get("/search", |_| async move { Ok(Response::new(Body::from("search"))) })
+        .get("/suggest", |_| async move { Ok(Response::new(Body::from("suggest"))) })
+        .build()
+        .unwrap();
+
+    let serve = serve(router).await;
+    for (user, path, status) in [
+        ("alice", "/search", StatusCode::OK),
+        ("alice", "/suggest", StatusCode::TOO_MANY_REQUESTS),
+        ("bob", "/suggest", StatusCode::OK),
+        ("root", "/search", StatusCode::OK),
+        ("root", "/suggest", StatusCode::OK),
+        ("root", "/search", StatusCode::OK),
+        ("root", "/search", StatusCode::TOO_MANY_REQUESTS),
+    ] {
+        let resp = Client::new()
+            .request(
+                serve
+                    .new_request("GET", path)
+                    .header("x-user", user)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), status, "{} {}", user, path);
+    }
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_audit_mutating_requests() {
+    use routerify::middleware::audit::{Audit, AuditEvent};
+    use routerify::Principal;
+
+    let events = Arc::new(Mutex::new(Vec::<AuditEvent>::new()));
+    let sink_events = events.clone();
+    let audit =
+        Audit::new(move |event: &AuditEvent| sink_events.lock().unwrap().push(event.clone())).redact_param("token");
+
+    let router: Router<Body, RouteError> = Router::builder()
+        .middleware(Middleware::pre(|req: Request<Body>| async move {
+            req.set_context(Principal::new("alice"));
+            Ok(req)
+        }))
+        .middleware(audit.middleware())
+        .get(
+            "/invites/:token",
+            |_| async move { Ok(Response::new(Body::from("Invite"))) },
+        )
+        .post("/invites/:token", |_| async move {
+            Ok(Response::builder()
+                .status(StatusCode::CREATED)
+                .body(Body::from("Accepted"))
+                .unwrap())
+        })
+        .build()
+        .unwrap();
+
+    let serve = serve(router).await;
+    for method in ["GET", "POST"] {
+        let _ = Client::new()
+            .request(
+                serve
+                    .new_request(method, "/invites/secret")
+                    .header("x-request-id", "req-1")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+    }
+    serve.shutdown();
+
+    let events = events.lock().unwrap();
+    assert_eq!(events.len(), 1);
+    let event = &events[0];
+    assert_eq!(event.method(), Method::POST);
+    assert_eq!(event.route(), Some("/invites/:token/"));
+    assert_eq!(event.principal(), Some("alice"));
+    assert_eq!(event.path(), "/invites/[REDACTED]");
+    assert_eq!(event.params(), &[("token".to_owned(), "[REDACTED]".to_owned())]);
+    assert_eq!(event.status(), StatusCode::CREATED);
+    assert_eq!(event.request_id(), Some("req-1"));
+}
+
+#[tokio::test]
+async fn can_redact_secrets_from_debug_error_pages() {
+    use routerify::Redaction;
+
+    let router: Router<Body, RouteError> = Router::builder()
+        .debug_errors(true)
+        .data(
+            Redaction::new()
+                .redact_params(["token", "password"])
+                .redact_headers(["x-api-key"]),
+        )
+        .post(
+            "/invites/:token",
+            |_| async move { Err(io::Error::other("failed").into()) },
+        )
+        .build()
+        .unwrap();
+
+    let serve = serve(router).await;
+    let resp = Client::new()
+        .request(
+            serve
+                .new_request("POST", "/invites/s3cret?password=hunter2&page=1")
+                .header("x-api-key", "k3y")
+                .header("authorization", "Bearer t0ken")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let page = into_text(resp.into_body()).await;
+    assert!(page.contains("/invites/[REDACTED]?password=[REDACTED]&amp;page=1"));
+    for secret in ["s3cret", "hunter2", "k3y", "t0ken"] {
+        assert!(!page.contains(secret), "{} is leaked", secret);
+    }
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_count_response_bytes() {
+    use routerify::middleware::response_stats;
+    use routerify::ResponseStats;
+
+    let collected = Arc::new(Mutex::new(Vec::<ResponseStats>::new()));
+    let collected2 = collected.clone();
+    let router: Router<Body, RouteError> = Router::builder()
+        .middleware(response_stats())
+        .middleware(Middleware::post(move |res: Response<Body>| {
+            let collected = collected2.clone();
+            async move {
+                collected
+                    .lock()
+                    .unwrap()
+                    .push(res.extensions().get::<ResponseStats>().cloned().unwrap());
+                Ok(res)
+            }
+        }))
+        .get("/", |_| async move { Ok(Response::new(Body::from("Hello world"))) })
+        .build()
+        .unwrap();
+
+    let serve = serve(router).await;
+    let resp = Client::new()
+        .request(serve.new_request("GET", "/").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(resp.headers()["content-length"], "11");
+    assert_eq!(into_text(resp.into_body()).await, "Hello world");
+
+    let stats = collected.lock().unwrap().pop().unwrap();
+    assert_eq!(stats.finished().await, 11);
+    assert!(stats.is_complete());
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_cache_route_matches() {
+    let router: Router<Body, RouteError> = Router::builder()
+        .match_cache(1)
+        .get("/users/:id", |req| async move {
+            let id = req.param("id").unwrap().clone();
+            Ok(Response::new(Body::from(id)))
+        })
+        .post(
+            "/users/:id",
+            |_| async move { Ok(Response::new(Body::from("Updated"))) },
+        )
+        .any(|_| async move { Ok(Response::builder().status(404).body(Body::from("Not found")).unwrap()) })
+        .build()
+        .unwrap();
+
+    let serve = serve(router).await;
+    for (method, path, expected) in [
+        ("GET", "/users/1", "1"),
+        ("GET", "/users/1", "1"),
+        ("POST", "/users/1", "Updated"),
+        ("GET", "/users/2", "2"),
+        ("GET", "/users/1", "1"),
+        ("GET", "/unknown", "Not found"),
+    ] {
+        let resp = Client::new()
+            .request(serve.new_request(method, path).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(into_text(resp.into_body()).await, expected);
+    }
+    serve.shutdown();
+}
+
+#[cfg(feature = "fast-match")]
+#[tokio::test]
+async fn can_match_routes_with_fast_match() {
+    let router: Router<Body, RouteError> = Router::builder()
+        .fast_match(true)
+        .middleware(
+            Middleware::pre_with_path("/users/*", |req| async move {
+                req.set_context("users");
+                Ok(req)
+            })
+            .unwrap(),
+        )
+        .get("/users/:id", |req| async move {
+            let scope = req.context::<&str>().unwrap();
+            Ok(Response::new(Body::from(format!(
+                "{} {}",
+                scope,
+                req.param("id").unwrap()
+            ))))
+        })
+        .get("/files/*", |_| async move { Ok(Response::new(Body::from("File"))) })
+        .post(
+            "/users/:id",
+            |_| async move { Ok(Response::new(Body::from("Updated"))) },
+        )
+        .any(|_| async move { Ok(Response::builder().status(404).body(Body::from("Not found")).unwrap()) })
+        .build()
+        .unwrap();
+
+    let serve = serve(router).await;
+    for (method, path, expected) in [
+        ("GET", "/users/1", "users 1"),
+        ("POST", "/users/1", "Updated"),
+        ("DELETE", "/users/1", "Not found"),
+        ("GET", "/files/a/b", "File"),
+        ("GET", "/unknown", "Not found"),
+    ] {
+        let resp = Client::new()
+            .request(serve.new_request(method, path).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(into_text(resp.into_body()).await, expected);
+    }
+    serve.shutdown();
+}
+
+#[cfg(feature = "fast-match")]
+#[tokio::test]
+async fn can_match_overlapping_routes_alike_with_both_backends() {
+    for fast_match in [false, true] {
+        let router: Router<Body, RouteError> = Router::builder()
+            .fast_match(fast_match)
+            .get("/users/:id", |req| async move {
+                Ok(Response::new(Body::from(format!("User {}", req.param("id").unwrap()))))
+            })
+            .post(
+                "/users/new",
+                |_| async move { Ok(Response::new(Body::from("Created"))) },
+            )
+            .get(
+                "/users/new/edit",
+                |_| async move { Ok(Response::new(Body::from("Edit"))) },
+            )
+            .get(
+                "/users/:id/view",
+                |_| async move { Ok(Response::new(Body::from("View"))) },
+            )
+            .build()
+            .unwrap();
+
+        let serve = serve(router).await;
+        for (method, path, status, expected) in [
+            ("GET", "/users/new", 200, "User new"),
+            ("POST", "/users/new", 200, "Created"),
+            ("GET", "/users/1", 200, "User 1"),
+            ("GET", "/users/new/edit", 200, "Edit"),
+            ("GET", "/users/new/view", 200, "View"),
+            ("DELETE", "/users/new", 405, ""),
+        ] {
+            let resp = Client::new()
+                .request(serve.new_request(method, path).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(
+                resp.status().as_u16(),
+                status,
+                "{} {} (fast_match: {})",
+                method,
+                path,
+                fast_match
+            );
+            if status == 200 {
+                assert_eq!(into_text(resp.into_body()).await, expected);
+            }
+        }
+        serve.shutdown();
+    }
+}
+
+#[cfg(feature = "fs")]
+#[tokio::test]
+async fn can_assemble_chunked_uploads() {
+    use routerify::uploads::{FileChunkStore, Uploads};
+
+    let completed = Arc::new(Mutex::new(Vec::new()));
+    let completed2 = completed.clone();
+    let uploads = Uploads::new(FileChunkStore::new(std::env::temp_dir()), move |upload| {
+        let completed = completed2.clone();
+        async move {
+            let bytes = upload.body().to_bytes().await.unwrap();
+            completed.lock().unwrap().push((upload.upload_id().to_owned(), bytes));
+            Ok(())
+        }
+    });
+    let router: Router<Body, RouteError> = Router::builder()
+        .put("/uploads/:upload_id", move |req| {
+            let uploads = uploads.clone();
+            async move { Ok(uploads.handle(req).await?) }
+        })
+        .build()
+        .unwrap();
+
+    let serve = serve(router).await;
+    let upload_id = format!("it-{}", std::process::id());
+    let put = |range: &str, body: &'static str| {
+        serve
+            .new_request("PUT", &format!("/uploads/{}", upload_id))
+            .header("content-range", range)
+            .body(Body::from(body))
+            .unwrap()
+    };
+
+    let resp = Client::new().request(put("bytes 0-4/*", "Hello")).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::ACCEPTED);
+    assert_eq!(resp.headers()["range"], "bytes=0-4");
+
+    let resp = Client::new().request(put("bytes 2-4/11", "llo")).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    assert_eq!(resp.headers()["range"], "bytes=0-4");
+
+    let resp = Client::new().request(put("bytes */11", "")).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::ACCEPTED);
+    assert_eq!(resp.headers()["range"], "bytes=0-4");
+
+    let resp = Client::new().request(put("bytes 5-10/11", " world")).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    let completed = completed.lock().unwrap();
+    assert_eq!(completed.len(), 1);
+    assert_eq!(completed[0].0, upload_id);
+    assert_eq!(completed[0].1, "Hello world");
+    serve.shutdown();
+}
+
+#[cfg(feature = "protobuf")]
+#[tokio::test]
+async fn can_exchange_protobuf_messages() {
+    use prost::Message;
+    use routerify::ext::RequestBodyExt;
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    struct Greeting {
+        #[prost(string, tag = "1")]
+        name: String,
+    }
+
+    let router: Router<Body, RouteError> = Router::builder()
+        .post("/greetings", |mut req| async move {
+            let greeting = req.proto_with_limit::<Greeting>(64).await?;
+            Ok(routerify::proto(&Greeting {
+                name: format!("Hello {}", greeting.name),
+            }))
+        })
+        .build()
+        .unwrap();
+
+    let serve = serve(router).await;
+    let resp = Client::new()
+        .request(
+            serve
+                .new_request("POST", "/greetings")
+                .header("content-type", "application/x-protobuf")
+                .body(Body::from(Greeting { name: "world".into() }.encode_to_vec()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.headers()["content-type"], "application/x-protobuf");
+    let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+    assert_eq!(Greeting::decode(body).unwrap().name, "Hello world");
+
+    let resp = Client::new()
+        .request(
+            serve
+                .new_request("POST", "/greetings")
+                .header("content-type", "application/json")
+                .body(Body::from("{}"))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+    let resp = Client::new()
+        .request(
+            serve
+                .new_request("POST", "/greetings")
+                .header("content-type", "application/x-protobuf")
+                .body(Body::from(vec![0u8; 128]))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    serve.shutdown();
+}
+
+#[cfg(all(feature = "json", feature = "msgpack"))]
+#[tokio::test]
+async fn can_negotiate_body_codecs() {
+    use routerify::ext::RequestBodyExt;
+    use routerify::responses;
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Greeting {
+        name: String,
+    }
+
+    let router: Router<Body, RouteError> = Router::builder()
+        .post("/greetings", |mut req| async move {
+            let greeting = req.decode::<Greeting>().await?;
+            let greeting = Greeting {
+                name: format!("Hello {}", greeting.name),
+            };
+            Ok(responses::negotiated(req.headers(), &greeting)?)
+        })
+        .build()
+        .unwrap();
+
+    let serve = serve(router).await;
+    let body = rmp_serde::to_vec_named(&Greeting { name: "world".into() }).unwrap();
+    let resp = Client::new()
+        .request(
+            serve
+                .new_request("POST", "/greetings")
+                .header("content-type", "application/msgpack")
+                .header("accept", "application/msgpack;q=0.5, application/json")
+                .body(Body::from(body))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.headers()["content-type"], "application/json");
+    assert_eq!(into_text(resp.into_body()).await, r#"{"name":"Hello world"}"#);
+
+    let resp = Client::new()
+        .request(
+            serve
+                .new_request("POST", "/greetings")
+                .header("content-type", "application/json")
+                .header("accept", "text/html")
+                .body(Body::from(r#"{"name":"world"}"#))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_ACCEPTABLE);
+
+    let resp = Client::new()
+        .request(
+            serve
+                .new_request("POST", "/greetings")
+                .header("content-type", "text/plain")
+                .body(Body::from("world"))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    serve.shutdown();
+}
+
+#[cfg(feature = "graphql")]
+#[tokio::test]
+async fn can_serve_graphql_scope() {
+    use async_graphql::{Context, EmptyMutation, EmptySubscription, Object, Schema};
+    use routerify::graphql::{GraphQL, RouterData};
+
+    struct Query;
+
+    #[Object]
+    impl Query {
+        async fn greeting(&self, ctx: &Context<'_>) -> String {
+            ctx.data_unchecked::<RouterData>().get::<String>().unwrap().clone()
+        }
+    }
+
+    let schema = Schema::new(Query, EmptyMutation, EmptySubscription);
+    let router: Router<Body, RouteError> = Router::builder()
+        .data("Hello world".to_owned())
+        .scope("/graphql", GraphQL::new(schema).graphiql(true).into_router())
+        .build()
+        .unwrap();
+
+    let serve = serve(router).await;
+    let resp = Client::new()
+        .request(
+            serve
+                .new_request("POST", "/graphql")
+                .header("content-type", "application/json")
+                .body(Body::from(r#"{"query":"{ greeting }"}"#))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        into_text(resp.into_body()).await,
+        r#"{"data":{"greeting":"Hello world"}}"#
+    );
+
+    let resp = Client::new()
+        .request(
+            serve
+                .new_request("GET", "/graphql?query=%7B%20greeting%20%7D")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        into_text(resp.into_body()).await,
+        r#"{"data":{"greeting":"Hello world"}}"#
+    );
+
+    let resp = Client::new()
+        .request(serve.new_request("GET", "/graphql").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert!(resp.headers()["content-type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/html"));
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_exchange_realtime_messages_over_long_polling() {
+    use routerify::realtime::Realtime;
+
+    let realtime = Realtime::new(|mut channel| async move {
+        channel.send("welcome");
+        while let Some(msg) = channel.recv().await {
+            channel.send(format!("echo: {}", msg));
+        }
+    })
+    .poll_timeout(std::time::Duration::from_millis(200));
+    let router: Router<Body, RouteError> = Router::builder()
+        .scope("/realtime", realtime.into_router())
+        .build()
+        .unwrap();
+
+    let serve = serve(router).await;
+    let resp = Client::new()
+        .request(
+            serve
+                .new_request("POST", "/realtime/negotiate?transports=websocket,polling")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let negotiation = into_text(resp.into_body()).await;
+    assert!(negotiation.contains(r#""transport":"polling""#));
+    let sid = negotiation.split('"').nth(3).unwrap().to_owned();
+    let poll_path = format!("/realtime/poll/{}", sid);
+
+    let poll = || async {
+        let resp = Client::new()
+            .request(serve.new_request("GET", &poll_path).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        into_text(resp.into_body()).await
+    };
+    assert_eq!(poll().await, r#"["welcome"]"#);
+
+    let resp = Client::new()
+        .request(serve.new_request("POST", &poll_path).body(Body::from("hi")).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    assert_eq!(poll().await, r#"["echo: hi"]"#);
+    assert_eq!(poll().await, "[]");
+
+    let resp = Client::new()
+        .request(
+            serve
+                .new_request("POST", "/realtime/negotiate?transports=sse")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    serve.shutdown();
+}
+
+#[cfg(feature = "client")]
+#[tokio::test]
+async fn can_propagate_request_context_to_outbound_requests() {
+    use routerify::client::{Client as HttpClient, Deadline};
+    use std::time::Duration;
+
+    let upstream: Router<Body, RouteError> = Router::builder()
+        .get("/echo", |req| async move {
+            let header = |name: &str| {
+                req.headers()
+                    .get(name)
+                    .map(|val| val.to_str().unwrap().to_owned())
+                    .unwrap_or_default()
+            };
+            let timeout = header("x-request-timeout-ms").parse::<u64>().unwrap_or(0);
+            Ok(Response::new(Body::from(format!(
+                "{}|{}|{}|{}",
+                header("x-request-id"),
+                header("traceparent"),
+                header("x-tenant-id"),
+                timeout > 0 && timeout <= 5000
+            ))))
+        })
+        .build()
+        .unwrap();
+    let upstream = serve(upstream).await;
+
+    let upstream_uri = format!("http://{}/echo", upstream.addr());
+    let router: Router<Body, RouteError> = Router::builder()
+        .data(HttpClient::new().propagate_header("x-tenant-id"))
+        .data(upstream_uri)
+        .get("/proxy", |req| async move {
+            req.set_context(Deadline::after(Duration::from_secs(5)));
+            let uri = req.data::<String>().unwrap().clone();
+            let resp = req.http_client().get(uri).await?;
+            Ok(Response::new(resp.into_body()))
+        })
+        .build()
+        .unwrap();
+
+    let serve = serve(router).await;
+    let resp = Client::new()
+        .request(
+            serve
+                .new_request("GET", "/proxy")
+                .header("x-request-id", "req-1")
+                .header("traceparent", "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01")
+                .header("x-tenant-id", "acme")
+                .header("authorization", "secret")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        into_text(resp.into_body()).await,
+        "req-1|00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01|acme|true"
+    );
+
+    serve.shutdown();
+    upstream.shutdown();
+}
+
+#[cfg(feature = "opentelemetry")]
+#[tokio::test]
+async fn can_extract_trace_context() {
+    use opentelemetry::trace::TraceContextExt;
+    use routerify::middleware::otel::OpenTelemetry;
+
+    let otel = OpenTelemetry::new();
+    let router: Router<Body, RouteError> = Router::builder()
+        .middleware(otel.pre_middleware())
+        .middleware(otel.post_middleware())
+        .get("/users/:id", |req| async move {
+            let cx = req.trace_context().unwrap();
+            let trace_id = cx.span().span_context().trace_id().to_string();
+            Ok(Response::new(Body::from(trace_id)))
+        })
+        .build()
+        .unwrap();
+
+    let serve = serve(router).await;
+    let resp = Client::new()
+        .request(
+            serve
+                .new_request("GET", "/users/1")
+                .header("traceparent", "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(into_text(resp.into_body()).await, "0af7651916cd43dd8448eb211c80319c");
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_negotiate_locale() {
+    use routerify::middleware::locale::{Locale, LocaleNegotiation};
+
+    let locales = LocaleNegotiation::new(vec!["en", "de-DE"])
+        .cookie("lang")
+        .query_param("lang");
+    let router: Router<Body, RouteError> = Router::builder()
+        .middleware(locales.pre_middleware())
+        .middleware(locales.post_middleware())
+        .get("/", |req| async move {
+            Ok(Response::new(Body::from(req.context::<Locale>().unwrap().to_string())))
+        })
+        .build()
+        .unwrap();
+
+    let serve = serve(router).await;
+    let get = |path: &'static str, header: (&'static str, &'static str)| {
+        Client::new().request(
+            serve
+                .new_request("GET", path)
+                .header(header.0, header.1)
+                .body(Body::empty())
+                .unwrap(),
+        )
+    };
+
+    let resp = get("/", ("accept-language", "fr, de;q=0.8, en;q=0.5")).await.unwrap();
+    assert_eq!(resp.headers()["content-language"], "de-DE");
+    assert_eq!(resp.headers()["vary"], "accept-language");
+    assert_eq!(into_text(resp.into_body()).await, "de-DE");
+
+    let resp = get("/", ("cookie", "theme=dark; lang=en")).await.unwrap();
+    assert_eq!(into_text(resp.into_body()).await, "en");
+
+    let resp = get("/?lang=de", ("cookie", "lang=en")).await.unwrap();
+    assert_eq!(into_text(resp.into_body()).await, "de-DE");
+
+    let resp = get("/", ("accept-language", "ja")).await.unwrap();
+    assert_eq!(into_text(resp.into_body()).await, "en");
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_evaluate_feature_flags_once_per_request() {
+    use routerify::middleware::feature_flags::{self, FeatureFlags, FlagFuture, FlagSet};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct BetaHeader(Arc<AtomicUsize>);
+
+    impl FeatureFlags for BetaHeader {
+        fn evaluate<'a>(&'a self, req_info: &'a RequestInfo) -> FlagFuture<'a> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async move { FlagSet::new().with("new_checkout", req_info.headers().contains_key("x-beta")) })
+        }
+    }
+
+    let evaluations = Arc::new(AtomicUsize::new(0));
+    let router: Router<Body, RouteError> = Router::builder()
+        .middleware(feature_flags::middleware(BetaHeader(evaluations.clone())))
+        .middleware(feature_flags::middleware(FlagSet::new().with("new_checkout", false)))
+        .middleware(Middleware::post_with_info(|mut res, req_info| async move {
+            let flags = req_info.context::<FlagSet>().unwrap();
+            res.headers_mut()
+                .insert("x-flags", flags.iter().count().to_string().parse().unwrap());
+            Ok(res)
+        }))
+        .get("/checkout", |req| async move {
+            let page = if req.flag("new_checkout") {
+                "New checkout"
+            } else {
+                "Checkout"
+            };
+            Ok(Response::new(Body::from(page)))
+        })
+        .build()
+        .unwrap();
+
+    let serve = serve(router).await;
+    let resp = Client::new()
+        .request(
+            serve
+                .new_request("GET", "/checkout")
+                .header("x-beta", "1")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.headers()["x-flags"], "1");
+    assert_eq!(into_text(resp.into_body()).await, "New checkout");
+
+    let resp = Client::new()
+        .request(serve.new_request("GET", "/checkout").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(into_text(resp.into_body()).await, "Checkout");
+    assert_eq!(evaluations.load(Ordering::SeqCst), 2);
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_split_traffic_between_handlers() {
+    use routerify::split::{Split, StickyCookie, Variant};
+
+    let router: Router<Body, RouteError> = Router::builder()
+        .get_split(
+            "/search",
+            0,
+            |_| async move { Ok(Response::new(Body::from("Old search"))) },
+            10,
+            |_| async move { Ok(Response::new(Body::from("New search"))) },
+        )
+        .add_split(
+            "/checkout",
+            vec![Method::POST],
+            Split::new()
+                .variant("v1", 50, |_| async move { Ok(Response::new(Body::from("v1"))) })
+                .variant("v2", 50, |_| async move { Ok(Response::new(Body::from("v2"))) })
+                .decider(StickyCookie::new("checkout_variant")),
+        )
+        .middleware(Middleware::post_with_info(|mut res, req_info| async move {
+            let variant = req_info.context::<Variant>().unwrap();
+            res.headers_mut().insert("x-variant", variant.name().parse().unwrap());
+            Ok(res)
+        }))
+        .build()
+        .unwrap();
+
+    let serve = serve(router).await;
+    let resp = Client::new()
+        .request(serve.new_request("GET", "/search").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(resp.headers()["x-variant"], "canary");
+    assert_eq!(into_text(resp.into_body()).await, "New search");
+
+    for variant in &["v1", "v2"] {
+        let resp = Client::new()
+            .request(
+                serve
+                    .new_request("POST", "/checkout")
+                    .header("cookie", format!("checkout_variant={}", variant))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.headers()["x-variant"], *variant);
+        assert!(resp.headers()["set-cookie"]
+            .to_str()
+            .unwrap()
+            .starts_with(&format!("checkout_variant={};", variant)));
+        assert_eq!(into_text(resp.into_body()).await, *variant);
+    }
+    serve.shutdown();
+
+    let result = Router::<Body, RouteError>::builder()
+        .get_split(
+            "/",
+            0,
+            |_| async move { Ok(Response::new(Body::empty())) },
+            0,
+            |_| async move { Ok(Response::new(Body::empty())) },
+        )
+        .build();
+    assert!(result.is_err());
+}
+
+#[cfg(feature = "html-rewrite")]
+#[tokio::test]
+async fn can_rewrite_html_responses() {
+    use routerify::middleware::html_rewrite::HtmlRewrite;
+
+    let rewrite = HtmlRewrite::new()
+        .inject_head(r#"<link rel="stylesheet" href="/theme.css">"#)
+        .inject_body_end("<script>reload()</script>")
+        .prefix_links("/app/");
+    let router: Router<Body, RouteError> = Router::builder()
+        .middleware(rewrite.middleware())
+        .get("/", |_| async move {
+            let chunks: Vec<Result<_, io::Error>> = vec![
+                Ok("<html><head></head><body><a href=\"/ab"),
+                Ok("out\">About</a><img src=\"//cdn/x.png\"></bo"),
+                Ok("dy></html>"),
+            ];
+            Ok(Response::builder()
+                .header("content-type", "text/html; charset=utf-8")
+                .body(Body::wrap_stream(futures::stream::iter(chunks)))
+                .unwrap())
+        })
+        .get("/data", |_| async move {
+            Ok(Response::builder()
+                .header("content-type", "application/json")
+                .header("content-length", "17")
+                .body(Body::from(r#"{"href":"/about"}"#))
+                .unwrap())
+        })
+        .build()
+        .unwrap();
+
+    let serve = serve(router).await;
+    let resp = Client::new()
+        .request(serve.new_request("GET", "/").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(
+        into_text(resp.into_body()).await,
+        "<html><head><link rel=\"stylesheet\" href=\"/theme.css\"></head><body><a href=\"/app/about\">About</a><img src=\"//cdn/x.png\"><script>reload()</script></body></html>"
+    );
+
+    let resp = Client::new()
+        .request(serve.new_request("GET", "/data").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(resp.headers()["content-length"], "17");
+    assert_eq!(into_text(resp.into_body()).await, r#"{"href":"/about"}"#);
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_serve_under_base_path() {
+    let router: Router<Body, RouteError> = Router::builder()
+        .base_path("/myapp/")
+        .get("/", |req| async move {
+            Ok(Response::new(Body::from(format!(
+                "{:?} {}",
+                req.base_path(),
+                req.url_for("/about")
+            ))))
+        })
+        .get("/account", |req| async move {
+            routerify::responses::redirect(&req, "/login")
+        })
+        .get("/healthz", |req| async move {
+            Ok(Response::new(Body::from(format!(
+                "{:?} {}",
+                req.base_path(),
+                req.url_for("/")
+            ))))
+        })
+        .build()
+        .unwrap();
+
+    let serve = serve(router).await;
+    let resp = Client::new()
+        .request(serve.new_request("GET", "/myapp").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(into_text(resp.into_body()).await, "Some(\"/myapp\") /myapp/about");
+
+    let resp = Client::new()
+        .request(serve.new_request("GET", "/myapp/account").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::FOUND);
+    assert_eq!(resp.headers()["location"], "/myapp/login");
+
+    let resp = Client::new()
+        .request(serve.new_request("GET", "/healthz").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(into_text(resp.into_body()).await, "None /");
+
+    let resp = Client::new()
+        .request(serve.new_request("GET", "/myapplication/").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_render_error_responses_by_scope_templates() {
+    use routerify::responses::ResponseTemplates;
+
+    let api: Router<Body, RouteError> = Router::builder()
+        .data(ResponseTemplates::json())
+        .get(
+            "/fail",
+            |_| async move { Err(io::Error::other("Broken \"pipe\"").into()) },
+        )
+        .build()
+        .unwrap();
+    let router: Router<Body, RouteError> = Router::builder()
+        .data(ResponseTemplates::html(|status, message| {
+            format!("<h1>{}</h1><p>{}</p>", status.as_u16(), message)
+        }))
+        .get("/fail", |_| async move { Err(io::Error::other("<oops>").into()) })
+        .scope("/api", api)
+        .build()
+        .unwrap();
+
+    let serve = serve(router).await;
+    let request =
+        |path: &'static str| Client::new().request(serve.new_request("GET", path).body(Body::empty()).unwrap());
+
+    let resp = request("/api/missing").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    assert_eq!(resp.headers()["content-type"], "application/json");
+    assert_eq!(into_text(resp.into_body()).await, r#"{"error":"Not Found"}"#);
+
+    let resp = request("/api/fail").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(into_text(resp.into_body()).await, r#"{"error":"Broken \"pipe\""}"#);
+
+    let resp = request("/missing").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    assert_eq!(resp.headers()["content-type"], "text/html; charset=utf-8");
+    assert_eq!(into_text(resp.into_body()).await, "<h1>404</h1><p>Not Found</p>");
+
+    let resp = request("/fail").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(into_text(resp.into_body()).await, "<h1>500</h1><p>&lt;oops&gt;</p>");
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_wrap_route_handlers_with_around_middlewares() {
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let attempts = Arc::new(Mutex::new(0));
+
+    let (calls1, calls2, calls3, calls4) = (calls.clone(), calls.clone(), calls.clone(), calls.clone());
+    let attempts1 = attempts.clone();
+    let router: Router<Body, RouteError> = Router::builder()
+        .middleware(Middleware::pre(move |req| {
+            calls1.lock().unwrap().push("pre");
+            async move { Ok(req) }
+        }))
+        .middleware(Middleware::post(move |res| {
+            calls2.lock().unwrap().push("post");
+            async move { Ok(res) }
+        }))
+        .middleware(Middleware::around(move |req, next| {
+            let calls = calls3.clone();
+            async move {
+                calls.lock().unwrap().push("around:before");
+                let res = next.run(req).await;
+                calls.lock().unwrap().push("around:after");
+                // Converts the errors of the handler into responses.
+                res.or_else(|err| {
+                    Ok(Response::builder()
+                        .status(StatusCode::SERVICE_UNAVAILABLE)
+                        .body(Body::from(err.to_string()))
+                        .unwrap())
+                })
+            }
+        }))
+        .middleware(
+            Middleware::around_with_path("/flaky/*", |req: Request<Body>, next| async move {
+                // Retries the request once.
+                let retry = Request::builder().uri(req.uri().clone()).body(Body::empty()).unwrap();
+                match next.run(req).await {
+                    Ok(res) => Ok(res),
+                    Err(_) => next.run(retry).await,
+                }
+            })
+            .unwrap(),
+        )
+        .get("/", move |_| {
+            calls4.lock().unwrap().push("handler");
+            async move { Ok(Response::new(Body::from("Home"))) }
+        })
+        .get("/fail", |_| async move { Err(io::Error::other("Down").into()) })
+        .get("/flaky", move |_| {
+            let attempts = attempts1.clone();
+            async move {
+                let mut attempts = attempts.lock().unwrap();
+                *attempts += 1;
+                if *attempts == 1 {
+                    return Err(io::Error::other("Flaky").into());
+                }
+                Ok(Response::new(Body::from(format!("Attempt {}", attempts))))
+            }
+        })
+        .build()
+        .unwrap();
+
+    let order = router.middleware_order(&Method::GET, "/flaky");
+    assert_eq!(order.len(), 4);
+    assert_eq!(order[1].kind(), routerify::MiddlewareKind::Around);
+
+    let serve = serve(router).await;
+    let request =
+        |path: &'static str| Client::new().request(serve.new_request("GET", path).body(Body::empty()).unwrap());
+
+    let resp = request("/").await.unwrap();
+    assert_eq!(into_text(resp.into_body()).await, "Home");
+    assert_eq!(
+        *calls.lock().unwrap(),
+        vec!["pre", "around:before", "handler", "around:after", "post"]
+    );
+
+    let resp = request("/fail").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    let resp = request("/flaky").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(into_text(resp.into_body()).await, "Attempt 2");
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_run_requests_in_transactions() {
+    use routerify::middleware::transactional::{TransactionManager, Transactional, TxFuture};
+
+    #[derive(Clone)]
+    struct Tx(u32);
+
+    struct Db(Arc<Mutex<Vec<String>>>);
+
+    impl TransactionManager for Db {
+        type Transaction = Tx;
+
+        fn begin(&self) -> TxFuture<'_, Tx> {
+            Box::pin(async move {
+                let mut events = self.0.lock().unwrap();
+                events.push("begin".to_owned());
+                Ok(Tx(events.len() as u32))
+            })
+        }
+
+        fn commit(&self, tx: Tx) -> TxFuture<'_, ()> {
+            Box::pin(async move {
+                self.0.lock().unwrap().push(format!("commit {}", tx.0));
+                Ok(())
+            })
+        }
+
+        fn rollback(&self, tx: Tx) -> TxFuture<'_, ()> {
+            Box::pin(async move {
+                self.0.lock().unwrap().push(format!("rollback {}", tx.0));
+                Ok(())
+            })
+        }
+    }
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let router: Router<Body, RouteError> = Router::builder()
+        .middleware(Transactional::new(Db(events.clone())).middleware())
+        .get("/ok", |req| async move {
+            let tx = req.extensions().get::<Tx>().unwrap();
+            Ok(Response::new(Body::from(format!("Tx {}", tx.0))))
+        })
+        .get("/conflict", |_| async move {
+            Ok(Response::builder()
+                .status(StatusCode::CONFLICT)
+                .body(Body::empty())
+                .unwrap())
+        })
+        .get("/fail", |_| async move { Err(io::Error::other("Failed").into()) })
+        .build()
+        .unwrap();
+
+    let serve = serve(router).await;
+    let request =
+        |path: &'static str| Client::new().request(serve.new_request("GET", path).body(Body::empty()).unwrap());
+
+    let resp = request("/ok").await.unwrap();
+    assert_eq!(into_text(resp.into_body()).await, "Tx 1");
+    let resp = request("/conflict").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    let resp = request("/fail").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec!["begin", "commit 1", "begin", "rollback 3", "begin", "rollback 5"]
+    );
+    serve.shutdown();
+}
+
+#[test]
+fn can_verify_middleware_dependencies() {
+    use routerify::{Principal, RequestServiceBuilder};
+
+    fn auth() -> Middleware<Body, RouteError> {
+        Middleware::pre(|req: Request<Body>| async move {
+            req.set_context(Principal::new("u1"));
+            Ok(req)
+        })
+        .provides::<Principal>()
+    }
+
+    fn throttle() -> Middleware<Body, RouteError> {
+        Middleware::pre(|req| async move { Ok(req) })
+            .named("throttle")
+            .requires::<Principal>()
+    }
+
+    let router: Router<Body, RouteError> = Router::builder()
+        .middleware(Middleware::post(|res| async move { Ok(res) }).requires::<Principal>())
+        .middleware(auth())
+        .middleware(throttle())
+        .build()
+        .unwrap();
+    assert!(RequestServiceBuilder::new(router).is_ok());
+
+    let router: Router<Body, RouteError> = Router::builder()
+        .middleware(auth())
+        .middleware_with_priority(throttle(), 10)
+        .build()
+        .unwrap();
+    let err = RequestServiceBuilder::new(router).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("The pre middleware \"throttle\" at \"/*\" requires"));
+
+    let api: Router<Body, RouteError> = Router::builder().middleware(auth()).build().unwrap();
+    let router: Router<Body, RouteError> = Router::builder()
+        .scope("/api", api)
+        .middleware(throttle())
+        .build()
+        .unwrap();
+    assert!(RequestServiceBuilder::new(router).is_err());
+}
+
+#[tokio::test]
+async fn can_handle_errors_with_the_request_context() {
+    #[derive(Clone)]
+    struct RequestId(&'static str);
+
+    let router: Router<Body, RouteError> = Router::builder()
+        .middleware(Middleware::pre(|req: Request<Body>| async move {
+            req.set_context(RequestId("req-1"));
+            Ok(req)
+        }))
+        .get("/fail", |_| async move { Err(io::Error::other("boom").into()) })
+        .err_handler_with_ctx(|err, ctx: ErrorContext| async move {
+            let request_id = ctx.get::<RequestId>().map(|id| id.0).unwrap_or("none");
+            Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(Body::from(format!(
+                    "{} {} {}",
+                    request_id,
+                    ctx.req_info().uri().path(),
+                    err
+                )))
+                .unwrap()
+        })
+        .build()
+        .unwrap();
+
+    let serve = serve(router).await;
+    let resp = Client::new()
+        .request(serve.new_request("GET", "/fail").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(into_text(resp.into_body()).await, "req-1 /fail boom");
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_answer_expect_continue_by_policy() {
+    use routerify::middleware::expect_continue::{ContinueDecision, ExpectContinue};
+    use routerify::HttpError;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    async fn read_head(stream: &mut TcpStream) -> String {
+        let mut buf = vec![0; 1024];
+        let len = stream.read(&mut buf).await.unwrap();
+        String::from_utf8_lossy(&buf[..len]).to_string()
+    }
+
+    let policy = ExpectContinue::new(|req| {
+        if req.headers().contains_key("authorization") {
+            ContinueDecision::Continue
+        } else {
+            ContinueDecision::Reject(HttpError::new(StatusCode::UNAUTHORIZED, "Unauthorized"))
+        }
+    });
+    let router: Router<Body, RouteError> = Router::builder()
+        .middleware(policy.middleware_with_path("/upload/*").unwrap())
+        .post("/upload", |req| async move {
+            // The body is read after the 100 Continue response has already been sent.
+            tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+            let body = hyper::body::to_bytes(req.into_body()).await?;
+            Ok(Response::new(Body::from(body)))
+        })
+        .build()
+        .unwrap();
+    let serve = serve(router).await;
+
+    let mut stream = TcpStream::connect(serve.addr()).await.unwrap();
+    stream
+        .write_all(b"POST /upload HTTP/1.1\r\nhost: localhost\r\nexpect: 100-continue\r\ncontent-length: 5\r\n\r\n")
+        .await
+        .unwrap();
+    assert!(read_head(&mut stream).await.starts_with("HTTP/1.1 401 Unauthorized"));
+
+    let mut stream = TcpStream::connect(serve.addr()).await.unwrap();
+    stream
+        .write_all(b"POST /upload HTTP/1.1\r\nhost: localhost\r\nauthorization: Bearer t\r\nexpect: 100-continue\r\ncontent-length: 5\r\nconnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let head = tokio::time::timeout(std::time::Duration::from_millis(250), read_head(&mut stream))
+        .await
+        .unwrap();
+    assert!(head.starts_with("HTTP/1.1 100 Continue"));
+    stream.write_all(b"hello").await.unwrap();
+    let mut resp = String::new();
+    stream.read_to_string(&mut resp).await.unwrap();
+    assert!(resp.starts_with("HTTP/1.1 200 OK"));
+    assert!(resp.ends_with("hello"));
+
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_emit_deprecation_headers() {
+    use routerify::Deprecation;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let v1: Router<Body, RouteError> = Router::builder()
+        .get("/users", |_| async move { Ok(Response::new(Body::from("v1"))) })
+        .deprecated(
+            "/users",
+            Deprecation::new(UNIX_EPOCH + Duration::from_secs(1_688_169_599))
+                .with_sunset(UNIX_EPOCH + Duration::from_secs(1_719_791_999))
+                .with_link("https://example.com/migrate"),
+        )
+        .build()
+        .unwrap();
+    let router: Router<Body, RouteError> = Router::builder()
+        .scope("/v1", v1)
+        .get("/v2/users", |_| async move { Ok(Response::new(Body::from("v2"))) })
+        .build()
+        .unwrap();
+
+    assert!(router.deprecation(&Method::GET, "/v1/users").is_some());
+    assert!(router.deprecation(&Method::GET, "/v2/users").is_none());
+
+    let serve = serve(router).await;
+    let resp = Client::new()
+        .request(serve.new_request("GET", "/v1/users").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(resp.headers()["deprecation"], "@1688169599");
+    assert_eq!(resp.headers()["sunset"], "Sun, 30 Jun 2024 23:59:59 GMT");
+    assert_eq!(
+        resp.headers()["link"],
+        "<https://example.com/migrate>; rel=\"deprecation\""
+    );
+
+    let resp = Client::new()
+        .request(serve.new_request("GET", "/v2/users").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert!(!resp.headers().contains_key("deprecation"));
+    serve.shutdown();
+
+    let res: routerify::Result<Router<Body, RouteError>> = Router::builder()
+        .deprecated("/missing", Deprecation::new(UNIX_EPOCH))
+        .build();
+    assert!(res.is_err());
+}
+
+#[tokio::test]
+async fn can_filter_requests_by_host() {
+    use routerify::middleware::host_filter::HostFilter;
+
+    let router: Router<Body, RouteError> = Router::builder()
+        .middleware(HostFilter::new().allow("*.example.com").allow("127.0.0.1").middleware())
+        .get("/", |_| async move { Ok(Response::new(Body::from("Home"))) })
+        .build()
+        .unwrap();
+    let serve = serve(router).await;
+
+    let request = |host: &'static str| {
+        let serve = &serve;
+        async move {
+            Client::new()
+                .request(
+                    serve
+                        .new_request("GET", "/")
+                        .header("host", host)
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap()
+                .status()
+        }
+    };
+
+    assert_eq!(request("api.example.com").await, StatusCode::OK);
+    assert_eq!(request("127.0.0.1:8080").await, StatusCode::OK);
+    assert_eq!(request("attacker.com").await, StatusCode::MISDIRECTED_REQUEST);
+    assert_eq!(request("bad host").await, StatusCode::BAD_REQUEST);
+
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_reject_requests_with_header_anomalies() {
+    use routerify::middleware::header_sanity::{Anomaly, HeaderSanity};
+
+    let sanity = HeaderSanity::new().max_headers(10);
+    let router: Router<Body, RouteError> = Router::builder()
+        .middleware(sanity.middleware())
+        .get("/*", |_| async move { Ok(Response::new(Body::from("Home"))) })
+        .build()
+        .unwrap();
+    let serve = serve(router).await;
+
+    let resp = Client::new()
+        .request(serve.new_request("GET", "/files/a%00.txt").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let mut req = serve.new_request("GET", "/");
+    for idx in 0..20 {
+        req = req.header(format!("x-header-{}", idx).as_str(), "val");
+    }
+    let resp = Client::new().request(req.body(Body::empty()).unwrap()).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE);
+
+    let resp = Client::new()
+        .request(serve.new_request("GET", "/files/a.txt").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    assert_eq!(sanity.rejects(Anomaly::ControlCharInPath), 1);
+    assert_eq!(sanity.rejects(Anomaly::TooManyHeaders), 1);
+    assert_eq!(sanity.total_rejects(), 2);
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_guard_routes_and_scopes() {
+    use http::request::Parts;
+    use routerify::guard::{Guard, GuardOutcome};
+    use routerify::HttpError;
+
+    fn header(name: &'static str) -> impl Guard {
+        move |req: &Parts| match req.headers.contains_key(name) {
+            true => GuardOutcome::Allow,
+            false => GuardOutcome::Deny(HttpError::new(StatusCode::FORBIDDEN, "Forbidden")),
+        }
+    }
+
+    let admin: Router<Body, RouteError> = Router::builder()
+        .get("/stats", |_| async move { Ok(Response::new(Body::from("Stats"))) })
+        .guard("/*", header("x-admin"))
+        .build()
+        .unwrap();
+    let router: Router<Body, RouteError> = Router::builder()
+        .scope("/admin", admin)
+        .get("/users", |_| async move { Ok(Response::new(Body::from("Users"))) })
+        .get("/health", |_| async move { Ok(Response::new(Body::from("OK"))) })
+        .guard("/users", header("x-user").or(header("x-admin")))
+        .build()
+        .unwrap();
+    let serve = serve(router).await;
+
+    for (path, header, status) in [
+        ("/admin/stats", None, StatusCode::FORBIDDEN),
+        ("/admin/stats", Some("x-admin"), StatusCode::OK),
+        ("/users", None, StatusCode::FORBIDDEN),
+        ("/users", Some("x-admin"), StatusCode::OK),
+        ("/health", None, StatusCode::OK),
+    ] {
+        let mut req = serve.new_request("GET", path);
+        if let Some(header) = header {
+            req = req.header(header, "1");
+        }
+        let resp = Client::new().request(req.body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(resp.status(), status, "{} {:?}", path, header);
+    }
+    serve.shutdown();
+
+    let res: routerify::Result<Router<Body, RouteError>> =
+        Router::builder().guard("/missing", header("x-admin")).build();
+    assert!(res.is_err());
+}
+
+#[cfg(feature = "multipart")]
+#[tokio::test]
+async fn can_store_multipart_uploads() {
+    use routerify::uploads::{LocalStorage, UploadConfig};
+
+    let dir = std::env::temp_dir().join(format!("routerify-uploads-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let router: Router<Body, RouteError> = Router::builder()
+        .upload(
+            "/files",
+            UploadConfig {
+                max_size: 16,
+                allowed_types: vec!["text/*".to_owned()],
+                storage: Arc::new(LocalStorage::new(&dir)),
+            },
+        )
+        .build()
+        .unwrap();
+    let serve = serve(router).await;
+
+    let upload = |content_type: &'static str, content: &'static str| {
+        let body = format!(
+            "--XYZ\r\nContent-Disposition: form-data; name=\"title\"\r\n\r\nNotes\r\n\
+             --XYZ\r\nContent-Disposition: form-data; name=\"file\"; filename=\"notes.txt\"\r\n\
+             Content-Type: {}\r\n\r\n{}\r\n--XYZ--\r\n",
+            content_type, content
+        );
+        serve
+            .new_request("POST", "/files")
+            .header("content-type", "multipart/form-data; boundary=XYZ")
+            .body(Body::from(body))
+            .unwrap()
+    };
+
+    let resp = Client::new().request(upload("text/plain", "Hello")).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let resp = into_text(resp.into_body()).await;
+    assert!(resp.starts_with(
+        "{\"files\":[{\"field\":\"file\",\"filename\":\"notes.txt\",\"content_type\":\"text/plain\",\"size\":5,"
+    ));
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+    let resp = Client::new().request(upload("image/png", "Hello")).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    let resp = Client::new()
+        .request(upload("text/plain", "Hello, this is too long"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+    serve.shutdown();
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "embed")]
+#[tokio::test]
+async fn can_serve_embedded_assets() {
+    use routerify::responses::EmbeddedAssets;
+    use std::borrow::Cow;
+
+    struct Assets;
+
+    impl EmbeddedAssets for Assets {
+        fn get(&self, path: &str) -> Option<Cow<'static, [u8]>> {
+            match path {
+                "index.html" => Some(Cow::Borrowed(b"<h1>Home</h1>")),
+                "css/site.css" => Some(Cow::Borrowed(b"body {}")),
+                "css/site.css.gz" => Some(Cow::Borrowed(b"gzipped")),
+                _ => None,
+            }
+        }
+    }
+
+    static ASSETS: Assets = Assets;
+    let router: Router<Body, RouteError> = Router::builder().serve_embedded("/assets", &ASSETS).build().unwrap();
+    let serve = serve(router).await;
+
+    let resp = Client::new()
+        .request(
+            serve
+                .new_request("GET", "/assets/css/site.css")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers()["content-type"], "text/css");
+    let etag = resp.headers()["etag"].clone();
+    assert_eq!(into_text(resp.into_body()).await, "body {}");
+
+    let resp = Client::new()
+        .request(
+            serve
+                .new_request("GET", "/assets/css/site.css")
+                .header("accept-encoding", "br;q=0, gzip")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.headers()["content-encoding"], "gzip");
+    assert_ne!(resp.headers()["etag"], etag);
+    assert_eq!(into_text(resp.into_body()).await, "gzipped");
+
+    let resp = Client::new()
+        .request(
+            serve
+                .new_request("GET", "/assets/css/site.css")
+                .header("if-none-match", etag)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+
+    let resp = Client::new()
+        .request(serve.new_request("GET", "/assets/").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(into_text(resp.into_body()).await, "<h1>Home</h1>");
+
+    let resp = Client::new()
+        .request(
+            serve
+                .new_request("GET", "/assets/missing.js")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    serve.shutdown();
+}
+
+#[cfg(feature = "fs")]
+#[tokio::test]
+async fn can_serve_single_page_apps() {
+    use routerify::responses::Spa;
+
+    let dir = std::env::temp_dir().join(format!("routerify-spa-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("assets")).unwrap();
+    std::fs::write(dir.join("index.html"), "<div id=\"app\"></div>").unwrap();
+    std::fs::write(dir.join("assets/app.js"), "render()").unwrap();
+
+    let router: Router<Body, RouteError> = Router::builder()
+        .get("/app/api/users", |_| async move { Ok(Response::new(Body::from("[]"))) })
+        .spa("/app", Spa::new(&dir).exclude("/app/api"))
+        .build()
+        .unwrap();
+    let serve = serve(router).await;
+
+    for (path, status, body) in [
+        ("/app/assets/app.js", StatusCode::OK, "render()"),
+        ("/app/users/42/edit", StatusCode::OK, "<div id=\"app\"></div>"),
+        ("/app", StatusCode::OK, "<div id=\"app\"></div>"),
+        ("/app/api/users", StatusCode::OK, "[]"),
+        ("/app/api/missing", StatusCode::NOT_FOUND, "Not Found: Not Found"),
+    ] {
+        let resp = Client::new()
+            .request(serve.new_request("GET", path).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), status, "{}", path);
+        assert_eq!(into_text(resp.into_body()).await, body, "{}", path);
+    }
+    serve.shutdown();
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "fs")]
+#[tokio::test]
+async fn can_serve_directory_listings() {
+    use http::request::Parts;
+    use routerify::guard::GuardOutcome;
+    use routerify::responses::StaticDir;
+    use routerify::HttpError;
+
+    let dir = std::env::temp_dir().join(format!("routerify-listing-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("docs")).unwrap();
+    std::fs::write(dir.join("docs/a.txt"), "Hello").unwrap();
+    std::fs::write(dir.join("docs/b c.txt"), "Hello, world").unwrap();
+    std::fs::write(dir.join("docs/.secret"), "").unwrap();
+
+    let guard = |req: &Parts| match req.headers.contains_key("authorization") {
+        true => GuardOutcome::Allow,
+        false => GuardOutcome::Deny(HttpError::new(StatusCode::UNAUTHORIZED, "Unauthorized")),
+    };
+    let router: Router<Body, RouteError> = Router::builder()
+        .serve_dir("/public", StaticDir::new(&dir))
+        .serve_dir("/files", StaticDir::new(&dir).listings(true).guard(guard))
+        .build()
+        .unwrap();
+    let serve = serve(router).await;
+
+    let request = |path: &str, auth: bool| {
+        let mut req = serve.new_request("GET", path);
+        if auth {
+            req = req.header("authorization", "Bearer token");
+        }
+        Client::new().request(req.body(Body::empty()).unwrap())
+    };
+
+    let resp = request("/public/docs/a.txt", false).await.unwrap();
+    assert_eq!(into_text(resp.into_body()).await, "Hello");
+    assert_eq!(
+        request("/public/docs/", true).await.unwrap().status(),
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        request("/files/docs/", false).await.unwrap().status(),
+        StatusCode::UNAUTHORIZED
+    );
+
+    let resp = request("/files/docs", true).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::MOVED_PERMANENTLY);
+    assert_eq!(resp.headers()["location"], "docs/");
+
+    let resp = request("/files/docs/", true).await.unwrap();
+    assert_eq!(resp.headers()["content-type"], "text/html; charset=utf-8");
+    let html = into_text(resp.into_body()).await;
+    assert!(html.contains("<a href=\"b%20c.txt\">b c.txt</a>"));
+    assert!(!html.contains(".secret"));
+
+    let resp = request("/files/docs/?format=json&sort=size&order=desc", true)
+        .await
+        .unwrap();
+    assert_eq!(resp.headers()["content-type"], "application/json");
+    let json = into_text(resp.into_body()).await;
+    assert!(json.starts_with("{\"path\":\"/docs/\",\"entries\":[{\"name\":\"b c.txt\",\"type\":\"file\",\"size\":12,"));
+    assert!(json.contains("{\"name\":\"a.txt\",\"type\":\"file\",\"size\":5,"));
+
+    serve.shutdown();
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn can_coalesce_concurrent_requests() {
+    use routerify::middleware::coalesce::Coalesce;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    let router: Router<Body, RouteError> = Router::builder()
+        .middleware(Coalesce::new().middleware())
+        .get("/report", move |req| {
+            let counter = counter.clone();
+            async move {
+                let call = counter.fetch_add(1, Ordering::SeqCst) + 1;
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                Ok(Response::new(Body::from(format!("{} {}", req.uri(), call))))
+            }
+        })
+        .build()
+        .unwrap();
+    let serve = serve(router).await;
+
+    let request = |path: &str, user: &str| {
+        let req = serve.new_request("GET", path).header("authorization", user);
+        let res = Client::new().request(req.body(Body::empty()).unwrap());
+        async move { into_text(res.await.unwrap().into_body()).await }
+    };
+
+    let bodies = futures::future::join_all((0..5).map(|_| request("/report", "a"))).await;
+    assert!(bodies.iter().all(|body| body == "/report 1"), "{:?}", bodies);
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    let (a, b) = tokio::join!(request("/report", "a"), request("/report", "b"));
+    assert_ne!(a, b);
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_serve_stale_cached_responses() {
+    use routerify::cache::{CachePolicy, ResponseCache};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    let cache = ResponseCache::new();
+    let policy = CachePolicy::new(Duration::from_millis(100))
+        .stale_while_revalidate(Duration::from_millis(200))
+        .stale_if_error(Duration::from_secs(60));
+    let router: Router<Body, RouteError> = Router::builder()
+        .get(
+            "/prices/:id",
+            cache.cached(policy, move |req| {
+                let call = counter.fetch_add(1, Ordering::SeqCst) + 1;
+                async move {
+                    match call {
+                        4 => Err(routerify::HttpError::new(StatusCode::BAD_GATEWAY, "Upstream is down").into()),
+                        _ => Ok(Response::new(Body::from(format!(
+                            "{} v{}",
+                            req.param("id").unwrap(),
+                            call
+                        )))),
+                    }
+                }
+            }),
+        )
+        .build()
+        .unwrap();
+    let serve = serve(router).await;
+
+    let request = || async {
+        let req = serve.new_request("GET", "/prices/7").body(Body::empty()).unwrap();
+        let resp = Client::new().request(req).await.unwrap();
+        (resp.status(), into_text(resp.into_body()).await)
+    };
+
+    assert_eq!(request().await, (StatusCode::OK, "7 v1".to_owned()));
+    assert_eq!(request().await, (StatusCode::OK, "7 v1".to_owned()));
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    // The stale response is served while it's refreshed in the background.
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert_eq!(request().await, (StatusCode::OK, "7 v1".to_owned()));
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+    assert_eq!(request().await, (StatusCode::OK, "7 v2".to_owned()));
+
+    // Past the stale-while-revalidate period, the handler is called and its failure is covered by the stale response.
+    tokio::time::sleep(Duration::from_millis(350)).await;
+    assert_eq!(request().await, (StatusCode::OK, "7 v3".to_owned()));
+    tokio::time::sleep(Duration::from_millis(350)).await;
+    assert_eq!(request().await, (StatusCode::OK, "7 v3".to_owned()));
+    assert_eq!(calls.load(Ordering::SeqCst), 4);
+
+    cache.invalidate("/prices/7");
+    assert_eq!(request().await, (StatusCode::OK, "7 v5".to_owned()));
+
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_send_the_router_fingerprint() {
+    fn build(extra_route: bool) -> Router<Body, RouteError> {
+        let mut builder = Router::builder()
+            .fingerprint_header("x-router-fingerprint")
+            .middleware(Middleware::pre(|req| async move { Ok(req) }).named("auth"))
+            .get("/users/:id", |_| async move { Ok(Response::new(Body::from("User"))) });
+        if extra_route {
+            builder = builder.delete(
+                "/users/:id",
+                |_| async move { Ok(Response::new(Body::from("Deleted"))) },
+            );
+        }
+        builder.build().unwrap()
+    }
+
+    assert_eq!(build(false).fingerprint(), build(false).fingerprint());
+    assert_ne!(build(false).fingerprint(), build(true).fingerprint());
+
+    let router = build(true);
+    let fingerprint = router.fingerprint().to_owned();
+    let serve = serve(router).await;
+    for path in ["/users/1", "/missing"] {
+        let resp = Client::new()
+            .request(serve.new_request("GET", path).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(resp.headers()["x-router-fingerprint"], fingerprint.as_str());
+    }
+    serve.shutdown();
+
+    assert!(Router::<Body, RouteError>::builder()
+        .fingerprint_header("x router")
+        .build()
+        .is_err());
+}
+
+#[cfg(feature = "config")]
+#[tokio::test]
+async fn can_load_routes_from_a_manifest() {
+    use routerify::config::{HandlerRegistry, ManifestFormat, RouteManifest};
+
+    let manifest = RouteManifest::from_str(
+        "routes:
+  - path: /users/:id
+    handler: get_user
+    middleware: [tag]
+  - path: /health
+    methods: [get, head]
+    handler: health
+    skip: [auth]
+",
+        ManifestFormat::Yaml,
+    )
+    .unwrap();
+    let registry = || {
+        HandlerRegistry::new()
+            .handler("get_user", |req: Request<Body>| async move {
+                let id = req.param("id").unwrap().clone();
+                Ok(Response::new(Body::from(format!("User {}", id))))
+            })
+            .handler("health", |_| async move { Ok(Response::new(Body::from("OK"))) })
+            .middleware("tag", |path| {
+                Middleware::post_with_path(path, |mut res| async move {
+                    res.headers_mut().insert("x-tag", "tagged".parse().unwrap());
+                    Ok(res)
+                })
+            })
+    };
+
+    let router: Router<Body, RouteError> = Router::builder()
+        .middleware(
+            Middleware::pre(|req| async move {
+                match req.headers().contains_key("authorization") {
+                    true => Ok(req),
+                    false => Err(routerify::HttpError::new(StatusCode::UNAUTHORIZED, "Unauthorized").into()),
+                }
+            })
+            .named("auth"),
+        )
+        .routes_from(&manifest, &registry())
+        .build()
+        .unwrap();
+    let serve = serve(router).await;
+
+    let resp = Client::new()
+        .request(
+            serve
+                .new_request("GET", "/users/7")
+                .header("authorization", "Bearer token")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.headers()["x-tag"], "tagged");
+    assert_eq!(into_text(resp.into_body()).await, "User 7");
+
+    let resp = Client::new()
+        .request(serve.new_request("GET", "/health").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(!resp.headers().contains_key("x-tag"));
+    serve.shutdown();
+
+    let manifest =
+        RouteManifest::from_str("[[routes]]\npath = \"/\"\nhandler = \"home\"\n", ManifestFormat::Toml).unwrap();
+    let err = Router::builder()
+        .routes_from(&manifest, &registry())
+        .build()
+        .unwrap_err();
+    assert!(err.to_string().contains("Unknown handler \"home\""));
+}
+
+#[tokio::test]
+async fn can_replace_a_route_handler_at_runtime() {
+    let checkout = routerify::RouteHandle::new(|_| async move { Ok(Response::new(Body::from("Checkout"))) });
+    let router: Router<Body, RouteError> = Router::builder()
+        .get("/checkout", checkout.handler())
+        .get("/cart", |_| async move { Ok(Response::new(Body::from("Cart"))) })
+        .build()
+        .unwrap();
+    let serve = serve(router).await;
+
+    let get = |path: &'static str| {
+        let req = serve.new_request("GET", path).body(Body::empty()).unwrap();
+        async move { into_text(Client::new().request(req).await.unwrap().into_body()).await }
+    };
+
+    assert_eq!(get("/checkout").await, "Checkout");
+    checkout
+        .clone()
+        .replace(|_| async move { Ok(Response::new(Body::from("Maintenance"))) });
+    assert!(checkout.is_replaced());
+    assert_eq!(get("/checkout").await, "Maintenance");
+    assert_eq!(get("/cart").await, "Cart");
+
+    checkout.restore();
+    assert!(!checkout.is_replaced());
+    assert_eq!(get("/checkout").await, "Checkout");
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_isolate_a_scope_from_the_parent_middlewares() {
+    let tag = |name: &'static str| {
+        Middleware::post(move |mut res: Response<Body>| async move {
+            res.headers_mut().append("x-tag", name.parse().unwrap());
+            Ok(res)
+        })
+    };
+    let webhooks: Router<Body, RouteError> = Router::builder()
+        .isolate_middleware()
+        .middleware(tag("webhooks"))
+        .post("/stripe", |_| async move { Ok(Response::new(Body::from("Received"))) })
+        .build()
+        .unwrap();
+    let router: Router<Body, RouteError> = Router::builder()
+        .middleware(tag("app"))
+        .get("/", |_| async move { Ok(Response::new(Body::from("Home"))) })
+        .scope("/webhooks", webhooks)
+        .build()
+        .unwrap();
+
+    let order = router.middleware_order(&Method::POST, "/webhooks/stripe");
+    assert_eq!(order.len(), 1);
+    assert_eq!(order[0].scope_depth(), 2);
+
+    let serve = serve(router).await;
+    for (method, path, tags) in [
+        ("POST", "/webhooks/stripe", vec!["webhooks"]),
+        ("GET", "/", vec!["app"]),
+    ] {
+        let resp = Client::new()
+            .request(serve.new_request(method, path).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let resp_tags = resp.headers().get_all("x-tag").iter().collect::<Vec<_>>();
+        assert_eq!(resp_tags, tags);
+    }
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_instrument_the_request_memory() {
+    use routerify::ext::RequestBodyExt;
+    use routerify::RequestMemory;
+
+    let recorded = Arc::new(Mutex::new(None));
+    let recorded_clone = recorded.clone();
+    let router: Router<Body, RouteError> = Router::builder()
+        .middleware(routerify::middleware::memory_stats())
+        .middleware(Middleware::post_with_info(move |res, req_info: RequestInfo| {
+            let recorded = recorded_clone.clone();
+            async move {
+                let memory = req_info.context::<RequestMemory>().unwrap();
+                *recorded.lock().unwrap() = Some((memory.body_bytes(), memory.param_bytes(), memory.context_entries()));
+                Ok(res)
+            }
+        }))
+        .post("/users/:id", |mut req| async move {
+            req.set_context(7u32);
+            let body = req.buffer_body(1024, None).await?;
+            Ok(Response::new(Body::from(format!("{} bytes", body.len()))))
+        })
+        .build()
+        .unwrap();
+    let serve = serve(router).await;
+
+    let resp = Client::new()
+        .request(
+            serve
+                .new_request("POST", "/users/42")
+                .body(Body::from("hello"))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(into_text(resp.into_body()).await, "5 bytes");
+    assert_eq!(*recorded.lock().unwrap(), Some((5, 4, 1)));
+    serve.shutdown();
+}
+
+#[cfg(feature = "record")]
+#[tokio::test]
+async fn can_record_and_replay_exchanges() {
+    use routerify::record::{Exchange, Recorder, Replayer};
+    use routerify::Redaction;
+    use std::time::Duration;
+
+    fn app(greeting: &'static str, recorder: Option<Recorder>) -> Router<Body, RouteError> {
+        let mut builder = Router::builder().data(Redaction::new().redact_params(["token"]));
+        if let Some(recorder) = recorder {
+            builder = builder.middleware(recorder.middleware());
+        }
+        builder
+            .get(
+                "/greet",
+                move |_| async move { Ok(Response::new(Body::from(greeting))) },
+            )
+            .post("/echo", |req| async move { Ok(Response::new(req.into_body())) })
+            .build()
+            .unwrap()
+    }
+
+    let capture_file = std::env::temp_dir().join(format!("routerify-capture-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&capture_file);
+    let recorder = Recorder::new(&capture_file).unwrap().max_body_size(8);
+    let serve = serve(app("Hello", Some(recorder))).await;
+    for (method, path, body) in [
+        ("GET", "/greet?token=s3cret", ""),
+        ("POST", "/echo", "a long request body"),
+    ] {
+        let resp = Client::new()
+            .request(
+                serve
+                    .new_request(method, path)
+                    .header("authorization", "Bearer s3cret")
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            into_text(resp.into_body()).await,
+            if body.is_empty() { "Hello" } else { body }
+        );
+    }
+    serve.shutdown();
+
+    // The exchanges are written once their response bodies are sent.
+    let mut exchanges = Vec::new();
+    for _ in 0..100 {
+        exchanges = Exchange::read_all(&capture_file).unwrap();
+        if exchanges.len() == 2 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    exchanges.sort_by_key(|exchange| exchange.method().to_owned());
+    assert_eq!(exchanges.len(), 2);
+    assert_eq!(exchanges[0].uri(), "/greet?token=[REDACTED]");
+    assert!(exchanges[0]
+        .request_headers()
+        .contains(&("authorization".to_owned(), "[REDACTED]".to_owned())));
+    assert_eq!(exchanges[1].request_body(), b"a long r");
+    assert!(exchanges[1].is_truncated());
+
+    let report = Replayer::run(app("Hello", None), &capture_file).await.unwrap();
+    assert_eq!(report.total(), 2);
+    assert!(report.is_success(), "{:?}", report.mismatches());
+
+    let report = Replayer::run(app("Hi", None), &capture_file).await.unwrap();
+    assert_eq!(report.mismatches().len(), 1);
+    assert_eq!(report.mismatches()[0].uri, "/greet?token=[REDACTED]");
+    assert!(report.mismatches()[0].body_differs);
+    let _ = std::fs::remove_file(&capture_file);
+}
+
+#[cfg(feature = "record")]
+#[tokio::test]
+async fn can_record_streaming_responses_without_holding_them_back() {
+    use hyper::body::{Bytes, HttpBody};
+    use routerify::record::{Exchange, Recorder};
+    use std::time::Duration;
+
+    let (mut sender, body) = Body::channel();
+    let body = Arc::new(Mutex::new(Some(body)));
+    let capture_file = std::env::temp_dir().join(format!("routerify-stream-capture-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&capture_file);
+    let router: Router<Body, RouteError> = Router::builder()
+        .middleware(Recorder::new(&capture_file).unwrap().max_body_size(4).middleware())
+        .get("/events", move |_| {
+            let body = body.lock().unwrap().take().unwrap();
+            async move { Ok(Response::new(body)) }
+        })
+        .build()
+        .unwrap();
+
+    let serve = serve(router).await;
+    let req = Client::new().request(serve.new_request("GET", "/events").body(Body::empty()).unwrap());
+    sender.send_data(Bytes::from("data: 1\n\n")).await.unwrap();
+
+    // The response and its first event are sent before the stream ends.
+    let (resp_body, chunk) = tokio::time::timeout(Duration::from_secs(5), async move {
+        let mut resp_body = req.await.unwrap().into_body();
+        let chunk = resp_body.data().await.unwrap().unwrap();
+        (resp_body, chunk)
+    })
+    .await
+    .unwrap();
+    assert_eq!(chunk, "data: 1\n\n");
+    assert!(Exchange::read_all(&capture_file).unwrap().is_empty());
+
+    sender.send_data(Bytes::from("data: 2\n\n")).await.unwrap();
+    drop(sender);
+    assert_eq!(into_text(resp_body).await, "data: 2\n\n");
+    serve.shutdown();
+
+    let mut exchanges = Vec::new();
+    for _ in 0..100 {
+        exchanges = Exchange::read_all(&capture_file).unwrap();
+        if !exchanges.is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(exchanges.len(), 1);
+    assert_eq!(exchanges[0].response_body(), b"data");
+    assert!(exchanges[0].is_truncated());
+    let _ = std::fs::remove_file(&capture_file);
+}
+
+#[tokio::test]
+async fn can_inject_faults_with_the_chaos_middleware() {
+    use routerify::middleware::chaos::Chaos;
+
+    let chaos = Chaos::new()
+        .error(100.0, StatusCode::SERVICE_UNAVAILABLE)
+        .path("/flaky")
+        .only_with_header("x-chaos");
+    let router: Router<Body, RouteError> = Router::builder()
+        .middleware(chaos.middleware())
+        .scope(
+            "/broken",
+            Router::builder()
+                .middleware(Chaos::new().drop_connection(100.0).middleware())
+                .get("/", |_| async move { Ok(Response::new(Body::from("Unreachable"))) })
+                .build()
+                .unwrap(),
+        )
+        .scope(
+            "/partial",
+            Router::builder()
+                .middleware(Chaos::new().truncate_body(100.0).middleware())
+                .get("/", |_| async move { Ok(Response::new(Body::from("A complete body"))) })
+                .build()
+                .unwrap(),
+        )
+        .get("/flaky", |_| async move { Ok(Response::new(Body::from("Flaky"))) })
+        .get("/stable", |_| async move { Ok(Response::new(Body::from("Stable"))) })
+        .build()
+        .unwrap();
+    let serve = serve(router).await;
+
+    let send = |path: &str, chaos: bool| {
+        let mut req = serve.new_request("GET", path);
+        if chaos {
+            req = req.header("x-chaos", "1");
+        }
+        Client::new().request(req.body(Body::empty()).unwrap())
+    };
+    let resp = send("/flaky", true).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let resp = send("/flaky", false).await.unwrap();
+    assert_eq!(into_text(resp.into_body()).await, "Flaky");
+    let resp = send("/stable", true).await.unwrap();
+    assert_eq!(into_text(resp.into_body()).await, "Stable");
+
+    let dropped = match send("/broken", false).await {
+        Ok(resp) => hyper::body::to_bytes(resp.into_body()).await.is_err(),
+        Err(_) => true,
+    };
+    assert!(dropped);
+
+    let resp = send("/partial", false).await.unwrap();
+    assert_eq!(resp.headers()["content-length"], "15");
+    assert!(hyper::body::to_bytes(resp.into_body()).await.is_err());
+
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_read_body_with_deadline() {
+    use routerify::ext::RequestBodyExt;
+    use routerify::Deadline;
+    use std::time::Duration;
+
+    let router: Router<Body, RouteError> = Router::builder()
+        .post("/uploads", |mut req| async move {
+            req.set_context(Deadline::after(Duration::from_millis(100)));
+            let body = req.read_body_with_deadline(8).await?;
+            let replayed = hyper::body::to_bytes(req.into_body()).await?;
+            assert_eq!(body, replayed);
+            Ok(Response::new(Body::from(format!("{} bytes", body.len()))))
+        })
+        .build()
+        .unwrap();
+    let serve = serve(router).await;
+
+    let send = |body: Body| Client::new().request(serve.new_request("POST", "/uploads").body(body).unwrap());
+    let resp = send(Body::from("hello")).await.unwrap();
+    assert_eq!(into_text(resp.into_body()).await, "5 bytes");
+
+    let resp = send(Body::from("hello world")).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    let (mut sender, body) = Body::channel();
+    sender.send_data("hel".into()).await.unwrap();
+    let resp = send(body).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::REQUEST_TIMEOUT);
+    drop(sender);
+
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_answer_503_until_the_warmup_is_ready() {
+    use routerify::warmup::{Warmup, WarmupState};
+
+    let warmup = Warmup::new()
+        .task("caches", || async move { Ok::<_, routerify::Error>(()) })
+        .allow("/healthz");
+    let router: Router<Body, routerify::Error> = Router::builder()
+        .warmup(warmup.clone())
+        .get("/healthz", |_| async move { Ok(Response::new(Body::from("OK"))) })
+        .get("/orders", |_| async move { Ok(Response::new(Body::from("Orders"))) })
+        .build()
+        .unwrap();
+    let service = routerify::RouterService::new(router).unwrap();
+    let lifecycle = service.lifecycle();
+    let server = hyper::Server::bind(&([127, 0, 0, 1], 0).into()).serve(service);
+    let addr = server.local_addr();
+    let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+    tokio::spawn(server.with_graceful_shutdown(async {
+        rx.await.unwrap();
+    }));
+
+    let get = |path: &str| Client::new().get(format!("http://{}{}", addr, path).parse().unwrap());
+    let resp = get("/orders").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(resp.headers()["retry-after"], "5");
+    let resp = get("/healthz").await.unwrap();
+    assert_eq!(into_text(resp.into_body()).await, "OK");
+
+    lifecycle.startup().await.unwrap();
+    assert_eq!(warmup.wait().await, WarmupState::Ready);
+    let resp = get("/orders").await.unwrap();
+    assert_eq!(into_text(resp.into_body()).await, "Orders");
+
+    tx.send(()).unwrap();
+}
+
+#[tokio::test]
+async fn can_observe_router_events() {
+    use routerify::events::{MiddlewareStage, Observer, RouterEvent};
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let observer = {
+        let events = events.clone();
+        Observer::from_fn(move |event| events.lock().unwrap().push(event.clone()))
+    };
+    let router: Router<Body, routerify::Error> = Router::builder()
+        .observer(observer)
+        .middleware(Middleware::pre(|req| async move {
+            if req.uri().path() == "/denied" {
+                return Err(routerify::Error::new("Denied"));
+            }
+            Ok(req)
+        }))
+        .get("/users/:id", |_| async move { Ok(Response::new(Body::from("User"))) })
+        .get("/panic", |_| async move { panic!("Boom") })
+        .build()
+        .unwrap();
+    let serve = serve(router).await;
+
+    let get = |path: &str| Client::new().request(serve.new_request("GET", path).body(Body::empty()).unwrap());
+    let resp = get("/users/1").await.unwrap();
+    assert_eq!(into_text(resp.into_body()).await, "User");
+    {
+        let events = std::mem::take(&mut *events.lock().unwrap());
+        assert_eq!(events.len(), 3);
+        assert!(matches!(events[0], RouterEvent::RequestStarted { ref path, .. } if path == "/users/1"));
+        assert!(matches!(events[1], RouterEvent::RouteMatched { ref route_path, .. } if route_path == "/users/:id"));
+        assert!(matches!(events[2], RouterEvent::ResponseSent { status, .. } if status == StatusCode::OK));
+    }
+
+    let resp = get("/denied").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(events.lock().unwrap().iter().any(|event| matches!(
+        event,
+        RouterEvent::MiddlewareFailed { stage: MiddlewareStage::Pre, error, .. } if error.contains("Denied")
+    )));
+
+    let resp = get("/panic").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(events
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|event| matches!(event, RouterEvent::PanicCaught { message, .. } if message == "Boom")));
+
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_catch_panics_without_an_observer() {
+    for catch_panics in [false, true] {
+        let router: Router<Body, routerify::Error> = Router::builder()
+            .catch_panics(catch_panics)
+            .get("/panic", |_| async move { panic!("Boom") })
+            .build()
+            .unwrap();
+        let serve = serve(router).await;
+
+        let resp = Client::new()
+            .request(serve.new_request("GET", "/panic").body(Body::empty()).unwrap())
+            .await;
+        match catch_panics {
+            // The panic isn't caught, so the connection is closed without a response.
+            false => assert!(resp.is_err()),
+            true => assert_eq!(resp.unwrap().status(), StatusCode::INTERNAL_SERVER_ERROR),
+        }
+
+        serve.shutdown();
+    }
+}
+
+#[tokio::test]
+async fn can_map_err_responses_per_scope() {
+    let envelope = |scope: &'static str| {
+        move |status: StatusCode, _: RequestInfo| async move {
+            let body = format!("{} {}", scope, status.as_u16());
+            Response::builder().status(status).body(Body::from(body)).unwrap()
+        }
+    };
+    let admin: Router<Body, routerify::Error> = Router::builder()
+        .get("/stats", |_| async move { Err(routerify::Error::new("No stats")) })
+        .map_err_responses(envelope("admin"))
+        .build()
+        .unwrap();
+    let api: Router<Body, routerify::Error> = Router::builder()
+        .map_err_responses(envelope("api"))
+        .get("/users/:id", |_| async move {
+            Ok(Response::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .body(Body::from("Bad id"))
+                .unwrap())
+        })
+        .get("/users", |_| async move { Ok(Response::new(Body::from("Users"))) })
+        .scope("/admin", admin)
+        .build()
+        .unwrap();
+    let router: Router<Body, routerify::Error> = Router::builder().scope("/api", api).build().unwrap();
+    let serve = serve(router).await;
+
+    let get = |path: &str| Client::new().request(serve.new_request("GET", path).body(Body::empty()).unwrap());
+    let resp = get("/api/users").await.unwrap();
+    assert_eq!(into_text(resp.into_body()).await, "Users");
+    let resp = get("/api/users/1").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(into_text(resp.into_body()).await, "api 400");
+    let resp = get("/api/unknown").await.unwrap();
+    assert_eq!(into_text(resp.into_body()).await, "api 404");
+    let resp = get("/api/admin/stats").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(into_text(resp.into_body()).await, "admin 500");
+    let resp = get("/other").await.unwrap();
+    assert_eq!(into_text(resp.into_body()).await, "Not Found");
+
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_add_static_response_headers() {
+    let admin: Router<Body, routerify::Error> = Router::builder()
+        .get("/users", |_| async move { Ok(Response::new(Body::from("Users"))) })
+        .get("/embed", |_| async move {
+            Ok(Response::builder()
+                .header("x-frame-options", "SAMEORIGIN")
+                .body(Body::from("Embed"))
+                .unwrap())
+        })
+        .with_headers("/*", [("x-frame-options", "DENY"), ("cache-control", "no-store")])
+        .build()
+        .unwrap();
+    let router: Router<Body, routerify::Error> = Router::builder()
+        .get("/", |_| async move { Ok(Response::new(Body::from("Home"))) })
+        .scope("/admin", admin)
+        .with_headers("/*", [("x-content-type-options", "nosniff")])
+        .build()
+        .unwrap();
+    let serve = serve(router).await;
+
+    let get = |path: &str| Client::new().request(serve.new_request("GET", path).body(Body::empty()).unwrap());
+    let resp = get("/admin/users").await.unwrap();
+    assert_eq!(resp.headers()["x-frame-options"], "DENY");
+    assert_eq!(resp.headers()["cache-control"], "no-store");
+    assert_eq!(resp.headers()["x-content-type-options"], "nosniff");
+    let resp = get("/admin/embed").await.unwrap();
+    assert_eq!(resp.headers()["x-frame-options"], "SAMEORIGIN");
+    let resp = get("/admin/unknown").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    assert_eq!(resp.headers()["x-frame-options"], "DENY");
+    let resp = get("/").await.unwrap();
+    assert!(resp.headers().get("x-frame-options").is_none());
+    assert_eq!(resp.headers()["x-content-type-options"], "nosniff");
+
+    let err = Router::<Body, routerify::Error>::builder()
+        .with_headers("/*", [("x frame", "DENY")])
+        .build()
+        .unwrap_err();
+    assert!(err.to_string().contains("Invalid response header name"));
+
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_revalidate_conditional_gets() {
+    use http::request::Parts;
+    use routerify::revalidate::Validator;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let renders = Arc::new(AtomicUsize::new(0));
+    let handler_renders = renders.clone();
+    let docs: Router<Body, RouteError> = Router::builder()
+        .get("/:id", move |_| {
+            let renders = handler_renders.clone();
+            async move {
+                renders.fetch_add(1, Ordering::SeqCst);
+                Ok(Response::new(Body::from("Document")))
+            }
+        })
+        .revalidate("/*", |req: &Parts| {
+            let version = req.param("id")?.parse::<u32>().ok()?;
+            Some(Validator::new().with_etag(format!("\"v{}\"", version)))
+        })
+        .build()
+        .unwrap();
+    let router: Router<Body, RouteError> = Router::builder().scope("/docs", docs).build().unwrap();
+    let serve = serve(router).await;
+
+    for (path, if_none_match, status, etag) in [
+        ("/docs/7", None, StatusCode::OK, Some("\"v7\"")),
+        ("/docs/7", Some("\"v7\""), StatusCode::NOT_MODIFIED, Some("\"v7\"")),
+        (
+            "/docs/7",
+            Some("W/\"v6\", W/\"v7\""),
+            StatusCode::NOT_MODIFIED,
+            Some("\"v7\""),
+        ),
+        ("/docs/8", Some("\"v7\""), StatusCode::OK, Some("\"v8\"")),
+        ("/docs/draft", Some("\"v7\""), StatusCode::OK, None),
+    ] {
+        let mut req = serve.new_request("GET", path);
+        if let Some(if_none_match) = if_none_match {
+            req = req.header("if-none-match", if_none_match);
+        }
+        let resp = Client::new().request(req.body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(resp.status(), status, "{} {:?}", path, if_none_match);
+        assert_eq!(
+            resp.headers().get("etag").map(|val| val.to_str().unwrap()),
+            etag,
+            "{} {:?}",
+            path,
+            if_none_match
+        );
+    }
+    assert_eq!(renders.load(Ordering::SeqCst), 3);
+    serve.shutdown();
+
+    let res: routerify::Result<Router<Body, RouteError>> = Router::builder()
+        .revalidate("/missing", |_: &Parts| None::<Validator>)
+        .build();
+    assert!(res.is_err());
+}
+
+#[tokio::test]
+async fn can_rewrite_urls_before_matching() {
+    use routerify::middleware::rewrite::Rewrite;
+
+    let rewrite = Rewrite::new()
+        .rewrite("/users/:id/profile", "/profiles/:id")
+        .regex(r"^/blog/(\d{4})/(.+)$", "/posts/$2?year=$1");
+    let router: Router<Body, RouteError> = Router::builder()
+        .rewrite(rewrite)
+        .get("/profiles/:id", |req| async move {
+            let tab = req.uri().query().unwrap_or("");
+            Ok(Response::new(Body::from(format!(
+                "Profile {} {}",
+                req.param("id").unwrap(),
+                tab
+            ))))
+        })
+        .get("/posts/:slug", |req| async move {
+            let year = req.uri().query().unwrap_or("");
+            Ok(Response::new(Body::from(format!(
+                "Post {} {}",
+                req.param("slug").unwrap(),
+                year
+            ))))
+        })
+        .build()
+        .unwrap();
+    let serve = serve(router).await;
+
+    for (path, expected) in [
+        ("/users/7/profile?tab=posts", "Profile 7 tab=posts"),
+        ("/profiles/8", "Profile 8 "),
+        ("/blog/2024/hello", "Post hello year=2024"),
+    ] {
+        let resp = Client::new()
+            .request(serve.new_request("GET", path).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(into_text(resp.into_body()).await, expected);
+    }
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_rewrite_urls_under_a_base_path() {
+    use routerify::middleware::rewrite::Rewrite;
+
+    let router: Router<Body, RouteError> = Router::builder()
+        .base_path("/app")
+        .rewrite(Rewrite::new().rewrite("/old", "/new"))
+        .pre_match(|req| {
+            let base_path = req.base_path().unwrap_or("").to_owned();
+            if req.uri().path().strip_prefix(base_path.as_str()) == Some("/index.html") {
+                *req.uri_mut() = format!("{}/new", base_path).parse().unwrap();
+            }
+        })
+        .get("/new", |req| async move {
+            Ok(Response::new(Body::from(req.uri().path().to_owned())))
+        })
+        .build()
+        .unwrap();
+    let serve = serve(router).await;
+
+    for path in ["/app/old", "/app/index.html"].iter() {
+        let resp = Client::new()
+            .request(serve.new_request("GET", path).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(into_text(resp.into_body()).await, "/app/new");
+    }
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_forward_requests_to_other_routes() {
+    let router: Router<Body, RouteError> = Router::builder()
+        .post("/legacy/:id/comments", |req| async move {
+            let path = format!("/posts/{}/comments", req.param("id").unwrap());
+            Ok(req.forward_to(path))
+        })
+        .post("/posts/:postId/comments", |req| async move {
+            let post_id = req.param("postId").unwrap().clone();
+            let body = hyper::body::to_bytes(req.into_body()).await.unwrap();
+            Ok(Response::new(Body::from(format!(
+                "{}: {}",
+                post_id,
+                String::from_utf8_lossy(&body)
+            ))))
+        })
+        .get("/ping", |req| async move { Ok(req.forward_to("/pong")) })
+        .get("/pong", |req| async move { Ok(req.forward_to("/ping")) })
+        .build()
+        .unwrap();
+    let serve = serve(router).await;
+
+    let resp = Client::new()
+        .request(
+            serve
+                .new_request("POST", "/legacy/7/comments")
+                .body(Body::from("Nice"))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(into_text(resp.into_body()).await, "7: Nice");
+
+    let resp = Client::new()
+        .request(serve.new_request("GET", "/ping").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::LOOP_DETECTED);
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_forward_requests_past_post_middlewares_which_rebuild_responses() {
+    let statuses = Arc::new(Mutex::new(Vec::new()));
+    let statuses_clone = statuses.clone();
+
+    let router: Router<Body, RouteError> = Router::builder()
+        .middleware(Middleware::post(move |res: Response<Body>| {
+            statuses_clone.lock().unwrap().push(res.status().as_u16());
+            async move {
+                // The response is rebuilt, which drops its extensions.
+                let (parts, body) = res.into_parts();
+                Ok(Response::builder()
+                    .status(parts.status)
+                    .header("x-rebuilt", "true")
+                    .body(body)
+                    .unwrap())
+            }
+        }))
+        .get("/old", |req| async move { Ok(req.forward_to("/new")) })
+        .get("/new", |_| async move {
+            Ok(Response::builder()
+                .status(StatusCode::CREATED)
+                .body(Body::from("New"))
+                .unwrap())
+        })
+        .build()
+        .unwrap();
+    let serve = serve(router).await;
+
+    let resp = Client::new()
+        .request(serve.new_request("GET", "/old").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    assert_eq!(resp.headers()["x-rebuilt"], "true");
+    assert_eq!(into_text(resp.into_body()).await, "New");
+    assert_eq!(*statuses.lock().unwrap(), vec![201]);
+
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_map_response_statuses() {
+    let private: Router<Body, routerify::Error> = Router::builder()
+        .get("/reports", |_| async move { Ok(Response::new(Body::from("Reports"))) })
+        .map_status(|status, meta| match status {
+            StatusCode::NOT_FOUND if meta.method() == Method::GET => StatusCode::FORBIDDEN,
+            status => status,
+        })
+        .build()
+        .unwrap();
+    let router: Router<Body, routerify::Error> = Router::builder()
+        .scope("/private", private)
+        .map_status(|status, _| match status {
+            StatusCode::FORBIDDEN => StatusCode::UNAUTHORIZED,
+            status => status,
+        })
+        .build()
+        .unwrap();
+    let serve = serve(router).await;
+
+    let get = |path: &str| Client::new().request(serve.new_request("GET", path).body(Body::empty()).unwrap());
+    let resp = get("/private/reports").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(into_text(resp.into_body()).await, "Reports");
+    let resp = get("/private/unknown").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let resp = get("/unknown").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_set_configs_per_scope() {
+    struct Limit(u32);
+
+    let api: Router<Body, routerify::Error> = Router::builder()
+        .config(Limit(10))
+        .data(Limit(1))
+        .get("/users/:id", |req| async move {
+            let limit = req.scope_config::<Limit>().unwrap().0;
+            Ok(Response::new(Body::from(format!("user {}", limit))))
+        })
+        .build()
+        .unwrap();
+    let router: Router<Body, routerify::Error> = Router::builder()
+        .config(Limit(100))
+        .middleware(Middleware::post_with_info(|res, req_info| async move {
+            let limit = req_info.scope_config::<Limit>().unwrap().0;
+            let mut res = res;
+            res.headers_mut().insert("x-limit", limit.into());
+            Ok(res)
+        }))
+        .get("/", |_| async move { Ok(Response::new(Body::from("home"))) })
+        .scope("/api", api)
+        .build()
+        .unwrap();
+    let serve = serve(router).await;
+
+    let get = |path: &str| Client::new().request(serve.new_request("GET", path).body(Body::empty()).unwrap());
+    let resp = get("/api/users/1").await.unwrap();
+    assert_eq!(resp.headers()["x-limit"], "10");
+    assert_eq!(into_text(resp.into_body()).await, "user 10");
+    let resp = get("/api/unknown").await.unwrap();
+    assert_eq!(resp.headers()["x-limit"], "10");
+    let resp = get("/").await.unwrap();
+    assert_eq!(resp.headers()["x-limit"], "100");
+
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_answer_options_and_405_by_the_method_table() {
+    let api: Router<Body, routerify::Error> = Router::builder()
+        .put(
+            "/users/:id",
+            |_| async move { Ok(Response::new(Body::from("Updated"))) },
+        )
+        .build()
+        .unwrap();
+    let router: Router<Body, routerify::Error> = Router::builder()
+        .get(
+            "/api/users/:id",
+            |_| async move { Ok(Response::new(Body::from("User"))) },
+        )
+        .scope("/api", api)
+        .build()
+        .unwrap();
+    assert_eq!(router.allowed_methods("/api/users/1"), vec![Method::GET, Method::PUT]);
+    let serve = serve(router).await;
+
+    let resp = Client::new()
+        .request(
+            serve
+                .new_request("OPTIONS", "/api/users/1")
+                .header("access-control-request-method", "PUT")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    assert_eq!(resp.headers()["allow"], "GET, PUT, OPTIONS");
+    assert_eq!(resp.headers()["access-control-allow-methods"], "GET, PUT, OPTIONS");
+
+    let resp = Client::new()
+        .request(serve.new_request("DELETE", "/api/users/1").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(resp.headers()["allow"], "GET, PUT, OPTIONS");
+
+    let resp = Client::new()
+        .request(serve.new_request("DELETE", "/api/posts/1").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    serve.shutdown();
+}
+
+#[tokio::test]
+async fn can_observe_route_results_before_the_err_handler() {
+    let outcomes = Arc::new(Mutex::new(Vec::new()));
+    let outcomes_clone = outcomes.clone();
+
+    let router: Router<Body, routerify::Error> = Router::builder()
+        .middleware(Middleware::post_with_result(move |result, _elapsed| {
+            outcomes_clone.lock().unwrap().push(match result {
+                Ok(res) => res.status().as_u16().to_string(),
+                Err(err) => err.to_string(),
+            });
+        }))
+        .middleware(Middleware::pre(|req| async move {
+            match req.uri().path() {
+                "/denied" => Err(routerify::Error::new("Denied")),
+                _ => Ok(req),
+            }
+        }))
+        .get("/ok", |_| async move { Ok(Response::new(Body::from("OK"))) })
+        .get("/fail", |_| async move { Err(routerify::Error::new("Failed")) })
+        .err_handler(|_| async move { Response::new(Body::from("Handled")) })
+        .build()
+        .unwrap();
+    let serve = serve(router).await;
+
+    for path in ["/ok", "/fail", "/denied"].iter() {
+        let resp = Client::new()
+            .request(serve.new_request("GET", path).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+    assert_eq!(
+        *outcomes.lock().unwrap(),
+        vec!["200", "routerify::Error: Failed", "routerify::Error: Denied"]
+    );
+
+    serve.shutdown();
+}